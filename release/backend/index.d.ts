@@ -3,94 +3,1468 @@
 
 /* auto-generated by NAPI-RS */
 
+/** metadata of the recording matched by AcoustID */
+export interface TrackIdentity {
+  title?: string
+  /** artists joined by ", " */
+  artist?: string
+  /** MusicBrainz recording id */
+  recordingId: string
+  /** confidence of the match (0 ~ 1) */
+  score: number
+}
 export interface AudioFormatInfo {
   name: string
   sampleRate: number
   bitDepth: string
   bitrate: string
+  /** container format (e.g. "wav", "mp4", "mkv"). "raw" for headerless PCM files */
+  container: string
+  /** number of the streams other than audio (e.g. video) in the container, which are ignored */
+  nIgnoredStreams: number
+  /** album tag of the file */
+  album?: string
+  /** recording (or release) date tag of the file as written (e.g. "2024" or "2024-05-01") */
+  date?: string
+}
+/**
+ * time range of the audio which failed to decode (e.g. corrupted packets).
+ * The range is filled with silence. end_sec == start_sec if the length is unknown.
+ */
+export interface DecodeGap {
+  startSec: number
+  endSec: number
+}
+/** format info read from the headers without decoding the audio */
+export interface ProbedAudioFile {
+  path: string
+  /** container format (e.g. "wav", "flac", "mp4") */
+  formatName: string
+  sampleRate: number
+  channels: number
+  /** 0 if the headers don't tell the length */
+  durationSec: number
+  /** empty if the headers don't tell the bit depth */
+  bitDepth: string
+  /** empty if probed successfully */
+  err: string
+}
+/** sample format of headerless PCM files */
+export const enum RawSampleFormat {
+  U8 = 'U8',
+  S16LE = 'S16LE',
+  S16BE = 'S16BE',
+  S24LE = 'S24LE',
+  S24BE = 'S24BE',
+  S32LE = 'S32LE',
+  S32BE = 'S32BE',
+  F32LE = 'F32LE',
+  F32BE = 'F32BE',
+  F64LE = 'F64LE',
+  F64BE = 'F64BE'
 }
 export const enum GuardClippingMode {
   Clip = 'Clip',
   ReduceGlobalLevel = 'ReduceGlobalLevel',
   Limiter = 'Limiter'
 }
+export const enum WavViewMode {
+  Original = 'Original',
+  /** 20 Hz high-passed */
+  HighPass = 'HighPass',
+  AWeighted = 'AWeighted'
+}
+export interface InitialView {
+  startSec: number
+  endSec: number
+  /** (min, max) amplitude of the waveform view */
+  ampRange: [number, number]
+}
+export const enum DownmixMode {
+  Off = 'Off',
+  /** (L+R) at -3 dB */
+  MonoMinus3dB = 'MonoMinus3dB',
+  /** (L+R) at -6 dB */
+  MonoMinus6dB = 'MonoMinus6dB',
+  /** (L+R) with the gain keeping the power of the stereo signal (depends on the correlation) */
+  MonoAuto = 'MonoAuto',
+  LeftOnly = 'LeftOnly',
+  RightOnly = 'RightOnly'
+}
+export const enum ChannelRelationKind {
+  /** normal stereo */
+  Stereo = 'Stereo',
+  /** L and R are identical or near-identical */
+  DualMono = 'DualMono',
+  /** R is (near-)identical to the polarity-inverted L */
+  PolarityInverted = 'PolarityInverted',
+  LeftSilent = 'LeftSilent',
+  RightSilent = 'RightSilent',
+  BothSilent = 'BothSilent'
+}
+export interface ChannelRelationship {
+  kind: ChannelRelationKind
+  /** if L and R are exactly the same */
+  isBitIdentical: boolean
+  /** pearson correlation coefficient between L and R (-1 ~ 1) */
+  correlation: number
+  /** power of the side (L-R)/2 relative to the mid (L+R)/2 */
+  sideToMiddB: number
+}
+export const enum DropoutKind {
+  /** abrupt jump of the waveform (e.g. dropped samples) */
+  Discontinuity = 'Discontinuity',
+  /** run of zero samples in the middle of the signal */
+  ZeroRun = 'ZeroRun'
+}
+export interface Dropout {
+  sec: number
+  durationSec: number
+  ch: number
+  kind: DropoutKind
+}
+export interface LossyGenerationEstimate {
+  /** 0 ~ 1. how likely the track has been through a lossy encoder */
+  confidence: number
+  /**
+   * frequency of the steepest drop of the long-term spectrum above 10 kHz.
+   * None if the sample rate is too low to search.
+   */
+  cutoffHz?: number
+  /** level of the band below cutoff_hz minus that of the band above it (dB) */
+  cutoffDropdB: number
+  /** 0 ~ 1. ratio of the transients preceded by a rise of the energy (pre-echo) */
+  preEchoScore: number
+  nTransients: number
+}
+export interface BitDepthUsage {
+  /**
+   * bit depth of the coarsest fixed-point grid on which all samples lie.
+   * 0 if all samples are zero.
+   */
+  effectiveBits: number
+  /**
+   * false if some samples are not on the 32-bit fixed-point grid (e.g. floating-point data).
+   * effective_bits is 32 in this case.
+   */
+  isFixedPoint: boolean
+}
+/** comparison of a track (row) to the other track (column) of the compare matrix */
+export interface TrackPairComparison {
+  /** the other track delayed by offset_sec is aligned with this track (negative: advanced) */
+  offsetSec: number
+  /** correlation coefficient of the aligned tracks (negative if the polarity is inverted) */
+  correlation: number
+  /** RMS of this track minus the aligned and gain-matched other track (dBFS) */
+  residualRmsdB: number
+  /** residual RMS relative to the RMS of this track (dB). The lower, the deeper the null. */
+  nullDepthdB: number
+}
+export interface SimilarSegment {
+  trackId: number
+  ch: number
+  startSec: number
+  endSec: number
+  /** cosine similarity of the MFCC sequences (1 for the identical spectral shape) */
+  similarity: number
+}
+export interface MonoCompatibility {
+  /** pearson correlation coefficient between L and R (-1 ~ 1) */
+  correlation: number
+  /** power of the mono sum (L+R)/2 relative to the mean power of L and R */
+  monoLossdB: number
+  /** power of the side (L-R)/2 relative to the mid (L+R)/2 */
+  sideToMiddB: number
+}
+/** unit of the positions shown in the readouts and labels */
+export const enum PositionUnit {
+  /** hh:mm:ss.mmm */
+  Time = 'Time',
+  /** index of the sample at or before the position */
+  Samples = 'Samples',
+  /** sample index with the fractional part (sub-sample position) */
+  FractionalSamples = 'FractionalSamples'
+}
+/** range of the dB values mapped to the colormap of the spectrogram */
+export const enum AutoContrastMode {
+  /** min/max dB of all tracks */
+  Global = 'Global',
+  /** min/max dB of the visible time and frequency region */
+  Viewport = 'Viewport'
+}
+/**
+ * Break of the frequency axis of the spectrogram, which splits the axis into two segments
+ * scaled independently (e.g. 0~100 Hz expanded to the lower half,
+ * and 100 Hz~Nyquist compressed to the upper half).
+ */
+export interface FreqAxisBreak {
+  /** frequency at the break (Hz) */
+  hz: number
+  /** ratio of the height below the break (0 < ratio < 1) */
+  ratio: number
+}
+/** interpolation of the frequency axis of the spectrogram images */
+export const enum FreqInterpolation {
+  /** Lanczos (band-limited) interpolation */
+  BandLimited = 'BandLimited',
+  /**
+   * average of the bins in each pixel if the bins are denser than the pixels,
+   * linear interpolation otherwise
+   */
+  BinAverage = 'BinAverage'
+}
 export const enum FreqScale {
   Linear = 'Linear',
   Mel = 'Mel'
 }
+/** layers of the spectrogram view */
+export const enum SpecLayer {
+  /** dB spectrogram */
+  Magnitude = 'Magnitude',
+  /** temporal first difference of the dB spectrogram (emphasizing onsets and modulation) */
+  Delta = 'Delta'
+}
 export interface SpecSetting {
   winMillisec: number
   tOverlap: number
   fOverlap: number
   freqScale: FreqScale
 }
+export const enum TestSignalKind {
+  /** exponential sine sweep from start_hz to end_hz */
+  SineSweep = 'SineSweep',
+  /** a single full-scale sample at the start */
+  Impulse = 'Impulse',
+  /** -3 dB/oct noise generated from the seed */
+  PinkNoise = 'PinkNoise',
+  /** band-limited (PolyBLEP) square wave of hz */
+  Square = 'Square'
+}
+export const enum SelectionTransformKind {
+  Reverse = 'Reverse',
+  /** linear fade from silence over the selection */
+  FadeIn = 'FadeIn',
+  /** linear fade to silence over the selection */
+  FadeOut = 'FadeOut',
+  /** gain of gaindB */
+  Gain = 'Gain'
+}
 export interface UserSettingsOptionals {
   specSetting?: SpecSetting
   blend?: number
   dBRange?: number
+  /** dB/octave */
+  displayTilt?: number
   commonGuardClipping?: GuardClippingMode
   commonNormalize?: any
+  seekIncrements?: SeekIncrements
+  /** cpal host forced by the user (see listAudioHosts). null for the default host. */
+  audioHost?: string
+  /** schema version of the stored settings (see settings.rs) */
+  settingsVersion?: number
 }
 export interface UserSettings {
   specSetting: SpecSetting
   blend: number
   dBRange: number
+  /** dB/octave */
+  displayTilt: number
   commonGuardClipping: GuardClippingMode
   commonNormalize: any
+  seekIncrements: SeekIncrements
+  audioHost?: string
+  settingsVersion: number
+}
+/** amounts (sec) of seeking by the keyboard shortcuts */
+export interface SeekIncrements {
+  small: number
+  medium: number
+  large: number
+}
+export const enum SeekAmountKind {
+  Small = 'Small',
+  Medium = 'Medium',
+  Large = 'Large'
+}
+export interface ViewportState {
+  startSec: number
+  endSec: number
+  minHz: number
+  maxHz: number
+  /** incremented on every change */
+  version: number
+}
+/** how the view follows the playhead during playback */
+export const enum FollowMode {
+  /** turn the page when the playhead reaches the end of the view */
+  Page = 'Page',
+  /** keep the playhead at the center of the view */
+  Continuous = 'Continuous'
+}
+/** spectral centroid of each frame for drawing a brightness curve over the spectrogram */
+export interface CentroidTimeseries {
+  /** hop (sec) actually used. The frame i is centered at i * hopSec. */
+  hopSec: number
+  /** spectral centroid (Hz) of each frame. NaN for the silent frames. */
+  hz: Array<number>
+}
+export const enum ClipboardContentKind {
+  /** PNG of the waveform/spectrogram with the axes */
+  Image = 'Image',
+  /** WAV of the original audio */
+  Audio = 'Audio'
+}
+/** temporary files of the selection to be put on the clipboard. null if not requested */
+export interface SelectionClipboardFiles {
+  imagePath?: string
+  audioPath?: string
+}
+export const enum TranscodeCodec {
+  Wav = 'Wav',
+  Flac = 'Flac',
+  Ogg = 'Ogg'
+}
+export interface SpecTileGrid {
+  /** width and height of a tile (px) */
+  tileSize: number
+  /** number of tiles along time */
+  nTilesX: number
+  /** number of tiles along frequency */
+  nTilesY: number
+  /** time resolution of the spectrogram at zoom level 0 */
+  secPerFrame: number
+  /** if the time resolution was reduced to keep the spectrogram under the memory limit */
+  isResolutionReduced: boolean
+}
+/** STFT parameters actually used for the spectrogram of a track */
+export interface EffectiveStftParams {
+  winLength: number
+  hopLength: number
+  nFft: number
+  /** frequency resolution of the FFT (Hz/bin, before the mel filterbank) */
+  hzPerBin: number
+  /** time resolution (ms/frame) */
+  msPerFrame: number
+  /** if hop_length was increased to keep the spectrogram under the memory limit */
+  isResolutionReduced: boolean
+}
+export interface JobCount {
+  queued: number
+  running: number
+}
+export interface BackendBusyState {
+  /** calculating specs or greys */
+  specUpdate: JobCount
+  /** loading tracks, normalization, guard clipping, and other analyses */
+  stats: JobCount
+  /** writing files */
+  export: JobCount
+  /** drawing images */
+  drawing: JobCount
+  /** rendering the playback previews (time-stretch and pitch-shift) */
+  playback: JobCount
+  /** if any job is queued or running */
+  isBusy: boolean
+}
+/** number and size of the temporary files registered in tempfiles.rs */
+export interface TempFilesUsage {
+  nFiles: number
+  sizeMb: number
+}
+/** progress of a job of n_total items (e.g. files to transcode or to probe, or pairs to compare) */
+export interface JobProgress {
+  nDone: number
+  nTotal: number
+}
+export const enum TrackGroupBy {
+  Album = 'Album',
+  Date = 'Date',
+  Folder = 'Folder'
+}
+/** progress of the spectrogram of a channel being calculated */
+export interface SpecProgress {
+  idCh: string
+  /** fraction of the STFT frames done (0 ~ 1) */
+  fraction: number
+}
+export interface SpecProgressState {
+  progress: Array<SpecProgress>
+  /** incremented on every change */
+  version: number
+}
+export interface DownloadProgress {
+  /** id of the track to be added from the url */
+  trackId: number
+  url: string
+  downloadedBytes: number
+  /** None if the server doesn't tell the content length */
+  totalBytes?: number
+}
+/**
+ * result of adding a track from a URL.
+ * Either download_err or decode_err is non-empty if failed.
+ */
+export interface UrlTrackResult {
+  trackId: number
+  url: string
+  /** path of the downloaded file in the cache */
+  path: string
+  downloadErr: string
+  decodeErr: string
+}
+/** a color of the colorbar and its dB value */
+export interface ColorbarEntry {
+  dB: number
+  /** [r, g, b, a] */
+  rgba: Array<number>
+}
+/** magnitude response of the DSP applied to the playback (for plotting the coloration) */
+export interface PlaybackChainResponse {
+  /** log-spaced frequencies */
+  hzs: Array<number>
+  /**
+   * magnitude (dB) at hzs of each channel of the impulse response
+   * (a single flat channel if the impulse response is disabled)
+   */
+  magnitudesdB: Array<Array<number>>
+  /**
+   * ceiling of the output limiter (dBFS). null if the limiter is disabled.
+   * The limiter doesn't change the magnitude response below the ceiling.
+   */
+  limiterCeilingdB?: number
+}
+/** frequency axis of the spectrogram image of a track */
+export interface TrackFreqInfo {
+  /**
+   * [min_hz, max_hz] spanned by the image.
+   * Differs among the tracks only if the frequency axis is unlocked.
+   */
+  hzRange: Array<number>
+  nyquistHz: number
+  /**
+   * ratio of the height (from the top) of the zero-padded region above the Nyquist frequency
+   * (to be hatched). 0 if the image has no such region.
+   */
+  paddingRatio: number
+}
+export interface TrackGroup {
+  groupId: number
+  /** the value of the key (e.g. folder path) shared by the tracks */
+  name: string
+  trackIds: Array<number>
+}
+/** tracks paired by autoPairTracks for A/B comparison */
+export interface TrackPair {
+  /** the part of the file names matched by "*" of the patterns */
+  key: string
+  trackIdA: number
+  trackIdB: number
+}
+export interface TranscodeResult {
+  trackId: number
+  /** output path. empty if failed */
+  path: string
+  err: string
+}
+/** measurement between two time-frequency points (start, end) on the spectrogram */
+export interface SpecMeasurement {
+  deltaSec: number
+  deltaHz: number
+  /** delta_hz / delta_sec. None if the two points are at the same time */
+  slopeHzPerSec?: number
+  /** difference on the mel scale */
+  deltaMel: number
+  /** log2(end_hz / start_hz). None if any of the frequencies is not positive */
+  deltaOctave?: number
+  startdB: number
+  enddB: number
+  deltadB: number
+}
+/** noise floor spectrum of a channel */
+export interface NoiseProfile {
+  /** center frequency of each frequency bin (or mel bin) */
+  hz: Array<number>
+  dB: Array<number>
+}
+export interface SegmentLoudness {
+  startSec: number
+  endSec: number
+  /** integrated loudness of the segment (gated as the global loudness) */
+  integratedLUFS: number
+  /** maximum momentary loudness (400 ms window) in the segment */
+  maxMomentaryLUFS: number
+  /** maximum short-term loudness (3 s window) in the segment */
+  maxShortTermLUFS: number
+}
+export interface BandLoudness {
+  centerHz: number
+  /** (min, max) Hz. The lowest band starts at 0 Hz and the highest band ends at the Nyquist freq. */
+  hzRange: [number, number]
+  /** loudness of the band alone (in the blocks gated by the whole signal) */
+  LUFS: number
+  /**
+   * ratio of the gated K-weighted power of the band to that of the whole signal (0 ~ 1).
+   * The contributions of all the bands sum up to 1.
+   */
+  contribution: number
+}
+export interface BandLoudnessContribution {
+  /** integrated loudness of the whole signal */
+  integratedLUFS: number
+  /** from the lowest band */
+  bands: Array<BandLoudness>
+}
+/** dynamics measurements for comparing masters. Not finite for the silent audio. */
+export interface DynamicsStats {
+  /** loudness range (LU, EBU Tech 3342) */
+  LRA: number
+  /** max sample peak to RMS ratio (dB) */
+  crestFactordB: number
+  /** max true peak to max short-term loudness ratio (dB) */
+  PSR: number
+  /** max true peak to integrated loudness ratio (dB) */
+  PLR: number
+}
+/** loudness and level of a channel for quantifying the imbalance between channels */
+export interface ChannelStats {
+  /** integrated loudness of the channel measured alone (as a mono signal) */
+  globalLUFS: number
+  rmsdB: number
+  maxPeakdB: number
+}
+export interface ChannelHeadroom {
+  /** true peak of the original audio (dBTP) */
+  preTruePeakdB: number
+  /** maximum gain reduction by guard clipping (dB, <= 0) */
+  guardClippingGaindB: number
+  /** sample peak after normalization and guard clipping (dBFS) */
+  postPeakdB: number
+  /** true peak after normalization and guard clipping (dBTP) */
+  postTruePeakdB: number
+  /** margin of the post true peak to 0 dBTP */
+  headroomdB: number
+}
+/** headroom of the track. The numbers of the track are the worst of all channels. */
+export interface HeadroomReport {
+  preTruePeakdB: number
+  /** gain applied by the normalization (dB) */
+  normalizeGaindB: number
+  guardClippingGaindB: number
+  postPeakdB: number
+  postTruePeakdB: number
+  headroomdB: number
+  channels: Array<ChannelHeadroom>
+}
+export interface LatencyMeasurement {
+  /** round-trip latency from handing the test signal to the player to recording it */
+  latencyMs: number
+  /** normalized cross-correlation of the detected test signal (0 ~ 1) */
+  confidence: number
+  /** sample rate of the input device */
+  sr: number
+}
+/** result of a request coalesced by coalescer.rs */
+export interface ChannelLane {
+  /** channel of the track shown in the lane */
+  ch: number
+  /** height of the lane (px) */
+  height: number
+}
+/** lanes of a track in the display order */
+export interface TrackLayout {
+  trackId: number
+  collapsed: boolean
+  lanes: Array<ChannelLane>
+}
+/** action requested by the media keys or the media controls of the OS */
+export const enum MediaAction {
+  Play = 'Play',
+  Pause = 'Pause',
+  PlayPause = 'PlayPause',
+  /** pause (the playhead is kept) */
+  Stop = 'Stop',
+  SeekBackward = 'SeekBackward',
+  SeekForward = 'SeekForward',
+  SeekTo = 'SeekTo',
+  PreviousTrack = 'PreviousTrack',
+  NextTrack = 'NextTrack'
+}
+export const enum SessionImportMode {
+  /** keep the current tracks and add the tracks of the session file */
+  Append = 'Append',
+  /** remove the current tracks before adding the tracks of the session file */
+  Replace = 'Replace'
+}
+/** metadata and position of the track loaded to the player for the OS media controls */
+export interface NowPlaying {
+  /** title found by identifyTrack, or the file name */
+  title: string
+  artist?: string
+  durationSec: number
+  positionSec: number
+  isPlaying: boolean
+}
+/** position under the cursor */
+export interface CursorPosition {
+  sec: number
+  /** sample index with the fractional part (integer if snapped to the sample) */
+  samples: number
+}
+/** spectrum of a spectrogram frame captured by captureSpectrumSnapshot */
+export interface SpectrumSnapshot {
+  /** unique name of the snapshot */
+  name: string
+  idCh: string
+  sec: number
+  /** center frequency of each frequency bin (or mel bin) */
+  hz: Array<number>
+  dB: Array<number>
+}
+export interface AddedTrack {
+  id: number
+  /**
+   * suggested view skipping the leading and trailing silence,
+   * with the amp range fitted to the 99.9th percentile of the amplitude
+   */
+  initialView: InitialView
+}
+/** track of the session file imported by importSession */
+export interface ImportedTrack {
+  /** id in the session file */
+  sessionId: number
+  /** id in the current session */
+  id: number
+  /** false if the file is already open (Append mode), so the open track is used */
+  isNew: boolean
+}
+export interface ImportedSession {
+  tracks: Array<ImportedTrack>
+  /** paths of the tracks which are not found or failed to open */
+  missingPaths: Array<string>
+  /** markers of the session file with trackId mapped to the current session */
+  markers: Array<any>
+  /** annotations of the session file with trackId mapped to the current session */
+  annotations: Array<any>
+}
+export interface CoalescedBuffer {
+  /** if a newer request for the same target superseded this request (buf is empty) */
+  isStale: boolean
+  buf: Buffer
+}
+/** vertical layout (px) of the overview image drawn by getOverview with the same height and dpr */
+export interface OverviewDrawingInfo {
+  /** top of each channel drawn */
+  chTops: Array<number>
+  chHeight: number
+  /**
+   * height of the limiter gain lanes at the top and the bottom of each channel.
+   * zero if the limiter gain is not drawn.
+   */
+  gainLaneHeight: number
+  /**
+   * dB axis markers [position, label] of the upper limiter gain lanes
+   * (0: top, 1: bottom of the lane). The lower lanes are upside down (1 - position).
+   */
+  gainAxisMarkers: any
+}
+/** information for the renderer drawing the waveform slice */
+export interface WavDrawingInfo {
+  /** if a newer request for the same channel superseded this request (the others are null) */
+  isStale: boolean
+  /**
+   * class of each pixel column (0: normal, 1: limited, 2: clipped).
+   * null if the track is neither clipped nor limited.
+   */
+  pixelClasses?: Buffer
+  /**
+   * envelope of the channel before the normalization and guard clipping (limiting),
+   * to draw the ghost of the original waveform.
+   * null if not requested or if neither of them changes the channel.
+   */
+  preEnvelope?: WavEnvelope
+  /** envelope of the processed channel, with the same pixel columns as pre_envelope */
+  postEnvelope?: WavEnvelope
+}
+/** min/max amplitude of each pixel column of the waveform */
+export interface WavEnvelope {
+  min: Array<number>
+  max: Array<number>
 }
 export interface PlayerState {
   isPlaying: boolean
   positionSec: number
+  /** if the output limiter is reducing the gain (player-limiter-active indicator) */
+  isLimiterActive: boolean
   err: string
+  /** name of the current output device */
+  deviceName: string
+  /**
+   * number of the automatic output device changes (e.g. the device was unplugged).
+   * The frontend emits player-device-changed when it increases.
+   */
+  deviceChangeCount: number
+  /** monitor dim (attenuation by 20 dB) */
+  monitorDim: boolean
+  monitorMute: boolean
+  /** calibrated monitor gain (dB) applied on top of the volume */
+  referenceLeveldB: number
+}
+/** transport of the player for mirroring by external tools (e.g. OSC bridges) */
+export interface TransportState {
+  isPlaying: boolean
+  positionSec: number
+  /** playing speed relative to the track (for extrapolating the position while playing) */
+  speed: number
+  loopStartSec?: number
+  loopEndSec?: number
+  /** ids of the tracks loaded to the player */
+  activeTrackIds: Array<number>
+  /**
+   * increased on every discontinuous change of the transport
+   * (play/pause, seek, track change, loop change, jump back at the loop end, end of the tracks).
+   * The frontend emits transport-changed when it changes.
+   */
+  revision: number
+}
+/** format of the output stream negotiated with the output device */
+export interface AudioStreamInfo {
+  deviceName: string
+  /** e.g. "f32", "i16" */
+  sampleFormat: string
+  sampleRate: number
+  channels: number
+  isFloat: boolean
+  /** if TPDF dither is added to the playback (integer sample formats of 24 bits or less) */
+  isDithered: boolean
+  /** [min, max] buffer size (frames) supported by the device. null if unknown. */
+  bufferSizeRange?: Array<number>
+}
+/** buffer underruns (dropouts) of the output stream */
+export interface BufferHealthInfo {
+  /** number of the underruns detected since the app started */
+  nUnderruns: number
+  /** buffer size (frames) of the output stream. null for the default of the device. */
+  bufferSize?: number
+  /** if the buffer size is doubled on repeated underruns */
+  adaptive: boolean
+}
+/** global time selection shared by all tracks */
+export interface Selection {
+  startSec: number
+  endSec: number
+}
+export interface SelectionStats {
+  globalLUFS: number
+  rmsdB: number
+  maxPeakdB: number
+}
+export interface ScriptResult {
+  /** number of the steps finished successfully */
+  nDone: number
+  /** error of the failed step. The steps after it are not executed. */
+  error?: string
+  /**
+   * tracks added by the script (with the ids reserved like reserveTrackIds),
+   * which the frontend should add to its track list
+   */
+  addedTrackIds: Array<number>
 }
+/**
+ * Migrate the settings stored by the frontend to the current schema version.
+ * Should be called before init.
+ */
+export declare function migrateUserSettings(userSettings: any): any
+/**
+ * Set the directory (app data dir) where the crash report is saved if the backend panics.
+ * Should be called before init.
+ */
+export declare function setCrashReportDir(dir: string): void
+/**
+ * Take the crash report (JSON) saved by the previous run in the crash report dir
+ * to offer it to the user. The report is removed from the dir.
+ * null if the previous run didn't crash.
+ */
+export declare function takeCrashReport(): string | null
 export declare function init(userSettings: UserSettingsOptionals): UserSettings
-export declare function addTracks(idList: Array<number>, pathList: Array<string>): Promise<Array<number>>
+/**
+ * Set the store file of electron-settings where setUserSetting writes the changed settings.
+ * Should be called before init.
+ */
+export declare function setSettingsStorePath(path: string): void
+/**
+ * Change the stored user setting. Returns immediately without touching the disk.
+ * The changed settings are written together after they stop changing for a moment
+ * (e.g. after dragging a slider), and on exit.
+ */
+export declare function setUserSetting(key: string, value: any): void
+/** Write the changed settings now (e.g. before the window is closed) */
+export declare function flushUserSettings(): void
+/**
+ * Queue the files to open (passed by argv or the OS). Can be called before init.
+ * The paths already in the queue are ignored.
+ */
+export declare function queueOpenFiles(paths: Array<string>): void
+/**
+ * Take the files queued by queueOpenFiles in the order of arrival.
+ * Should be called after init, and whenever the files are queued after that.
+ */
+export declare function takePendingOpenFiles(): Array<string>
+/**
+ * Reserve the ids of the tracks to be added by addTracks.
+ * The ids are shared with the tracks added by scripts and session imports, so they never collide.
+ */
+export declare function reserveTrackIds(n: number): Promise<Array<number>>
+/** Returns the tracks added successfully with the suggested initial views framing them. */
+export declare function addTracks(idList: Array<number>, pathList: Array<string>): Promise<Array<AddedTrack>>
+/**
+ * add a track from a headerless PCM file (.pcm, .raw) of interleaved samples.
+ * offset: number of bytes to skip at the beginning of the file (default: 0)
+ */
+export declare function addRawTrack(trackId: number, path: string, sr: number, channels: number, sampleFormat: RawSampleFormat, offset?: number | undefined | null): Promise<void>
+/**
+ * add a track of a synthetic signal (e.g. a sine sweep as a reference of the analyzer).
+ * params: {sr, sec, channels, peakdB, hz, startHz, endHz, seed} (all optional).
+ * The signal is written to a 32-bit float wav file in the temporary directory,
+ * and its path is returned.
+ */
+export declare function addSyntheticTrack(trackId: number, kind: TestSignalKind, params: any): Promise<string>
+/**
+ * Preview an edit of the global selection of the track (reverse, fades, or gain) without
+ * modifying the track. The whole track with the edited selection is written to a 32-bit float
+ * wav file in the temporary directory and added as a track of new_track_id,
+ * placed at the same offset as the source. params: {gaindB} (for Gain).
+ * Returns the path of the derived track.
+ */
+export declare function previewSelectionTransform(trackId: number, kind: SelectionTransformKind, newTrackId: number, params: any): Promise<string>
+/**
+ * download http(s) URLs to a temporary cache in parallel, and add tracks of the downloaded files.
+ * The progress of each URL can be polled by getDownloadProgress while downloading.
+ */
+export declare function addTracksFromUrls(idList: Array<number>, urls: Array<string>): Promise<Array<UrlTrackResult>>
+/** progress of the running downloads of addTracksFromUrls (including concurrent calls) */
+export declare function getDownloadProgress(): Array<DownloadProgress>
+/**
+ * Read the format, sample rate, channels, and duration of the files from the headers
+ * without decoding the audio (e.g. to show a dropped folder of files immediately).
+ * The audio is decoded and analyzed (LUFS, peak, ...) by add_tracks on demand.
+ * Each result has a non-empty err if the file can't be probed or the probing was cancelled.
+ */
+export declare function probeFiles(paths: Array<string>): Promise<Array<ProbedAudioFile>>
+export declare function getProbeProgress(): JobProgress
+/** cancel the running probe_files. The files already probed are still returned. */
+export declare function cancelProbeFiles(): void
 export declare function reloadTracks(trackIds: Array<number>): Promise<Array<number>>
+/**
+ * Inform the tracks on screen (e.g. on scroll). On loading or reloading, the specs of the
+ * other tracks existing now are calculated after the specs of the visible tracks.
+ * The deferred specs of the visible tracks are calculated immediately, and the id_ch strings
+ * to be refreshed are returned (like applyTrackListChanges).
+ */
+export declare function setVisibleTracks(trackIds: Array<number>): Promise<Array<string>>
 export declare function removeTracks(trackIds: Array<number>): void
 export declare function applyTrackListChanges(): Promise<Array<string>>
-export declare function setImageState(idChStrs: Array<string>, startSec: number, width: number, height: number, pxPerSec: number, optForWav: any, blend: number): Promise<void>
+/**
+ * view_id: images of each view are drawn and cached independently (default: main view 0).
+ * hz_range: frequency range of the spectrogram of the view within the current hz range
+ * (e.g. a detail lane zoomed to 0 ~ 2 kHz). The current hz range is used if not given.
+ * Returns the (min, max) dB mapped to the colormap if the auto contrast mode is Viewport
+ * (for the colorbar). Returns None in Global mode (use getMindB and getMaxdB).
+ */
+export declare function setImageState(idChStrs: Array<string>, startSec: number, width: number, height: number, pxPerSec: number, optForWav: any, blend: number, viewId?: number | undefined | null, hzRange?: [number, number] | undefined | null): Promise<[number, number] | null>
+/** remove the image caches of the view (e.g. when the detail lane is closed) */
+export declare function removeImageView(viewId: number): Promise<void>
+/**
+ * Break the frequency axis of the view (default: main view 0) into two segments
+ * (e.g. {hz: 100, ratio: 0.5} for 0 ~ 100 Hz in the lower half, and the rest in the upper half).
+ * None removes the break. The axis isn't broken if the break is out of the hz range of a track.
+ * Call setImageState of the view again to redraw the images.
+ */
+export declare function setFreqAxisBreak(freqBreak?: FreqAxisBreak | undefined | null, viewId?: number | undefined | null): void
+/** break of the frequency axis of the view (default: main view 0). None if not broken. */
+export declare function getFreqAxisBreak(viewId?: number | undefined | null): FreqAxisBreak | null
 export declare function getdBRange(): Promise<number>
 export declare function setdBRange(dBRange: number): Promise<void>
+export declare function getAutoContrastMode(): Promise<AutoContrastMode>
+/**
+ * Global: the colormap covers the dB range of all tracks.
+ * Viewport: the colormap is stretched to the dB range of the visible time and frequency region.
+ */
+export declare function setAutoContrastMode(mode: AutoContrastMode): Promise<void>
+export declare function getFreqInterpolation(): Promise<FreqInterpolation>
+/**
+ * interpolation of the frequency axis of the spectrogram.
+ * BandLimited: Lanczos interpolation.
+ * BinAverage: average of the bins in each pixel (e.g. dense high bins of a zoomed-out view)
+ */
+export declare function setFreqInterpolation(interpolation: FreqInterpolation): Promise<void>
+export declare function getDisplayTilt(): Promise<number>
+/** tilt (dB/octave) pivoted at 1 kHz, applied only to the spectrogram display */
+export declare function setDisplayTilt(dBPerOct: number): Promise<void>
 export declare function getHzRange(maxTrackHz: number): number[]
 export declare function setHzRange(minHz: number, maxHz: number): Promise<boolean>
+export declare function getFreqAxisLocked(): Promise<boolean>
+/**
+ * If locked, all tracks are drawn in the same absolute hz range so that tracks of different
+ * sample rates line up (the region above the Nyquist frequency of each track is zero-padded).
+ * If unlocked, the hz range of each track is clipped to its Nyquist frequency.
+ * Use getTrackFreqInfo to draw the frequency axis or the hatched region of each track.
+ */
+export declare function setFreqAxisLocked(locked: boolean): Promise<boolean>
+/** returns null if the track doesn't exist */
+export declare function getTrackFreqInfo(trackId: number): TrackFreqInfo | null
 export declare function getSpecSetting(): SpecSetting
 export declare function setSpecSetting(specSetting: SpecSetting): Promise<void>
+/** maximum size of the spectrogram per channel (MB). zero means no limit. */
+export declare function getMaxSpecMemoryMb(): Promise<number>
+/**
+ * The time resolution of the spectrogram of long tracks is reduced
+ * to keep the size under max_mb per channel. zero or negative means no limit.
+ */
+export declare function setMaxSpecMemoryMb(maxMb: number): Promise<void>
 export declare function getCommonGuardClipping(): GuardClippingMode
 export declare function setCommonGuardClipping(mode: GuardClippingMode): Promise<void>
+export declare function getWavViewMode(trackId: number): WavViewMode
+export declare function setWavViewMode(trackId: number, mode: WavViewMode): Promise<void>
+/** setWavViewMode of multiple tracks at once. Nothing is changed if any of the tracks doesn't exist. */
+export declare function setTracksWavViewMode(trackIds: Array<number>, mode: WavViewMode): Promise<void>
+export declare function getTrackPolarityInvert(trackId: number): boolean
+/**
+ * non-destructive polarity inversion of all channels of the track
+ * (affects playback, waveform, and spectrogram)
+ */
+export declare function setTrackPolarityInvert(trackId: number, inverted: boolean): Promise<void>
+/**
+ * setTrackPolarityInvert of multiple tracks at once with a single recalculation of the specs.
+ * Nothing is changed if any of the tracks doesn't exist.
+ */
+export declare function setTracksPolarityInvert(trackIds: Array<number>, inverted: boolean): Promise<void>
+/** source channel of each channel of the track */
+export declare function getChannelOrder(trackId: number): Array<number>
+/**
+ * non-destructive swapping of two channels of the track
+ * (affects playback, waveform, and spectrogram)
+ */
+export declare function swapChannels(trackId: number, chA: number, chB: number): Promise<void>
+/**
+ * Decode only the channels of the track where mask is true (e.g. a few channels of
+ * an ambisonics file to save memory). mask has an element per channel of the file.
+ */
+export declare function setDecodedChannels(trackId: number, mask: Array<boolean>): Promise<void>
+/**
+ * Assign group ids to tracks sharing the same album, date, or folder.
+ * The album and the date are read from the tags of the files.
+ * The tracks without the tag are not grouped.
+ */
+export declare function autoGroupTracks(by: TrackGroupBy): Promise<Array<TrackGroup>>
+/**
+ * Pair the tracks for A/B comparison by the file names matching pattern_a and pattern_b
+ * with the same part matched by "*" (e.g. "*_orig.wav" and "*_proc.wav").
+ * Each pattern should contain exactly one "*", and is matched case-sensitively.
+ * The pairs are ordered by the matched part. Tracks without the counterpart are not paired.
+ */
+export declare function autoPairTracks(patternA: string, patternB: string): Promise<Array<TrackPair>>
+/** group id assigned by autoGroupTracks. null if the track isn't grouped. */
+export declare function getTrackGroup(trackId: number): number | null
+/**
+ * ids of the tracks satisfying all the predicates of the filter
+ * (e.g. {nameContains: "vocal", sr: 44100, maxLUFS: -20, minSec: 600})
+ */
+export declare function queryTracks(filter: any): Promise<Array<number>>
+/** channel lanes (in the display order) of all the tracks */
+export declare function getLayout(): Promise<Array<TrackLayout>>
+export declare function setLaneHeight(trackId: number, ch: number, height: number): Promise<void>
+/** set the height of all the lanes including those of the tracks added later */
+export declare function setAllLaneHeights(height: number): Promise<void>
+/** move the lane of the track at from_index to to_index in the display order */
+export declare function moveLane(trackId: number, fromIndex: number, toIndex: number): Promise<void>
+/** The images of the channels of collapsed tracks are not drawn. */
+export declare function setTrackCollapsed(trackId: number, collapsed: boolean): Promise<void>
+/**
+ * restore the layout returned by getLayout (e.g. from a session).
+ * Nothing is changed if any of the tracks doesn't match.
+ */
+export declare function setLayout(layout: Array<TrackLayout>): Promise<void>
 export declare function getCommonNormalize(): any
 export declare function setCommonNormalize(target: any): Promise<void>
-export declare function getImages(): Record<string, Buffer>
+export declare function getImages(viewId?: number | undefined | null): Record<string, Buffer>
+/**
+ * path of the track relative to the directory containing the session file.
+ * The absolute path is returned if the relative path can't be made (e.g. different drives).
+ */
+export declare function toSessionRelativePath(path: string, sessionPath: string): string
+/**
+ * resolve the track path stored in the session file.
+ * relative_path (from the directory containing the session file) is tried first,
+ * then absolute_path. Returns null if the file doesn't exist at both paths.
+ */
+export declare function resolveSessionTrackPath(relativePath: string | undefined | null, absolutePath: string | undefined | null, sessionPath: string): string | null
+/**
+ * Import the tracks of the session file into the current session.
+ * Append: the ids colliding with the current tracks are moved, and the open files are reused.
+ * Replace: the current tracks are removed first.
+ * The markers and annotations are returned with the mapped track ids to be merged by the renderer.
+ * applyTrackListChanges should be called after this.
+ */
+export declare function importSession(path: string, mode: SessionImportMode): Promise<ImportedSession>
 export declare function findIdByPath(path: string): Promise<number>
-export declare function getOverview(trackId: number, width: number, height: number, dpr: number): Promise<Buffer>
-export declare function freqPosToHzOnCurrentRange(y: number, height: number): number
-export declare function freqPosToHz(y: number, height: number, hzRange: [number, number]): number
-export declare function freqHzToPos(hz: number, height: number, hzRange: [number, number]): number
+/**
+ * Requests for the same track are coalesced.
+ * Superseded requests (e.g. during resizing) return immediately with is_stale = true.
+ */
+export declare function getOverview(trackId: number, width: number, height: number, dpr: number): Promise<CoalescedBuffer>
+/**
+ * tiny PNG of the overview of the whole track for previews (e.g. the recent files list).
+ * Cached by the content hash of the track.
+ */
+export declare function getThumbnail(trackId: number, width: number, height: number): Promise<Buffer>
+/**
+ * layout of the overview image and the dB axis markers of its limiter gain lanes.
+ * null if the track doesn't exist.
+ */
+export declare function getOverviewDrawingInfo(trackId: number, height: number, dpr: number, maxNumTicks: number): OverviewDrawingInfo | null
+/**
+ * samples of the channel in the time range, copied into a new buffer
+ * with the header of the binary layout (see binary_layout.rs)
+ */
+export declare function getWavSlice(idChStr: string, startSec: number, endSec: number): Buffer
+/**
+ * per-pixel classes of the waveform slice drawn with width px,
+ * so that the renderer can color the clipped or limited samples.
+ * If with_envelopes, the envelopes before and after the normalization and guard clipping
+ * are included for the compare mode.
+ * Requests for the same channel are coalesced.
+ * Superseded requests (e.g. during scrolling) return immediately with is_stale = true.
+ */
+export declare function getWavDrawingInfo(idChStr: string, startSec: number, endSec: number, width: number, withEnvelopes?: boolean | undefined | null): Promise<WavDrawingInfo>
+/**
+ * Returns the tile of the spectrogram grey image with the binary layout.
+ * zoom_level 0 is the resolution of the spectrogram (one column per frame),
+ * and each level above halves the resolution.
+ * tile_x: index along time, tile_y: index along frequency (from the highest frequency)
+ * Requests for the same tile position of the channel are coalesced.
+ * Superseded requests (e.g. during zooming) return immediately with is_stale = true.
+ */
+export declare function getSpecTile(idChStr: string, zoomLevel: number, tileX: number, tileY: number): Promise<CoalescedBuffer>
+/**
+ * Returns the tile of the grey image of the spectrogram layer with the same layout as getSpecTile.
+ * The delta layer should be enabled by setSpecDeltaLayer in advance.
+ * Coalesced with getSpecTile of the same tile position.
+ */
+export declare function getSpectrogramLayer(idChStr: string, layer: SpecLayer, zoomLevel: number, tileX: number, tileY: number): Promise<CoalescedBuffer>
+export declare function getSpecDeltaLayer(): Promise<boolean>
+/** enable or disable calculating the delta (temporal first difference) layer of spectrograms */
+export declare function setSpecDeltaLayer(enabled: boolean): Promise<void>
+export declare function getSpecTileGrid(idChStr: string, zoomLevel: number): Promise<SpecTileGrid>
+/**
+ * win/hop/n_fft derived from the sample rate of the track and the spec setting
+ * (and the memory limit of the spectrogram)
+ */
+export declare function getEffectiveStftParams(trackId: number): Promise<EffectiveStftParams>
+/** point_a, point_b: (sec, hz) */
+export declare function measure(idChStr: string, pointA: [number, number], pointB: [number, number]): Promise<SpecMeasurement>
+/**
+ * dB at the percentile (0 ~ 100) over time for each frequency bin of the spectrogram
+ * (e.g. 10th percentile as the noise floor spectrum)
+ */
+export declare function getNoiseProfile(trackId: number, ch: number, percentile: number): Promise<NoiseProfile>
+/**
+ * Capture the spectrum of id_ch at sec (e.g. the playhead) to compare it with other spectra
+ * captured at different times or from different tracks.
+ * name: "{file name} {id_ch} @ {time}" if not given. The snapshot with the same name is replaced.
+ */
+export declare function captureSpectrumSnapshot(idChStr: string, sec: number, name?: string | undefined | null): Promise<SpectrumSnapshot>
+/** captured spectra in the captured order */
+export declare function listSpectrumSnapshots(): Array<SpectrumSnapshot>
+/** false if the snapshot of the name doesn't exist */
+export declare function removeSpectrumSnapshot(name: string): boolean
+export declare function clearSpectrumSnapshots(): void
+/** view_id: the frequency axis of the view may be broken (default: main view 0) */
+export declare function freqPosToHzOnCurrentRange(y: number, height: number, viewId?: number | undefined | null): number
+export declare function freqPosToHz(y: number, height: number, hzRange: [number, number], viewId?: number | undefined | null): number
+export declare function freqHzToPos(hz: number, height: number, hzRange: [number, number], viewId?: number | undefined | null): number
 export declare function secondsToLabel(sec: number): string
+/** string of the position in the unit. sr is only used for the units in samples. */
+export declare function formatPosition(sec: number, unit: PositionUnit, sr: number): string
+/**
+ * Convert the cursor x (px) on the view starting at start_sec to the position.
+ * The position is snapped to the nearest sample if getSnapToSample() is true.
+ */
+export declare function cursorToPosition(x: number, startSec: number, pxPerSec: number, sr: number): CursorPosition
+export declare function getSnapToSample(): boolean
+export declare function setSnapToSample(enabled: boolean): void
 export declare function timeLabelToSeconds(label: string): number
 export declare function hzToLabel(hz: number): string
 export declare function freqLabelToHz(label: string): number
 export declare function getTimeAxisMarkers(startSec: number, endSec: number, tickUnit: number, labelInterval: number, maxSec: number): any
-export declare function getFreqAxisMarkers(maxNumTicks: number, maxNumLabels: number, maxTrackHz: number): any
+/**
+ * Show the time axis in bars/beats instead of seconds.
+ * The markers of getTimeAxisMarkers are in bars/beats (tickUnit in beats),
+ * and getFittedTimeAxisMarkers labels bar numbers (and beats if zoomed in).
+ * time_signature: (beats per bar, beat unit), offset_sec: time of the first beat of bar 1
+ */
+export declare function setTempoGrid(bpm: number, timeSignature: [number, number], offsetSec: number): void
+/** show the time axis in seconds again */
+export declare function clearTempoGrid(): void
+export declare function getFreqAxisMarkers(maxNumTicks: number, maxNumLabels: number, maxTrackHz: number, viewId?: number | undefined | null): any
 export declare function getAmpAxisMarkers(maxNumTicks: number, maxNumLabels: number, ampRange: [number, number]): any
 export declare function getdBAxisMarkers(maxNumTicks: number, maxNumLabels: number, minDB: number, maxDB: number): any
+/**
+ * Time axis markers with the densest labels not overlapping each other in the width (px).
+ * label_metrics: {charWidths, defaultCharWidth, labelHeight, minGap} measured in the frontend.
+ * Unlabeled markers are minor ticks.
+ */
+export declare function getFittedTimeAxisMarkers(startSec: number, endSec: number, width: number, maxSec: number, labelMetrics: any): any
+/** frequency axis markers with the most labels not overlapping each other in the height (px) */
+export declare function getFittedFreqAxisMarkers(height: number, maxTrackHz: number, labelMetrics: any, viewId?: number | undefined | null): any
+/** amplitude axis markers with the most labels not overlapping each other in the height (px) */
+export declare function getFittedAmpAxisMarkers(height: number, ampRange: [number, number], labelMetrics: any): any
+/** dB axis markers with the most labels not overlapping each other in the height (px) */
+export declare function getFitteddBAxisMarkers(height: number, minDB: number, maxDB: number, labelMetrics: any): any
 export declare function getMaxdB(): Promise<number>
 export declare function getMindB(): Promise<number>
 export declare function getMaxTrackHz(): number
 export declare function getLongestTrackLengthSec(): number
+/**
+ * start sec of the view following the playhead at position_sec during playback.
+ * All the render layers should scroll to this value to move identically.
+ */
+export declare function getFollowScrollTarget(positionSec: number, viewWidthSec: number, mode: FollowMode): number
+/** visible time and frequency ranges shared with the observers (e.g. OSC clients) */
+export declare function getViewport(): ViewportState
+/**
+ * Set the visible ranges. sec_range: [start, end], hz_range: [min, max].
+ * The observers waiting in waitViewportChanged are notified if the ranges are changed.
+ */
+export declare function setViewport(secRange: Array<number>, hzRange: Array<number>): ViewportState
+/**
+ * viewport-changed event: resolved with the viewport when its version differs from version
+ * (immediately if it already differs), e.g. changed by an OSC client mirroring another view.
+ */
+export declare function waitViewportChanged(version: number): Promise<ViewportState>
 export declare function getChannelCounts(trackId: number): number
 export declare function getLengthSec(trackId: number): number
 export declare function getSampleRate(trackId: number): number
 export declare function getFormatInfo(trackId: number): AudioFormatInfo
+/**
+ * Enable the online lookup of identifyTrack with the AcoustID API key of the user.
+ * null disables the lookup.
+ */
+export declare function setAcoustidApiKey(key?: string | undefined | null): void
+/** true if fpcalc of Chromaprint (needed by identifyTrack) is bundled or in PATH */
+export declare function isFingerprintAvailable(): boolean
+/**
+ * Identify the track by its AcoustID fingerprint (calculated by fpcalc of Chromaprint)
+ * and keep the title and the artist of the matched recording (see getTrackIdentity).
+ * null if no recording matches or the lookup failed (e.g. offline).
+ * Err with "fpcalc of Chromaprint is not found. ..." if fpcalc is not available.
+ */
+export declare function identifyTrack(trackId: number): Promise<TrackIdentity | null>
+/** title and artist found by identifyTrack. null if not identified. */
+export declare function getTrackIdentity(trackId: number): TrackIdentity | null
+/**
+ * true for each channel of the file which is decoded (see setDecodedChannels).
+ * Empty if the track doesn't exist.
+ */
+export declare function getDecodedChannelMask(trackId: number): Array<boolean>
+/** time ranges of corrupt packets which were filled with silence while decoding the track */
+export declare function getDecodeGaps(trackId: number): Array<DecodeGap>
 export declare function getGlobalLUFS(trackId: number): number
 export declare function getRMSdB(trackId: number): number
 export declare function getMaxPeakdB(trackId: number): number
+/** stats of the track in the global selection. null if no selection or out of the track */
+export declare function getSelectionStats(trackId: number): Promise<SelectionStats | null>
+/** LRA, crest factor, PSR, and PLR of the track (after normalization and guard clipping) */
+export declare function getDynamicsStats(trackId: number): DynamicsStats
+/**
+ * loudness, RMS, and peak of the channel of the track measured alone,
+ * for quantifying the imbalance between channels
+ */
+export declare function getChannelStats(trackId: number, ch: number): ChannelStats
 export declare function getGuardClipStats(trackId: number): string
+/**
+ * gain staging of the track: true peak before normalization, normalization gain,
+ * guard clipping gain, and the resulting peaks and headroom (overall and per channel)
+ */
+export declare function getHeadroomReport(trackId: number): Promise<HeadroomReport>
+/**
+ * SHA-256 (hex) of the decoded PCM of the track (before normalization and channel transforms).
+ * Equal hashes mean the files decode to bit-identical audio with the same sample rate.
+ */
+export declare function getPcmHash(trackId: number): Promise<string>
+/** returns null for non-stereo tracks */
+export declare function getMonoCompatibility(trackId: number): Promise<MonoCompatibility | null>
+/**
+ * transcode the original audio (before normalization) of tracks to out_dir on the rayon pool.
+ * Only the global selection is exported if exists unless params.wholeTrack is true.
+ * The progress can be polled by getTranscodeProgress.
+ */
+export declare function transcodeTracks(trackIds: Array<number>, codec: TranscodeCodec, params: any, outDir: string): Promise<Array<TranscodeResult>>
+/**
+ * integrated loudness and maximum momentary/short-term loudness of each segment_sec of the track
+ * (e.g. 60 for a per-minute table). The last segment can be shorter.
+ */
+export declare function getSegmentedLoudness(trackId: number, segmentSec: number): Promise<Array<SegmentLoudness>>
+/**
+ * how much each of the n_bands highest octave bands (centered at 1 kHz x 2^k, down to 15.625 Hz)
+ * contributes to the integrated loudness of the track (K-weighted, gated by the whole signal).
+ * The bands are ordered from the lowest, and their contributions sum up to 1.
+ */
+export declare function getBandLoudnessContribution(trackId: number, nBands: number): Promise<BandLoudnessContribution>
+/** write the table of get_segmented_loudness as CSV */
+export declare function exportSegmentedLoudnessCsv(trackId: number, segmentSec: number, path: string): Promise<void>
+/**
+ * write a standalone HTML report with the overview images, stats, and guard clipping summaries
+ * of all tracks
+ */
+export declare function exportSessionReportHtml(path: string): Promise<void>
+/**
+ * Run a script (JSON array of steps such as addFolder, setCommonNormalize, exportReport)
+ * sequentially. Stops at the first failed step.
+ * The frontend should add result.addedTrackIds to its track list.
+ */
+export declare function runScript(script: any): Promise<ScriptResult>
+/**
+ * write a PNG of the visible time/freq window of the visible tracks stacked.
+ * layout.axes, layout.freqGrid, and layout.colorbar burn in the axes, the frequency grid,
+ * and the colorbar (dB) respectively.
+ */
+export declare function exportViewportImage(layout: any, path: string): Promise<void>
+/**
+ * Write the global selection of the track to temporary files to be put on the clipboard
+ * by the frontend (e.g. for bug reports or handing off to a DAW).
+ * The files of the previous call of the same selection are overwritten.
+ */
+export declare function exportSelectionForClipboard(trackId: number, kinds: Array<ClipboardContentKind>): Promise<SelectionClipboardFiles>
+/** number and size of the temporary files created by thesia (downloads, exports, ...) */
+export declare function getTempFilesUsage(): TempFilesUsage
+/**
+ * Remove the temporary files except the sources of the open tracks (e.g. downloaded URLs).
+ * Returns the usage freed. All the temporary files are removed on exit anyway.
+ */
+export declare function cleanTempFiles(): Promise<TempFilesUsage>
+/** counts of queued/running background jobs for the busy indicator */
+export declare function getBackendBusyState(): BackendBusyState
+/**
+ * channels whose specs are being calculated (or queued) with the fraction done,
+ * e.g. to show the progress on the tracks of long files after addTracks or setSpecSetting.
+ * The changes are notified by waitSpecProgressChanged.
+ */
+export declare function getSpecProgress(): Array<SpecProgress>
+/**
+ * spec-progress event: resolved with the progress when its version differs from version
+ * (immediately if it already differs). Not resolved while the progress is unchanged.
+ */
+export declare function waitSpecProgressChanged(version: number): Promise<SpecProgressState>
+export declare function getTranscodeProgress(): JobProgress
+/** detect dual-mono, polarity-inverted, or silent channels. returns null for non-stereo tracks */
+export declare function analyzeChannelRelationship(trackId: number): Promise<ChannelRelationship | null>
+/**
+ * find discontinuities and zero runs characteristic of recording dropouts.
+ * sensitivity: 0 ~ 1 (higher detects more)
+ */
+export declare function detectDropouts(trackId: number, sensitivity: number): Promise<Array<Dropout>>
+/**
+ * number of the samples of the channel in each of n_bins bins dividing -1 ~ 1 uniformly
+ * (before normalization). The samples out of the range are counted in the first/last bin.
+ */
+export declare function getAmplitudeHistogram(trackId: number, ch: number, nBins: number): Promise<Array<number>>
+/**
+ * bit depth actually used by the samples of the track,
+ * e.g. 16 for a 24-bit file which contains 16-bit data.
+ */
+export declare function getBitDepthUsage(trackId: number): Promise<BitDepthUsage>
+/**
+ * Estimate how likely the track has been through a lossy encoder (e.g. a wav converted from
+ * an mp3) from the spectral cutoff shelf and pre-echo before transients.
+ */
+export declare function estimateLossyGeneration(trackId: number): Promise<LossyGenerationEstimate>
+/**
+ * Find the segments of all the tracks similar to the selection (start_sec ~ end_sec) of
+ * the channel ch of the track (e.g. every occurrence of a beep or an artifact across takes).
+ * threshold: minimum similarity (0 ~ 1, e.g. 0.9).
+ * The result is ranked by the similarity and includes the selection itself.
+ */
+export declare function findSimilar(trackId: number, ch: number, startSec: number, endSec: number, threshold: number): Promise<Array<SimilarSegment>>
+/**
+ * N x N matrix comparing every pair of the tracks (e.g. which files are versions of each other).
+ * [i][j]: the estimated offset, correlation coefficient and the residual RMS after aligning
+ * track_ids[j] to track_ids[i] and matching the gain. null on the diagonal or if either is silent.
+ * The beginning (30 sec) of the mono mixdown of the original audio is compared.
+ * The progress (number of pairs) can be polled by getCompareProgress.
+ */
+export declare function computeCompareMatrix(trackIds: Array<number>): Promise<Array<Array<TrackPairComparison | undefined | null>>>
+export declare function getCompareProgress(): JobProgress
+/**
+ * spectral centroid (Hz) of each frame of the channel (e.g. to compare the high frequency
+ * behavior of de-essers or codecs over time). The window is the same as the spectrogram.
+ * hop_sec: default to the hop of the spectrogram
+ */
+export declare function getCentroidTimeseries(trackId: number, ch: number, hopSec?: number | undefined | null): Promise<CentroidTimeseries>
 export declare function getPath(trackId: number): string
 export declare function getFileName(trackId: number): string
 export declare function getColorMap(): Buffer
+/**
+ * Negotiate the length of the colormap with the gradient resolution of the frontend
+ * (e.g. the height of the colorbar in device pixels, which depends on the number of tracks
+ * shown, the display scaling and the zoom). Call again whenever the resolution changes.
+ * Returns the RGB colors of the length (2 ~ 8192) from the lowest to the highest dB.
+ * The colormaps of the recent lengths are cached, so switching back to a length is cheap.
+ */
+export declare function setColormapLength(length: number): Promise<Buffer>
+/**
+ * n_steps (>= 2) colors of the spectrogram colormap from the lowest to the highest dB
+ * paired with their exact dB values under the current dB range and display tilt.
+ * dB_range: the range returned by setImageState in the Viewport auto contrast mode.
+ * min dB ~ max dB of all tracks is used if not given.
+ */
+export declare function getColorbar(nSteps: number, dBRange?: [number, number] | undefined | null): Promise<Array<ColorbarEntry>>
 export declare function setVolumedB(volumeDB: number): Promise<void>
+/** attenuate the playback by 20 dB (on top of the volume) */
+export declare function setMonitorDim(dim: boolean): Promise<void>
+export declare function setMonitorMute(mute: boolean): Promise<void>
+/**
+ * calibrated monitor gain (dB, <= 0) applied on top of the volume,
+ * so that A/B listening happens at a consistent level
+ */
+export declare function setReferenceLeveldB(levelDB: number): Promise<void>
+/** thresholdDB is clamped to 0 dBFS at most */
+export declare function setOutputLimiter(enabled: boolean, thresholdDB: number): Promise<void>
+export declare function setPlayerDownmix(mode: DownmixMode): Promise<void>
+/**
+ * convolve the playback with the impulse response in the audio file (e.g. headphone correction).
+ * null to disable.
+ */
+export declare function setPlayerIr(path?: string | undefined | null): Promise<void>
+/**
+ * preview of denoising by subtracting the noise floor of the profile track from the playback.
+ * amount: 0 ~ 1. null profile_track_id to disable.
+ */
+export declare function setPlayerDenoise(profileTrackId: number | undefined | null, amount: number): Promise<void>
+/**
+ * magnitude response of the DSP applied to the playback at n_points log-spaced frequencies
+ * from 20 Hz to the Nyquist frequency of the output device.
+ * Only the impulse response convolution changes the magnitude response;
+ * the output limiter is reported by its ceiling.
+ */
+export declare function getPlaybackChainResponse(nPoints: number): Promise<PlaybackChainResponse>
+/**
+ * Render the playback of the current track over sec_range (session time) offline
+ * with the DSP chain of the player (time-stretch, denoiser, TF box solo, downmix, IR,
+ * volume and output limiter) at the sample rate of the output device,
+ * and write it to a 32-bit float stereo wav file at path. Dither is not applied.
+ * Returns path.
+ */
+export declare function bouncePlayback(path: string, secRange: [number, number]): Promise<string>
+export declare function getInputDevices(): Array<string>
+/**
+ * round-trip latency measured by playing a chirp through the player (the track is paused)
+ * while recording from the input device (e.g. via a loopback cable or a microphone).
+ * input_device: name of the input device. The default input device is used if not given.
+ */
+export declare function measureOutputLatency(inputDevice?: string | undefined | null): Promise<LatencyMeasurement>
+/** fade duration (ms) on pause/resume. zero to disable fading */
+export declare function setPlayerFadeMillisec(fadeMs: number): Promise<void>
+/** playback starts pre_roll_ms earlier than the seek point */
+export declare function setPlayerPreRollMillisec(preRollMs: number): Promise<void>
+/**
+ * non-destructive time-stretch of the track for playback.
+ * ratio: output duration / original duration (> 1: slower). clamped to 0.25 ~ 4
+ */
+export declare function setTrackStretch(trackId: number, ratio: number): Promise<void>
+/** setTrackStretch of multiple tracks at once. Nothing is changed if any of the tracks doesn't exist. */
+export declare function setTracksStretch(trackIds: Array<number>, ratio: number): Promise<void>
+/** non-destructive pitch-shift of the track for playback. clamped to ±24 semitones */
+export declare function setTrackPitch(trackId: number, semitones: number): Promise<void>
+/** setTrackPitch of multiple tracks at once. Nothing is changed if any of the tracks doesn't exist. */
+export declare function setTracksPitch(trackIds: Array<number>, semitones: number): Promise<void>
+export declare function getTrackOffset(trackId: number): number
+/**
+ * Place the track at offset_sec of the session timeline.
+ * The selection, the playhead, and the drawing requests are on the session timeline.
+ */
+export declare function setTrackOffset(trackId: number, offsetSec: number): Promise<void>
+/** Convert the session time (e.g. of a marker) to the time of the track */
+export declare function sessionToTrackSec(trackId: number, sec: number): number
+/** Convert the time of the track (e.g. of a detected event) to the session time */
+export declare function trackToSessionSec(trackId: number, sec: number): number
 export declare function setTrackPlayer(trackId: number, sec?: number | undefined | null): Promise<void>
 export declare function seekPlayer(sec: number): Promise<void>
+/**
+ * seek forward (backward if backward is true) by the seek increment of amount_kind.
+ * The destination is snapped to a sample of the current track.
+ */
+export declare function seekPlayerRelative(amountKind: SeekAmountKind, backward?: boolean | undefined | null): Promise<void>
+export declare function getSeekIncrements(): SeekIncrements
+/** amounts (sec) of seeking by seekPlayerRelative. All should be positive. */
+export declare function setSeekIncrements(small: number, medium: number, large: number): void
+/**
+ * Tape-style scrubbing while dragging the playhead.
+ * The playback is paused and a short grain around sec is played at the speed
+ * (track sec per sec, negative: backward).
+ */
+export declare function scrubPlayer(sec: number, speed: number): Promise<void>
 export declare function pausePlayer(): Promise<void>
 export declare function resumePlayer(): Promise<void>
+/**
+ * Execute the action of the OS media keys or media controls forwarded by navigator.mediaSession.
+ * seek_sec: destination of SeekTo, or the offset of SeekBackward/SeekForward
+ * (the medium seek increment if not given).
+ */
+export declare function handleMediaAction(action: MediaAction, seekSec?: number | undefined | null): Promise<void>
+/** metadata and position for the OS media controls. null if no track is loaded to the player. */
+export declare function getNowPlaying(): Promise<NowPlaying | null>
 export declare function getPlayerState(): PlayerState
+/**
+ * transport of the player for external sync.
+ * The revision is read before the state, so the state is never older than the revision.
+ */
+export declare function getTransportState(): TransportState
+/**
+ * Start the OSC server for the remote control of the player on the UDP port
+ * (0: any free port) and return the bound port. The running server is restarted.
+ * The server listens on 127.0.0.1 unless bind_addr (e.g. "0.0.0.0" to accept the other hosts
+ * on the network, which can then control the player without authentication) is given.
+ */
+export declare function startOscServer(port: number, bindAddr?: string | undefined | null): number
+export declare function stopOscServer(): void
+/** null if the OSC server is not running */
+export declare function getOscServerPort(): number | null
+/**
+ * format of the output stream negotiated with the output device.
+ * returns null if the format is unknown.
+ */
+export declare function getAudioStreamInfo(): AudioStreamInfo | null
+/**
+ * names of the audio hosts (cpal) available on this platform
+ * (e.g. "WASAPI", "ASIO", "ALSA", "JACK"). The first is the default.
+ */
+export declare function listAudioHosts(): Array<string>
+/**
+ * Force the audio host of the name (one of listAudioHosts). null for the default host.
+ * The output stream is rebuilt on the default output device of the host.
+ */
+export declare function setAudioHost(name?: string | undefined | null): Promise<void>
+/** null if the default host is used */
+export declare function getAudioHost(): string | null
+export declare function getBufferHealth(): BufferHealthInfo
+/**
+ * Set the buffer size (frames) of the output stream. null for the default of the device.
+ * The output stream is rebuilt.
+ */
+export declare function setBufferSize(bufferSize?: number | undefined | null): Promise<void>
+/** If true, the buffer size is doubled when the underruns repeat. */
+export declare function setAdaptiveBufferSize(adaptive: boolean): void
+/** set the global time selection (sec) shared by all tracks */
+export declare function setSelection(startSec: number, endSec: number): Promise<void>
+export declare function clearSelection(): Promise<void>
+export declare function getSelection(): Selection | null
+/** if enabled, the player loops the global selection */
+export declare function setPlayerLooping(enabled: boolean): Promise<void>
+export declare function getPlayerLooping(): boolean
+/**
+ * Play only the time-frequency box (e.g. dragged on the spectrogram) from start_sec.
+ * The box is band-pass filtered with zero phase and crossfaded at the time edges.
+ * The outside of the box is silent until clearTfBoxSolo is called.
+ */
+export declare function soloTfBox(startSec: number, endSec: number, minHz: number, maxHz: number): Promise<void>
+export declare function clearTfBoxSolo(): Promise<void>
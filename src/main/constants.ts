@@ -1,4 +1,17 @@
-export const SUPPORTED_TYPES = ["aac", "flac", "m4a", "mp3", "oga", "ogg", "wav"]; // keep ascending order
+export const SUPPORTED_TYPES = [
+  "aac",
+  "flac",
+  "m4a",
+  "m4v",
+  "mkv",
+  "mov",
+  "mp3",
+  "mp4",
+  "oga",
+  "ogg",
+  "wav",
+  "webm",
+]; // keep ascending order
 
 export const SUPPORTED_MIME = [
   "audio/aac",
@@ -11,6 +24,11 @@ export const SUPPORTED_MIME = [
   "audio/x-flac",
   "audio/x-m4a",
   "audio/x-wav",
+  "video/mp4",
+  "video/quicktime",
+  "video/webm",
+  "video/x-m4v",
+  "video/x-matroska",
 ]; // keep ascending order;
 
 export const PLAY_JUMP_SEC = 1.0; // sec
@@ -1,7 +1,16 @@
-import {BrowserWindow, ipcMain, dialog, Menu, MenuItemConstructorOptions, app} from "electron";
+import {
+  BrowserWindow,
+  ipcMain,
+  dialog,
+  Menu,
+  MenuItemConstructorOptions,
+  app,
+  clipboard,
+  nativeImage,
+} from "electron";
 import os from "os";
 import path from "path";
-import settings from "electron-settings";
+import {pathToFileURL} from "url";
 import {SUPPORTED_TYPES} from "./constants";
 
 export function mutateEditMenu(func: (item: Electron.MenuItem) => void) {
@@ -45,6 +54,31 @@ export function showOpenDialog() {
   });
 }
 
+export function showScriptOpenDialog() {
+  return dialog.showOpenDialog({
+    title: "Select the script to run",
+    defaultPath: app.getPath("home"),
+    filters: [{name: "Thesia Script", extensions: ["json"]}],
+    properties: ["openFile"],
+  });
+}
+
+// file reference which can be pasted as a file (e.g. into a DAW or a file manager)
+function writeFileReferenceToClipboard(filePath: string) {
+  if (process.platform === "darwin") {
+    const escaped = filePath.replace(/&/g, "&amp;").replace(/</g, "&lt;").replace(/>/g, "&gt;");
+    const plist =
+      '<?xml version="1.0" encoding="UTF-8"?>' +
+      '<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">' +
+      `<plist version="1.0"><array><string>${escaped}</string></array></plist>`;
+    clipboard.writeBuffer("NSFilenamesPboardType", Buffer.from(plist));
+  } else if (process.platform === "win32") {
+    clipboard.writeBuffer("FileNameW", Buffer.from(`${filePath}\0`, "ucs2"));
+  } else {
+    clipboard.writeBuffer("text/uri-list", Buffer.from(pathToFileURL(filePath).href));
+  }
+}
+
 export function addAppRenderedListener(pathsToOpen: string[]) {
   ipcMain.once("app-rendered", (event) => {
     if (
@@ -54,13 +88,11 @@ export function addAppRenderedListener(pathsToOpen: string[]) {
     )
       event.reply("open-files", process.argv.slice(1));
     else if (process.platform === "darwin" && pathsToOpen.length > 0)
-      event.reply("open-files", pathsToOpen);
+      event.reply("open-files", pathsToOpen.splice(0));
   });
 }
 
 export default function addIPCListeners() {
-  ipcMain.on("set-setting", (_, key, value) => settings.set(key, value));
-
   ipcMain.on("show-open-dialog", async (event) => {
     event.reply("remove-global-focusout-listener");
     mutateEditMenu((item) => {
@@ -116,6 +148,22 @@ export default function addIPCListeners() {
     },
   );
 
+  // Each write replaces the whole clipboard, so the image is written with the path of the audio
+  // as text, and the file reference is written only when the audio is copied alone.
+  ipcMain.on(
+    "copy-files-to-clipboard",
+    (_, imagePath: string | null, audioPath: string | null) => {
+      if (imagePath) {
+        clipboard.write({
+          image: nativeImage.createFromPath(imagePath),
+          text: audioPath ?? imagePath,
+        });
+      } else if (audioPath) {
+        writeFileReferenceToClipboard(audioPath);
+      }
+    },
+  );
+
   ipcMain.on("show-track-context-menu", (event) => {
     const menu = Menu.buildFromTemplate([
       {
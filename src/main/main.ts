@@ -90,7 +90,7 @@ const createWindow = async (pathsToOpen: string[]) => {
   mainWindow.on("ready-to-show", () => {
     if (!mainWindow) throw new Error('"mainWindow" is not defined');
 
-    mainWindow.webContents.send("render-with-settings", settings.getSync());
+    mainWindow.webContents.send("render-with-settings", settings.getSync(), settings.file());
     if (process.env.START_MINIMIZED) mainWindow.minimize();
     else mainWindow.show();
   });
@@ -147,8 +147,13 @@ app
 
     app.on("open-file", (e, filePath) => {
       e.preventDefault();
-      if (mainWindow === null) createWindow([filePath]);
-      else mainWindow.webContents.send("open-files", [filePath]);
+      // sent on "app-rendered" if the renderer isn't loaded yet
+      if (mainWindow === null || mainWindow.webContents.isLoading()) {
+        pathsToOpenAfterLaunch.push(filePath);
+        if (mainWindow === null) createWindow(pathsToOpenAfterLaunch);
+      } else {
+        mainWindow.webContents.send("open-files", [filePath]);
+      }
     });
     app.on("activate", () => {
       // On macOS it's common to re-create a window in the app when the
@@ -1,6 +1,7 @@
-import {Menu, shell, BrowserWindow, MenuItemConstructorOptions, MenuItem} from "electron";
+import {Menu, shell, BrowserWindow, MenuItemConstructorOptions, MenuItem, dialog} from "electron";
+import {readFile} from "fs/promises";
 import {PLAY_BIG_JUMP_SEC, PLAY_JUMP_SEC} from "./constants";
-import {mutateEditMenu, showOpenDialog} from "./ipc";
+import {mutateEditMenu, showOpenDialog, showScriptOpenDialog} from "./ipc";
 
 interface DarwinMenuItemConstructorOptions extends MenuItemConstructorOptions {
   selector?: string;
@@ -30,6 +31,17 @@ const clickOpenMenu: MenuItemClick = async (_, browserWindow) => {
   browserWindow?.webContents.send("add-global-focusout-listener");
 };
 
+const clickRunScriptMenu: MenuItemClick = async (_, browserWindow) => {
+  const dialogResult = await showScriptOpenDialog();
+  if (dialogResult.canceled || !dialogResult.filePaths.length) return;
+  try {
+    const script = JSON.parse(await readFile(dialogResult.filePaths[0], "utf-8"));
+    browserWindow?.webContents.send("run-script", script);
+  } catch (err) {
+    dialog.showErrorBox("Could not read the script", String(err));
+  }
+};
+
 const clickFreqZoomIn: MenuItemClick = (_, browserWindow, event) => {
   if (!event.triggeredByAccelerator) browserWindow?.webContents.send("freq-zoom-in");
 };
@@ -112,6 +124,7 @@ export default class MenuBuilder {
       label: "File",
       submenu: [
         {label: "Open Audio Tracks...", accelerator: "Command+O", click: clickOpenMenu},
+        {label: "Run Script...", click: clickRunScriptMenu},
         {type: "separator"},
         {role: "close"},
       ],
@@ -353,6 +366,7 @@ export default class MenuBuilder {
         label: "&File",
         submenu: [
           {label: "&Open Audio Tracks...", accelerator: "Ctrl+O", click: clickOpenMenu},
+          {label: "&Run Script...", click: clickRunScriptMenu},
           {type: "separator"},
           {role: "close"},
         ],
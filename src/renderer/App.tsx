@@ -18,6 +18,7 @@ import {
   showEditContextMenuIfEditableNode,
   showElectronFileOpenErrorMsg,
 } from "./lib/ipc-sender";
+import BackendAPI from "./api";
 import {SUPPORTED_MIME} from "../main/constants";
 import useTracks from "./hooks/useTracks";
 import useSelectedTracks from "./hooks/useSelectedTracks";
@@ -44,6 +45,7 @@ function MyApp({userSettings}: AppProps) {
     reloadTracks,
     refreshTracks,
     addTracks,
+    addTrackIds,
     removeTracks,
     hideTracks,
     changeTrackOrder,
@@ -125,9 +127,15 @@ function MyApp({userSettings}: AppProps) {
   });
 
   useEffect(() => {
-    ipcRenderer.on("open-files", async (_, filePaths) => openFiles(filePaths));
+    // the files are queued in the backend (see index.tsx) even before init
+    const openPendingFiles = () => {
+      const filePaths = BackendAPI.takePendingOpenFiles();
+      if (filePaths.length) openFiles(filePaths);
+    };
+    ipcRenderer.on("open-files", openPendingFiles);
+    openPendingFiles();
     return () => {
-      ipcRenderer.removeAllListeners("open-files");
+      ipcRenderer.removeListener("open-files", openPendingFiles);
     };
   }, [openFiles]);
 
@@ -157,6 +165,43 @@ function MyApp({userSettings}: AppProps) {
     };
   }, [removeSelectedTracks]);
 
+  const runScript = useEvent(async (_: Electron.IpcRendererEvent, script: unknown) => {
+    try {
+      const result = await BackendAPI.runScript(script);
+      if (result.addedTrackIds.length) addTrackIds(result.addedTrackIds);
+      await refreshTracks();
+      if (result.error) alert(`Script failed at ${result.error}`);
+    } catch (err) {
+      console.error("Could not run the script", err);
+      alert(`Could not run the script: ${err}`);
+    }
+  });
+
+  useEffect(() => {
+    ipcRenderer.on("run-script", runScript);
+    return () => {
+      ipcRenderer.removeAllListeners("run-script");
+    };
+  }, [runScript]);
+
+  useEffect(() => {
+    // re-dispatch the changes of the spec progress to the track views
+    let stopped = false;
+    const waitSpecProgress = (version: number) => {
+      BackendAPI.waitSpecProgressChanged(version)
+        .then((state) => {
+          if (stopped) return;
+          window.dispatchEvent(new CustomEvent("spec-progress", {detail: state.progress}));
+          waitSpecProgress(state.version);
+        })
+        .catch(() => {});
+    };
+    waitSpecProgress(0);
+    return () => {
+      stopped = true;
+    };
+  }, []);
+
   useEffect(() => {
     document.body.addEventListener("contextmenu", showEditContextMenuIfEditableNode);
     return () => {
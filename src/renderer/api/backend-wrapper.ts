@@ -1,6 +1,6 @@
 import backend from "backend";
 
-export {GuardClippingMode, FreqScale, SpecSetting} from "backend";
+export {GuardClippingMode, WavViewMode, DownmixMode, ChannelRelationKind, DropoutKind, TranscodeCodec, TrackGroupBy, TestSignalKind, FreqScale, SpecLayer, SpecSetting, FollowMode, PositionUnit, MediaAction, SessionImportMode, FreqAxisBreak, SelectionTransformKind} from "backend";
 
 // most api returns empty array for edge case
 /* get each track file's information */
@@ -19,6 +19,8 @@ export type MarkerDrawOption = {
   endSec?: number;
   maxSec?: number;
   maxTrackHz?: number;
+  // view whose frequency axis may be broken (default: main view)
+  viewId?: number;
   ampRange?: [number, number];
   mindB?: number;
   maxdB?: number;
@@ -46,13 +48,13 @@ export function getFreqAxisMarkers(
   maxNumLabels: number,
   markerDrawOptions?: MarkerDrawOption,
 ): Markers {
-  const {maxTrackHz} = markerDrawOptions || {};
+  const {maxTrackHz, viewId} = markerDrawOptions || {};
 
   if (maxTrackHz === undefined) {
     console.error("no markerDrawOptions for freq axis exist");
     return [];
   }
-  return backend.getFreqAxisMarkers(maxNumTicks, maxNumLabels, maxTrackHz);
+  return backend.getFreqAxisMarkers(maxNumTicks, maxNumLabels, maxTrackHz, viewId);
 }
 
 export function getAmpAxisMarkers(
@@ -94,8 +96,8 @@ export type SpecWavImages = {
 };
 
 /* images */
-export function getImages(): SpecWavImages {
-  return backend.getImages();
+export function getImages(viewId?: number): SpecWavImages {
+  return backend.getImages(viewId);
 }
 
 // written in snake case for compatibility with native api
@@ -104,6 +106,14 @@ export type DrawOptionForWav = {
   dpr: number;
 };
 
+// pixel metrics of axis labels measured in the frontend
+export type LabelMetrics = {
+  charWidths: Record<string, number>;
+  defaultCharWidth: number;
+  labelHeight: number;
+  minGap: number;
+};
+
 export const NormalizeOnTypeValues = ["LUFS", "RMSdB", "PeakdB"] as const;
 export type NormalizeOnType = (typeof NormalizeOnTypeValues)[number];
 export type NormalizeTarget =
@@ -125,6 +135,12 @@ export type PlayerState = {
   err: string;
   isPlaying: boolean;
   positionSec: number;
+  isLimiterActive: boolean;
+  deviceName: string;
+  deviceChangeCount: number;
+  monitorDim: boolean;
+  monitorMute: boolean;
+  referenceLeveldB: number;
 };
 
 export function getPlayerState(): PlayerState {
@@ -133,24 +149,63 @@ export function getPlayerState(): PlayerState {
 
 export const {
   init,
+  migrateUserSettings,
+  setCrashReportDir,
+  takeCrashReport,
+  setSettingsStorePath,
+  setUserSetting,
+  flushUserSettings,
+  queueOpenFiles,
+  takePendingOpenFiles,
+  reserveTrackIds,
   addTracks,
+  addRawTrack,
+  addSyntheticTrack,
+  previewSelectionTransform,
+  addTracksFromUrls,
+  getDownloadProgress,
+  probeFiles,
+  getProbeProgress,
+  cancelProbeFiles,
   reloadTracks,
   removeTracks,
   applyTrackListChanges,
+  setVisibleTracks,
   setImageState,
+  removeImageView,
+  setFreqAxisBreak,
+  getFreqAxisBreak,
   findIdByPath,
+  toSessionRelativePath,
+  resolveSessionTrackPath,
+  importSession,
   getPath,
   getFileName,
   getLengthSec,
   getFormatInfo,
+  setAcoustidApiKey,
+  isFingerprintAvailable,
+  identifyTrack,
+  getTrackIdentity,
+  getDecodedChannelMask,
+  getDecodeGaps,
   getGlobalLUFS,
   getRMSdB,
   getMaxPeakdB,
+  getSelectionStats,
   getLongestTrackLengthSec,
+  getFollowScrollTarget,
+  getViewport,
+  setViewport,
+  waitViewportChanged,
   freqPosToHzOnCurrentRange,
   freqPosToHz,
   freqHzToPos,
   secondsToLabel,
+  formatPosition,
+  cursorToPosition,
+  getSnapToSample,
+  setSnapToSample,
   timeLabelToSeconds,
   hzToLabel,
   freqLabelToHz,
@@ -158,19 +213,146 @@ export const {
   getMindB,
   getMaxTrackHz,
   getColorMap,
+  setColormapLength,
+  getColorbar,
   getOverview,
+  getThumbnail,
+  getOverviewDrawingInfo,
+  getWavSlice,
+  getWavDrawingInfo,
+  getSpecTile,
+  getSpectrogramLayer,
+  getSpecDeltaLayer,
+  setSpecDeltaLayer,
+  getSpecTileGrid,
+  getEffectiveStftParams,
+  measure,
+  getNoiseProfile,
+  captureSpectrumSnapshot,
+  listSpectrumSnapshots,
+  removeSpectrumSnapshot,
+  clearSpectrumSnapshots,
+  getFittedTimeAxisMarkers,
+  setTempoGrid,
+  clearTempoGrid,
+  getFittedFreqAxisMarkers,
+  getFittedAmpAxisMarkers,
+  getFitteddBAxisMarkers,
   getdBRange,
   setdBRange,
+  getAutoContrastMode,
+  setAutoContrastMode,
+  getFreqInterpolation,
+  setFreqInterpolation,
+  getDisplayTilt,
+  setDisplayTilt,
   getHzRange,
   setHzRange,
+  getFreqAxisLocked,
+  setFreqAxisLocked,
+  getTrackFreqInfo,
   getSpecSetting,
   setSpecSetting,
+  getMaxSpecMemoryMb,
+  setMaxSpecMemoryMb,
   getCommonGuardClipping,
   setCommonGuardClipping,
+  getWavViewMode,
+  setWavViewMode,
+  setTracksWavViewMode,
+  getTrackPolarityInvert,
+  setTrackPolarityInvert,
+  setTracksPolarityInvert,
+  getChannelOrder,
+  swapChannels,
+  setDecodedChannels,
+  autoGroupTracks,
+  autoPairTracks,
+  getTrackGroup,
+  queryTracks,
+  getLayout,
+  setLaneHeight,
+  setAllLaneHeights,
+  moveLane,
+  setTrackCollapsed,
+  setLayout,
+  getDynamicsStats,
+  getChannelStats,
   getGuardClipStats,
+  getHeadroomReport,
+  getPcmHash,
+  getMonoCompatibility,
+  analyzeChannelRelationship,
+  detectDropouts,
+  getAmplitudeHistogram,
+  getBitDepthUsage,
+  estimateLossyGeneration,
+  findSimilar,
+  computeCompareMatrix,
+  getCompareProgress,
+  getCentroidTimeseries,
+  transcodeTracks,
+  getSegmentedLoudness,
+  getBandLoudnessContribution,
+  exportSegmentedLoudnessCsv,
+  exportSessionReportHtml,
+  runScript,
+  exportViewportImage,
+  exportSelectionForClipboard,
+  getTempFilesUsage,
+  cleanTempFiles,
+  getBackendBusyState,
+  getSpecProgress,
+  waitSpecProgressChanged,
+  getTranscodeProgress,
   setVolumedB,
+  setMonitorDim,
+  setMonitorMute,
+  setReferenceLeveldB,
+  setOutputLimiter,
+  setPlayerDownmix,
+  setPlayerIr,
+  setPlayerDenoise,
+  getPlaybackChainResponse,
+  bouncePlayback,
+  getInputDevices,
+  measureOutputLatency,
+  setPlayerFadeMillisec,
+  setPlayerPreRollMillisec,
+  setTrackStretch,
+  setTracksStretch,
+  setTrackPitch,
+  setTracksPitch,
+  getTrackOffset,
+  setTrackOffset,
+  sessionToTrackSec,
+  trackToSessionSec,
   setTrackPlayer,
   pausePlayer,
   resumePlayer,
+  handleMediaAction,
+  getNowPlaying,
+  getAudioStreamInfo,
+  listAudioHosts,
+  setAudioHost,
+  getAudioHost,
+  getBufferHealth,
+  setBufferSize,
+  setAdaptiveBufferSize,
+  getTransportState,
+  startOscServer,
+  stopOscServer,
+  getOscServerPort,
   seekPlayer,
+  seekPlayerRelative,
+  getSeekIncrements,
+  setSeekIncrements,
+  scrubPlayer,
+  setSelection,
+  clearSelection,
+  getSelection,
+  setPlayerLooping,
+  getPlayerLooping,
+  soloTfBox,
+  clearTfBoxSolo,
 } = backend;
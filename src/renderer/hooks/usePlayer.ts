@@ -3,7 +3,7 @@ import {RefObject, useEffect, useRef, useState} from "react";
 import {PLAY_BIG_JUMP_SEC, PLAY_JUMP_SEC} from "main/constants";
 import useEvent from "react-use-event-hook";
 import {useHotkeys} from "react-hotkeys-hook";
-import BackendAPI from "../api";
+import BackendAPI, {MediaAction} from "../api";
 import {disableTogglePlayMenu, enableTogglePlayMenu, showPlayOrPauseMenu} from "../lib/ipc-sender";
 
 export type Player = {
@@ -28,13 +28,31 @@ function usePlayer(selectedTrackId: number, maxTrackSec: number) {
   });
 
   const requestRef = useRef<number>(0);
+  const deviceChangeCountRef = useRef<number>(0);
+  const transportRevisionRef = useRef<number>(0);
 
   const updatePlayerStates = useEvent(() => {
     // const start = performance.now();
-    const {isPlaying: newIsPlaying, positionSec, err} = BackendAPI.getPlayerState();
+    const {
+      isPlaying: newIsPlaying,
+      positionSec,
+      err,
+      deviceName,
+      deviceChangeCount,
+    } = BackendAPI.getPlayerState();
     // const end = performance.now();
     // console.log(`Execution time: ${end - start} ms`);
     if (err) console.error(err);
+    if (deviceChangeCount > deviceChangeCountRef.current) {
+      console.info(`output device changed: ${deviceName}`);
+      window.dispatchEvent(new CustomEvent("player-device-changed", {detail: deviceName}));
+    }
+    deviceChangeCountRef.current = deviceChangeCount;
+    const transport = BackendAPI.getTransportState();
+    if (transport.revision !== transportRevisionRef.current) {
+      window.dispatchEvent(new CustomEvent("transport-changed", {detail: transport}));
+    }
+    transportRevisionRef.current = transport.revision;
     if (isPlaying !== newIsPlaying) setIsPlaying(newIsPlaying);
     positionSecRef.current = positionSec;
     requestRef.current = requestAnimationFrame(updatePlayerStates);
@@ -120,6 +138,59 @@ function usePlayer(selectedTrackId: number, maxTrackSec: number) {
     showPlayOrPauseMenu(isPlaying);
   }, [isPlaying]);
 
+  // OS media keys and media controls (navigator.mediaSession uses the native API of each platform)
+  useEffect(() => {
+    const {mediaSession} = navigator;
+    if (!mediaSession) return undefined;
+    const actions: [MediaSessionAction, MediaAction][] = [
+      ["play", MediaAction.Play],
+      ["pause", MediaAction.Pause],
+      ["stop", MediaAction.Stop],
+      ["seekbackward", MediaAction.SeekBackward],
+      ["seekforward", MediaAction.SeekForward],
+      ["seekto", MediaAction.SeekTo],
+      ["previoustrack", MediaAction.PreviousTrack],
+      ["nexttrack", MediaAction.NextTrack],
+    ];
+    actions.forEach(([name, action]) => {
+      mediaSession.setActionHandler(name, (details) => {
+        const seekSec = action === MediaAction.SeekTo ? details.seekTime : details.seekOffset;
+        BackendAPI.handleMediaAction(action, seekSec).catch(console.error);
+      });
+    });
+    return () => actions.forEach(([name]) => mediaSession.setActionHandler(name, null));
+  }, []);
+
+  const updateNowPlaying = useEvent(async () => {
+    const {mediaSession} = navigator;
+    if (!mediaSession) return;
+    const nowPlaying = await BackendAPI.getNowPlaying();
+    if (!nowPlaying) {
+      mediaSession.metadata = null;
+      mediaSession.playbackState = "none";
+      return;
+    }
+    const {title, artist, durationSec, positionSec} = nowPlaying;
+    mediaSession.metadata = new MediaMetadata({title, artist: artist ?? ""});
+    mediaSession.playbackState = nowPlaying.isPlaying ? "playing" : "paused";
+    if (durationSec > 0) {
+      mediaSession.setPositionState({
+        duration: durationSec,
+        position: Math.min(Math.max(positionSec, 0), durationSec),
+      });
+    }
+  });
+
+  // the track can also be changed by the media controls (transport-changed)
+  useEffect(() => {
+    updateNowPlaying().catch(console.error);
+  }, [currentPlayingTrack, isPlaying, updateNowPlaying]);
+  useEffect(() => {
+    const onTransportChanged = () => updateNowPlaying().catch(console.error);
+    window.addEventListener("transport-changed", onTransportChanged);
+    return () => window.removeEventListener("transport-changed", onTransportChanged);
+  }, [updateNowPlaying]);
+
   return {
     isPlaying,
     positionSecRef,
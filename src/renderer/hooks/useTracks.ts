@@ -1,4 +1,4 @@
-import {useRef, useState, useMemo} from "react";
+import {useState, useMemo} from "react";
 import {difference} from "renderer/utils/arrayUtils";
 import useEvent from "react-use-event-hook";
 import {setUserSetting} from "renderer/lib/ipc-sender";
@@ -22,6 +22,9 @@ function useTracks(userSettings: UserSettings) {
   );
   const [blend, setBlend] = useState<number>(userSettings.blend);
   const [currentdBRange, setCurrentdBRange] = useState<number>(userSettings.dBRange);
+  const [currentDisplayTilt, setCurrentDisplayTilt] = useState<number>(
+    userSettings.displayTilt,
+  );
   const [currentCommonGuardClipping, setCurrentCommonGuardClipping] = useState<GuardClippingMode>(
     userSettings.commonGuardClipping,
   );
@@ -29,11 +32,6 @@ function useTracks(userSettings: UserSettings) {
     userSettings.commonNormalize,
   );
 
-  const waitingIdsRef = useRef<Set<number>>(new Set());
-  const addToWaitingIds = useEvent((ids: number[]) => {
-    ids.forEach((id) => waitingIdsRef.current.add(id));
-  });
-
   // eslint-disable-next-line react-hooks/exhaustive-deps
   const maxTrackSec = useMemo(BackendAPI.getLongestTrackLengthSec, [trackIds]);
 
@@ -83,16 +81,11 @@ function useTracks(userSettings: UserSettings) {
 
       if (!newPaths.length) return {existingIds, invalidPaths: []};
 
-      const newIds = [...Array(newPaths.length).keys()].map((i) => {
-        if (waitingIdsRef.current.size > 0) {
-          const id = waitingIdsRef.current.values().next().value as number;
-          waitingIdsRef.current.delete(id);
-          return id;
-        }
-        return trackIds.length + i;
-      });
+      // the ids are reserved by the backend not to collide with the tracks added by scripts
+      const newIds = await BackendAPI.reserveTrackIds(newPaths.length);
 
-      const addedIds = await BackendAPI.addTracks(newIds, newPaths);
+      const addedTracks = await BackendAPI.addTracks(newIds, newPaths);
+      const addedIds = addedTracks.map(({id}) => id);
       if (addedIds.length) {
         setTrackIds((prevTrackIds) => prevTrackIds.concat(addedIds));
       }
@@ -101,7 +94,6 @@ function useTracks(userSettings: UserSettings) {
 
       const invalidIds = difference(newIds, addedIds);
       const invalidPaths = invalidIds.map((id) => newPaths[newIds.indexOf(id)]);
-      addToWaitingIds(invalidIds);
 
       return {existingIds, invalidPaths};
     } catch (err) {
@@ -112,6 +104,11 @@ function useTracks(userSettings: UserSettings) {
     }
   });
 
+  // add the tracks already added in the backend (e.g. by a script)
+  const addTrackIds = useEvent((ids: number[]) => {
+    setTrackIds((prevTrackIds) => prevTrackIds.concat(difference(ids, prevTrackIds)));
+  });
+
   const ignoreError = useEvent((erroredId: number) => {
     setErroredTrackIds((prevErroredTrackIds) => difference(prevErroredTrackIds, [erroredId]));
   });
@@ -121,8 +118,6 @@ function useTracks(userSettings: UserSettings) {
       BackendAPI.removeTracks(ids);
       setTrackIds((prevTrackIds) => difference(prevTrackIds, ids));
       setErroredTrackIds((prevErroredTrackIds) => difference(prevErroredTrackIds, ids));
-
-      addToWaitingIds(ids);
     } catch (err) {
       console.error("Could not remove track", err);
       alert("Could not remove track");
@@ -178,6 +173,14 @@ function useTracks(userSettings: UserSettings) {
     setNeedRefreshTrackIdChArr(Array.from(trackIdChMap.values()).flat());
   });
 
+  const setDisplayTilt = useEvent(async (v: number) => {
+    await BackendAPI.setDisplayTilt(v);
+    const displayTilt = await BackendAPI.getDisplayTilt();
+    setCurrentDisplayTilt(displayTilt);
+    setUserSetting("displayTilt", displayTilt);
+    setNeedRefreshTrackIdChArr(Array.from(trackIdChMap.values()).flat());
+  });
+
   const setCommonGuardClipping = useEvent(async (v: GuardClippingMode) => {
     await BackendAPI.setCommonGuardClipping(v);
     setNeedRefreshTrackIdChArr(Array.from(trackIdChMap.values()).flat());
@@ -209,11 +212,13 @@ function useTracks(userSettings: UserSettings) {
     specSetting: currentSpecSetting,
     blend,
     dBRange: currentdBRange,
+    displayTilt: currentDisplayTilt,
     commonNormalize: currentCommonNormalize,
     commonGuardClipping: currentCommonGuardClipping,
     reloadTracks,
     refreshTracks,
     addTracks,
+    addTrackIds,
     removeTracks,
     hideTracks,
     changeTrackOrder,
@@ -222,6 +227,7 @@ function useTracks(userSettings: UserSettings) {
     setSpecSetting,
     setBlend: setBlendAndSetUserSetting,
     setdBRange,
+    setDisplayTilt,
     setCommonNormalize,
     setCommonGuardClipping,
     finishRefreshTracks,
@@ -8,8 +8,13 @@ import {setUserSetting} from "./lib/ipc-sender";
 const container = document.getElementById("root") as HTMLElement;
 const root = createRoot(container);
 
-ipcRenderer.once("render-with-settings", (_, settings) => {
-  const userSettingsOrInitialValues = BackendAPI.init(settings);
+// registered before init not to miss the files to open. The App takes them after rendering.
+ipcRenderer.on("open-files", (_, filePaths: string[]) => BackendAPI.queueOpenFiles(filePaths));
+
+ipcRenderer.once("render-with-settings", (_, settings, settingsPath: string) => {
+  BackendAPI.setSettingsStorePath(settingsPath);
+  window.addEventListener("beforeunload", () => BackendAPI.flushUserSettings());
+  const userSettingsOrInitialValues = BackendAPI.init(BackendAPI.migrateUserSettings(settings));
   Object.entries(userSettingsOrInitialValues).forEach(([key, value]) =>
     setUserSetting(key as keyof UserSettings, value),
   );
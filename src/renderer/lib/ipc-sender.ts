@@ -1,5 +1,6 @@
-import {UserSettings} from "backend";
+import {ClipboardContentKind, UserSettings} from "backend";
 import {ipcRenderer} from "electron";
+import BackendAPI from "../api";
 
 export function notifyAppRendered() {
   ipcRenderer.send("app-rendered");
@@ -9,13 +10,20 @@ export function setUserSetting<K extends keyof UserSettings>(
   key: K,
   value: NonNullable<UserSettings[K]>,
 ) {
-  ipcRenderer.send("set-setting", key, value);
+  // written to the store file by the backend (debounced)
+  BackendAPI.setUserSetting(key, value);
 }
 
 export function showElectronOpenDialog() {
   ipcRenderer.send("show-open-dialog");
 }
 
+/** copy the global selection of the track to the clipboard as an image and/or an audio file */
+export async function copySelectionToClipboard(trackId: number, kinds: ClipboardContentKind[]) {
+  const {imagePath, audioPath} = await BackendAPI.exportSelectionForClipboard(trackId, kinds);
+  ipcRenderer.send("copy-files-to-clipboard", imagePath ?? null, audioPath ?? null);
+}
+
 export function showTrackContextMenu() {
   ipcRenderer.send("show-track-context-menu");
 }
@@ -1,11 +1,21 @@
-import React, {forwardRef, useRef, useImperativeHandle, useState, useContext, useMemo} from "react";
+import React, {
+  forwardRef,
+  useRef,
+  useImperativeHandle,
+  useState,
+  useContext,
+  useMemo,
+  useEffect,
+} from "react";
 import useEvent from "react-use-event-hook";
 import {throttle} from "throttle-debounce";
+import {SpecProgress} from "backend";
 import {DevicePixelRatioContext} from "renderer/contexts";
 import styles from "./ImgCanvas.module.scss";
 import BackendAPI from "../api";
 
 type ImgCanvasProps = {
+  idChStr: string;
   width: number;
   height: number;
   maxTrackSec: number;
@@ -19,10 +29,11 @@ const calcTooltipPos = (e: React.MouseEvent) => {
 };
 
 const ImgCanvas = forwardRef((props: ImgCanvasProps, ref) => {
-  const {width, height, maxTrackSec, canvasIsFit} = props;
+  const {idChStr, width, height, maxTrackSec, canvasIsFit} = props;
   const devicePixelRatio = useContext(DevicePixelRatioContext);
   const canvasElem = useRef<HTMLCanvasElement>(null);
   const loadingElem = useRef<HTMLDivElement>(null);
+  const specProgressElem = useRef<HTMLDivElement>(null);
   const startSecRef = useRef<number>(0);
   const pxPerSecRef = useRef<number>(1);
   const tooltipElem = useRef<HTMLSpanElement>(null);
@@ -58,6 +69,19 @@ const ImgCanvas = forwardRef((props: ImgCanvasProps, ref) => {
     if (loadingElem.current) loadingElem.current.style.display = "block";
   });
 
+  // shimmer while the spec of this channel is being calculated (spec-progress from App)
+  useEffect(() => {
+    const onSpecProgress = (e: Event) => {
+      const progress = (e as CustomEvent<SpecProgress[]>).detail.find((x) => x.idCh === idChStr);
+      const elem = specProgressElem.current;
+      if (!elem) return;
+      elem.style.display = progress ? "block" : "none";
+      if (progress) elem.style.setProperty("--fraction", `${progress.fraction}`);
+    };
+    window.addEventListener("spec-progress", onSpecProgress);
+    return () => window.removeEventListener("spec-progress", onSpecProgress);
+  }, [idChStr]);
+
   const updateLensParams = useEvent((params: OptionalLensParams) => {
     startSecRef.current = params.startSec ?? startSecRef.current;
     pxPerSecRef.current = params.pxPerSec ?? pxPerSecRef.current;
@@ -126,6 +150,7 @@ const ImgCanvas = forwardRef((props: ImgCanvasProps, ref) => {
         </span>
       ) : null}
       <div ref={loadingElem} className={styles.loading} style={{display: "none"}} />
+      <div ref={specProgressElem} className={styles.specProgress} style={{display: "none"}} />
       <canvas
         className={styles.ImgCanvas}
         ref={canvasElem}
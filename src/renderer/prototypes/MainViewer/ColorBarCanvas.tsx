@@ -1,4 +1,4 @@
-import React, {useMemo, useRef, useCallback, useEffect, useContext} from "react";
+import React, {useState, useRef, useCallback, useEffect, useContext} from "react";
 import {chunk} from "renderer/utils/arrayUtils";
 import {COLORBAR_CANVAS_WIDTH} from "renderer/prototypes/constants/tracks";
 import {DevicePixelRatioContext} from "renderer/contexts";
@@ -11,6 +11,7 @@ type ColorBarCanvasProps = {
 };
 
 const COLORBAR_CENTER = COLORBAR_CANVAS_WIDTH / 2;
+const MAX_COLORMAP_LENGTH = 8192;
 
 function ColorBarCanvas(props: ColorBarCanvasProps) {
   const {width, height} = props;
@@ -18,10 +19,22 @@ function ColorBarCanvas(props: ColorBarCanvasProps) {
   const canvasElem = useRef<HTMLCanvasElement>(null);
   const ctxRef = useRef<CanvasRenderingContext2D | null>(null);
 
-  const colorBarGradientBuf = useMemo(
-    () => chunk([...new Uint8Array(BackendAPI.getColorMap())], 3).reverse(),
-    [],
+  const [colorBarGradientBuf, setColorBarGradientBuf] = useState<number[][]>([]);
+
+  // one color per device pixel. renegotiated when the height or the display scaling changes
+  const colormapLength = Math.min(
+    Math.max(Math.round(height * devicePixelRatio), 2),
+    MAX_COLORMAP_LENGTH,
   );
+  useEffect(() => {
+    let cancelled = false;
+    BackendAPI.setColormapLength(colormapLength).then((buf) => {
+      if (!cancelled) setColorBarGradientBuf(chunk([...new Uint8Array(buf)], 3).reverse());
+    });
+    return () => {
+      cancelled = true;
+    };
+  }, [colormapLength]);
 
   const draw = useCallback(() => {
     const ctx = ctxRef.current;
@@ -936,6 +936,7 @@ function MainViewer(props: MainViewerProps) {
               >
                 <ImgCanvas
                   ref={registerImgCanvas(idChStr)}
+                  idChStr={idChStr}
                   width={width}
                   height={imgHeight}
                   maxTrackSec={maxTrackSec}
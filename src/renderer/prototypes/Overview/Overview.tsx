@@ -122,7 +122,13 @@ const Overview = forwardRef((props: OverviewProps, ref) => {
     ) {
       let imbmp = null;
       if (width >= 1) {
-        const buf = await BackendAPI.getOverview(selectedTrackId, width, height, devicePixelRatio);
+        const {isStale, buf} = await BackendAPI.getOverview(
+          selectedTrackId,
+          width,
+          height,
+          devicePixelRatio,
+        );
+        if (isStale) return;
         if (buf.length === width * height * 4) {
           const imdata = new ImageData(new Uint8ClampedArray(buf), width, height);
           imbmp = await createImageBitmap(imdata);
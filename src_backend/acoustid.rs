@@ -0,0 +1,243 @@
+//! Opt-in online lookup of the title and the artist of unlabeled files via AcoustID
+//! (https://acoustid.org). The fingerprint is calculated by fpcalc of Chromaprint,
+//! which is bundled with the app (assets/bin of the resources) or found in PATH.
+//! The lookup is disabled until the API key of the user is set.
+
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use identity_hash::IntMap;
+use napi_derive::napi;
+use parking_lot::{Mutex, RwLock};
+use serde_json::Value;
+
+const LOOKUP_URL: &str = "https://api.acoustid.org/v2/lookup";
+const FPCALC: &str = "fpcalc";
+
+/// client API key of AcoustID. None if the lookup is disabled.
+static API_KEY: RwLock<Option<String>> = RwLock::new(None);
+/// identities of the tracks found by the lookup
+static IDENTITIES: Mutex<Option<IntMap<usize, TrackIdentity>>> = Mutex::new(None);
+
+/// metadata of the recording matched by AcoustID
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackIdentity {
+    pub title: Option<String>,
+    /// artists joined by ", "
+    pub artist: Option<String>,
+    /// MusicBrainz recording id
+    pub recording_id: String,
+    /// confidence of the match (0 ~ 1)
+    pub score: f64,
+}
+
+pub fn set_api_key(key: Option<String>) {
+    *API_KEY.write() = key.filter(|key| !key.trim().is_empty());
+}
+
+#[inline]
+pub fn is_enabled() -> bool {
+    API_KEY.read().is_some()
+}
+
+pub fn identity(id: usize) -> Option<TrackIdentity> {
+    IDENTITIES.lock().as_ref()?.get(&id).cloned()
+}
+
+pub fn set_identity(id: usize, identity: TrackIdentity) {
+    IDENTITIES
+        .lock()
+        .get_or_insert_with(Default::default)
+        .insert(id, identity);
+}
+
+/// forget the identities of the removed tracks
+pub fn remove_identities(ids: &[usize]) {
+    if let Some(identities) = IDENTITIES.lock().as_mut() {
+        ids.iter().for_each(|id| {
+            identities.remove(id);
+        });
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FingerprintError {
+    /// fpcalc is neither bundled nor in PATH
+    FpcalcNotFound,
+    Failed(String),
+}
+
+impl fmt::Display for FingerprintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FingerprintError::FpcalcNotFound => write!(
+                f,
+                "{} of Chromaprint is not found. \
+                Install Chromaprint (https://acoustid.org/chromaprint) to identify tracks.",
+                FPCALC
+            ),
+            FingerprintError::Failed(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// directories of the bundled fpcalc: next to the executable and assets/bin of the resources
+/// (the resources are next to the executable, or ../Resources on macOS)
+fn bundled_dirs() -> Vec<PathBuf> {
+    let Some(exe_dir) = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    else {
+        return Vec::new();
+    };
+    vec![
+        exe_dir.join("resources/assets/bin"),
+        exe_dir.join("../Resources/assets/bin"),
+        exe_dir,
+    ]
+}
+
+/// path of the bundled fpcalc or fpcalc in PATH. None if not found.
+pub fn find_fpcalc() -> Option<PathBuf> {
+    let name = format!("{}{}", FPCALC, env::consts::EXE_SUFFIX);
+    let path_dirs: Vec<_> = env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).collect())
+        .unwrap_or_default();
+    bundled_dirs()
+        .into_iter()
+        .chain(path_dirs)
+        .map(|dir| dir.join(&name))
+        .find(|path| path.is_file())
+}
+
+/// (duration (sec), fingerprint) calculated by fpcalc. Should be called in a blocking thread.
+pub fn fingerprint(path: &Path) -> Result<(f64, String), FingerprintError> {
+    let fpcalc = find_fpcalc().ok_or(FingerprintError::FpcalcNotFound)?;
+    let output = Command::new(fpcalc)
+        .arg("-json")
+        .arg(path)
+        .output()
+        .map_err(|e| {
+            FingerprintError::Failed(format!("Failed to run {} of Chromaprint: {}", FPCALC, e))
+        })?;
+    if !output.status.success() {
+        let msg = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(FingerprintError::Failed(msg));
+    }
+    let value: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| FingerprintError::Failed(e.to_string()))?;
+    match (value["duration"].as_f64(), value["fingerprint"].as_str()) {
+        (Some(duration), Some(fingerprint)) => Ok((duration, fingerprint.to_string())),
+        _ => Err(FingerprintError::Failed(format!(
+            "Invalid output of {}.",
+            FPCALC
+        ))),
+    }
+}
+
+/// Query the recording of the fingerprint. None if no recording matches.
+/// Err if the lookup is disabled or failed (e.g. offline).
+/// Should be called in a blocking thread.
+pub fn lookup(duration: f64, fingerprint: &str) -> Result<Option<TrackIdentity>, String> {
+    let Some(api_key) = API_KEY.read().clone() else {
+        return Err("The AcoustID API key is not set.".into());
+    };
+    let duration = format!("{}", duration.round() as u64);
+    let response = ureq::post(LOOKUP_URL).send_form(&[
+        ("client", api_key.as_str()),
+        ("meta", "recordings"),
+        ("duration", duration.as_str()),
+        ("fingerprint", fingerprint),
+    ]);
+    let body = match response {
+        Ok(response) => response.into_string(),
+        // the error message of AcoustID is in the body
+        Err(ureq::Error::Status(_, response)) => response.into_string(),
+        Err(e) => return Err(e.to_string()),
+    }
+    .map_err(|e| e.to_string())?;
+    let value: Value = serde_json::from_str(&body).map_err(|e| e.to_string())?;
+    parse_lookup_response(&value)
+}
+
+/// the recording of the best result with the metadata
+fn parse_lookup_response(value: &Value) -> Result<Option<TrackIdentity>, String> {
+    if value["status"] != "ok" {
+        let msg = value["error"]["message"]
+            .as_str()
+            .unwrap_or("Unknown error");
+        return Err(format!("AcoustID: {}", msg));
+    }
+    let Some(results) = value["results"].as_array() else {
+        return Ok(None);
+    };
+    let best = results
+        .iter()
+        .filter_map(|result| {
+            let recording = result["recordings"]
+                .as_array()?
+                .iter()
+                .find(|recording| recording["title"].is_string())?;
+            Some((result["score"].as_f64().unwrap_or(0.), recording))
+        })
+        .max_by(|(a, _), (b, _)| a.total_cmp(b));
+    Ok(best.and_then(|(score, recording)| {
+        let artists: Vec<_> = recording["artists"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|artist| artist["name"].as_str())
+            .collect();
+        Some(TrackIdentity {
+            title: recording["title"].as_str().map(Into::into),
+            artist: (!artists.is_empty()).then(|| artists.join(", ")),
+            recording_id: recording["id"].as_str()?.into(),
+            score,
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parse_lookup_response_works() {
+        let value = json!({
+            "status": "ok",
+            "results": [
+                {"id": "a", "score": 0.5, "recordings": [{"id": "r0"}]},
+                {"id": "b", "score": 0.9, "recordings": [{
+                    "id": "r1",
+                    "title": "Song",
+                    "artists": [{"name": "A"}, {"name": "B"}],
+                }]},
+                {"id": "c", "score": 0.7, "recordings": [{"id": "r2", "title": "Other"}]},
+            ],
+        });
+        assert_eq!(
+            parse_lookup_response(&value),
+            Ok(Some(TrackIdentity {
+                title: Some("Song".into()),
+                artist: Some("A, B".into()),
+                recording_id: "r1".into(),
+                score: 0.9,
+            }))
+        );
+        assert_eq!(
+            parse_lookup_response(&json!({"status": "ok", "results": []})),
+            Ok(None)
+        );
+        assert_eq!(
+            parse_lookup_response(
+                &json!({"status": "error", "error": {"message": "invalid API key"}})
+            ),
+            Err("AcoustID: invalid API key".into())
+        );
+    }
+}
@@ -6,16 +6,19 @@ use kittyaudio::Frame;
 use napi_derive::napi;
 use ndarray::prelude::*;
 use rayon::prelude::*;
+use sha2::{Digest, Sha256};
 use symphonia::core::audio::GenericAudioBufferRef;
 use symphonia::core::codecs::audio::AudioCodecParameters;
 use symphonia::core::errors::Error as SymphoniaError;
-use symphonia::core::formats::{probe::Hint, Track as SymphoniaTrack};
+use symphonia::core::formats::{probe::Hint, FormatReader, Track as SymphoniaTrack};
 use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataRevision, StandardTag};
 
 use super::dynamics::{
     AudioStats, GuardClipping, GuardClippingMode, GuardClippingResult, GuardClippingStats,
     LimiterManager, MaxPeak, StatCalculator,
 };
+use super::visualize::FftResampler;
 
 #[readonly::make]
 #[derive(PartialEq, Clone)]
@@ -134,21 +137,55 @@ impl GuardClipping<Ix2> for Audio {
 }
 
 impl From<&Audio> for Vec<Frame> {
+    #[inline]
     fn from(value: &Audio) -> Self {
-        value
-            .view()
-            .axis_iter(Axis(1))
-            .map(|frame| {
-                match frame.len() {
-                    1 => frame[0].into(),
-                    2 => (frame[0], frame[1]).into(),
-                    _ => unimplemented!(), // TODO
-                }
-            })
-            .collect()
+        wavs_to_frames(value.view())
     }
 }
 
+pub fn wavs_to_frames(wavs: ArrayView2<f32>) -> Vec<Frame> {
+    wavs.axis_iter(Axis(1))
+        .map(|frame| {
+            match frame.len() {
+                1 => frame[0].into(),
+                2 => (frame[0], frame[1]).into(),
+                _ => unimplemented!(), // TODO
+            }
+        })
+        .collect()
+}
+
+/// resample the whole wav from sr to target_sr at once
+pub fn resample_to(wav: ArrayView1<f32>, sr: u32, target_sr: u32) -> Array1<f32> {
+    if sr == target_sr || wav.is_empty() {
+        return wav.to_owned();
+    }
+    let output_len = ((wav.len() as f64 * target_sr as f64 / sr as f64).round() as usize).max(1);
+    FftResampler::new(wav.len(), output_len)
+        .resample(wav)
+        .to_owned()
+}
+
+/// SHA-256 (hex) of the decoded PCM for checking if two files decode to bit-identical audio.
+/// The sample rate and the number of channels (u32 LE) are hashed first,
+/// followed by the interleaved f32 LE samples.
+pub fn calc_pcm_hash(wavs: ArrayView2<f32>, sr: u32) -> String {
+    const BUF_SIZE: usize = 64 * 1024;
+    let mut hasher = Sha256::new();
+    hasher.update(sr.to_le_bytes());
+    hasher.update((wavs.shape()[0] as u32).to_le_bytes());
+    let mut buf = Vec::with_capacity(BUF_SIZE + wavs.shape()[0] * 4);
+    for frame in wavs.axis_iter(Axis(1)) {
+        buf.extend(frame.iter().flat_map(|x| x.to_le_bytes()));
+        if buf.len() >= BUF_SIZE {
+            hasher.update(&buf);
+            buf.clear();
+        }
+    }
+    hasher.update(&buf);
+    format!("{:x}", hasher.finalize())
+}
+
 #[napi(object)]
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct AudioFormatInfo {
@@ -157,6 +194,14 @@ pub struct AudioFormatInfo {
     pub sr: u32,
     pub bit_depth: String,
     pub bitrate: String,
+    /// container format (e.g. "wav", "mp4", "mkv"). "raw" for headerless PCM files
+    pub container: String,
+    /// number of the streams other than audio (e.g. video) in the container, which are ignored
+    pub n_ignored_streams: u32,
+    /// album tag of the file
+    pub album: Option<String>,
+    /// recording (or release) date tag of the file as written (e.g. "2024" or "2024-05-01")
+    pub date: Option<String>,
 }
 
 impl AudioFormatInfo {
@@ -168,12 +213,13 @@ impl AudioFormatInfo {
         found_sample_format: &str,
         total_packets_byte: usize,
         decoded_wav_len: usize,
+        n_ignored_streams: u32,
     ) -> Self {
-        let format_name = format_name.replace("wave", "wav");
-        let name = if format_name == codec_name {
-            format_name
+        let container = container_name(format_name);
+        let name = if container == codec_name {
+            container.clone()
         } else {
-            format!("{} - {}", format_name, codec_name)
+            format!("{} - {}", container, codec_name)
         };
         if codec_name == "alac" {
             return Self {
@@ -181,12 +227,17 @@ impl AudioFormatInfo {
                 sr: found_sr,
                 bit_depth: found_sample_format.into(),
                 bitrate: "".into(),
+                container,
+                n_ignored_streams,
+                ..Default::default()
             };
         }
         if name.starts_with("wav") {
             return Self {
                 name,
                 sr: found_sr,
+                container,
+                n_ignored_streams,
                 ..Default::default()
             };
         }
@@ -213,11 +264,92 @@ impl AudioFormatInfo {
             sr: found_sr,
             bit_depth,
             bitrate,
+            container,
+            n_ignored_streams,
+            ..Default::default()
         }
     }
 }
 
-pub fn open_audio_file(path: &str) -> Result<(Array2<f32>, AudioFormatInfo), SymphoniaError> {
+/// album and date read from the metadata of the file
+#[derive(Default)]
+struct AudioTags {
+    album: Option<String>,
+    date: Option<String>,
+}
+
+impl AudioTags {
+    /// Update the album and the date with the standard tags of the metadata revision.
+    /// The tags of the later revisions take precedence. The recording date is preferred
+    /// to the release date.
+    fn update(&mut self, rev: &MetadataRevision) {
+        let non_empty = |value: String| {
+            let value = value.trim();
+            (!value.is_empty()).then(|| value.to_string())
+        };
+        let mut release_date = None;
+        for tag in &rev.media.tags {
+            match &tag.std {
+                Some(StandardTag::Album(album)) => {
+                    self.album = non_empty(album.to_string()).or(self.album.take());
+                }
+                Some(StandardTag::RecordingDate(date)) => {
+                    self.date = non_empty(date.to_string()).or(self.date.take());
+                }
+                Some(StandardTag::ReleaseDate(date)) => {
+                    release_date = non_empty(date.to_string());
+                }
+                _ => {}
+            }
+        }
+        if self.date.is_none() {
+            self.date = release_date;
+        }
+    }
+}
+
+/// short name of the container format shown to the user
+fn container_name(short_name: &str) -> String {
+    match short_name {
+        "wave" => "wav",
+        "isomp4" => "mp4",
+        name => name,
+    }
+    .into()
+}
+
+/// number of the tracks other than audio (e.g. video, subtitles) in the container
+fn count_non_audio_tracks(format: &dyn FormatReader) -> u32 {
+    format
+        .tracks()
+        .iter()
+        .filter(|t| t.codec_params.as_ref().is_some_and(|p| p.audio().is_none()))
+        .count() as u32
+}
+
+/// format info read from the headers without decoding the audio
+#[napi(object)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProbedAudioFile {
+    pub path: String,
+    /// container format (e.g. "wav", "flac", "mp4")
+    pub format_name: String,
+    #[napi(js_name = "sampleRate")]
+    pub sr: u32,
+    pub channels: u32,
+    /// 0 if the headers don't tell the length
+    pub duration_sec: f64,
+    /// empty if the headers don't tell the bit depth
+    pub bit_depth: String,
+    /// empty if probed successfully
+    pub err: String,
+}
+
+/// Probe the format of the file and find the first audio track with a known codec.
+/// The other tracks (e.g. video of mp4, mov, mkv, webm) are ignored.
+fn probe_audio_track(
+    path: &str,
+) -> Result<(Box<dyn FormatReader>, SymphoniaTrack), SymphoniaError> {
     let src = std::fs::File::open(path)?;
 
     // Create the media source stream.
@@ -239,14 +371,7 @@ pub fn open_audio_file(path: &str) -> Result<(Array2<f32>, AudioFormatInfo), Sym
     )?;
 
     // Find the first audio track with a known (decodeable) codec.
-    let SymphoniaTrack {
-        id: track_id,
-        codec_params,
-        language: _,
-        time_base,
-        num_frames,
-        ..
-    } = format
+    let track = format
         .tracks()
         .iter()
         .find(|t| t.codec_params.as_ref().is_some_and(|p| p.audio().is_some()))
@@ -257,6 +382,71 @@ pub fn open_audio_file(path: &str) -> Result<(Array2<f32>, AudioFormatInfo), Sym
             ))
         })?
         .clone();
+    Ok((format, track))
+}
+
+/// Read the format info from the headers only (much faster than open_audio_file).
+pub fn probe_audio_file(path: &str) -> Result<ProbedAudioFile, SymphoniaError> {
+    let (format, track) = probe_audio_track(path)?;
+    let codec_params = track.codec_params.as_ref().unwrap().audio().unwrap();
+    let duration_sec = match (track.time_base, track.num_frames) {
+        (Some(tb), Some(nf)) => {
+            let duration = tb.calc_time(nf);
+            duration.seconds as f64 + duration.frac
+        }
+        _ => 0.,
+    };
+    Ok(ProbedAudioFile {
+        path: path.into(),
+        format_name: container_name(format.format_info().short_name),
+        sr: codec_params.sample_rate.unwrap_or_default(),
+        channels: codec_params.channels.as_ref().map_or(0, |c| c.count()) as u32,
+        duration_sec,
+        bit_depth: codec_params
+            .bits_per_sample
+            .map_or_else(String::new, |bits| format!("{} bit", bits)),
+        err: String::new(),
+    })
+}
+
+/// time range of the audio which failed to decode (e.g. corrupted packets).
+/// The range is filled with silence. end_sec == start_sec if the length is unknown.
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodeGap {
+    pub start_sec: f64,
+    pub end_sec: f64,
+}
+
+/// append the sample range [start, end) to gaps (merged with the last gap if adjacent)
+fn push_gap(gaps: &mut Vec<(usize, usize)>, start: usize, end: usize) {
+    match gaps.last_mut() {
+        Some(last) if last.1 == start => last.1 = end,
+        _ => gaps.push((start, end)),
+    }
+}
+
+pub fn open_audio_file(path: &str) -> Result<(Array2<f32>, AudioFormatInfo), SymphoniaError> {
+    open_audio_file_with_gaps(path).map(|(wavs, format_info, _)| (wavs, format_info))
+}
+
+/// The packets failed to decode are replaced with silence of the length of the previous packet
+/// (or the max packet length of the codec before any good packet) instead of being dropped,
+/// so that the timing of the rest is kept.
+/// If the stream can't be read anymore (e.g. truncated), the rest is filled with silence up to
+/// the duration in the header. Returns the silent ranges as DecodeGaps.
+pub fn open_audio_file_with_gaps(
+    path: &str,
+) -> Result<(Array2<f32>, AudioFormatInfo, Vec<DecodeGap>), SymphoniaError> {
+    let (mut format, track) = probe_audio_track(path)?;
+    let SymphoniaTrack {
+        id: track_id,
+        codec_params,
+        language: _,
+        time_base,
+        num_frames,
+        ..
+    } = track;
     let codec_params = codec_params.as_ref().unwrap().audio().unwrap();
     let mut n_ch = codec_params.channels.as_ref().map_or(0, |c| c.count());
     let mut sr = codec_params.sample_rate.unwrap_or_default();
@@ -274,6 +464,17 @@ pub fn open_audio_file(path: &str) -> Result<(Array2<f32>, AudioFormatInfo), Sym
     let mut planes = vec![Vec::with_capacity(n_samples); n_ch];
     let mut found_sample_format = "";
     let mut total_packets_byte = 0;
+    // sample ranges filled with silence
+    let mut gaps = Vec::new();
+    // length of the previous packet. The max length of the codec until the first good packet.
+    let mut last_packet_len = codec_params
+        .max_frames_per_packet
+        .map_or(0, |n_frames| n_frames as usize);
+    // tags read before the first packet (e.g. ID3v2 or the header of the container)
+    let mut tags = AudioTags::default();
+    if let Some(rev) = format.metadata().current() {
+        tags.update(rev);
+    }
     // The decode loop.
     loop {
         // Get the next packet from the media format.
@@ -291,8 +492,13 @@ pub fn open_audio_file(path: &str) -> Result<(Array2<f32>, AudioFormatInfo), Sym
                 unimplemented!();
             }
             Err(err) => {
-                // A unrecoverable error occurred, halt decoding.
-                panic!("{}", err);
+                // The rest of the stream can't be read (e.g. truncated file), halt decoding.
+                println!("[Warning] Stopped reading the audio file: {}", err);
+                let len = planes.first().map_or(0, Vec::len);
+                let end = n_samples.max(len);
+                planes.iter_mut().for_each(|plane| plane.resize(end, 0.));
+                push_gap(&mut gaps, len, end);
+                break;
             }
         };
 
@@ -302,6 +508,9 @@ pub fn open_audio_file(path: &str) -> Result<(Array2<f32>, AudioFormatInfo), Sym
             format.metadata().pop();
 
             // Consume the new metadata at the head of the metadata queue.
+            if let Some(rev) = format.metadata().current() {
+                tags.update(rev);
+            }
         }
 
         // If the packet does not belong to the selected track, skip over it.
@@ -341,17 +550,20 @@ pub fn open_audio_file(path: &str) -> Result<(Array2<f32>, AudioFormatInfo), Sym
                     .collect();
                 _decoded.copy_to_slice_planar(&mut slices);
                 total_packets_byte += packet.buf().len();
+                last_packet_len = _decoded.samples_planar();
             }
-            Err(SymphoniaError::IoError(_)) => {
-                // The packet failed to decode due to an IO error, skip the packet.
-                continue;
-            }
-            Err(SymphoniaError::DecodeError(err)) => {
-                // The packet failed to decode due to invalid data, skip the packet.
+            Err(err @ (SymphoniaError::IoError(_) | SymphoniaError::DecodeError(_))) => {
+                // The packet failed to decode due to an IO error or invalid data.
+                // Fill silence instead of the packet.
                 println!(
                     "[Warning] DecodeError by wrong packet of audio file: {}",
                     err
                 );
+                let len = planes.first().map_or(0, Vec::len);
+                planes
+                    .iter_mut()
+                    .for_each(|plane| plane.resize(len + last_packet_len, 0.));
+                push_gap(&mut gaps, len, len + last_packet_len);
                 continue;
             }
             Err(err) => {
@@ -376,21 +588,143 @@ pub fn open_audio_file(path: &str) -> Result<(Array2<f32>, AudioFormatInfo), Sym
     vec.truncate(shape.0 * shape.1); // defensive code
     let wavs = Array2::from_shape_vec(shape, vec).unwrap();
 
-    let format_info = AudioFormatInfo::from_decoding_result(
-        format.format_info().short_name,
-        decoder.codec_info().short_name,
-        codec_params,
-        sr,
-        found_sample_format,
-        total_packets_byte,
-        wavs.shape()[1],
-    );
+    let format_info = AudioFormatInfo {
+        album: tags.album,
+        date: tags.date,
+        ..AudioFormatInfo::from_decoding_result(
+            format.format_info().short_name,
+            decoder.codec_info().short_name,
+            codec_params,
+            sr,
+            found_sample_format,
+            total_packets_byte,
+            wavs.shape()[1],
+            count_non_audio_tracks(format.as_ref()),
+        )
+    };
+    let gaps = gaps
+        .into_iter()
+        .map(|(start, end)| DecodeGap {
+            start_sec: start as f64 / sr as f64,
+            end_sec: end as f64 / sr as f64,
+        })
+        .collect();
+    Ok((wavs, format_info, gaps))
+}
+
+/// sample format of headerless PCM files
+#[napi(string_enum)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RawSampleFormat {
+    U8,
+    S16LE,
+    S16BE,
+    S24LE,
+    S24BE,
+    S32LE,
+    S32BE,
+    F32LE,
+    F32BE,
+    F64LE,
+    F64BE,
+}
+
+impl RawSampleFormat {
+    pub fn n_bytes(self) -> usize {
+        match self {
+            RawSampleFormat::U8 => 1,
+            RawSampleFormat::S16LE | RawSampleFormat::S16BE => 2,
+            RawSampleFormat::S24LE | RawSampleFormat::S24BE => 3,
+            RawSampleFormat::S32LE
+            | RawSampleFormat::S32BE
+            | RawSampleFormat::F32LE
+            | RawSampleFormat::F32BE => 4,
+            RawSampleFormat::F64LE | RawSampleFormat::F64BE => 8,
+        }
+    }
+
+    /// decode a sample from bytes of length n_bytes()
+    fn decode(self, b: &[u8]) -> f32 {
+        match self {
+            RawSampleFormat::U8 => (b[0] as f32 - 128.) / 128.,
+            RawSampleFormat::S16LE => i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.,
+            RawSampleFormat::S16BE => i16::from_be_bytes([b[0], b[1]]) as f32 / 32768.,
+            // shift right after filling the lowest byte to extend the sign
+            RawSampleFormat::S24LE => {
+                (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8388608.
+            }
+            RawSampleFormat::S24BE => {
+                (i32::from_be_bytes([b[0], b[1], b[2], 0]) >> 8) as f32 / 8388608.
+            }
+            RawSampleFormat::S32LE => {
+                (i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64 / 2147483648.) as f32
+            }
+            RawSampleFormat::S32BE => {
+                (i32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64 / 2147483648.) as f32
+            }
+            RawSampleFormat::F32LE => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            RawSampleFormat::F32BE => f32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+            RawSampleFormat::F64LE => f64::from_le_bytes(b[..8].try_into().unwrap()) as f32,
+            RawSampleFormat::F64BE => f64::from_be_bytes(b[..8].try_into().unwrap()) as f32,
+        }
+    }
+}
+
+/// how to interpret a headerless PCM file
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RawPcmParams {
+    pub sr: u32,
+    pub n_ch: usize,
+    pub sample_format: RawSampleFormat,
+    /// number of bytes to skip at the beginning of the file (e.g. unknown header)
+    pub offset: usize,
+}
+
+/// Decode a headerless PCM file of interleaved samples.
+/// Trailing bytes not filling a whole frame are ignored.
+pub fn open_raw_pcm_file(
+    path: &str,
+    params: &RawPcmParams,
+) -> Result<(Array2<f32>, AudioFormatInfo), SymphoniaError> {
+    let invalid = |kind, msg| SymphoniaError::IoError(io::Error::new(kind, msg));
+    if params.sr == 0 || params.n_ch == 0 {
+        return Err(invalid(
+            io::ErrorKind::InvalidInput,
+            "sample rate and number of channels should be positive",
+        ));
+    }
+    let bytes = std::fs::read(path)?;
+    let data = bytes
+        .get(params.offset..)
+        .ok_or_else(|| invalid(io::ErrorKind::InvalidInput, "offset exceeds the file size"))?;
+    let n_bytes = params.sample_format.n_bytes();
+    let n_frames = data.len() / (n_bytes * params.n_ch);
+    if n_frames == 0 {
+        return Err(invalid(
+            io::ErrorKind::InvalidData,
+            "no audio samples found",
+        ));
+    }
+    let wavs = Array2::from_shape_fn((params.n_ch, n_frames), |(ch, i)| {
+        let start = (i * params.n_ch + ch) * n_bytes;
+        params.sample_format.decode(&data[start..start + n_bytes])
+    });
+    let format_info = AudioFormatInfo {
+        name: "raw".into(),
+        sr: params.sr,
+        bit_depth: format!("{:?}", params.sample_format),
+        bitrate: "".into(),
+        container: "raw".into(),
+        n_ignored_streams: 0,
+        ..Default::default()
+    };
     Ok((wavs, format_info))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use approx::assert_abs_diff_eq;
     use itertools::Itertools;
 
     #[test]
@@ -405,12 +739,18 @@ mod tests {
                 sr: 48000,
                 bit_depth: "".into(),
                 bitrate: "".into(),
+                container: "wav".into(),
+                n_ignored_streams: 0,
+                ..Default::default()
             },
             AudioFormatInfo {
                 name: "wav - pcm_s16le".into(),
                 sr: 48000,
                 bit_depth: "".into(),
                 bitrate: "".into(),
+                container: "wav".into(),
+                n_ignored_streams: 0,
+                ..Default::default()
             },
         ];
         for (path, format_info_answer) in paths.into_iter().zip(format_infos.into_iter()) {
@@ -440,4 +780,174 @@ mod tests {
             assert_eq!(format_info, format_info_answer);
         }
     }
+
+    /// copy of the sample in the temp dir modified by modify
+    fn modified_copy(src: &str, name: &str, modify: impl FnOnce(&mut Vec<u8>)) -> String {
+        let mut bytes = std::fs::read(src).unwrap();
+        modify(&mut bytes);
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path.to_str().unwrap().into()
+    }
+
+    /// byte offset of the first frame of the FLAC file (right after the metadata blocks)
+    fn flac_first_frame(bytes: &[u8]) -> usize {
+        let mut pos = 4;
+        loop {
+            let is_last = bytes[pos] & 0x80 != 0;
+            let len = u32::from_be_bytes([0, bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]]);
+            pos += 4 + len as usize;
+            if is_last {
+                return pos;
+            }
+        }
+    }
+
+    #[test]
+    fn decode_gaps_works() {
+        let (_, _, gaps) = open_audio_file_with_gaps("samples/sample_48k.wav").unwrap();
+        assert!(gaps.is_empty());
+        let (wavs, _, gaps) = open_audio_file_with_gaps("samples/sample_44k1.flac").unwrap();
+        assert!(gaps.is_empty());
+        let flac_len = wavs.shape()[1];
+
+        // truncated file: the rest is filled with silence up to the duration in the header
+        let path = modified_copy(
+            "samples/sample_48k.wav",
+            "thesia_decode_gaps_truncated.wav",
+            |bytes| bytes.truncate(bytes.len() / 2 + 1),
+        );
+        let (wavs, _, gaps) = open_audio_file_with_gaps(&path).unwrap();
+        assert_eq!(wavs.shape(), &[1, 2113529]);
+        assert_eq!(gaps.len(), 1);
+        assert!(gaps[0].start_sec > 0. && gaps[0].start_sec < 2113529. / 48000.);
+        assert_abs_diff_eq!(gaps[0].end_sec, 2113529. / 48000., epsilon = 1e-9);
+        std::fs::remove_file(&path).unwrap();
+
+        // corrupted packet in the middle: replaced with the silence of the previous packet length
+        let path = modified_copy(
+            "samples/sample_44k1.flac",
+            "thesia_decode_gaps_corrupted.flac",
+            |bytes| {
+                let mid = bytes.len() / 2;
+                bytes[mid..mid + 8].iter_mut().for_each(|x| *x = !*x);
+            },
+        );
+        let (wavs, _, gaps) = open_audio_file_with_gaps(&path).unwrap();
+        assert_eq!(wavs.shape()[1], flac_len);
+        assert_eq!(gaps.len(), 1);
+        assert!(gaps[0].start_sec > 0. && gaps[0].end_sec > gaps[0].start_sec);
+        std::fs::remove_file(&path).unwrap();
+
+        // corrupted first packet: replaced with the silence of the max packet length
+        let path = modified_copy(
+            "samples/sample_44k1.flac",
+            "thesia_decode_gaps_first_corrupted.flac",
+            |bytes| {
+                let pos = flac_first_frame(bytes) + 500;
+                bytes[pos..pos + 8].iter_mut().for_each(|x| *x = !*x);
+            },
+        );
+        let (wavs, _, gaps) = open_audio_file_with_gaps(&path).unwrap();
+        assert_eq!(wavs.shape()[1], flac_len);
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].start_sec, 0.);
+        assert_abs_diff_eq!(gaps[0].end_sec, 4608. / 44100., epsilon = 1e-9);
+        std::fs::remove_file(&path).unwrap();
+
+        let mut gaps = Vec::new();
+        push_gap(&mut gaps, 0, 1152);
+        push_gap(&mut gaps, 1152, 2304);
+        push_gap(&mut gaps, 4608, 5760);
+        assert_eq!(gaps, vec![(0, 2304), (4608, 5760)]);
+    }
+
+    #[test]
+    fn probe_audio_works() {
+        let probed = probe_audio_file("samples/sample_48k_wav_no_extension").unwrap();
+        assert_eq!(probed.format_name, "wav");
+        assert_eq!(container_name("isomp4"), "mp4");
+        assert_eq!((probed.sr, probed.channels), (48000, 1));
+        assert_abs_diff_eq!(probed.duration_sec, 2113529. / 48000., epsilon = 1e-9);
+        assert!(probed.err.is_empty());
+        assert!(probe_audio_file("samples/not_exist.wav").is_err());
+    }
+
+    #[test]
+    fn pcm_hash_works() {
+        let (wavs, _) = open_audio_file("samples/sample_48k.wav").unwrap();
+        let (wavs_no_ext, _) = open_audio_file("samples/sample_48k_wav_no_extension").unwrap();
+        let hash = calc_pcm_hash(wavs.view(), 48000);
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, calc_pcm_hash(wavs_no_ext.view(), 48000));
+        assert_ne!(hash, calc_pcm_hash(wavs.view(), 44100));
+
+        let mut modified = wavs.clone();
+        modified[[0, 1000]] += f32::EPSILON;
+        assert_ne!(hash, calc_pcm_hash(modified.view(), 48000));
+
+        // interleaved order
+        let stereo = arr2(&[[1f32, 2.], [3., 4.]]);
+        let swapped = arr2(&[[1f32, 3.], [2., 4.]]);
+        assert_ne!(
+            calc_pcm_hash(stereo.view(), 48000),
+            calc_pcm_hash(swapped.view(), 48000)
+        );
+        assert_eq!(
+            calc_pcm_hash(stereo.view(), 48000),
+            calc_pcm_hash(stereo.t().as_standard_layout().t(), 48000)
+        );
+    }
+
+    #[test]
+    fn open_raw_pcm_works() {
+        let dir = std::env::temp_dir();
+        let path = dir.join("thesia_open_raw_pcm_works.raw");
+        let path_str = path.to_str().unwrap();
+
+        // 3-byte header + 2 frames of stereo S16LE + a trailing byte
+        let bytes = [
+            0xff, 0xff, 0xff, 0x00, 0x40, 0x00, 0xc0, 0xff, 0x7f, 0x00, 0x80, 0x01,
+        ];
+        std::fs::write(&path, bytes).unwrap();
+        let params = RawPcmParams {
+            sr: 8000,
+            n_ch: 2,
+            sample_format: RawSampleFormat::S16LE,
+            offset: 3,
+        };
+        let (wavs, format_info) = open_raw_pcm_file(path_str, &params).unwrap();
+        assert_eq!(wavs, arr2(&[[0.5, 32767. / 32768.], [-0.5, -1.]]));
+        assert_eq!(format_info.sr, 8000);
+        assert_eq!(format_info.bit_depth, "S16LE");
+
+        let bytes: Vec<_> = [0.25f32, -0.75]
+            .iter()
+            .flat_map(|x| x.to_be_bytes())
+            .chain([0x80, 0x00, 0x00])
+            .collect();
+        std::fs::write(&path, bytes).unwrap();
+        let params = RawPcmParams {
+            n_ch: 1,
+            sample_format: RawSampleFormat::F32BE,
+            offset: 0,
+            ..params
+        };
+        let (wavs, _) = open_raw_pcm_file(path_str, &params).unwrap();
+        assert_eq!(wavs, arr2(&[[0.25, -0.75]]));
+        let params = RawPcmParams {
+            sample_format: RawSampleFormat::S24BE,
+            offset: 8,
+            ..params
+        };
+        let (wavs, _) = open_raw_pcm_file(path_str, &params).unwrap();
+        assert_eq!(wavs, arr2(&[[-1.]]));
+
+        let params = RawPcmParams {
+            offset: 100,
+            ..params
+        };
+        assert!(open_raw_pcm_file(path_str, &params).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,183 @@
+//! Binary layout of data slices passed to the renderer as a Buffer without JSON serialization.
+//! This only defines the header format; it is not a shared-memory transport.
+//! Each slice is copied into a new buffer (which is handed to JS without another copy).
+//! All fields are little-endian.
+//! Don't change the layout without bumping LAYOUT_VERSION.
+//!
+//! | offset | size | field       |
+//! |--------|------|-------------|
+//! | 0      | 4    | magic       |
+//! | 4      | 2    | version     |
+//! | 6      | 2    | kind        |
+//! | 8      | 4    | id          |
+//! | 12     | 4    | ch          |
+//! | 16     | 4    | n_rows      |
+//! | 20     | 4    | n_cols      |
+//! | 24     | 4    | sr          |
+//! | 28     | 4    | (reserved)  |
+//! | 32     | 8    | start_sec   |
+//! | 40     | 8    | sec_per_col |
+//! | 48     | ..   | payload     |
+
+use fast_image_resize::pixels::U16;
+use ndarray::prelude::*;
+
+pub const LAYOUT_MAGIC: [u8; 4] = *b"THSL";
+pub const LAYOUT_VERSION: u16 = 1;
+pub const HEADER_SIZE: usize = 48;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u16)]
+pub enum SliceKind {
+    /// payload: n_cols f32 samples
+    Wav = 0,
+    /// payload: n_rows x n_cols u16 grey values (row-major, the highest frequency first)
+    SpecGrey = 1,
+}
+
+impl SliceKind {
+    #[inline]
+    fn bytes_per_elem(&self) -> usize {
+        match self {
+            SliceKind::Wav => 4,
+            SliceKind::SpecGrey => 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SliceHeader {
+    pub kind: SliceKind,
+    pub id: u32,
+    pub ch: u32,
+    pub n_rows: u32,
+    pub n_cols: u32,
+    pub sr: u32,
+    /// time of the first column (sec)
+    pub start_sec: f64,
+    /// time interval between columns (sec)
+    pub sec_per_col: f64,
+}
+
+impl SliceHeader {
+    #[inline]
+    pub fn payload_size(&self) -> usize {
+        self.n_rows as usize * self.n_cols as usize * self.kind.bytes_per_elem()
+    }
+
+    pub fn write_to(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&LAYOUT_MAGIC);
+        buf.extend_from_slice(&LAYOUT_VERSION.to_le_bytes());
+        buf.extend_from_slice(&(self.kind as u16).to_le_bytes());
+        buf.extend_from_slice(&self.id.to_le_bytes());
+        buf.extend_from_slice(&self.ch.to_le_bytes());
+        buf.extend_from_slice(&self.n_rows.to_le_bytes());
+        buf.extend_from_slice(&self.n_cols.to_le_bytes());
+        buf.extend_from_slice(&self.sr.to_le_bytes());
+        buf.extend_from_slice(&[0; 4]);
+        buf.extend_from_slice(&self.start_sec.to_le_bytes());
+        buf.extend_from_slice(&self.sec_per_col.to_le_bytes());
+    }
+}
+
+pub fn encode_wav_slice(
+    id: usize,
+    ch: usize,
+    wav: ArrayView1<f32>,
+    sr: u32,
+    start_sec: f64,
+) -> Vec<u8> {
+    let header = SliceHeader {
+        kind: SliceKind::Wav,
+        id: id as u32,
+        ch: ch as u32,
+        n_rows: 1,
+        n_cols: wav.len() as u32,
+        sr,
+        start_sec,
+        sec_per_col: 1. / sr as f64,
+    };
+    let mut buf = Vec::with_capacity(HEADER_SIZE + header.payload_size());
+    header.write_to(&mut buf);
+    wav.iter()
+        .for_each(|x| buf.extend_from_slice(&x.to_le_bytes()));
+    buf
+}
+
+pub fn encode_spec_grey_slice(
+    id: usize,
+    ch: usize,
+    grey: ArrayView2<U16>,
+    sr: u32,
+    start_sec: f64,
+    sec_per_col: f64,
+) -> Vec<u8> {
+    let header = SliceHeader {
+        kind: SliceKind::SpecGrey,
+        id: id as u32,
+        ch: ch as u32,
+        n_rows: grey.shape()[0] as u32,
+        n_cols: grey.shape()[1] as u32,
+        sr,
+        start_sec,
+        sec_per_col,
+    };
+    let mut buf = Vec::with_capacity(HEADER_SIZE + header.payload_size());
+    header.write_to(&mut buf);
+    grey.iter()
+        .for_each(|x| buf.extend_from_slice(&x.0.to_le_bytes()));
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_layout_is_stable() {
+        let header = SliceHeader {
+            kind: SliceKind::SpecGrey,
+            id: 3,
+            ch: 1,
+            n_rows: 2,
+            n_cols: 5,
+            sr: 48000,
+            start_sec: 1.5,
+            sec_per_col: 0.01,
+        };
+        let mut buf = Vec::new();
+        header.write_to(&mut buf);
+        assert_eq!(buf.len(), HEADER_SIZE);
+        assert_eq!(&buf[0..4], b"THSL");
+        assert_eq!(buf[4..6], 1u16.to_le_bytes());
+        assert_eq!(buf[6..8], 1u16.to_le_bytes());
+        assert_eq!(buf[8..12], 3u32.to_le_bytes());
+        assert_eq!(buf[12..16], 1u32.to_le_bytes());
+        assert_eq!(buf[16..20], 2u32.to_le_bytes());
+        assert_eq!(buf[20..24], 5u32.to_le_bytes());
+        assert_eq!(buf[24..28], 48000u32.to_le_bytes());
+        assert_eq!(buf[32..40], 1.5f64.to_le_bytes());
+        assert_eq!(buf[40..48], 0.01f64.to_le_bytes());
+    }
+
+    #[test]
+    fn encode_works() {
+        let wav = arr1(&[0.5f32, -0.25, 1.]);
+        let buf = encode_wav_slice(2, 0, wav.view(), 8000, 0.25);
+        assert_eq!(buf.len(), HEADER_SIZE + 3 * 4);
+        assert_eq!(buf[6..8], (SliceKind::Wav as u16).to_le_bytes());
+        assert_eq!(buf[16..20], 1u32.to_le_bytes());
+        assert_eq!(buf[20..24], 3u32.to_le_bytes());
+        assert_eq!(buf[32..40], 0.25f64.to_le_bytes());
+        assert_eq!(
+            buf[HEADER_SIZE + 4..HEADER_SIZE + 8],
+            (-0.25f32).to_le_bytes()
+        );
+
+        let grey = Array2::from_shape_fn((2, 3), |(i, j)| U16::new((i * 3 + j) as u16));
+        let buf = encode_spec_grey_slice(1, 1, grey.view(), 8000, 0., 0.02);
+        assert_eq!(buf.len(), HEADER_SIZE + 6 * 2);
+        assert_eq!(buf[6..8], (SliceKind::SpecGrey as u16).to_le_bytes());
+        assert_eq!(buf[HEADER_SIZE + 10..], 5u16.to_le_bytes());
+    }
+}
@@ -0,0 +1,201 @@
+//! Pairwise comparison of tracks (e.g. which files are versions of each other): the offset by
+//! the cross-correlation, and the residual after aligning and matching the gain (null test)
+
+// allow for whole file because [napi(object)] attribite on struct blocks allow(non_snake_case)
+#![allow(non_snake_case)]
+
+use napi_derive::napi;
+use ndarray::prelude::*;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+
+use super::audio::resample_to;
+use super::dynamics::DeciBel;
+
+/// duration analyzed from the start of each track
+const MAX_ANALYSIS_SEC: f64 = 30.;
+/// maximum offset between two tracks searched by the cross-correlation
+const MAX_OFFSET_SEC: f64 = 10.;
+
+/// comparison of a track (row) to the other track (column) of the compare matrix
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackPairComparison {
+    /// the other track delayed by offset_sec is aligned with this track (negative: advanced)
+    pub offset_sec: f64,
+    /// correlation coefficient of the aligned tracks (negative if the polarity is inverted)
+    pub correlation: f64,
+    /// RMS of this track minus the aligned and gain-matched other track (dBFS)
+    #[napi(js_name = "residualRmsdB")]
+    pub residual_rms_dB: f64,
+    /// residual RMS relative to the RMS of this track (dB). The lower, the deeper the null.
+    #[napi(js_name = "nullDepthdB")]
+    pub null_depth_dB: f64,
+}
+
+/// mono mixdown of the beginning of wavs analyzed by compare_all_pairs
+pub fn mixdown_for_compare(wavs: ArrayView2<f32>, sr: u32) -> Array1<f32> {
+    let len = wavs.shape()[1].min((MAX_ANALYSIS_SEC * sr as f64).round() as usize);
+    wavs.slice(s![.., ..len])
+        .mean_axis(Axis(0))
+        .unwrap_or_else(|| Array1::zeros(0))
+}
+
+/// Compare every pair of (mono wav, sr). [i][j]: track j compared to track i.
+/// None on the diagonal or if either is silent. on_pair_done is called after each pair.
+pub fn compare_all_pairs(
+    wavs: &[(Array1<f32>, u32)],
+    on_pair_done: impl Fn() + Sync,
+) -> Vec<Vec<Option<TrackPairComparison>>> {
+    let n = wavs.len();
+    let pairs: Vec<_> = (0..n)
+        .flat_map(|i| ((i + 1)..n).map(move |j| (i, j)))
+        .collect();
+    let results: Vec<_> = pairs
+        .into_par_iter()
+        .map(|(i, j)| {
+            let result = compare_pair(&wavs[i], &wavs[j]);
+            on_pair_done();
+            ((i, j), result)
+        })
+        .collect();
+
+    let mut matrix = vec![vec![None; n]; n];
+    for ((i, j), result) in results {
+        if let Some((i_to_j, j_to_i)) = result {
+            matrix[i][j] = Some(i_to_j);
+            matrix[j][i] = Some(j_to_i);
+        }
+    }
+    matrix
+}
+
+/// (a compared to b, b compared to a). The higher sample rate is resampled to the lower one.
+fn compare_pair(
+    (a, sr_a): &(Array1<f32>, u32),
+    (b, sr_b): &(Array1<f32>, u32),
+) -> Option<(TrackPairComparison, TrackPairComparison)> {
+    let sr = *sr_a.min(sr_b);
+    let a = resample_to(a.view(), *sr_a, sr);
+    let b = resample_to(b.view(), *sr_b, sr);
+    let max_lag = ((MAX_OFFSET_SEC * sr as f64) as usize).min(a.len().min(b.len()) / 2);
+    let lag = find_lag(a.view(), b.view(), max_lag);
+    let (a, b) = overlap_aligned(a.view(), b.view(), lag);
+
+    let sum_of_products = |x: ArrayView1<f32>, y: ArrayView1<f32>| {
+        x.iter()
+            .zip(y)
+            .map(|(&x, &y)| x as f64 * y as f64)
+            .sum::<f64>()
+    };
+    let (aa, bb, ab) = (
+        sum_of_products(a, a),
+        sum_of_products(b, b),
+        sum_of_products(a, b),
+    );
+    if aa == 0. || bb == 0. {
+        return None;
+    }
+    let correlation = ab / (aa * bb).sqrt();
+    let len = a.len() as f64;
+    // the energy of x - g * y minimized by g = xy / yy
+    let compare = |xx: f64, yy: f64, lag: isize| {
+        let residual = (xx - ab * ab / yy).max(0.);
+        TrackPairComparison {
+            offset_sec: lag as f64 / sr as f64,
+            correlation,
+            residual_rms_dB: (residual / len).sqrt().dB_from_amp_default(),
+            null_depth_dB: (residual / xx).dB_from_power_default(),
+        }
+    };
+    Some((compare(aa, bb, lag), compare(bb, aa, -lag)))
+}
+
+/// lag (|lag| <= max_lag) maximizing |sum_t a[t + lag] * b[t]|
+fn find_lag(a: ArrayView1<f32>, b: ArrayView1<f32>, max_lag: usize) -> isize {
+    let n_fft = (a.len() + b.len()).next_power_of_two();
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(n_fft);
+    let inverse = planner.plan_fft_inverse(n_fft);
+    let spectrum_of = |wav: ArrayView1<f32>| {
+        let mut buf = forward.make_input_vec();
+        buf.iter_mut().zip(wav).for_each(|(y, &x)| *y = x);
+        let mut spectrum = forward.make_output_vec();
+        forward.process(&mut buf, &mut spectrum).unwrap();
+        spectrum
+    };
+    let spectrum_a = spectrum_of(a);
+    let mut cross_spectrum = spectrum_of(b);
+    cross_spectrum
+        .iter_mut()
+        .zip(&spectrum_a)
+        .for_each(|(y, x)| *y = x * y.conj());
+    cross_spectrum[0].im = 0.;
+    cross_spectrum[n_fft / 2].im = 0.;
+    let mut xcorr = inverse.make_output_vec();
+    inverse.process(&mut cross_spectrum, &mut xcorr).unwrap();
+
+    // negative lags are at the end of xcorr
+    let xcorr_at = |lag: isize| xcorr[lag.rem_euclid(n_fft as isize) as usize].abs();
+    let max_lag = max_lag as isize;
+    (-max_lag..=max_lag)
+        .max_by(|&x, &y| xcorr_at(x).total_cmp(&xcorr_at(y)))
+        .unwrap_or(0)
+}
+
+/// overlapping parts of a and b aligned by a[t + lag] = b[t]
+fn overlap_aligned<'a>(
+    a: ArrayView1<'a, f32>,
+    b: ArrayView1<'a, f32>,
+    lag: isize,
+) -> (ArrayView1<'a, f32>, ArrayView1<'a, f32>) {
+    let (a, b) = if lag >= 0 {
+        (a.slice_move(s![lag as usize..]), b)
+    } else {
+        (a, b.slice_move(s![(-lag) as usize..]))
+    };
+    let len = a.len().min(b.len());
+    (a.slice_move(s![..len]), b.slice_move(s![..len]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+    use ndarray_rand::{rand::prelude::*, rand_distr::Uniform, RandomExt};
+
+    #[test]
+    fn compare_all_pairs_works() {
+        let sr = 8000;
+        let mut rng = StdRng::seed_from_u64(0);
+        let uniform = Uniform::new_inclusive(-0.5f32, 0.5);
+        let noise = Array1::random_using(sr as usize * 2, uniform, &mut rng);
+        // delayed by 0.1 sec, 6 dB quieter, and polarity inverted
+        let delayed = ndarray::concatenate(
+            Axis(0),
+            &[Array1::zeros(800).view(), noise.mapv(|x| -0.5 * x).view()],
+        )
+        .unwrap();
+        let other = Array1::random_using(sr as usize * 2, uniform, &mut rng);
+        let wavs = vec![(noise, sr), (delayed, sr), (other, sr)];
+        let n_done = std::sync::atomic::AtomicUsize::new(0);
+        let matrix = compare_all_pairs(&wavs, || {
+            n_done.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        });
+        assert_eq!(n_done.into_inner(), 3);
+        assert!((0..3).all(|i| matrix[i][i].is_none()));
+
+        let cmp = matrix[0][1].as_ref().unwrap();
+        assert_abs_diff_eq!(cmp.offset_sec, -0.1);
+        assert_abs_diff_eq!(cmp.correlation, -1., epsilon = 1e-4);
+        assert!(cmp.null_depth_dB < -60.);
+        let cmp = matrix[1][0].as_ref().unwrap();
+        assert_abs_diff_eq!(cmp.offset_sec, 0.1);
+        assert!(cmp.null_depth_dB < -60.);
+
+        let cmp = matrix[0][2].as_ref().unwrap();
+        assert!(cmp.correlation.abs() < 0.1);
+        assert!(cmp.null_depth_dB > -1.);
+    }
+}
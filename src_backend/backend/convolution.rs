@@ -0,0 +1,251 @@
+//! FIR filtering of the playback with a user-provided impulse response
+//! (e.g. headphone correction or room EQ)
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use ndarray::prelude::*;
+use realfft::num_complex::Complex;
+use realfft::RealFftPlanner;
+use symphonia::core::errors::Error as SymphoniaError;
+
+use super::audio::open_audio_file;
+
+/// size of the IR partitions and the input blocks
+const BLOCK_SIZE: usize = 4096;
+/// IRs longer than this are truncated
+const MAX_IR_SEC: f64 = 10.;
+/// minimum FFT size for the frequency response of short IRs
+const MIN_RESPONSE_N_FFT: usize = 8192;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImpulseResponse {
+    wavs: Array2<f32>,
+    sr: u32,
+}
+
+impl ImpulseResponse {
+    pub fn new(wavs: Array2<f32>, sr: u32) -> Self {
+        let max_len = (MAX_IR_SEC * sr as f64).round() as usize;
+        let wavs = if wavs.shape()[1] > max_len {
+            wavs.slice_move(s![.., ..max_len])
+        } else {
+            wavs
+        };
+        ImpulseResponse { wavs, sr }
+    }
+
+    pub fn open(path: &str) -> Result<Self, SymphoniaError> {
+        let (wavs, format_info) = open_audio_file(path)?;
+        Ok(Self::new(wavs, format_info.sr))
+    }
+
+    #[inline]
+    pub fn n_ch(&self) -> usize {
+        self.wavs.shape()[0]
+    }
+
+    /// length of the tail added by the convolution
+    #[inline]
+    pub fn sec(&self) -> f64 {
+        self.wavs.shape()[1] as f64 / self.sr as f64
+    }
+
+    /// Convolve each channel of wavs with the IR resampled to sr.
+    /// A mono IR is applied to all channels, otherwise channel ch uses the IR channel ch % n_ch.
+    /// The output has the same length as the input (the tail is truncated).
+    pub fn convolve(&self, wavs: ArrayView2<f32>, sr: u32) -> Array2<f32> {
+        let ir = if sr == self.sr {
+            Cow::Borrowed(&self.wavs)
+        } else {
+            let out_len = (self.wavs.shape()[1] as f64 * sr as f64 / self.sr as f64).round();
+            let mut resampled = Array2::zeros((self.n_ch(), (out_len as usize).max(1)));
+            Zip::from(resampled.rows_mut())
+                .and(self.wavs.rows())
+                .for_each(|mut y, x| y.assign(&resample_fft(x, y.len())));
+            Cow::Owned(resampled)
+        };
+        let mut output = Array2::zeros(wavs.raw_dim());
+        Zip::indexed(output.rows_mut())
+            .and(wavs.rows())
+            .par_for_each(|ch, mut y, x| {
+                y.assign(&convolve_partitioned(x, ir.row(ch % ir.shape()[0])));
+            });
+        output
+    }
+
+    /// Magnitude (linear) of the frequency response of each channel at hzs.
+    /// The magnitudes between the FFT bins are linearly interpolated,
+    /// and frequencies above the Nyquist frequency of the IR have zero magnitude.
+    pub fn magnitude_response(&self, hzs: &[f64]) -> Array2<f32> {
+        let n_fft = self.wavs.shape()[1]
+            .max(MIN_RESPONSE_N_FFT)
+            .next_power_of_two();
+        let mut planner = RealFftPlanner::<f32>::new();
+        let forward = planner.plan_fft_forward(n_fft);
+        let mut output = Array2::zeros((self.n_ch(), hzs.len()));
+        Zip::from(output.rows_mut())
+            .and(self.wavs.rows())
+            .par_for_each(|mut y, ir| {
+                let mut input = forward.make_input_vec();
+                input.iter_mut().zip(ir).for_each(|(y, &x)| *y = x);
+                let mut spectrum = forward.make_output_vec();
+                forward.process(&mut input, &mut spectrum).unwrap();
+                let i_last = spectrum.len() - 1;
+                y.iter_mut().zip(hzs).for_each(|(y, &hz)| {
+                    let pos = hz * n_fft as f64 / self.sr as f64;
+                    if !(0.0..=i_last as f64).contains(&pos) {
+                        return;
+                    }
+                    let i = (pos as usize).min(i_last - 1);
+                    let frac = (pos - i as f64) as f32;
+                    *y = (spectrum[i + 1].norm() - spectrum[i].norm())
+                        .mul_add(frac, spectrum[i].norm());
+                });
+            });
+        output
+    }
+}
+
+/// band-limited resampling of the whole signal by zero-padding or truncating the spectrum.
+/// The sum of the samples (DC gain of the filter) is preserved.
+fn resample_fft(wav: ArrayView1<f32>, out_len: usize) -> Array1<f32> {
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(wav.len());
+    let inverse = planner.plan_fft_inverse(out_len);
+    let mut input = wav.to_vec();
+    let mut spectrum = forward.make_output_vec();
+    forward.process(&mut input, &mut spectrum).unwrap();
+
+    let mut out_spectrum = inverse.make_input_vec();
+    let n_bins = spectrum.len().min(out_spectrum.len());
+    out_spectrum[..n_bins].copy_from_slice(&spectrum[..n_bins]);
+    out_spectrum[0].im = 0.;
+    if out_len % 2 == 0 {
+        out_spectrum[out_len / 2].im = 0.;
+    }
+    let mut output = inverse.make_output_vec();
+    inverse.process(&mut out_spectrum, &mut output).unwrap();
+    Array1::from_vec(output) / out_len as f32
+}
+
+/// uniformly partitioned overlap-save convolution.
+/// The output has the same length as wav.
+fn convolve_partitioned(wav: ArrayView1<f32>, ir: ArrayView1<f32>) -> Array1<f32> {
+    if wav.is_empty() || ir.is_empty() {
+        return Array1::zeros(wav.len());
+    }
+    let n_fft = 2 * BLOCK_SIZE;
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(n_fft);
+    let inverse = planner.plan_fft_inverse(n_fft);
+
+    let mut frame = forward.make_input_vec();
+    let ir_spectra: Vec<_> = ir
+        .axis_chunks_iter(Axis(0), BLOCK_SIZE)
+        .map(|part| {
+            frame.fill(0.);
+            frame.iter_mut().zip(part).for_each(|(y, &x)| *y = x);
+            let mut spectrum = forward.make_output_vec();
+            forward.process(&mut frame, &mut spectrum).unwrap();
+            spectrum
+        })
+        .collect();
+
+    let n_blocks = wav.len().div_ceil(BLOCK_SIZE);
+    let mut output = Array1::zeros(n_blocks * BLOCK_SIZE);
+    // [previous block, current block]
+    let mut window = vec![0f32; n_fft];
+    // spectra of the recent input windows (the latest first)
+    let mut delay_line: VecDeque<Vec<Complex<f32>>> = VecDeque::with_capacity(ir_spectra.len());
+    let mut acc = inverse.make_input_vec();
+    let mut out_frame = inverse.make_output_vec();
+    for i_block in 0..n_blocks {
+        let i_start = i_block * BLOCK_SIZE;
+        window.copy_within(BLOCK_SIZE.., 0);
+        window[BLOCK_SIZE..]
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, y)| *y = wav.get(i_start + i).copied().unwrap_or(0.));
+
+        let mut spectrum = if delay_line.len() == ir_spectra.len() {
+            delay_line.pop_back().unwrap()
+        } else {
+            forward.make_output_vec()
+        };
+        frame.copy_from_slice(&window);
+        forward.process(&mut frame, &mut spectrum).unwrap();
+        delay_line.push_front(spectrum);
+
+        acc.fill(Complex::default());
+        for (x, h) in delay_line.iter().zip(&ir_spectra) {
+            acc.iter_mut()
+                .zip(x.iter().zip(h))
+                .for_each(|(y, (&a, &b))| *y += a * b);
+        }
+        acc[0].im = 0.;
+        acc[BLOCK_SIZE].im = 0.;
+        inverse.process(&mut acc, &mut out_frame).unwrap();
+        output
+            .slice_mut(s![i_start..i_start + BLOCK_SIZE])
+            .iter_mut()
+            .zip(&out_frame[BLOCK_SIZE..])
+            .for_each(|(y, &x)| *y = x / n_fft as f32);
+    }
+    output.slice_move(s![..wav.len()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    fn convolve_direct(wav: ArrayView1<f32>, ir: ArrayView1<f32>) -> Array1<f32> {
+        Array1::from_shape_fn(wav.len(), |i| {
+            (0..ir.len().min(i + 1)).map(|j| wav[i - j] * ir[j]).sum()
+        })
+    }
+
+    #[test]
+    fn convolve_partitioned_works() {
+        let wav = Array1::from_shape_fn(3 * BLOCK_SIZE + 100, |i| {
+            ((i * 7919) % 200) as f32 / 100. - 1.
+        });
+        let ir = Array1::from_shape_fn(BLOCK_SIZE + 50, |i| 0.999f32.powi(i as i32) * 0.01);
+        let expected = convolve_direct(wav.view(), ir.view());
+        let convolved = convolve_partitioned(wav.view(), ir.view());
+        assert_eq!(convolved.len(), wav.len());
+        for (&y, &y_expected) in convolved.iter().zip(&expected) {
+            assert_abs_diff_eq!(y, y_expected, epsilon = 1e-3);
+        }
+    }
+
+    #[test]
+    fn magnitude_response_works() {
+        let delta = ImpulseResponse::new(arr2(&[[1., 0., 0., 0.]]), 48000);
+        let response = delta.magnitude_response(&[0., 1000., 24000., 30000.]);
+        assert_eq!(response.shape(), &[1, 4]);
+        for &y in response.slice(s![0, ..3]) {
+            assert_abs_diff_eq!(y, 1., epsilon = 1e-6);
+        }
+        assert_eq!(response[[0, 3]], 0.);
+
+        // two-tap moving average: cos(pi * f / sr)
+        let lowpass = ImpulseResponse::new(arr2(&[[0.5, 0.5], [1., 0.]]), 48000);
+        let response = lowpass.magnitude_response(&[0., 12000., 23990.]);
+        assert_abs_diff_eq!(response[[0, 0]], 1., epsilon = 1e-6);
+        assert_abs_diff_eq!(response[[0, 1]], 0.5f32.sqrt(), epsilon = 1e-3);
+        assert!(response[[0, 2]] < 0.01);
+        assert_abs_diff_eq!(response[[1, 2]], 1., epsilon = 1e-6);
+    }
+
+    #[test]
+    fn resampled_ir_keeps_dc_gain() {
+        let ir = ImpulseResponse::new(arr2(&[[0.5, 0.3, 0.2, 0., 0., 0.]]), 48000);
+        let wavs = Array2::ones((2, 1000));
+        let convolved = ir.convolve(wavs.view(), 96000);
+        assert_abs_diff_eq!(convolved[[0, 999]], 1., epsilon = 1e-3);
+        assert_abs_diff_eq!(convolved[[1, 999]], 1., epsilon = 1e-3);
+    }
+}
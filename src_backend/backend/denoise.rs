@@ -0,0 +1,219 @@
+//! Spectral subtraction of the noise floor for previewing how much noise (e.g. hiss) is removable
+
+use std::borrow::Cow;
+
+use ndarray::prelude::*;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+
+use super::spectrogram::calc_percentile_spectrum;
+use super::windows::hann;
+
+const N_FFT: usize = 2048;
+const HOP_LENGTH: usize = N_FFT / 4;
+/// percentile of the magnitude over time regarded as the noise floor
+const NOISE_PERCENTILE: f64 = 10.;
+/// maximum number of frames per channel used for the noise profile
+const MAX_PROFILE_FRAMES: usize = 2048;
+/// The percentile is lower than the mean magnitude of the noise (about 2.7 times for Gaussian noise)
+const OVER_SUBTRACTION: f32 = 2.5;
+/// spectral floor (minimum gain of a bin) reducing musical noise
+const MIN_GAIN: f32 = 0.05;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpectralDenoiser {
+    /// magnitude of the noise floor for each frequency bin (N_FFT / 2 + 1 bins)
+    noise_mag: Array1<f32>,
+    sr: u32,
+    /// 0 (bypass) ~ 1 (full subtraction)
+    amount: f32,
+}
+
+impl SpectralDenoiser {
+    /// The noise profile is the percentile of the magnitude spectrogram of all channels of wavs.
+    pub fn new(wavs: ArrayView2<f32>, sr: u32, amount: f64) -> Self {
+        let mag_specs: Vec<_> = wavs
+            .axis_iter(Axis(0))
+            .into_par_iter()
+            .map(calc_mag_spec)
+            .collect();
+        let mag_views: Vec<_> = mag_specs.iter().map(|x| x.view()).collect();
+        let mag_spec = ndarray::concatenate(Axis(0), &mag_views).unwrap();
+        // the percentile of the magnitude is the same as that of dB
+        let noise_mag = calc_percentile_spectrum(mag_spec.view(), NOISE_PERCENTILE);
+        SpectralDenoiser {
+            noise_mag,
+            sr,
+            amount: amount.clamp(0., 1.) as f32,
+        }
+    }
+
+    /// the same noise profile with another amount (0 ~ 1)
+    pub fn with_amount(&self, amount: f64) -> Self {
+        SpectralDenoiser {
+            amount: amount.clamp(0., 1.) as f32,
+            ..self.clone()
+        }
+    }
+
+    /// Subtract the noise floor from each channel of wavs. The output has the same shape.
+    pub fn denoise(&self, wavs: ArrayView2<f32>, sr: u32) -> Array2<f32> {
+        if self.amount == 0. {
+            return wavs.to_owned();
+        }
+        let noise_mag = self.noise_mag_at(sr);
+        let mut output = Array2::zeros(wavs.raw_dim());
+        Zip::from(output.rows_mut())
+            .and(wavs.rows())
+            .par_for_each(|mut y, x| {
+                y.assign(&subtract_spectrum(x, noise_mag.view(), self.amount));
+            });
+        output
+    }
+
+    /// noise profile mapped to the frequency bins of sr
+    fn noise_mag_at(&self, sr: u32) -> Cow<Array1<f32>> {
+        if sr == self.sr {
+            return Cow::Borrowed(&self.noise_mag);
+        }
+        let ratio = sr as f64 / self.sr as f64;
+        let last = self.noise_mag.len() - 1;
+        Cow::Owned(Array1::from_shape_fn(self.noise_mag.len(), |k| {
+            let pos = (k as f64 * ratio).min(last as f64);
+            let i_floor = pos.floor() as usize;
+            let frac = (pos - i_floor as f64) as f32;
+            let x0 = self.noise_mag[i_floor];
+            let x1 = self.noise_mag[(i_floor + 1).min(last)];
+            x0 + (x1 - x0) * frac
+        }))
+    }
+}
+
+/// magnitude spectrogram (T x F) of wav with at most MAX_PROFILE_FRAMES frames
+fn calc_mag_spec(wav: ArrayView1<f32>) -> Array2<f32> {
+    let hop_length = wav.len().div_ceil(MAX_PROFILE_FRAMES).max(HOP_LENGTH);
+    let n_frames = wav.len().div_ceil(hop_length).max(1);
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(N_FFT);
+    let window = hann::<f32>(N_FFT, false);
+    let mut frame = forward.make_input_vec();
+    let mut spectrum = forward.make_output_vec();
+    let mut mag_spec = Array2::zeros((n_frames, N_FFT / 2 + 1));
+    for (i_frame, mut mag) in mag_spec.outer_iter_mut().enumerate() {
+        let i_start = i_frame * hop_length;
+        frame.iter_mut().enumerate().for_each(|(i, y)| {
+            *y = wav.get(i_start + i).map_or(0., |&x| x * window[i]);
+        });
+        forward.process(&mut frame, &mut spectrum).unwrap();
+        mag.iter_mut()
+            .zip(&spectrum)
+            .for_each(|(y, x)| *y = x.norm());
+    }
+    mag_spec
+}
+
+/// STFT-domain spectral subtraction with overlap-add. The output has the same length as wav.
+fn subtract_spectrum(wav: ArrayView1<f32>, noise_mag: ArrayView1<f32>, amount: f32) -> Array1<f32> {
+    if wav.is_empty() {
+        return Array1::zeros(0);
+    }
+    let n_half = N_FFT / 2;
+    let mut padded = Array1::<f32>::zeros(wav.len() + N_FFT);
+    padded
+        .slice_mut(s![n_half..n_half + wav.len()])
+        .assign(&wav);
+
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(N_FFT);
+    let inverse = planner.plan_fft_inverse(N_FFT);
+    let window = hann::<f32>(N_FFT, false);
+
+    let n_frames = (wav.len() + N_FFT).div_ceil(HOP_LENGTH);
+    let mut output = Array1::<f32>::zeros((n_frames - 1) * HOP_LENGTH + N_FFT);
+    let mut win_sum = Array1::<f32>::zeros(output.len());
+    let mut frame = vec![0f32; N_FFT];
+    let mut spectrum = forward.make_output_vec();
+    for i_frame in 0..n_frames {
+        let i_start = i_frame * HOP_LENGTH;
+        frame.iter_mut().enumerate().for_each(|(i, y)| {
+            *y = padded.get(i_start + i).map_or(0., |&x| x * window[i]);
+        });
+        forward.process(&mut frame, &mut spectrum).unwrap();
+        spectrum.iter_mut().zip(&noise_mag).for_each(|(x, &noise)| {
+            let noise = noise * OVER_SUBTRACTION;
+            let mag = x.norm();
+            let gain = if mag > noise {
+                1. - amount * noise / mag
+            } else {
+                1. - amount
+            };
+            *x *= gain.max(MIN_GAIN);
+        });
+        spectrum[0].im = 0.;
+        spectrum[n_half].im = 0.;
+
+        inverse.process(&mut spectrum, &mut frame).unwrap();
+        for (i, &x) in frame.iter().enumerate() {
+            output[i_start + i] += x / N_FFT as f32 * window[i];
+            win_sum[i_start + i] += window[i] * window[i];
+        }
+    }
+    Zip::from(&mut output)
+        .and(&win_sum)
+        .for_each(|y, &w| *y = if w > 1e-3 { *y / w } else { 0. });
+    output.slice_move(s![n_half..n_half + wav.len()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    /// deterministic white noise (xorshift)
+    fn noise(len: usize, amp: f32) -> Array1<f32> {
+        let mut state = 0x2545f491u32;
+        Array1::from_shape_fn(len, |_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state as f32 / u32::MAX as f32 * 2. - 1.) * amp
+        })
+    }
+
+    fn rms(wav: ArrayView1<f32>) -> f32 {
+        (wav.iter().map(|x| x * x).sum::<f32>() / wav.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn denoise_works() {
+        let wavs = noise(48000, 0.1).insert_axis(Axis(0));
+        let denoiser = SpectralDenoiser::new(wavs.view(), 48000, 1.);
+        let denoised = denoiser.denoise(wavs.view(), 48000);
+        assert_eq!(denoised.shape(), wavs.shape());
+        assert!(rms(denoised.row(0)) < rms(wavs.row(0)) * 0.5);
+        // the noise profile is reused with another amount
+        assert_eq!(denoiser.with_amount(0.).denoise(wavs.view(), 48000), wavs);
+        assert_eq!(denoiser.with_amount(1.), denoiser);
+
+        // a loud sine over the noise is kept
+        let sine = Array1::from_shape_fn(48000, |i| {
+            (2. * std::f32::consts::PI * 1000. * i as f32 / 48000.).sin() * 0.5
+        });
+        let noisy_sine = (&sine + &noise(48000, 0.1)).insert_axis(Axis(0));
+        let denoised = denoiser.denoise(noisy_sine.view(), 96000);
+        assert_abs_diff_eq!(rms(denoised.row(0)), rms(sine.view()), epsilon = 0.05);
+    }
+
+    #[test]
+    fn zero_amount_bypasses() {
+        let wavs = noise(10000, 0.1).insert_axis(Axis(0));
+        let denoiser = SpectralDenoiser::new(wavs.view(), 48000, 0.);
+        assert_eq!(denoiser.denoise(wavs.view(), 48000), wavs);
+        // unity gain through the STFT
+        let denoised = subtract_spectrum(wavs.row(0), Array1::zeros(N_FFT / 2 + 1).view(), 1.);
+        for (&y, &x) in denoised.iter().zip(wavs.iter()) {
+            assert_abs_diff_eq!(y, x, epsilon = 1e-4);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! TPDF dither of the playback for the output streams of integer sample formats.
+//! The output stream quantizes the frames without dither,
+//! so the output stage adds the dither to each frame after all the gains are applied.
+
+use kittyaudio::Frame;
+
+use super::utils::Xorshift;
+
+/// The dither is not needed if the LSB is below the precision of f32 around full scale
+const MAX_DITHER_BITS: u32 = 24;
+
+/// LSB (amplitude) of the integer sample format of bits.
+/// None for float formats (is_float) or if the dither is not needed.
+pub fn calc_dither_lsb(bits: u32, is_float: bool) -> Option<f32> {
+    (!is_float && (2..=MAX_DITHER_BITS).contains(&bits)).then(|| 2f32.powi(1 - bits as i32))
+}
+
+/// TPDF (triangular, ±lsb) dither with its own random state (one per output stream)
+#[derive(Clone, Debug)]
+pub struct TpdfDither(Xorshift);
+
+impl TpdfDither {
+    pub fn new(seed: u64) -> Self {
+        TpdfDither(Xorshift::new(seed))
+    }
+
+    /// sum of two uniform noises in [-lsb / 2, lsb / 2)
+    #[inline]
+    fn noise(&mut self, lsb: f32) -> f32 {
+        ((self.0.next_f64() + self.0.next_f64()) * 0.5) as f32 * lsb
+    }
+
+    /// Add the dither to the frame. Should be called after all the gains are applied.
+    #[inline]
+    pub fn process(&mut self, frame: Frame, lsb: f32) -> Frame {
+        let left = frame.left + self.noise(lsb);
+        Frame::from((left, frame.right + self.noise(lsb)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn calc_dither_lsb_works() {
+        assert_eq!(calc_dither_lsb(16, false), Some(1. / 32768.));
+        assert_eq!(calc_dither_lsb(32, false), None);
+        assert_eq!(calc_dither_lsb(32, true), None);
+    }
+
+    #[test]
+    fn tpdf_dither_works() {
+        let lsb = calc_dither_lsb(16, false).unwrap();
+        let mut dither = TpdfDither::new(0);
+        let frames: Vec<_> = (0..100000)
+            .map(|_| dither.process(Frame::from((0.25, -0.25)), lsb))
+            .collect();
+        let errors: Vec<_> = frames
+            .iter()
+            .flat_map(|frame| [frame.left - 0.25, frame.right + 0.25])
+            .collect();
+        assert!(errors.iter().all(|x| x.abs() <= lsb * 1.001));
+        let mean = errors.iter().sum::<f32>() / errors.len() as f32;
+        let var = errors.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / errors.len() as f32;
+        assert_abs_diff_eq!(mean / lsb, 0., epsilon = 0.01);
+        // variance of TPDF of ±1 LSB is LSB^2 / 6
+        assert_abs_diff_eq!(var / (lsb * lsb), 1. / 6., epsilon = 0.01);
+    }
+}
@@ -0,0 +1,230 @@
+use napi_derive::napi;
+use ndarray::prelude::*;
+
+use super::dynamics::DeciBel;
+
+/// channels with all samples under this amplitude (-90 dBFS) are regarded as silent
+const SILENT_AMP: f32 = 3.1622776e-5;
+/// side-to-mid ratio (or mid-to-side ratio) under this is regarded as near-identical channels
+#[allow(non_upper_case_globals)]
+const NEAR_IDENTICAL_dB: f64 = -60.;
+
+#[napi(string_enum)]
+#[derive(Debug, Default, Eq, PartialEq)]
+pub enum DownmixMode {
+    #[default]
+    Off,
+    /// (L+R) at -3 dB
+    MonoMinus3dB,
+    /// (L+R) at -6 dB
+    MonoMinus6dB,
+    /// (L+R) with the gain keeping the power of the stereo signal (depends on the correlation)
+    MonoAuto,
+    LeftOnly,
+    RightOnly,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, Default, PartialEq)]
+#[allow(non_snake_case)]
+pub struct MonoCompatibility {
+    /// pearson correlation coefficient between L and R (-1 ~ 1)
+    pub correlation: f64,
+    /// power of the mono sum (L+R)/2 relative to the mean power of L and R
+    #[napi(js_name = "monoLossdB")]
+    pub mono_loss_dB: f64,
+    /// power of the side (L-R)/2 relative to the mid (L+R)/2
+    #[napi(js_name = "sideToMiddB")]
+    pub side_to_mid_dB: f64,
+}
+
+#[napi(string_enum)]
+#[derive(Debug, Eq, PartialEq)]
+pub enum ChannelRelationKind {
+    /// normal stereo
+    Stereo,
+    /// L and R are identical or near-identical
+    DualMono,
+    /// R is (near-)identical to the polarity-inverted L
+    PolarityInverted,
+    LeftSilent,
+    RightSilent,
+    BothSilent,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+#[allow(non_snake_case)]
+pub struct ChannelRelationship {
+    pub kind: ChannelRelationKind,
+    /// if L and R are exactly the same
+    pub is_bit_identical: bool,
+    /// pearson correlation coefficient between L and R (-1 ~ 1)
+    pub correlation: f64,
+    /// power of the side (L-R)/2 relative to the mid (L+R)/2
+    #[napi(js_name = "sideToMiddB")]
+    pub side_to_mid_dB: f64,
+}
+
+/// sums of (L^2, R^2, L*R)
+fn calc_power_sums(left: ArrayView1<f32>, right: ArrayView1<f32>) -> (f64, f64, f64) {
+    left.iter()
+        .zip(right)
+        .fold((0., 0., 0.), |(ll, rr, lr), (&l, &r)| {
+            let (l, r) = (l as f64, r as f64);
+            (l.mul_add(l, ll), r.mul_add(r, rr), l.mul_add(r, lr))
+        })
+}
+
+/// returns None if the downmix doesn't change the audio.
+/// The output has the same number of channels as the input.
+pub fn downmix(wavs: ArrayView2<f32>, mode: DownmixMode) -> Option<Array2<f32>> {
+    if wavs.shape()[0] != 2 || mode == DownmixMode::Off {
+        return None;
+    }
+    let (left, right) = (wavs.row(0), wavs.row(1));
+    let mono = match mode {
+        DownmixMode::Off => unreachable!(),
+        DownmixMode::MonoMinus3dB | DownmixMode::MonoMinus6dB | DownmixMode::MonoAuto => {
+            let gain = match mode {
+                DownmixMode::MonoMinus3dB => (-3f32).amp_from_dB_default(),
+                DownmixMode::MonoMinus6dB => (-6f32).amp_from_dB_default(),
+                _ => {
+                    let (ll, rr, lr) = calc_power_sums(left, right);
+                    let sum_power = ll + rr + 2. * lr;
+                    if sum_power > 0. {
+                        ((ll + rr) / 2. / sum_power).sqrt().min(1.) as f32
+                    } else {
+                        1.
+                    }
+                }
+            };
+            (&left + &right) * gain
+        }
+        DownmixMode::LeftOnly => left.to_owned(),
+        DownmixMode::RightOnly => right.to_owned(),
+    };
+    Some(
+        mono.broadcast((2, mono.len()))
+            .unwrap()
+            .as_standard_layout()
+            .into_owned(),
+    )
+}
+
+/// returns None for non-stereo audio
+pub fn calc_mono_compatibility(wavs: ArrayView2<f32>) -> Option<MonoCompatibility> {
+    if wavs.shape()[0] != 2 {
+        return None;
+    }
+    let (ll, rr, lr) = calc_power_sums(wavs.row(0), wavs.row(1));
+    if ll + rr <= 0. {
+        return Some(Default::default());
+    }
+    let mid_power = (ll + rr + 2. * lr) / 4.;
+    let side_power = (ll + rr - 2. * lr) / 4.;
+    let correlation = if ll > 0. && rr > 0. {
+        (lr / (ll * rr).sqrt()).clamp(-1., 1.)
+    } else {
+        0.
+    };
+    Some(MonoCompatibility {
+        correlation,
+        mono_loss_dB: (mid_power / ((ll + rr) / 2.)).dB_from_power_default(),
+        side_to_mid_dB: (side_power / mid_power).dB_from_power_default(),
+    })
+}
+
+/// returns None for non-stereo audio
+#[allow(non_snake_case)]
+pub fn analyze_channel_relationship(wavs: ArrayView2<f32>) -> Option<ChannelRelationship> {
+    let compat = calc_mono_compatibility(wavs)?;
+    let (left, right) = (wavs.row(0), wavs.row(1));
+    let is_silent = |wav: ArrayView1<f32>| wav.iter().all(|x| x.abs() < SILENT_AMP);
+    let kind = match (is_silent(left), is_silent(right)) {
+        (true, true) => ChannelRelationKind::BothSilent,
+        (true, false) => ChannelRelationKind::LeftSilent,
+        (false, true) => ChannelRelationKind::RightSilent,
+        _ if compat.side_to_mid_dB < NEAR_IDENTICAL_dB => ChannelRelationKind::DualMono,
+        _ if -compat.side_to_mid_dB < NEAR_IDENTICAL_dB => ChannelRelationKind::PolarityInverted,
+        _ => ChannelRelationKind::Stereo,
+    };
+    Some(ChannelRelationship {
+        kind,
+        is_bit_identical: left == right,
+        correlation: compat.correlation,
+        side_to_mid_dB: compat.side_to_mid_dB,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn downmix_works() {
+        let wavs = arr2(&[[0.5f32, -0.5, 0.25], [0.5, -0.5, 0.25]]);
+        assert!(downmix(wavs.view(), DownmixMode::Off).is_none());
+        assert!(downmix(wavs.slice(s![0..1, ..]), DownmixMode::MonoMinus6dB).is_none());
+
+        let mono = downmix(wavs.view(), DownmixMode::MonoMinus6dB).unwrap();
+        assert_eq!(mono.shape(), &[2, 3]);
+        for (&y, &x) in mono.iter().zip(wavs.iter()) {
+            assert_abs_diff_eq!(y, x, epsilon = 1e-2);
+        }
+        let mono = downmix(wavs.view(), DownmixMode::MonoAuto).unwrap();
+        for (&y, &x) in mono.iter().zip(wavs.iter()) {
+            assert_abs_diff_eq!(y, x, epsilon = 1e-6);
+        }
+
+        let wavs = arr2(&[[0.5f32, -0.5], [0.1, 0.2]]);
+        let right = downmix(wavs.view(), DownmixMode::RightOnly).unwrap();
+        assert_eq!(right, arr2(&[[0.1f32, 0.2], [0.1, 0.2]]));
+    }
+
+    #[test]
+    fn mono_compatibility_works() {
+        let wav = Array1::linspace(-1f32, 1., 101);
+        let same = ndarray::stack![Axis(0), wav, wav];
+        let compat = calc_mono_compatibility(same.view()).unwrap();
+        assert_abs_diff_eq!(compat.correlation, 1., epsilon = 1e-6);
+        assert_abs_diff_eq!(compat.mono_loss_dB, 0., epsilon = 1e-6);
+
+        let inverted = ndarray::stack![Axis(0), wav, -&wav];
+        let compat = calc_mono_compatibility(inverted.view()).unwrap();
+        assert_abs_diff_eq!(compat.correlation, -1., epsilon = 1e-6);
+        assert!(compat.mono_loss_dB < -100.);
+
+        assert!(calc_mono_compatibility(wav.insert_axis(Axis(0)).view()).is_none());
+    }
+
+    #[test]
+    fn analyze_channel_relationship_works() {
+        let wav = Array1::linspace(-1f32, 1., 101);
+        let zeros = Array1::zeros(wav.len());
+        let analyze = |l: &Array1<f32>, r: &Array1<f32>| {
+            analyze_channel_relationship(ndarray::stack![Axis(0), l, r].view()).unwrap()
+        };
+
+        let same = analyze(&wav, &wav);
+        assert_eq!(same.kind, ChannelRelationKind::DualMono);
+        assert!(same.is_bit_identical);
+        let near_same = analyze(&wav, &(&wav * 1.0001));
+        assert_eq!(near_same.kind, ChannelRelationKind::DualMono);
+        assert!(!near_same.is_bit_identical);
+        assert_eq!(
+            analyze(&wav, &-&wav).kind,
+            ChannelRelationKind::PolarityInverted
+        );
+        assert_eq!(analyze(&zeros, &wav).kind, ChannelRelationKind::LeftSilent);
+        assert_eq!(analyze(&wav, &zeros).kind, ChannelRelationKind::RightSilent);
+        assert_eq!(
+            analyze(&zeros, &zeros).kind,
+            ChannelRelationKind::BothSilent
+        );
+        let other = wav.mapv(|x| (x * 10.).sin());
+        assert_eq!(analyze(&wav, &other).kind, ChannelRelationKind::Stereo);
+    }
+}
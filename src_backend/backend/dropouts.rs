@@ -0,0 +1,205 @@
+use napi_derive::napi;
+use ndarray::prelude::*;
+use rayon::prelude::*;
+
+/// size of blocks for estimating the local level of the second difference
+const BLOCK_SIZE: usize = 1024;
+/// minimum jump (amplitude) regarded as a discontinuity
+const MIN_JUMP: f32 = 1e-3;
+/// level (amplitude) of the signal around zero runs to be regarded as a dropout
+const MIN_LEVEL_AROUND_ZEROS: f32 = 1e-3;
+/// detections of the same kind closer than this are merged
+const MERGE_SEC: f64 = 0.01;
+const MAX_DROPOUTS: usize = 1000;
+
+#[napi(string_enum)]
+#[derive(Debug, Eq, PartialEq)]
+pub enum DropoutKind {
+    /// abrupt jump of the waveform (e.g. dropped samples)
+    Discontinuity,
+    /// run of zero samples in the middle of the signal
+    ZeroRun,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dropout {
+    pub sec: f64,
+    pub duration_sec: f64,
+    pub ch: u32,
+    pub kind: DropoutKind,
+}
+
+#[inline]
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    (b - a).mul_add(t, a)
+}
+
+/// returns [start, end) of zero runs surrounded by non-silent signal
+fn find_zero_runs(wav: ArrayView1<f32>, min_len: usize, context: usize) -> Vec<(usize, usize)> {
+    let level_of = |i_start: usize, i_end: usize| {
+        wav.slice(s![i_start..i_end])
+            .iter()
+            .fold(0f32, |max, x| max.max(x.abs()))
+    };
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < wav.len() {
+        if wav[i] != 0. {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < wav.len() && wav[i] == 0. {
+            i += 1;
+        }
+        if i - start >= min_len
+            && start > 0
+            && i < wav.len()
+            && level_of(start.saturating_sub(context), start) > MIN_LEVEL_AROUND_ZEROS
+            && level_of(i, (i + context).min(wav.len())) > MIN_LEVEL_AROUND_ZEROS
+        {
+            runs.push((start, i));
+        }
+    }
+    runs
+}
+
+/// indices where the second difference is much larger than its median in the block
+fn find_discontinuities(wav: ArrayView1<f32>, threshold: f32) -> Vec<usize> {
+    if wav.len() < 3 {
+        return Vec::new();
+    }
+    let mut diff2 = Array1::zeros(wav.len());
+    Zip::from(diff2.slice_mut(s![2..]))
+        .and(wav.slice(s![2..]))
+        .and(wav.slice(s![1..-1]))
+        .and(wav.slice(s![..-2]))
+        .for_each(|y, &x0, &x1, &x2| *y = (x0 - 2. * x1 + x2).abs());
+
+    let mut indices = Vec::new();
+    let mut buf = Vec::with_capacity(BLOCK_SIZE);
+    for (i_block, block) in diff2.axis_chunks_iter(Axis(0), BLOCK_SIZE).enumerate() {
+        buf.clear();
+        buf.extend(block.iter().copied());
+        let i_mid = buf.len() / 2;
+        let (_, &mut median, _) = buf.select_nth_unstable_by(i_mid, f32::total_cmp);
+        let limit = (median * threshold).max(MIN_JUMP);
+        indices.extend(
+            block
+                .iter()
+                .enumerate()
+                .filter(|&(_, &x)| x > limit)
+                .map(|(i, _)| i_block * BLOCK_SIZE + i),
+        );
+    }
+    indices
+}
+
+/// merge (start, end) ranges closer than max_gap
+fn merge_ranges(
+    ranges: impl IntoIterator<Item = (usize, usize)>,
+    max_gap: usize,
+) -> Vec<(usize, usize)> {
+    ranges
+        .into_iter()
+        .fold(Vec::new(), |mut merged, (start, end)| {
+            match merged.last_mut() {
+                Some((_, last_end)) if start <= *last_end + max_gap => {
+                    *last_end = (*last_end).max(end);
+                }
+                _ => merged.push((start, end)),
+            }
+            merged
+        })
+}
+
+/// Detect dropouts (discontinuities and zero runs) in each channel.
+/// sensitivity: 0 ~ 1 (higher detects more). The result is sorted by time.
+pub fn detect_dropouts(wavs: ArrayView2<f32>, sr: u32, sensitivity: f64) -> Vec<Dropout> {
+    let sensitivity = sensitivity.clamp(0., 1.);
+    let min_zero_run = lerp(32., 4., sensitivity).round() as usize;
+    let threshold = lerp(40., 8., sensitivity) as f32;
+    let context = (sr as usize / 1000).max(1);
+    let max_gap = (MERGE_SEC * sr as f64).round() as usize;
+    let to_sec = |i: usize| i as f64 / sr as f64;
+
+    let mut dropouts: Vec<Dropout> = wavs
+        .axis_iter(Axis(0))
+        .into_par_iter()
+        .enumerate()
+        .flat_map_iter(|(ch, wav)| {
+            let zero_runs = merge_ranges(find_zero_runs(wav, min_zero_run, context), max_gap);
+            let near_zero_run = |i: usize| {
+                zero_runs
+                    .iter()
+                    .any(|&(start, end)| i + max_gap >= start && i <= end + max_gap)
+            };
+            let jumps = find_discontinuities(wav, threshold)
+                .into_iter()
+                .filter(|&i| !near_zero_run(i))
+                .map(|i| (i, i));
+            let jumps = merge_ranges(jumps, max_gap);
+
+            let make_dropout = move |kind| {
+                move |(start, end): (usize, usize)| Dropout {
+                    sec: to_sec(start),
+                    duration_sec: to_sec(end - start),
+                    ch: ch as u32,
+                    kind,
+                }
+            };
+            zero_runs
+                .into_iter()
+                .map(make_dropout(DropoutKind::ZeroRun))
+                .chain(
+                    jumps
+                        .into_iter()
+                        .map(make_dropout(DropoutKind::Discontinuity)),
+                )
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    dropouts.sort_by(|a, b| a.sec.total_cmp(&b.sec).then(a.ch.cmp(&b.ch)));
+    dropouts.truncate(MAX_DROPOUTS);
+    dropouts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    fn sine(len: usize) -> Array1<f32> {
+        Array1::from_shape_fn(len, |i| {
+            (2. * std::f32::consts::PI * 440. * i as f32 / 48000.).sin() * 0.5
+        })
+    }
+
+    #[test]
+    fn clean_sine_has_no_dropouts() {
+        let wav = sine(48000);
+        let wavs = wav.insert_axis(Axis(0));
+        assert!(detect_dropouts(wavs.view(), 48000, 1.).is_empty());
+    }
+
+    #[test]
+    fn detect_dropouts_works() {
+        let mut wav = sine(48000);
+        // zero run at 0.25 sec
+        wav.slice_mut(s![12000..12100]).fill(0.);
+        // 30 samples dropped at 0.75 sec
+        let wav = ndarray::concatenate![Axis(0), wav.slice(s![..36000]), wav.slice(s![36030..])];
+        let wavs = ndarray::stack![Axis(0), wav, sine(wav.len())];
+
+        let dropouts = detect_dropouts(wavs.view(), 48000, 0.5);
+        assert_eq!(dropouts.len(), 2);
+        assert_eq!(dropouts[0].kind, DropoutKind::ZeroRun);
+        assert_eq!(dropouts[0].ch, 0);
+        assert_abs_diff_eq!(dropouts[0].sec, 0.25, epsilon = 1e-3);
+        assert_abs_diff_eq!(dropouts[0].duration_sec, 100. / 48000., epsilon = 1e-6);
+        assert_eq!(dropouts[1].kind, DropoutKind::Discontinuity);
+        assert_abs_diff_eq!(dropouts[1].sec, 0.75, epsilon = 1e-3);
+    }
+}
@@ -1,12 +1,20 @@
+mod band_loudness;
 pub mod decibel;
 mod envelope;
 mod guardclipping;
+mod headroom;
 mod limiter;
+mod loudness;
 mod normalize;
 mod stats;
 
+pub use band_loudness::{calc_band_loudness, BandLoudness, BandLoudnessContribution};
 pub use decibel::DeciBel;
 pub use guardclipping::{GuardClipping, GuardClippingMode, GuardClippingResult};
-pub use limiter::LimiterManager;
+pub use headroom::{calc_headroom_report, ChannelHeadroom, HeadroomReport};
+pub use limiter::{LimiterManager, PerfectLimiter};
+pub use loudness::{calc_segmented_loudness, segmented_loudness_to_csv, SegmentLoudness};
 pub use normalize::{Normalize, NormalizeTarget};
-pub use stats::{AudioStats, GuardClippingStats, MaxPeak, StatCalculator};
+pub use stats::{
+    AudioStats, ChannelStats, DynamicsStats, GuardClippingStats, MaxPeak, StatCalculator,
+};
@@ -0,0 +1,248 @@
+//! Contribution of each octave band to the integrated loudness (ITU-R BS.1770)
+//!
+//! The K-weighted power of each gating block is split into the octave bands
+//! by the shares of the bands in the spectrum of the block,
+//! so that the powers of the bands sum up to the power of the block.
+//! The blocks are gated by the loudness of the whole signal, not of each band.
+
+use std::f64::consts::SQRT_2;
+
+use napi_derive::napi;
+use ndarray::prelude::*;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+
+use super::super::filters::FilterCascade;
+use super::super::windows::hann;
+
+const BLOCK_SEC: f64 = 0.4;
+const BLOCK_HOP_SEC: f64 = 0.1;
+const ABSOLUTE_GATE_LUFS: f64 = -70.;
+const RELATIVE_GATE_LU: f64 = -10.;
+/// the octave bands are centered at 1 kHz x 2^k (k >= MIN_OCTAVE)
+const MIN_OCTAVE: i32 = -6;
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BandLoudness {
+    pub center_hz: f64,
+    /// (min, max) Hz. The lowest band starts at 0 Hz and the highest band ends at the Nyquist freq.
+    pub hz_range: (f64, f64),
+    /// loudness of the band alone (in the blocks gated by the whole signal)
+    #[napi(js_name = "LUFS")]
+    pub lufs: f64,
+    /// ratio of the gated K-weighted power of the band to that of the whole signal (0 ~ 1).
+    /// The contributions of all the bands sum up to 1.
+    pub contribution: f64,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BandLoudnessContribution {
+    /// integrated loudness of the whole signal
+    #[napi(js_name = "integratedLUFS")]
+    pub integrated_lufs: f64,
+    /// from the lowest band
+    pub bands: Vec<BandLoudness>,
+}
+
+/// centers of the n_bands (at most) highest octave bands below the Nyquist freq
+fn octave_centers(n_bands: usize, sr: u32) -> Vec<f64> {
+    let nyquist = sr as f64 / 2.;
+    let max_octave = ((nyquist / 1000.).log2().ceil() as i32 - 1).max(MIN_OCTAVE);
+    let min_octave = (max_octave + 1 - n_bands as i32).max(MIN_OCTAVE);
+    (min_octave..=max_octave)
+        .map(|k| 1000. * 2f64.powi(k))
+        .collect()
+}
+
+/// Contribution of each of the n_bands (> 0) highest octave bands to the integrated loudness
+/// of wavs. All the channels are weighted equally (as the channels of stereo).
+pub fn calc_band_loudness(
+    wavs: ArrayView2<f32>,
+    sr: u32,
+    n_bands: usize,
+) -> BandLoudnessContribution {
+    let centers = octave_centers(n_bands, sr);
+    let nyquist = sr as f64 / 2.;
+    let hz_ranges: Vec<_> = centers
+        .iter()
+        .enumerate()
+        .map(|(i, &center)| {
+            let min_hz = if i == 0 { 0. } else { center / SQRT_2 };
+            let max_hz = if i == centers.len() - 1 {
+                nyquist
+            } else {
+                center * SQRT_2
+            };
+            (min_hz, max_hz)
+        })
+        .collect();
+
+    let k_weighting = FilterCascade::k_weighting(sr);
+    let weighted: Vec<_> = wavs
+        .axis_iter(Axis(0))
+        .into_par_iter()
+        .map(|wav| k_weighting.filter(wav))
+        .collect();
+    let block_len = (BLOCK_SEC * sr as f64).round() as usize;
+    let hop = (BLOCK_HOP_SEC * sr as f64).round() as usize;
+    let len = wavs.shape()[1];
+    let n_blocks = if len >= block_len && block_len > 0 {
+        (len - block_len) / hop + 1
+    } else {
+        0
+    };
+
+    // band of each frequency bin
+    let forward = RealFftPlanner::<f32>::new().plan_fft_forward(block_len.max(1));
+    let bin_bands: Vec<_> = (0..block_len / 2 + 1)
+        .map(|k| {
+            let hz = k as f64 * sr as f64 / block_len as f64;
+            hz_ranges
+                .iter()
+                .position(|&(_, max_hz)| hz < max_hz)
+                .unwrap_or(centers.len() - 1)
+        })
+        .collect();
+    let window = hann::<f32>(block_len, false);
+
+    // (power of the block, power of each band) of the blocks
+    let blocks: Vec<(f64, Vec<f64>)> = (0..n_blocks)
+        .into_par_iter()
+        .map_init(
+            || (forward.make_input_vec(), forward.make_output_vec()),
+            |(frame, spectrum), i_block| {
+                let i_start = i_block * hop;
+                let mut band_powers = vec![0.; centers.len()];
+                let mut block_power = 0.;
+                for wav in &weighted {
+                    let block = wav.slice(s![i_start..i_start + block_len]);
+                    let power =
+                        block.iter().map(|&x| (x as f64).powi(2)).sum::<f64>() / block_len as f64;
+                    if power == 0. {
+                        continue;
+                    }
+                    block_power += power;
+                    frame
+                        .iter_mut()
+                        .zip(block.iter().zip(&window))
+                        .for_each(|(y, (&x, &w))| *y = x * w);
+                    forward.process(frame, spectrum).unwrap();
+                    let mut shares = vec![0.; centers.len()];
+                    for (x, &band) in spectrum.iter().zip(&bin_bands) {
+                        shares[band] += x.norm_sqr() as f64;
+                    }
+                    let total: f64 = shares.iter().sum();
+                    if total > 0. {
+                        band_powers
+                            .iter_mut()
+                            .zip(shares)
+                            .for_each(|(y, share)| *y += power * share / total);
+                    }
+                }
+                (block_power, band_powers)
+            },
+        )
+        .collect();
+
+    // -inf if silent
+    let lufs_of = |power: f64| -0.691 + 10. * power.log10();
+    let mean_power = |blocks: &[&(f64, Vec<f64>)]| {
+        blocks.iter().map(|(power, _)| power).sum::<f64>() / blocks.len().max(1) as f64
+    };
+    let above_absolute: Vec<_> = blocks
+        .iter()
+        .filter(|(power, _)| lufs_of(*power) > ABSOLUTE_GATE_LUFS)
+        .collect();
+    let relative_gate = lufs_of(mean_power(&above_absolute)) + RELATIVE_GATE_LU;
+    let gated: Vec<_> = above_absolute
+        .into_iter()
+        .filter(|(power, _)| lufs_of(*power) > relative_gate)
+        .collect();
+
+    let total_power: f64 = gated.iter().map(|(power, _)| power).sum();
+    let bands = centers
+        .into_iter()
+        .zip(hz_ranges)
+        .enumerate()
+        .map(|(i, (center_hz, hz_range))| {
+            let band_power: f64 = gated.iter().map(|(_, powers)| powers[i]).sum();
+            BandLoudness {
+                center_hz,
+                hz_range,
+                lufs: lufs_of(band_power / gated.len().max(1) as f64),
+                contribution: if total_power > 0. {
+                    band_power / total_power
+                } else {
+                    0.
+                },
+            }
+        })
+        .collect();
+    BandLoudnessContribution {
+        integrated_lufs: lufs_of(mean_power(&gated)),
+        bands,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use approx::assert_abs_diff_eq;
+    use ebur128::{EbuR128, Mode as LoudnessMode};
+
+    use super::*;
+
+    #[test]
+    fn octave_centers_work() {
+        assert_eq!(octave_centers(3, 48000), [4000., 8000., 16000.]);
+        assert_eq!(octave_centers(2, 32000), [4000., 8000.]);
+        assert_eq!(octave_centers(100, 44100).len(), 11);
+        assert_eq!(octave_centers(100, 44100)[0], 15.625);
+    }
+
+    #[test]
+    fn band_loudness_works() {
+        let sr = 48000;
+        // 100 Hz and 1 kHz sines of -20 dBFS for 5 sec
+        let wavs = Array2::from_shape_fn((2, sr as usize * 5), |(_, i)| {
+            let t = i as f32 / sr as f32;
+            0.1 * (2. * PI * 100. * t).sin() + 0.1 * (2. * PI * 1000. * t).sin()
+        });
+        let result = calc_band_loudness(wavs.view(), sr, 10);
+        assert_eq!(result.bands.len(), 10);
+        assert_eq!(result.bands[0].hz_range.0, 0.);
+        assert_eq!(result.bands[9].hz_range.1, 24000.);
+
+        let mut analyzer = EbuR128::new(2, sr, LoudnessMode::I).unwrap();
+        let planes: Vec<_> = wavs.outer_iter().map(|x| x.to_vec()).collect();
+        let planes: Vec<_> = planes.iter().map(|x| x.as_slice()).collect();
+        analyzer.add_frames_planar_f32(&planes).unwrap();
+        let expected = analyzer.loudness_global().unwrap();
+        assert_abs_diff_eq!(result.integrated_lufs, expected, epsilon = 0.1);
+
+        let sum: f64 = result.bands.iter().map(|band| band.contribution).sum();
+        assert_abs_diff_eq!(sum, 1., epsilon = 1e-9);
+        let band_of = |hz: f64| {
+            result
+                .bands
+                .iter()
+                .find(|band| band.hz_range.0 <= hz && hz < band.hz_range.1)
+                .unwrap()
+        };
+        assert!(band_of(100.).contribution + band_of(1000.).contribution > 0.99);
+        // the K-weighting attenuates 100 Hz
+        assert!(band_of(100.).contribution < band_of(1000.).contribution);
+        // a -20 dBFS 1 kHz sine is -23 LUFS in a channel (stereo: +3 dB)
+        assert_abs_diff_eq!(band_of(1000.).lufs, -20., epsilon = 0.1);
+
+        let silence = calc_band_loudness(Array2::zeros((1, sr as usize)).view(), sr, 4);
+        assert_eq!(silence.integrated_lufs, f64::NEG_INFINITY);
+        assert!(silence
+            .bands
+            .iter()
+            .all(|band| band.contribution == 0. && band.lufs == f64::NEG_INFINITY));
+    }
+}
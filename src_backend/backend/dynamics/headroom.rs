@@ -0,0 +1,146 @@
+//! Gain staging of a track from the original audio through normalization and guard clipping
+
+use ebur128::{EbuR128, Mode as LoudnessMode};
+use napi_derive::napi;
+use ndarray::prelude::*;
+
+use super::super::utils::Planes;
+use super::decibel::DeciBel;
+use super::stats::{GuardClippingStats, MaxPeak};
+
+#[napi(object)]
+#[derive(Clone, Debug, Default, PartialEq)]
+#[allow(non_snake_case)]
+pub struct ChannelHeadroom {
+    /// true peak of the original audio (dBTP)
+    #[napi(js_name = "preTruePeakdB")]
+    pub pre_true_peak_dB: f64,
+    /// maximum gain reduction by guard clipping (dB, <= 0)
+    #[napi(js_name = "guardClippingGaindB")]
+    pub guard_clipping_gain_dB: f64,
+    /// sample peak after normalization and guard clipping (dBFS)
+    #[napi(js_name = "postPeakdB")]
+    pub post_peak_dB: f64,
+    /// true peak after normalization and guard clipping (dBTP)
+    #[napi(js_name = "postTruePeakdB")]
+    pub post_true_peak_dB: f64,
+    /// margin of the post true peak to 0 dBTP
+    #[napi(js_name = "headroomdB")]
+    pub headroom_dB: f64,
+}
+
+/// headroom of the track. The numbers of the track are the worst of all channels.
+#[napi(object)]
+#[derive(Clone, Debug, Default, PartialEq)]
+#[allow(non_snake_case)]
+pub struct HeadroomReport {
+    #[napi(js_name = "preTruePeakdB")]
+    pub pre_true_peak_dB: f64,
+    /// gain applied by the normalization (dB)
+    #[napi(js_name = "normalizeGaindB")]
+    pub normalize_gain_dB: f64,
+    #[napi(js_name = "guardClippingGaindB")]
+    pub guard_clipping_gain_dB: f64,
+    #[napi(js_name = "postPeakdB")]
+    pub post_peak_dB: f64,
+    #[napi(js_name = "postTruePeakdB")]
+    pub post_true_peak_dB: f64,
+    #[napi(js_name = "headroomdB")]
+    pub headroom_dB: f64,
+    pub channels: Vec<ChannelHeadroom>,
+}
+
+/// true peak (linear) of each channel (4x oversampling of ebur128)
+fn calc_true_peaks(wavs: ArrayView2<f32>, sr: u32) -> Vec<f64> {
+    let n_ch = wavs.shape()[0] as u32;
+    let mut analyzer = EbuR128::new(n_ch, sr, LoudnessMode::TRUE_PEAK).unwrap();
+    analyzer
+        .add_frames_planar_f32(&wavs.as_standard_layout().planes())
+        .unwrap();
+    (0..n_ch)
+        .map(|ch| analyzer.true_peak(ch).unwrap_or(0.))
+        .collect()
+}
+
+/// original: audio before normalization (channels in the original order)
+/// processed: audio after normalization and guard clipping
+/// channel_order: channel of original for each channel of processed
+pub fn calc_headroom_report(
+    original: ArrayView2<f32>,
+    processed: ArrayView2<f32>,
+    channel_order: &[usize],
+    sr: u32,
+    normalize_gain: f32,
+    guard_clip_stats: ArrayView1<GuardClippingStats>,
+) -> HeadroomReport {
+    let (pre_true_peaks, post_true_peaks) = rayon::join(
+        || calc_true_peaks(original, sr),
+        || calc_true_peaks(processed, sr),
+    );
+    let channels: Vec<_> = processed
+        .outer_iter()
+        .enumerate()
+        .map(|(ch, wav)| {
+            let post_true_peak_dB = post_true_peaks[ch].dB_from_amp_default();
+            ChannelHeadroom {
+                pre_true_peak_dB: pre_true_peaks[channel_order[ch]].dB_from_amp_default(),
+                guard_clipping_gain_dB: guard_clip_stats
+                    .get(ch)
+                    .or(guard_clip_stats.get(0))
+                    .map_or(0., |stats| stats.max_reduction_gain_dB as f64),
+                post_peak_dB: wav.max_peak().dB_from_amp_default() as f64,
+                post_true_peak_dB,
+                headroom_dB: -post_true_peak_dB,
+            }
+        })
+        .collect();
+    let max_of =
+        |f: fn(&ChannelHeadroom) -> f64| channels.iter().map(f).fold(f64::NEG_INFINITY, f64::max);
+    let post_true_peak_dB = max_of(|x| x.post_true_peak_dB);
+    HeadroomReport {
+        pre_true_peak_dB: max_of(|x| x.pre_true_peak_dB),
+        normalize_gain_dB: normalize_gain.dB_from_amp_default() as f64,
+        guard_clipping_gain_dB: -max_of(|x| -x.guard_clipping_gain_dB),
+        post_peak_dB: max_of(|x| x.post_peak_dB),
+        post_true_peak_dB,
+        headroom_dB: -post_true_peak_dB,
+        channels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn headroom_report_works() {
+        let sr = 48000;
+        let sine = |amp: f32| {
+            Array1::from_shape_fn(sr as usize, |i| {
+                (2. * std::f32::consts::PI * 997. * i as f32 / sr as f32).sin() * amp
+            })
+        };
+        let original = ndarray::stack![Axis(0), sine(0.5), sine(0.25)];
+        // normalized by +6 dB and channels swapped
+        let processed = ndarray::stack![Axis(0), sine(0.5), sine(1.)];
+        let stats = Array1::from_elem(2, GuardClippingStats::default());
+        let report = calc_headroom_report(
+            original.view(),
+            processed.view(),
+            &[1, 0],
+            sr,
+            2.,
+            stats.view(),
+        );
+        assert_eq!(report.channels.len(), 2);
+        assert_abs_diff_eq!(report.normalize_gain_dB, 6.02, epsilon = 0.01);
+        assert_abs_diff_eq!(report.channels[1].pre_true_peak_dB, -6.02, epsilon = 0.1);
+        assert_abs_diff_eq!(report.channels[0].pre_true_peak_dB, -12.04, epsilon = 0.1);
+        assert_abs_diff_eq!(report.post_peak_dB, 0., epsilon = 0.01);
+        assert!(report.post_true_peak_dB >= report.post_peak_dB - 0.01);
+        assert_abs_diff_eq!(report.headroom_dB, -report.post_true_peak_dB);
+        assert_eq!(report.guard_clipping_gain_dB, 0.);
+    }
+}
@@ -106,6 +106,25 @@ impl PerfectLimiter {
         (out, gain as f32)
     }
 
+    /// process one stereo frame without allocation (for the output stream of the player),
+    /// and returns (delayed_output, gain). reset(2) should be called before the first frame.
+    pub fn step_stereo(&mut self, (left, right): (f32, f32)) -> ((f32, f32), f32) {
+        let i_buf = self.i_buf;
+        let delayed = (self.buffer[[i_buf, 0]], self.buffer[[i_buf, 1]]);
+        let gain = self.calc_gain(aview1(&[left, right]));
+        self.buffer[[i_buf, 0]] = left as f64;
+        self.buffer[[i_buf, 1]] = right as f64;
+        self.i_buf = (i_buf + 1) % self.buffer.shape()[0];
+        let limit = |x: f64| (x * gain).clamp(-1., 1.) as f32;
+        ((limit(delayed.0), limit(delayed.1)), gain as f32)
+    }
+
+    #[inline]
+    pub fn set_threshold(&mut self, threshold: f64) {
+        debug_assert!(threshold > f32::EPSILON as f64);
+        self.threshold = threshold;
+    }
+
     /// apply limiter to wav inplace , return gain array. parallel over channel axis (=Axis(0))
     pub fn process_inplace(&mut self, mut wavs: ArrayViewMut2<f32>) -> Array1<f32> {
         self.reset(0);
@@ -288,4 +307,26 @@ mod tests {
         }
         writer.finalize().unwrap();
     }
+
+    #[test]
+    fn step_stereo_works() {
+        let sr = 48000;
+        let wav = Array1::from_shape_fn(sr as usize, |i| {
+            4. * (2. * std::f32::consts::PI * 440. * i as f32 / sr as f32).sin()
+        });
+        let mut limiter = PerfectLimiter::with_default(sr);
+        limiter.reset(2);
+        let outputs: Vec<_> = wav.iter().map(|&x| limiter.step_stereo((x, -x))).collect();
+        assert!(outputs.iter().all(|&((l, r), _)| l.abs() <= 1. && l == -r));
+        assert!(outputs.iter().any(|&(_, gain)| gain < 0.3));
+
+        // the same gain as the offline process
+        let mut wavs = ndarray::stack(Axis(0), &[wav.view(), (-&wav).view()]).unwrap();
+        let gain_seq = PerfectLimiter::with_default(sr).process_inplace(wavs.view_mut());
+        let latency = limiter._latency_samples();
+        for (i, &((l, _), _)) in outputs.iter().enumerate().skip(latency).step_by(97) {
+            assert!((l - wavs[[0, i - latency]]).abs() < 1e-4);
+        }
+        assert_eq!(gain_seq.len(), wav.len());
+    }
 }
@@ -0,0 +1,120 @@
+//! Loudness of consecutive segments (e.g. per minute) for compliance checks of long programs
+
+use std::fmt::Write;
+
+use ebur128::{EbuR128, Mode as LoudnessMode};
+use napi_derive::napi;
+use ndarray::prelude::*;
+
+use super::super::utils::Planes;
+
+/// interval of measuring the momentary and short-term loudness (EBU Tech 3341: at least 10 Hz)
+pub(super) const MEASURE_INTERVAL_SEC: f64 = 0.1;
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SegmentLoudness {
+    pub start_sec: f64,
+    pub end_sec: f64,
+    /// integrated loudness of the segment (gated as the global loudness)
+    #[napi(js_name = "integratedLUFS")]
+    pub integrated_lufs: f64,
+    /// maximum momentary loudness (400 ms window) in the segment
+    #[napi(js_name = "maxMomentaryLUFS")]
+    pub max_momentary_lufs: f64,
+    /// maximum short-term loudness (3 s window) in the segment
+    #[napi(js_name = "maxShortTermLUFS")]
+    pub max_short_term_lufs: f64,
+}
+
+/// Loudness of each segment_sec of wavs (the last segment can be shorter).
+/// The momentary/short-term windows continue over the segment boundaries.
+pub fn calc_segmented_loudness(
+    wavs: ArrayView2<f32>,
+    sr: u32,
+    segment_sec: f64,
+) -> Vec<SegmentLoudness> {
+    let n_ch = wavs.shape()[0] as u32;
+    let len = wavs.shape()[1];
+    let segment_len = ((segment_sec * sr as f64).round() as usize).max(1);
+    let interval = ((MEASURE_INTERVAL_SEC * sr as f64).round() as usize).max(1);
+    let mut sliding = EbuR128::new(n_ch, sr, LoudnessMode::M | LoudnessMode::S).unwrap();
+    let mut integrated = EbuR128::new(n_ch, sr, LoudnessMode::I).unwrap();
+
+    (0..len)
+        .step_by(segment_len)
+        .map(|i_start| {
+            let i_end = (i_start + segment_len).min(len);
+            integrated.reset();
+            let mut max_momentary_lufs = f64::NEG_INFINITY;
+            let mut max_short_term_lufs = f64::NEG_INFINITY;
+            for i in (i_start..i_end).step_by(interval) {
+                let chunk = wavs.slice(s![.., i..(i + interval).min(i_end)]);
+                sliding.add_frames_planar_f32(&chunk.planes()).unwrap();
+                integrated.add_frames_planar_f32(&chunk.planes()).unwrap();
+                max_momentary_lufs = max_momentary_lufs.max(sliding.loudness_momentary().unwrap());
+                max_short_term_lufs =
+                    max_short_term_lufs.max(sliding.loudness_shortterm().unwrap());
+            }
+            SegmentLoudness {
+                start_sec: i_start as f64 / sr as f64,
+                end_sec: i_end as f64 / sr as f64,
+                integrated_lufs: integrated.loudness_global().unwrap(),
+                max_momentary_lufs,
+                max_short_term_lufs,
+            }
+        })
+        .collect()
+}
+
+/// CSV table with a header row. Silent values are written as -inf.
+pub fn segmented_loudness_to_csv(segments: &[SegmentLoudness]) -> String {
+    let mut csv =
+        String::from("start_sec,end_sec,integrated_LUFS,max_momentary_LUFS,max_short_term_LUFS\n");
+    for segment in segments {
+        writeln!(
+            csv,
+            "{:.3},{:.3},{:.2},{:.2},{:.2}",
+            segment.start_sec,
+            segment.end_sec,
+            segment.integrated_lufs,
+            segment.max_momentary_lufs,
+            segment.max_short_term_lufs
+        )
+        .unwrap();
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn segmented_loudness_works() {
+        let sr = 48000;
+        // 1 kHz sine of -20 dBFS for 5 sec then silence for 2.5 sec
+        let wavs = Array2::from_shape_fn((1, sr as usize * 15 / 2), |(_, i)| {
+            if i < sr as usize * 5 {
+                0.1 * (2. * std::f32::consts::PI * 1000. * i as f32 / sr as f32).sin()
+            } else {
+                0.
+            }
+        });
+        let segments = calc_segmented_loudness(wavs.view(), sr, 2.);
+        assert_eq!(segments.len(), 4);
+        assert_abs_diff_eq!(segments[3].start_sec, 6.);
+        assert_abs_diff_eq!(segments[3].end_sec, 7.5);
+        assert_abs_diff_eq!(segments[0].integrated_lufs, -23., epsilon = 0.1);
+        assert_abs_diff_eq!(segments[1].max_momentary_lufs, -23., epsilon = 0.1);
+        assert_eq!(segments[3].integrated_lufs, f64::NEG_INFINITY);
+        // the short-term window still includes the sine at the start of the last segment
+        assert!(segments[3].max_short_term_lufs > -40.);
+
+        let csv = segmented_loudness_to_csv(&segments);
+        assert_eq!(csv.lines().count(), 5);
+        assert!(csv.lines().nth(1).unwrap().starts_with("0.000,2.000,-23.0"));
+    }
+}
@@ -25,8 +25,14 @@ pub trait Normalize {
         target: NormalizeTarget,
         guard_clipping_mode: GuardClippingMode,
     ) {
+        let gain = self.normalize_gain(target);
+        self.apply_gain(gain, guard_clipping_mode);
+    }
+
+    /// linear gain to the target (before guard clipping)
+    fn normalize_gain(&self, target: NormalizeTarget) -> f32 {
         // TODO: guard clipping can make lufs/rms different from target
-        let gain = match target {
+        match target {
             NormalizeTarget::LUFS(target_lufs) => {
                 10f32.powf((target_lufs - self.stats_for_normalize().global_lufs as f32) / 20.)
             }
@@ -40,8 +46,7 @@ pub trait Normalize {
                 10f32.powf((target_peak_dB - self.stats_for_normalize().max_peak_dB) / 20.)
             }
             NormalizeTarget::Off => 1.,
-        };
-        self.apply_gain(gain, guard_clipping_mode);
+        }
     }
 
     fn stats_for_normalize(&self) -> &AudioStats;
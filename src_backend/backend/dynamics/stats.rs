@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
 use ebur128::{EbuR128, Mode as LoudnessMode};
+use napi_derive::napi;
 use ndarray::prelude::*;
 use ndarray::{Data, RemoveAxis};
 use ndarray_stats::{MaybeNan, QuantileExt};
@@ -10,6 +11,7 @@ use rayon::prelude::*;
 use super::super::utils::Planes;
 use super::decibel::DeciBel;
 use super::guardclipping::GuardClippingResult;
+use super::loudness::MEASURE_INTERVAL_SEC;
 
 #[readonly::make]
 #[derive(Clone, PartialEq)]
@@ -19,6 +21,69 @@ pub struct AudioStats {
     pub rms_dB: f32,
     pub max_peak: f32,
     pub max_peak_dB: f32,
+    /// loudness range (LU, EBU Tech 3342)
+    pub loudness_range: f64,
+    /// maximum short-term loudness (3 s window)
+    pub max_short_term_lufs: f64,
+    /// maximum true peak of all channels (dBTP)
+    pub max_true_peak_dB: f32,
+    /// integrated loudness of each channel measured alone
+    pub channel_lufs: Vec<f64>,
+    pub channel_rms_dB: Vec<f32>,
+    pub channel_max_peak_dB: Vec<f32>,
+}
+
+impl AudioStats {
+    pub fn dynamics(&self) -> DynamicsStats {
+        DynamicsStats {
+            loudness_range: self.loudness_range,
+            crest_factor_dB: (self.max_peak_dB - self.rms_dB) as f64,
+            psr: self.max_true_peak_dB as f64 - self.max_short_term_lufs,
+            plr: self.max_true_peak_dB as f64 - self.global_lufs,
+        }
+    }
+
+    /// stats of the channel. None if the channel doesn't exist.
+    pub fn channel(&self, ch: usize) -> Option<ChannelStats> {
+        Some(ChannelStats {
+            global_lufs: *self.channel_lufs.get(ch)?,
+            rms_dB: *self.channel_rms_dB.get(ch)? as f64,
+            max_peak_dB: *self.channel_max_peak_dB.get(ch)? as f64,
+        })
+    }
+}
+
+/// loudness and level of a channel for quantifying the imbalance between channels
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+#[allow(non_snake_case)]
+pub struct ChannelStats {
+    /// integrated loudness of the channel measured alone (as a mono signal)
+    #[napi(js_name = "globalLUFS")]
+    pub global_lufs: f64,
+    #[napi(js_name = "rmsdB")]
+    pub rms_dB: f64,
+    #[napi(js_name = "maxPeakdB")]
+    pub max_peak_dB: f64,
+}
+
+/// dynamics measurements for comparing masters. Not finite for the silent audio.
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+#[allow(non_snake_case)]
+pub struct DynamicsStats {
+    /// loudness range (LU, EBU Tech 3342)
+    #[napi(js_name = "LRA")]
+    pub loudness_range: f64,
+    /// max sample peak to RMS ratio (dB)
+    #[napi(js_name = "crestFactordB")]
+    pub crest_factor_dB: f64,
+    /// max true peak to max short-term loudness ratio (dB)
+    #[napi(js_name = "PSR")]
+    pub psr: f64,
+    /// max true peak to integrated loudness ratio (dB)
+    #[napi(js_name = "PLR")]
+    pub plr: f64,
 }
 
 pub struct StatCalculator(EbuR128);
@@ -35,32 +100,77 @@ impl StatCalculator {
 
     pub fn calc(&mut self, wavs: ArrayView2<f32>) -> AudioStats {
         self.0.reset();
-        let (global_lufs, mean_squared) = rayon::join(
+        let sr = self.0.rate();
+        let (
+            (global_lufs, loudness_range, max_short_term_lufs),
+            (channel_lufs, channel_mean_squared),
+        ) = rayon::join(
             || {
-                self.0.add_frames_planar_f32(&wavs.planes()).unwrap();
-                self.0.loudness_global().unwrap()
+                // fed by the interval to follow the maximum short-term loudness
+                let interval = ((MEASURE_INTERVAL_SEC * sr as f64).round() as usize).max(1);
+                let mut max_short_term_lufs = f64::NEG_INFINITY;
+                for i in (0..wavs.shape()[1]).step_by(interval) {
+                    let chunk = wavs.slice(s![.., i..(i + interval).min(wavs.shape()[1])]);
+                    self.0.add_frames_planar_f32(&chunk.planes()).unwrap();
+                    max_short_term_lufs =
+                        max_short_term_lufs.max(self.0.loudness_shortterm().unwrap());
+                }
+                (
+                    self.0.loudness_global().unwrap(),
+                    self.0.loudness_range().unwrap(),
+                    max_short_term_lufs,
+                )
             },
             || {
-                let n_elem = wavs.len();
                 wavs.axis_iter(Axis(0))
                     .into_par_iter()
-                    .map(|x| x.iter().map(|x| x.powi(2)).sum::<f32>())
-                    .sum::<f32>()
-                    / n_elem as f32
+                    .map(|wav| {
+                        let mut analyzer = EbuR128::new(1, sr, LoudnessMode::I).unwrap();
+                        analyzer
+                            .add_frames_planar_f32(&[wav.to_slice().unwrap()])
+                            .unwrap();
+                        let mean_squared =
+                            wav.iter().map(|x| x.powi(2)).sum::<f32>() / wav.len() as f32;
+                        (analyzer.loudness_global().unwrap(), mean_squared)
+                    })
+                    .unzip::<_, _, Vec<_>, Vec<_>>()
             },
         );
 
+        // all channels have the same length
+        let mean_squared = channel_mean_squared.iter().sum::<f32>() / wavs.shape()[0] as f32;
         #[allow(non_snake_case)]
         let rms_dB = mean_squared.dB_from_power_default();
+        #[allow(non_snake_case)]
+        let channel_rms_dB = channel_mean_squared
+            .into_iter()
+            .map(|x| x.dB_from_power_default())
+            .collect();
+        #[allow(non_snake_case)]
+        let channel_max_peak_dB = wavs
+            .axis_iter(Axis(0))
+            .map(|wav| wav.max_peak().dB_from_amp_default())
+            .collect();
         let max_peak = wavs.max_peak();
         #[allow(non_snake_case)]
         let max_peak_dB = max_peak.dB_from_amp_default();
+        #[allow(non_snake_case)]
+        let max_true_peak_dB = (0..wavs.shape()[0] as u32)
+            .map(|ch| self.0.true_peak(ch).unwrap())
+            .fold(0., f64::max)
+            .dB_from_amp_default() as f32;
 
         AudioStats {
             global_lufs,
             rms_dB,
             max_peak,
             max_peak_dB,
+            loudness_range,
+            max_short_term_lufs,
+            max_true_peak_dB,
+            channel_lufs,
+            channel_rms_dB,
+            channel_max_peak_dB,
         }
     }
 }
@@ -188,3 +298,49 @@ impl<D: Dimension + RemoveAxis> From<GuardClippingResult<D>>
         (&value).into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn dynamics_stats_works() {
+        let sr = 48000;
+        let wavs = Array1::range(0., (sr * 10) as f32, 1.)
+            .mapv(|i| 0.5 * (2. * std::f32::consts::PI * 1000. * i / sr as f32).sin())
+            .insert_axis(Axis(0));
+        let dynamics = StatCalculator::new(1, sr).calc(wavs.view()).dynamics();
+        // sine wave
+        assert_abs_diff_eq!(dynamics.crest_factor_dB, 3.01, epsilon = 0.01);
+        // steady loudness
+        assert_abs_diff_eq!(dynamics.loudness_range, 0., epsilon = 0.1);
+        assert_abs_diff_eq!(dynamics.psr, dynamics.plr, epsilon = 0.1);
+        assert!(dynamics.plr > 0.);
+    }
+
+    #[test]
+    fn channel_stats_works() {
+        let sr = 48000;
+        let sine = Array1::range(0., (sr * 5) as f32, 1.)
+            .mapv(|i| (2. * std::f32::consts::PI * 1000. * i / sr as f32).sin());
+        // the right channel is 6 dB quieter
+        let wavs = ndarray::stack![Axis(0), sine.mapv(|x| 0.5 * x), sine.mapv(|x| 0.25 * x)];
+        let stats = StatCalculator::new(2, sr).calc(wavs.view());
+        let (left, right) = (stats.channel(0).unwrap(), stats.channel(1).unwrap());
+        assert_abs_diff_eq!(left.global_lufs - right.global_lufs, 6.02, epsilon = 0.01);
+        assert_abs_diff_eq!(left.rms_dB - right.rms_dB, 6.02, epsilon = 0.01);
+        assert_abs_diff_eq!(left.max_peak_dB, stats.max_peak_dB as f64);
+        assert_abs_diff_eq!(right.max_peak_dB, -12.04, epsilon = 0.01);
+        assert!(stats.channel(2).is_none());
+
+        let mono = StatCalculator::new(1, sr).calc(wavs.slice(s![0..1, ..]));
+        assert_abs_diff_eq!(
+            mono.channel(0).unwrap().global_lufs,
+            mono.global_lufs,
+            epsilon = 1e-6
+        );
+        assert_abs_diff_eq!(mono.channel_rms_dB[0], mono.rms_dB);
+    }
+}
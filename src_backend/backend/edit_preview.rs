@@ -0,0 +1,155 @@
+//! Simple edits of a selection (reverse, fades, gain) rendered to a copy of the track,
+//! so that what-if edits can be auditioned without an external editor.
+//! The source track is never modified.
+
+use napi_derive::napi;
+use ndarray::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::dynamics::DeciBel;
+
+const MAX_GAIN_DB: f64 = 40.;
+
+#[napi(string_enum)]
+#[derive(Debug, Eq, PartialEq)]
+pub enum SelectionTransformKind {
+    Reverse,
+    /// linear fade from silence over the selection
+    FadeIn,
+    /// linear fade to silence over the selection
+    FadeOut,
+    /// gain of gaindB
+    Gain,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+#[allow(non_snake_case)]
+pub struct SelectionTransformParams {
+    /// gain of Gain
+    #[serde(rename = "gaindB")]
+    pub gain_dB: f64,
+}
+
+impl Default for SelectionTransformParams {
+    fn default() -> Self {
+        SelectionTransformParams { gain_dB: 0. }
+    }
+}
+
+impl SelectionTransformParams {
+    pub fn validate(&self, kind: SelectionTransformKind) -> Result<(), String> {
+        match kind {
+            SelectionTransformKind::Gain
+                if !(-MAX_GAIN_DB..=MAX_GAIN_DB).contains(&self.gain_dB) =>
+            {
+                Err(format!(
+                    "The gain should be in [-{0}, {0}] dB.",
+                    MAX_GAIN_DB
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// file stem suffix describing the edit (e.g. "gain-6dB")
+    pub fn suffix(&self, kind: SelectionTransformKind) -> String {
+        match kind {
+            SelectionTransformKind::Reverse => "reversed".into(),
+            SelectionTransformKind::FadeIn => "fade_in".into(),
+            SelectionTransformKind::FadeOut => "fade_out".into(),
+            SelectionTransformKind::Gain => format!("gain{:+}dB", self.gain_dB),
+        }
+    }
+}
+
+/// copy of wavs with the samples in i_range (start, end) transformed
+pub fn transform_selection(
+    wavs: ArrayView2<f32>,
+    (i_start, i_end): (usize, usize),
+    kind: SelectionTransformKind,
+    params: &SelectionTransformParams,
+) -> Array2<f32> {
+    let mut output = wavs.to_owned();
+    let mut selection = output.slice_mut(s![.., i_start..i_end]);
+    let len = selection.shape()[1];
+    let ramp = |i: usize| (i as f64 + 0.5) as f32 / len as f32;
+    match kind {
+        SelectionTransformKind::Reverse => {
+            let reversed = wavs.slice(s![.., i_start..i_end;-1]);
+            selection.assign(&reversed);
+        }
+        SelectionTransformKind::FadeIn => {
+            for mut wav in selection.rows_mut() {
+                wav.indexed_iter_mut().for_each(|(i, x)| *x *= ramp(i));
+            }
+        }
+        SelectionTransformKind::FadeOut => {
+            for mut wav in selection.rows_mut() {
+                wav.indexed_iter_mut()
+                    .for_each(|(i, x)| *x *= ramp(len - 1 - i));
+            }
+        }
+        SelectionTransformKind::Gain => {
+            let gain = params.gain_dB.amp_from_dB_default() as f32;
+            selection.mapv_inplace(|x| x * gain);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use ndarray::array;
+
+    use super::*;
+
+    #[test]
+    fn transform_selection_works() {
+        let wavs = array![[1f32, 2., 3., 4., 5.], [1., 1., 1., 1., 1.]];
+        let params = SelectionTransformParams::default();
+        let transform = |kind| transform_selection(wavs.view(), (1, 4), kind, &params);
+
+        let reversed = transform(SelectionTransformKind::Reverse);
+        assert_eq!(reversed.row(0), array![1., 4., 3., 2., 5.]);
+
+        let faded_in = transform(SelectionTransformKind::FadeIn);
+        assert_abs_diff_eq!(
+            faded_in.row(1),
+            array![1., 1. / 6., 0.5, 5. / 6., 1.],
+            epsilon = 1e-6
+        );
+        let faded_out = transform(SelectionTransformKind::FadeOut);
+        assert_abs_diff_eq!(
+            faded_out.row(1),
+            array![1., 5. / 6., 0.5, 1. / 6., 1.],
+            epsilon = 1e-6
+        );
+
+        let params = SelectionTransformParams { gain_dB: -6. };
+        let gained =
+            transform_selection(wavs.view(), (0, 2), SelectionTransformKind::Gain, &params);
+        assert_abs_diff_eq!(gained[[0, 1]], 2. * 0.501187, epsilon = 1e-5);
+        assert_eq!(gained[[0, 2]], 3.);
+
+        // empty selection
+        assert_eq!(
+            transform_selection(
+                wavs.view(),
+                (2, 2),
+                SelectionTransformKind::FadeOut,
+                &params
+            ),
+            wavs
+        );
+        assert!(params.validate(SelectionTransformKind::Gain).is_ok());
+        let params = SelectionTransformParams { gain_dB: f64::NAN };
+        assert!(params.validate(SelectionTransformKind::Gain).is_err());
+        assert!(params.validate(SelectionTransformKind::Reverse).is_ok());
+        assert_eq!(
+            SelectionTransformParams { gain_dB: -6. }.suffix(SelectionTransformKind::Gain),
+            "gain-6dB"
+        );
+    }
+}
@@ -0,0 +1,401 @@
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+use napi_derive::napi;
+use ndarray::prelude::*;
+use rayon::prelude::*;
+use realfft::num_complex::Complex;
+
+use super::dynamics::DeciBel;
+
+const HIGHPASS_HZ: f64 = 20.;
+// pole frequencies of A-weighting (IEC 61672-1)
+const A_WEIGHTING_HZ: [f64; 4] = [20.598997, 107.65265, 737.86223, 12194.217];
+// Q of the two sections of a 4th-order butterworth filter
+const BUTTERWORTH4_Q: [f64; 2] = [0.5411961, 1.3065630];
+// high shelf (hz, gain dB, Q) and high-pass (hz, Q) of K-weighting (ITU-R BS.1770)
+// in the analog prototypes of libebur128, so that any sample rate gives the same response
+const K_SHELF: (f64, f64, f64) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+const K_HIGHPASS: (f64, f64) = (38.13547087602444, 0.5003270373238773);
+/// duration of the crossfade at the time edges of the solo box
+const SOLO_FADE_SEC: f64 = 0.01;
+/// signal outside the solo box filtered together so that the filters settle at the edges
+const SOLO_MARGIN_SEC: f64 = 0.05;
+
+#[napi(string_enum)]
+#[derive(Default, Eq, PartialEq)]
+pub enum WavViewMode {
+    #[default]
+    Original,
+    /// 20 Hz high-passed
+    HighPass,
+    AWeighted,
+}
+
+impl WavViewMode {
+    pub fn filter_cascade(&self, sr: u32) -> Option<FilterCascade> {
+        match self {
+            WavViewMode::Original => None,
+            WavViewMode::HighPass => Some(FilterCascade::highpass(HIGHPASS_HZ, sr)),
+            WavViewMode::AWeighted => Some(FilterCascade::a_weighting(sr)),
+        }
+    }
+}
+
+/// biquad filter with normalized a0
+#[derive(Clone, Copy, Debug)]
+pub struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+impl Biquad {
+    /// bilinear transform of s / (s + w) with prewarping
+    pub fn first_order_highpass(hz: f64, sr: u32) -> Self {
+        let (w, k) = Self::prewarp(hz, sr);
+        Biquad {
+            b: [k / (k + w), -k / (k + w), 0.],
+            a: [(w - k) / (k + w), 0.],
+        }
+    }
+
+    /// bilinear transform of w / (s + w) with prewarping
+    pub fn first_order_lowpass(hz: f64, sr: u32) -> Self {
+        let (w, k) = Self::prewarp(hz, sr);
+        Biquad {
+            b: [w / (k + w), w / (k + w), 0.],
+            a: [(w - k) / (k + w), 0.],
+        }
+    }
+
+    /// 2nd-order butterworth high-pass filter from RBJ Audio EQ Cookbook
+    #[inline]
+    pub fn butterworth_highpass(hz: f64, sr: u32) -> Self {
+        Self::highpass(hz, sr, FRAC_1_SQRT_2)
+    }
+
+    /// 2nd-order high-pass filter from RBJ Audio EQ Cookbook
+    pub fn highpass(hz: f64, sr: u32, q: f64) -> Self {
+        let w0 = 2. * PI * hz / sr as f64;
+        let (sin_w0, cos_w0) = w0.sin_cos();
+        let alpha = sin_w0 / (2. * q);
+        let a0 = 1. + alpha;
+        Biquad {
+            b: [
+                (1. + cos_w0) / 2. / a0,
+                -(1. + cos_w0) / a0,
+                (1. + cos_w0) / 2. / a0,
+            ],
+            a: [-2. * cos_w0 / a0, (1. - alpha) / a0],
+        }
+    }
+
+    /// 2nd-order low-pass filter from RBJ Audio EQ Cookbook
+    pub fn lowpass(hz: f64, sr: u32, q: f64) -> Self {
+        let w0 = 2. * PI * hz / sr as f64;
+        let (sin_w0, cos_w0) = w0.sin_cos();
+        let alpha = sin_w0 / (2. * q);
+        let a0 = 1. + alpha;
+        Biquad {
+            b: [
+                (1. - cos_w0) / 2. / a0,
+                (1. - cos_w0) / a0,
+                (1. - cos_w0) / 2. / a0,
+            ],
+            a: [-2. * cos_w0 / a0, (1. - alpha) / a0],
+        }
+    }
+
+    /// returns (analog angular freq, bilinear transform constant)
+    fn prewarp(hz: f64, sr: u32) -> (f64, f64) {
+        let sr = sr as f64;
+        let w = 2. * PI * hz;
+        let k = if hz < 0.45 * sr {
+            w / (w / (2. * sr)).tan()
+        } else {
+            2. * sr
+        };
+        (w, k)
+    }
+
+    pub fn response_at(&self, hz: f64, sr: u32) -> Complex<f64> {
+        let z_inv = Complex::from_polar(1., -2. * PI * hz / sr as f64);
+        let z_inv2 = z_inv * z_inv;
+        (self.b[0] + self.b[1] * z_inv + self.b[2] * z_inv2)
+            / (1. + self.a[0] * z_inv + self.a[1] * z_inv2)
+    }
+
+    /// direct form II transposed
+    pub fn process_inplace(&self, mut wav: ArrayViewMut1<f64>) {
+        let (mut s1, mut s2) = (0., 0.);
+        for x in wav.iter_mut() {
+            let y = self.b[0].mul_add(*x, s1);
+            s1 = self.b[1].mul_add(*x, -self.a[0] * y) + s2;
+            s2 = self.b[2].mul_add(*x, -self.a[1] * y);
+            *x = y;
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FilterCascade {
+    sections: Vec<Biquad>,
+    gain: f64,
+}
+
+impl FilterCascade {
+    pub fn highpass(hz: f64, sr: u32) -> Self {
+        FilterCascade {
+            sections: vec![Biquad::butterworth_highpass(hz, sr)],
+            gain: 1.,
+        }
+    }
+
+    /// A-weighting normalized to 0 dB at 1 kHz
+    pub fn a_weighting(sr: u32) -> Self {
+        let [f1, f2, f3, f4] = A_WEIGHTING_HZ;
+        let sections = vec![
+            Biquad::first_order_highpass(f1, sr),
+            Biquad::first_order_highpass(f1, sr),
+            Biquad::first_order_highpass(f2, sr),
+            Biquad::first_order_highpass(f3, sr),
+            Biquad::first_order_lowpass(f4, sr),
+            Biquad::first_order_lowpass(f4, sr),
+        ];
+        let mut cascade = FilterCascade { sections, gain: 1. };
+        cascade.gain = cascade.response_at(1000., sr).norm().recip();
+        cascade
+    }
+
+    /// K-weighting of ITU-R BS.1770 (the gain at 1 kHz is about +0.691 dB)
+    pub fn k_weighting(sr: u32) -> Self {
+        let (hz, gain_dB, q) = K_SHELF;
+        let k = (PI * hz / sr as f64).tan();
+        let vh = 10f64.powf(gain_dB / 20.);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1. + k / q + k * k;
+        let shelf = Biquad {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2. * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+        };
+        let (hz, q) = K_HIGHPASS;
+        let k = (PI * hz / sr as f64).tan();
+        let a0 = 1. + k / q + k * k;
+        let highpass = Biquad {
+            b: [1., -2., 1.],
+            a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+        };
+        FilterCascade {
+            sections: vec![shelf, highpass],
+            gain: 1.,
+        }
+    }
+
+    /// 4th-order butterworth filters passing hz_range (min, max).
+    /// None if the range covers 0 Hz ~ the Nyquist frequency.
+    pub fn bandpass(hz_range: (f64, f64), sr: u32) -> Option<Self> {
+        let nyquist = sr as f64 / 2.;
+        let mut sections = Vec::with_capacity(4);
+        if hz_range.0 > 0. && hz_range.0 < nyquist {
+            sections.extend(BUTTERWORTH4_Q.map(|q| Biquad::highpass(hz_range.0, sr, q)));
+        }
+        if hz_range.1 > 0. && hz_range.1 < nyquist {
+            sections.extend(BUTTERWORTH4_Q.map(|q| Biquad::lowpass(hz_range.1, sr, q)));
+        }
+        (!sections.is_empty()).then_some(FilterCascade { sections, gain: 1. })
+    }
+
+    pub fn response_at(&self, hz: f64, sr: u32) -> Complex<f64> {
+        self.sections
+            .iter()
+            .fold(Complex::new(self.gain, 0.), |acc, section| {
+                acc * section.response_at(hz, sr)
+            })
+    }
+
+    #[allow(non_snake_case)]
+    pub fn magnitude_dB_at(&self, hz: f64, sr: u32) -> f64 {
+        self.response_at(hz, sr).norm().dB_from_amp_default()
+    }
+
+    pub fn filter(&self, wav: ArrayView1<f32>) -> Array1<f32> {
+        let mut buf = wav.mapv(|x| x as f64);
+        for section in &self.sections {
+            section.process_inplace(buf.view_mut());
+        }
+        buf.mapv(|x| (x * self.gain) as f32)
+    }
+
+    /// zero-phase filtering (forward and backward). The magnitude response is squared.
+    pub fn filtfilt(&self, wav: ArrayView1<f32>) -> Array1<f32> {
+        let mut buf = wav.mapv(|x| x as f64);
+        for section in &self.sections {
+            section.process_inplace(buf.view_mut());
+        }
+        for section in &self.sections {
+            section.process_inplace(buf.slice_mut(s![..;-1]));
+        }
+        let gain = self.gain * self.gain;
+        buf.mapv(|x| (x * gain) as f32)
+    }
+}
+
+/// Time-frequency box soloed in the playback. The outside of the box is silent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TfBoxSolo {
+    /// (start, end) in the time of the track (sec)
+    pub sec_range: (f64, f64),
+    /// (min, max) Hz
+    pub hz_range: (f32, f32),
+}
+
+impl TfBoxSolo {
+    /// (start, end) indices of the box in the audio stretched by stretch_ratio (not clamped)
+    fn box_idx_range(&self, sr: u32, stretch_ratio: f64) -> (usize, usize) {
+        let sec_to_idx = |sec: f64| (sec * stretch_ratio * sr as f64).round().max(0.) as usize;
+        (sec_to_idx(self.sec_range.0), sec_to_idx(self.sec_range.1))
+    }
+
+    /// Range of the audio (of len samples, stretched by stretch_ratio) needed to solo the box,
+    /// i.e. the box with the margin for the band-pass filter to settle.
+    /// The outside of it is silent after apply.
+    pub fn segment_range(&self, len: usize, sr: u32, stretch_ratio: f64) -> (usize, usize) {
+        let (i_start, i_end) = self.box_idx_range(sr, stretch_ratio);
+        let n_margin = (SOLO_MARGIN_SEC * sr as f64).round() as usize;
+        (
+            i_start.saturating_sub(n_margin).min(len),
+            (i_end + n_margin).min(len),
+        )
+    }
+
+    /// Zero-phase band-pass filter the box of wavs with the crossfade at the time edges.
+    /// wavs are stretched by stretch_ratio from the time of the track.
+    #[inline]
+    pub fn apply(&self, wavs: ArrayView2<f32>, sr: u32, stretch_ratio: f64) -> Array2<f32> {
+        self.apply_at(wavs, 0, sr, stretch_ratio)
+    }
+
+    /// the same as apply, but wavs is the part of the audio from i_offset (e.g. segment_range)
+    pub fn apply_at(
+        &self,
+        wavs: ArrayView2<f32>,
+        i_offset: usize,
+        sr: u32,
+        stretch_ratio: f64,
+    ) -> Array2<f32> {
+        let len = wavs.shape()[1];
+        let sr_f64 = sr as f64;
+        let (i_start, i_end) = self.box_idx_range(sr, stretch_ratio);
+        let (i_start, i_end) = (
+            i_start.saturating_sub(i_offset).min(len),
+            i_end.saturating_sub(i_offset).min(len),
+        );
+        let mut output = Array2::zeros(wavs.raw_dim());
+        if i_start >= i_end {
+            return output;
+        }
+        let n_margin = (SOLO_MARGIN_SEC * sr_f64).round() as usize;
+        let (i_seg_start, i_seg_end) = (
+            i_start.saturating_sub(n_margin),
+            (i_end + n_margin).min(len),
+        );
+        let n_fade = ((SOLO_FADE_SEC * sr_f64).round() as usize).min((i_end - i_start) / 2);
+        let fade_in = Array1::from_shape_fn(n_fade, |i| {
+            (0.5 - 0.5 * (PI * (i as f64 + 0.5) / n_fade as f64).cos()) as f32
+        });
+        let cascade = FilterCascade::bandpass((self.hz_range.0 as f64, self.hz_range.1 as f64), sr);
+        Zip::from(output.rows_mut())
+            .and(wavs.rows())
+            .par_for_each(|mut y, x| {
+                let segment = x.slice(s![i_seg_start..i_seg_end]);
+                let filtered = match &cascade {
+                    Some(cascade) => cascade.filtfilt(segment),
+                    None => segment.to_owned(),
+                };
+                let mut y = y.slice_mut(s![i_start..i_end]);
+                y.assign(&filtered.slice(s![i_start - i_seg_start..i_end - i_seg_start]));
+                let n = y.len();
+                for (i, &g) in fade_in.iter().enumerate() {
+                    y[i] *= g;
+                    y[n - 1 - i] *= g;
+                }
+            });
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn a_weighting_works() {
+        let cascade = FilterCascade::a_weighting(48000);
+        assert_abs_diff_eq!(cascade.magnitude_dB_at(1000., 48000), 0., epsilon = 1e-6);
+        assert_abs_diff_eq!(cascade.magnitude_dB_at(100., 48000), -19.1, epsilon = 0.3);
+        assert_abs_diff_eq!(cascade.magnitude_dB_at(31.5, 48000), -39.4, epsilon = 0.3);
+    }
+
+    #[test]
+    fn k_weighting_works() {
+        for sr in [44100, 48000, 96000] {
+            let cascade = FilterCascade::k_weighting(sr);
+            assert_abs_diff_eq!(cascade.magnitude_dB_at(1000., sr), 0.691, epsilon = 0.02);
+            assert_abs_diff_eq!(cascade.magnitude_dB_at(10000., sr), 4., epsilon = 0.2);
+            assert!(cascade.magnitude_dB_at(20., sr) < -10.);
+        }
+    }
+
+    #[test]
+    fn tf_box_solo_works() {
+        let sr = 48000;
+        let sine = |hz: f64| {
+            Array1::from_shape_fn(sr as usize, |i| {
+                (2. * PI * hz * i as f64 / sr as f64).sin() as f32 * 0.5
+            })
+        };
+        let high = sine(5000.);
+        let wav = &sine(100.) + &high;
+        let solo = TfBoxSolo {
+            sec_range: (0.25, 0.75),
+            hz_range: (1000., 10000.),
+        };
+        let output = solo.apply(wav.view().insert_axis(Axis(0)), sr, 1.);
+        let output = output.row(0);
+        assert_eq!(output.len(), wav.len());
+        assert!(output.slice(s![..12000]).iter().all(|&x| x == 0.));
+        assert!(output.slice(s![36000..]).iter().all(|&x| x == 0.));
+        // zero-phase: the passed sine is aligned with the original
+        assert_abs_diff_eq!(
+            output.slice(s![14000..34000]),
+            high.slice(s![14000..34000]),
+            epsilon = 0.02
+        );
+        // crossfade
+        assert!(output[12000].abs() < 0.01);
+        assert!(output[35999].abs() < 0.01);
+
+        // the segment with the margin is enough to solo the box
+        let (i_seg_start, i_seg_end) = solo.segment_range(wav.len(), sr, 1.);
+        assert_eq!((i_seg_start, i_seg_end), (9600, 38400));
+        let segment = wav.slice(s![i_seg_start..i_seg_end]).insert_axis(Axis(0));
+        let output_seg = solo.apply_at(segment, i_seg_start, sr, 1.);
+        assert_eq!(output_seg.row(0), output.slice(s![i_seg_start..i_seg_end]));
+    }
+
+    #[test]
+    fn highpass_blocks_dc() {
+        let cascade = FilterCascade::highpass(20., 48000);
+        assert_abs_diff_eq!(cascade.magnitude_dB_at(1000., 48000), 0., epsilon = 0.01);
+        assert!(cascade.magnitude_dB_at(5., 48000) < -20.);
+
+        let dc = Array1::from_elem(48000, 0.5f32);
+        let filtered = cascade.filter(dc.view());
+        assert!(filtered[47999].abs() < 1e-3);
+    }
+}
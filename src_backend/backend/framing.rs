@@ -0,0 +1,92 @@
+//! Suggested initial view of a newly added track framing its program material,
+//! so that the track doesn't open at a fixed default view
+
+use napi_derive::napi;
+use ndarray::prelude::*;
+
+use super::dynamics::DeciBel;
+
+/// samples quieter than this are regarded as the leading or trailing silence
+#[allow(non_upper_case_globals)]
+const SILENCE_dB: f32 = -60.;
+/// margin on both sides of the program material (ratio to its length)
+const MARGIN_RATIO: f64 = 0.02;
+/// percentile of the absolute amplitude fitted to the amp range
+const AMP_PERCENTILE: f64 = 99.9;
+/// maximum number of samples for calculating the percentile (the others are skipped evenly)
+const MAX_PERCENTILE_SAMPLES: usize = 1 << 20;
+/// headroom of the amp range above the percentile
+const AMP_HEADROOM: f32 = 1.1;
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct InitialView {
+    pub start_sec: f64,
+    pub end_sec: f64,
+    /// (min, max) amplitude of the waveform view
+    pub amp_range: (f64, f64),
+}
+
+/// View skipping the leading and trailing silence of wavs (C x T),
+/// with the amp range fitted to the 99.9th percentile of the absolute amplitude.
+/// The whole track with the amp range of (-1, 1) for the silent track.
+pub fn calc_initial_view(wavs: ArrayView2<f32>, sr: u32) -> InitialView {
+    let len = wavs.shape()[1];
+    let sr = sr as f64;
+    let thr = SILENCE_dB.amp_from_dB_default();
+    let is_loud = |frame: ArrayView1<f32>| frame.iter().any(|x| x.abs() > thr);
+    let Some(i_first) = wavs.axis_iter(Axis(1)).position(is_loud) else {
+        return InitialView {
+            start_sec: 0.,
+            end_sec: len as f64 / sr,
+            amp_range: (-1., 1.),
+        };
+    };
+    let i_last = len - 1 - wavs.axis_iter(Axis(1)).rev().position(is_loud).unwrap();
+
+    let program = wavs.slice(s![.., i_first..=i_last]);
+    let step = program.len().div_ceil(MAX_PERCENTILE_SAMPLES);
+    let mut abs: Vec<_> = program.iter().step_by(step).map(|x| x.abs()).collect();
+    let idx = ((abs.len() - 1) as f64 * AMP_PERCENTILE / 100.).round() as usize;
+    let (_, &mut amp, _) = abs.select_nth_unstable_by(idx, f32::total_cmp);
+    let max_amp = (amp * AMP_HEADROOM).max(thr) as f64;
+
+    let margin = (i_last + 1 - i_first) as f64 / sr * MARGIN_RATIO;
+    InitialView {
+        start_sec: (i_first as f64 / sr - margin).max(0.),
+        end_sec: ((i_last + 1) as f64 / sr + margin).min(len as f64 / sr),
+        amp_range: (-max_amp, max_amp),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn calc_initial_view_works() {
+        let sr = 1000;
+        let mut wavs = Array2::zeros((2, 10000));
+        wavs.slice_mut(s![1, 2000..7000])
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, x)| *x = if i % 2 == 0 { 0.5 } else { -0.5 });
+        let view = calc_initial_view(wavs.view(), sr);
+        assert_abs_diff_eq!(view.start_sec, 2. - 0.1, epsilon = 1e-9);
+        assert_abs_diff_eq!(view.end_sec, 7. + 0.1, epsilon = 1e-9);
+        assert_abs_diff_eq!(view.amp_range.1, 0.55, epsilon = 1e-6);
+        assert_eq!(view.amp_range.0, -view.amp_range.1);
+
+        let view = calc_initial_view(Array2::zeros((1, 500)).view(), sr);
+        assert_eq!(
+            view,
+            InitialView {
+                start_sec: 0.,
+                end_sec: 0.5,
+                amp_range: (-1., 1.),
+            }
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Amplitude histogram and the bit depth actually used by the samples
+//! (e.g. 16-bit data padded to a 24-bit file)
+
+use napi_derive::napi;
+use ndarray::prelude::*;
+
+/// resolution of the fixed-point grid tested by calc_bit_depth_usage
+const MAX_BITS: u32 = 32;
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitDepthUsage {
+    /// bit depth of the coarsest fixed-point grid on which all samples lie.
+    /// 0 if all samples are zero.
+    pub effective_bits: u32,
+    /// false if some samples are not on the 32-bit fixed-point grid (e.g. floating-point data).
+    /// effective_bits is 32 in this case.
+    pub is_fixed_point: bool,
+}
+
+/// number of samples in each of n_bins bins dividing -1 ~ 1 uniformly.
+/// The samples out of the range are counted in the first/last bin.
+pub fn calc_amplitude_histogram(wav: ArrayView1<f32>, n_bins: usize) -> Vec<u32> {
+    let mut counts = vec![0; n_bins];
+    if n_bins == 0 {
+        return counts;
+    }
+    let scale = n_bins as f32 / 2.;
+    for &x in wav {
+        let i = ((x + 1.) * scale).floor().clamp(0., (n_bins - 1) as f32) as usize;
+        counts[i] += 1;
+    }
+    counts
+}
+
+/// Detect the bit depth actually used by the samples.
+/// A sample of N-bit fixed-point data is a multiple of 2^(1 - N), so the trailing zero bits of
+/// the samples on the 32-bit grid tell how many of the lower bits are truncated.
+pub fn calc_bit_depth_usage(wavs: ArrayView2<f32>) -> BitDepthUsage {
+    let scale = (1u64 << (MAX_BITS - 1)) as f64;
+    let mut used_bits = 0u64;
+    let mut is_fixed_point = true;
+    for &x in wavs {
+        let scaled = x as f64 * scale;
+        let rounded = scaled.round();
+        if rounded != scaled {
+            is_fixed_point = false;
+            break;
+        }
+        used_bits |= rounded as i64 as u64;
+    }
+    let effective_bits = if !is_fixed_point {
+        MAX_BITS
+    } else if used_bits == 0 {
+        0
+    } else {
+        MAX_BITS - used_bits.trailing_zeros().min(MAX_BITS - 1)
+    };
+    BitDepthUsage {
+        effective_bits,
+        is_fixed_point,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ndarray_rand::{rand::prelude::*, rand_distr::Uniform, RandomExt};
+
+    #[test]
+    fn amplitude_histogram_works() {
+        let wav = arr1(&[-1.5, -1., -0.4, 0., 0.3, 0.99, 1., 2.]);
+        assert_eq!(calc_amplitude_histogram(wav.view(), 4), vec![2, 1, 2, 3]);
+        assert!(calc_amplitude_histogram(wav.view(), 0).is_empty());
+    }
+
+    #[test]
+    fn bit_depth_usage_works() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let ints = Array2::random_using((2, 1000), Uniform::new(-32768i32, 32768), &mut rng);
+        // 16-bit data in a 24-bit file
+        let wavs = ints.mapv(|x| ((x << 8) as f64 / (1 << 23) as f64) as f32);
+        let usage = calc_bit_depth_usage(wavs.view());
+        assert_eq!(usage.effective_bits, 16);
+        assert!(usage.is_fixed_point);
+
+        let usage = calc_bit_depth_usage(Array2::zeros((1, 10)).view());
+        assert_eq!(usage.effective_bits, 0);
+
+        let usage = calc_bit_depth_usage(arr2(&[[0.1f32, 1e-10]]).view());
+        assert_eq!(usage.effective_bits, 32);
+        assert!(!usage.is_fixed_point);
+    }
+}
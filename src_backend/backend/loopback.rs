@@ -0,0 +1,124 @@
+//! Test signal and delay estimation for measuring the round-trip latency of the audio devices
+
+use std::f64::consts::PI;
+
+use napi_derive::napi;
+use ndarray::prelude::*;
+use realfft::num_complex::Complex;
+use realfft::RealFftPlanner;
+
+pub const CHIRP_SEC: f64 = 0.5;
+const CHIRP_HZ_RANGE: (f64, f64) = (200., 8000.);
+const CHIRP_AMP: f32 = 0.5;
+const CHIRP_FADE_SEC: f64 = 0.01;
+/// normalized cross-correlation below this is regarded as not detected
+const MIN_CONFIDENCE: f32 = 0.1;
+
+#[napi(object)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LatencyMeasurement {
+    /// round-trip latency from handing the test signal to the player to recording it
+    pub latency_ms: f64,
+    /// normalized cross-correlation of the detected test signal (0 ~ 1)
+    pub confidence: f64,
+    /// sample rate of the input device
+    pub sr: u32,
+}
+
+/// exponential sine sweep with short fades at both ends
+pub fn log_chirp(sr: u32) -> Array1<f32> {
+    let len = (CHIRP_SEC * sr as f64).round() as usize;
+    let (start_hz, end_hz) = (CHIRP_HZ_RANGE.0, CHIRP_HZ_RANGE.1.min(sr as f64 * 0.45));
+    let log_ratio = (end_hz / start_hz).ln();
+    let fade_len = ((CHIRP_FADE_SEC * sr as f64).round() as usize).max(1);
+    Array1::from_shape_fn(len, |i| {
+        let t = i as f64 / sr as f64;
+        let phase =
+            2. * PI * start_hz * CHIRP_SEC / log_ratio * ((t / CHIRP_SEC * log_ratio).exp() - 1.);
+        let fade = (i.min(len - 1 - i) as f64 / fade_len as f64).min(1.);
+        (phase.sin() * fade) as f32 * CHIRP_AMP
+    })
+}
+
+/// Find the position of reference in recorded by the cross-correlation.
+/// Returns (delay (samples), normalized cross-correlation at the delay).
+/// None if recorded is shorter than reference or the reference isn't detected.
+pub fn estimate_delay(
+    reference: ArrayView1<f32>,
+    recorded: ArrayView1<f32>,
+) -> Option<(usize, f32)> {
+    if reference.is_empty() || recorded.len() < reference.len() {
+        return None;
+    }
+    let n_fft = (recorded.len() + reference.len()).next_power_of_two();
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(n_fft);
+    let inverse = planner.plan_fft_inverse(n_fft);
+    let spectrum_of = |x: ArrayView1<f32>| {
+        let mut input = forward.make_input_vec();
+        input.iter_mut().zip(x).for_each(|(y, &x)| *y = x);
+        let mut spectrum = forward.make_output_vec();
+        forward.process(&mut input, &mut spectrum).unwrap();
+        spectrum
+    };
+    let ref_spectrum = spectrum_of(reference);
+    let mut spectrum: Vec<Complex<f32>> = spectrum_of(recorded)
+        .into_iter()
+        .zip(&ref_spectrum)
+        .map(|(x, r)| x * r.conj())
+        .collect();
+    spectrum[0].im = 0.;
+    spectrum[n_fft / 2].im = 0.;
+    let mut xcorr = inverse.make_output_vec();
+    inverse.process(&mut spectrum, &mut xcorr).unwrap();
+
+    let max_delay = recorded.len() - reference.len();
+    let (delay, &peak) = xcorr[..=max_delay]
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    let ref_energy: f32 = reference.iter().map(|x| x * x).sum();
+    let rec_energy: f32 = recorded
+        .slice(s![delay..delay + reference.len()])
+        .iter()
+        .map(|x| x * x)
+        .sum();
+    let denom = (ref_energy * rec_energy).sqrt();
+    if denom <= 0. {
+        return None;
+    }
+    let confidence = peak / n_fft as f32 / denom;
+    (confidence >= MIN_CONFIDENCE).then_some((delay, confidence.min(1.)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn estimate_delay_works() {
+        let chirp = log_chirp(48000);
+        assert_eq!(chirp.len(), 24000);
+        assert_abs_diff_eq!(chirp[0], 0.);
+
+        let delay = 4321;
+        let mut recorded = Array1::zeros(chirp.len() + 20000);
+        recorded
+            .slice_mut(s![delay..delay + chirp.len()])
+            .assign(&(&chirp * 0.1));
+        // some hum
+        recorded
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, x)| *x += (i as f32 * 0.0065).sin() * 0.01);
+        let (estimated, confidence) = estimate_delay(chirp.view(), recorded.view()).unwrap();
+        assert_eq!(estimated, delay);
+        assert!(confidence > 0.9);
+
+        let silence = Array1::zeros(chirp.len() * 2);
+        assert!(estimate_delay(chirp.view(), silence.view()).is_none());
+        assert!(estimate_delay(chirp.view(), chirp.slice(s![..100])).is_none());
+    }
+}
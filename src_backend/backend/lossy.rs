@@ -0,0 +1,204 @@
+//! Estimation of lossy encoding in the history of a track (e.g. a wav converted from an mp3)
+//! by the telltale signs of lossy codecs: the spectral cutoff shelf and pre-echo before transients
+
+// allow for whole file because [napi(object)] attribite on struct blocks allow(non_snake_case)
+#![allow(non_snake_case)]
+
+use napi_derive::napi;
+use ndarray::prelude::*;
+use realfft::RealFftPlanner;
+
+use super::dynamics::DeciBel;
+use super::windows::hann;
+
+const N_FFT: usize = 4096;
+const HOP_LENGTH: usize = N_FFT / 2;
+/// maximum number of frames for the long-term spectrum (spread over the track)
+const MAX_FRAMES: usize = 2048;
+/// frames quieter than this (mean power of a bin) are excluded from the long-term spectrum
+const MIN_FRAME_POWER: f32 = 1e-12;
+/// lossy encoders cut off the high frequencies above this (typically 11 ~ 20 kHz)
+const MIN_CUTOFF_HZ: f64 = 10000.;
+/// width of the bands compared below and above the cutoff
+const SHELF_BAND_HZ: f64 = 1000.;
+/// drop of the level at the cutoff regarded as no shelf (confidence 0) ~ a definite shelf (1)
+const SHELF_DROP_dB_RANGE: (f64, f64) = (10., 40.);
+
+/// length of the blocks of the short-term energy for detecting transients and pre-echo
+const BLOCK_SEC: f64 = 0.002;
+/// rise of the energy (power ratio) from the preceding blocks regarded as a transient
+const TRANSIENT_RATIO: f32 = 30.;
+/// the blocks within this before a transient are examined for pre-echo
+const PRE_ECHO_SEC: f64 = 0.01;
+/// the blocks between PRE_ECHO_SEC and this before a transient are the reference
+const REFERENCE_SEC: f64 = 0.04;
+/// the energy before a transient higher than the reference by this ratio is regarded as pre-echo
+const PRE_ECHO_RATIO: f32 = 2.;
+/// pre_echo_score is 0 if there are fewer transients than this
+const MIN_TRANSIENTS: usize = 5;
+/// weight of the shelf in the confidence (the rest is pre-echo, which is less reliable)
+const SHELF_WEIGHT: f64 = 0.8;
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct LossyGenerationEstimate {
+    /// 0 ~ 1. how likely the track has been through a lossy encoder
+    pub confidence: f64,
+    /// frequency of the steepest drop of the long-term spectrum above 10 kHz.
+    /// None if the sample rate is too low to search.
+    pub cutoff_hz: Option<f64>,
+    /// level of the band below cutoff_hz minus that of the band above it (dB)
+    #[napi(js_name = "cutoffDropdB")]
+    pub cutoff_drop_dB: f64,
+    /// 0 ~ 1. ratio of the transients preceded by a rise of the energy (pre-echo)
+    pub pre_echo_score: f64,
+    pub n_transients: u32,
+}
+
+/// Estimate lossy encoding from the mono mixdown of wavs.
+pub fn estimate_lossy_generation(wavs: ArrayView2<f32>, sr: u32) -> LossyGenerationEstimate {
+    let mono = wavs.mean_axis(Axis(0)).unwrap_or_else(|| Array1::zeros(0));
+    let (cutoff_hz, cutoff_drop_dB) = find_cutoff_shelf(mono.view(), sr).unzip();
+    let cutoff_drop_dB = cutoff_drop_dB.unwrap_or(0.);
+    let (pre_echo_score, n_transients) = calc_pre_echo_score(mono.view(), sr);
+
+    let (min_drop, max_drop) = SHELF_DROP_dB_RANGE;
+    let shelf_confidence = ((cutoff_drop_dB - min_drop) / (max_drop - min_drop)).clamp(0., 1.);
+    LossyGenerationEstimate {
+        confidence: SHELF_WEIGHT * shelf_confidence + (1. - SHELF_WEIGHT) * pre_echo_score,
+        cutoff_hz,
+        cutoff_drop_dB,
+        pre_echo_score,
+        n_transients: n_transients as u32,
+    }
+}
+
+/// mean power spectrum (dB) of the non-silent frames of wav
+fn calc_long_term_spectrum(wav: ArrayView1<f32>) -> Array1<f64> {
+    let n_freqs = N_FFT / 2 + 1;
+    let hop_length = wav.len().div_ceil(MAX_FRAMES).max(HOP_LENGTH);
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(N_FFT);
+    let window = hann::<f32>(N_FFT, false);
+    let mut frame = forward.make_input_vec();
+    let mut spectrum = forward.make_output_vec();
+    let mut sum = Array1::<f64>::zeros(n_freqs);
+    let mut n_frames = 0;
+    for i_start in (0..wav.len()).step_by(hop_length) {
+        frame.iter_mut().enumerate().for_each(|(i, y)| {
+            *y = wav.get(i_start + i).map_or(0., |&x| x * window[i]);
+        });
+        forward.process(&mut frame, &mut spectrum).unwrap();
+        let power: Vec<_> = spectrum.iter().map(|x| x.norm_sqr()).collect();
+        if power.iter().sum::<f32>() / (n_freqs as f32) < MIN_FRAME_POWER {
+            continue;
+        }
+        sum.iter_mut().zip(power).for_each(|(y, x)| *y += x as f64);
+        n_frames += 1;
+    }
+    sum.mapv(|x| (x / n_frames.max(1) as f64).dB_from_power_default())
+}
+
+/// (cutoff_hz, drop_dB) at the steepest drop of the long-term spectrum above MIN_CUTOFF_HZ
+fn find_cutoff_shelf(wav: ArrayView1<f32>, sr: u32) -> Option<(f64, f64)> {
+    let hz_per_bin = sr as f64 / N_FFT as f64;
+    let band = (SHELF_BAND_HZ / hz_per_bin).round().max(1.) as usize;
+    let n_freqs = N_FFT / 2 + 1;
+    let k_min = (MIN_CUTOFF_HZ / hz_per_bin).ceil() as usize;
+    if k_min + band > n_freqs {
+        return None;
+    }
+    let spectrum = calc_long_term_spectrum(wav);
+    let mean = |range: std::ops::Range<usize>| spectrum.slice(s![range]).mean().unwrap();
+    (k_min..=(n_freqs - band))
+        .map(|k| (k, mean((k - band)..k) - mean(k..(k + band))))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(k, drop)| (k as f64 * hz_per_bin, drop))
+}
+
+/// (ratio of the transients with pre-echo, number of transients)
+fn calc_pre_echo_score(wav: ArrayView1<f32>, sr: u32) -> (f64, usize) {
+    let block_len = ((BLOCK_SEC * sr as f64).round() as usize).max(1);
+    let n_pre = (PRE_ECHO_SEC / BLOCK_SEC).round() as usize;
+    let n_ref = (REFERENCE_SEC / BLOCK_SEC).round() as usize;
+    // energy of the first difference emphasizing the high frequencies where pre-echo is audible
+    let energies: Vec<f32> = wav
+        .as_slice()
+        .map(|x| {
+            x.windows(2)
+                .map(|w| (w[1] - w[0]).powi(2))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default()
+        .chunks(block_len)
+        .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
+        .collect();
+    let mean = |blocks: &[f32]| blocks.iter().sum::<f32>() / blocks.len() as f32;
+
+    let (mut n_transients, mut n_pre_echoes) = (0, 0);
+    let mut i = n_ref;
+    while i < energies.len() {
+        let reference = mean(&energies[(i - n_ref)..(i - n_pre)]);
+        let pre = mean(&energies[(i - n_pre)..i]);
+        if energies[i] > MIN_FRAME_POWER && energies[i] > TRANSIENT_RATIO * pre.max(reference) {
+            n_transients += 1;
+            if pre > PRE_ECHO_RATIO * reference {
+                n_pre_echoes += 1;
+            }
+            // skip the decay of the transient
+            i += n_ref;
+        } else {
+            i += 1;
+        }
+    }
+    let score = if n_transients >= MIN_TRANSIENTS {
+        n_pre_echoes as f64 / n_transients as f64
+    } else {
+        0.
+    };
+    (score, n_transients)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ndarray_rand::{rand::prelude::*, rand_distr::Uniform, RandomExt};
+
+    /// white noise lowpassed by zeroing the bins above cutoff_hz
+    fn lowpassed_noise(sr: u32, cutoff_hz: f64) -> Array1<f32> {
+        let len = sr as usize * 2;
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut noise = Array1::random_using(len, Uniform::new(-0.5f32, 0.5), &mut rng).to_vec();
+        let mut planner = RealFftPlanner::<f32>::new();
+        let forward = planner.plan_fft_forward(len);
+        let inverse = planner.plan_fft_inverse(len);
+        let mut spectrum = forward.make_output_vec();
+        forward.process(&mut noise, &mut spectrum).unwrap();
+        let k_cutoff = (cutoff_hz * len as f64 / sr as f64) as usize;
+        spectrum[k_cutoff..]
+            .iter_mut()
+            .for_each(|x| *x = Default::default());
+        spectrum[0].im = 0.;
+        spectrum[len / 2].im = 0.;
+        inverse.process(&mut spectrum, &mut noise).unwrap();
+        Array1::from(noise) / len as f32
+    }
+
+    #[test]
+    fn estimate_lossy_generation_works() {
+        let sr = 44100;
+        let wavs = lowpassed_noise(sr, 16000.).insert_axis(Axis(0));
+        let estimate = estimate_lossy_generation(wavs.view(), sr);
+        assert!((estimate.cutoff_hz.unwrap() - 16000.).abs() < 100.);
+        assert!(estimate.confidence > 0.7);
+
+        let wavs = lowpassed_noise(sr, sr as f64 / 2.).insert_axis(Axis(0));
+        let estimate = estimate_lossy_generation(wavs.view(), sr);
+        assert!(estimate.confidence < 0.2);
+        assert_eq!(estimate.n_transients, 0);
+
+        let estimate = estimate_lossy_generation(wavs.view(), 16000);
+        assert_eq!(estimate.cutoff_hz, None);
+    }
+}
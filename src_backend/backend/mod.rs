@@ -5,26 +5,82 @@ use ndarray::prelude::*;
 use rayon::prelude::*;
 
 mod audio;
+mod binary_layout;
+mod compare;
+mod convolution;
+mod denoise;
+mod dither;
+mod downmix;
+mod dropouts;
 mod dynamics;
+mod edit_preview;
+mod filters;
+mod framing;
+mod histogram;
+mod loopback;
+mod lossy;
+mod pairing;
+mod report;
+mod similarity;
 mod sinc;
+mod spec_progress;
 mod spectrogram;
+mod stretch;
+mod testsignal;
+mod thumbnail;
+mod timeline;
 mod track;
+mod transcode;
 mod tuple_hasher;
 mod utils;
+mod viewport;
 pub mod visualize;
 mod windows;
 
-pub use audio::AudioFormatInfo;
-pub use dynamics::{DeciBel, GuardClippingMode};
-pub use spectrogram::SpecSetting;
-pub use track::TrackList;
-pub use tuple_hasher::TupleIntMap;
-use tuple_hasher::{TupleIntDMap, TupleIntSet};
-pub use utils::Pad;
+pub use audio::{
+    probe_audio_file, AudioFormatInfo, DecodeGap, ProbedAudioFile, RawPcmParams, RawSampleFormat,
+};
+pub use binary_layout::encode_wav_slice;
+pub use compare::TrackPairComparison;
+pub use convolution::ImpulseResponse;
+pub use denoise::SpectralDenoiser;
+pub use dither::{calc_dither_lsb, TpdfDither};
+pub use downmix::{ChannelRelationKind, ChannelRelationship, DownmixMode, MonoCompatibility};
+pub use dropouts::{Dropout, DropoutKind};
+pub use dynamics::{
+    segmented_loudness_to_csv, BandLoudness, BandLoudnessContribution, ChannelStats, DeciBel,
+    DynamicsStats, GuardClippingMode, HeadroomReport, PerfectLimiter, SegmentLoudness,
+};
+pub use edit_preview::{transform_selection, SelectionTransformKind, SelectionTransformParams};
+pub use filters::{TfBoxSolo, WavViewMode};
+pub use framing::InitialView;
+pub use histogram::BitDepthUsage;
+pub use loopback::{estimate_delay, log_chirp, LatencyMeasurement, CHIRP_SEC};
+pub use lossy::LossyGenerationEstimate;
+pub use report::render_report_html;
+pub use similarity::SimilarSegment;
+pub use spec_progress::{spec_progress, spec_progress_changed};
+pub use spectrogram::{
+    calc_spectral_centroid, NoiseProfile, SpecLayer, SpecMeasurement, SpecSetting,
+};
+pub use stretch::StretchParams;
+pub use testsignal::{generate_test_signal, TestSignalKind, TestSignalParams};
+pub use thumbnail::{get_or_render_thumbnail, MAX_THUMBNAIL_SIZE};
+pub use timeline::TrackTimeline;
+pub use track::{PendingPlayback, PendingStretch, TrackFilter, TrackGroupBy, TrackList};
+pub use transcode::{transcode, unique_output_path, TranscodeCodec, TranscodeParams};
+use tuple_hasher::TupleIntSet;
+pub use tuple_hasher::{TupleIntDMap, TupleIntMap};
+pub use utils::{relative_to_session, resolve_session_path, Pad};
+pub use viewport::{render_viewport_png, ViewportLayout};
 pub use visualize::{
-    calc_amp_axis_markers, calc_dB_axis_markers, calc_freq_axis_markers, calc_time_axis_markers,
-    convert_freq_label_to_hz, convert_hz_to_label, convert_sec_to_label, convert_time_label_to_sec,
-    DrawOptionForWav, DrawParams, TrackDrawer,
+    calc_amp_axis_markers, calc_broken_freq_axis_markers, calc_dB_axis_markers,
+    calc_freq_axis_markers, calc_gain_axis_markers, calc_overview_layout,
+    calc_overview_segment_hashes, calc_time_axis_markers, convert_freq_label_to_hz,
+    convert_hz_to_label, convert_sec_to_label, convert_sec_to_samples, convert_time_label_to_sec,
+    fit_amp_axis_markers, fit_dB_axis_markers, fit_freq_axis_markers, fit_time_axis_markers,
+    format_position, AutoContrastMode, DrawOptionForWav, DrawParams, FreqAxisBreak,
+    FreqInterpolation, LabelMetrics, PositionUnit, TempoGrid, TrackDrawer,
 };
 
 pub type IdCh = (usize, usize);
@@ -35,7 +91,23 @@ pub type IdChValueArr<T> = [(IdCh, T)];
 pub type IdChMap<T> = TupleIntMap<IdCh, T>;
 pub type IdChDMap<T> = TupleIntDMap<IdCh, T>;
 
-use spectrogram::{SpectrogramAnalyzer, SrWinNfft};
+use spec_progress::SpecProgressGuard;
+use spectrogram::{calc_delta_spec, calc_percentile_spectrum, SpectrogramAnalyzer, SrWinNfft};
+use visualize::CalcWidth;
+
+const TILT_PIVOT_HZ: f32 = 1000.;
+const TILT_MIN_HZ: f32 = 20.;
+pub const DEFAULT_MAX_SPEC_BYTES: usize = 1 << 30;
+/// number of the channels of a track whose specs are calculated on adding/reloading.
+/// The specs of the other channels (e.g. of ambisonics files) are deferred.
+const MAX_EAGER_CHANNELS: usize = 8;
+/// maximum memory temporarily used by the spec calculation in parallel (see calc_specs)
+const MAX_SPEC_CALC_BYTES: usize = 1 << 31;
+/// the delta layer maps -DELTA_dB_RANGE ~ +DELTA_dB_RANGE to the whole colormap
+#[allow(non_upper_case_globals)]
+const DELTA_dB_RANGE: f32 = 24.;
+/// number of the colormaps of different lengths cached at the same time
+const MAX_CACHED_COLORMAPS: usize = 4;
 
 #[readonly::make]
 #[allow(non_snake_case)]
@@ -44,12 +116,153 @@ pub struct TrackManager {
     pub min_dB: f32,
     pub max_sr: u32,
     pub spec_greys: IdChMap<Array2<U16>>,
+    /// greys of the delta layer. Only calculated if delta_layer_enabled.
+    pub delta_greys: IdChMap<Array2<U16>>,
+    pub delta_layer_enabled: bool,
     pub setting: SpecSetting,
     pub dB_range: f32,
+    /// display tilt (dB/octave) pivoted at TILT_PIVOT_HZ
+    pub display_tilt: f32,
+    /// maximum size of the spectrogram (and its grey image) per id_ch. None for no limit.
+    pub max_spec_bytes: Option<usize>,
+    pub auto_contrast_mode: AutoContrastMode,
+    pub freq_interpolation: FreqInterpolation,
+    /// If true, all tracks are drawn in the same absolute hz range
+    /// (zero-padded above the Nyquist frequency of each track).
+    /// Otherwise, the hz range of each track is clipped to its Nyquist frequency.
+    pub freq_axis_locked: bool,
     hz_range: (f32, f32),
     spec_analyzer: SpectrogramAnalyzer,
     specs: IdChMap<Array2<f32>>,
+    /// hop lengths actually used for specs (can be larger than the setting)
+    spec_hop_lengths: IdChMap<usize>,
     no_grey_ids: Vec<usize>,
+    /// channels whose specs are not calculated yet (e.g. of offscreen tracks,
+    /// or beyond MAX_EAGER_CHANNELS of a track). See DeferredSpecJob.
+    deferred_id_chs: TupleIntSet<IdCh>,
+    /// (length, RGB colors) of the recently used colormaps. The most recent one is the last.
+    colormaps: Vec<(usize, Vec<u8>)>,
+}
+
+/// Calculation of the deferred specs without holding the lock of TrackManager,
+/// so that the specs of the other (e.g. visible) tracks can be updated meanwhile.
+pub struct DeferredSpecJob {
+    setting: SpecSetting,
+    max_spec_bytes: Option<usize>,
+    id_ch_tuples: IdChVec,
+}
+
+/// result of DeferredSpecJob to be inserted by TrackManager::insert_deferred_specs
+pub struct DeferredSpecs {
+    setting: SpecSetting,
+    max_spec_bytes: Option<usize>,
+    specs: Vec<(IdCh, Array2<f32>, usize)>,
+}
+
+impl DeferredSpecJob {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.id_ch_tuples.is_empty()
+    }
+
+    pub fn run(self, tracklist: &TrackList) -> DeferredSpecs {
+        let id_ch_tuples: IdChVec = self
+            .id_ch_tuples
+            .into_iter()
+            .filter(|&(id, ch)| tracklist.has(id) && ch < tracklist[id].n_ch())
+            .collect();
+        let ids: Vec<_> = id_ch_tuples.iter().map(|&(id, _)| id).unique().collect();
+        let mut spec_analyzer = SpectrogramAnalyzer::new();
+        spec_analyzer.prepare(
+            &tracklist.construct_sr_win_nfft_set(&ids, &self.setting),
+            self.setting.freq_scale,
+        );
+        let specs = calc_specs(
+            &spec_analyzer,
+            tracklist,
+            id_ch_tuples,
+            &self.setting,
+            self.max_spec_bytes,
+        );
+        DeferredSpecs {
+            setting: self.setting,
+            max_spec_bytes: self.max_spec_bytes,
+            specs,
+        }
+    }
+}
+
+/// (id_ch, spec, hop length actually used) of each id_ch.
+/// The channels are calculated in parallel by batches within MAX_SPEC_CALC_BYTES
+/// so that many channels (e.g. of ambisonics files) don't exhaust the memory at once.
+/// The progress of the channels is reported to spec_progress until they are done.
+fn calc_specs(
+    spec_analyzer: &SpectrogramAnalyzer,
+    tracklist: &TrackList,
+    id_ch_tuples: IdChVec,
+    setting: &SpecSetting,
+    max_spec_bytes: Option<usize>,
+) -> Vec<(IdCh, Array2<f32>, usize)> {
+    let estimate_bytes = |&(id, ch): &IdCh| {
+        let track = &tracklist[id];
+        spec_analyzer.estimate_calc_bytes(
+            track.channel(ch).len(),
+            track.sr(),
+            setting,
+            max_spec_bytes,
+        )
+    };
+    let progress = SpecProgressGuard::start(&id_ch_tuples);
+    let mut specs = Vec::with_capacity(id_ch_tuples.len());
+    for batch in split_into_batches(id_ch_tuples, estimate_bytes, MAX_SPEC_CALC_BYTES) {
+        let parallel = batch.len() < rayon::current_num_threads();
+        specs.par_extend(batch.into_par_iter().map(|(id, ch)| {
+            let track = &tracklist[id];
+            let (spec, hop_length) = spec_analyzer.calc_spec(
+                track.channel(ch),
+                track.sr(),
+                setting,
+                max_spec_bytes,
+                Some(&|fraction| progress.report((id, ch), fraction)),
+                parallel,
+            );
+            progress.finish((id, ch));
+            ((id, ch), spec, hop_length)
+        }));
+    }
+    specs
+}
+
+/// Split items into consecutive batches whose total bytes don't exceed max_bytes.
+/// An item larger than max_bytes forms a batch alone.
+fn split_into_batches<T>(
+    items: Vec<T>,
+    bytes: impl Fn(&T) -> usize,
+    max_bytes: usize,
+) -> Vec<Vec<T>> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut batch_bytes = 0;
+    for item in items {
+        let item_bytes = bytes(&item);
+        if !batch.is_empty() && batch_bytes + item_bytes > max_bytes {
+            batches.push(std::mem::take(&mut batch));
+            batch_bytes = 0;
+        }
+        batch_bytes += item_bytes;
+        batch.push(item);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+/// (channels calculated on adding/reloading, channels deferred to DeferredSpecJob)
+fn partition_eager_channels(id_ch_tuples: IdChVec) -> (IdChVec, IdChVec) {
+    id_ch_tuples
+        .into_iter()
+        .partition(|&(_, ch)| ch < MAX_EAGER_CHANNELS)
 }
 
 impl TrackManager {
@@ -59,45 +272,105 @@ impl TrackManager {
             min_dB: f32::INFINITY,
             max_sr: 0,
             spec_greys: IdChMap::with_capacity_and_hasher(2, Default::default()),
+            delta_greys: IdChMap::with_capacity_and_hasher(2, Default::default()),
+            delta_layer_enabled: false,
             setting: Default::default(),
             dB_range: 100.,
+            display_tilt: 0.,
+            max_spec_bytes: Some(DEFAULT_MAX_SPEC_BYTES),
+            auto_contrast_mode: Default::default(),
+            freq_interpolation: Default::default(),
+            freq_axis_locked: true,
             hz_range: (0., f32::INFINITY),
             spec_analyzer: SpectrogramAnalyzer::new(),
             specs: IdChMap::with_capacity_and_hasher(2, Default::default()),
+            spec_hop_lengths: IdChMap::with_capacity_and_hasher(2, Default::default()),
             no_grey_ids: Vec::new(),
+            deferred_id_chs: TupleIntSet::default(),
+            colormaps: Vec::with_capacity(MAX_CACHED_COLORMAPS),
         }
     }
 
+    /// The specs of the channels beyond MAX_EAGER_CHANNELS are deferred (see DeferredSpecJob).
     pub fn add_tracks(&mut self, tracklist: &TrackList, added_ids: &[usize]) {
         let sr_win_nfft_set = tracklist.construct_sr_win_nfft_set(added_ids, &self.setting);
 
-        self.update_specs(
-            tracklist,
-            tracklist.id_ch_tuples_from(added_ids),
-            &sr_win_nfft_set,
-        );
+        let (eager_id_chs, deferred_id_chs) =
+            partition_eager_channels(tracklist.id_ch_tuples_from(added_ids));
+        self.update_specs(tracklist, eager_id_chs, &sr_win_nfft_set);
+        self.deferred_id_chs.extend(deferred_id_chs);
         self.no_grey_ids.extend(added_ids.iter().copied());
     }
 
+    /// The specs of the channels beyond MAX_EAGER_CHANNELS are deferred (see DeferredSpecJob).
     pub fn reload_tracks(&mut self, tracklist: &TrackList, reloaded_ids: &[usize]) {
+        self.remove_stale_channels(tracklist, reloaded_ids);
         let sr_win_nfft_set = tracklist.construct_sr_win_nfft_set(reloaded_ids, &self.setting);
 
-        self.update_specs(
-            tracklist,
-            tracklist.id_ch_tuples_from(reloaded_ids),
-            &sr_win_nfft_set,
-        );
+        let (eager_id_chs, deferred_id_chs) =
+            partition_eager_channels(tracklist.id_ch_tuples_from(reloaded_ids));
+        self.update_specs(tracklist, eager_id_chs, &sr_win_nfft_set);
+        self.deferred_id_chs.extend(deferred_id_chs);
         self.no_grey_ids.extend(reloaded_ids.iter().copied());
     }
 
+    /// Defer calculating the specs of the added or reloaded tracks (e.g. offscreen)
+    /// instead of add_tracks or reload_tracks. The specs are calculated by DeferredSpecJob.
+    pub fn defer_specs(&mut self, tracklist: &TrackList, ids: &[usize]) {
+        self.remove_stale_channels(tracklist, ids);
+        self.deferred_id_chs
+            .extend(tracklist.id_ch_tuples_from(ids));
+    }
+
+    /// job calculating the deferred specs of ids (all deferred specs if None)
+    pub fn deferred_spec_job(&self, ids: Option<&[usize]>) -> DeferredSpecJob {
+        let id_ch_tuples = self
+            .deferred_id_chs
+            .iter()
+            .copied()
+            .filter(|(id, _)| ids.is_none_or(|ids| ids.contains(id)))
+            .sorted_unstable()
+            .collect();
+        DeferredSpecJob {
+            setting: self.setting.clone(),
+            max_spec_bytes: self.max_spec_bytes,
+            id_ch_tuples,
+        }
+    }
+
+    /// Insert the specs still deferred. The greys are updated by apply_track_list_changes.
+    /// Discarded if the setting changed meanwhile (all the specs are recalculated then).
+    pub fn insert_deferred_specs(&mut self, deferred: DeferredSpecs) {
+        if deferred.setting != self.setting || deferred.max_spec_bytes != self.max_spec_bytes {
+            return;
+        }
+        let mut ids = IntSet::default();
+        for (id_ch, spec, hop_length) in deferred.specs {
+            if self.deferred_id_chs.remove(&id_ch) {
+                self.specs.insert(id_ch, spec);
+                self.spec_hop_lengths.insert(id_ch, hop_length);
+                ids.insert(id_ch.0);
+            }
+        }
+        self.no_grey_ids.extend(ids);
+    }
+
+    /// true if any of ids has the spec without the grey (to be updated by apply_track_list_changes)
+    pub fn needs_greys(&self, ids: &[usize]) -> bool {
+        ids.iter().any(|id| self.no_grey_ids.contains(id))
+    }
+
     pub fn remove_tracks(
         &mut self,
         tracklist: &TrackList,
         removed_id_ch_tuples: &IdChArr,
     ) -> Option<(f32, f32)> {
         for tup in removed_id_ch_tuples {
+            self.deferred_id_chs.remove(tup);
             self.specs.remove(tup);
+            self.spec_hop_lengths.remove(tup);
             self.spec_greys.remove(tup);
+            self.delta_greys.remove(tup);
         }
         if self.specs.capacity() > 2 * self.specs.len() {
             self.specs.shrink_to(2);
@@ -105,6 +378,9 @@ impl TrackManager {
         if self.spec_greys.capacity() > 2 * self.spec_greys.len() {
             self.spec_greys.shrink_to(2);
         }
+        if self.delta_greys.capacity() > 2 * self.delta_greys.len() {
+            self.delta_greys.shrink_to(2);
+        }
 
         self.spec_analyzer.retain(
             &tracklist.construct_all_sr_win_nfft_set(&self.setting),
@@ -119,6 +395,18 @@ impl TrackManager {
         }
     }
 
+    /// remove everything of the channels of ids not existing anymore
+    /// (e.g. the file has fewer channels on reloading, or the decoded channels are changed)
+    fn remove_stale_channels(&mut self, tracklist: &TrackList, ids: &[usize]) {
+        let is_stale =
+            |&(id, ch): &IdCh| ids.contains(&id) && tracklist.has(id) && ch >= tracklist[id].n_ch();
+        self.deferred_id_chs.retain(|id_ch| !is_stale(id_ch));
+        self.specs.retain(|id_ch, _| !is_stale(id_ch));
+        self.spec_hop_lengths.retain(|id_ch, _| !is_stale(id_ch));
+        self.spec_greys.retain(|id_ch, _| !is_stale(id_ch));
+        self.delta_greys.retain(|id_ch, _| !is_stale(id_ch));
+    }
+
     pub fn apply_track_list_changes(&mut self, tracklist: &TrackList) -> (IntSet<usize>, u32) {
         let set = self.update_greys(tracklist, false);
         (set, self.max_sr)
@@ -129,6 +417,226 @@ impl TrackManager {
         self.specs.contains_key(id_ch)
     }
 
+    /// (number of tiles along frequency, number of tiles along time) of the spectrogram grey image
+    pub fn spec_tile_grid(&self, id_ch: IdCh, zoom_level: u32) -> Option<(usize, usize)> {
+        let grey = self.spec_greys.get(&id_ch)?;
+        visualize::calc_n_tiles(grey.dim(), zoom_level)
+    }
+
+    /// Spectrogram grey image of id_ch in hz_range cropped from the grey of the current hz range.
+    /// The part of hz_range out of the current hz range is clipped.
+    /// If hz_range is None, the entire grey image is returned.
+    pub fn spec_grey_in(
+        &self,
+        tracklist: &TrackList,
+        (id, ch): IdCh,
+        hz_range: Option<(f32, f32)>,
+    ) -> Option<ArrayView2<U16>> {
+        let grey = self.spec_greys.get(&(id, ch))?;
+        let Some(hz_range) = hz_range else {
+            return Some(grey.view());
+        };
+        let n_freqs = self.specs.get(&(id, ch))?.shape()[1];
+        let sr = tracklist.get(id)?.sr();
+        let freq_scale = self.setting.freq_scale;
+        let (i_start, i_end) = freq_scale.hz_range_to_idx(self.track_hz_range(sr), sr, n_freqs);
+        let hz_range = self.track_hz_range_in(Some(hz_range), sr);
+        let (j_start, j_end) = freq_scale.hz_range_to_idx(hz_range, sr, n_freqs);
+        let j_start = j_start.clamp(i_start, i_end);
+        let j_end = j_end.clamp(j_start, i_end);
+        // the first row of the grey is the highest frequency
+        (j_end > j_start).then(|| grey.slice(s![i_end - j_end..i_end - j_start, ..]))
+    }
+
+    /// (min, max) of the grey values of id_ch_tuples in the region visible with params
+    /// (start_sec, width, px_per_sec and hz_range).
+    /// None if nothing is visible or the region is out of the frequency range.
+    pub fn visible_grey_range(
+        &self,
+        tracklist: &TrackList,
+        id_ch_tuples: &IdChArr,
+        params: &DrawParams,
+    ) -> Option<(u16, u16)> {
+        id_ch_tuples
+            .par_iter()
+            .filter_map(|&(id, ch)| {
+                let track = tracklist.get(id)?;
+                let grey = self.spec_grey_in(tracklist, (id, ch), params.hz_range)?;
+                let (i_w, width) = track
+                    .calc_part_grey_info(
+                        grey.shape()[1] as u64,
+                        track.timeline().session_to_track(params.start_sec),
+                        params.width,
+                        params.px_per_sec,
+                    )
+                    .i_w_and_width;
+                let (i_w, width) = visualize::calc_effective_slice(i_w, width, grey.shape()[1])?;
+                let part = grey.slice(s![.., i_w..i_w + width]);
+                let rows = part.outer_iter().map(|row| {
+                    // U16 is a transparent wrapper of u16
+                    let row = row.to_slice().unwrap();
+                    unsafe { std::slice::from_raw_parts(row.as_ptr() as *const u16, row.len()) }
+                });
+                visualize::calc_grey_range(rows)
+            })
+            .reduce_with(|(min, max), (current_min, current_max)| {
+                (min.min(current_min), max.max(current_max))
+            })
+    }
+
+    /// dB range corresponding to the range of grey values
+    #[allow(non_snake_case)]
+    pub fn grey_range_to_dB(&self, (min, max): (u16, u16)) -> (f32, f32) {
+        let dB_span = self.max_dB - self.min_dB;
+        let to_dB =
+            |x: u16| ((x.max(1) - 1) as f32 / (u16::MAX - 1) as f32).mul_add(dB_span, self.min_dB);
+        (to_dB(min), to_dB(max))
+    }
+
+    /// n_steps colors of the colormap from the lowest to the highest dB
+    /// paired with their dB values, mapped in the same way as the spectrogram images.
+    /// dB_range: the range mapped to the colormap (None: min_dB ~ max_dB of all tracks)
+    #[allow(non_snake_case)]
+    pub fn colorbar(&self, n_steps: usize, dB_range: Option<(f32, f32)>) -> Vec<(f32, [u8; 4])> {
+        let (min_dB, max_dB) = dB_range.unwrap_or((self.min_dB, self.max_dB));
+        let ratios = (0..n_steps).map(|i| i as f32 / (n_steps - 1).max(1) as f32);
+        let greys: Vec<_> = ratios
+            .clone()
+            .map(|ratio| ratio.mul_add((u16::MAX - 1) as f32, 1.).round() as u16)
+            .collect();
+        let colors: Vec<_> = visualize::map_grey_to_color_iter(&greys).collect();
+        ratios
+            .zip(colors.chunks_exact(4))
+            .map(|(ratio, color)| {
+                (
+                    ratio.mul_add(max_dB - min_dB, min_dB),
+                    color.try_into().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    /// RGB colors of the colormap of the length (see get_colormap_rgb_with_length).
+    /// The colormaps of the recent lengths are kept, so that switching between them
+    /// (e.g. moving the window between monitors of different scaling) doesn't sample them again.
+    pub fn colormap_rgb(&mut self, length: usize) -> &[u8] {
+        if let Some(i) = self.colormaps.iter().position(|(l, _)| *l == length) {
+            let colormap = self.colormaps.remove(i);
+            self.colormaps.push(colormap);
+        } else {
+            if self.colormaps.len() >= MAX_CACHED_COLORMAPS {
+                self.colormaps.remove(0);
+            }
+            let colormap = visualize::get_colormap_rgb_with_length(length);
+            self.colormaps.push((length, colormap));
+        }
+        &self.colormaps.last().unwrap().1
+    }
+
+    /// Encode the tile of the grey image of the layer with the layout of binary_layout module.
+    /// Tiles at a zoom level are downsampled by 2^zoom_level from the grey image.
+    /// None if the layer isn't calculated (e.g. the delta layer is disabled).
+    pub fn encode_spec_tile(
+        &self,
+        tracklist: &TrackList,
+        (id, ch): IdCh,
+        layer: SpecLayer,
+        zoom_level: u32,
+        (tile_y, tile_x): (usize, usize),
+    ) -> Option<Vec<u8>> {
+        let greys = match layer {
+            SpecLayer::Magnitude => &self.spec_greys,
+            SpecLayer::Delta => &self.delta_greys,
+        };
+        let grey = greys.get(&(id, ch))?;
+        let track = tracklist.get(id)?;
+        let tile = visualize::slice_grey_tile(grey.view(), zoom_level, (tile_y, tile_x))?;
+        let sec_per_col = track.sec() / grey.shape()[1] as f64 * (1u64 << zoom_level) as f64;
+        Some(binary_layout::encode_spec_grey_slice(
+            id,
+            ch,
+            tile.view(),
+            track.sr(),
+            (tile_x * visualize::SPEC_TILE_SIZE) as f64 * sec_per_col,
+            sec_per_col,
+        ))
+    }
+
+    /// measure between two (sec, hz) points on the spectrogram of id_ch
+    #[allow(non_snake_case)]
+    pub fn measure(
+        &self,
+        tracklist: &TrackList,
+        (id, ch): IdCh,
+        start: (f64, f64),
+        end: (f64, f64),
+    ) -> Option<SpecMeasurement> {
+        let spec = self.specs.get(&(id, ch))?;
+        let track = tracklist.get(id)?;
+        let (n_frames, n_freqs) = (spec.shape()[0], spec.shape()[1]);
+        if n_frames == 0 || n_freqs == 0 {
+            return None;
+        }
+        let sec_per_frame = track.sec() / n_frames as f64;
+        let dB_at = |(sec, hz): (f64, f64)| {
+            let i_frame = ((sec / sec_per_frame).floor().max(0.) as usize).min(n_frames - 1);
+            let i_freq = self
+                .setting
+                .freq_scale
+                .hz_to_idx(hz as f32, track.sr(), n_freqs);
+            spec[[i_frame, i_freq]] as f64
+        };
+        Some(SpecMeasurement::new(start, end, dB_at(start), dB_at(end)))
+    }
+
+    /// dB at the percentile (0 ~ 100) over time for each frequency bin of the spectrogram of id_ch
+    /// (e.g. 10th percentile as the noise floor spectrum)
+    pub fn noise_profile(
+        &self,
+        tracklist: &TrackList,
+        (id, ch): IdCh,
+        percentile: f64,
+    ) -> Option<NoiseProfile> {
+        let spec = self.specs.get(&(id, ch))?;
+        let track = tracklist.get(id)?;
+        let (n_frames, n_freqs) = (spec.shape()[0], spec.shape()[1]);
+        if n_frames == 0 || n_freqs == 0 {
+            return None;
+        }
+        let spectrum = calc_percentile_spectrum(spec.view(), percentile);
+        Some(NoiseProfile {
+            hz: self.bin_hzs(track.sr(), n_freqs),
+            dB: spectrum.iter().map(|&x| x as f64).collect(),
+        })
+    }
+
+    /// (hz, dB) of the frame at sec of the spectrogram of id_ch
+    #[allow(non_snake_case)]
+    pub fn spectrum_at(
+        &self,
+        tracklist: &TrackList,
+        (id, ch): IdCh,
+        sec: f64,
+    ) -> Option<(Vec<f64>, Vec<f64>)> {
+        let spec = self.specs.get(&(id, ch))?;
+        let track = tracklist.get(id)?;
+        let (n_frames, n_freqs) = (spec.shape()[0], spec.shape()[1]);
+        if n_frames == 0 || n_freqs == 0 || !(0.0..=track.sec()).contains(&sec) {
+            return None;
+        }
+        let sec_per_frame = track.sec() / n_frames as f64;
+        let i_frame = ((sec / sec_per_frame).floor() as usize).min(n_frames - 1);
+        let dB = spec.row(i_frame).iter().map(|&x| x as f64).collect();
+        Some((self.bin_hzs(track.sr(), n_freqs), dB))
+    }
+
+    /// center frequency of each frequency bin (or mel bin) of the spectrogram
+    fn bin_hzs(&self, sr: u32, n_freqs: usize) -> Vec<f64> {
+        (0..n_freqs)
+            .map(|i| self.setting.freq_scale.idx_to_hz(i, sr, n_freqs) as f64)
+            .collect()
+    }
+
     pub fn set_setting(&mut self, tracklist: &TrackList, setting: SpecSetting) {
         let sr_win_nfft_set = tracklist.construct_sr_win_nfft_set(&tracklist.all_ids(), &setting);
 
@@ -144,17 +652,143 @@ impl TrackManager {
         self.update_greys(tracklist, true);
     }
 
+    /// set the maximum size of the spectrogram per id_ch, and recalculate specs if changed
+    pub fn set_max_spec_bytes(&mut self, tracklist: &TrackList, max_bytes: Option<usize>) {
+        if self.max_spec_bytes != max_bytes {
+            self.max_spec_bytes = max_bytes;
+            self.update_all_specs_greys(tracklist);
+        }
+    }
+
+    /// (sec per frame, if the time resolution was reduced by max_spec_bytes)
+    pub fn spec_resolution(&self, tracklist: &TrackList, (id, ch): IdCh) -> Option<(f64, bool)> {
+        let hop_length = *self.spec_hop_lengths.get(&(id, ch))?;
+        let sr = tracklist.get(id)?.sr();
+        Some((
+            hop_length as f64 / sr as f64,
+            hop_length > self.setting.calc_hop_length(sr),
+        ))
+    }
+
+    /// (hop_length, win_length, n_fft) actually used for the spectrogram of the track.
+    /// hop_length can be larger than the setting (see max_spec_bytes).
+    pub fn effective_framing_params(
+        &self,
+        tracklist: &TrackList,
+        id: usize,
+    ) -> Option<(usize, usize, usize)> {
+        let sr = tracklist.get(id)?.sr();
+        let hop_length = *self.spec_hop_lengths.get(&(id, 0))?;
+        let (_, win_length, n_fft) = self.setting.calc_framing_params(sr);
+        Some((hop_length, win_length, n_fft))
+    }
+
+    /// enable or disable calculating the delta layer
+    pub fn set_delta_layer_enabled(&mut self, tracklist: &TrackList, enabled: bool) {
+        if self.delta_layer_enabled == enabled {
+            return;
+        }
+        self.delta_layer_enabled = enabled;
+        if enabled {
+            self.update_greys(tracklist, true);
+        } else {
+            self.delta_greys.clear();
+            self.delta_greys.shrink_to_fit();
+        }
+    }
+
     #[allow(non_snake_case)]
     pub fn set_dB_range(&mut self, tracklist: &TrackList, dB_range: f32) {
         self.dB_range = dB_range;
         self.update_greys(tracklist, true);
     }
 
+    /// set the display tilt (dB/octave) applied only when converting specs to greys
+    #[allow(non_snake_case)]
+    pub fn set_display_tilt(&mut self, tracklist: &TrackList, dB_per_oct: f32) {
+        self.display_tilt = dB_per_oct;
+        self.update_greys(tracklist, true);
+    }
+
+    /// tilt (dB) for each frequency bin. None if the tilt is zero.
+    #[allow(non_snake_case)]
+    fn calc_tilt_dB(&self, sr: u32, n_freqs: usize) -> Option<Vec<f32>> {
+        (self.display_tilt != 0.).then(|| {
+            (0..n_freqs)
+                .map(|i| {
+                    let hz = self
+                        .setting
+                        .freq_scale
+                        .idx_to_hz(i, sr, n_freqs)
+                        .max(TILT_MIN_HZ);
+                    self.display_tilt * (hz / TILT_PIVOT_HZ).log2()
+                })
+                .collect()
+        })
+    }
+
     #[inline]
     fn get_hz_range(&self) -> (f32, f32) {
         Self::calc_valid_hz_range(&self.hz_range, self.max_sr as f32 / 2.)
     }
 
+    /// clip hz_range to the Nyquist frequency of sr if the frequency axis is not locked.
+    /// hz_range is kept if it's entirely above the Nyquist frequency.
+    fn clip_hz_range_for(&self, hz_range: (f32, f32), sr: u32) -> (f32, f32) {
+        let half_sr = sr as f32 / 2.;
+        if self.freq_axis_locked || hz_range.0 >= half_sr {
+            hz_range
+        } else {
+            (hz_range.0, hz_range.1.min(half_sr))
+        }
+    }
+
+    /// hz range spanned by the spectrogram image of a track of sr
+    #[inline]
+    pub fn track_hz_range(&self, sr: u32) -> (f32, f32) {
+        self.clip_hz_range_for(self.get_hz_range(), sr)
+    }
+
+    /// hz range spanned by the spectrogram image of a track of sr drawn in hz_range
+    /// (None: the hz range of TrackManager)
+    pub fn track_hz_range_in(&self, hz_range: Option<(f32, f32)>, sr: u32) -> (f32, f32) {
+        match hz_range {
+            Some(hz_range) => self.clip_hz_range_for(
+                Self::calc_valid_hz_range(&hz_range, self.max_sr as f32 / 2.),
+                sr,
+            ),
+            None => self.track_hz_range(sr),
+        }
+    }
+
+    /// ratio of the height (from the top) of the region above the Nyquist frequency of sr
+    /// in the spectrogram image (zero-padded). 0 if the image has no such region.
+    pub fn nyquist_padding_ratio(&self, sr: u32) -> f32 {
+        let hz_range = self.track_hz_range(sr);
+        let half_sr = sr as f32 / 2.;
+        if half_sr >= hz_range.1 {
+            0.
+        } else if half_sr <= hz_range.0 {
+            1.
+        } else {
+            1. - self
+                .setting
+                .freq_scale
+                .hz_to_relative_freq(half_sr, hz_range)
+        }
+    }
+
+    /// Lock or unlock the frequency axis (see freq_axis_locked).
+    /// If it's changed, update greys and return true.
+    pub fn set_freq_axis_locked(&mut self, tracklist: &TrackList, locked: bool) -> bool {
+        if self.freq_axis_locked == locked {
+            return false;
+        }
+        self.freq_axis_locked = locked;
+        self.update_greys(tracklist, true);
+        true
+    }
+
     pub fn calc_valid_hz_range(hz_range: &(f32, f32), max_track_hz: f32) -> (f32, f32) {
         let max_hz = if hz_range.1.is_finite() {
             hz_range.1
@@ -195,21 +829,18 @@ impl TrackManager {
                 self.spec_analyzer.prepare(&p, self.setting.freq_scale);
             }
         }
-        let parallel = id_ch_tuples.len() < rayon::current_num_threads();
-        let specs: Vec<_> = id_ch_tuples
-            .into_par_iter()
-            .map(|(id, ch)| {
-                let track = &tracklist[id];
-                let spec = self.spec_analyzer.calc_spec(
-                    track.channel(ch),
-                    track.sr(),
-                    &self.setting,
-                    parallel,
-                );
-                ((id, ch), spec)
-            })
-            .collect();
-        self.specs.extend(specs);
+        let specs = calc_specs(
+            &self.spec_analyzer,
+            tracklist,
+            id_ch_tuples,
+            &self.setting,
+            self.max_spec_bytes,
+        );
+        for (id_ch, spec, hop_length) in specs {
+            self.deferred_id_chs.remove(&id_ch);
+            self.specs.insert(id_ch, spec);
+            self.spec_hop_lengths.insert(id_ch, hop_length);
+        }
     }
 
     /// update spec_greys, max_dB, min_dB, max_sr
@@ -258,23 +889,39 @@ impl TrackManager {
                 .map(|(&(id, ch), spec)| {
                     let sr = tracklist[id].sr();
                     let i_freq_range = self.setting.freq_scale.hz_range_to_idx(
-                        self.get_hz_range(),
+                        self.track_hz_range(sr),
                         sr,
                         spec.shape()[1],
                     );
+                    let tilt = self.calc_tilt_dB(sr, spec.shape()[1]);
                     let grey = visualize::convert_spec_to_grey(
                         spec.view(),
                         i_freq_range,
                         (self.min_dB, self.max_dB),
+                        tilt.as_deref(),
                     );
-                    ((id, ch), grey)
+                    // the tilt is cancelled out in the difference
+                    let delta_grey = self.delta_layer_enabled.then(|| {
+                        visualize::convert_spec_to_grey(
+                            calc_delta_spec(spec.view()).view(),
+                            i_freq_range,
+                            (-DELTA_dB_RANGE, DELTA_dB_RANGE),
+                            None,
+                        )
+                    });
+                    ((id, ch), grey, delta_grey)
                 })
                 .collect();
 
             if need_update_all {
-                self.spec_greys = new_spec_greys.into_iter().collect();
-            } else {
-                self.spec_greys.extend(new_spec_greys)
+                self.spec_greys.clear();
+                self.delta_greys.clear();
+            }
+            for (id_ch, grey, delta_grey) in new_spec_greys {
+                self.spec_greys.insert(id_ch, grey);
+                if let Some(delta_grey) = delta_grey {
+                    self.delta_greys.insert(id_ch, delta_grey);
+                }
             }
         }
         ids_need_update
@@ -283,12 +930,319 @@ impl TrackManager {
 
 #[cfg(test)]
 mod tests {
+    use approx::assert_abs_diff_eq;
     use image::RgbaImage;
     use visualize::DrawParams;
 
     use super::visualize::ImageKind;
     use super::*;
 
+    #[test]
+    fn measure_works() {
+        let mut tracklist = TrackList::new();
+        let mut tm = TrackManager::new();
+        let added_ids = tracklist.add_tracks(vec![0], vec!["samples/sample_48k.wav".into()]);
+        tm.add_tracks(&tracklist, &added_ids);
+
+        let measurement = tm
+            .measure(&tracklist, (0, 0), (1., 1000.), (2., 2000.))
+            .unwrap();
+        assert_abs_diff_eq!(measurement.delta_sec, 1.);
+        assert_abs_diff_eq!(measurement.delta_hz, 1000.);
+        assert_abs_diff_eq!(measurement.slope_hz_per_sec.unwrap(), 1000.);
+        assert_abs_diff_eq!(measurement.delta_octave.unwrap(), 1.);
+        assert!(measurement.start_dB.is_finite() && measurement.end_dB.is_finite());
+        assert_abs_diff_eq!(
+            measurement.delta_dB,
+            measurement.end_dB - measurement.start_dB
+        );
+        assert!(tm
+            .measure(&tracklist, (0, 1), (1., 1000.), (2., 2000.))
+            .is_none());
+
+        // degenerate points
+        let measurement = tm
+            .measure(&tracklist, (0, 0), (1., 0.), (1., 2000.))
+            .unwrap();
+        assert_eq!(measurement.slope_hz_per_sec, None);
+        assert_eq!(measurement.delta_octave, None);
+    }
+
+    #[test]
+    fn noise_profile_works() {
+        let mut tracklist = TrackList::new();
+        let mut tm = TrackManager::new();
+        let added_ids = tracklist.add_tracks(vec![0], vec!["samples/sample_48k.wav".into()]);
+        tm.add_tracks(&tracklist, &added_ids);
+
+        let floor = tm.noise_profile(&tracklist, (0, 0), 10.).unwrap();
+        let median = tm.noise_profile(&tracklist, (0, 0), 50.).unwrap();
+        assert_eq!(floor.hz.len(), tm.specs[&(0, 0)].shape()[1]);
+        assert_eq!(floor.dB.len(), floor.hz.len());
+        assert!(floor.hz.windows(2).all(|w| w[0] < w[1]));
+        assert!(floor.dB.iter().zip(&median.dB).all(|(a, b)| a <= b));
+        assert!(tm.noise_profile(&tracklist, (0, 1), 10.).is_none());
+
+        let spec = arr2(&[[-10., -20.], [-30., -40.], [-20., -60.]]);
+        let spectrum = calc_percentile_spectrum(spec.view(), 0.);
+        assert_eq!(spectrum, arr1(&[-30., -60.]));
+        let spectrum = calc_percentile_spectrum(spec.view(), 50.);
+        assert_eq!(spectrum, arr1(&[-20., -40.]));
+    }
+
+    #[test]
+    fn max_spec_bytes_works() {
+        let mut tracklist = TrackList::new();
+        let mut tm = TrackManager::new();
+        let added_ids = tracklist.add_tracks(vec![0], vec!["samples/sample_48k.wav".into()]);
+        tm.add_tracks(&tracklist, &added_ids);
+        let (sec_per_frame, is_reduced) = tm.spec_resolution(&tracklist, (0, 0)).unwrap();
+        assert!(!is_reduced);
+        let n_frames = tm.specs[&(0, 0)].shape()[0];
+
+        let max_bytes = tm.specs[&(0, 0)].len() * 6 / 4;
+        tm.set_max_spec_bytes(&tracklist, Some(max_bytes));
+        let spec = &tm.specs[&(0, 0)];
+        assert!(spec.len() * 6 <= max_bytes);
+        assert!(spec.shape()[0] < n_frames);
+        let (reduced_sec_per_frame, is_reduced) = tm.spec_resolution(&tracklist, (0, 0)).unwrap();
+        assert!(is_reduced);
+        assert!(reduced_sec_per_frame > sec_per_frame);
+
+        tm.set_max_spec_bytes(&tracklist, None);
+        assert_eq!(tm.specs[&(0, 0)].shape()[0], n_frames);
+    }
+
+    #[test]
+    fn delta_layer_works() {
+        let mut tracklist = TrackList::new();
+        let mut tm = TrackManager::new();
+        let added_ids = tracklist.add_tracks(vec![0], vec!["samples/sample_48k.wav".into()]);
+        tm.add_tracks(&tracklist, &added_ids);
+        tm.apply_track_list_changes(&tracklist);
+        assert!(tm.delta_greys.is_empty());
+        assert!(tm
+            .encode_spec_tile(&tracklist, (0, 0), SpecLayer::Delta, 0, (0, 0))
+            .is_none());
+
+        tm.set_delta_layer_enabled(&tracklist, true);
+        assert_eq!(tm.delta_greys[&(0, 0)].dim(), tm.spec_greys[&(0, 0)].dim());
+        assert!(tm
+            .encode_spec_tile(&tracklist, (0, 0), SpecLayer::Delta, 0, (0, 0))
+            .is_some());
+
+        let spec = arr2(&[[-10., -20.], [-4., -20.], [-4., -30.]]);
+        let delta = calc_delta_spec(spec.view());
+        assert_eq!(delta, arr2(&[[0., 0.], [6., 0.], [0., -10.]]));
+
+        tm.set_delta_layer_enabled(&tracklist, false);
+        assert!(tm.delta_greys.is_empty());
+    }
+
+    #[test]
+    fn colormap_cache_works() {
+        let mut tm = TrackManager::new();
+        assert_eq!(tm.colormap_rgb(10).len(), 30);
+        assert_eq!(tm.colormap_rgb(20).len(), 60);
+        assert_eq!(tm.colormap_rgb(10).len(), 30);
+        assert_eq!(tm.colormaps.len(), 2);
+        assert_eq!(tm.colormaps.last().unwrap().0, 10);
+        for length in 30..35 {
+            tm.colormap_rgb(length);
+        }
+        let lengths: Vec<_> = tm.colormaps.iter().map(|(length, _)| *length).collect();
+        assert_eq!(lengths, [31, 32, 33, 34]);
+    }
+
+    #[test]
+    fn spec_grey_in_works() {
+        let mut tracklist = TrackList::new();
+        let mut tm = TrackManager::new();
+        let added_ids = tracklist.add_tracks(vec![0], vec!["samples/sample_48k.wav".into()]);
+        tm.add_tracks(&tracklist, &added_ids);
+        tm.apply_track_list_changes(&tracklist);
+
+        let grey = tm.spec_greys[&(0, 0)].view();
+        assert_eq!(tm.spec_grey_in(&tracklist, (0, 0), None).unwrap(), grey);
+        assert_eq!(
+            tm.spec_grey_in(&tracklist, (0, 0), Some((0., f32::INFINITY)))
+                .unwrap(),
+            grey
+        );
+        let low_band = tm
+            .spec_grey_in(&tracklist, (0, 0), Some((0., 2000.)))
+            .unwrap();
+        assert!(low_band.shape()[0] < grey.shape()[0]);
+        assert_eq!(low_band.shape()[1], grey.shape()[1]);
+        // the lowest frequency is the last row
+        assert_eq!(
+            low_band.row(low_band.shape()[0] - 1),
+            grey.row(grey.shape()[0] - 1)
+        );
+        assert!(tm
+            .spec_grey_in(&tracklist, (0, 0), Some((30000., 40000.)))
+            .is_none());
+    }
+
+    #[test]
+    fn colorbar_works() {
+        let mut tracklist = TrackList::new();
+        let mut tm = TrackManager::new();
+        let added_ids = tracklist.add_tracks(vec![0], vec!["samples/sample_48k.wav".into()]);
+        tm.add_tracks(&tracklist, &added_ids);
+        tm.apply_track_list_changes(&tracklist);
+
+        let colorbar = tm.colorbar(5, None);
+        assert_eq!(colorbar.len(), 5);
+        assert_abs_diff_eq!(colorbar[0].0, tm.min_dB);
+        assert_abs_diff_eq!(colorbar[4].0, tm.max_dB, epsilon = 1e-4);
+        let extremes: Vec<_> = visualize::map_grey_to_color_iter(&[1, u16::MAX]).collect();
+        assert_eq!(colorbar[0].1, extremes[..4]);
+        assert_eq!(colorbar[4].1, extremes[4..]);
+
+        let colorbar = tm.colorbar(3, Some((-60., -20.)));
+        assert_eq!(
+            colorbar.iter().map(|x| x.0).collect::<Vec<_>>(),
+            [-60., -40., -20.]
+        );
+    }
+
+    #[test]
+    fn visible_grey_range_works() {
+        let mut tracklist = TrackList::new();
+        let mut tm = TrackManager::new();
+        let added_ids = tracklist.add_tracks(vec![0], vec!["samples/sample_48k.wav".into()]);
+        tm.add_tracks(&tracklist, &added_ids);
+        tm.apply_track_list_changes(&tracklist);
+
+        let params = DrawParams {
+            start_sec: 0.,
+            width: 1000,
+            height: 100,
+            px_per_sec: 1000. / tracklist[0].sec(),
+            ..Default::default()
+        };
+        let (min, max) = tm
+            .visible_grey_range(&tracklist, &[(0, 0)], &params)
+            .unwrap();
+        assert!(min >= 1 && min <= max);
+        let (min_dB, max_dB) = tm.grey_range_to_dB((min, max));
+        assert!(min_dB >= tm.min_dB - 1e-3 && max_dB <= tm.max_dB + 1e-3);
+        assert_abs_diff_eq!(tm.grey_range_to_dB((1, u16::MAX)).1, tm.max_dB);
+
+        // a part of the track is within the whole range
+        let part = DrawParams {
+            width: 10,
+            hz_range: Some((0., 2000.)),
+            ..params.clone()
+        };
+        let (part_min, part_max) = tm.visible_grey_range(&tracklist, &[(0, 0)], &part).unwrap();
+        assert!(part_min >= min && part_max <= max);
+
+        let out_of_track = DrawParams {
+            start_sec: tracklist[0].sec() + 1.,
+            ..params
+        };
+        assert!(tm
+            .visible_grey_range(&tracklist, &[(0, 0)], &out_of_track)
+            .is_none());
+        assert!(tm
+            .visible_grey_range(&tracklist, &[(1, 0)], &part)
+            .is_none());
+    }
+
+    #[test]
+    fn effective_framing_params_works() {
+        let mut tracklist = TrackList::new();
+        let mut tm = TrackManager::new();
+        let added_ids = tracklist.add_tracks(vec![0], vec!["samples/sample_48k.wav".into()]);
+        tm.add_tracks(&tracklist, &added_ids);
+
+        // 40 ms window with 4 times overlap
+        assert_eq!(
+            tm.effective_framing_params(&tracklist, 0),
+            Some((480, 1920, 2048))
+        );
+        assert_eq!(tm.effective_framing_params(&tracklist, 1), None);
+    }
+
+    #[test]
+    fn freq_axis_lock_works() {
+        let mut tracklist = TrackList::new();
+        let mut tm = TrackManager::new();
+        let added_ids = tracklist.add_tracks(
+            vec![0, 1],
+            vec![
+                "samples/sample_24k.wav".into(),
+                "samples/sample_48k.wav".into(),
+            ],
+        );
+        tm.add_tracks(&tracklist, &added_ids);
+        tm.apply_track_list_changes(&tracklist);
+
+        // locked: the region above 12 kHz of the 24 kHz track is zero-padded
+        assert!(tm.freq_axis_locked);
+        assert_eq!(tm.track_hz_range(24000), (0., 24000.));
+        let padding_ratio = tm.nyquist_padding_ratio(24000);
+        assert!(padding_ratio > 0. && padding_ratio < 1.);
+        assert_eq!(tm.nyquist_padding_ratio(48000), 0.);
+        let grey = &tm.spec_greys[&(0, 0)];
+        assert!(grey.row(0).iter().all(|x| x.0 == 0));
+        let n_padded = grey
+            .outer_iter()
+            .take_while(|row| row.iter().all(|x| x.0 == 0))
+            .count();
+        assert_abs_diff_eq!(
+            n_padded as f32 / grey.shape()[0] as f32,
+            padding_ratio,
+            epsilon = 0.01
+        );
+
+        // unlocked: each track fills the image up to its Nyquist frequency
+        assert!(tm.set_freq_axis_locked(&tracklist, false));
+        assert!(!tm.set_freq_axis_locked(&tracklist, false));
+        assert_eq!(tm.track_hz_range(24000), (0., 12000.));
+        assert_eq!(tm.nyquist_padding_ratio(24000), 0.);
+        assert!(tm.spec_greys[&(0, 0)].row(0).iter().any(|x| x.0 > 0));
+    }
+
+    #[test]
+    fn deferred_specs_work() {
+        let path_list = vec![
+            "samples/sample_8k.wav".to_string(),
+            "samples/sample_16k.wav".to_string(),
+        ];
+        let mut tracklist = TrackList::new();
+        let mut tm = TrackManager::new();
+        let added_ids = tracklist.add_tracks(vec![0, 1], path_list);
+        tm.add_tracks(&tracklist, &added_ids[..1]);
+        tm.defer_specs(&tracklist, &added_ids[1..]);
+        assert!(!tm.exists(&(1, 0)));
+        assert!(tm.deferred_spec_job(Some(&[0])).is_empty());
+
+        let job = tm.deferred_spec_job(None);
+        assert!(!job.is_empty());
+        let specs = job.run(&tracklist);
+        tm.insert_deferred_specs(specs);
+        assert!(tm.exists(&(1, 0)));
+        assert!(tm.deferred_spec_job(None).is_empty());
+        assert!(tm.needs_greys(&[1]));
+        tm.apply_track_list_changes(&tracklist);
+        assert!(!tm.needs_greys(&[0, 1]));
+        assert_eq!(tm.spec_greys.len(), 2);
+    }
+
+    #[test]
+    fn split_into_batches_works() {
+        let batches = split_into_batches(vec![3, 2, 4, 9, 1], |&x| x, 6);
+        assert_eq!(batches, vec![vec![3, 2], vec![4], vec![9], vec![1]]);
+        assert!(split_into_batches(Vec::<usize>::new(), |&x| x, 6).is_empty());
+        assert_eq!(
+            partition_eager_channels(vec![(0, 0), (0, MAX_EAGER_CHANNELS), (1, 1)]),
+            (vec![(0, 0), (1, 1)], vec![(0, MAX_EAGER_CHANNELS)])
+        );
+    }
+
     #[test]
     fn trackmanager_works() {
         let tags = ["8k", "16k", "22k05", "24k", "44k1", "48k", "stereo_48k"];
@@ -330,7 +1284,7 @@ mod tests {
             &tracklist.id_ch_tuples(),
             height,
             px_per_sec,
-            ImageKind::Spec,
+            ImageKind::Spec(None, None, None),
         );
         let wav_imgs = tm.draw_entire_imgs(
             &tracklist,
@@ -368,6 +1322,9 @@ mod tests {
                     px_per_sec: 16000.,
                     opt_for_wav,
                     blend: 0.,
+                    hz_range: None,
+                    grey_range: None,
+                    freq_break: None,
                 },
                 vec![false],
             )
@@ -0,0 +1,98 @@
+//! Pairing tracks by file name patterns for A/B sets (e.g. "*_orig.wav" vs "*_proc.wav")
+
+use std::collections::BTreeMap;
+
+/// the part of name matched by "*" of pattern (containing exactly one "*").
+/// The rest of the pattern is matched literally (case-sensitive).
+fn match_wildcard<'a>(pattern: &str, name: &'a str) -> Option<&'a str> {
+    let (prefix, suffix) = pattern.split_once('*')?;
+    if name.len() < prefix.len() + suffix.len() {
+        return None;
+    }
+    name.strip_prefix(prefix)?.strip_suffix(suffix)
+}
+
+fn validate_pattern(pattern: &str) -> Result<(), String> {
+    if pattern.matches('*').count() != 1 {
+        return Err(format!(
+            "The pattern \"{}\" should contain exactly one \"*\".",
+            pattern
+        ));
+    }
+    Ok(())
+}
+
+/// (the part matched by "*", id of A, id of B) of the names (id, file name) paired by the part
+/// matched by "*" of pattern_a and pattern_b, ordered by the matched part.
+/// If several tracks have the same matched part, they are paired in the given order.
+/// A name matching both patterns is taken as A.
+pub fn pair_by_pattern(
+    names: impl IntoIterator<Item = (usize, String)>,
+    pattern_a: &str,
+    pattern_b: &str,
+) -> Result<Vec<(String, usize, usize)>, String> {
+    validate_pattern(pattern_a)?;
+    validate_pattern(pattern_b)?;
+    if pattern_a == pattern_b {
+        return Err("The patterns of A and B should be different.".into());
+    }
+    // matched part -> (ids of A, ids of B)
+    let mut candidates: BTreeMap<String, (Vec<usize>, Vec<usize>)> = BTreeMap::new();
+    for (id, name) in names {
+        if let Some(key) = match_wildcard(pattern_a, &name) {
+            candidates.entry(key.into()).or_default().0.push(id);
+        } else if let Some(key) = match_wildcard(pattern_b, &name) {
+            candidates.entry(key.into()).or_default().1.push(id);
+        }
+    }
+    Ok(candidates
+        .into_iter()
+        .flat_map(|(key, (ids_a, ids_b))| {
+            ids_a
+                .into_iter()
+                .zip(ids_b)
+                .map(move |(id_a, id_b)| (key.clone(), id_a, id_b))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_wildcard_works() {
+        assert_eq!(match_wildcard("*_orig.wav", "song_orig.wav"), Some("song"));
+        assert_eq!(match_wildcard("*_orig.wav", "song_proc.wav"), None);
+        assert_eq!(match_wildcard("A_*", "A_song.flac"), Some("song.flac"));
+        assert_eq!(match_wildcard("*.wav", ".wav"), Some(""));
+        // the prefix and the suffix don't overlap
+        assert_eq!(match_wildcard("ab*ba", "aba"), None);
+        assert_eq!(match_wildcard("*_orig.wav", "song_ORIG.wav"), None);
+    }
+
+    #[test]
+    fn pair_by_pattern_works() {
+        let names = [
+            (0, "b_orig.wav"),
+            (1, "a_proc.wav"),
+            (2, "a_orig.wav"),
+            (3, "b_proc.wav"),
+            (4, "c_orig.wav"),
+            (5, "other.wav"),
+        ]
+        .map(|(id, name)| (id, name.to_string()));
+        let pairs = pair_by_pattern(names, "*_orig.wav", "*_proc.wav").unwrap();
+        assert_eq!(pairs, [("a".to_string(), 2, 1), ("b".to_string(), 0, 3)]);
+
+        // duplicates are paired in order
+        let names = [(0, "x_orig.wav"), (1, "x_orig.wav"), (2, "x_proc.wav")]
+            .map(|(id, name)| (id, name.to_string()));
+        let pairs = pair_by_pattern(names, "*_orig.wav", "*_proc.wav").unwrap();
+        assert_eq!(pairs, [("x".to_string(), 0, 2)]);
+
+        assert!(pair_by_pattern(Vec::new(), "orig.wav", "*_proc.wav").is_err());
+        assert!(pair_by_pattern(Vec::new(), "*_*.wav", "*_proc.wav").is_err());
+        assert!(pair_by_pattern(Vec::new(), "*.wav", "*.wav").is_err());
+    }
+}
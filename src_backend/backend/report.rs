@@ -0,0 +1,199 @@
+//! Standalone HTML report of all tracks rendered by the backend
+
+use std::fmt::Write;
+
+use tiny_skia::PixmapRef;
+
+use super::dynamics::GuardClippingMode;
+use super::track::TrackList;
+use super::visualize::{convert_sec_to_label, TrackDrawer};
+use super::TrackManager;
+
+const OVERVIEW_WIDTH: u32 = 1200;
+const OVERVIEW_HEIGHT: u32 = 160;
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; color: #222; }
+h2 { margin-top: 2em; word-break: break-all; }
+.path { color: #666; font-size: 0.85em; word-break: break-all; }
+img { display: block; width: 100%; max-width: 1200px; background: #111; }
+table { border-collapse: collapse; margin-top: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: left; }
+th { background: #f3f3f3; }
+pre { margin: 0; }";
+
+fn escape_html(s: &str) -> String {
+    s.chars()
+        .fold(String::with_capacity(s.len()), |mut out, c| {
+            match c {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' => out.push_str("&quot;"),
+                '\'' => out.push_str("&#39;"),
+                _ => out.push(c),
+            }
+            out
+        })
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | ((b as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(TABLE[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// data URI of the overview PNG image. None if the image can't be drawn.
+fn overview_data_uri(tm: &TrackManager, tracklist: &TrackList, id: usize) -> Option<String> {
+    let rgba = tm.draw_overview(tracklist, id, OVERVIEW_WIDTH, OVERVIEW_HEIGHT, 1.);
+    let png = PixmapRef::from_bytes(&rgba, OVERVIEW_WIDTH, OVERVIEW_HEIGHT)?
+        .encode_png()
+        .ok()?;
+    Some(format!("data:image/png;base64,{}", encode_base64(&png)))
+}
+
+fn write_row(html: &mut String, name: &str, value: &str) {
+    writeln!(
+        html,
+        "<tr><th>{}</th><td>{}</td></tr>",
+        name,
+        escape_html(value)
+    )
+    .unwrap();
+}
+
+/// HTML report including the overview image, stats, and guard clipping summary of every track
+pub fn render_report_html(tm: &TrackManager, tracklist: &TrackList) -> String {
+    let mut html = String::new();
+    writeln!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>Thesia Report</title>\n<style>\n{}\n</style>\n</head>\n<body>",
+        STYLE
+    )
+    .unwrap();
+    writeln!(html, "<h1>Thesia Report</h1>").unwrap();
+    writeln!(
+        html,
+        "<p>Generated at {}</p>",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+    )
+    .unwrap();
+    html.push_str("<table>\n");
+    write_row(&mut html, "Tracks", &tracklist.all_ids().len().to_string());
+    write_row(
+        &mut html,
+        "Normalization",
+        &serde_json::to_string(&tracklist.common_normalize).unwrap_or_default(),
+    );
+    let guard_clipping = match tracklist.common_guard_clipping {
+        GuardClippingMode::Clip => "Clip",
+        GuardClippingMode::ReduceGlobalLevel => "Reduce Global Level",
+        GuardClippingMode::Limiter => "Limiter",
+    };
+    write_row(&mut html, "Guard Clipping", guard_clipping);
+    html.push_str("</table>\n");
+
+    let mut ids = tracklist.all_ids();
+    ids.sort_unstable();
+    for id in ids {
+        let track = tracklist.get(id).unwrap();
+        writeln!(
+            html,
+            "<section>\n<h2>{}</h2>\n<div class=\"path\">{}</div>",
+            escape_html(tracklist.filename(id)),
+            escape_html(&track.path_string())
+        )
+        .unwrap();
+        if let Some(uri) = overview_data_uri(tm, tracklist, id) {
+            writeln!(html, "<img alt=\"overview\" src=\"{}\">", uri).unwrap();
+        }
+
+        html.push_str("<table>\n");
+        let format_info = &track.format_info;
+        write_row(&mut html, "Format", &format_info.name);
+        write_row(&mut html, "Sample Rate", &format!("{} Hz", track.sr()));
+        write_row(&mut html, "Bit Depth", &format_info.bit_depth);
+        write_row(&mut html, "Bitrate", &format_info.bitrate);
+        write_row(&mut html, "Channels", &track.n_ch().to_string());
+        write_row(&mut html, "Length", &convert_sec_to_label(track.sec()));
+        let stats = track.stats();
+        write_row(
+            &mut html,
+            "Integrated Loudness",
+            &format!("{:.2} LUFS", stats.global_lufs),
+        );
+        write_row(&mut html, "RMS", &format!("{:.2} dB", stats.rms_dB));
+        write_row(
+            &mut html,
+            "Max Peak",
+            &format!("{:.2} dB", stats.max_peak_dB),
+        );
+        if let Some(compat) = track.mono_compatibility() {
+            write_row(
+                &mut html,
+                "L/R Correlation",
+                &format!("{:.3}", compat.correlation),
+            );
+            write_row(
+                &mut html,
+                "Mono Loss",
+                &format!("{:.2} dB", compat.mono_loss_dB),
+            );
+        }
+        let guard_clip_summary = tracklist.guard_clip_summary(id).unwrap_or_default();
+        writeln!(
+            html,
+            "<tr><th>Guard Clipping</th><td><pre>{}</pre></td></tr>",
+            if guard_clip_summary.trim().is_empty() {
+                "none".to_string()
+            } else {
+                escape_html(&guard_clip_summary)
+            }
+        )
+        .unwrap();
+        html.push_str("</table>\n</section>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_base64_works() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"f"), "Zg==");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert_eq!(encode_base64(b"foo"), "Zm9v");
+        assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn render_report_works() {
+        let mut tracklist = TrackList::new();
+        let mut tm = TrackManager::new();
+        let added_ids = tracklist.add_tracks(vec![0], vec!["samples/sample_48k.wav".into()]);
+        tm.add_tracks(&tracklist, &added_ids);
+
+        let html = render_report_html(&tm, &tracklist);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("sample_48k.wav"));
+        assert!(html.contains("data:image/png;base64,"));
+        assert_eq!(escape_html("<a & 'b'>"), "&lt;a &amp; &#39;b&#39;&gt;");
+    }
+}
@@ -0,0 +1,262 @@
+//! Query by example: find segments similar to a selection (e.g. every occurrence of a beep)
+//! by comparing the sequences of MFCCs
+
+use std::f32::consts::PI;
+
+use napi_derive::napi;
+use ndarray::prelude::*;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+
+use super::spectrogram::mel::calc_mel_fb;
+use super::windows::hann;
+
+const WIN_SEC: f64 = 0.04;
+const HOP_SEC: f64 = 0.01;
+const N_MELS: usize = 40;
+/// number of MFCCs (c1 ~ c12) of each frame.
+/// c0 (the frame energy) is excluded so that the similarity doesn't depend on the level.
+const N_MFCC: usize = 12;
+/// the mel bands span 0 ~ MAX_HZ (or the lowest Nyquist frequency of the query and the target)
+const MAX_HZ: f32 = 8000.;
+const MIN_POWER: f32 = 1e-10;
+/// frames processed by each rayon task
+const FRAMES_PER_TASK: usize = 1024;
+const MAX_QUERY_SEC: f64 = 10.;
+const MAX_MATCHES: usize = 200;
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimilarSegment {
+    pub track_id: u32,
+    pub ch: u32,
+    pub start_sec: f64,
+    pub end_sec: f64,
+    /// cosine similarity of the MFCC sequences (1 for the identical spectral shape)
+    pub similarity: f64,
+}
+
+/// (win_length, hop_length) for sr
+#[inline]
+fn calc_framing(sr: u32) -> (usize, usize) {
+    let to_len = |sec: f64| ((sec * sr as f64).round() as usize).max(1);
+    (to_len(WIN_SEC), to_len(HOP_SEC))
+}
+
+/// MFCCs (c1 ~ c{N_MFCC}) of each frame (shape: (n_frames, N_MFCC)).
+/// Frames not fully inside wav are not included.
+fn calc_mfcc(wav: ArrayView1<f32>, sr: u32, max_hz: f32) -> Array2<f32> {
+    let (win_length, hop_length) = calc_framing(sr);
+    let n_frames = if wav.len() >= win_length {
+        (wav.len() - win_length) / hop_length + 1
+    } else {
+        0
+    };
+    let n_fft = win_length.next_power_of_two();
+    let window = hann::<f32>(win_length, false);
+    let mel_fb = calc_mel_fb(sr, n_fft, N_MELS, 0f32, Some(max_hz), true);
+    // DCT-II basis (N_MELS x N_MFCC) without the constant c0
+    let dct = Array2::from_shape_fn((N_MELS, N_MFCC), |(m, k)| {
+        (PI / N_MELS as f32 * (m as f32 + 0.5) * (k + 1) as f32).cos()
+    });
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(n_fft);
+
+    let mut mfcc = Array2::zeros((n_frames, N_MFCC));
+    mfcc.axis_chunks_iter_mut(Axis(0), FRAMES_PER_TASK)
+        .into_par_iter()
+        .enumerate()
+        .for_each(|(i_chunk, mut chunk)| {
+            let mut frame = forward.make_input_vec();
+            let mut spectrum = forward.make_output_vec();
+            let mut power = Array1::zeros(n_fft / 2 + 1);
+            for (i, mut y) in chunk.outer_iter_mut().enumerate() {
+                let i_start = (i_chunk * FRAMES_PER_TASK + i) * hop_length;
+                frame.fill(0.);
+                frame
+                    .iter_mut()
+                    .zip(wav.slice(s![i_start..i_start + win_length]))
+                    .zip(&window)
+                    .for_each(|((y, &x), &w)| *y = x * w);
+                forward.process(&mut frame, &mut spectrum).unwrap();
+                power
+                    .iter_mut()
+                    .zip(&spectrum)
+                    .for_each(|(y, x)| *y = x.norm_sqr());
+                let log_mel = power.dot(&mel_fb).mapv(|x| x.max(MIN_POWER).log10());
+                y.assign(&log_mel.dot(&dct));
+            }
+        });
+    mfcc
+}
+
+/// (start frame, similarity) of the segments of features similar to template,
+/// sorted by the similarity in descending order.
+/// Overlapping segments are suppressed except for the most similar one.
+fn match_template(
+    template: ArrayView2<f32>,
+    features: ArrayView2<f32>,
+    threshold: f32,
+) -> Vec<(usize, f32)> {
+    let n_template = template.nrows();
+    if n_template == 0 || features.nrows() < n_template {
+        return Vec::new();
+    }
+    let template_norm = template.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if template_norm == 0. {
+        return Vec::new();
+    }
+    // cumulative squared norms of the frames for the norm of each segment
+    let mut cum_sq = Vec::with_capacity(features.nrows() + 1);
+    cum_sq.push(0f64);
+    for row in features.outer_iter() {
+        let sq = row.dot(&row) as f64;
+        cum_sq.push(cum_sq.last().unwrap() + sq);
+    }
+    let n_segments = features.nrows() - n_template + 1;
+    let similarities: Vec<f32> = (0..n_segments)
+        .into_par_iter()
+        .map(|i| {
+            let norm = (cum_sq[i + n_template] - cum_sq[i]).max(0.).sqrt() as f32;
+            if norm == 0. {
+                return 0.;
+            }
+            let dot: f32 = template
+                .outer_iter()
+                .zip(features.slice(s![i..i + n_template, ..]).outer_iter())
+                .map(|(a, b)| a.dot(&b))
+                .sum();
+            dot / (template_norm * norm)
+        })
+        .collect();
+
+    let mut candidates: Vec<_> = similarities
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, similarity)| similarity >= threshold)
+        .collect();
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    let mut matches: Vec<(usize, f32)> = Vec::new();
+    for (i, similarity) in candidates {
+        if matches
+            .iter()
+            .all(|&(j, _)| i + n_template <= j || j + n_template <= i)
+        {
+            matches.push((i, similarity));
+        }
+    }
+    matches
+}
+
+/// Find the segments of the targets similar to the query.
+/// targets: (track id, ch, wav, sr).
+/// threshold: minimum similarity (0 ~ 1).
+/// The result is ranked by the similarity (including the query itself if it's in the targets).
+pub fn find_similar_segments(
+    query: ArrayView1<f32>,
+    query_sr: u32,
+    targets: &[(usize, usize, ArrayView1<f32>, u32)],
+    threshold: f64,
+) -> Result<Vec<SimilarSegment>, String> {
+    let query_sec = query.len() as f64 / query_sr as f64;
+    if query_sec < WIN_SEC || query_sec > MAX_QUERY_SEC {
+        return Err(format!(
+            "The selection should be {} ~ {} sec long.",
+            WIN_SEC, MAX_QUERY_SEC
+        ));
+    }
+    let mut segments: Vec<_> = targets
+        .par_iter()
+        .flat_map_iter(|&(id, ch, wav, sr)| {
+            let max_hz = MAX_HZ.min(query_sr.min(sr) as f32 / 2.);
+            let template = calc_mfcc(query, query_sr, max_hz);
+            let features = calc_mfcc(wav, sr, max_hz);
+            let (win_length, hop_length) = calc_framing(sr);
+            let n_template = template.nrows();
+            match_template(template.view(), features.view(), threshold as f32)
+                .into_iter()
+                .map(move |(i, similarity)| {
+                    let i_end = (i + n_template - 1) * hop_length + win_length;
+                    SimilarSegment {
+                        track_id: id as u32,
+                        ch: ch as u32,
+                        start_sec: (i * hop_length) as f64 / sr as f64,
+                        end_sec: i_end as f64 / sr as f64,
+                        similarity: similarity as f64,
+                    }
+                })
+        })
+        .collect();
+    segments.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    segments.truncate(MAX_MATCHES);
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::testsignal::{generate_test_signal, TestSignalKind, TestSignalParams};
+
+    const SR: u32 = 48000;
+
+    /// pink noise at -60 dBFS with beeps of hz starting at each of starts (sec)
+    fn noise_with_beeps(sec: f64, beeps: &[(f64, f64, f32)]) -> Array1<f32> {
+        let params = TestSignalParams {
+            sr: SR,
+            sec,
+            peak_dB: -60.,
+            ..Default::default()
+        };
+        let mut wav = generate_test_signal(TestSignalKind::PinkNoise, &params)
+            .unwrap()
+            .row(0)
+            .to_owned();
+        for &(start, hz, amp) in beeps {
+            let i_start = (start * SR as f64) as usize;
+            for i in 0..(0.1 * SR as f64) as usize {
+                wav[i_start + i] += amp * (2. * PI * hz as f32 * i as f32 / SR as f32).sin();
+            }
+        }
+        wav
+    }
+
+    #[test]
+    fn find_similar_works() {
+        // beeps of 1 kHz with different levels and a beep of 3 kHz
+        let wav = noise_with_beeps(
+            4.,
+            &[
+                (0.5, 1000., 0.5),
+                (1.5, 1000., 0.05),
+                (2.5, 3000., 0.5),
+                (3.5, 1000., 0.2),
+            ],
+        );
+        let query = wav.slice(s![24000..28800]);
+        let segments = find_similar_segments(query, SR, &[(0, 0, wav.view(), SR)], 0.9).unwrap();
+        assert_eq!(segments.len(), 3);
+        let mut starts: Vec<_> = segments.iter().map(|x| x.start_sec).collect();
+        starts.sort_by(f64::total_cmp);
+        for (start, answer) in starts.into_iter().zip([0.5, 1.5, 3.5]) {
+            assert!((start - answer).abs() <= 2. * HOP_SEC, "{}", start);
+        }
+        assert!(segments[0].similarity > 0.99);
+        assert!(segments
+            .windows(2)
+            .all(|x| x[0].similarity >= x[1].similarity));
+
+        assert!(find_similar_segments(wav.slice(s![..100]), SR, &[], 0.9).is_err());
+    }
+
+    #[test]
+    fn match_template_suppresses_overlaps() {
+        let features = Array2::from_shape_fn((20, 2), |(i, j)| if i % 10 == j { 1. } else { 0.1 });
+        let template = features.slice(s![0..3, ..]);
+        let matches = match_template(template, features.view(), 0.9);
+        let mut starts: Vec<_> = matches.iter().map(|x| x.0).collect();
+        starts.sort_unstable();
+        assert_eq!(starts, [0, 10]);
+        assert!(matches.iter().all(|x| x.1 > 0.999));
+    }
+}
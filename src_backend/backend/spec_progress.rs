@@ -0,0 +1,167 @@
+//! Progress of the spectrograms being calculated, so that the frontend can show
+//! which channels (e.g. of hour-long files) are still computing.
+//! Every change bumps the version so that the frontend can wait for the next change.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::LazyLock;
+
+use napi::tokio::sync::watch;
+use parking_lot::Mutex;
+
+use super::{IdCh, IdChArr, IdChMap};
+
+/// id_ch -> (generation of the guard, fraction of the STFT frames done (0 ~ 1))
+static PROGRESS: LazyLock<Mutex<IdChMap<(u64, f32)>>> =
+    LazyLock::new(|| Mutex::new(IdChMap::default()));
+/// generation of the last SpecProgressGuard
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+/// incremented on every change of PROGRESS
+static VERSION: LazyLock<watch::Sender<u32>> = LazyLock::new(|| watch::channel(0).0);
+
+#[inline]
+fn bump_version() {
+    VERSION.send_modify(|version| *version = version.wrapping_add(1));
+}
+
+/// (id_ch, fraction done) of the channels whose specs are being calculated (or queued),
+/// ordered by id_ch
+pub fn spec_progress() -> Vec<(IdCh, f32)> {
+    let mut progress: Vec<_> = PROGRESS
+        .lock()
+        .iter()
+        .map(|(&id_ch, &(_, fraction))| (id_ch, fraction))
+        .collect();
+    progress.sort_unstable_by_key(|&(id_ch, _)| id_ch);
+    progress
+}
+
+/// Wait until the version of the progress differs from version (immediately if already differs).
+/// Returns (the new version, spec_progress).
+pub async fn spec_progress_changed(version: u32) -> (u32, Vec<(IdCh, f32)>) {
+    let mut rx = VERSION.subscribe();
+    loop {
+        let current = *rx.borrow_and_update();
+        if current != version || rx.changed().await.is_err() {
+            return (current, spec_progress());
+        }
+    }
+}
+
+/// RAII registration of the channels of a spec calculation.
+/// The channels not finished are removed when dropped (e.g. on panic),
+/// unless a newer calculation of the same channels has started since then.
+pub(super) struct SpecProgressGuard {
+    generation: u64,
+    id_ch_tuples: Vec<IdCh>,
+}
+
+impl SpecProgressGuard {
+    pub fn start(id_ch_tuples: &IdChArr) -> Self {
+        let generation = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+        {
+            let mut progress = PROGRESS.lock();
+            for &id_ch in id_ch_tuples {
+                progress.insert(id_ch, (generation, 0.));
+            }
+        }
+        bump_version();
+        SpecProgressGuard {
+            generation,
+            id_ch_tuples: id_ch_tuples.to_vec(),
+        }
+    }
+
+    /// The version is bumped only when the percentage changes not to flood the observers.
+    pub fn report(&self, id_ch: IdCh, fraction: f32) {
+        let percent = |x: f32| (x * 100.) as u32;
+        let changed = match PROGRESS.lock().get_mut(&id_ch) {
+            Some((generation, x)) if *generation == self.generation => {
+                let fraction = fraction.clamp(0., 1.);
+                let changed = percent(*x) != percent(fraction);
+                *x = fraction;
+                changed
+            }
+            _ => false,
+        };
+        if changed {
+            bump_version();
+        }
+    }
+
+    pub fn finish(&self, id_ch: IdCh) {
+        if self.remove_own(&[id_ch]) {
+            bump_version();
+        }
+    }
+
+    /// remove the entries set by this guard. true if any of them is removed.
+    fn remove_own(&self, id_ch_tuples: &IdChArr) -> bool {
+        let mut progress = PROGRESS.lock();
+        let mut removed = false;
+        for id_ch in id_ch_tuples {
+            if progress
+                .get(id_ch)
+                .is_some_and(|&(generation, _)| generation == self.generation)
+            {
+                progress.remove(id_ch);
+                removed = true;
+            }
+        }
+        removed
+    }
+}
+
+impl Drop for SpecProgressGuard {
+    fn drop(&mut self) {
+        if self.remove_own(&self.id_ch_tuples) {
+            bump_version();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fraction_of(id_ch: IdCh) -> Option<f32> {
+        spec_progress()
+            .into_iter()
+            .find(|&(x, _)| x == id_ch)
+            .map(|(_, fraction)| fraction)
+    }
+
+    #[test]
+    fn spec_progress_works() {
+        // ids not used by the other tests
+        let id_chs = [(1_000_001, 1), (1_000_001, 0)];
+        let version = *VERSION.borrow();
+        let guard = SpecProgressGuard::start(&id_chs);
+        assert_ne!(*VERSION.borrow(), version);
+        assert_eq!(fraction_of(id_chs[0]), Some(0.));
+        guard.report(id_chs[1], 0.5);
+        assert_eq!(fraction_of(id_chs[1]), Some(0.5));
+        guard.finish(id_chs[1]);
+        assert_eq!(fraction_of(id_chs[1]), None);
+        // not reported after finished
+        guard.report(id_chs[1], 0.7);
+        assert_eq!(fraction_of(id_chs[1]), None);
+        drop(guard);
+        assert_eq!(fraction_of(id_chs[0]), None);
+    }
+
+    #[test]
+    fn old_guard_keeps_newer_progress() {
+        let id_chs = [(1_000_002, 0)];
+        let old = SpecProgressGuard::start(&id_chs);
+        let new = SpecProgressGuard::start(&id_chs);
+        old.report(id_chs[0], 0.9);
+        assert_eq!(fraction_of(id_chs[0]), Some(0.));
+        old.finish(id_chs[0]);
+        drop(old);
+        assert_eq!(fraction_of(id_chs[0]), Some(0.));
+        new.report(id_chs[0], 0.3);
+        assert_eq!(fraction_of(id_chs[0]), Some(0.3));
+        drop(new);
+        assert_eq!(fraction_of(id_chs[0]), None);
+    }
+}
@@ -16,6 +16,8 @@ use super::windows::{calc_normalized_win, WindowType};
 use stft::perform_stft;
 
 const DEFAULT_WINTYPE: WindowType = WindowType::Hann;
+/// f32 for the spectrogram + u16 for the grey image
+const BYTES_PER_BIN: usize = 6;
 
 type FramingParams = (usize, usize, usize); // hop, win, n_fft
 type WinNfft = (usize, usize);
@@ -77,6 +79,59 @@ impl FreqScale {
 
         (min_idx, max_idx)
     }
+
+    /// center frequency of the frequency bin (or mel bin)
+    #[inline]
+    pub fn idx_to_hz(&self, idx: usize, sr: u32, n_freqs_or_mels: usize) -> f32 {
+        self.relative_freq_to_hz(idx as f32 / n_freqs_or_mels as f32, (0., sr as f32 / 2.))
+    }
+
+    /// index of the nearest frequency bin (or mel bin)
+    #[inline]
+    pub fn hz_to_idx(&self, hz: f32, sr: u32, n_freqs_or_mels: usize) -> usize {
+        let ratio = self.calc_ratio_to_max_freq(hz.max(0.), sr);
+        ((ratio * n_freqs_or_mels as f32).round() as usize).min(n_freqs_or_mels.max(1) - 1)
+    }
+}
+
+/// measurement between two time-frequency points (start, end) on the spectrogram
+#[napi(object)]
+#[derive(Clone, Debug, Default, PartialEq)]
+#[allow(non_snake_case)]
+pub struct SpecMeasurement {
+    pub delta_sec: f64,
+    pub delta_hz: f64,
+    /// delta_hz / delta_sec. None if the two points are at the same time
+    pub slope_hz_per_sec: Option<f64>,
+    /// difference on the mel scale
+    pub delta_mel: f64,
+    /// log2(end_hz / start_hz). None if any of the frequencies is not positive
+    pub delta_octave: Option<f64>,
+    #[napi(js_name = "startdB")]
+    pub start_dB: f64,
+    #[napi(js_name = "enddB")]
+    pub end_dB: f64,
+    #[napi(js_name = "deltadB")]
+    pub delta_dB: f64,
+}
+
+impl SpecMeasurement {
+    /// points: (sec, hz)
+    #[allow(non_snake_case)]
+    pub fn new(start: (f64, f64), end: (f64, f64), start_dB: f64, end_dB: f64) -> Self {
+        let delta_sec = end.0 - start.0;
+        let delta_hz = end.1 - start.1;
+        SpecMeasurement {
+            delta_sec,
+            delta_hz,
+            slope_hz_per_sec: (delta_sec != 0.).then(|| delta_hz / delta_sec),
+            delta_mel: mel::from_hz(end.1) - mel::from_hz(start.1),
+            delta_octave: (start.1 > 0. && end.1 > 0.).then(|| (end.1 / start.1).log2()),
+            start_dB,
+            end_dB,
+            delta_dB: end_dB - start_dB,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
@@ -87,7 +142,7 @@ pub struct SrWinNfft {
 }
 
 #[napi(object)]
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct SpecSetting {
     #[napi(js_name = "winMillisec")]
     pub win_ms: f64,
@@ -157,6 +212,90 @@ impl SpecSetting {
     }
 }
 
+/// layers of the spectrogram view
+#[napi(string_enum)]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum SpecLayer {
+    /// dB spectrogram
+    #[default]
+    Magnitude,
+    /// temporal first difference of the dB spectrogram (emphasizing onsets and modulation)
+    Delta,
+}
+
+/// temporal first difference of the dB spectrogram (T x F). The first frame is zero.
+pub fn calc_delta_spec(spec: ArrayView2<f32>) -> Array2<f32> {
+    let mut delta = Array2::zeros(spec.raw_dim());
+    if spec.shape()[0] > 1 {
+        Zip::from(delta.slice_mut(s![1.., ..]))
+            .and(spec.slice(s![1.., ..]))
+            .and(spec.slice(s![..-1, ..]))
+            .par_for_each(|y, &curr, &prev| *y = curr - prev);
+    }
+    delta
+}
+
+/// noise floor spectrum of a channel
+#[napi(object)]
+#[derive(Clone, Debug, Default, PartialEq)]
+#[allow(non_snake_case)]
+pub struct NoiseProfile {
+    /// center frequency of each frequency bin (or mel bin)
+    pub hz: Vec<f64>,
+    #[napi(js_name = "dB")]
+    pub dB: Vec<f64>,
+}
+
+/// dB at the percentile (0 ~ 100) over time for each frequency bin of the dB spectrogram (T x F)
+pub fn calc_percentile_spectrum(spec: ArrayView2<f32>, percentile: f64) -> Array1<f32> {
+    let mut spectrum = Array1::zeros(spec.shape()[1]);
+    let n_frames = spec.shape()[0];
+    if n_frames == 0 {
+        return spectrum;
+    }
+    let idx = (percentile.clamp(0., 100.) / 100. * (n_frames - 1) as f64).round() as usize;
+    Zip::from(&mut spectrum)
+        .and(spec.columns())
+        .par_for_each(|y, column| {
+            let mut buf = column.to_vec();
+            let (_, &mut x, _) = buf.select_nth_unstable_by(idx, f32::total_cmp);
+            *y = x;
+        });
+    spectrum
+}
+
+/// spectral centroid (Hz, the magnitude-weighted mean frequency) of each frame
+/// centered at i * hop_length. NaN for the silent frames.
+pub fn calc_spectral_centroid(
+    wav: ArrayView1<f32>,
+    sr: u32,
+    win_length: usize,
+    hop_length: usize,
+    n_fft: usize,
+) -> Array1<f32> {
+    let stft = perform_stft(wav, win_length, hop_length, n_fft, None, None, None, true);
+    let hz_per_bin = sr as f32 / n_fft as f32;
+    let mut centroid = Array1::zeros(stft.shape()[0]);
+    Zip::from(&mut centroid)
+        .and(stft.rows())
+        .par_for_each(|y, frame| {
+            let (weighted_sum, sum) =
+                frame
+                    .iter()
+                    .enumerate()
+                    .fold((0., 0.), |(weighted_sum, sum), (i, x)| {
+                        let mag = x.norm();
+                        (weighted_sum + i as f32 * mag, sum + mag)
+                    });
+            *y = if sum > 0. {
+                weighted_sum / sum * hz_per_bin
+            } else {
+                f32::NAN
+            };
+        });
+    centroid
+}
+
 pub struct SpectrogramAnalyzer {
     windows: TupleIntMap<WinNfft, Array1<f32>>,
     fft_modules: IntMap<usize, Arc<dyn RealToComplex<f32>>>,
@@ -243,21 +382,69 @@ impl SpectrogramAnalyzer {
         }
     }
 
+    /// Returns the hop length of the setting, or larger one (time decimation) if needed
+    /// to keep the size of the spectrogram and its grey image under max_bytes.
+    pub fn calc_hop_length_within(
+        &self,
+        n_samples: usize,
+        sr: u32,
+        setting: &SpecSetting,
+        max_bytes: Option<usize>,
+    ) -> usize {
+        let (hop_length, _, n_fft) = setting.calc_framing_params(sr);
+        let Some(max_bytes) = max_bytes else {
+            return hop_length;
+        };
+        let n_freqs = match setting.freq_scale {
+            FreqScale::Linear => n_fft / 2 + 1,
+            FreqScale::Mel => self.mel_fb(sr, n_fft).shape()[1],
+        };
+        let max_frames = (max_bytes / (n_freqs * BYTES_PER_BIN)).max(2);
+        // n_frames = n_samples / hop_length + 1
+        hop_length.max(n_samples.div_ceil(max_frames - 1))
+    }
+
+    /// memory (bytes) temporarily used by calc_spec (the complex STFT and its magnitude)
+    pub fn estimate_calc_bytes(
+        &self,
+        n_samples: usize,
+        sr: u32,
+        setting: &SpecSetting,
+        max_bytes: Option<usize>,
+    ) -> usize {
+        let (_, _, n_fft) = setting.calc_framing_params(sr);
+        let hop_length = self.calc_hop_length_within(n_samples, sr, setting, max_bytes);
+        let n_frames = n_samples / hop_length + 1;
+        n_frames * (n_fft / 2 + 1) * 3 * size_of::<f32>()
+    }
+
+    /// Returns (spectrogram, hop length actually used).
+    /// on_progress is called with the fraction of the STFT frames done.
     pub fn calc_spec(
         &self,
         wav: ArrayView1<f32>,
         sr: u32,
         setting: &SpecSetting,
+        max_bytes: Option<usize>,
+        on_progress: Option<&(dyn Fn(f32) + Sync)>,
         parallel: bool,
-    ) -> Array2<f32> {
-        let (hop_length, win_length, n_fft) = setting.calc_framing_params(sr);
+    ) -> (Array2<f32>, usize) {
+        let (_, win_length, n_fft) = setting.calc_framing_params(sr);
+        let hop_length = self.calc_hop_length_within(wav.len(), sr, setting, max_bytes);
         let window = self.window(win_length, n_fft);
         let fft_module = self.fft_module(n_fft);
         let stft = perform_stft(
-            wav, win_length, hop_length, n_fft, window, fft_module, parallel,
+            wav,
+            win_length,
+            hop_length,
+            n_fft,
+            window,
+            fft_module,
+            on_progress,
+            parallel,
         );
         let mut linspec = stft.mapv(|x| x.norm());
-        match setting.freq_scale {
+        let spec = match setting.freq_scale {
             FreqScale::Linear => {
                 linspec.dB_from_amp_inplace_default();
                 linspec
@@ -267,7 +454,8 @@ impl SpectrogramAnalyzer {
                 melspec.dB_from_amp_inplace_default();
                 melspec
             }
-        }
+        };
+        (spec, hop_length)
     }
 
     fn window(&self, win_length: usize, n_fft: usize) -> CowArray<f32, Ix1> {
@@ -310,3 +498,32 @@ impl SpectrogramAnalyzer {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f32::consts::PI;
+
+    #[test]
+    fn spectral_centroid_works() {
+        let sr = 48000;
+        let (_, win_length, n_fft) = SpecSetting::new().calc_framing_params(sr);
+        let hop_length = 480;
+        // 1 kHz sine for 0.5 sec followed by 0.5 sec of silence
+        let wav = Array1::from_shape_fn(sr as usize, |i| {
+            if i < sr as usize / 2 {
+                (2. * PI * 1000. * i as f32 / sr as f32).sin()
+            } else {
+                0.
+            }
+        });
+        let centroid = calc_spectral_centroid(wav.view(), sr, win_length, hop_length, n_fft);
+        assert_eq!(centroid.len(), sr as usize / hop_length + 1);
+        assert!(centroid
+            .slice(s![5..20])
+            .iter()
+            .all(|&x| (x - 1000.).abs() < 50.));
+        assert!(centroid.slice(s![-10..]).iter().all(|x| x.is_nan()));
+    }
+}
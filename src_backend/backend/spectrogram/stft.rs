@@ -1,4 +1,5 @@
 use std::ops::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use itertools::Itertools;
@@ -12,13 +13,18 @@ use super::super::utils::{Pad, PadMode};
 use super::super::windows::{calc_normalized_win, WindowType};
 use realfft::{RealFftPlanner, RealToComplex};
 
-pub fn perform_stft<'a, A>(
+/// number of the progress reports of an STFT at most
+const N_PROGRESS_STEPS: usize = 100;
+
+/// on_progress is called with the fraction of the frames done (at most N_PROGRESS_STEPS times)
+pub fn perform_stft<'a, 'b, A>(
     input: ArrayView1<A>,
     win_length: usize,
     hop_length: usize,
     n_fft: usize,
     window: impl Into<Option<CowArray<'a, A, Ix1>>>,
     fft_module: impl Into<Option<Arc<dyn RealToComplex<A>>>>,
+    on_progress: impl Into<Option<&'b (dyn Fn(f32) + Sync)>>,
     parallel: bool,
 ) -> Array2<Complex<A>>
 where
@@ -45,6 +51,7 @@ where
         fft_module.process(x, y).unwrap();
     };
 
+    let on_progress = on_progress.into();
     let n_chunks = rayon::current_num_threads();
 
     if input.len() < win_length {
@@ -98,6 +105,7 @@ where
         .axis_iter_mut(Axis(0))
         .map(|x| x.into_slice().unwrap())
         .collect();
+    let counter = FrameCounter::new(n_frames, on_progress);
 
     if parallel {
         let (front_out_frames, out_frames) = out_frames.split_at_mut(front_frames.len());
@@ -114,22 +122,55 @@ where
                     .chain(back_out_frames.par_chunks_mut(n_chunks)),
             )
             .for_each(|(in_chunk, out_chunk)| {
+                let n_frames_chunk = in_chunk.len();
                 in_chunk
                     .iter_mut()
                     .zip_eq(out_chunk)
-                    .for_each(|(frame, out_frame)| do_fft((frame, *out_frame)))
+                    .for_each(|(frame, out_frame)| do_fft((frame, *out_frame)));
+                counter.add(n_frames_chunk);
             });
     } else {
         let in_frames = front_frames
             .iter_mut()
             .chain(frames.iter_mut())
             .chain(back_frames.iter_mut());
-        in_frames.zip_eq(out_frames).for_each(do_fft);
+        in_frames.zip_eq(out_frames).for_each(|x| {
+            do_fft(x);
+            counter.add(1);
+        });
     }
 
     output
 }
 
+/// counter of the frames done reporting the progress every 1 / N_PROGRESS_STEPS
+struct FrameCounter<'a> {
+    n_done: AtomicUsize,
+    n_total: usize,
+    on_progress: Option<&'a (dyn Fn(f32) + Sync)>,
+}
+
+impl<'a> FrameCounter<'a> {
+    fn new(n_total: usize, on_progress: Option<&'a (dyn Fn(f32) + Sync)>) -> Self {
+        FrameCounter {
+            n_done: AtomicUsize::new(0),
+            n_total,
+            on_progress,
+        }
+    }
+
+    fn add(&self, n_frames: usize) {
+        let Some(on_progress) = self.on_progress else {
+            return;
+        };
+        let prev = self.n_done.fetch_add(n_frames, Ordering::Relaxed);
+        let step = |n_done: usize| n_done * N_PROGRESS_STEPS / self.n_total.max(1);
+        if step(prev + n_frames) > step(prev) {
+            on_progress((prev + n_frames) as f32 / self.n_total as f32);
+        }
+    }
+}
+
 #[inline]
 fn to_windowed_frames<A: Float>(
     input: ArrayView1<A>,
@@ -177,7 +218,7 @@ mod tests {
     fn stft_works() {
         let impulse = Array1::<f32>::impulse(4, 2);
         assert_eq!(
-            perform_stft(impulse.view(), 4, 2, 4, None, None, false),
+            perform_stft(impulse.view(), 4, 2, 4, None, None, None, false),
             arr2(&[
                 [
                     Complex::<f32>::new(0., 0.),
@@ -201,7 +242,22 @@ mod tests {
     #[test]
     fn stft_short_wav() {
         let impulse = Array1::<f32>::impulse(2, 1);
-        let spec = perform_stft(impulse.view(), 8, 6, 8, None, None, false);
+        let spec = perform_stft(impulse.view(), 8, 6, 8, None, None, None, false);
         dbg!(spec.shape());
     }
+
+    #[test]
+    fn stft_progress_works() {
+        let wav = Array1::<f32>::impulse(48000, 100);
+        for parallel in [false, true] {
+            let fractions = parking_lot::Mutex::new(Vec::new());
+            let on_progress: &(dyn Fn(f32) + Sync) = &|fraction| fractions.lock().push(fraction);
+            let spec = perform_stft(wav.view(), 64, 16, 64, None, None, on_progress, parallel);
+            let mut fractions = fractions.into_inner();
+            assert!(fractions.len() <= N_PROGRESS_STEPS);
+            fractions.sort_by(f32::total_cmp);
+            assert_eq!(*fractions.last().unwrap(), 1.);
+            assert!(spec.shape()[0] > N_PROGRESS_STEPS);
+        }
+    }
 }
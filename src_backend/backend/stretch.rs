@@ -0,0 +1,275 @@
+use std::f32::consts::PI;
+
+use ndarray::prelude::*;
+use realfft::num_complex::Complex;
+use realfft::RealFftPlanner;
+
+use super::windows::hann;
+
+const N_FFT: usize = 2048;
+const SYNTH_HOP: usize = N_FFT / 4;
+
+/// range of the stretch ratio, which bounds the length of the stretched audio
+pub const STRETCH_RATIO_RANGE: (f64, f64) = (0.25, 4.);
+/// maximum pitch shift (semitones) in both directions
+pub const MAX_PITCH_SEMITONES: f64 = 24.;
+
+/// Parameters of non-destructive playback transforms of a track
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StretchParams {
+    /// output duration / input duration (> 1: slower)
+    pub ratio: f64,
+    pub semitones: f64,
+}
+
+impl Default for StretchParams {
+    fn default() -> Self {
+        StretchParams {
+            ratio: 1.,
+            semitones: 0.,
+        }
+    }
+}
+
+impl StretchParams {
+    #[inline]
+    pub fn is_identity(&self) -> bool {
+        self.ratio == 1. && self.semitones == 0.
+    }
+
+    #[inline]
+    pub fn pitch_factor(&self) -> f64 {
+        (self.semitones / 12.).exp2()
+    }
+
+    /// ratio and semitones clamped to the supported ranges (they should not be NaN)
+    #[inline]
+    pub fn clamped(self) -> Self {
+        StretchParams {
+            ratio: self
+                .ratio
+                .clamp(STRETCH_RATIO_RANGE.0, STRETCH_RATIO_RANGE.1),
+            semitones: self
+                .semitones
+                .clamp(-MAX_PITCH_SEMITONES, MAX_PITCH_SEMITONES),
+        }
+    }
+}
+
+#[inline]
+fn wrap_phase(x: f32) -> f32 {
+    x - 2. * PI * (x / (2. * PI)).round()
+}
+
+/// phase vocoder time stretching. The output length is round(input length * ratio).
+fn phase_vocoder(wav: ArrayView1<f32>, ratio: f64) -> Array1<f32> {
+    let out_len = (wav.len() as f64 * ratio).round() as usize;
+    if wav.is_empty() || out_len == 0 {
+        return Array1::zeros(out_len);
+    }
+    let n_half = N_FFT / 2;
+    let mut padded = Array1::<f32>::zeros(wav.len() + N_FFT);
+    padded
+        .slice_mut(s![n_half..n_half + wav.len()])
+        .assign(&wav);
+
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(N_FFT);
+    let inverse = planner.plan_fft_inverse(N_FFT);
+    let window = hann::<f32>(N_FFT, false);
+    let omega = Array1::from_shape_fn(n_half + 1, |k| 2. * PI * k as f32 / N_FFT as f32);
+
+    let n_frames = (out_len + N_FFT).div_ceil(SYNTH_HOP) + 1;
+    let mut output = Array1::<f32>::zeros((n_frames - 1) * SYNTH_HOP + N_FFT);
+    let mut win_sum = Array1::<f32>::zeros(output.len());
+    let mut frame = vec![0f32; N_FFT];
+    let mut spectrum = forward.make_output_vec();
+    let mut prev_phase = Array1::<f32>::zeros(n_half + 1);
+    let mut synth_phase = Array1::<f32>::zeros(n_half + 1);
+    let mut prev_i_ana = 0;
+    for i_frame in 0..n_frames {
+        let i_ana = ((i_frame * SYNTH_HOP) as f64 / ratio).round() as usize;
+        frame.iter_mut().enumerate().for_each(|(i, y)| {
+            *y = padded.get(i_ana + i).map_or(0., |&x| x * window[i]);
+        });
+        forward.process(&mut frame, &mut spectrum).unwrap();
+
+        let ana_hop = (i_ana - prev_i_ana) as f32;
+        for (k, x) in spectrum.iter_mut().enumerate() {
+            let (mag, phase) = x.to_polar();
+            if i_frame == 0 {
+                synth_phase[k] = phase;
+            } else {
+                let deviation = wrap_phase(phase - prev_phase[k] - omega[k] * ana_hop);
+                let true_freq = if ana_hop > 0. {
+                    omega[k] + deviation / ana_hop
+                } else {
+                    omega[k]
+                };
+                synth_phase[k] = wrap_phase(synth_phase[k] + true_freq * SYNTH_HOP as f32);
+            }
+            prev_phase[k] = phase;
+            *x = Complex::from_polar(mag, synth_phase[k]);
+        }
+        spectrum[0].im = 0.;
+        spectrum[n_half].im = 0.;
+        prev_i_ana = i_ana;
+
+        inverse.process(&mut spectrum, &mut frame).unwrap();
+        let i_synth = i_frame * SYNTH_HOP;
+        for (i, &x) in frame.iter().enumerate() {
+            output[i_synth + i] += x / N_FFT as f32 * window[i];
+            win_sum[i_synth + i] += window[i] * window[i];
+        }
+    }
+    Zip::from(&mut output)
+        .and(&win_sum)
+        .for_each(|y, &w| *y = if w > 1e-3 { *y / w } else { 0. });
+    output.slice_move(s![n_half..n_half + out_len])
+}
+
+/// linearly interpolated sample at the fractional index pos (zero outside of the signal)
+#[inline]
+fn interp_linear(wav: ArrayView1<f32>, pos: f64) -> f32 {
+    if pos < 0. {
+        return 0.;
+    }
+    let i_floor = pos.floor() as usize;
+    let frac = (pos - i_floor as f64) as f32;
+    let x0 = wav.get(i_floor).copied().unwrap_or(0.);
+    let x1 = wav.get(i_floor + 1).copied().unwrap_or(0.);
+    x0 + (x1 - x0) * frac
+}
+
+/// linear interpolation reading the input `factor` times faster (raises the pitch if factor > 1)
+fn resample_linear(wav: ArrayView1<f32>, factor: f64, out_len: usize) -> Array1<f32> {
+    Array1::from_shape_fn(out_len, |i| interp_linear(wav, i as f64 * factor))
+}
+
+/// Hann-windowed grain of out_len samples read around the index center at the speed
+/// (varispeed; a negative speed reads backward). Used for tape-style scrubbing.
+pub fn varispeed_grain(
+    wav: ArrayView1<f32>,
+    center: f64,
+    speed: f64,
+    out_len: usize,
+) -> Array1<f32> {
+    let window = hann::<f32>(out_len, false);
+    let half_len = out_len as f64 / 2.;
+    Array1::from_shape_fn(out_len, |i| {
+        interp_linear(wav, (i as f64 - half_len).mul_add(speed, center)) * window[i]
+    })
+}
+
+/// time-stretch and pitch-shift a channel.
+/// Pitch shifting = stretching by the pitch factor followed by resampling.
+pub fn stretch_and_shift(wav: ArrayView1<f32>, params: StretchParams) -> Array1<f32> {
+    if params.is_identity() {
+        return wav.to_owned();
+    }
+    let out_len = (wav.len() as f64 * params.ratio).round() as usize;
+    let pitch_factor = params.pitch_factor();
+    let stretched = phase_vocoder(wav, params.ratio * pitch_factor);
+    if params.semitones == 0. {
+        stretched
+    } else {
+        resample_linear(stretched.view(), pitch_factor, out_len)
+    }
+}
+
+/// stretch_and_shift of all the channels in parallel. None if params is the identity.
+pub fn stretch_and_shift_wavs(wavs: ArrayView2<f32>, params: StretchParams) -> Option<Array2<f32>> {
+    if params.is_identity() {
+        return None;
+    }
+    let out_len = (wavs.shape()[1] as f64 * params.ratio).round() as usize;
+    let mut stretched = Array2::zeros((wavs.shape()[0], out_len));
+    Zip::from(stretched.rows_mut())
+        .and(wavs.rows())
+        .par_for_each(|mut y, x| y.assign(&stretch_and_shift(x, params)));
+    Some(stretched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    fn sine(hz: f32, sr: u32, len: usize) -> Array1<f32> {
+        Array1::from_shape_fn(len, |i| (2. * PI * hz * i as f32 / sr as f32).sin() * 0.5)
+    }
+
+    /// frequency of the sine estimated by counting zero crossings in the middle of the signal
+    fn estimate_hz(wav: ArrayView1<f32>, sr: u32) -> f32 {
+        let mid = wav.slice(s![wav.len() / 4..wav.len() * 3 / 4]);
+        let n_crossings = mid
+            .windows(2)
+            .into_iter()
+            .filter(|w| (w[0] < 0.) != (w[1] < 0.))
+            .count();
+        n_crossings as f32 / 2. / (mid.len() as f32 / sr as f32)
+    }
+
+    #[test]
+    fn stretch_keeps_pitch() {
+        let wav = sine(440., 48000, 48000);
+        let params = StretchParams {
+            ratio: 1.5,
+            semitones: 0.,
+        };
+        let stretched = stretch_and_shift(wav.view(), params);
+        assert_eq!(stretched.len(), 72000);
+        assert_abs_diff_eq!(estimate_hz(stretched.view(), 48000), 440., epsilon = 5.);
+    }
+
+    #[test]
+    fn clamped_works() {
+        let params = StretchParams {
+            ratio: 1e6,
+            semitones: -200.,
+        };
+        assert_eq!(
+            params.clamped(),
+            StretchParams {
+                ratio: 4.,
+                semitones: -24.,
+            }
+        );
+        let params = StretchParams {
+            ratio: 1.5,
+            semitones: 3.,
+        };
+        assert_eq!(params.clamped(), params);
+    }
+
+    #[test]
+    fn varispeed_grain_works() {
+        let wav = sine(440., 48000, 48000);
+        let grain = varispeed_grain(wav.view(), 24000., 2., 4800);
+        assert_eq!(grain.len(), 4800);
+        assert_abs_diff_eq!(grain[0], 0.);
+        // the middle of the grain is the center of the input
+        assert_abs_diff_eq!(grain[2400], wav[24000], epsilon = 1e-3);
+        assert_abs_diff_eq!(estimate_hz(grain.view(), 48000), 880., epsilon = 20.);
+
+        let reversed = varispeed_grain(wav.view(), 24000., -1., 4800);
+        assert_abs_diff_eq!(
+            reversed[2401],
+            wav[23999] * hann::<f32>(4800, false)[2401],
+            epsilon = 1e-5
+        );
+    }
+
+    #[test]
+    fn pitch_shift_keeps_length() {
+        let wav = sine(440., 48000, 48000);
+        let params = StretchParams {
+            ratio: 1.,
+            semitones: 12.,
+        };
+        let shifted = stretch_and_shift(wav.view(), params);
+        assert_eq!(shifted.len(), 48000);
+        assert_abs_diff_eq!(estimate_hz(shifted.view(), 48000), 880., epsilon = 10.);
+    }
+}
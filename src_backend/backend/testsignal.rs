@@ -0,0 +1,275 @@
+//! Deterministic synthetic signals for references of the analyzer and the monitoring
+//! (and fixtures of the tests)
+
+use std::f64::consts::PI;
+
+use napi_derive::napi;
+use ndarray::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::dynamics::DeciBel;
+use super::utils::Xorshift;
+
+const MAX_SEC: f64 = 600.;
+const MAX_N_CH: u32 = 32;
+
+#[napi(string_enum)]
+#[derive(Debug, Eq, PartialEq)]
+pub enum TestSignalKind {
+    /// exponential sine sweep from start_hz to end_hz
+    SineSweep,
+    /// a single full-scale sample at the start
+    Impulse,
+    /// -3 dB/oct noise generated from the seed
+    PinkNoise,
+    /// band-limited (PolyBLEP) square wave of hz
+    Square,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+#[allow(non_snake_case)]
+pub struct TestSignalParams {
+    pub sr: u32,
+    pub sec: f64,
+    pub channels: u32,
+    /// peak amplitude (dBFS). Ignored for Impulse.
+    #[serde(rename = "peakdB")]
+    pub peak_dB: f64,
+    /// frequency of Square
+    pub hz: f64,
+    /// frequency range of SineSweep
+    pub start_hz: f64,
+    pub end_hz: f64,
+    /// seed of PinkNoise. Each channel uses a different seed derived from it.
+    pub seed: u64,
+}
+
+impl Default for TestSignalParams {
+    fn default() -> Self {
+        TestSignalParams {
+            sr: 48000,
+            sec: 5.,
+            channels: 1,
+            peak_dB: -6.,
+            hz: 1000.,
+            start_hz: 20.,
+            end_hz: 20000.,
+            seed: 0,
+        }
+    }
+}
+
+impl TestSignalParams {
+    fn validate(&self, kind: TestSignalKind) -> Result<(), String> {
+        let nyquist = self.sr as f64 / 2.;
+        if self.sr == 0 {
+            return Err("The sample rate should be positive.".into());
+        }
+        if !(self.sec > 0. && self.sec <= MAX_SEC) {
+            return Err(format!("The length should be in (0, {}] sec.", MAX_SEC));
+        }
+        if !(1..=MAX_N_CH).contains(&self.channels) {
+            return Err(format!(
+                "The number of channels should be 1 ~ {}.",
+                MAX_N_CH
+            ));
+        }
+        if !self.peak_dB.is_finite() || self.peak_dB > 0. {
+            return Err("The peak should be at most 0 dBFS.".into());
+        }
+        match kind {
+            TestSignalKind::SineSweep
+                if !(0. < self.start_hz
+                    && self.start_hz < self.end_hz
+                    && self.end_hz <= nyquist) =>
+            {
+                Err("The sweep range should be 0 < startHz < endHz <= sr / 2.".into())
+            }
+            TestSignalKind::Square if !(0. < self.hz && self.hz < nyquist) => {
+                Err("The frequency should be in (0, sr / 2).".into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        ((self.sec * self.sr as f64).round() as usize).max(1)
+    }
+
+    /// file stem describing the signal (e.g. "sine_sweep_20-20000Hz_48000Hz_5s")
+    pub fn stem(&self, kind: TestSignalKind) -> String {
+        let desc = match kind {
+            TestSignalKind::SineSweep => format!("sine_sweep_{}-{}Hz", self.start_hz, self.end_hz),
+            TestSignalKind::Impulse => "impulse".into(),
+            TestSignalKind::PinkNoise => format!("pink_noise_seed{}", self.seed),
+            TestSignalKind::Square => format!("square_{}Hz", self.hz),
+        };
+        format!("{}_{}Hz_{}s", desc, self.sr, self.sec)
+    }
+}
+
+fn sine_sweep(len: usize, sr: u32, start_hz: f64, end_hz: f64) -> Array1<f64> {
+    let sec = len as f64 / sr as f64;
+    let log_ratio = (end_hz / start_hz).ln();
+    Array1::from_shape_fn(len, |i| {
+        let t = i as f64 / sr as f64;
+        (2. * PI * start_hz * sec / log_ratio * ((t / sec * log_ratio).exp() - 1.)).sin()
+    })
+}
+
+/// Paul Kellet's refined filter of white noise (accurate to ±0.05 dB above 9.2 Hz at 44.1 kHz)
+fn pink_noise(len: usize, seed: u64) -> Array1<f64> {
+    let mut rng = Xorshift::new(seed);
+    let mut b = [0f64; 7];
+    Array1::from_shape_simple_fn(len, || {
+        let white = rng.next_f64();
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.1538520;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+        let y = b.iter().sum::<f64>() + white * 0.5362;
+        b[6] = white * 0.115926;
+        y
+    })
+}
+
+fn square(len: usize, sr: u32, hz: f64) -> Array1<f64> {
+    let dt = hz / sr as f64;
+    // residual of the band-limited step at the discontinuity
+    let poly_blep = |t: f64| {
+        if t < dt {
+            let t = t / dt;
+            2. * t - t * t - 1.
+        } else if t > 1. - dt {
+            let t = (t - 1.) / dt;
+            t * t + 2. * t + 1.
+        } else {
+            0.
+        }
+    };
+    Array1::from_shape_fn(len, |i| {
+        let phase = (i as f64 * dt).fract();
+        let naive = if phase < 0.5 { 1. } else { -1. };
+        naive + poly_blep(phase) - poly_blep((phase + 0.5).fract())
+    })
+}
+
+/// Generate the signal (shape: (channels, sec * sr))
+pub fn generate_test_signal(
+    kind: TestSignalKind,
+    params: &TestSignalParams,
+) -> Result<Array2<f32>, String> {
+    params.validate(kind)?;
+    let (n_ch, len) = (params.channels as usize, params.len());
+    if kind == TestSignalKind::Impulse {
+        let mut wavs = Array2::zeros((n_ch, len));
+        wavs.column_mut(0).fill(1.);
+        return Ok(wavs);
+    }
+    let peak = params.peak_dB.amp_from_dB_default();
+    let normalize = |wav: Array1<f64>| {
+        let max = wav.iter().fold(0f64, |max, x| max.max(x.abs()));
+        wav.mapv(|x| (x / max * peak) as f32)
+    };
+    let mut wavs = Array2::zeros((n_ch, len));
+    for (ch, mut y) in wavs.outer_iter_mut().enumerate() {
+        let wav = match kind {
+            TestSignalKind::SineSweep => sine_sweep(len, params.sr, params.start_hz, params.end_hz),
+            TestSignalKind::PinkNoise => pink_noise(len, params.seed.wrapping_add(ch as u64)),
+            TestSignalKind::Square => square(len, params.sr, params.hz),
+            TestSignalKind::Impulse => unreachable!(),
+        };
+        y.assign(&normalize(wav));
+    }
+    Ok(wavs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn generate_works() {
+        let params = TestSignalParams {
+            channels: 2,
+            sec: 1.,
+            ..Default::default()
+        };
+        let peak = (-6f64).amp_from_dB_default() as f32;
+        for kind in [
+            TestSignalKind::SineSweep,
+            TestSignalKind::PinkNoise,
+            TestSignalKind::Square,
+        ] {
+            let wavs = generate_test_signal(kind, &params).unwrap();
+            assert_eq!(wavs.shape(), &[2, 48000]);
+            let max = wavs.iter().fold(0f32, |max, x| max.max(x.abs()));
+            assert_abs_diff_eq!(max, peak, epsilon = 1e-6);
+        }
+
+        let impulse = generate_test_signal(TestSignalKind::Impulse, &params).unwrap();
+        assert_eq!(impulse.sum(), 2.);
+        assert_eq!(impulse[[1, 0]], 1.);
+
+        // deterministic, and the channels of pink noise differ
+        let noise = generate_test_signal(TestSignalKind::PinkNoise, &params).unwrap();
+        assert_eq!(
+            noise,
+            generate_test_signal(TestSignalKind::PinkNoise, &params).unwrap()
+        );
+        assert_ne!(noise.row(0), noise.row(1));
+        // square wave has zero mean
+        let square = generate_test_signal(TestSignalKind::Square, &params).unwrap();
+        assert_abs_diff_eq!(square.row(0).mean().unwrap(), 0., epsilon = 1e-3);
+
+        let invalid = [
+            TestSignalParams {
+                sr: 0,
+                ..Default::default()
+            },
+            TestSignalParams {
+                sec: 0.,
+                ..Default::default()
+            },
+            TestSignalParams {
+                channels: 0,
+                ..Default::default()
+            },
+            TestSignalParams {
+                peak_dB: 1.,
+                ..Default::default()
+            },
+            TestSignalParams {
+                end_hz: 30000.,
+                ..Default::default()
+            },
+        ];
+        for params in invalid {
+            assert!(generate_test_signal(TestSignalKind::SineSweep, &params).is_err());
+        }
+        let params = TestSignalParams {
+            hz: 24000.,
+            ..Default::default()
+        };
+        assert!(generate_test_signal(TestSignalKind::Square, &params).is_err());
+    }
+
+    #[test]
+    fn stem_works() {
+        let params = TestSignalParams::default();
+        assert_eq!(
+            params.stem(TestSignalKind::SineSweep),
+            "sine_sweep_20-20000Hz_48000Hz_5s"
+        );
+        assert_eq!(
+            params.stem(TestSignalKind::Square),
+            "square_1000Hz_48000Hz_5s"
+        );
+    }
+}
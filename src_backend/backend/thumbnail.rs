@@ -0,0 +1,51 @@
+//! Tiny PNG thumbnails of the overview waveform for previews (e.g. the recent files list).
+//! The thumbnails are cached by the content hash of the track,
+//! so the same file opened again (even with another id) is not drawn again.
+
+use std::sync::LazyLock;
+
+use cached::{Cached, SizedCache};
+use parking_lot::Mutex;
+use tiny_skia::PixmapRef;
+
+use super::track::TrackList;
+use super::visualize::draw_overview_of;
+
+pub const MAX_THUMBNAIL_SIZE: u32 = 512;
+const CACHE_SIZE: usize = 256;
+
+/// (PCM hash, width, height, normalization gain bits)
+type ThumbnailKey = (String, u32, u32, u32);
+
+static CACHE: LazyLock<Mutex<SizedCache<ThumbnailKey, Vec<u8>>>> =
+    LazyLock::new(|| Mutex::new(SizedCache::with_size(CACHE_SIZE)));
+
+/// PNG of the overview of the whole track fitted to width x height px. None if no track exists.
+pub fn get_or_render_thumbnail(
+    tracklist: &TrackList,
+    id: usize,
+    width: u32,
+    height: u32,
+) -> Option<Result<Vec<u8>, String>> {
+    let track = tracklist.get(id)?;
+    let key = (
+        track.pcm_hash(),
+        width,
+        height,
+        track.normalize_gain().to_bits(),
+    );
+    if let Some(png) = CACHE.lock().cache_get(&key) {
+        return Some(Ok(png.clone()));
+    }
+    if track.sec() <= 0. {
+        return Some(Err("The track is empty.".into()));
+    }
+    let rgba = draw_overview_of(track, width, height, 1., width as f64 / track.sec());
+    let png = PixmapRef::from_bytes(&rgba, width, height)
+        .ok_or_else(|| "Failed to draw the thumbnail.".to_string())
+        .and_then(|pixmap| pixmap.encode_png().map_err(|e| e.to_string()));
+    if let Ok(png) = &png {
+        CACHE.lock().cache_set(key, png.clone());
+    }
+    Some(png)
+}
@@ -0,0 +1,133 @@
+//! Mapping between the coordinates of time
+//!
+//! - session time: the timeline shared by all tracks (markers, the selection, the playhead).
+//!   A track starts at its offset on the session timeline.
+//! - track time: the time of the (not stretched) audio of a track, used by drawing and stats.
+//! - sound time: the time of the time-stretched sound played by the player.
+
+/// time mapping of a track
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrackTimeline {
+    /// session time where the track starts (sec)
+    pub offset_sec: f64,
+    /// output duration / input duration of the played sound (> 1: slower)
+    pub stretch_ratio: f64,
+}
+
+impl Default for TrackTimeline {
+    fn default() -> Self {
+        TrackTimeline {
+            offset_sec: 0.,
+            stretch_ratio: 1.,
+        }
+    }
+}
+
+impl TrackTimeline {
+    #[inline]
+    pub fn session_to_track(&self, sec: f64) -> f64 {
+        sec - self.offset_sec
+    }
+
+    #[inline]
+    pub fn track_to_session(&self, sec: f64) -> f64 {
+        sec + self.offset_sec
+    }
+
+    #[inline]
+    pub fn track_to_sound(&self, sec: f64) -> f64 {
+        sec * self.stretch_ratio
+    }
+
+    #[inline]
+    pub fn sound_to_track(&self, sec: f64) -> f64 {
+        sec / self.stretch_ratio
+    }
+
+    /// position of the sound to seek to. The session time before the track maps to the start.
+    #[inline]
+    pub fn session_to_sound(&self, sec: f64) -> f64 {
+        self.track_to_sound(self.session_to_track(sec)).max(0.)
+    }
+
+    #[inline]
+    pub fn sound_to_session(&self, sec: f64) -> f64 {
+        self.track_to_session(self.sound_to_track(sec))
+    }
+
+    /// (start, end) of the track time
+    #[inline]
+    pub fn session_to_track_range(&self, (start, end): (f64, f64)) -> (f64, f64) {
+        (self.session_to_track(start), self.session_to_track(end))
+    }
+
+    /// (start, end) of the session time
+    #[inline]
+    pub fn track_to_session_range(&self, (start, end): (f64, f64)) -> (f64, f64) {
+        (self.track_to_session(start), self.track_to_session(end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn timeline() -> impl Strategy<Value = TrackTimeline> {
+        (-1e4..1e4f64, 0.01..100f64).prop_map(|(offset_sec, stretch_ratio)| TrackTimeline {
+            offset_sec,
+            stretch_ratio,
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn session_track_roundtrip(timeline in timeline(), sec in -1e5..1e5f64) {
+            let track_sec = timeline.session_to_track(sec);
+            assert_abs_diff_eq!(timeline.track_to_session(track_sec), sec, epsilon = 1e-9);
+        }
+
+        #[test]
+        fn sound_session_roundtrip(timeline in timeline(), sound_sec in 0.0..1e5f64) {
+            let sec = timeline.sound_to_session(sound_sec);
+            assert_abs_diff_eq!(
+                timeline.session_to_sound(sec),
+                sound_sec,
+                epsilon = 1e-6 * sound_sec.max(1.)
+            );
+        }
+
+        #[test]
+        fn mapping_is_monotonic(timeline in timeline(), a in -1e5..1e5f64, b in -1e5..1e5f64) {
+            let (a, b) = (a.min(b), a.max(b));
+            let (start, end) = timeline.session_to_track_range((a, b));
+            prop_assert!(start <= end);
+            prop_assert!(timeline.session_to_sound(a) <= timeline.session_to_sound(b));
+            // the length of a range is kept on the session timeline
+            assert_abs_diff_eq!(end - start, b - a, epsilon = 1e-9);
+            let (start, end) = timeline.track_to_session_range((start, end));
+            assert_abs_diff_eq!(start, a, epsilon = 1e-9);
+            assert_abs_diff_eq!(end, b, epsilon = 1e-9);
+        }
+
+        #[test]
+        fn sound_before_track_is_clamped(timeline in timeline(), before in 0.0..1e4f64) {
+            prop_assert_eq!(timeline.session_to_sound(timeline.offset_sec - before), 0.);
+        }
+    }
+
+    #[test]
+    fn timeline_works() {
+        let timeline = TrackTimeline {
+            offset_sec: 2.,
+            stretch_ratio: 2.,
+        };
+        assert_eq!(timeline.session_to_track(3.), 1.);
+        assert_eq!(timeline.session_to_sound(3.), 2.);
+        assert_eq!(timeline.sound_to_session(2.), 3.);
+        assert_eq!(timeline.session_to_sound(1.), 0.);
+        assert_eq!(TrackTimeline::default().session_to_sound(1.5), 1.5);
+    }
+}
@@ -1,23 +1,51 @@
+use std::borrow::Cow;
 use std::fmt;
 use std::ops::Index;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
 
 use identity_hash::{IntMap, IntSet};
 use kittyaudio::Frame;
+use napi_derive::napi;
 use ndarray::prelude::*;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use symphonia::core::errors::Error as SymphoniaError;
 
-use super::audio::{open_audio_file, Audio, AudioFormatInfo};
+use super::audio::{
+    calc_pcm_hash, open_audio_file_with_gaps, open_raw_pcm_file, resample_to, wavs_to_frames,
+    Audio, AudioFormatInfo, DecodeGap, RawPcmParams,
+};
+use super::compare::{compare_all_pairs, mixdown_for_compare, TrackPairComparison};
+use super::convolution::ImpulseResponse;
+use super::denoise::SpectralDenoiser;
+use super::downmix::{
+    analyze_channel_relationship, calc_mono_compatibility, downmix, ChannelRelationship,
+    DownmixMode, MonoCompatibility,
+};
+use super::dropouts::{detect_dropouts, Dropout};
 use super::dynamics::{
-    AudioStats, GuardClippingMode, GuardClippingResult, GuardClippingStats, Normalize,
-    NormalizeTarget, StatCalculator,
+    calc_band_loudness, calc_headroom_report, calc_segmented_loudness, AudioStats,
+    BandLoudnessContribution, GuardClippingMode, GuardClippingResult, GuardClippingStats,
+    HeadroomReport, Normalize, NormalizeTarget, PerfectLimiter, SegmentLoudness, StatCalculator,
 };
+use super::filters::{TfBoxSolo, WavViewMode};
+use super::framing::{calc_initial_view, InitialView};
+use super::histogram::{calc_amplitude_histogram, calc_bit_depth_usage, BitDepthUsage};
+use super::lossy::{estimate_lossy_generation, LossyGenerationEstimate};
+use super::pairing::pair_by_pattern;
+use super::similarity::{find_similar_segments, SimilarSegment};
 use super::spectrogram::{SpecSetting, SrWinNfft};
+use super::stretch::{stretch_and_shift_wavs, varispeed_grain, StretchParams};
+use super::timeline::TrackTimeline;
 use super::tuple_hasher::TupleIntSet;
 use super::utils::unique_filenames;
-use super::visualize::{CalcWidth, IdxLen, PartGreyInfo};
-use super::IdChVec;
+use super::visualize::{
+    calc_wav_pixel_envelope, classify_wav_pixels, BlockHashes, CalcWidth, IdxLen, PartGreyInfo,
+    WavPixelClass, WavPyramid,
+};
+use super::{IdCh, IdChVec};
 
 macro_rules! iter_filtered {
     ($vec: expr) => {
@@ -47,52 +75,385 @@ macro_rules! indexed_par_iter_mut_filtered {
     };
 }
 
+#[napi(string_enum)]
+#[derive(Debug, Eq, PartialEq)]
+pub enum TrackGroupBy {
+    Album,
+    Date,
+    Folder,
+}
+
+/// Predicates of tracks for querying. A track matches if it satisfies all the given predicates.
+/// The min/max ranges are inclusive.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+#[allow(non_snake_case)]
+pub struct TrackFilter {
+    /// case-insensitive substring of the file name
+    pub name_contains: Option<String>,
+    pub sr: Option<u32>,
+    pub n_ch: Option<usize>,
+    pub min_sec: Option<f64>,
+    pub max_sec: Option<f64>,
+    #[serde(rename = "minLUFS")]
+    pub min_lufs: Option<f64>,
+    #[serde(rename = "maxLUFS")]
+    pub max_lufs: Option<f64>,
+    #[serde(rename = "minPeakdB")]
+    pub min_peak_dB: Option<f32>,
+    #[serde(rename = "maxPeakdB")]
+    pub max_peak_dB: Option<f32>,
+}
+
+impl TrackFilter {
+    fn matches(&self, track: &AudioTrack, filename: &str) -> bool {
+        fn in_range<T: PartialOrd>(x: T, min: Option<T>, max: Option<T>) -> bool {
+            min.is_none_or(|min| x >= min) && max.is_none_or(|max| x <= max)
+        }
+        let stats = track.stats();
+        self.name_contains
+            .as_ref()
+            .is_none_or(|name| filename.to_lowercase().contains(&name.to_lowercase()))
+            && self.sr.is_none_or(|sr| track.sr() == sr)
+            && self.n_ch.is_none_or(|n_ch| track.n_ch() == n_ch)
+            && in_range(track.sec(), self.min_sec, self.max_sec)
+            && in_range(stats.global_lufs, self.min_lufs, self.max_lufs)
+            && in_range(stats.max_peak_dB, self.min_peak_dB, self.max_peak_dB)
+    }
+}
+
+/// time-stretch of a track computed without holding the lock of the track list
+/// (prepared by TrackList::prepare_stretch and applied by TrackList::apply_stretch)
+pub struct PendingStretch {
+    id: usize,
+    params: StretchParams,
+    /// copy of the audio of the track when prepared
+    input: Array2<f32>,
+    stretched: Option<Array2<f32>>,
+}
+
+impl PendingStretch {
+    /// run the phase vocoder over the whole track (takes seconds for long tracks)
+    pub fn compute(&mut self) {
+        self.stretched = stretch_and_shift_wavs(self.input.view(), self.params);
+    }
+}
+
+/// audio of a track copied to render the processed playback by PendingPlayback::render
+/// without holding the lock of the track list (prepared by AudioTrack::prepare_playback)
+pub struct PendingPlayback {
+    /// the part of the audio to play needed for the processing
+    wavs: Array2<f32>,
+    /// index of the first sample of wavs in the audio to play
+    i_offset: usize,
+    /// length of the audio to play
+    len: usize,
+    sr: u32,
+    stretch_ratio: f64,
+}
+
+impl PendingPlayback {
+    /// frames for playback at the unity gain (takes seconds for long tracks with the IR).
+    /// The outside of the copied part is silent.
+    pub fn render(
+        &self,
+        downmix_mode: DownmixMode,
+        ir: Option<&ImpulseResponse>,
+        denoiser: Option<&SpectralDenoiser>,
+        solo: Option<&TfBoxSolo>,
+    ) -> Vec<Frame> {
+        let processed = process_playback(
+            self.wavs.view(),
+            self.i_offset,
+            self.sr,
+            self.stretch_ratio,
+            downmix_mode,
+            ir,
+            denoiser,
+            solo,
+        );
+        let wavs = processed.as_ref().map_or(self.wavs.view(), |x| x.view());
+        if self.i_offset == 0 && wavs.shape()[1] == self.len {
+            return wavs_to_frames(wavs);
+        }
+        let mut padded = Array2::zeros((wavs.shape()[0], self.len));
+        padded
+            .slice_mut(s![.., self.i_offset..self.i_offset + wavs.shape()[1]])
+            .assign(&wavs);
+        wavs_to_frames(padded.view())
+    }
+}
+
+/// last version given to the audio to play of the tracks
+static PLAYBACK_VERSION: AtomicU64 = AtomicU64::new(0);
+
+#[inline]
+fn next_playback_version() -> u64 {
+    PLAYBACK_VERSION.fetch_add(1, Ordering::Relaxed) + 1
+}
+
 #[readonly::make]
 pub struct AudioTrack {
     pub format_info: AudioFormatInfo,
+    pub wav_view_mode: WavViewMode,
+    pub stretch_params: StretchParams,
+    /// session time where the track starts (sec)
+    pub offset_sec: f64,
+    pub polarity_inverted: bool,
+    /// source channel of each channel (for swapping channels)
+    pub channel_order: Vec<usize>,
+    pub group_id: Option<usize>,
     path: PathBuf,
+    /// Some if the track is loaded from a headerless PCM file
+    raw_params: Option<RawPcmParams>,
+    /// time ranges of corrupt packets filled with silence while decoding
+    decode_gaps: Vec<DecodeGap>,
+    /// source channels kept on decoding (e.g. a few channels of an ambisonics file).
+    /// None for all channels.
+    decoded_channels: Option<Vec<usize>>,
     original: Audio,
     audio: Audio,
     interleaved: Vec<Frame>,
+    filtered_for_drawing: Option<Array2<f32>>,
+    /// decimated min/max and block hashes of each channel of wav_for_overview
+    overview_pyramids: Vec<WavPyramid>,
+    /// block hashes of the limiter gain drawn in the overview. Empty if no gain sequence.
+    limiter_gain_hashes: BlockHashes,
+    /// normalization gain applied to the original audio
+    normalize_gain: f32,
+    /// calculated on the first request
+    pcm_hash: OnceLock<String>,
+    /// changed whenever the audio to play changes (unique across the tracks)
+    playback_version: u64,
+    stretched: Option<Array2<f32>>,
     stat_calculator: StatCalculator,
 }
 
 impl AudioTrack {
     pub fn new(path: String) -> Result<Self, SymphoniaError> {
-        let (wavs, format_info) = open_audio_file(&path)?;
+        let (wavs, format_info, decode_gaps) = open_audio_file_with_gaps(&path)?;
+        Ok(Self::from_decoded(
+            path,
+            wavs,
+            format_info,
+            None,
+            decode_gaps,
+        ))
+    }
+
+    pub fn new_raw(path: String, raw_params: RawPcmParams) -> Result<Self, SymphoniaError> {
+        let (wavs, format_info) = open_raw_pcm_file(&path, &raw_params)?;
+        Ok(Self::from_decoded(
+            path,
+            wavs,
+            format_info,
+            Some(raw_params),
+            Vec::new(),
+        ))
+    }
+
+    fn from_decoded(
+        path: String,
+        wavs: Array2<f32>,
+        format_info: AudioFormatInfo,
+        raw_params: Option<RawPcmParams>,
+        decode_gaps: Vec<DecodeGap>,
+    ) -> Self {
         let mut stat_calculator = StatCalculator::new(wavs.shape()[0] as u32, format_info.sr);
         let original = Audio::new(wavs, format_info.sr, &mut stat_calculator);
 
         let audio = original.clone();
         let interleaved = (&audio).into();
+        let overview_pyramids = audio.view().outer_iter().map(WavPyramid::new).collect();
 
-        Ok(AudioTrack {
+        AudioTrack {
+            channel_order: (0..audio.n_ch()).collect(),
             format_info,
+            wav_view_mode: Default::default(),
+            stretch_params: Default::default(),
+            offset_sec: 0.,
+            polarity_inverted: false,
+            group_id: None,
             path: PathBuf::from(path).canonicalize().unwrap(),
+            raw_params,
+            decode_gaps,
+            decoded_channels: None,
             original,
             audio,
             interleaved,
+            filtered_for_drawing: None,
+            overview_pyramids,
+            limiter_gain_hashes: Default::default(),
+            normalize_gain: 1.,
+            pcm_hash: OnceLock::new(),
+            playback_version: next_playback_version(),
+            stretched: None,
             stat_calculator,
-        })
+        }
     }
 
     pub fn reload(&mut self) -> Result<bool, SymphoniaError> {
-        let (wavs, format_info) = open_audio_file(self.path.to_string_lossy().as_ref())?;
-        if wavs.view() == self.original.view() && format_info == self.format_info {
+        let path = self.path.to_string_lossy();
+        let (wavs, format_info, decode_gaps) = match &self.raw_params {
+            Some(raw_params) => {
+                let (wavs, format_info) = open_raw_pcm_file(&path, raw_params)?;
+                (wavs, format_info, Vec::new())
+            }
+            None => open_audio_file_with_gaps(&path)?,
+        };
+        let wavs = select_decoded_channels(wavs, self.decoded_channels.as_deref());
+        if wavs.view() == self.original.view()
+            && format_info == self.format_info
+            && decode_gaps == self.decode_gaps
+        {
             return Ok(false);
         }
         self.stat_calculator
             .change_parameters(wavs.shape()[0] as u32, format_info.sr);
         let original = Audio::new(wavs, format_info.sr, &mut self.stat_calculator);
 
+        if self.channel_order.len() != original.n_ch() {
+            self.channel_order = (0..original.n_ch()).collect();
+        }
         self.format_info = format_info;
+        self.decode_gaps = decode_gaps;
         self.original = original.clone();
+        self.pcm_hash = OnceLock::new();
         self.audio = original;
+        self.normalize_gain = 1.;
         self.interleaved = (&self.audio).into();
+        self.update_filtered_for_drawing();
+        self.update_overview_pyramids();
+        self.update_stretched();
 
         Ok(true)
     }
 
+    /// Decode only the source channels (all channels if None), and reload the track.
+    /// The previous channels are kept on error.
+    pub fn set_decoded_channels(
+        &mut self,
+        channels: Option<Vec<usize>>,
+    ) -> Result<bool, SymphoniaError> {
+        if channels == self.decoded_channels {
+            return Ok(false);
+        }
+        let prev_channels = std::mem::replace(&mut self.decoded_channels, channels);
+        let result = self.reload();
+        if result.is_err() {
+            self.decoded_channels = prev_channels;
+        }
+        result
+    }
+
+    /// source channel of each decoded channel
+    pub fn decoded_channels(&self) -> Vec<usize> {
+        self.decoded_channels
+            .clone()
+            .unwrap_or_else(|| (0..self.format_info.n_ch).collect())
+    }
+
+    pub fn set_wav_view_mode(&mut self, mode: WavViewMode) {
+        if self.wav_view_mode != mode {
+            self.wav_view_mode = mode;
+            self.update_filtered_for_drawing();
+        }
+    }
+
+    fn update_filtered_for_drawing(&mut self) {
+        self.filtered_for_drawing = self.wav_view_mode.filter_cascade(self.sr()).map(|cascade| {
+            let wavs = match self.guard_clip_result() {
+                GuardClippingResult::WavBeforeClip(before_clip) => before_clip.view(),
+                _ => self.audio.view(),
+            };
+            let mut filtered = Array2::zeros(wavs.raw_dim());
+            Zip::from(filtered.rows_mut())
+                .and(wavs.rows())
+                .par_for_each(|mut y, x| y.assign(&cascade.filter(x)));
+            filtered
+        });
+    }
+
+    /// waveform drawn in the overview (before clipping if guard clipping clipped the audio)
+    pub fn wav_for_overview(&self) -> ArrayView2<f32> {
+        match self.guard_clip_result() {
+            GuardClippingResult::WavBeforeClip(before_clip) => before_clip.view(),
+            _ => self.audio.view(),
+        }
+    }
+
+    #[inline]
+    pub fn overview_pyramid(&self, ch: usize) -> &WavPyramid {
+        &self.overview_pyramids[ch]
+    }
+
+    #[inline]
+    pub fn limiter_gain_hashes(&self) -> &BlockHashes {
+        &self.limiter_gain_hashes
+    }
+
+    fn update_overview_pyramids(&mut self) {
+        self.overview_pyramids = self
+            .wav_for_overview()
+            .outer_iter()
+            .map(WavPyramid::new)
+            .collect();
+        self.limiter_gain_hashes = match self.guard_clip_result() {
+            GuardClippingResult::GainSequence(gain_seq) => BlockHashes::new(gain_seq.row(0)),
+            _ => Default::default(),
+        };
+    }
+
+    /// if polarity inversion or channel swapping is applied
+    #[inline]
+    fn has_channel_transform(&self) -> bool {
+        self.polarity_inverted
+            || self
+                .channel_order
+                .iter()
+                .enumerate()
+                .any(|(ch, &src_ch)| ch != src_ch)
+    }
+
+    /// Set the params with the audio stretched from input (a copy of the audio taken before).
+    /// The stretched audio is recalculated if the audio has changed (e.g. normalized) since then.
+    fn set_stretched(
+        &mut self,
+        params: StretchParams,
+        input: ArrayView2<f32>,
+        stretched: Option<Array2<f32>>,
+    ) {
+        self.stretch_params = params;
+        if self.audio.view() == input {
+            self.stretched = stretched;
+            self.playback_version = next_playback_version();
+        } else {
+            self.update_stretched();
+        }
+    }
+
+    fn update_stretched(&mut self) {
+        self.stretched = stretch_and_shift_wavs(self.audio.view(), self.stretch_params);
+        self.playback_version = next_playback_version();
+    }
+
+    /// mapping between the session time, the time of the track, and the time of the played sound
+    #[inline]
+    pub fn timeline(&self) -> TrackTimeline {
+        TrackTimeline {
+            offset_sec: self.offset_sec,
+            stretch_ratio: self.stretch_params.ratio,
+        }
+    }
+
+    /// audio to play (time-stretched and pitch-shifted if needed)
+    #[inline]
+    fn playback_wavs(&self) -> ArrayView2<f32> {
+        self.stretched
+            .as_ref()
+            .map_or_else(|| self.audio.view(), |x| x.view())
+    }
+
     #[inline]
     pub fn channel(&self, ch: usize) -> ArrayView1<f32> {
         self.audio.channel(ch)
@@ -103,8 +464,192 @@ impl AudioTrack {
         &self.interleaved
     }
 
+    /// version of the audio to play, which is changed by stretching or editing the track
+    #[inline]
+    pub fn playback_version(&self) -> u64 {
+        self.playback_version
+    }
+
+    /// true if the audio to play differs from interleaved_frames
+    #[inline]
+    pub fn is_stretched(&self) -> bool {
+        self.stretched.is_some()
+    }
+
+    /// Copy the audio to play, to be processed by PendingPlayback::render
+    /// without holding the lock of the track list.
+    /// If soloing, only the segment of the box is copied with the tail_sec after it
+    /// (e.g. for the tail of the IR).
+    pub fn prepare_playback(&self, solo: Option<&TfBoxSolo>, tail_sec: f64) -> PendingPlayback {
+        let wavs = self.playback_wavs();
+        let len = wavs.shape()[1];
+        let ratio = self.stretch_params.ratio;
+        let (i_start, i_end) = match solo {
+            Some(solo) => {
+                let (i_start, i_end) = solo.segment_range(len, self.sr(), ratio);
+                let n_tail = (tail_sec * self.sr() as f64).round() as usize;
+                (i_start, (i_end + n_tail).min(len).max(i_start))
+            }
+            None => (0, len),
+        };
+        PendingPlayback {
+            wavs: wavs.slice(s![.., i_start..i_end]).to_owned(),
+            i_offset: i_start,
+            len,
+            sr: self.sr(),
+            stretch_ratio: ratio,
+        }
+    }
+
+    /// frames for playback at the unity gain.
+    /// The volume and the output limiter are applied by the output stage of the player.
+    pub fn playback_frames(
+        &self,
+        downmix_mode: DownmixMode,
+        ir: Option<&ImpulseResponse>,
+        denoiser: Option<&SpectralDenoiser>,
+        solo: Option<&TfBoxSolo>,
+    ) -> Cow<[Frame]> {
+        let processed = process_playback(
+            self.playback_wavs(),
+            0,
+            self.sr(),
+            self.stretch_params.ratio,
+            downmix_mode,
+            ir,
+            denoiser,
+            solo,
+        );
+        match processed {
+            Some(processed) => Cow::Owned(wavs_to_frames(processed.view())),
+            None if self.stretched.is_some() => Cow::Owned(wavs_to_frames(self.playback_wavs())),
+            None => Cow::Borrowed(&self.interleaved),
+        }
+    }
+
+    /// frames of a short grain around sec played at the speed (tape-style scrubbing)
+    pub fn scrub_grain_frames(
+        &self,
+        sec: f64,
+        speed: f64,
+        grain_sec: f64,
+        downmix_mode: DownmixMode,
+    ) -> Vec<Frame> {
+        let sr = self.sr() as f64;
+        let out_len = (grain_sec * sr).round() as usize;
+        let mut grain = Array2::zeros((self.n_ch(), out_len));
+        Zip::from(grain.rows_mut())
+            .and(self.audio.view().rows())
+            .for_each(|mut y, x| y.assign(&varispeed_grain(x, sec * sr, speed, out_len)));
+        match downmix(grain.view(), downmix_mode) {
+            Some(downmixed) => wavs_to_frames(downmixed.view()),
+            None => wavs_to_frames(grain.view()),
+        }
+    }
+
+    /// Render the playback of sec_range (on the session timeline) offline in the same way as
+    /// the player (playback_frames followed by the volume and the output limiter),
+    /// resampled to sr (e.g. of the output device).
+    /// Returns the stereo frames of the player as (2, n_samples).
+    /// The time out of the track is silent.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_playback(
+        &self,
+        downmix_mode: DownmixMode,
+        ir: Option<&ImpulseResponse>,
+        denoiser: Option<&SpectralDenoiser>,
+        solo: Option<&TfBoxSolo>,
+        volume: f32,
+        limiter_threshold: Option<f64>,
+        (start_sec, end_sec): (f64, f64),
+        sr: u32,
+    ) -> Array2<f32> {
+        let frames = self.playback_frames(downmix_mode, ir, denoiser, solo);
+        let timeline = self.timeline();
+        let to_sound_idx = |sec: f64| {
+            let sound_sec = timeline.track_to_sound(timeline.session_to_track(sec));
+            (sound_sec * self.sr() as f64).round() as isize
+        };
+        let i_start = to_sound_idx(start_sec);
+        let i_end = to_sound_idx(end_sec).max(i_start);
+        let mut wavs = Array2::zeros((2, (i_end - i_start) as usize));
+        let src_start = i_start.clamp(0, frames.len() as isize);
+        let src_end = i_end.clamp(0, frames.len() as isize);
+        let dst_start = (src_start - i_start) as usize;
+        for (j, frame) in frames[src_start as usize..src_end as usize]
+            .iter()
+            .enumerate()
+        {
+            wavs[[0, dst_start + j]] = frame.left * volume;
+            wavs[[1, dst_start + j]] = frame.right * volume;
+        }
+        let mut wavs = if sr == self.sr() {
+            wavs
+        } else {
+            let [left, right] = [0, 1].map(|ch| resample_to(wavs.row(ch), self.sr(), sr));
+            ndarray::stack(Axis(0), &[left.view(), right.view()]).unwrap()
+        };
+        // the output limiter of the player runs at the sample rate of the output stream
+        if let Some(threshold) = limiter_threshold {
+            let mut limiter = PerfectLimiter::with_default(sr);
+            limiter.set_threshold(threshold);
+            limiter.process_inplace(wavs.view_mut());
+        }
+        wavs
+    }
+
+    /// spectral denoiser using the noise floor of this track as the noise profile
+    #[inline]
+    pub fn spectral_denoiser(&self, amount: f64) -> SpectralDenoiser {
+        SpectralDenoiser::new(self.audio.view(), self.sr(), amount)
+    }
+
+    #[inline]
+    pub fn channel_relationship(&self) -> Option<ChannelRelationship> {
+        analyze_channel_relationship(self.original.view())
+    }
+
+    /// dropouts found in the original audio
+    #[inline]
+    pub fn dropouts(&self, sensitivity: f64) -> Vec<Dropout> {
+        detect_dropouts(self.original.view(), self.sr(), sensitivity)
+    }
+
+    #[inline]
+    pub fn mono_compatibility(&self) -> Option<MonoCompatibility> {
+        calc_mono_compatibility(self.audio.view())
+    }
+
+    /// histogram of the original samples (before normalization) of the channel
+    pub fn amplitude_histogram(&self, ch: usize, n_bins: usize) -> Vec<u32> {
+        calc_amplitude_histogram(self.original.channel(ch), n_bins)
+    }
+
+    #[inline]
+    pub fn bit_depth_usage(&self) -> BitDepthUsage {
+        calc_bit_depth_usage(self.original.view())
+    }
+
+    /// suggested view framing the program material of the track
+    #[inline]
+    pub fn initial_view(&self) -> InitialView {
+        calc_initial_view(self.audio.view(), self.sr())
+    }
+
+    #[inline]
+    pub fn lossy_generation(&self) -> LossyGenerationEstimate {
+        estimate_lossy_generation(self.original.view(), self.sr())
+    }
+
     #[inline]
     pub fn channel_for_drawing(&self, ch: usize) -> (ArrayView1<f32>, bool) {
+        if let Some(filtered) = &self.filtered_for_drawing {
+            let show_clipping = matches!(
+                self.guard_clip_result(),
+                GuardClippingResult::WavBeforeClip(_)
+            );
+            return (filtered.slice(s![ch, ..]), show_clipping);
+        }
         match self.guard_clip_result() {
             GuardClippingResult::WavBeforeClip(before_clip) => {
                 (before_clip.slice(s![ch, ..]), true)
@@ -113,11 +658,103 @@ impl AudioTrack {
         }
     }
 
+    /// classes of the pixel columns of the channel drawn in the time range with width px.
+    /// None if the track is neither clipped nor limited.
+    pub fn wav_pixel_classes(
+        &self,
+        ch: usize,
+        range: (f64, f64),
+        width: u32,
+    ) -> Option<Vec<WavPixelClass>> {
+        let (wav, show_clipping) = self.channel_for_drawing(ch);
+        let gain_seq = match self.guard_clip_result() {
+            GuardClippingResult::GainSequence(gain_seq) => Some(gain_seq.slice(s![0, ..])),
+            _ => None,
+        };
+        if !show_clipping && gain_seq.is_none() {
+            return None;
+        }
+        let (i_start, i_end) = self.sample_range(range);
+        Some(classify_wav_pixels(
+            wav.slice(s![i_start..i_end]),
+            gain_seq.map(|gain_seq| gain_seq.slice_move(s![i_start..i_end])),
+            width,
+        ))
+    }
+
+    /// (min, max) envelopes of the channel before and after the normalization and guard clipping,
+    /// drawn in the time range with width px. None if neither of them changes the channel.
+    pub fn wav_compare_envelopes(
+        &self,
+        ch: usize,
+        range: (f64, f64),
+        width: u32,
+    ) -> Option<[(Vec<f32>, Vec<f32>); 2]> {
+        let is_guard_clipped = self.guard_clip_stats()[ch].max_reduction_gain_dB != 0.;
+        if self.normalize_gain == 1. && !is_guard_clipped {
+            return None;
+        }
+        let (i_start, i_end) = self.sample_range(range);
+        let (mut pre_min, mut pre_max) = calc_wav_pixel_envelope(
+            self.original
+                .channel(self.channel_order[ch])
+                .slice_move(s![i_start..i_end]),
+            width,
+        );
+        if self.polarity_inverted {
+            // aligned with the processed channel
+            (pre_min, pre_max) = (
+                pre_max.into_iter().map(|x| -x).collect(),
+                pre_min.into_iter().map(|x| -x).collect(),
+            );
+        }
+        let post = calc_wav_pixel_envelope(self.channel(ch).slice_move(s![i_start..i_end]), width);
+        Some([(pre_min, pre_max), post])
+    }
+
+    /// key to group tracks by. None if the track has no value for the key
+    /// (e.g. the file has no album or date tag).
+    pub fn group_key(&self, by: TrackGroupBy) -> Option<String> {
+        match by {
+            TrackGroupBy::Album => self.format_info.album.clone(),
+            TrackGroupBy::Date => self.format_info.date.clone(),
+            TrackGroupBy::Folder => self
+                .path
+                .parent()
+                .map(|dir| dir.to_string_lossy().into_owned()),
+        }
+    }
+
     #[inline]
     pub fn path_string(&self) -> String {
         self.path.as_os_str().to_string_lossy().into_owned()
     }
 
+    #[inline]
+    pub fn file_stem(&self) -> String {
+        self.path
+            .file_stem()
+            .map_or_else(String::new, |x| x.to_string_lossy().into_owned())
+    }
+
+    /// wavs before normalization and guard clipping
+    #[inline]
+    pub fn original_wavs(&self) -> ArrayView2<f32> {
+        self.original.view()
+    }
+
+    /// SHA-256 of the decoded PCM (before normalization and channel transforms)
+    pub fn pcm_hash(&self) -> String {
+        self.pcm_hash
+            .get_or_init(|| calc_pcm_hash(self.original_wavs(), self.original.sr))
+            .clone()
+    }
+
+    #[inline]
+    pub fn normalize_gain(&self) -> f32 {
+        self.normalize_gain
+    }
+
     #[inline]
     pub fn sr(&self) -> u32 {
         self.audio.sr
@@ -145,6 +782,43 @@ impl AudioTrack {
         &self.audio.stats
     }
 
+    #[inline]
+    pub fn decode_gaps(&self) -> &[DecodeGap] {
+        &self.decode_gaps
+    }
+
+    /// sample indices [start, end) of the time range clamped to the track length
+    pub fn sample_range(&self, (start_sec, end_sec): (f64, f64)) -> (usize, usize) {
+        let to_idx =
+            |sec: f64| ((sec * self.sr() as f64).round().max(0.) as usize).min(self.audio.len());
+        let i_start = to_idx(start_sec);
+        (i_start, to_idx(end_sec).max(i_start))
+    }
+
+    /// stats of the audio (after normalization and guard clipping) in the time range.
+    /// None if the range doesn't overlap the track.
+    pub fn stats_in_range(&self, range: (f64, f64)) -> Option<AudioStats> {
+        let (i_start, i_end) = self.sample_range(range);
+        if i_start == i_end {
+            return None;
+        }
+        let mut stat_calculator = StatCalculator::new(self.n_ch() as u32, self.sr());
+        Some(stat_calculator.calc(self.audio.view().slice(s![.., i_start..i_end])))
+    }
+
+    /// loudness of each segment_sec of the audio (after normalization as global_lufs)
+    #[inline]
+    pub fn segmented_loudness(&self, segment_sec: f64) -> Vec<SegmentLoudness> {
+        calc_segmented_loudness(self.audio.view(), self.sr(), segment_sec)
+    }
+
+    /// contribution of the n_bands (> 0) highest octave bands to the loudness of the audio
+    /// (after normalization as segmented_loudness)
+    #[inline]
+    pub fn band_loudness(&self, n_bands: usize) -> BandLoudnessContribution {
+        calc_band_loudness(self.audio.view(), self.sr(), n_bands)
+    }
+
     #[inline]
     pub fn guard_clip_result(&self) -> &GuardClippingResult<Ix2> {
         &self.audio.guard_clip_result
@@ -154,6 +828,19 @@ impl AudioTrack {
     pub fn guard_clip_stats(&self) -> ArrayView1<GuardClippingStats> {
         self.audio.guard_clip_stats.view()
     }
+
+    /// gain staging from the original audio through normalization to the target and guard clipping
+    pub fn headroom_report(&self, target: NormalizeTarget) -> HeadroomReport {
+        let gain = self.normalize_gain(target);
+        calc_headroom_report(
+            self.original.view(),
+            self.audio.view(),
+            &self.channel_order,
+            self.sr(),
+            if gain.is_finite() { gain } else { 1. },
+            self.guard_clip_stats(),
+        )
+    }
 }
 
 impl CalcWidth for AudioTrack {
@@ -192,18 +879,26 @@ impl Normalize for AudioTrack {
     }
 
     fn apply_gain(&mut self, gain: f32, guard_clipping_mode: GuardClippingMode) {
-        if !gain.is_finite() || gain == 1. {
+        let gain = if gain.is_finite() { gain } else { 1. };
+        self.normalize_gain = gain;
+        if gain == 1. && !self.has_channel_transform() {
             self.audio.clone_from(&self.original);
         } else {
+            let gain = if self.polarity_inverted { -gain } else { gain };
             self.audio.mutate(
-                |wavs| {
-                    azip!((y in wavs, x in self.original.view()) *y = gain * x);
+                |mut wavs| {
+                    for (y, &src_ch) in wavs.outer_iter_mut().zip(&self.channel_order) {
+                        azip!((y in y, x in self.original.channel(src_ch)) *y = gain * x);
+                    }
                 },
                 &mut self.stat_calculator,
                 guard_clipping_mode,
             );
         }
         self.interleaved = (&self.audio).into();
+        self.update_filtered_for_drawing();
+        self.update_overview_pyramids();
+        self.update_stretched();
     }
 }
 
@@ -231,6 +926,8 @@ pub struct TrackList {
     tracks: Vec<Option<AudioTrack>>,
     filenames: Vec<Option<String>>,
     id_max_sec: usize,
+    /// ids handed out to be added, which are released when the tracks are added (or failed to)
+    reserved_ids: IntSet<usize>,
 }
 
 impl TrackList {
@@ -242,10 +939,47 @@ impl TrackList {
             id_max_sec: 0,
             common_normalize: NormalizeTarget::Off,
             common_guard_clipping: GuardClippingMode::ReduceGlobalLevel,
+            reserved_ids: IntSet::default(),
         }
     }
 
+    /// Reserve n ids which are neither used nor reserved, the smallest first.
+    /// The renderer, the scripts and the session import all take the ids from here,
+    /// so that the tracks added by them never collide.
+    pub fn reserve_ids(&mut self, n: usize) -> Vec<usize> {
+        let mut ids = Vec::with_capacity(n);
+        let mut id = 0;
+        while ids.len() < n {
+            if !self.has(id) && !self.reserved_ids.contains(&id) {
+                ids.push(id);
+            }
+            id += 1;
+        }
+        self.reserved_ids.extend(ids.iter().copied());
+        ids
+    }
+
+    /// Reserve the given ids. They should be chosen among the ids not in `unavailable_ids`.
+    pub fn reserve_given_ids(&mut self, ids: impl IntoIterator<Item = usize>) {
+        self.reserved_ids.extend(ids);
+    }
+
+    /// release the ids reserved by the previous session
+    pub fn clear_reserved_ids(&mut self) {
+        self.reserved_ids.clear();
+    }
+
+    /// ids which are used or reserved
+    pub fn unavailable_ids(&self) -> IntSet<usize> {
+        let mut ids = self.all_id_set();
+        ids.extend(self.reserved_ids.iter().copied());
+        ids
+    }
+
     pub fn add_tracks(&mut self, id_list: Vec<usize>, path_list: Vec<String>) -> Vec<usize> {
+        for id in &id_list {
+            self.reserved_ids.remove(id);
+        }
         let id_tracks: Vec<_> = id_list
             .into_par_iter()
             .zip(path_list.into_par_iter())
@@ -258,16 +992,7 @@ impl TrackList {
             .collect();
         let mut added_ids = Vec::with_capacity(id_tracks.len());
         for (id, track) in id_tracks.into_iter() {
-            let sec = track.sec();
-            if sec > self.max_sec {
-                self.max_sec = sec;
-                self.id_max_sec = id;
-            }
-            if id >= self.tracks.len() {
-                self.tracks
-                    .extend((self.tracks.len()..(id + 1)).map(|_| None));
-            }
-            self.tracks[id].replace(track);
+            self.insert_track(id, track);
             added_ids.push(id);
         }
 
@@ -275,6 +1000,34 @@ impl TrackList {
         added_ids
     }
 
+    /// add a track from a headerless PCM file
+    pub fn add_raw_track(
+        &mut self,
+        id: usize,
+        path: String,
+        raw_params: RawPcmParams,
+    ) -> Result<(), SymphoniaError> {
+        self.reserved_ids.remove(&id);
+        let mut track = AudioTrack::new_raw(path, raw_params)?;
+        track.normalize(self.common_normalize, self.common_guard_clipping);
+        self.insert_track(id, track);
+        self.update_filenames();
+        Ok(())
+    }
+
+    fn insert_track(&mut self, id: usize, track: AudioTrack) {
+        let sec = track.sec();
+        if sec > self.max_sec {
+            self.max_sec = sec;
+            self.id_max_sec = id;
+        }
+        if id >= self.tracks.len() {
+            self.tracks
+                .extend((self.tracks.len()..(id + 1)).map(|_| None));
+        }
+        self.tracks[id].replace(track);
+    }
+
     pub fn reload_tracks(&mut self, id_list: &[usize]) -> (Vec<usize>, Vec<usize>) {
         let reload_results: Vec<_> = indexed_par_iter_mut_filtered!(self.tracks)
             .filter(|(id, _)| id_list.contains(id))
@@ -370,6 +1123,247 @@ impl TrackList {
         self.apply_normalize_guard_clipping();
     }
 
+    /// Apply f to the tracks of ids in parallel.
+    /// Nothing is changed and false is returned if any of the tracks doesn't exist.
+    fn update_tracks(&mut self, ids: &[usize], f: impl Fn(&mut AudioTrack) + Send + Sync) -> bool {
+        if !ids.iter().all(|&id| self.has(id)) {
+            return false;
+        }
+        let ids: IntSet<usize> = ids.iter().copied().collect();
+        self.tracks
+            .par_iter_mut()
+            .enumerate()
+            .filter(|(id, _)| ids.contains(id))
+            .filter_map(|(_, track)| track.as_mut())
+            .for_each(f);
+        true
+    }
+
+    pub fn set_wav_view_mode(&mut self, id: usize, mode: WavViewMode) -> bool {
+        self.set_tracks_wav_view_mode(&[id], mode)
+    }
+
+    /// false if any of the tracks doesn't exist (nothing is changed)
+    pub fn set_tracks_wav_view_mode(&mut self, ids: &[usize], mode: WavViewMode) -> bool {
+        self.update_tracks(ids, |track| track.set_wav_view_mode(mode))
+    }
+
+    /// Copy the audio of the tracks whose stretch params are changed by update
+    /// (clamped to the supported ranges), to be stretched by PendingStretch::compute
+    /// without holding the lock of the track list. None if any of the tracks doesn't exist.
+    pub fn prepare_stretch(
+        &self,
+        ids: &[usize],
+        update: impl Fn(&mut StretchParams),
+    ) -> Option<Vec<PendingStretch>> {
+        if !ids.iter().all(|&id| self.has(id)) {
+            return None;
+        }
+        let ids: IntSet<usize> = ids.iter().copied().collect();
+        let pending = ids
+            .into_iter()
+            .filter_map(|id| {
+                let track = &self[id];
+                let mut params = track.stretch_params;
+                update(&mut params);
+                let params = params.clamped();
+                (params != track.stretch_params).then(|| PendingStretch {
+                    id,
+                    params,
+                    input: track.audio.view().to_owned(),
+                    stretched: None,
+                })
+            })
+            .collect();
+        Some(pending)
+    }
+
+    /// Apply the stretches computed by PendingStretch::compute. Removed tracks are skipped.
+    pub fn apply_stretch(&mut self, pending: Vec<PendingStretch>) {
+        for p in pending {
+            if let Some(track) = self.tracks.get_mut(p.id).and_then(Option::as_mut) {
+                track.set_stretched(p.params, p.input.view(), p.stretched);
+            }
+        }
+    }
+
+    /// Place the track at offset_sec of the session timeline. false if the track doesn't exist.
+    pub fn set_offset(&mut self, id: usize, offset_sec: f64) -> bool {
+        self.update_tracks(&[id], |track| track.offset_sec = offset_sec)
+    }
+
+    /// non-destructive polarity inversion of all channels. false if the track doesn't exist.
+    pub fn set_polarity_inverted(&mut self, id: usize, inverted: bool) -> bool {
+        self.set_tracks_polarity_inverted(&[id], inverted)
+    }
+
+    /// false if any of the tracks doesn't exist (nothing is changed)
+    pub fn set_tracks_polarity_inverted(&mut self, ids: &[usize], inverted: bool) -> bool {
+        let (common_normalize, common_guard_clipping) =
+            (self.common_normalize, self.common_guard_clipping);
+        self.update_tracks(ids, |track| {
+            if track.polarity_inverted != inverted {
+                track.polarity_inverted = inverted;
+                track.normalize(common_normalize, common_guard_clipping);
+            }
+        })
+    }
+
+    /// Decode only the source channels of the track (all channels if None).
+    /// Ok(false) if nothing changed.
+    pub fn set_decoded_channels(
+        &mut self,
+        id: usize,
+        channels: Option<Vec<usize>>,
+    ) -> Result<bool, SymphoniaError> {
+        let track = self.tracks[id]
+            .as_mut()
+            .expect("[set_decoded_channels] Wrong Track ID!");
+        let changed = track.set_decoded_channels(channels)?;
+        if changed {
+            track.normalize(self.common_normalize, self.common_guard_clipping);
+        }
+        Ok(changed)
+    }
+
+    /// non-destructive swapping of two channels.
+    /// false if the track or one of the channels doesn't exist.
+    pub fn swap_channels(&mut self, id: usize, ch_a: usize, ch_b: usize) -> bool {
+        match self.tracks.get_mut(id).and_then(Option::as_mut) {
+            Some(track) if ch_a < track.n_ch() && ch_b < track.n_ch() => {
+                if ch_a != ch_b {
+                    track.channel_order.swap(ch_a, ch_b);
+                    track.normalize(self.common_normalize, self.common_guard_clipping);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Assign the same group id to tracks with the same key (ordered by the smallest track id).
+    /// Tracks without the key are ungrouped. Returns (key, track ids) of each group id.
+    pub fn auto_group_tracks(&mut self, by: TrackGroupBy) -> Vec<(String, Vec<usize>)> {
+        let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+        for (id, track) in self
+            .tracks
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(id, track)| Some((id, track.as_mut()?)))
+        {
+            track.group_id = track.group_key(by).map(|key| {
+                match groups.iter().position(|(group_key, _)| *group_key == key) {
+                    Some(group_id) => {
+                        groups[group_id].1.push(id);
+                        group_id
+                    }
+                    None => {
+                        groups.push((key, vec![id]));
+                        groups.len() - 1
+                    }
+                }
+            });
+        }
+        groups
+    }
+
+    /// (the part matched by "*", id of A, id of B) of the tracks paired by the file names
+    /// matching pattern_a and pattern_b (e.g. "*_orig.wav" and "*_proc.wav").
+    /// Err if a pattern doesn't contain exactly one "*".
+    pub fn auto_pair_tracks(
+        &self,
+        pattern_a: &str,
+        pattern_b: &str,
+    ) -> Result<Vec<(String, usize, usize)>, String> {
+        let names = indexed_iter_filtered!(self.tracks).map(|(id, track)| {
+            let name = track
+                .path
+                .file_name()
+                .map_or_else(String::new, |x| x.to_string_lossy().into_owned());
+            (id, name)
+        });
+        pair_by_pattern(names, pattern_a, pattern_b)
+    }
+
+    /// ids of the tracks matching the filter in ascending order
+    pub fn query_tracks(&self, filter: &TrackFilter) -> Vec<usize> {
+        indexed_iter_filtered!(self.tracks)
+            .filter(|&(id, track)| filter.matches(track, self.filename(id)))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// guard clipping stats of the track (one line per channel, or one line for all channels
+    /// if the global level is reduced). Empty string if no guard clipping occurred.
+    pub fn guard_clip_summary(&self, id: usize) -> Option<String> {
+        let track = self.get(id)?;
+        let mode = self.common_guard_clipping;
+        let stats = if mode == GuardClippingMode::ReduceGlobalLevel {
+            track.guard_clip_stats().slice_move(s![0..1])
+        } else {
+            track.guard_clip_stats()
+        };
+        let lines: Vec<_> = stats
+            .iter()
+            .map(|stat| {
+                let stat = stat.to_string();
+                if !stat.is_empty() {
+                    format!("{} by {}", mode, stat)
+                } else {
+                    stat
+                }
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    /// Find the segments of all the tracks similar to the time range of (id, ch)
+    /// ranked by the similarity (see similarity.rs).
+    pub fn find_similar(
+        &self,
+        (id, ch): IdCh,
+        range: (f64, f64),
+        threshold: f64,
+    ) -> Result<Vec<SimilarSegment>, String> {
+        let track = self.get(id).ok_or("The track doesn't exist.")?;
+        if ch >= track.n_ch() {
+            return Err("The channel doesn't exist.".into());
+        }
+        let (i_start, i_end) = track.sample_range(range);
+        let query = track.channel(ch).slice_move(s![i_start..i_end]);
+        let targets: Vec<_> = indexed_iter_filtered!(self.tracks)
+            .flat_map(|(id, track)| {
+                (0..track.n_ch()).map(move |ch| (id, ch, track.channel(ch), track.sr()))
+            })
+            .collect();
+        find_similar_segments(query, track.sr(), &targets, threshold)
+    }
+
+    /// Compare every pair of the original audio of the tracks (see compare.rs).
+    /// [i][j]: ids[j] compared to ids[i]. on_pair_done is called after each pair.
+    pub fn compare_tracks(
+        &self,
+        ids: &[usize],
+        on_pair_done: impl Fn() + Sync,
+    ) -> Result<Vec<Vec<Option<TrackPairComparison>>>, String> {
+        let wavs = ids
+            .iter()
+            .map(|&id| {
+                let track = self.get(id).ok_or("Some of the tracks don't exist.")?;
+                Ok((
+                    mixdown_for_compare(track.original_wavs(), track.sr()),
+                    track.sr(),
+                ))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(compare_all_pairs(&wavs, on_pair_done))
+    }
+
+    #[inline]
+    pub fn headroom_report(&self, id: usize) -> Option<HeadroomReport> {
+        Some(self.get(id)?.headroom_report(self.common_normalize))
+    }
+
     pub fn set_common_guard_clipping(&mut self, guard_clipping_mode: GuardClippingMode) {
         self.common_guard_clipping = guard_clipping_mode;
         self.apply_normalize_guard_clipping();
@@ -483,6 +1477,49 @@ impl Index<usize> for TrackList {
     }
 }
 
+/// keep only the channels of wavs (all channels if None or none of them exists)
+fn select_decoded_channels(wavs: Array2<f32>, channels: Option<&[usize]>) -> Array2<f32> {
+    let Some(channels) = channels else {
+        return wavs;
+    };
+    let n_ch = wavs.shape()[0];
+    let channels: Vec<_> = channels.iter().copied().filter(|&ch| ch < n_ch).collect();
+    if channels.is_empty() {
+        wavs
+    } else {
+        wavs.select(Axis(0), &channels)
+    }
+}
+
+/// wavs (stretched by stretch_ratio) soloed in the time-frequency box, denoised, downmixed,
+/// and convolved with the IR if needed. None if no processing is needed.
+/// wavs can be the part of the audio from i_offset.
+#[allow(clippy::too_many_arguments)]
+fn process_playback(
+    wavs: ArrayView2<f32>,
+    i_offset: usize,
+    sr: u32,
+    stretch_ratio: f64,
+    downmix_mode: DownmixMode,
+    ir: Option<&ImpulseResponse>,
+    denoiser: Option<&SpectralDenoiser>,
+    solo: Option<&TfBoxSolo>,
+) -> Option<Array2<f32>> {
+    let soloed = solo.map(|solo| solo.apply_at(wavs, i_offset, sr, stretch_ratio));
+    let denoised = denoiser
+        .map(|denoiser| {
+            let wavs = soloed.as_ref().map_or(wavs, |x| x.view());
+            denoiser.denoise(wavs, sr)
+        })
+        .or(soloed);
+    let downmixed = downmix(denoised.as_ref().map_or(wavs, |x| x.view()), downmix_mode);
+    let processed = downmixed.or(denoised);
+    match ir {
+        Some(ir) => Some(ir.convolve(processed.as_ref().map_or(wavs, |x| x.view()), sr)),
+        None => processed,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use approx::assert_abs_diff_eq;
@@ -507,9 +1544,266 @@ mod tests {
         );
     }
 
+    #[test]
+    fn render_playback_works() {
+        let mut track = AudioTrack::new("samples/sample_48k.wav".into()).unwrap();
+        let render = |track: &AudioTrack, sec_range, sr| {
+            track.render_playback(DownmixMode::Off, None, None, None, 0.5, None, sec_range, sr)
+        };
+        let wavs = render(&track, (0., 1.), track.sr());
+        assert_eq!(wavs.shape(), [2, track.sr() as usize]);
+        assert_abs_diff_eq!(wavs[[0, 1000]], track.channel(0)[1000] * 0.5);
+        assert_eq!(wavs.row(0), wavs.row(1));
+        assert_eq!(render(&track, (0., 1.), 24000).shape(), [2, 24000]);
+
+        // silent before the start of the track on the session timeline
+        track.offset_sec = 1.;
+        let wavs = render(&track, (0., 2.), track.sr());
+        let sr = track.sr() as usize;
+        assert!(wavs.slice(s![.., ..sr]).iter().all(|&x| x == 0.));
+        assert_abs_diff_eq!(wavs[[0, sr + 1000]], track.channel(0)[1000] * 0.5);
+
+        // the limiter is applied after the volume
+        let wavs = track.render_playback(
+            DownmixMode::Off,
+            None,
+            None,
+            None,
+            8.,
+            Some(0.5),
+            (1., 2.),
+            track.sr(),
+        );
+        assert!(wavs.iter().all(|x| x.abs() <= 0.5 + 1e-4));
+        assert!(wavs.iter().any(|x| x.abs() > 0.4));
+    }
+
+    #[test]
+    fn pending_playback_works() {
+        let mut tracklist = TrackList::new();
+        tracklist.add_tracks(vec![0], vec!["samples/stereo/sample_48k.wav".into()]);
+        let version = tracklist[0].playback_version();
+        let assert_frames_eq = |frames: &[Frame], expected: &[Frame]| {
+            assert_eq!(frames.len(), expected.len());
+            assert!(frames
+                .iter()
+                .zip(expected)
+                .all(|(a, b)| a.left == b.left && a.right == b.right));
+        };
+        let track = &tracklist[0];
+        let mode = DownmixMode::MonoMinus6dB;
+        let frames = track
+            .prepare_playback(None, 0.)
+            .render(mode, None, None, None);
+        assert_frames_eq(&frames, &track.playback_frames(mode, None, None, None));
+
+        // only the segment of the box is processed when soloing
+        let solo = TfBoxSolo {
+            sec_range: (0.2, 0.4),
+            hz_range: (500., 4000.),
+        };
+        let pending = track.prepare_playback(Some(&solo), 0.1);
+        assert!(pending.wavs.shape()[1] < track.playback_wavs().shape()[1]);
+        let frames = pending.render(mode, None, None, Some(&solo));
+        assert_frames_eq(
+            &frames,
+            &track.playback_frames(mode, None, None, Some(&solo)),
+        );
+
+        // stretching changes the audio to play
+        let mut pending = tracklist
+            .prepare_stretch(&[0], |params| params.ratio = 2.)
+            .unwrap();
+        pending.iter_mut().for_each(PendingStretch::compute);
+        tracklist.apply_stretch(pending);
+        assert!(tracklist[0].is_stretched());
+        assert_ne!(tracklist[0].playback_version(), version);
+    }
+
     #[test]
     fn calc_loudness_works() {
         let track = AudioTrack::new("samples/sample_48k.wav".into()).unwrap();
         assert_abs_diff_eq!(track.stats().global_lufs, -26.20331705029079);
     }
+
+    #[test]
+    fn channel_transform_works() {
+        let mut tracklist = TrackList::new();
+        let added_ids = tracklist.add_tracks(vec![0], vec!["samples/stereo/sample_48k.wav".into()]);
+        assert_eq!(added_ids, vec![0]);
+        let original = tracklist[0].original_wavs().to_owned();
+
+        assert!(tracklist.swap_channels(0, 0, 1));
+        assert_eq!(tracklist[0].channel_order, vec![1, 0]);
+        assert_eq!(tracklist[0].channel(0), original.slice(s![1, ..]));
+        assert!(!tracklist.swap_channels(0, 0, 2));
+
+        assert!(tracklist.set_polarity_inverted(0, true));
+        assert_eq!(
+            tracklist[0].channel(1),
+            original.slice(s![0, ..]).mapv(|x| -x)
+        );
+        assert!(!tracklist.set_polarity_inverted(1, true));
+    }
+
+    #[test]
+    fn decoded_channels_work() {
+        let mut tracklist = TrackList::new();
+        tracklist.add_tracks(vec![0], vec!["samples/stereo/sample_48k.wav".into()]);
+        let original = tracklist[0].original_wavs().to_owned();
+        assert_eq!(tracklist[0].decoded_channels(), vec![0, 1]);
+
+        assert!(tracklist.set_decoded_channels(0, Some(vec![1])).unwrap());
+        assert_eq!(tracklist[0].n_ch(), 1);
+        assert_eq!(tracklist[0].decoded_channels(), vec![1]);
+        assert_eq!(tracklist[0].original_wavs().row(0), original.row(1));
+        assert!(!tracklist.set_decoded_channels(0, Some(vec![1])).unwrap());
+
+        assert!(tracklist.set_decoded_channels(0, None).unwrap());
+        assert_eq!(tracklist[0].original_wavs(), original);
+    }
+
+    #[test]
+    fn batch_setters_work() {
+        let mut tracklist = TrackList::new();
+        tracklist.add_tracks(
+            vec![0, 1],
+            vec![
+                "samples/sample_48k.wav".into(),
+                "samples/stereo/sample_48k.wav".into(),
+            ],
+        );
+        assert!(tracklist.set_tracks_polarity_inverted(&[0, 1], true));
+        assert!(tracklist[0].polarity_inverted && tracklist[1].polarity_inverted);
+        assert_eq!(
+            tracklist[1].channel(0),
+            tracklist[1].original_wavs().slice(s![0, ..]).mapv(|x| -x)
+        );
+
+        // nothing is changed if any of the tracks doesn't exist
+        assert!(tracklist
+            .prepare_stretch(&[0, 2], |params| params.ratio = 2.)
+            .is_none());
+        let pending = tracklist
+            .prepare_stretch(&[0, 1], |params| params.ratio = 1e6)
+            .unwrap();
+        assert_eq!(pending.len(), 2);
+        // clamped
+        assert!(pending.iter().all(|p| p.params.ratio == 4.));
+        assert_eq!(tracklist[0].stretch_params.ratio, 1.);
+        // unchanged params
+        assert!(tracklist
+            .prepare_stretch(&[0], |params| params.ratio = 1.)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn auto_group_tracks_works() {
+        let mut tracklist = TrackList::new();
+        tracklist.add_tracks(
+            vec![0, 1, 2],
+            vec![
+                "samples/sample_48k.wav".into(),
+                "samples/stereo/sample_48k.wav".into(),
+                "samples/sample_8k.wav".into(),
+            ],
+        );
+        let groups = tracklist.auto_group_tracks(TrackGroupBy::Folder);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].1, vec![0, 2]);
+        assert_eq!(groups[1].1, vec![1]);
+        assert!(groups[1].0.ends_with("stereo"));
+        assert_eq!(tracklist[2].group_id, Some(0));
+        assert_eq!(tracklist[1].group_id, Some(1));
+
+        // the samples have no tags
+        assert!(tracklist.auto_group_tracks(TrackGroupBy::Album).is_empty());
+        assert_eq!(tracklist[0].group_id, None);
+
+        for (id, album) in [(0, "A"), (1, "B"), (2, "A")] {
+            tracklist.tracks[id].as_mut().unwrap().format_info.album = Some(album.into());
+        }
+        let groups = tracklist.auto_group_tracks(TrackGroupBy::Album);
+        assert_eq!(
+            groups,
+            vec![("A".into(), vec![0, 2]), ("B".into(), vec![1])]
+        );
+        assert_eq!(tracklist[1].group_id, Some(1));
+        assert!(tracklist.auto_group_tracks(TrackGroupBy::Date).is_empty());
+    }
+
+    #[test]
+    fn query_tracks_works() {
+        let mut tracklist = TrackList::new();
+        tracklist.add_tracks(
+            vec![0, 1, 2],
+            vec![
+                "samples/sample_48k.wav".into(),
+                "samples/stereo/sample_48k.wav".into(),
+                "samples/sample_8k.wav".into(),
+            ],
+        );
+        let query = |filter: TrackFilter| tracklist.query_tracks(&filter);
+        assert_eq!(query(TrackFilter::default()), vec![0, 1, 2]);
+        assert_eq!(
+            query(TrackFilter {
+                sr: Some(48000),
+                ..Default::default()
+            }),
+            vec![0, 1]
+        );
+        assert_eq!(
+            query(TrackFilter {
+                name_contains: Some("8K".into()),
+                n_ch: Some(1),
+                ..Default::default()
+            }),
+            vec![0, 2]
+        );
+        assert!(query(TrackFilter {
+            min_sec: Some(tracklist.max_sec + 1.),
+            ..Default::default()
+        })
+        .is_empty());
+        assert_eq!(
+            query(TrackFilter {
+                max_lufs: Some(tracklist[2].stats().global_lufs),
+                min_lufs: Some(tracklist[2].stats().global_lufs),
+                ..Default::default()
+            }),
+            vec![2]
+        );
+
+        let filter: TrackFilter =
+            serde_json::from_str(r#"{"sr": 8000, "maxLUFS": 0, "minPeakdB": -100}"#).unwrap();
+        assert_eq!(filter.max_lufs, Some(0.));
+        assert_eq!(filter.min_peak_dB, Some(-100.));
+        assert_eq!(tracklist.query_tracks(&filter), vec![2]);
+    }
+
+    #[test]
+    fn reserve_ids_works() {
+        let mut tracklist = TrackList::new();
+        tracklist.add_tracks(vec![1], vec!["samples/sample_48k.wav".into()]);
+        assert_eq!(tracklist.reserve_ids(2), vec![0, 2]);
+        assert_eq!(tracklist.reserve_ids(1), vec![3]);
+        assert_eq!(tracklist.unavailable_ids(), IntSet::from_iter([0, 1, 2, 3]));
+
+        // the ids are released when added, even if the file can't be added
+        tracklist.add_tracks(vec![0, 2], vec!["samples/sample_48k.wav".into(), "".into()]);
+        assert_eq!(tracklist.reserve_ids(2), vec![2, 4]);
+        tracklist.clear_reserved_ids();
+        assert_eq!(tracklist.unavailable_ids(), IntSet::from_iter([0, 1]));
+    }
+
+    #[test]
+    fn stats_in_range_works() {
+        let track = AudioTrack::new("samples/sample_48k.wav".into()).unwrap();
+        assert_eq!(track.sample_range((1., 2.)), (48000, 96000));
+        assert_eq!(track.sample_range((-1., 1000.)), (0, track.audio.len()));
+        let stats = track.stats_in_range((0., track.sec())).unwrap();
+        assert_abs_diff_eq!(stats.global_lufs, track.stats().global_lufs);
+        assert!(track.stats_in_range((1000., 1001.)).is_none());
+    }
 }
@@ -0,0 +1,220 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::num::{NonZeroU32, NonZeroU8};
+use std::path::{Path, PathBuf};
+
+use flacenc::component::BitRepr;
+use flacenc::error::Verify;
+use napi_derive::napi;
+use ndarray::prelude::*;
+use serde::{Deserialize, Serialize};
+use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
+
+const OGG_BLOCK_SIZE: usize = 4096;
+
+#[napi(string_enum)]
+#[derive(Debug, Eq, PartialEq)]
+pub enum TranscodeCodec {
+    Wav,
+    Flac,
+    Ogg,
+}
+
+impl TranscodeCodec {
+    #[inline]
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TranscodeCodec::Wav => "wav",
+            TranscodeCodec::Flac => "flac",
+            TranscodeCodec::Ogg => "ogg",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TranscodeParams {
+    /// Wav: 16, 24 (int) or 32 (float), Flac: 16 or 24. default: 24
+    pub bit_depth: Option<u16>,
+    /// Ogg Vorbis quality (-0.1 ~ 1.0). default: 0.6
+    pub quality: Option<f32>,
+    /// export the whole track even if the global selection exists. default: false
+    pub whole_track: bool,
+}
+
+impl TranscodeParams {
+    fn bit_depth_for(&self, codec: TranscodeCodec) -> io::Result<u16> {
+        let bit_depth = self.bit_depth.unwrap_or(24);
+        let valid = match codec {
+            TranscodeCodec::Wav => matches!(bit_depth, 16 | 24 | 32),
+            TranscodeCodec::Flac => matches!(bit_depth, 16 | 24),
+            TranscodeCodec::Ogg => true,
+        };
+        if valid {
+            Ok(bit_depth)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}-bit isn't supported for {:?}", bit_depth, codec),
+            ))
+        }
+    }
+}
+
+/// returns the path of "{out_dir}/{stem}.{ext}" neither existing nor reserved,
+/// adding a number suffix if needed.
+pub fn unique_output_path(
+    out_dir: &Path,
+    stem: &str,
+    codec: TranscodeCodec,
+    reserved: &[PathBuf],
+) -> PathBuf {
+    let ext = codec.extension();
+    let mut path = out_dir.join(format!("{}.{}", stem, ext));
+    let mut i = 1;
+    while path.exists() || reserved.contains(&path) {
+        path = out_dir.join(format!("{} ({}).{}", stem, i, ext));
+        i += 1;
+    }
+    path
+}
+
+pub fn transcode(
+    wavs: ArrayView2<f32>,
+    sr: u32,
+    codec: TranscodeCodec,
+    params: &TranscodeParams,
+    path: &Path,
+) -> io::Result<()> {
+    match codec {
+        TranscodeCodec::Wav => write_wav(wavs, sr, params.bit_depth_for(codec)?, path),
+        TranscodeCodec::Flac => write_flac(wavs, sr, params.bit_depth_for(codec)?, path),
+        TranscodeCodec::Ogg => write_ogg(wavs, sr, params.quality.unwrap_or(0.6), path),
+    }
+}
+
+#[inline]
+fn quantize(x: f32, bit_depth: u16) -> i32 {
+    let scale = (1i64 << (bit_depth - 1)) as f32;
+    (x * scale).round().clamp(-scale, scale - 1.) as i32
+}
+
+fn write_wav(wavs: ArrayView2<f32>, sr: u32, bit_depth: u16, path: &Path) -> io::Result<()> {
+    let is_float = bit_depth == 32;
+    let spec = hound::WavSpec {
+        channels: wavs.shape()[0] as u16,
+        sample_rate: sr,
+        bits_per_sample: bit_depth,
+        sample_format: if is_float {
+            hound::SampleFormat::Float
+        } else {
+            hound::SampleFormat::Int
+        },
+    };
+    let mut writer = hound::WavWriter::create(path, spec).map_err(io::Error::other)?;
+    for frame in wavs.axis_iter(Axis(1)) {
+        for &x in frame {
+            let result = if is_float {
+                writer.write_sample(x)
+            } else {
+                writer.write_sample(quantize(x, bit_depth))
+            };
+            result.map_err(io::Error::other)?;
+        }
+    }
+    writer.finalize().map_err(io::Error::other)
+}
+
+fn write_flac(wavs: ArrayView2<f32>, sr: u32, bit_depth: u16, path: &Path) -> io::Result<()> {
+    let interleaved: Vec<i32> = wavs.t().iter().map(|&x| quantize(x, bit_depth)).collect();
+    let config = flacenc::config::Encoder::default()
+        .into_verified()
+        .map_err(|(_, e)| io::Error::other(format!("{:?}", e)))?;
+    let source = flacenc::source::MemSource::from_samples(
+        &interleaved,
+        wavs.shape()[0],
+        bit_depth as usize,
+        sr as usize,
+    );
+    let stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+        .map_err(|e| io::Error::other(format!("{:?}", e)))?;
+    let mut sink = flacenc::bitsink::ByteSink::new();
+    stream
+        .write(&mut sink)
+        .map_err(|e| io::Error::other(format!("{:?}", e)))?;
+    std::fs::write(path, sink.as_slice())
+}
+
+fn write_ogg(wavs: ArrayView2<f32>, sr: u32, quality: f32, path: &Path) -> io::Result<()> {
+    let invalid_input = |msg: &str| io::Error::new(io::ErrorKind::InvalidInput, msg.to_owned());
+    let sr = NonZeroU32::new(sr).ok_or_else(|| invalid_input("zero sample rate"))?;
+    let n_ch = u8::try_from(wavs.shape()[0])
+        .ok()
+        .and_then(NonZeroU8::new)
+        .ok_or_else(|| invalid_input("invalid number of channels"))?;
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = VorbisEncoderBuilder::new(sr, n_ch, file)
+        .map_err(io::Error::other)?
+        .bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr {
+            target_quality: quality.clamp(-0.1, 1.),
+        })
+        .build()
+        .map_err(io::Error::other)?;
+    let wavs = wavs.as_standard_layout();
+    for i_start in (0..wavs.shape()[1]).step_by(OGG_BLOCK_SIZE) {
+        let block = wavs.slice(s![
+            ..,
+            i_start..(i_start + OGG_BLOCK_SIZE).min(wavs.shape()[1])
+        ]);
+        let block: Vec<Vec<f32>> = block.outer_iter().map(|ch| ch.to_vec()).collect();
+        encoder
+            .encode_audio_block(&block)
+            .map_err(io::Error::other)?;
+    }
+    encoder.finish().map_err(io::Error::other)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::audio::open_audio_file;
+
+    #[test]
+    fn quantize_works() {
+        assert_eq!(quantize(1., 16), i16::MAX as i32);
+        assert_eq!(quantize(-1., 16), i16::MIN as i32);
+        assert_eq!(quantize(0.5, 24), 1 << 22);
+    }
+
+    #[test]
+    fn transcode_works() {
+        let wavs = Array2::from_shape_fn((2, 4800), |(ch, i)| {
+            ((i as f32 / 48. + ch as f32) * std::f32::consts::PI).sin() * 0.5
+        });
+        let out_dir = std::env::temp_dir().join("thesia_transcode_test");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        for codec in [
+            TranscodeCodec::Wav,
+            TranscodeCodec::Flac,
+            TranscodeCodec::Ogg,
+        ] {
+            let path = unique_output_path(&out_dir, "sine", codec, &[]);
+            transcode(wavs.view(), 48000, codec, &Default::default(), &path).unwrap();
+            let (decoded, format_info) = open_audio_file(path.to_str().unwrap()).unwrap();
+            assert_eq!(format_info.sr, 48000);
+            assert_eq!(decoded.shape()[0], 2);
+            if codec != TranscodeCodec::Ogg {
+                assert_eq!(decoded.shape(), wavs.shape());
+            }
+            std::fs::remove_file(path).unwrap();
+        }
+        let params = TranscodeParams {
+            bit_depth: Some(32),
+            ..Default::default()
+        };
+        let path = out_dir.join("invalid.flac");
+        assert!(transcode(wavs.view(), 48000, TranscodeCodec::Flac, &params, &path).is_err());
+    }
+}
@@ -58,6 +58,68 @@ pub fn unique_filenames(paths: IntMap<usize, PathBuf>) -> IntMap<usize, String>
     result
 }
 
+#[inline]
+fn canonicalize_or_keep(path: &Path) -> PathBuf {
+    dunce::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Returns `path` relative to the directory containing `session_path`.
+/// Falls back to the absolute path if they don't share the same root (e.g. different drives).
+pub fn relative_to_session(path: &Path, session_path: &Path) -> PathBuf {
+    let path = canonicalize_or_keep(path);
+    let base = canonicalize_or_keep(session_path.parent().unwrap_or(Path::new("")));
+    let mut path_comps = path.components().peekable();
+    let mut base_comps = base.components().peekable();
+    if path_comps.peek() != base_comps.peek() {
+        return path;
+    }
+    while path_comps.peek().is_some() && path_comps.peek() == base_comps.peek() {
+        path_comps.next();
+        base_comps.next();
+    }
+    base_comps
+        .map(|_| path::Component::ParentDir)
+        .chain(path_comps)
+        .collect()
+}
+
+/// Resolves the track path stored in a session file.
+/// `relative` is resolved from the directory containing `session_path` first,
+/// and `absolute` is used as a fallback. Returns None if neither exists.
+pub fn resolve_session_path(
+    relative: Option<&Path>,
+    absolute: Option<&Path>,
+    session_path: &Path,
+) -> Option<PathBuf> {
+    let session_dir = session_path.parent().unwrap_or(Path::new(""));
+    relative
+        .map(|relative| session_dir.join(relative))
+        .into_iter()
+        .chain(absolute.map(Path::to_path_buf))
+        .find(|path| path.is_file())
+        .map(|path| canonicalize_or_keep(&path))
+}
+
+/// xorshift64* (deterministic for the same seed)
+#[derive(Clone, Debug)]
+pub struct Xorshift(u64);
+
+impl Xorshift {
+    pub fn new(seed: u64) -> Self {
+        // the state should not be zero
+        Xorshift(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    /// uniform in [-1, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let x = self.0.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (x >> 11) as f64 / (1u64 << 52) as f64 - 1.
+    }
+}
+
 pub enum PadMode<A> {
     Constant(A),
     Reflect,
@@ -163,6 +225,23 @@ mod tests {
 
     use ndarray::arr2;
 
+    #[test]
+    fn session_path_works() {
+        let session_path = Path::new("samples/stereo/session.json");
+        let track_path = Path::new("samples/sample_48k.wav");
+        let relative = relative_to_session(track_path, session_path);
+        assert_eq!(relative, Path::new("..").join("sample_48k.wav"));
+
+        let resolved = resolve_session_path(Some(&relative), None, session_path).unwrap();
+        assert_eq!(resolved, dunce::canonicalize(track_path).unwrap());
+
+        let moved_session_path = Path::new("samples/session.json");
+        let resolved =
+            resolve_session_path(Some(&relative), Some(&resolved), moved_session_path).unwrap();
+        assert_eq!(resolved, dunce::canonicalize(track_path).unwrap());
+        assert!(resolve_session_path(Some(&relative), None, moved_session_path).is_none());
+    }
+
     #[test]
     fn pad_works() {
         assert_eq!(
@@ -0,0 +1,465 @@
+//! Composite PNG of the visible time/frequency window (viewport) of tracks rendered by the backend
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use tiny_skia::{Color, Paint, Pixmap, PixmapPaint, PixmapRef, Rect, Transform};
+
+use super::track::TrackList;
+use super::visualize::{
+    calc_dB_axis_markers, calc_freq_axis_markers, calc_time_axis_markers, map_grey_to_color_iter,
+    AutoContrastMode, DrawOptionForWav, DrawParams, TrackDrawer,
+};
+use super::TrackManager;
+
+const FREQ_AXIS_WIDTH: u32 = 48;
+const TIME_AXIS_HEIGHT: u32 = 24;
+/// gap between channels of a track
+const CH_GAP: u32 = 4;
+/// gap between tracks
+const TRACK_GAP: u32 = 12;
+const TICK_LEN: f32 = 4.;
+/// gap between the plot area and the colorbar
+const COLORBAR_GAP: u32 = 8;
+const COLORBAR_WIDTH: u32 = 12;
+/// width of the dB labels on the right of the colorbar
+const COLORBAR_AXIS_WIDTH: u32 = 40;
+/// space for the unit label below the colorbar
+const COLORBAR_UNIT_HEIGHT: u32 = 16;
+/// size of a pixel of the glyphs
+const GLYPH_SCALE: f32 = 2.;
+const GLYPH_ROWS: usize = 5;
+/// glyph width + spacing (in glyph pixels)
+const GLYPH_ADVANCE: usize = 4;
+/// minimum distance between time labels (px)
+const MIN_TIME_LABEL_GAP: f64 = 100.;
+const TIME_TICK_UNITS: [f64; 21] = [
+    0.001, 0.002, 0.005, 0.01, 0.02, 0.05, 0.1, 0.2, 0.5, 1., 2., 5., 10., 15., 30., 60., 120.,
+    300., 600., 1800., 3600.,
+];
+
+/// Visible tracks and the time/freq window to be rendered
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ViewportLayout {
+    /// visible tracks from the top
+    pub track_ids: Vec<usize>,
+    pub start_sec: f64,
+    pub end_sec: f64,
+    /// width of the plot area (px)
+    pub width: u32,
+    /// height of each channel (px)
+    pub ch_height: u32,
+    pub blend: f64,
+    /// burn in the frequency axis of each channel and the time axis at the bottom
+    pub axes: bool,
+    /// overlay the frequency ticks as grid lines on the images
+    pub freq_grid: bool,
+    /// burn in the colorbar of the spectrogram (dB) on the right
+    pub colorbar: bool,
+}
+
+impl Default for ViewportLayout {
+    fn default() -> Self {
+        ViewportLayout {
+            track_ids: Vec::new(),
+            start_sec: 0.,
+            end_sec: 0.,
+            width: 1200,
+            ch_height: 160,
+            blend: 0.5,
+            axes: true,
+            freq_grid: false,
+            colorbar: false,
+        }
+    }
+}
+
+/// 3x5 bitmap glyphs of the characters used in the axis labels (rows from the top)
+fn glyph(c: char) -> Option<[u8; GLYPH_ROWS]> {
+    let rows = match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        'k' => [0b100, 0b101, 0b110, 0b101, 0b101],
+        'd' => [0b001, 0b001, 0b111, 0b101, 0b111],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        _ => return None,
+    };
+    Some(rows)
+}
+
+#[inline]
+fn text_width(text: &str) -> f32 {
+    (text.chars().count() * GLYPH_ADVANCE).saturating_sub(1) as f32 * GLYPH_SCALE
+}
+
+/// draw text whose top-left corner is (x, y). Unknown characters are left blank.
+fn draw_text(pixmap: &mut Pixmap, text: &str, x: f32, y: f32, paint: &Paint) {
+    for (i_char, c) in text.chars().enumerate() {
+        let Some(rows) = glyph(c) else {
+            continue;
+        };
+        let x_char = x + (i_char * GLYPH_ADVANCE) as f32 * GLYPH_SCALE;
+        for (i_row, row) in rows.iter().enumerate() {
+            for i_col in (0..3).filter(|i_col| row & (0b100 >> i_col) != 0) {
+                let rect = Rect::from_xywh(
+                    x_char + i_col as f32 * GLYPH_SCALE,
+                    y + i_row as f32 * GLYPH_SCALE,
+                    GLYPH_SCALE,
+                    GLYPH_SCALE,
+                );
+                if let Some(rect) = rect {
+                    pixmap.fill_rect(rect, paint, Transform::identity(), None);
+                }
+            }
+        }
+    }
+}
+
+fn fill_rect(pixmap: &mut Pixmap, x: f32, y: f32, w: f32, h: f32, paint: &Paint) {
+    if let Some(rect) = Rect::from_xywh(x, y, w, h) {
+        pixmap.fill_rect(rect, paint, Transform::identity(), None);
+    }
+}
+
+/// the smallest tick unit keeping labels (one per tick) MIN_TIME_LABEL_GAP apart
+fn calc_time_tick_unit(duration: f64, width: u32) -> f64 {
+    let min_unit = duration * MIN_TIME_LABEL_GAP / width as f64;
+    TIME_TICK_UNITS
+        .into_iter()
+        .find(|&unit| unit >= min_unit)
+        .unwrap_or(TIME_TICK_UNITS[TIME_TICK_UNITS.len() - 1])
+}
+
+/// draw label of a vertical axis centered at y_tick, kept inside y_top..y_bottom
+fn draw_vertical_axis_label(
+    pixmap: &mut Pixmap,
+    label: &str,
+    x: f32,
+    y_tick: f32,
+    (y_top, y_bottom): (f32, f32),
+    paint: &Paint,
+) {
+    let text_height = GLYPH_ROWS as f32 * GLYPH_SCALE;
+    let y_label = (y_tick - text_height / 2.)
+        .min(y_bottom - text_height)
+        .max(y_top);
+    draw_text(pixmap, label, x, y_label, paint);
+}
+
+/// colorbar of the spectrogram from min_dB (bottom) to max_dB (top) with dB labels on the right
+#[allow(non_snake_case)]
+fn draw_colorbar(
+    pixmap: &mut Pixmap,
+    x: u32,
+    height: u32,
+    (min_dB, max_dB): (f32, f32),
+    paint: &Paint,
+) -> Result<(), String> {
+    let greys: Vec<_> = (0..height)
+        .map(|i| {
+            let ratio = 1. - i as f32 / (height - 1).max(1) as f32;
+            ratio.mul_add((u16::MAX - 1) as f32, 1.).round() as u16
+        })
+        .collect();
+    let row_colors: Vec<_> = map_grey_to_color_iter(&greys).collect();
+    let rgba: Vec<_> = row_colors
+        .chunks_exact(4)
+        .flat_map(|color| itertools::repeat_n(color, COLORBAR_WIDTH as usize).flatten())
+        .copied()
+        .collect();
+    let bar = PixmapRef::from_bytes(&rgba, COLORBAR_WIDTH, height).ok_or("Too large viewport.")?;
+    pixmap.draw_pixmap(
+        x as i32,
+        0,
+        bar,
+        &PixmapPaint::default(),
+        Transform::identity(),
+        None,
+    );
+    draw_text(pixmap, "dB", x as f32, (height + 4) as f32, paint);
+
+    let n = (height / 32).clamp(2, 10);
+    let x_tick = (x + COLORBAR_WIDTH) as f32;
+    for (pos, label) in calc_dB_axis_markers(n, n, (min_dB, max_dB)) {
+        let y_tick = (pos * height as f32).min((height - 1) as f32);
+        fill_rect(pixmap, x_tick, y_tick, TICK_LEN, 1., paint);
+        if !label.is_empty() {
+            let x_label = x_tick + TICK_LEN + 2.;
+            draw_vertical_axis_label(pixmap, &label, x_label, y_tick, (0., height as f32), paint);
+        }
+    }
+    Ok(())
+}
+
+/// PNG of the visible window of all visible tracks stacked from the top.
+/// Optionally burns in the frequency axis on the left of each channel,
+/// the time axis at the bottom, the frequency grid, and the colorbar on the right.
+pub fn render_viewport_png(
+    tm: &TrackManager,
+    tracklist: &TrackList,
+    layout: &ViewportLayout,
+) -> Result<Vec<u8>, String> {
+    if !layout.start_sec.is_finite()
+        || !layout.end_sec.is_finite()
+        || layout.end_sec <= layout.start_sec
+        || layout.width == 0
+        || layout.ch_height == 0
+    {
+        return Err("Invalid viewport layout.".into());
+    }
+    let id_ch_tuples = tracklist.id_ch_tuples_from(&layout.track_ids);
+    if id_ch_tuples.is_empty() {
+        return Err("No visible track exists.".into());
+    }
+    let (width, ch_height) = (layout.width, layout.ch_height);
+    let mut params = DrawParams {
+        start_sec: layout.start_sec,
+        width,
+        height: ch_height,
+        px_per_sec: width as f64 / (layout.end_sec - layout.start_sec),
+        opt_for_wav: DrawOptionForWav::default(),
+        blend: layout.blend.clamp(0., 1.),
+        hz_range: None,
+        grey_range: None,
+        freq_break: None,
+    };
+    if tm.auto_contrast_mode == AutoContrastMode::Viewport {
+        params.grey_range = tm.visible_grey_range(tracklist, &id_ch_tuples, &params);
+    }
+    let imgs = tm.draw_part_imgs(tracklist, &id_ch_tuples, &params, None);
+
+    let n_ch_total = id_ch_tuples.len() as u32;
+    let n_tracks = id_ch_tuples.iter().map(|&(id, _)| id).dedup().count() as u32;
+    let plot_height =
+        n_ch_total * ch_height + (n_ch_total - n_tracks) * CH_GAP + (n_tracks - 1) * TRACK_GAP;
+    let (plot_left, axis_height) = if layout.axes {
+        (FREQ_AXIS_WIDTH, TIME_AXIS_HEIGHT)
+    } else {
+        (0, 0)
+    };
+    let (colorbar_width, colorbar_height) = if layout.colorbar {
+        (
+            COLORBAR_GAP + COLORBAR_WIDTH + COLORBAR_AXIS_WIDTH,
+            plot_height + COLORBAR_UNIT_HEIGHT,
+        )
+    } else {
+        (0, 0)
+    };
+    let mut pixmap = Pixmap::new(
+        plot_left + width + colorbar_width,
+        (plot_height + axis_height).max(colorbar_height),
+    )
+    .ok_or("Too large viewport.")?;
+    pixmap.fill(Color::from_rgba8(17, 17, 17, 255));
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(204, 204, 204, 255);
+    paint.anti_alias = false;
+    let mut grid_paint = paint.clone();
+    grid_paint.set_color_rgba8(204, 204, 204, 80);
+
+    let freq_markers = calc_freq_axis_markers(
+        tm.get_hz_range(),
+        tm.setting.freq_scale,
+        (ch_height / 32).clamp(2, 10),
+        (ch_height / 32).clamp(2, 10),
+    );
+    let mut y = 0;
+    let mut prev_id = None;
+    for ((id, _), img) in &imgs {
+        if let Some(prev_id) = prev_id {
+            y += if prev_id == *id { CH_GAP } else { TRACK_GAP };
+        }
+        prev_id = Some(*id);
+        if let Some(img) = PixmapRef::from_bytes(img, width, ch_height) {
+            pixmap.draw_pixmap(
+                plot_left as i32,
+                y as i32,
+                img,
+                &PixmapPaint::default(),
+                Transform::identity(),
+                None,
+            );
+        }
+        for (pos, label) in &freq_markers {
+            let y_tick = (y as f32 + pos * ch_height as f32).min((y + ch_height - 1) as f32);
+            if layout.freq_grid {
+                fill_rect(
+                    &mut pixmap,
+                    plot_left as f32,
+                    y_tick,
+                    width as f32,
+                    1.,
+                    &grid_paint,
+                );
+            }
+            if !layout.axes {
+                continue;
+            }
+            fill_rect(
+                &mut pixmap,
+                FREQ_AXIS_WIDTH as f32 - TICK_LEN,
+                y_tick,
+                TICK_LEN,
+                1.,
+                &paint,
+            );
+            if !label.is_empty() {
+                let x_label = FREQ_AXIS_WIDTH as f32 - TICK_LEN - 2. - text_width(label);
+                let y_range = (y as f32, (y + ch_height) as f32);
+                draw_vertical_axis_label(&mut pixmap, label, x_label, y_tick, y_range, &paint);
+            }
+        }
+        y += ch_height;
+    }
+
+    if layout.axes {
+        let tick_unit = calc_time_tick_unit(layout.end_sec - layout.start_sec, width);
+        let time_markers = calc_time_axis_markers(
+            layout.start_sec,
+            layout.end_sec,
+            tick_unit,
+            1,
+            tracklist.max_sec,
+            None,
+        );
+        for (x, label) in time_markers
+            .into_iter()
+            .filter(|(x, _)| (0. ..=1.).contains(x))
+        {
+            let x_tick = plot_left as f32 + (x * width as f32).min((width - 1) as f32);
+            fill_rect(
+                &mut pixmap,
+                x_tick,
+                plot_height as f32,
+                1.,
+                TICK_LEN,
+                &paint,
+            );
+            if !label.is_empty() {
+                let y_label = plot_height as f32 + TICK_LEN + 2.;
+                draw_text(&mut pixmap, &label, x_tick + 2., y_label, &paint);
+            }
+        }
+    }
+    if layout.colorbar {
+        let x_colorbar = plot_left + width + COLORBAR_GAP;
+        draw_colorbar(
+            &mut pixmap,
+            x_colorbar,
+            plot_height,
+            params
+                .grey_range
+                .map_or((tm.min_dB, tm.max_dB), |range| tm.grey_range_to_dB(range)),
+            &paint,
+        )?;
+    }
+    pixmap.encode_png().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_viewport_works() {
+        let mut tracklist = TrackList::new();
+        let mut tm = TrackManager::new();
+        let added_ids = tracklist.add_tracks(
+            vec![0, 1],
+            vec![
+                "samples/sample_48k.wav".into(),
+                "samples/stereo/sample_48k.wav".into(),
+            ],
+        );
+        tm.add_tracks(&tracklist, &added_ids);
+
+        let layout = ViewportLayout {
+            track_ids: vec![1, 0],
+            start_sec: 0.5,
+            end_sec: 1.5,
+            width: 400,
+            ch_height: 100,
+            ..Default::default()
+        };
+        let png = render_viewport_png(&tm, &tracklist, &layout).unwrap();
+        let pixmap = Pixmap::decode_png(&png).unwrap();
+        assert_eq!(pixmap.width(), FREQ_AXIS_WIDTH + 400);
+        assert_eq!(
+            pixmap.height(),
+            3 * 100 + CH_GAP + TRACK_GAP + TIME_AXIS_HEIGHT
+        );
+
+        let invalid = ViewportLayout {
+            end_sec: 0.5,
+            ..layout.clone()
+        };
+        assert!(render_viewport_png(&tm, &tracklist, &invalid).is_err());
+        let no_tracks = ViewportLayout {
+            track_ids: vec![5],
+            ..layout
+        };
+        assert!(render_viewport_png(&tm, &tracklist, &no_tracks).is_err());
+    }
+
+    #[test]
+    fn render_viewport_burn_in_options_work() {
+        let mut tracklist = TrackList::new();
+        let mut tm = TrackManager::new();
+        let added_ids = tracklist.add_tracks(vec![0], vec!["samples/sample_48k.wav".into()]);
+        tm.add_tracks(&tracklist, &added_ids);
+
+        let layout = ViewportLayout {
+            track_ids: vec![0],
+            start_sec: 0.,
+            end_sec: 1.,
+            width: 300,
+            ch_height: 100,
+            blend: 1.,
+            axes: false,
+            ..Default::default()
+        };
+        let png = render_viewport_png(&tm, &tracklist, &layout).unwrap();
+        let pixmap = Pixmap::decode_png(&png).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (300, 100));
+
+        let layout = ViewportLayout {
+            axes: true,
+            freq_grid: true,
+            colorbar: true,
+            ..layout
+        };
+        let png = render_viewport_png(&tm, &tracklist, &layout).unwrap();
+        let pixmap = Pixmap::decode_png(&png).unwrap();
+        let x_colorbar = FREQ_AXIS_WIDTH + 300 + COLORBAR_GAP;
+        assert_eq!(
+            pixmap.width(),
+            x_colorbar + COLORBAR_WIDTH + COLORBAR_AXIS_WIDTH
+        );
+        assert_eq!(pixmap.height(), 100 + TIME_AXIS_HEIGHT);
+        // the top of the colorbar is brighter than the bottom
+        let brightness = |y| {
+            let pixel = pixmap.pixel(x_colorbar + COLORBAR_WIDTH / 2, y).unwrap();
+            pixel.red() as u32 + pixel.green() as u32 + pixel.blue() as u32
+        };
+        assert!(brightness(0) > brightness(99));
+    }
+
+    #[test]
+    fn calc_time_tick_unit_works() {
+        assert_eq!(calc_time_tick_unit(10., 1000), 1.);
+        assert_eq!(calc_time_tick_unit(1., 1000), 0.1);
+        assert_eq!(calc_time_tick_unit(1e6, 100), 3600.);
+        assert_eq!(text_width("1k"), 14.);
+    }
+}
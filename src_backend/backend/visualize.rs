@@ -2,15 +2,35 @@ mod axis;
 mod colorize;
 mod drawing;
 mod drawing_wav;
+#[cfg(test)]
+mod golden_tests;
 mod img_slice;
 mod params;
 mod resample;
+mod wav_pyramid;
 
 pub use axis::{
-    calc_amp_axis_markers, calc_dB_axis_markers, calc_freq_axis_markers, calc_time_axis_markers,
-    convert_freq_label_to_hz, convert_hz_to_label, convert_sec_to_label, convert_time_label_to_sec,
+    calc_amp_axis_markers, calc_broken_freq_axis_markers, calc_dB_axis_markers,
+    calc_freq_axis_markers, calc_gain_axis_markers, calc_time_axis_markers,
+    convert_freq_label_to_hz, convert_hz_to_label, convert_sec_to_label, convert_sec_to_samples,
+    convert_time_label_to_sec, fit_amp_axis_markers, fit_dB_axis_markers, fit_freq_axis_markers,
+    fit_time_axis_markers, format_position, LabelMetrics, PositionUnit, TempoGrid,
 };
-pub use colorize::get_colormap_rgb;
-pub use drawing::{blend_img_to, convert_spec_to_grey, make_opaque, TrackDrawer};
-pub use img_slice::{calc_effective_slice, CalcWidth, IdxLen, LeftWidth, PartGreyInfo};
-pub use params::{DrawOptionForWav, DrawParams, ImageKind};
+pub use colorize::{
+    calc_grey_range, get_colormap_rgb, get_colormap_rgb_with_length, map_grey_to_color_iter,
+    stretch_grey_range,
+};
+pub use drawing::{
+    blend_img_to, calc_overview_layout, calc_overview_segment_hashes, convert_spec_to_grey,
+    draw_overview_of, draw_overview_segment_of, make_opaque, TrackDrawer, OVERVIEW_SEGMENT_WIDTH,
+};
+pub use drawing_wav::{calc_wav_pixel_envelope, classify_wav_pixels, WavPixelClass};
+pub use img_slice::{
+    calc_effective_slice, calc_n_tiles, slice_grey_tile, CalcWidth, IdxLen, LeftWidth,
+    PartGreyInfo, SPEC_TILE_SIZE,
+};
+pub use params::{
+    AutoContrastMode, DrawOptionForWav, DrawParams, FreqAxisBreak, FreqInterpolation, ImageKind,
+};
+pub use resample::FftResampler;
+pub use wav_pyramid::{BlockHashes, WavPyramid};
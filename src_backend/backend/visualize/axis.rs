@@ -1,22 +1,163 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 
 use approx::abs_diff_ne;
 use chrono::naive::NaiveTime;
+use napi_derive::napi;
 use num_traits::Zero;
+use serde::{Deserialize, Serialize};
 
+use super::super::dynamics::DeciBel;
 use super::super::spectrogram::{mel, FreqScale};
+use super::drawing::MIN_LIMITER_GAIN_DRAWN;
+use super::params::FreqAxisBreak;
 
 pub type AxisMarkers = Vec<(f32, String)>;
 
 const POSSIBLE_TEN_UNITS: [u32; 4] = [10, 20, 50, 100];
+/// minimum distance between adjacent ticks (px)
+const MIN_TICK_GAP: f32 = 5.;
+/// maximum number of divisions by minor ticks between labeled ticks of vertical axes
+const MAX_MINOR_DIVISIONS: u32 = 4;
+/// (label unit (sec), number of divisions by minor ticks) of the time axis in ascending order
+const TIME_LABEL_UNITS: [(f64, u32); 23] = [
+    (0.001, 5),
+    (0.002, 4),
+    (0.005, 5),
+    (0.01, 5),
+    (0.02, 4),
+    (0.05, 5),
+    (0.1, 5),
+    (0.2, 4),
+    (0.5, 5),
+    (1., 5),
+    (2., 4),
+    (5., 5),
+    (10., 5),
+    (15., 3),
+    (30., 3),
+    (60., 6),
+    (120., 4),
+    (300., 5),
+    (600., 5),
+    (900., 3),
+    (1800., 6),
+    (3600., 6),
+    (7200., 4),
+];
 
+/// dB units of the limiter gain axis in ascending order
+const GAIN_DB_UNITS: [f32; 6] = [0.1, 0.2, 0.5, 1., 2., 3.];
+
+/// bar numbers available as label units of the bars/beats time axis in ascending order
+const BAR_LABEL_UNITS: [u32; 9] = [1, 2, 4, 8, 16, 32, 64, 128, 256];
+/// number of divisions of a beat by minor ticks when labeling every beat
+const BEAT_DIVISIONS: u32 = 4;
+
+/// Tempo and meter for the time axis in bars/beats.
+/// A beat is a note of the denominator of the time signature.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TempoGrid {
+    /// beats per minute
+    pub bpm: f64,
+    pub beats_per_bar: u32,
+    pub beat_unit: u32,
+    /// time (sec) of the first beat of bar 1
+    pub offset_sec: f64,
+}
+
+impl TempoGrid {
+    pub fn new(bpm: f64, time_signature: (u32, u32), offset_sec: f64) -> Option<Self> {
+        let (beats_per_bar, beat_unit) = time_signature;
+        (bpm.is_finite()
+            && bpm > 0.
+            && beats_per_bar > 0
+            && beat_unit > 0
+            && offset_sec.is_finite())
+        .then_some(TempoGrid {
+            bpm,
+            beats_per_bar,
+            beat_unit,
+            offset_sec,
+        })
+    }
+
+    #[inline]
+    pub fn beat_sec(&self) -> f64 {
+        60. / self.bpm
+    }
+
+    /// "bar" on the first beat of bars, "bar.beat" otherwise (both 1-based).
+    /// Bars before offset_sec are 0 or negative.
+    fn label_of(&self, beat: f64) -> String {
+        let beat = beat.floor() as i64;
+        let bar = beat.div_euclid(self.beats_per_bar as i64) + 1;
+        let beat_in_bar = beat.rem_euclid(self.beats_per_bar as i64) + 1;
+        if beat_in_bar == 1 {
+            bar.to_string()
+        } else {
+            format!("{}.{}", bar, beat_in_bar)
+        }
+    }
+}
+
+/// pixel metrics of axis labels measured by the frontend (e.g. by measureText of the canvas)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LabelMetrics {
+    /// width of each character (px)
+    pub char_widths: HashMap<char, f32>,
+    /// width of the characters not in char_widths (px)
+    pub default_char_width: f32,
+    /// height of a label (px)
+    pub label_height: f32,
+    /// minimum space between adjacent labels (px)
+    pub min_gap: f32,
+}
+
+impl Default for LabelMetrics {
+    fn default() -> Self {
+        LabelMetrics {
+            char_widths: HashMap::new(),
+            default_char_width: 7.,
+            label_height: 12.,
+            min_gap: 8.,
+        }
+    }
+}
+
+impl LabelMetrics {
+    pub fn label_width(&self, label: &str) -> f32 {
+        label
+            .chars()
+            .map(|c| {
+                self.char_widths
+                    .get(&c)
+                    .copied()
+                    .unwrap_or(self.default_char_width)
+            })
+            .sum()
+    }
+}
+
+/// If tempo_grid is Some, the axis is in bars/beats and tick_unit is the number of beats.
 pub fn calc_time_axis_markers(
     start_sec: f64,
     end_sec: f64,
     tick_unit: f64,
     label_interval: u32,
     max_sec: f64,
+    tempo_grid: Option<&TempoGrid>,
 ) -> AxisMarkers {
+    if let Some(tempo_grid) = tempo_grid {
+        return calc_bar_beat_axis_markers(
+            start_sec,
+            end_sec,
+            tick_unit,
+            label_interval,
+            tempo_grid,
+        );
+    }
     let first_unit = (start_sec / tick_unit).ceil() as u32;
     // The label just before start_sec (at negative coordinate) should be drawn.
     let first_unit = first_unit.saturating_sub(label_interval);
@@ -83,6 +224,190 @@ pub fn calc_time_axis_markers(
         .collect()
 }
 
+fn calc_bar_beat_axis_markers(
+    start_sec: f64,
+    end_sec: f64,
+    tick_unit: f64,
+    label_interval: u32,
+    tempo_grid: &TempoGrid,
+) -> AxisMarkers {
+    let beat_of = |sec: f64| (sec - tempo_grid.offset_sec) / tempo_grid.beat_sec();
+    // The label just before start_sec (at negative coordinate) should be drawn.
+    let first_unit = (beat_of(start_sec) / tick_unit).ceil() as i64 - label_interval as i64;
+    let last_unit = (beat_of(end_sec) / tick_unit).ceil() as i64;
+    let elem_format_display = (i32::MIN as f32, "bar.beat".to_string());
+    (first_unit..last_unit)
+        .map(|unit| {
+            let beat = unit as f64 * tick_unit;
+            let sec = tempo_grid.offset_sec + beat * tempo_grid.beat_sec();
+            let x = ((sec - start_sec) / (end_sec - start_sec)) as f32;
+            if unit.rem_euclid(label_interval as i64) > 0 {
+                (x, String::new())
+            } else {
+                (x, tempo_grid.label_of(beat + 1e-9))
+            }
+        })
+        .chain(Some(elem_format_display))
+        .collect()
+}
+
+/// (label unit (beats), number of divisions by minor ticks) of the bars/beats time axis
+/// in ascending order
+fn bar_beat_label_units(tempo_grid: &TempoGrid) -> Vec<(f64, u32)> {
+    let beats_per_bar = tempo_grid.beats_per_bar;
+    Some((1., BEAT_DIVISIONS))
+        .into_iter()
+        .filter(|_| beats_per_bar > 1)
+        .chain(BAR_LABEL_UNITS.into_iter().map(|n_bars| {
+            let n_divisions = if n_bars == 1 {
+                beats_per_bar
+            } else {
+                n_bars.min(MAX_MINOR_DIVISIONS)
+            };
+            ((n_bars * beats_per_bar) as f64, n_divisions)
+        }))
+        .collect()
+}
+
+/// Time axis markers with the densest labels not overlapping each other in the width (px).
+/// Labels are assumed to be drawn right of the labeled (major) ticks.
+/// The unlabeled ticks are minor ticks.
+/// If tempo_grid is Some, the axis is in bars/beats.
+pub fn fit_time_axis_markers(
+    start_sec: f64,
+    end_sec: f64,
+    width: f32,
+    max_sec: f64,
+    tempo_grid: Option<&TempoGrid>,
+    metrics: &LabelMetrics,
+) -> AxisMarkers {
+    // label units are in sec, or in beats for the bars/beats axis
+    let (label_units, px_per_unit) = match tempo_grid {
+        Some(tempo_grid) => (
+            bar_beat_label_units(tempo_grid),
+            width as f64 / (end_sec - start_sec) * tempo_grid.beat_sec(),
+        ),
+        None => (
+            TIME_LABEL_UNITS.to_vec(),
+            width as f64 / (end_sec - start_sec),
+        ),
+    };
+    let mut markers = AxisMarkers::new();
+    for (label_unit, n_divisions) in label_units {
+        let n_divisions = if label_unit / n_divisions as f64 * px_per_unit >= MIN_TICK_GAP as f64 {
+            n_divisions
+        } else {
+            1
+        };
+        markers = calc_time_axis_markers(
+            start_sec,
+            end_sec,
+            label_unit / n_divisions as f64,
+            n_divisions,
+            max_sec,
+            tempo_grid,
+        );
+        if labels_fit(&markers, width, |label| {
+            metrics.label_width(label) + metrics.min_gap
+        }) {
+            break;
+        }
+    }
+    markers
+}
+
+/// Frequency axis markers with the most labels not overlapping each other in the height (px).
+/// The unlabeled ticks are minor ticks.
+pub fn fit_freq_axis_markers(
+    hz_range: (f32, f32),
+    freq_scale: FreqScale,
+    freq_break: Option<FreqAxisBreak>,
+    height: f32,
+    metrics: &LabelMetrics,
+) -> AxisMarkers {
+    fit_vertical_markers(height, metrics, |max_num_ticks, max_num_labels| {
+        let calc = |max_num_ticks| {
+            calc_broken_freq_axis_markers(
+                hz_range,
+                freq_scale,
+                freq_break,
+                max_num_ticks,
+                max_num_ticks,
+            )
+        };
+        let major = calc(max_num_labels);
+        let minor: Vec<_> = calc(max_num_ticks)
+            .into_iter()
+            .filter(|(y, _)| major.iter().all(|(y_major, _)| (y - y_major).abs() > 1e-4))
+            .map(|(y, _)| (y, String::new()))
+            .collect();
+        let mut markers: AxisMarkers = major.into_iter().chain(minor).collect();
+        markers.sort_by(|a, b| b.0.total_cmp(&a.0));
+        markers
+    })
+}
+
+/// Amplitude axis markers with the most labels not overlapping each other in the height (px)
+pub fn fit_amp_axis_markers(
+    amp_range: (f32, f32),
+    height: f32,
+    metrics: &LabelMetrics,
+) -> AxisMarkers {
+    fit_vertical_markers(height, metrics, |max_num_ticks, max_num_labels| {
+        // the number of ticks should be odd
+        calc_amp_axis_markers(max_num_ticks.max(3) | 1, max_num_labels, amp_range)
+    })
+}
+
+/// dB axis markers with the most labels not overlapping each other in the height (px)
+#[allow(non_snake_case)]
+pub fn fit_dB_axis_markers(
+    dB_range: (f32, f32),
+    height: f32,
+    metrics: &LabelMetrics,
+) -> AxisMarkers {
+    fit_vertical_markers(height, metrics, |max_num_ticks, max_num_labels| {
+        calc_dB_axis_markers(max_num_ticks, max_num_labels, dB_range)
+    })
+}
+
+/// markers from calc(max_num_ticks, max_num_labels) with the most labels fitting in the height.
+/// Labels are assumed to be drawn above the ticks.
+fn fit_vertical_markers(
+    height: f32,
+    metrics: &LabelMetrics,
+    calc: impl Fn(u32, u32) -> AxisMarkers,
+) -> AxisMarkers {
+    let label_space = (metrics.label_height + metrics.min_gap).max(1.);
+    let max_num_labels = ((height / label_space) as u32 + 1).max(2);
+    let max_num_ticks = (height / MIN_TICK_GAP) as u32 + 1;
+    let mut markers = AxisMarkers::new();
+    for max_num_labels in (2..=max_num_labels).rev() {
+        let max_num_ticks = ((max_num_labels - 1) * MAX_MINOR_DIVISIONS + 1)
+            .min(max_num_ticks)
+            .max(max_num_labels);
+        markers = calc(max_num_ticks, max_num_labels);
+        if labels_fit(&markers, height, |_| label_space) {
+            break;
+        }
+    }
+    markers
+}
+
+/// if the distance between adjacent labels (in px) is larger than the space the former requires.
+/// The element for the format display of the time axis is ignored.
+fn labels_fit(markers: &[(f32, String)], length: f32, space_of: impl Fn(&str) -> f32) -> bool {
+    let mut labels: Vec<_> = markers
+        .iter()
+        .filter(|(pos, label)| !label.is_empty() && *pos != i32::MIN as f32)
+        .map(|(pos, label)| (pos * length, label.as_str()))
+        .collect();
+    labels.sort_by(|a, b| a.0.total_cmp(&b.0));
+    labels
+        .windows(2)
+        .all(|w| w[1].0 - w[0].0 >= space_of(w[0].1) - 1e-3)
+}
+
 pub fn calc_freq_axis_markers(
     hz_range: (f32, f32),
     freq_scale: FreqScale,
@@ -92,7 +417,11 @@ pub fn calc_freq_axis_markers(
     // TODO: max_num_labels
     fn coarse_band(fine_band: f32) -> f32 {
         if fine_band <= 100. {
-            100.
+            // narrow (e.g. infrasound) ranges get 1, 2, 5, 10, 20, 50 or 100 Hz bands
+            [1., 2., 5., 10., 20., 50., 100.]
+                .into_iter()
+                .find(|&band| fine_band <= band)
+                .unwrap()
         } else if fine_band <= 200. {
             200.
         } else if fine_band <= 500. {
@@ -171,6 +500,43 @@ pub fn calc_freq_axis_markers(
     result
 }
 
+/// Frequency axis markers of the axis broken by freq_break.
+/// Each segment gets the ticks in proportion to its height, and the break is labeled once.
+/// Same as calc_freq_axis_markers if the axis isn't broken.
+pub fn calc_broken_freq_axis_markers(
+    hz_range: (f32, f32),
+    freq_scale: FreqScale,
+    freq_break: Option<FreqAxisBreak>,
+    max_num_ticks: u32,
+    max_num_labels: u32,
+) -> AxisMarkers {
+    let Some(freq_break) = freq_break.filter(|x| x.is_inside(hz_range)) else {
+        return calc_freq_axis_markers(hz_range, freq_scale, max_num_ticks, max_num_labels);
+    };
+    let ratio = freq_break.ratio as f32;
+    let break_hz = freq_break.hz as f32;
+    let n_for = |n: u32, ratio: f32| ((n as f32 * ratio).round() as u32).max(2);
+    let lower = calc_freq_axis_markers(
+        (hz_range.0, break_hz),
+        freq_scale,
+        n_for(max_num_ticks, ratio),
+        n_for(max_num_labels, ratio),
+    );
+    let upper = calc_freq_axis_markers(
+        (break_hz, hz_range.1),
+        freq_scale,
+        n_for(max_num_ticks, 1. - ratio),
+        n_for(max_num_labels, 1. - ratio),
+    );
+    // the top of the lower segment is the bottom of the upper segment
+    lower
+        .into_iter()
+        .filter(|(y, _)| *y > 0.)
+        .map(|(y, s)| (ratio.mul_add(y, 1. - ratio), s))
+        .chain(upper.into_iter().map(|(y, s)| (y * (1. - ratio), s)))
+        .collect()
+}
+
 pub fn calc_amp_axis_markers(
     max_num_ticks: u32,
     max_num_labels: u32,
@@ -233,6 +599,34 @@ pub fn calc_dB_axis_markers(
     omit_labels_from_linear_axis(axis.into_iter(), len, max_num_labels).collect()
 }
 
+/// dB axis markers of the upper limiter gain lane of the overview down to max_reduction_dB
+/// (sign ignored). The position is 0 at the top (0 dB) and 1 at the bottom of the lane.
+/// The lower lane is upside down (1 - position).
+#[allow(non_snake_case)]
+pub fn calc_gain_axis_markers(max_reduction_dB: f32, max_num_ticks: u32) -> AxisMarkers {
+    let mut markers = vec![(0., "0".to_string())];
+    if !max_reduction_dB.is_finite() || max_num_ticks < 2 {
+        return markers;
+    }
+    let floor_dB = -MIN_LIMITER_GAIN_DRAWN.dB_from_amp_default();
+    let reduction_dB = max_reduction_dB.abs().min(floor_dB);
+    let n_units_of = |unit: f32| ((reduction_dB + 1e-3) / unit).floor() as u32;
+    let unit = GAIN_DB_UNITS
+        .into_iter()
+        .find(|&unit| n_units_of(unit) < max_num_ticks)
+        .unwrap_or(GAIN_DB_UNITS[GAIN_DB_UNITS.len() - 1]);
+    let unit_exponent = if unit < 1. { -1 } else { 0 };
+    let gain_to_pos = |gain: f32| (1. - gain) / (1. - MIN_LIMITER_GAIN_DRAWN);
+    markers.extend((1..=n_units_of(unit).min(max_num_ticks - 1)).map(|i| {
+        let dB = -(i as f32) * unit;
+        (
+            gain_to_pos(dB.amp_from_dB_default()).min(1.),
+            format_ticklabel(dB, unit_exponent),
+        )
+    }));
+    markers
+}
+
 fn calc_linear_axis(min: f32, max: f32, max_num_ticks: u32) -> AxisMarkers {
     if max_num_ticks == 2 {
         return vec![
@@ -311,6 +705,44 @@ pub fn convert_time_label_to_sec(label: &str) -> Result<f64, <f64 as FromStr>::E
     }
 }
 
+/// unit of the positions shown in the readouts and labels
+#[napi(string_enum)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PositionUnit {
+    /// hh:mm:ss.mmm
+    #[default]
+    Time,
+    /// index of the sample at or before the position
+    Samples,
+    /// sample index with the fractional part (sub-sample position)
+    FractionalSamples,
+}
+
+/// tolerance for the positions converted from sample indices (e.g. 1 / 48000 * 48000)
+const SAMPLE_EPSILON: f64 = 1e-6;
+
+/// Convert the position (sec) to the sample index with the fractional part.
+/// If snap_to_sample, the position is rounded to the nearest sample.
+pub fn convert_sec_to_samples(sec: f64, sr: u32, snap_to_sample: bool) -> f64 {
+    let samples = sec * sr as f64;
+    if snap_to_sample {
+        samples.round()
+    } else {
+        samples
+    }
+}
+
+/// string of the position (sec) in the unit used by all readouts and labels.
+/// sr is only used for the units in samples.
+pub fn format_position(sec: f64, unit: PositionUnit, sr: u32) -> String {
+    let samples = convert_sec_to_samples(sec.max(0.), sr, false);
+    match unit {
+        PositionUnit::Time => convert_sec_to_label(sec),
+        PositionUnit::Samples => format!("{}", (samples + SAMPLE_EPSILON).floor() as u64),
+        PositionUnit::FractionalSamples => format!("{:.3}", samples),
+    }
+}
+
 pub fn convert_hz_to_label(freq: f32) -> String {
     let freq = freq.round().max(0.);
     let freq_int = freq as usize;
@@ -410,11 +842,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn format_position_works() {
+        assert_eq!(
+            format_position(1.5, PositionUnit::Time, 48000),
+            "00:00:01.500"
+        );
+        assert_eq!(format_position(1.5, PositionUnit::Samples, 48000), "72000");
+        assert_eq!(
+            format_position(1. / 3., PositionUnit::Samples, 48000),
+            "16000"
+        );
+        assert_eq!(
+            format_position(0.25 / 48000., PositionUnit::FractionalSamples, 48000),
+            "0.250"
+        );
+        assert_eq!(convert_sec_to_samples(0.75 / 48000., 48000, true), 1.);
+    }
+
     #[test]
     fn time_axis_works() {
-        dbg!(calc_time_axis_markers(1.999, 2.0015, 0.0005, 1, 59.));
+        dbg!(calc_time_axis_markers(1.999, 2.0015, 0.0005, 1, 59., None));
         assert_axis_eq(
-            &calc_time_axis_markers(1.999, 2.0015, 0.0005, 1, 59.),
+            &calc_time_axis_markers(1.999, 2.0015, 0.0005, 1, 59., None),
             &[
                 (-0.2, "1.998"),
                 (0.0, "1.999"),
@@ -426,7 +876,7 @@ mod tests {
             ],
         );
         assert_axis_eq(
-            &calc_time_axis_markers(1.999, 2.001, 0.001, 1, 60.),
+            &calc_time_axis_markers(1.999, 2.001, 0.001, 1, 60., None),
             &[
                 (-0.5, "00:01.998"),
                 (0.0, "00:01.999"),
@@ -436,6 +886,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn bar_beat_axis_works() {
+        assert!(TempoGrid::new(0., (4, 4), 0.).is_none());
+        assert!(TempoGrid::new(120., (0, 4), 0.).is_none());
+        // 120 bpm, 3/4, bar 1 starts at 0.25 sec: a beat = 0.5 sec, a bar = 1.5 sec
+        let tempo_grid = TempoGrid::new(120., (3, 4), 0.25).unwrap();
+        assert_axis_eq(
+            &calc_time_axis_markers(0.25, 2.25, 1., 1, 10., Some(&tempo_grid)),
+            &[
+                (-0.25, "0.3"),
+                (0., "1"),
+                (0.25, "1.2"),
+                (0.5, "1.3"),
+                (0.75, "2"),
+                (i32::MIN as f32, "bar.beat"),
+            ],
+        );
+        // labels on bars with minor ticks on beats
+        let markers = calc_time_axis_markers(0.25, 3.25, 1., 3, 10., Some(&tempo_grid));
+        let labels: Vec<_> = markers.iter().map(|(_, s)| s.as_str()).collect();
+        assert_eq!(labels, ["0", "", "", "1", "", "", "2", "", "", "bar.beat"]);
+
+        let metrics = LabelMetrics::default();
+        // 100 px/sec: 50 px/beat, every beat is labeled
+        let markers = fit_time_axis_markers(0.25, 10.25, 1000., 10., Some(&tempo_grid), &metrics);
+        assert!(markers.iter().any(|(_, s)| s == "1.2"));
+        // 10 px/sec: 15 px/bar, only some bars are labeled
+        let markers = fit_time_axis_markers(0.25, 100.25, 1000., 10., Some(&tempo_grid), &metrics);
+        assert!(markers
+            .iter()
+            .all(|(_, s)| !s.contains('.') || s == "bar.beat"));
+        assert!(labels_fit(&markers, 1000., |label| {
+            metrics.label_width(label) + metrics.min_gap
+        }));
+    }
+
     #[test]
     fn freq_axis_works() {
         assert_axis_eq(
@@ -515,6 +1001,102 @@ mod tests {
         );
     }
 
+    #[test]
+    fn broken_freq_axis_works() {
+        let freq_break = FreqAxisBreak {
+            hz: 100.,
+            ratio: 0.5,
+        };
+        assert_axis_eq(
+            &calc_broken_freq_axis_markers((0., 12000.), FreqScale::Linear, Some(freq_break), 4, 4),
+            &vec![(1., "0"), (0.5, "100"), (0., "12k")],
+        );
+        let markers = calc_broken_freq_axis_markers(
+            (0., 12000.),
+            FreqScale::Linear,
+            Some(freq_break),
+            12,
+            12,
+        );
+        assert_axis_eq(
+            &markers[..4],
+            &vec![(1., "0"), (0.9, "20"), (0.8, "40"), (0.7, "60")],
+        );
+        assert!(markers.windows(2).all(|w| w[0].0 > w[1].0));
+        assert_eq!(markers.iter().filter(|(_, s)| s == "100").count(), 1);
+
+        // not broken if the break is out of the range
+        assert_eq!(
+            calc_broken_freq_axis_markers(
+                (200., 12000.),
+                FreqScale::Linear,
+                Some(freq_break),
+                8,
+                8,
+            ),
+            calc_freq_axis_markers((200., 12000.), FreqScale::Linear, 8, 8),
+        );
+
+        let hz_range = (0., 24000.);
+        for freq_scale in [FreqScale::Linear, FreqScale::Mel] {
+            assert_abs_diff_eq!(
+                freq_break.hz_to_relative_freq(100., hz_range, freq_scale),
+                0.5
+            );
+            for hz in [50., 100., 1000., 20000.] {
+                let rel_freq = freq_break.hz_to_relative_freq(hz, hz_range, freq_scale);
+                assert_abs_diff_eq!(
+                    freq_break.relative_freq_to_hz(rel_freq, hz_range, freq_scale),
+                    hz,
+                    epsilon = hz * 1e-4
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fit_axis_works() {
+        let metrics = LabelMetrics::default();
+        assert_eq!(metrics.label_width("0.5"), 21.);
+        let metrics_with_widths = LabelMetrics {
+            char_widths: HashMap::from([('.', 3.)]),
+            ..Default::default()
+        };
+        assert_eq!(metrics_with_widths.label_width("0.5"), 17.);
+
+        // 100 px/sec: labels every 0.5 sec with minor ticks every 0.1 sec
+        let markers = fit_time_axis_markers(0., 10., 1000., 10., None, &metrics);
+        assert_eq!(markers.len(), 101);
+        assert_eq!(markers.iter().filter(|(_, s)| !s.is_empty()).count(), 21);
+        assert_axis_eq(
+            &markers[..6],
+            &[
+                (0., "0"),
+                (0.01, ""),
+                (0.02, ""),
+                (0.03, ""),
+                (0.04, ""),
+                (0.05, "0.5"),
+            ],
+        );
+        assert_eq!(markers.last().unwrap().1, "ss.x");
+
+        let markers = fit_freq_axis_markers((0., 12000.), FreqScale::Linear, None, 100., &metrics);
+        let labels: Vec<_> = markers
+            .iter()
+            .filter(|(_, s)| !s.is_empty())
+            .map(|(_, s)| s.as_str())
+            .collect();
+        assert_eq!(labels, ["0", "3k", "6k", "9k", "12k"]);
+        assert_eq!(markers.len(), 13);
+        assert!(markers.windows(2).all(|w| w[0].0 > w[1].0));
+
+        let markers = fit_dB_axis_markers((-60., 0.), 200., &metrics);
+        assert!(labels_fit(&markers, 200., |_| 20.));
+        assert!(markers.iter().any(|(_, s)| s == "0"));
+        assert!(markers.iter().any(|(_, s)| s.is_empty()));
+    }
+
     #[test]
     #[allow(non_snake_case)]
     fn dB_axis_works() {
@@ -531,4 +1113,24 @@ mod tests {
             &vec![((-1.5 + 1.1) / (-2. + 1.1), "-1.5"), (1., "-2.0")],
         );
     }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn gain_axis_works() {
+        let assert_gain_axis_eq = |max_reduction_dB: f32, max_num_ticks, labels: &[&str]| {
+            let markers = calc_gain_axis_markers(max_reduction_dB, max_num_ticks);
+            assert_eq!(markers.len(), labels.len());
+            for ((pos, label), expected) in markers.iter().zip(labels) {
+                assert_eq!(label, expected);
+                let gain = 10f32.powf(expected.parse::<f32>().unwrap() / 20.);
+                assert_abs_diff_eq!(*pos, (1. - gain) * 2., epsilon = 1e-4);
+            }
+        };
+        assert_gain_axis_eq(0., 5, &["0"]);
+        assert_gain_axis_eq(-3., 4, &["0", "-1", "-2", "-3"]);
+        assert_gain_axis_eq(3., 4, &["0", "-1", "-2", "-3"]);
+        assert_gain_axis_eq(-0.25, 3, &["0", "-0.1", "-0.2"]);
+        // the lane is limited to about -6 dB
+        assert_gain_axis_eq(-20., 3, &["0", "-3", "-6"]);
+    }
 }
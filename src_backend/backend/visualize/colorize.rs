@@ -74,6 +74,8 @@ const COLORMAP_B: [f32; 256] = [
     162.0, 165.0,
 ];
 const GREY_TO_POS: f32 = COLORMAP_R.len() as f32 / (u16::MAX - 1) as f32;
+/// number of lanes of the min/max fold (auto-vectorized to SIMD min/max instructions)
+const MIN_MAX_LANES: usize = 16;
 
 #[inline]
 pub fn get_colormap_rgb() -> Vec<u8> {
@@ -83,6 +85,15 @@ pub fn get_colormap_rgb() -> Vec<u8> {
         .collect()
 }
 
+/// length (>= 2) RGB colors sampled evenly from the lowest to the highest grey,
+/// in the same way as the spectrogram images (so the last color is white).
+pub fn get_colormap_rgb_with_length(length: usize) -> Vec<u8> {
+    let step = (u16::MAX - 1) as f32 / (length - 1).max(1) as f32;
+    (0..length)
+        .flat_map(|i| map_grey_to_color((i as f32).mul_add(step, 1.).round() as u16))
+        .collect()
+}
+
 #[inline]
 fn interpolate<const L: usize>(color1: &[f32; L], color2: &[f32; L], ratio: f32) -> [u8; L] {
     let mut iter = color1.iter().zip(color2).map(|(&a, &b)| {
@@ -483,6 +494,41 @@ pub unsafe fn map_grey_to_color_iter_neon(grey: &[u16]) -> impl Iterator<Item =
         .chain(map_grey_to_color_iter_fallback(grey_fallback))
 }
 
+/// (min, max) of the grey values except 0 (outside of the frequency range).
+/// None if all values are 0.
+pub fn calc_grey_range<'a>(rows: impl IntoIterator<Item = &'a [u16]>) -> Option<(u16, u16)> {
+    // min of x - 1 (wrapping) excludes 0
+    let mut mins = [u16::MAX; MIN_MAX_LANES];
+    let mut maxs = [0u16; MIN_MAX_LANES];
+    for row in rows {
+        let chunks = row.chunks_exact(MIN_MAX_LANES);
+        let remainder = chunks.remainder();
+        for chunk in chunks {
+            for i in 0..MIN_MAX_LANES {
+                mins[i] = mins[i].min(chunk[i].wrapping_sub(1));
+                maxs[i] = maxs[i].max(chunk[i]);
+            }
+        }
+        for &x in remainder {
+            mins[0] = mins[0].min(x.wrapping_sub(1));
+            maxs[0] = maxs[0].max(x);
+        }
+    }
+    let max = maxs.into_iter().max().unwrap();
+    (max > 0).then(|| (mins.into_iter().min().unwrap() + 1, max))
+}
+
+/// Stretch the grey values in grey_range to the whole colormap (in-place). 0 is kept.
+pub fn stretch_grey_range(grey: &mut [u16], (min, max): (u16, u16)) {
+    let scale = (u16::MAX - 1) as f32 / max.saturating_sub(min).max(1) as f32;
+    grey.iter_mut().filter(|x| **x > 0).for_each(|x| {
+        *x = (*x as f32 - min as f32)
+            .mul_add(scale, 1.)
+            .clamp(1., u16::MAX as f32)
+            .round() as u16;
+    });
+}
+
 pub fn map_grey_to_color_iter(grey: &[u16]) -> Box<dyn Iterator<Item = u8> + '_> {
     #[cfg(target_arch = "x86_64")]
     {
@@ -544,6 +590,16 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn colormap_with_length_works() {
+        for length in [2, 100, 1000] {
+            let colormap = get_colormap_rgb_with_length(length);
+            assert_eq!(colormap.len(), length * 3);
+            assert_eq!(colormap[..3], map_grey_to_color(1));
+            assert_eq!(colormap[colormap.len() - 3..], WHITE);
+        }
+    }
+
     #[cfg(target_arch = "x86_64")]
     #[test]
     fn grey_to_color_work_with_avx2() {
@@ -649,4 +705,23 @@ mod tests {
             100. - sum_elapsed_neon.as_secs_f64() / sum_elapsed.as_secs_f64() * 100.
         );
     }
+
+    #[test]
+    fn grey_range_works() {
+        let grey: Vec<u16> = (0..100)
+            .map(|i| if i % 10 == 0 { 0 } else { i * 100 })
+            .collect();
+        assert_eq!(calc_grey_range(grey.chunks(33)), Some((100, 9900)));
+        assert_eq!(calc_grey_range([&[0u16; 40][..]]), None);
+        assert_eq!(
+            calc_grey_range([&[u16::MAX][..]]),
+            Some((u16::MAX, u16::MAX))
+        );
+
+        let mut stretched = vec![0, 100, 5000, 9900];
+        stretch_grey_range(&mut stretched, (100, 9900));
+        assert_eq!(stretched[..2], [0, 1]);
+        assert_eq!(stretched[3], u16::MAX);
+        assert!(stretched[2] > 5000);
+    }
 }
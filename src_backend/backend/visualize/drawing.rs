@@ -10,18 +10,68 @@ use tiny_skia::{
     FillRule, IntRect, Paint, PathBuilder, Pixmap, PixmapMut, PixmapPaint, PixmapRef, Transform,
 };
 
-use super::super::dynamics::{GuardClippingResult, MaxPeak};
-use super::super::track::TrackList;
+use super::super::dynamics::{DeciBel, GuardClippingResult, MaxPeak};
+use super::super::track::{AudioTrack, TrackList};
 use super::super::utils::Pad;
-use super::super::{IdChArr, IdChValueVec, TrackManager};
+use super::super::{IdCh, IdChArr, IdChValueVec, TrackManager};
 use super::colorize::*;
-use super::drawing_wav::{draw_limiter_gain_to, draw_wav_to};
+use super::drawing_wav::{draw_limiter_gain_to, draw_wav_envelope_to, draw_wav_to, RESAMPLE_TAIL};
 use super::img_slice::{ArrWithSliceInfo, CalcWidth, LeftWidth, OverviewHeights, PartGreyInfo};
-use super::params::{DrawOptionForWav, DrawParams, ImageKind};
+use super::params::{DrawOptionForWav, DrawParams, FreqAxisBreak, FreqInterpolation, ImageKind};
+use super::wav_pyramid::{mix_hash, WavPyramid};
 
+/// width of the segments of the overview drawn and cached independently
+pub const OVERVIEW_SEGMENT_WIDTH: u32 = 256;
 const OVERVIEW_MAX_CH: usize = 4;
 const OVERVIEW_CH_GAP_HEIGHT: f32 = 1.;
 const LIMITER_GAIN_HEIGHT_DENOM: usize = 5; // 1/5 of the height will be used for draw limiter gain
+/// limiter gain at the bottom of the upper gain lane (the top of the lower lane)
+pub(super) const MIN_LIMITER_GAIN_DRAWN: f32 = 0.5;
+
+/// vertical layout (px) of the overview image of a track
+#[derive(Clone, Debug, PartialEq)]
+pub struct OverviewLayout {
+    /// top of each channel drawn
+    pub ch_tops: Vec<usize>,
+    pub ch_height: usize,
+    /// height of the limiter gain lanes at the top and the bottom of each channel.
+    /// zero if the limiter gain is not drawn.
+    pub gain_lane_height: usize,
+    /// maximum gain reduction of the limiter (dB, zero or negative)
+    #[allow(non_snake_case)]
+    pub max_reduction_dB: f32,
+}
+
+/// layout of the overview image drawn by TrackDrawer::draw_overview with the same height and dpr
+pub fn calc_overview_layout(track: &AudioTrack, height: u32, dpr: f32) -> OverviewLayout {
+    let n_ch = track.n_ch().min(OVERVIEW_MAX_CH);
+    let heights = OverviewHeights::new(height, n_ch, OVERVIEW_CH_GAP_HEIGHT, dpr);
+    let min_gain = min_limiter_gain(track);
+    let gain_lane_height = if min_gain.is_some() {
+        heights.decompose_by_gain(LIMITER_GAIN_HEIGHT_DENOM).0
+    } else {
+        0
+    };
+    OverviewLayout {
+        ch_tops: (0..n_ch)
+            .map(|ch| heights.margin + ch * heights.ch_and_gap())
+            .collect(),
+        ch_height: heights.ch,
+        gain_lane_height,
+        max_reduction_dB: min_gain.map_or(0., |x| x.dB_from_amp_default()),
+    }
+}
+
+/// minimum gain of the limiter. None if the limiter gain is not drawn in the overview.
+fn min_limiter_gain(track: &AudioTrack) -> Option<f32> {
+    match track.guard_clip_result() {
+        GuardClippingResult::GainSequence(gain_seq) => {
+            let min_gain = gain_seq.iter().fold(1f32, |acc, &x| acc.min(x));
+            (min_gain < 1.).then_some(min_gain)
+        }
+        _ => None,
+    }
+}
 
 pub trait TrackDrawer {
     fn draw_entire_imgs(
@@ -74,13 +124,30 @@ impl TrackDrawer for TrackManager {
                 let width = track.calc_width(px_per_sec);
                 let shape = (height as usize, width as usize, 4);
                 let arr = match &kind {
-                    ImageKind::Spec => {
-                        let grey = if let Some(grey) = self.spec_greys.get(&(id, ch)) {
-                            grey.view()
-                        } else {
+                    ImageKind::Spec(hz_range, grey_range, freq_break) => {
+                        let Some(grey) = self.spec_grey_in(tracklist, (id, ch), *hz_range) else {
                             return out_for_not_exist();
                         };
-                        let vec = resize_colorize_grey(grey.into(), width, height, false, parallel);
+                        let split = freq_break.and_then(|freq_break| {
+                            calc_grey_split(
+                                self,
+                                tracklist,
+                                (id, ch),
+                                *hz_range,
+                                freq_break,
+                                height,
+                            )
+                        });
+                        let vec = resize_colorize_grey(
+                            grey.into(),
+                            width,
+                            height,
+                            *grey_range,
+                            split,
+                            self.freq_interpolation,
+                            false,
+                            parallel,
+                        );
                         Array3::from_shape_vec(shape, vec).unwrap()
                     }
                     ImageKind::Wav(opt_for_wav) => {
@@ -120,6 +187,9 @@ impl TrackDrawer for TrackManager {
             px_per_sec,
             ref opt_for_wav,
             blend,
+            hz_range,
+            grey_range,
+            freq_break,
         } = params;
         let fast_resize_vec = fast_resize_vec.into();
         let parallel = id_ch_tuples.len() < rayon::current_num_threads();
@@ -133,11 +203,14 @@ impl TrackDrawer for TrackManager {
                 } else {
                     return out_for_not_exist();
                 };
-                let spec_grey = if let Some(grey) = self.spec_greys.get(&(id, ch)) {
-                    grey
-                } else {
+                let Some(spec_grey) = self.spec_grey_in(tracklist, (id, ch), hz_range) else {
                     return out_for_not_exist();
                 };
+                let split = freq_break.and_then(|freq_break| {
+                    calc_grey_split(self, tracklist, (id, ch), hz_range, freq_break, height)
+                });
+                // start_sec is on the session timeline
+                let start_sec = track.timeline().session_to_track(start_sec);
                 let PartGreyInfo {
                     i_w_and_width,
                     start_sec_with_margin,
@@ -168,6 +241,9 @@ impl TrackDrawer for TrackManager {
                     height,
                     opt_for_wav,
                     blend,
+                    grey_range,
+                    split,
+                    self.freq_interpolation,
                     fast_resize_vec.as_ref().map_or(false, |v| v[i]),
                     show_clipping,
                     parallel,
@@ -210,123 +286,260 @@ impl TrackDrawer for TrackManager {
         } else {
             return Vec::new();
         };
-        let (pad_left, drawing_width, pad_right) =
-            track.decompose_width_of(0., width, width as f64 / tracklist.max_sec);
-        let (pad_left, drawing_width_usize, pad_right) = (
-            pad_left as usize,
-            drawing_width as usize,
-            pad_right as usize,
-        );
-        let n_ch = track.n_ch().min(OVERVIEW_MAX_CH);
-        let heights = OverviewHeights::new(height, n_ch, OVERVIEW_CH_GAP_HEIGHT, dpr);
-        let (clipped_peak, draw_gain_heights) = match track.guard_clip_result() {
-            GuardClippingResult::WavBeforeClip(before_clip) => {
-                (before_clip.max_peak(), Default::default())
+        draw_overview_of(track, width, height, dpr, width as f64 / tracklist.max_sec)
+    }
+}
+
+/// Draw the overview of the track with the time scale of px_per_sec
+/// (the right side is padded if the track is shorter than width).
+pub fn draw_overview_of(
+    track: &AudioTrack,
+    width: u32,
+    height: u32,
+    dpr: f32,
+    px_per_sec: f64,
+) -> Vec<u8> {
+    draw_overview_segment_of(track, width, height, dpr, px_per_sec, (0, width))
+}
+
+/// (clipped peak, heights of the limiter gain lane and the waveform) of the overview.
+/// The heights are zero if the limiter gain is not drawn.
+fn overview_amp_layout(track: &AudioTrack, heights: &OverviewHeights) -> (f32, (usize, usize)) {
+    match track.guard_clip_result() {
+        GuardClippingResult::WavBeforeClip(before_clip) => {
+            (before_clip.max_peak(), Default::default())
+        }
+        GuardClippingResult::GainSequence(_) if min_limiter_gain(track).is_some() => {
+            (1., heights.decompose_by_gain(LIMITER_GAIN_HEIGHT_DENOM))
+        }
+        _ => (1., Default::default()),
+    }
+}
+
+/// sample range of the columns px_start..px_end of the overview where drawing_width columns
+/// show len samples (at least one sample)
+fn overview_sample_range(
+    len: usize,
+    drawing_width: u32,
+    (px_start, px_end): (u32, u32),
+) -> (usize, usize) {
+    let px_to_sample = |px: u32| {
+        (px.min(drawing_width) as f64 * len as f64 / drawing_width as f64).round() as usize
+    };
+    let i_start = px_to_sample(px_start).min(len.saturating_sub(1));
+    let i_end = px_to_sample(px_end).max(i_start + 1).min(len);
+    (i_start, i_end)
+}
+
+/// Hash of the data drawn in each segment of OVERVIEW_SEGMENT_WIDTH columns of the overview
+/// (see draw_overview_segment_of). A segment needs to be redrawn only if its hash changed.
+pub fn calc_overview_segment_hashes(
+    track: &AudioTrack,
+    width: u32,
+    height: u32,
+    dpr: f32,
+    px_per_sec: f64,
+) -> Vec<u64> {
+    let (_, drawing_width, _) = track.decompose_width_of(0., width, px_per_sec);
+    let n_ch = track.n_ch().min(OVERVIEW_MAX_CH);
+    let heights = OverviewHeights::new(height, n_ch, OVERVIEW_CH_GAP_HEIGHT, dpr);
+    let (clipped_peak, draw_gain_heights) = overview_amp_layout(track, &heights);
+    let layout_hash = mix_hash(
+        mix_hash(n_ch as u64, clipped_peak.to_bits() as u64),
+        draw_gain_heights.0 as u64,
+    );
+    let len = track.wav_for_overview().shape()[1];
+    // the envelope of a column covers its neighbors by the context size
+    let margin_px = dpr.ceil() as u32 + 1;
+    (0..width.div_ceil(OVERVIEW_SEGMENT_WIDTH))
+        .map(|i_seg| {
+            let px_start = i_seg * OVERVIEW_SEGMENT_WIDTH;
+            if px_start >= drawing_width {
+                return layout_hash;
             }
-            GuardClippingResult::GainSequence(gain_seq) if gain_seq.iter().any(|&x| x < 1.) => {
-                (1., heights.decompose_by_gain(LIMITER_GAIN_HEIGHT_DENOM))
+            let px_end = (px_start + OVERVIEW_SEGMENT_WIDTH).min(width);
+            let (i_start, i_end) = overview_sample_range(
+                len,
+                drawing_width,
+                (px_start.saturating_sub(margin_px), px_end + margin_px),
+            );
+            let i_end = i_end + RESAMPLE_TAIL;
+            let hash = (0..n_ch).fold(layout_hash, |hash, ch| {
+                let pyramid = track.overview_pyramid(ch);
+                mix_hash(hash, pyramid.hashes().hash_range(i_start, i_end))
+            });
+            if draw_gain_heights != Default::default() {
+                let gain_hashes = track.limiter_gain_hashes();
+                mix_hash(hash, gain_hashes.hash_range(i_start, i_end))
+            } else {
+                hash
             }
-            _ => (1., Default::default()),
-        };
+        })
+        .collect()
+}
 
-        let mut arr = Array3::zeros((heights.total, drawing_width_usize, 4));
-        arr.slice_mut(s![heights.margin.., .., ..])
-            .axis_chunks_iter_mut(Axis(0), heights.ch_and_gap())
-            .into_par_iter()
-            .enumerate()
-            .for_each(|(ch, mut arr_ch)| {
-                let mut draw_wav = |i_h, h| {
-                    draw_wav_to(
+/// Draw the columns px_start..px_end of the overview of width (see draw_overview_of).
+pub fn draw_overview_segment_of(
+    track: &AudioTrack,
+    width: u32,
+    height: u32,
+    dpr: f32,
+    px_per_sec: f64,
+    (px_start, px_end): (u32, u32),
+) -> Vec<u8> {
+    let (_, total_drawing_width, _) = track.decompose_width_of(0., width, px_per_sec);
+    let n_ch = track.n_ch().min(OVERVIEW_MAX_CH);
+    let heights = OverviewHeights::new(height, n_ch, OVERVIEW_CH_GAP_HEIGHT, dpr);
+    let segment_width = (px_end - px_start) as usize;
+    let drawing_width = px_end.min(total_drawing_width).saturating_sub(px_start);
+    let drawing_width_usize = drawing_width as usize;
+    if drawing_width == 0 {
+        return vec![0; heights.total * segment_width * 4];
+    }
+    let (clipped_peak, draw_gain_heights) = overview_amp_layout(track, &heights);
+    let sample_range = |len: usize| {
+        overview_sample_range(
+            len,
+            total_drawing_width,
+            (px_start, px_start + drawing_width),
+        )
+    };
+
+    // long waveforms are drawn from the decimated min/max not to scan all samples
+    let draw_wav_or_envelope = |output: &mut [u8],
+                                wav: ArrayView1<f32>,
+                                pyramid: &WavPyramid,
+                                height: u32,
+                                opt_for_wav: &DrawOptionForWav,
+                                show_clipping: bool| {
+        let (i_start, i_end) = sample_range(wav.len());
+        match pyramid.level_for(wav.len() as f64 / total_drawing_width as f64) {
+            Some(level) => draw_wav_envelope_to(
+                output,
+                level,
+                (i_start, i_end),
+                drawing_width,
+                height,
+                opt_for_wav,
+                show_clipping,
+                false,
+            ),
+            None => draw_wav_to(
+                output,
+                ArrWithSliceInfo::new(wav, (i_start as isize, i_end - i_start)),
+                drawing_width,
+                height,
+                opt_for_wav,
+                show_clipping,
+                false,
+            ),
+        }
+    };
+    let mut arr = Array3::zeros((heights.total, drawing_width_usize, 4));
+    arr.slice_mut(s![heights.margin.., .., ..])
+        .axis_chunks_iter_mut(Axis(0), heights.ch_and_gap())
+        .into_par_iter()
+        .enumerate()
+        .for_each(|(ch, mut arr_ch)| {
+            let mut draw_wav = |i_h, h| {
+                draw_wav_or_envelope(
+                    arr_ch
+                        .slice_mut(s![i_h..(i_h + h), .., ..])
+                        .as_slice_mut()
+                        .unwrap(),
+                    track.channel(ch),
+                    track.overview_pyramid(ch),
+                    h as u32,
+                    &DrawOptionForWav::with_dpr(dpr),
+                    false,
+                )
+            };
+            match track.guard_clip_result() {
+                GuardClippingResult::WavBeforeClip(before_clip) if clipped_peak > 1. => {
+                    draw_wav_or_envelope(
                         arr_ch
-                            .slice_mut(s![i_h..(i_h + h), .., ..])
+                            .slice_mut(s![..heights.ch, .., ..])
                             .as_slice_mut()
                             .unwrap(),
-                        track.channel(ch).into(),
-                        drawing_width,
-                        h as u32,
-                        &DrawOptionForWav::with_dpr(dpr),
-                        false,
-                        false,
+                        before_clip.slice(s![ch, ..]),
+                        track.overview_pyramid(ch),
+                        heights.ch as u32,
+                        &DrawOptionForWav {
+                            amp_range: (-clipped_peak, clipped_peak),
+                            dpr,
+                        },
+                        true,
                     )
-                };
-                match track.guard_clip_result() {
-                    GuardClippingResult::WavBeforeClip(before_clip) if clipped_peak > 1. => {
-                        draw_wav_to(
+                }
+                GuardClippingResult::GainSequence(gain_seq)
+                    if draw_gain_heights != Default::default() =>
+                {
+                    let (gain_h, wav_h) = draw_gain_heights;
+                    draw_wav(gain_h, wav_h);
+                    if ch > 0 {
+                        return;
+                    }
+                    let (i_start, i_end) = sample_range(gain_seq.shape()[1]);
+                    let gain_seq = gain_seq.slice(s![0, i_start..i_end]);
+                    let neg_gain_seq = gain_seq.neg();
+                    let mut draw_gain = |i_h, gain: ArrayView1<f32>, amp_range, draw_bottom| {
+                        draw_limiter_gain_to(
                             arr_ch
-                                .slice_mut(s![..heights.ch, .., ..])
+                                .slice_mut(s![i_h..(i_h + gain_h), .., ..])
                                 .as_slice_mut()
                                 .unwrap(),
-                            before_clip.slice(s![ch, ..]).into(),
+                            gain,
                             drawing_width,
-                            heights.ch as u32,
-                            &DrawOptionForWav {
-                                amp_range: (-clipped_peak, clipped_peak),
-                                dpr,
-                            },
-                            true,
-                            false,
-                        )
-                    }
-                    GuardClippingResult::GainSequence(gain_seq)
-                        if draw_gain_heights != Default::default() =>
-                    {
-                        let (gain_h, wav_h) = draw_gain_heights;
-                        draw_wav(gain_h, wav_h);
-                        if ch > 0 {
-                            return;
-                        }
-                        let gain_seq = gain_seq.slice(s![0, ..]);
-                        let neg_gain_seq = gain_seq.neg();
-                        let mut draw_gain = |i_h, gain: ArrayView1<f32>, amp_range, draw_bottom| {
-                            draw_limiter_gain_to(
-                                arr_ch
-                                    .slice_mut(s![i_h..(i_h + gain_h), .., ..])
-                                    .as_slice_mut()
-                                    .unwrap(),
-                                gain,
-                                drawing_width,
-                                gain_h as u32,
-                                &DrawOptionForWav { amp_range, dpr },
-                                draw_bottom,
-                            );
-                        };
-                        draw_gain(0, gain_seq, (0.5, 1.), true);
-                        draw_gain(gain_h + wav_h, neg_gain_seq.view(), (-1., -0.5), false);
-                    }
-                    _ => {
-                        draw_wav(0, heights.ch);
-                    }
+                            gain_h as u32,
+                            &DrawOptionForWav { amp_range, dpr },
+                            draw_bottom,
+                        );
+                    };
+                    draw_gain(0, gain_seq, (MIN_LIMITER_GAIN_DRAWN, 1.), true);
+                    draw_gain(
+                        gain_h + wav_h,
+                        neg_gain_seq.view(),
+                        (-1., -MIN_LIMITER_GAIN_DRAWN),
+                        false,
+                    );
                 }
-            });
+                _ => {
+                    draw_wav(0, heights.ch);
+                }
+            }
+        });
 
-        if draw_gain_heights != Default::default() {
-            let (gain_h, wav_h) = draw_gain_heights;
-            let gain_upper = arr
-                .slice(s![heights.margin.., .., ..])
-                .slice(s![..gain_h, .., ..])
-                .to_owned();
-            let gain_lower = arr
-                .slice(s![heights.margin.., .., ..])
-                .slice(s![(gain_h + wav_h)..heights.ch, .., ..])
-                .to_owned();
-
-            arr.slice_mut(s![heights.margin.., .., ..])
-                .axis_chunks_iter_mut(Axis(0), heights.ch_and_gap())
-                .into_par_iter()
-                .enumerate()
-                .filter(|(ch, _)| *ch > 0)
-                .for_each(|(_, mut arr_ch)| {
-                    arr_ch.slice_mut(s![..gain_h, .., ..]).assign(&gain_upper);
-                    arr_ch
-                        .slice_mut(s![(gain_h + wav_h)..heights.ch, .., ..])
-                        .assign(&gain_lower);
-                });
-        }
-        if width != drawing_width {
-            arr = arr.pad((pad_left, pad_right), Axis(1), Default::default());
-        }
-        arr.into_raw_vec_and_offset().0
+    if draw_gain_heights != Default::default() {
+        let (gain_h, wav_h) = draw_gain_heights;
+        let gain_upper = arr
+            .slice(s![heights.margin.., .., ..])
+            .slice(s![..gain_h, .., ..])
+            .to_owned();
+        let gain_lower = arr
+            .slice(s![heights.margin.., .., ..])
+            .slice(s![(gain_h + wav_h)..heights.ch, .., ..])
+            .to_owned();
+
+        arr.slice_mut(s![heights.margin.., .., ..])
+            .axis_chunks_iter_mut(Axis(0), heights.ch_and_gap())
+            .into_par_iter()
+            .enumerate()
+            .filter(|(ch, _)| *ch > 0)
+            .for_each(|(_, mut arr_ch)| {
+                arr_ch.slice_mut(s![..gain_h, .., ..]).assign(&gain_upper);
+                arr_ch
+                    .slice_mut(s![(gain_h + wav_h)..heights.ch, .., ..])
+                    .assign(&gain_lower);
+            });
+    }
+    if segment_width != drawing_width_usize {
+        arr = arr.pad(
+            (0, segment_width - drawing_width_usize),
+            Axis(1),
+            Default::default(),
+        );
     }
+    arr.into_raw_vec_and_offset().0
 }
 
 #[allow(non_snake_case)]
@@ -334,8 +547,10 @@ pub fn convert_spec_to_grey(
     spec: ArrayView2<f32>,
     i_freq_range: (usize, usize),
     dB_range: (f32, f32),
+    tilt_dB: Option<&[f32]>,
 ) -> Array2<pixels::U16> {
     // spec: T x F
+    // tilt_dB: F (added to spec for display)
     // return: grey image with F(inverted) x T
     let (i_freq_start, i_freq_end) = i_freq_range;
     let dB_span = dB_range.1 - dB_range.0;
@@ -344,8 +559,9 @@ pub fn convert_spec_to_grey(
     Array2::from_shape_fn((height, width), |(i, j)| {
         let i_freq = i_freq_start + height - 1 - i;
         if i_freq < spec.raw_dim()[1] {
+            let dB = spec[[j, i_freq]] + tilt_dB.map_or(0., |tilt| tilt[i_freq]);
             pixels::U16::new(
-                (((spec[[j, i_freq]] - dB_range.0) / dB_span).mul_add((u16::MAX - 1) as f32, 1.))
+                (((dB - dB_range.0) / dB_span).mul_add((u16::MAX - 1) as f32, 1.))
                     .clamp(1., u16::MAX as f32)
                     .round() as u16,
             )
@@ -408,50 +624,166 @@ fn blend_wav_img_to(
     pixmap.draw_pixmap(0, 0, wav_pixmap, &paint, Transform::identity(), None);
 }
 
-fn resize_colorize_grey(
+/// resize (cropped horizontally) src_image to width x height, and return the buffer of U16
+fn resize_u16_image(
+    resizer: &mut Resizer,
+    src_image: &TypedImageRef<pixels::U16>,
+    (crop_left, crop_width): (f64, f64),
+    width: u32,
+    height: u32,
+    filter: FilterType,
+) -> Vec<u8> {
+    let resize_opt = ResizeOptions::new()
+        .crop(crop_left, 0., crop_width, src_image.height() as f64)
+        .resize_alg(ResizeAlg::Convolution(filter));
+    let mut dst_buf = vec![0; width as usize * height as usize * 2];
+    let mut dst_image =
+        TypedImage::<pixels::U16>::from_buffer(width, height, &mut dst_buf).unwrap();
+    resizer
+        .resize_typed(src_image, &mut dst_image, &resize_opt)
+        .unwrap();
+    dst_buf
+}
+
+/// Resize the grey image (cropped horizontally by the slice info) and return the buffer of U16.
+/// If the filter of freq_interpolation differs from that of the time axis,
+/// the frequency (vertical) axis is resized in a separate pass (regardless of fast_resize).
+fn resize_grey(
     grey: ArrWithSliceInfo<pixels::U16, Ix2>,
     width: u32,
     height: u32,
+    freq_interpolation: FreqInterpolation,
     fast_resize: bool,
-    parallel: bool,
 ) -> Vec<u8> {
     thread_local! {
         static RESIZER: RefCell<Resizer> = RefCell::new(Resizer::new());
     }
 
-    // let start = Instant::now();
     let (grey, trim_left, trim_width) = (grey.arr, grey.index, grey.length);
-    let resized_buf = RESIZER.with_borrow_mut(|resizer| {
-        let src_image = TypedImageRef::new(
-            grey.shape()[1] as u32,
-            grey.shape()[0] as u32,
-            grey.as_slice().unwrap(),
-        )
-        .unwrap();
-        let resize_opt = ResizeOptions::new()
-            .crop(
-                trim_left as f64,
-                0.,
-                trim_width as f64,
-                src_image.height() as f64,
+    let src_height = grey.shape()[0] as u32;
+    let time_filter = if fast_resize {
+        FilterType::Bilinear
+    } else {
+        FilterType::Lanczos3
+    };
+    // None if the time filter can be used for the frequency axis too
+    let freq_filter = match freq_interpolation {
+        FreqInterpolation::BandLimited if fast_resize => Some(FilterType::Lanczos3),
+        FreqInterpolation::BandLimited => None,
+        // box filter averages the bins in each pixel when downsampling,
+        // but it is nearest-neighbor when upsampling
+        FreqInterpolation::BinAverage if src_height > height => Some(FilterType::Box),
+        FreqInterpolation::BinAverage if fast_resize => None,
+        FreqInterpolation::BinAverage => Some(FilterType::Bilinear),
+    };
+    let crop = (trim_left as f64, trim_width as f64);
+    RESIZER.with_borrow_mut(|resizer| {
+        let src_image =
+            TypedImageRef::new(grey.shape()[1] as u32, src_height, grey.as_slice().unwrap())
+                .unwrap();
+        let Some(freq_filter) = freq_filter.filter(|_| src_height != height) else {
+            return resize_u16_image(resizer, &src_image, crop, width, height, time_filter);
+        };
+        // time axis first (keeping the frequency bins), then the frequency axis
+        let time_resized =
+            resize_u16_image(resizer, &src_image, crop, width, src_height, time_filter);
+        let time_resized_image = TypedImageRef::new(width, src_height, unsafe {
+            std::slice::from_raw_parts(
+                time_resized.as_ptr() as *const pixels::U16,
+                time_resized.len() / 2,
             )
-            .resize_alg(ResizeAlg::Convolution(if fast_resize {
-                FilterType::Bilinear
-            } else {
-                FilterType::Lanczos3
-            }));
-
-        let mut dst_buf = vec![0; width as usize * height as usize * 2];
-        let mut dst_image =
-            TypedImage::<pixels::U16>::from_buffer(width, height, &mut dst_buf).unwrap();
-        resizer
-            .resize_typed(&src_image, &mut dst_image, &resize_opt)
-            .unwrap();
-        dst_buf
-    });
+        })
+        .unwrap();
+        resize_u16_image(
+            resizer,
+            &time_resized_image,
+            (0., width as f64),
+            width,
+            height,
+            freq_filter,
+        )
+    })
+}
+
+/// (number of the rows of the grey above the break, height (px) above the break)
+/// if the spectrogram image of the track is split by freq_break
+fn calc_grey_split(
+    tm: &TrackManager,
+    tracklist: &TrackList,
+    (id, ch): IdCh,
+    hz_range: Option<(f32, f32)>,
+    freq_break: FreqAxisBreak,
+    height: u32,
+) -> Option<(usize, u32)> {
+    let hz_range = tm.track_hz_range_in(hz_range, tracklist.get(id)?.sr());
+    if !freq_break.is_inside(hz_range) {
+        return None;
+    }
+    let grey_above = tm.spec_grey_in(
+        tracklist,
+        (id, ch),
+        Some((freq_break.hz as f32, hz_range.1)),
+    )?;
+    let height_above = ((1. - freq_break.ratio) * height as f64).round() as u32;
+    Some((grey_above.shape()[0], height_above))
+}
+
+/// resize_grey for each segment of the frequency axis split by
+/// split: (number of the rows of the grey above the break, height (px) above the break)
+fn resize_split_grey(
+    grey: ArrWithSliceInfo<pixels::U16, Ix2>,
+    width: u32,
+    height: u32,
+    split: Option<(usize, u32)>,
+    freq_interpolation: FreqInterpolation,
+    fast_resize: bool,
+) -> Vec<u8> {
+    let n_rows = grey.arr.shape()[0];
+    let Some((n_rows_above, height_above)) = split.filter(|&(n_rows_above, height_above)| {
+        (1..n_rows).contains(&n_rows_above) && (1..height).contains(&height_above)
+    }) else {
+        return resize_grey(grey, width, height, freq_interpolation, fast_resize);
+    };
+    let slice_info = (grey.index, grey.length);
+    // the first row of the grey is the highest frequency
+    let (above, below) = grey.arr.split_at(Axis(0), n_rows_above);
+    let mut resized_buf = resize_grey(
+        ArrWithSliceInfo::new(above, slice_info),
+        width,
+        height_above,
+        freq_interpolation,
+        fast_resize,
+    );
+    resized_buf.extend(resize_grey(
+        ArrWithSliceInfo::new(below, slice_info),
+        width,
+        height - height_above,
+        freq_interpolation,
+        fast_resize,
+    ));
+    resized_buf
+}
+
+#[allow(clippy::too_many_arguments)]
+fn resize_colorize_grey(
+    grey: ArrWithSliceInfo<pixels::U16, Ix2>,
+    width: u32,
+    height: u32,
+    grey_range: Option<(u16, u16)>,
+    split: Option<(usize, u32)>,
+    freq_interpolation: FreqInterpolation,
+    fast_resize: bool,
+    parallel: bool,
+) -> Vec<u8> {
+    // let start = Instant::now();
+    let mut resized_buf =
+        resize_split_grey(grey, width, height, split, freq_interpolation, fast_resize);
     let resized = unsafe {
-        std::slice::from_raw_parts(resized_buf.as_ptr() as *const u16, resized_buf.len() / 2)
+        std::slice::from_raw_parts_mut(resized_buf.as_mut_ptr() as *mut u16, resized_buf.len() / 2)
     };
+    if let Some(grey_range) = grey_range {
+        stretch_grey_range(resized, grey_range);
+    }
 
     if parallel {
         resized
@@ -465,6 +797,7 @@ fn resize_colorize_grey(
 }
 
 /// blend can be < 0 for not drawing spec
+#[allow(clippy::too_many_arguments)]
 fn draw_blended_spec_wav(
     spec_grey: ArrWithSliceInfo<pixels::U16, Ix2>,
     wav: ArrWithSliceInfo<f32, Ix1>,
@@ -472,6 +805,9 @@ fn draw_blended_spec_wav(
     height: u32,
     opt_for_wav: &DrawOptionForWav,
     blend: f64,
+    grey_range: Option<(u16, u16)>,
+    split: Option<(usize, u32)>,
+    freq_interpolation: FreqInterpolation,
     fast_resize: bool,
     show_clipping: bool,
     parallel: bool,
@@ -481,7 +817,16 @@ fn draw_blended_spec_wav(
         return vec![0u8; height as usize * width as usize * 4];
     }
     let mut result = if blend > 0. {
-        resize_colorize_grey(spec_grey, width, height, fast_resize, parallel)
+        resize_colorize_grey(
+            spec_grey,
+            width,
+            height,
+            grey_range,
+            split,
+            freq_interpolation,
+            fast_resize,
+            parallel,
+        )
     } else {
         vec![0u8; height as usize * width as usize * 4]
     };
@@ -504,3 +849,96 @@ fn draw_blended_spec_wav(
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ndarray_stats::QuantileExt;
+
+    fn resize_column(
+        column: &[u16],
+        height: u32,
+        freq_interpolation: FreqInterpolation,
+        fast_resize: bool,
+    ) -> Vec<u16> {
+        let grey = Array2::from_shape_fn((column.len(), 4), |(i, _)| pixels::U16::new(column[i]));
+        let buf = resize_grey(
+            ArrWithSliceInfo::new(grey.view(), (0, 4)),
+            2,
+            height,
+            freq_interpolation,
+            fast_resize,
+        );
+        buf.chunks_exact(4)
+            .map(|px| u16::from_ne_bytes([px[0], px[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn bin_average_works() {
+        // 4 bins per pixel
+        let column: Vec<u16> = (0..32)
+            .map(|i| if i % 2 == 0 { 1000 } else { 3000 })
+            .collect();
+        let resized = resize_column(&column, 8, FreqInterpolation::BinAverage, false);
+        assert!(
+            resized.iter().all(|&x| x.abs_diff(2000) <= 1),
+            "{:?}",
+            resized
+        );
+        // Lanczos doesn't average the bins exactly but is still band-limited
+        let resized = resize_column(&column, 8, FreqInterpolation::BandLimited, false);
+        // (pixels away from the edges)
+        assert!(
+            resized[3..5].iter().all(|&x| x.abs_diff(2000) <= 100),
+            "{:?}",
+            resized
+        );
+    }
+
+    #[test]
+    fn overview_segments_work() {
+        let track = AudioTrack::new("samples/sample_48k.wav".into()).unwrap();
+        let (width, height) = (600, 40);
+        // the track fills the left half of the overview
+        let px_per_sec = width as f64 / track.sec() / 2.;
+        let hashes = calc_overview_segment_hashes(&track, width, height, 1., px_per_sec);
+        assert_eq!(hashes.len(), 3);
+        assert_eq!(
+            hashes,
+            calc_overview_segment_hashes(&track, width, height, 1., px_per_sec)
+        );
+
+        let segment = draw_overview_segment_of(&track, width, height, 1., px_per_sec, (0, 256));
+        assert_eq!(segment.len(), 40 * 256 * 4);
+        assert!(segment.iter().any(|&x| x > 0));
+        // the segment out of the track is blank
+        let blank = draw_overview_segment_of(&track, width, height, 1., px_per_sec, (512, 600));
+        assert_eq!(blank.len(), 40 * 88 * 4);
+        assert!(blank.iter().all(|&x| x == 0));
+
+        // all the segments showing the track change with the time scale
+        let zoomed = calc_overview_segment_hashes(&track, width, height, 1., px_per_sec * 2.);
+        assert!(hashes.iter().zip(&zoomed).take(2).all(|(a, b)| a != b));
+    }
+
+    #[test]
+    fn freq_upsampling_is_smooth() {
+        // a few low bins stretched to many pixels
+        let column = [1000, 1000, 30000, 1000, 1000];
+        for freq_interpolation in [
+            FreqInterpolation::BandLimited,
+            FreqInterpolation::BinAverage,
+        ] {
+            for fast_resize in [false, true] {
+                let resized = resize_column(&column, 100, freq_interpolation, fast_resize);
+                // no nearest-neighbor plateaus around the peak
+                let i_peak = Array1::from_vec(resized.clone()).argmax().unwrap();
+                assert!((45..55).contains(&i_peak), "{:?}", resized);
+                let n_plateau = resized[30..70].windows(2).filter(|x| x[0] == x[1]).count();
+                assert!(n_plateau < 5, "{:?}", resized);
+            }
+        }
+    }
+}
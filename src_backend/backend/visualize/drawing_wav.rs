@@ -10,12 +10,13 @@ use tiny_skia::{
 use super::img_slice::ArrWithSliceInfo;
 use super::params::DrawOptionForWav;
 use super::resample::FftResampler;
+use super::wav_pyramid::PyramidLevel;
 
 const WAV_COLOR: [u8; 3] = [19, 137, 235];
 const LIMITER_GAIN_COLOR: [u8; 3] = [218, 151, 46];
 const CLIPPING_COLOR: [u8; 3] = [196, 34, 50];
 
-const RESAMPLE_TAIL: usize = 500;
+pub(super) const RESAMPLE_TAIL: usize = 500;
 const THR_TOPBOTTOM_PERCENT: usize = 70;
 
 const WAV_STROKE_BORDER_WIDTH: f32 = 1.5; // this doesn't depend on dpr
@@ -167,6 +168,135 @@ pub fn draw_wav_to(
     // println!("drawing wav: {:?}", start.elapsed());
 }
 
+/// Draw the min/max envelope of the samples in i_start..i_end of a long waveform
+/// from a level of its pyramid. The samples around the range are used as the context.
+/// Unlike draw_wav_to, no sample of the waveform is touched.
+pub fn draw_wav_envelope_to(
+    output: &mut [u8],
+    level: &PyramidLevel,
+    (i_start, i_end): (usize, usize),
+    width: u32,
+    height: u32,
+    opt_for_wav: &DrawOptionForWav,
+    show_clipping: bool,
+    need_border: bool,
+) {
+    let &DrawOptionForWav { amp_range, dpr } = opt_for_wav;
+    let DprDependentConstants {
+        topbottom_context_size,
+        wav_stroke_width,
+        ..
+    } = DprDependentConstants::calc(dpr);
+    let amp_to_px = get_amp_to_px_fn(amp_range, height as f32);
+    let samples_per_px = (i_end - i_start) as f32 / width as f32;
+    let half_context_size = topbottom_context_size / 2.;
+    let clip_values = (show_clipping && (amp_range.0 < -1. || amp_range.1 > 1.))
+        .then_some((amp_to_px(-1.), amp_to_px(1.)));
+
+    let (top_envlop, btm_envlop): (Vec<_>, Vec<_>) = (0..width)
+        .map(|i_px| {
+            let i_px = i_px as f32;
+            let i_ctx_start = (i_start as f32 + (i_px - half_context_size) * samples_per_px)
+                .round()
+                .max(0.) as usize;
+            let i_ctx_end =
+                (i_start as f32 + (i_px + half_context_size) * samples_per_px).round() as usize;
+            let (min, max) = level.min_max(i_ctx_start, i_ctx_end);
+            (
+                amp_to_px(max) - wav_stroke_width / 2.,
+                amp_to_px(min) + wav_stroke_width / 2.,
+            )
+        })
+        .unzip();
+
+    let mut out_arr =
+        ArrayViewMut3::from_shape((height as usize, width as usize, 4), output).unwrap();
+    let mut pixmap =
+        PixmapMutWrapper::from_bytes(out_arr.as_slice_mut().unwrap(), width, height).unwrap();
+    fill_topbottom_envelope_with_clipping_to(
+        &mut pixmap,
+        &mut top_envlop.into_iter(),
+        &mut btm_envlop.into_iter(),
+        width as usize,
+        clip_values,
+        need_border,
+    );
+}
+
+/// class of a pixel column of the waveform classified by classify_wav_pixels
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum WavPixelClass {
+    #[default]
+    Normal = 0,
+    /// the gain of the samples is reduced by the limiter
+    Limited = 1,
+    /// the samples before guard clipping exceed the full scale
+    Clipped = 2,
+}
+
+/// Classify each of the width pixel columns of the waveform slice from its min/max envelope
+/// so that the renderer can color the problem samples without scanning them again.
+/// gain: limiter gain of the same range. Clipping takes precedence over limiting.
+pub fn classify_wav_pixels(
+    wav: ArrayView1<f32>,
+    gain: Option<ArrayView1<f32>>,
+    width: u32,
+) -> Vec<WavPixelClass> {
+    let samples_per_px = wav.len() as f64 / width as f64;
+    (0..width as usize)
+        .map(|i_px| {
+            let (i_start, i_end) = pixel_sample_range(i_px, samples_per_px, wav.len());
+            let wav_slice = wav.slice(s![i_start..i_end]);
+            let max_abs = wav_slice.iter().fold(0f32, |acc, x| acc.max(x.abs()));
+            let limited = gain.is_some_and(|gain| {
+                let i_end = i_end.min(gain.len());
+                gain.slice(s![i_start.min(i_end)..i_end])
+                    .iter()
+                    .any(|&x| x < 1.)
+            });
+            if max_abs > 1. {
+                WavPixelClass::Clipped
+            } else if limited {
+                WavPixelClass::Limited
+            } else {
+                WavPixelClass::Normal
+            }
+        })
+        .collect()
+}
+
+/// (min, max) of each of the width pixel columns of the waveform slice,
+/// with the same pixel boundaries as classify_wav_pixels. (0, 0) for the empty columns.
+pub fn calc_wav_pixel_envelope(wav: ArrayView1<f32>, width: u32) -> (Vec<f32>, Vec<f32>) {
+    let samples_per_px = wav.len() as f64 / width as f64;
+    (0..width as usize)
+        .map(|i_px| {
+            let (i_start, i_end) = pixel_sample_range(i_px, samples_per_px, wav.len());
+            let wav_slice = wav.slice(s![i_start..i_end]);
+            if wav_slice.is_empty() {
+                return (0., 0.);
+            }
+            wav_slice
+                .iter()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &x| {
+                    (min.min(x), max.max(x))
+                })
+        })
+        .unzip()
+}
+
+/// sample range of the pixel column. Adjacent columns overlap by one sample at most
+/// so that the columns narrower than a sample are not empty.
+#[inline]
+fn pixel_sample_range(i_px: usize, samples_per_px: f64, len: usize) -> (usize, usize) {
+    let i_start = ((i_px as f64 * samples_per_px).floor() as usize).min(len);
+    let i_end = (((i_px + 1) as f64 * samples_per_px).ceil() as usize)
+        .max(i_start + 1)
+        .min(len);
+    (i_start, i_end)
+}
+
 pub fn draw_limiter_gain_to(
     output: &mut [u8],
     gain: ArrayView1<f32>,
@@ -398,3 +528,44 @@ fn get_wav_paint(color: &[u8; 3]) -> Paint {
 fn create_resampler(input_size: usize, output_size: usize) -> FftResampler<f32> {
     FftResampler::new(input_size, output_size)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_wav_pixels_works() {
+        let wav = arr1(&[0.5, 1.5, 0.2, 0.1, 0.3, 0.2, -0.1, 0.]);
+        let gain = arr1(&[1., 1., 1., 1., 0.8, 1., 1., 1.]);
+        assert_eq!(
+            classify_wav_pixels(wav.view(), Some(gain.view()), 4),
+            [
+                WavPixelClass::Clipped,
+                WavPixelClass::Normal,
+                WavPixelClass::Limited,
+                WavPixelClass::Normal,
+            ]
+        );
+        assert_eq!(
+            classify_wav_pixels(wav.view(), None, 2),
+            [WavPixelClass::Clipped, WavPixelClass::Normal]
+        );
+        assert_eq!(
+            classify_wav_pixels(wav.slice(s![..0]), None, 2),
+            [WavPixelClass::Normal; 2]
+        );
+    }
+
+    #[test]
+    fn calc_wav_pixel_envelope_works() {
+        let wav = arr1(&[0.5, 1.5, 0.2, 0.1, 0.3, 0.2, -0.1, 0.]);
+        assert_eq!(
+            calc_wav_pixel_envelope(wav.view(), 2),
+            (vec![0.1, -0.1], vec![1.5, 0.3])
+        );
+        assert_eq!(
+            calc_wav_pixel_envelope(wav.slice(s![..0]), 2),
+            (vec![0.; 2], vec![0.; 2])
+        );
+    }
+}
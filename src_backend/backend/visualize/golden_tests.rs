@@ -0,0 +1,183 @@
+//! Golden-image regression tests of the spectrogram, waveform and overview images.
+//! The images of the bundled samples drawn at fixed settings are compared against
+//! samples/golden/*.png with a perceptual diff, so that the drawing can be refactored safely.
+//! Run with THESIA_BLESS_GOLDEN=1 to (re)write the golden images after an intended change.
+//! The image tests are ignored until the golden images are blessed on the reference platform:
+//! `THESIA_BLESS_GOLDEN=1 cargo test golden -- --ignored`, then commit samples/golden/*.png
+//! and remove the ignore attributes.
+
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+use ndarray::prelude::*;
+
+use super::super::track::TrackList;
+use super::super::TrackManager;
+use super::drawing::{draw_overview_of, TrackDrawer};
+use super::params::{DrawOptionForWav, ImageKind};
+
+const GOLDEN_DIR: &str = "samples/golden";
+const BLESS_ENV: &str = "THESIA_BLESS_GOLDEN";
+
+const HEIGHT: u32 = 256;
+const PX_PER_SEC: f64 = 100.;
+const OVERVIEW_WIDTH: u32 = 600;
+const OVERVIEW_HEIGHT: u32 = 120;
+
+/// luma difference (0 ~ 255) regarded as visible
+const LUMA_TOLERANCE: f32 = 8.;
+/// maximum ratio of the visibly different pixels
+const MAX_DIFF_RATIO: f64 = 0.002;
+
+/// result of comparing an image to the golden image
+#[derive(Debug, PartialEq)]
+enum GoldenDiff {
+    /// the size differs (the golden size, the image size)
+    Size((u32, u32), (u32, u32)),
+    /// ratio of the pixels whose luma differs more than LUMA_TOLERANCE
+    Pixels(f64),
+}
+
+impl GoldenDiff {
+    fn is_acceptable(&self) -> bool {
+        matches!(self, GoldenDiff::Pixels(ratio) if *ratio <= MAX_DIFF_RATIO)
+    }
+}
+
+/// luma of a non-premultiplied RGBA pixel composited over black
+fn luma(px: &image::Rgba<u8>) -> f32 {
+    let [r, g, b, a] = px.0.map(|x| x as f32);
+    (0.2126 * r + 0.7152 * g + 0.0722 * b) * a / 255.
+}
+
+/// Compare the luma of the pixels, which tolerates tiny color shifts
+/// from the floating-point differences between platforms.
+fn perceptual_diff(golden: &RgbaImage, img: &RgbaImage) -> GoldenDiff {
+    if golden.dimensions() != img.dimensions() {
+        return GoldenDiff::Size(golden.dimensions(), img.dimensions());
+    }
+    let n_pixels = (img.width() * img.height()) as usize;
+    if n_pixels == 0 {
+        return GoldenDiff::Pixels(0.);
+    }
+    let n_diff = golden
+        .pixels()
+        .zip(img.pixels())
+        .filter(|(a, b)| (luma(a) - luma(b)).abs() > LUMA_TOLERANCE)
+        .count();
+    GoldenDiff::Pixels(n_diff as f64 / n_pixels as f64)
+}
+
+fn to_rgba_image(arr: Array3<u8>) -> RgbaImage {
+    let (height, width) = (arr.shape()[0] as u32, arr.shape()[1] as u32);
+    RgbaImage::from_vec(width, height, arr.into_raw_vec_and_offset().0).unwrap()
+}
+
+/// Compare img to the golden image of the name, or write it if blessing.
+/// A missing golden image is a failure unless blessing,
+/// so that the images are never recorded silently.
+fn assert_golden(name: &str, img: &RgbaImage) {
+    let path: PathBuf = Path::new(GOLDEN_DIR).join(format!("{}.png", name));
+    let bless = std::env::var(BLESS_ENV).is_ok_and(|x| x == "1");
+    if bless {
+        std::fs::create_dir_all(GOLDEN_DIR).unwrap();
+        img.save(&path).unwrap();
+        return;
+    }
+    assert!(
+        path.exists(),
+        "golden image {} doesn't exist. Run with {}=1 to write it and commit it.",
+        path.display(),
+        BLESS_ENV,
+    );
+    let golden = image::open(&path).unwrap().into_rgba8();
+    let diff = perceptual_diff(&golden, img);
+    if !diff.is_acceptable() {
+        let actual_path = path.with_extension("actual.png");
+        img.save(&actual_path).unwrap();
+        panic!(
+            "{} differs from the golden image ({:?}). The drawn image is saved to {}. \
+            Run with {}=1 if the change is intended.",
+            name,
+            diff,
+            actual_path.display(),
+            BLESS_ENV,
+        );
+    }
+}
+
+fn prepare(paths: &[&str]) -> (TrackList, TrackManager) {
+    let mut tracklist = TrackList::new();
+    let mut tm = TrackManager::new();
+    let ids = (0..paths.len()).collect();
+    let added_ids = tracklist.add_tracks(ids, paths.iter().map(|&x| x.into()).collect());
+    tm.add_tracks(&tracklist, &added_ids);
+    tm.apply_track_list_changes(&tracklist);
+    (tracklist, tm)
+}
+
+const SAMPLES: [(&str, &str); 2] = [
+    ("48k", "samples/sample_48k.wav"),
+    ("stereo_48k", "samples/stereo/sample_48k.wav"),
+];
+
+#[test]
+#[ignore = "samples/golden/*.png are not blessed yet"]
+fn spec_and_wav_match_golden() {
+    let (tracklist, tm) = prepare(&SAMPLES.map(|(_, path)| path));
+    let id_ch_tuples = tracklist.id_ch_tuples();
+    let opt_for_wav = DrawOptionForWav::default();
+    let kinds = [
+        ("spec", ImageKind::Spec(None, None, None)),
+        ("wav", ImageKind::Wav(&opt_for_wav)),
+    ];
+    for (prefix, kind) in kinds {
+        let imgs = tm.draw_entire_imgs(&tracklist, &id_ch_tuples, HEIGHT, PX_PER_SEC, kind);
+        assert_eq!(imgs.len(), id_ch_tuples.len());
+        for ((id, ch), arr) in imgs {
+            let name = format!("{}_{}_{}", prefix, SAMPLES[id].0, ch);
+            assert_golden(&name, &to_rgba_image(arr));
+        }
+    }
+}
+
+#[test]
+#[ignore = "samples/golden/*.png are not blessed yet"]
+fn overview_matches_golden() {
+    let (tracklist, _) = prepare(&SAMPLES.map(|(_, path)| path));
+    for (id, (tag, _)) in SAMPLES.iter().enumerate() {
+        let track = tracklist.get(id).unwrap();
+        let px_per_sec = OVERVIEW_WIDTH as f64 / track.sec();
+        let rgba = draw_overview_of(track, OVERVIEW_WIDTH, OVERVIEW_HEIGHT, 1., px_per_sec);
+        let img = RgbaImage::from_vec(OVERVIEW_WIDTH, OVERVIEW_HEIGHT, rgba).unwrap();
+        assert_golden(&format!("overview_{}", tag), &img);
+    }
+}
+
+#[test]
+fn perceptual_diff_works() {
+    let golden = RgbaImage::from_fn(100, 10, |x, _| image::Rgba([x as u8 * 2, 100, 50, 255]));
+    assert_eq!(perceptual_diff(&golden, &golden), GoldenDiff::Pixels(0.));
+
+    // slight color shifts are tolerated
+    let mut img = golden.clone();
+    img.pixels_mut().for_each(|px| px.0[1] += 3);
+    assert!(perceptual_diff(&golden, &img).is_acceptable());
+
+    // a visible change of a few pixels is not
+    let mut img = golden.clone();
+    (0..5).for_each(|x| img.put_pixel(x, 0, image::Rgba([255, 255, 255, 255])));
+    assert_eq!(perceptual_diff(&golden, &img), GoldenDiff::Pixels(0.005));
+    assert!(!perceptual_diff(&golden, &img).is_acceptable());
+
+    // transparent pixels look the same regardless of the color
+    let clear = RgbaImage::from_pixel(4, 4, image::Rgba([0, 0, 0, 0]));
+    let clear_white = RgbaImage::from_pixel(4, 4, image::Rgba([255, 255, 255, 0]));
+    assert!(perceptual_diff(&clear, &clear_white).is_acceptable());
+
+    let smaller = RgbaImage::new(50, 10);
+    assert_eq!(
+        perceptual_diff(&golden, &smaller),
+        GoldenDiff::Size((100, 10), (50, 10))
+    );
+}
@@ -1,3 +1,4 @@
+use fast_image_resize::pixels::U16;
 use ndarray::prelude::*;
 
 use super::super::audio::Audio;
@@ -5,6 +6,9 @@ use super::super::audio::Audio;
 pub type LeftWidth = (u32, u32);
 pub type IdxLen = (isize, usize);
 
+/// width and height of a spectrogram tile (px)
+pub const SPEC_TILE_SIZE: usize = 256;
+
 #[derive(PartialEq, Debug)]
 pub struct PartGreyInfo {
     pub i_w_and_width: IdxLen,
@@ -136,6 +140,48 @@ pub fn calc_effective_slice(
     }
 }
 
+/// (number of tiles along rows (frequency), number of tiles along columns (time))
+/// of the grey image at the zoom level
+pub fn calc_n_tiles((n_rows, n_cols): (usize, usize), zoom_level: u32) -> Option<(usize, usize)> {
+    let tile_span = SPEC_TILE_SIZE.checked_mul(1usize.checked_shl(zoom_level)?)?;
+    Some((n_rows.div_ceil(tile_span), n_cols.div_ceil(tile_span)))
+}
+
+/// Tile of the grey image downsampled by 2^zoom_level (mean of each block).
+/// Zoom level 0 is the original resolution. Tiles have the size of SPEC_TILE_SIZE
+/// except those at the bottom and right edges.
+/// Returns None if the tile is out of the image.
+pub fn slice_grey_tile(
+    grey: ArrayView2<U16>,
+    zoom_level: u32,
+    (tile_y, tile_x): (usize, usize),
+) -> Option<Array2<U16>> {
+    let scale = 1usize.checked_shl(zoom_level)?;
+    let tile_span = SPEC_TILE_SIZE.checked_mul(scale)?;
+    let (n_rows, n_cols) = grey.dim();
+    let (i_row, i_col) = (
+        tile_y.checked_mul(tile_span)?,
+        tile_x.checked_mul(tile_span)?,
+    );
+    if i_row >= n_rows || i_col >= n_cols {
+        return None;
+    }
+    let block = grey.slice(s![
+        i_row..(i_row + tile_span).min(n_rows),
+        i_col..(i_col + tile_span).min(n_cols)
+    ]);
+    let (block_rows, block_cols) = block.dim();
+    let shape = (block_rows.div_ceil(scale), block_cols.div_ceil(scale));
+    Some(Array2::from_shape_fn(shape, |(i, j)| {
+        let cell = block.slice(s![
+            i * scale..((i + 1) * scale).min(block_rows),
+            j * scale..((j + 1) * scale).min(block_cols)
+        ]);
+        let sum: u64 = cell.iter().map(|x| x.0 as u64).sum();
+        U16::new((sum / cell.len() as u64) as u16)
+    }))
+}
+
 #[readonly::make]
 pub struct OverviewHeights {
     pub total: usize,
@@ -170,3 +216,30 @@ impl OverviewHeights {
         (gain_h, self.ch - 2 * gain_h)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slice_grey_tile_works() {
+        let (n_rows, n_cols) = (300, SPEC_TILE_SIZE * 2 + 10);
+        let grey = Array2::from_shape_fn((n_rows, n_cols), |(i, j)| U16::new((i + j) as u16));
+        assert_eq!(calc_n_tiles(grey.dim(), 0), Some((2, 3)));
+        assert_eq!(calc_n_tiles(grey.dim(), 1), Some((1, 2)));
+
+        let tile = slice_grey_tile(grey.view(), 0, (0, 1)).unwrap();
+        assert_eq!(tile.dim(), (SPEC_TILE_SIZE, SPEC_TILE_SIZE));
+        assert_eq!(tile[[0, 0]], U16::new(SPEC_TILE_SIZE as u16));
+        let edge = slice_grey_tile(grey.view(), 0, (1, 2)).unwrap();
+        assert_eq!(edge.dim(), (n_rows - SPEC_TILE_SIZE, 10));
+
+        let zoomed_out = slice_grey_tile(grey.view(), 1, (0, 0)).unwrap();
+        assert_eq!(zoomed_out.dim(), (n_rows / 2, SPEC_TILE_SIZE));
+        // mean of [[0, 1], [1, 2]]
+        assert_eq!(zoomed_out[[0, 0]], U16::new(1));
+
+        assert!(slice_grey_tile(grey.view(), 0, (2, 0)).is_none());
+        assert!(slice_grey_tile(grey.view(), 1, (0, 2)).is_none());
+    }
+}
@@ -1,6 +1,9 @@
 use approx::{relative_ne, AbsDiffEq, RelativeEq};
+use napi_derive::napi;
 use serde::{Deserialize, Serialize};
 
+use super::super::spectrogram::FreqScale;
+
 #[derive(Clone, PartialEq)]
 pub struct DrawParams {
     pub start_sec: f64,
@@ -9,11 +12,23 @@ pub struct DrawParams {
     pub px_per_sec: f64,
     pub opt_for_wav: DrawOptionForWav,
     pub blend: f64,
+    /// frequency range of the spectrogram.
+    /// None means the range shared by all views (the hz range of TrackManager).
+    pub hz_range: Option<(f32, f32)>,
+    /// range of the grey values stretched to the whole colormap
+    /// (auto contrast to the visible region). None means no stretching.
+    pub grey_range: Option<(u16, u16)>,
+    /// break of the frequency axis of the spectrogram. None means no break.
+    pub freq_break: Option<FreqAxisBreak>,
 }
 
 impl DrawParams {
     pub fn is_params_for_different_img_cache(&self, other: &Self) -> bool {
-        self.height != other.height || relative_ne!(self.px_per_sec, other.px_per_sec)
+        self.height != other.height
+            || relative_ne!(self.px_per_sec, other.px_per_sec)
+            || self.hz_range != other.hz_range
+            || self.grey_range != other.grey_range
+            || self.freq_break != other.freq_break
     }
 
     pub fn is_params_for_different_wav_cache(&self, other: &Self) -> bool {
@@ -30,6 +45,9 @@ impl Default for DrawParams {
             px_per_sec: 0.,
             opt_for_wav: Default::default(),
             blend: 1.,
+            hz_range: None,
+            grey_range: None,
+            freq_break: None,
         }
     }
 }
@@ -116,7 +134,99 @@ impl RelativeEq for DrawOptionForWav {
     }
 }
 
+/// range of the dB values mapped to the colormap of the spectrogram
+#[napi(string_enum)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum AutoContrastMode {
+    /// min/max dB of all tracks
+    #[default]
+    Global,
+    /// min/max dB of the visible time and frequency region
+    Viewport,
+}
+
+/// Break of the frequency axis of the spectrogram, which splits the axis into two segments
+/// scaled independently (e.g. 0~100 Hz expanded to the lower half,
+/// and 100 Hz~Nyquist compressed to the upper half).
+#[napi(object)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FreqAxisBreak {
+    /// frequency at the break (Hz)
+    pub hz: f64,
+    /// ratio of the height below the break (0 < ratio < 1)
+    pub ratio: f64,
+}
+
+impl FreqAxisBreak {
+    pub fn is_valid(&self) -> bool {
+        self.hz.is_finite() && self.hz > 0. && self.ratio > 0. && self.ratio < 1.
+    }
+
+    /// true if the break splits hz_range. The axis isn't broken otherwise.
+    pub fn is_inside(&self, hz_range: (f32, f32)) -> bool {
+        self.is_valid() && (hz_range.0 as f64) < self.hz && self.hz < hz_range.1 as f64
+    }
+
+    /// (hz range, relative height) of the segments below and above the break
+    fn segments(&self, hz_range: (f32, f32)) -> [((f32, f32), f32); 2] {
+        let (hz, ratio) = (self.hz as f32, self.ratio as f32);
+        [((hz_range.0, hz), ratio), ((hz, hz_range.1), 1. - ratio)]
+    }
+
+    /// relative frequency (0: bottom, 1: top) on the broken axis to Hz
+    pub fn relative_freq_to_hz(
+        &self,
+        rel_freq: f32,
+        hz_range: (f32, f32),
+        freq_scale: FreqScale,
+    ) -> f32 {
+        if !self.is_inside(hz_range) {
+            return freq_scale.relative_freq_to_hz(rel_freq, hz_range);
+        }
+        let [(lower, lower_ratio), (upper, upper_ratio)] = self.segments(hz_range);
+        if rel_freq < lower_ratio {
+            freq_scale.relative_freq_to_hz(rel_freq / lower_ratio, lower)
+        } else {
+            freq_scale.relative_freq_to_hz((rel_freq - lower_ratio) / upper_ratio, upper)
+        }
+    }
+
+    /// Hz to the relative frequency (0: bottom, 1: top) on the broken axis
+    pub fn hz_to_relative_freq(&self, hz: f32, hz_range: (f32, f32), freq_scale: FreqScale) -> f32 {
+        if !self.is_inside(hz_range) {
+            return freq_scale.hz_to_relative_freq(hz, hz_range);
+        }
+        let [(lower, lower_ratio), (upper, upper_ratio)] = self.segments(hz_range);
+        if hz < self.hz as f32 {
+            freq_scale.hz_to_relative_freq(hz, lower) * lower_ratio
+        } else {
+            freq_scale
+                .hz_to_relative_freq(hz, upper)
+                .mul_add(upper_ratio, lower_ratio)
+        }
+    }
+}
+
+/// interpolation of the frequency axis of the spectrogram images
+#[napi(string_enum)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum FreqInterpolation {
+    /// Lanczos (band-limited) interpolation
+    #[default]
+    BandLimited,
+    /// average of the bins in each pixel if the bins are denser than the pixels,
+    /// linear interpolation otherwise
+    BinAverage,
+}
+
 pub enum ImageKind<'a> {
-    Spec,
+    /// (frequency range of the spectrogram (None: the hz range of TrackManager),
+    /// grey range stretched to the whole colormap (None: no stretching),
+    /// break of the frequency axis (None: no break))
+    Spec(
+        Option<(f32, f32)>,
+        Option<(u16, u16)>,
+        Option<FreqAxisBreak>,
+    ),
     Wav(&'a DrawOptionForWav),
 }
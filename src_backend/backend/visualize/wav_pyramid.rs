@@ -0,0 +1,199 @@
+//! Hierarchically decimated min/max of a waveform (block lengths of powers of 4)
+//! so that drawing the overview of a long track touches about width × constant values.
+
+use ndarray::prelude::*;
+use rayon::prelude::*;
+
+/// block length of the finest level
+const BASE_BLOCK_LEN: usize = 256;
+/// ratio of the block lengths of adjacent levels
+const LEVEL_RATIO: usize = 4;
+/// minimum number of blocks in a pixel to keep the error of the envelope within a fraction of a pixel
+const MIN_BLOCKS_PER_PX: f64 = 4.;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PyramidLevel {
+    block_len: usize,
+    mins: Vec<f32>,
+    maxs: Vec<f32>,
+}
+
+impl PyramidLevel {
+    /// (min, max) of the samples in i_start..i_end, extended to the block boundaries.
+    /// NaNs are skipped.
+    pub fn min_max(&self, i_start: usize, i_end: usize) -> (f32, f32) {
+        let j_start = (i_start / self.block_len).min(self.mins.len() - 1);
+        let j_end = i_end
+            .div_ceil(self.block_len)
+            .clamp(j_start + 1, self.mins.len());
+        let min = self.mins[j_start..j_end]
+            .iter()
+            .copied()
+            .fold(f32::INFINITY, f32::min);
+        let max = self.maxs[j_start..j_end]
+            .iter()
+            .copied()
+            .fold(f32::NEG_INFINITY, f32::max);
+        (min, max)
+    }
+
+    fn decimate(&self) -> Self {
+        let reduce = |x: &[f32], f: fn(f32, f32) -> f32, init: f32| -> Vec<f32> {
+            x.chunks(LEVEL_RATIO)
+                .map(|chunk| chunk.iter().copied().fold(init, f))
+                .collect()
+        };
+        PyramidLevel {
+            block_len: self.block_len * LEVEL_RATIO,
+            mins: reduce(&self.mins, f32::min, f32::INFINITY),
+            maxs: reduce(&self.maxs, f32::max, f32::NEG_INFINITY),
+        }
+    }
+}
+
+/// hashes of the blocks of BASE_BLOCK_LEN samples to find the changed ranges of a waveform
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BlockHashes(Vec<u64>);
+
+impl BlockHashes {
+    pub fn new(wav: ArrayView1<f32>) -> Self {
+        BlockHashes(
+            wav.axis_chunks_iter(Axis(0), BASE_BLOCK_LEN)
+                .into_par_iter()
+                .map(|block| {
+                    block
+                        .iter()
+                        .fold(0, |hash, x| mix_hash(hash, x.to_bits() as u64))
+                })
+                .collect(),
+        )
+    }
+
+    /// hash of the blocks overlapping the samples in i_start..i_end and of the number of blocks
+    pub fn hash_range(&self, i_start: usize, i_end: usize) -> u64 {
+        let j_start = (i_start / BASE_BLOCK_LEN).min(self.0.len());
+        let j_end = i_end.div_ceil(BASE_BLOCK_LEN).clamp(j_start, self.0.len());
+        self.0[j_start..j_end]
+            .iter()
+            .fold(self.0.len() as u64, |hash, &x| mix_hash(hash, x))
+    }
+}
+
+/// combine x into hash (the multiplier of FxHash)
+#[inline]
+pub fn mix_hash(hash: u64, x: u64) -> u64 {
+    (hash.rotate_left(5) ^ x).wrapping_mul(0x517c_c1b7_2722_0a95)
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WavPyramid {
+    /// from the finest to the coarsest level (the coarsest level has a single block)
+    levels: Vec<PyramidLevel>,
+    hashes: BlockHashes,
+}
+
+impl WavPyramid {
+    /// No level if the waveform is too short to need decimation.
+    pub fn new(wav: ArrayView1<f32>) -> Self {
+        let hashes = BlockHashes::new(wav);
+        if wav.len() < BASE_BLOCK_LEN * LEVEL_RATIO {
+            return WavPyramid {
+                levels: Vec::new(),
+                hashes,
+            };
+        }
+        let (mins, maxs) = wav
+            .axis_chunks_iter(Axis(0), BASE_BLOCK_LEN)
+            .into_par_iter()
+            .map(|block| {
+                block
+                    .iter()
+                    .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &x| {
+                        (min.min(x), max.max(x))
+                    })
+            })
+            .unzip();
+        let mut levels = vec![PyramidLevel {
+            block_len: BASE_BLOCK_LEN,
+            mins,
+            maxs,
+        }];
+        while levels.last().unwrap().mins.len() > 1 {
+            levels.push(levels.last().unwrap().decimate());
+        }
+        WavPyramid { levels, hashes }
+    }
+
+    #[inline]
+    pub fn hashes(&self) -> &BlockHashes {
+        &self.hashes
+    }
+
+    /// The coarsest level with at least MIN_BLOCKS_PER_PX blocks in a pixel.
+    /// None if the samples should be drawn directly (not enough samples per pixel).
+    pub fn level_for(&self, samples_per_px: f64) -> Option<&PyramidLevel> {
+        self.levels
+            .iter()
+            .rev()
+            .find(|level| level.block_len as f64 * MIN_BLOCKS_PER_PX <= samples_per_px)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ndarray_stats::QuantileExt;
+
+    #[test]
+    fn wav_pyramid_works() {
+        let len = 100_000;
+        let wav = Array1::from_shape_fn(len, |i| ((i as f32 * 0.37).sin() * (i % 977) as f32));
+        let pyramid = WavPyramid::new(wav.view());
+        assert_eq!(pyramid.levels[0].mins.len(), len.div_ceil(BASE_BLOCK_LEN));
+        assert_eq!(pyramid.levels.last().unwrap().mins.len(), 1);
+
+        assert!(pyramid.level_for(100.).is_none());
+        let level = pyramid.level_for(5000.).unwrap();
+        assert_eq!(level.block_len, 1024);
+        assert_eq!(
+            pyramid.level_for(1e9).unwrap().block_len,
+            pyramid.levels.last().unwrap().block_len
+        );
+
+        // block-aligned ranges are exact
+        let (min, max) = level.min_max(2048, 8192);
+        let part = wav.slice(s![2048..8192]);
+        assert_eq!((min, max), (*part.min().unwrap(), *part.max().unwrap()));
+        // other ranges are extended to the block boundaries
+        let (min, max) = level.min_max(3000, 3001);
+        let block = wav.slice(s![2048..3072]);
+        assert_eq!((min, max), (*block.min().unwrap(), *block.max().unwrap()));
+        let (_, max) = level.min_max(len + 10, len + 20);
+        assert_eq!(max, *wav.slice(s![99328..]).max().unwrap());
+
+        assert!(WavPyramid::new(wav.slice(s![..1000])).levels.is_empty());
+    }
+
+    #[test]
+    fn block_hashes_work() {
+        let mut wav = Array1::from_shape_fn(10_000, |i| (i as f32 * 0.1).sin());
+        let hashes = BlockHashes::new(wav.view());
+        wav[5000] += 0.5;
+        let changed = BlockHashes::new(wav.view());
+        // only the block of the changed sample (4864..5120) differs
+        assert_eq!(hashes.hash_range(0, 4864), changed.hash_range(0, 4864));
+        assert_eq!(
+            hashes.hash_range(5120, 10_000),
+            changed.hash_range(5120, 10_000)
+        );
+        assert_ne!(
+            hashes.hash_range(4000, 5001),
+            changed.hash_range(4000, 5001)
+        );
+        // the length is a part of the hash
+        let longer = BlockHashes::new(Array1::zeros(10_300).view());
+        let shorter = BlockHashes::new(Array1::zeros(10_000).view());
+        assert_ne!(longer.hash_range(0, 256), shorter.hash_range(0, 256));
+    }
+}
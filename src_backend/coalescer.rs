@@ -0,0 +1,106 @@
+//! Coalescing of redundant requests of the frontend (e.g. storms of requests while resizing).
+//! For each key, only the newest pending request is executed,
+//! and the superseded requests return immediately as stale.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use napi::tokio;
+use parking_lot::Mutex;
+
+use crate::IdCh;
+
+/// time to wait for a newer request before executing a request
+const COALESCE_WINDOW: Duration = Duration::from_millis(8);
+
+/// target and kind of a request
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RequestKey {
+    /// overview image of the track
+    Overview(usize),
+    /// pixel classes and envelopes of the waveform slice of the channel
+    WavDrawingInfo(IdCh),
+    /// spectrogram tile of the channel at (tile_x, tile_y) of any zoom level or layer,
+    /// so that the other tiles of the grid are not superseded
+    SpecTile(IdCh, (u32, u32)),
+}
+
+impl RequestKey {
+    fn track_id(&self) -> usize {
+        match *self {
+            RequestKey::Overview(id)
+            | RequestKey::WavDrawingInfo((id, _))
+            | RequestKey::SpecTile((id, _), _) => id,
+        }
+    }
+}
+
+/// sequence number of the newest request for each key.
+/// The entries of the removed tracks are removed by remove_tracks.
+static NEWEST_SEQS: LazyLock<Mutex<HashMap<RequestKey, u64>>> = LazyLock::new(Default::default);
+
+/// sequence numbers are unique over all keys,
+/// so a request registered before its entry is removed never looks like the newest one.
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// A registered request. It becomes stale when a newer request with the same key is registered.
+#[derive(Debug)]
+pub struct Ticket {
+    key: RequestKey,
+    seq: u64,
+}
+
+impl Ticket {
+    pub fn register(key: RequestKey) -> Self {
+        let mut newest_seqs = NEWEST_SEQS.lock();
+        let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+        newest_seqs.insert(key, seq);
+        Ticket { key, seq }
+    }
+
+    /// true if a newer request with the same key is registered or the track is removed
+    pub fn is_stale(&self) -> bool {
+        NEWEST_SEQS.lock().get(&self.key) != Some(&self.seq)
+    }
+
+    /// Wait for COALESCE_WINDOW, and returns true if the request is still the newest.
+    pub async fn throttle(&self) -> bool {
+        tokio::time::sleep(COALESCE_WINDOW).await;
+        !self.is_stale()
+    }
+}
+
+/// forget the requests for the removed tracks (they become stale)
+pub fn remove_tracks(ids: &[usize]) {
+    NEWEST_SEQS
+        .lock()
+        .retain(|key, _| !ids.contains(&key.track_id()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_newest_ticket_is_valid() {
+        let first = Ticket::register(RequestKey::Overview(100));
+        let other = Ticket::register(RequestKey::Overview(101));
+        assert!(!first.is_stale());
+        let second = Ticket::register(RequestKey::Overview(100));
+        assert!(first.is_stale());
+        assert!(!second.is_stale());
+        assert!(!other.is_stale());
+
+        let tile = Ticket::register(RequestKey::SpecTile((100, 0), (0, 0)));
+        let other_tile = Ticket::register(RequestKey::SpecTile((100, 0), (1, 0)));
+        assert!(!tile.is_stale());
+        remove_tracks(&[100]);
+        assert!(second.is_stale());
+        assert!(tile.is_stale());
+        assert!(other_tile.is_stale());
+        assert!(!other.is_stale());
+        assert!(!Ticket::register(RequestKey::Overview(100)).is_stale());
+    }
+}
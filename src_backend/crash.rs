@@ -0,0 +1,153 @@
+//! Crash diagnostics: a panic hook saving a redacted JSON report of the backend state
+//! (track count, settings, recent commands, memory usage) into the app data dir.
+//! The report is taken by the frontend on the next launch to be offered to the user.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::panic;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{DateTime, Local};
+use parking_lot::{Mutex, RwLock};
+use serde_json::{json, Value};
+
+const REPORT_FILE_NAME: &str = "thesia_crash_report.json";
+/// number of the recent commands kept for the report
+const MAX_RECENT_COMMANDS: usize = 32;
+
+/// directory to save the report (app data dir). No report is saved if None.
+static REPORT_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+/// ring buffer of (time, command name)
+static RECENT_COMMANDS: Mutex<VecDeque<(DateTime<Local>, &'static str)>> =
+    Mutex::new(VecDeque::new());
+/// only the first panic is reported because the others are usually caused by it
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+pub fn set_report_dir(dir: PathBuf) {
+    *REPORT_DIR.write() = Some(dir);
+}
+
+/// record the command for the report
+pub fn record_command(name: &'static str) {
+    let mut commands = RECENT_COMMANDS.lock();
+    if commands.len() >= MAX_RECENT_COMMANDS {
+        commands.pop_front();
+    }
+    commands.push_back((Local::now(), name));
+}
+
+/// Install the panic hook saving the report. snapshot_state should not block or panic
+/// because the panic can occur while the state is locked.
+pub fn install_panic_hook(snapshot_state: fn() -> Value) {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if !REPORTED.swap(true, Ordering::SeqCst) {
+            if let Some(dir) = REPORT_DIR.try_read().and_then(|dir| dir.clone()) {
+                let report = build_report(&info.to_string(), snapshot_state());
+                if let Err(e) = fs::write(dir.join(REPORT_FILE_NAME), report) {
+                    eprintln!("Failed to save the crash report: {}", e);
+                }
+            }
+        }
+        default_hook(info);
+    }));
+}
+
+/// Read and remove the report saved by the previous run. None if the previous run didn't crash.
+pub fn take_report() -> io::Result<Option<String>> {
+    let Some(dir) = REPORT_DIR.read().clone() else {
+        return Ok(None);
+    };
+    let path = dir.join(REPORT_FILE_NAME);
+    match fs::read_to_string(&path) {
+        Ok(report) => {
+            fs::remove_file(&path)?;
+            Ok(Some(report))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn build_report(panic_msg: &str, state: Value) -> String {
+    let recent_commands: Vec<_> = RECENT_COMMANDS
+        .try_lock()
+        .map_or_else(Vec::new, |commands| {
+            commands
+                .iter()
+                .map(|(time, name)| json!({"time": time.to_rfc3339(), "command": name}))
+                .collect()
+        });
+    let thread = std::thread::current();
+    let report = json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "time": Local::now().to_rfc3339(),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "thread": thread.name().unwrap_or("<unnamed>"),
+        "panic": redact(panic_msg),
+        "backtrace": redact(&std::backtrace::Backtrace::force_capture().to_string()),
+        "state": state,
+        "recentCommands": recent_commands,
+        "residentMemoryMB": resident_memory_mb(),
+    });
+    serde_json::to_string_pretty(&report).unwrap_or_default()
+}
+
+/// replace the home directory (which contains the user name) with "~"
+fn redact(text: &str) -> String {
+    ["HOME", "USERPROFILE"]
+        .into_iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .filter(|home| home.len() > 1)
+        .fold(text.to_owned(), |text, home| text.replace(&home, "~"))
+}
+
+#[cfg(target_os = "linux")]
+fn resident_memory_mb() -> Option<f64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let kb: f64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb / 1024.)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident_memory_mb() -> Option<f64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_commands_are_bounded() {
+        for _ in 0..(MAX_RECENT_COMMANDS + 5) {
+            record_command("addTracks");
+        }
+        record_command("removeTracks");
+        let commands = RECENT_COMMANDS.lock();
+        assert_eq!(commands.len(), MAX_RECENT_COMMANDS);
+        assert_eq!(commands.back().unwrap().1, "removeTracks");
+    }
+
+    #[test]
+    fn report_is_redacted() {
+        let Ok(home) = std::env::var("HOME") else {
+            return;
+        };
+        let msg = format!("failed to open {}/music/a.wav", home);
+        let report: Value =
+            serde_json::from_str(&build_report(&msg, json!({"nTracks": 1}))).unwrap();
+        assert_eq!(report["panic"], "failed to open ~/music/a.wav");
+        assert_eq!(report["state"]["nTracks"], 1);
+    }
+}
@@ -0,0 +1,144 @@
+//! Downloading audio files of http(s) URLs to a temporary cache before decoding
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::tempfiles;
+
+const CACHE_DIR_NAME: &str = "thesia_url_cache";
+const CHUNK_SIZE: usize = 64 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+/// a stalled server fails the download instead of blocking the worker forever
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// not to fill the temporary disk with an endless response
+const MAX_DOWNLOAD_BYTES: u64 = 2 << 30;
+
+/// file name of the last path segment of the URL without the query and the fragment
+fn file_name_of(url: &str) -> String {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let path = without_scheme.split(['?', '#']).next().unwrap_or_default();
+    let name = match path.split_once('/') {
+        Some((_, path)) => path.rsplit('/').next().unwrap_or_default(),
+        None => "",
+    };
+    name.chars()
+        .filter(|c| !matches!(c, '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|'))
+        .collect::<String>()
+        .trim()
+        .trim_start_matches('.')
+        .to_string()
+}
+
+/// cache_dir/{hash of url}/{file name}, so the file name (and extension) of the URL is kept
+fn cache_path(url: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    let name = file_name_of(url);
+    std::env::temp_dir()
+        .join(CACHE_DIR_NAME)
+        .join(format!("{:016x}", hasher.finish()))
+        .join(if name.is_empty() { "download" } else { &name })
+}
+
+/// Download the URL to the cache (overwriting the previous download) and return the path.
+/// on_progress(downloaded_bytes, total_bytes) is called after each chunk.
+/// total_bytes is None if the server doesn't tell the content length.
+/// Fails if the server stalls for READ_TIMEOUT or the file exceeds MAX_DOWNLOAD_BYTES.
+/// Should be called in a blocking thread.
+pub fn download_to_cache(
+    url: &str,
+    mut on_progress: impl FnMut(u64, Option<u64>),
+) -> Result<PathBuf, String> {
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err("Only http and https URLs are supported.".into());
+    }
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build();
+    let response = agent.get(url).call().map_err(|e| e.to_string())?;
+    let total_bytes: Option<u64> = response
+        .header("Content-Length")
+        .and_then(|len| len.parse().ok());
+    let too_large_msg = || {
+        format!(
+            "The file is larger than the download limit ({} GiB).",
+            MAX_DOWNLOAD_BYTES >> 30
+        )
+    };
+    if total_bytes.is_some_and(|total| total > MAX_DOWNLOAD_BYTES) {
+        return Err(too_large_msg());
+    }
+    let path = cache_path(url);
+    fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string())?;
+    // write to a partial file first not to leave a broken file at path
+    let part_path = path.with_extension("part");
+    // one more byte than the limit to detect exceeding it
+    let mut reader = response.into_reader().take(MAX_DOWNLOAD_BYTES + 1);
+    let mut write_all = || -> io::Result<()> {
+        let mut file = fs::File::create(&part_path)?;
+        let mut buf = vec![0; CHUNK_SIZE];
+        let mut downloaded_bytes = 0;
+        on_progress(0, total_bytes);
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            downloaded_bytes += n as u64;
+            if downloaded_bytes > MAX_DOWNLOAD_BYTES {
+                return Err(io::Error::other(too_large_msg()));
+            }
+            file.write_all(&buf[..n])?;
+            on_progress(downloaded_bytes, total_bytes);
+        }
+    };
+    if let Err(e) = write_all().and_then(|_| fs::rename(&part_path, &path)) {
+        let _ = fs::remove_file(&part_path);
+        return Err(e.to_string());
+    }
+    tempfiles::register(&path);
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_path_works() {
+        assert_eq!(
+            file_name_of("https://example.com/a/b/test%20vector.flac?token=1#t=3"),
+            "test%20vector.flac"
+        );
+        assert_eq!(file_name_of("http://example.com"), "");
+        assert_eq!(file_name_of("http://example.com/dir/"), "");
+        assert_eq!(
+            file_name_of("http://example.com/../.hidden.wav"),
+            "hidden.wav"
+        );
+
+        let path = cache_path("https://example.com/x.wav");
+        assert_eq!(path.file_name().unwrap(), "x.wav");
+        assert!(path.starts_with(std::env::temp_dir().join(CACHE_DIR_NAME)));
+        assert_ne!(path, cache_path("https://example.org/x.wav"));
+        assert_eq!(
+            cache_path("https://example.com/").file_name().unwrap(),
+            "download"
+        );
+    }
+
+    #[test]
+    fn download_rejects_other_schemes() {
+        let mut n_called = 0;
+        assert!(download_to_cache("file:///etc/hosts", |_, _| n_called += 1).is_err());
+        assert!(download_to_cache("samples/sample_48k.wav", |_, _| n_called += 1).is_err());
+        assert_eq!(n_called, 0);
+    }
+}
@@ -1,29 +1,79 @@
 use std::num::Wrapping;
-use std::sync::atomic::{self, AtomicUsize};
-use std::sync::{Arc, OnceLock};
+use std::sync::{Arc, LazyLock, OnceLock};
 
 use approx::abs_diff_eq;
+use identity_hash::IntMap;
 use napi::bindgen_prelude::spawn;
 use napi::tokio::sync::{mpsc, RwLock};
-use napi::tokio::{self, join, task::JoinHandle};
+use napi::tokio::{self, task::JoinHandle};
 use ndarray::prelude::*;
 use num_traits::{AsPrimitive, Num, NumOps};
+use parking_lot::Mutex;
 use rayon::prelude::*;
 
+use crate::jobs::{Job, JobKind};
 use crate::visualize::*;
-use crate::{IdChArr, IdChDMap, IdChMap, IdChValueArr, IdChValueVec, IdChVec, Pad, TM, TRACK_LIST};
+use crate::{
+    IdChArr, IdChDMap, IdChMap, IdChValueArr, IdChValueVec, IdChVec, Pad, TupleIntDMap, TM,
+    TRACK_LIST,
+};
 
 type Images = IdChValueVec<Vec<u8>>;
 type ArcImgCaches = Arc<IdChDMap<Array3<u8>>>;
+type OverviewKey = (usize, u32); // (track_id, width)
+type ImgTx = mpsc::Sender<(ViewId, Wrapping<usize>, Images)>;
+
+/// id of the view drawing images of tracks independently of the other views
+/// (e.g. a detail lane showing a zoomed frequency range)
+pub type ViewId = u32;
+
+/// the view of the main track list
+pub const MAIN_VIEW_ID: ViewId = 0;
 
 const MAX_IMG_CACHE_WIDTH: u32 = 32768;
 
 static MSG_TX: OnceLock<mpsc::Sender<ImgMsg>> = OnceLock::new();
-static mut IMG_RX: OnceLock<mpsc::Receiver<(Wrapping<usize>, Images)>> = OnceLock::new();
+static mut IMG_RX: OnceLock<mpsc::Receiver<(ViewId, Wrapping<usize>, Images)>> = OnceLock::new();
+static RECEIVED_IMGS: LazyLock<Mutex<IntMap<ViewId, ReceivedImgs>>> =
+    LazyLock::new(Default::default);
+static OVERVIEW_CACHES: LazyLock<TupleIntDMap<OverviewKey, OverviewCache>> =
+    LazyLock::new(Default::default);
 
 pub enum ImgMsg {
-    Draw((IdChVec, DrawParams)),
+    Draw((ViewId, IdChVec, DrawParams)),
     Remove(IdChVec),
+    RemoveView(ViewId),
+}
+
+/// image caches and the drawing task of a view
+#[derive(Default)]
+struct ViewState {
+    spec_caches: ArcImgCaches,
+    wav_caches: ArcImgCaches,
+    prev_params: Arc<RwLock<DrawParams>>,
+    req_id: Wrapping<usize>,
+    task_handle: Option<JoinHandle<()>>,
+}
+
+/// the most recent images of a view not taken by recv yet
+#[derive(Default)]
+struct ReceivedImgs {
+    req_id: Wrapping<usize>,
+    imgs: Option<Images>,
+}
+
+#[derive(PartialEq)]
+struct OverviewParams {
+    height: u32,
+    dpr: f32,
+    max_sec: f64,
+}
+
+/// segments of OVERVIEW_SEGMENT_WIDTH columns of the overview image drawn with params
+struct OverviewCache {
+    params: OverviewParams,
+    /// (hash of the drawn data, image) of each segment
+    segments: Vec<(u64, Array3<u8>)>,
 }
 
 #[derive(Default, Debug)]
@@ -71,28 +121,77 @@ where
     }
 }
 
-pub fn recv() -> Option<Images> {
-    static RECENT_REQ_ID: AtomicUsize = AtomicUsize::new(0);
-
-    let mut max_req_id = Wrapping(RECENT_REQ_ID.load(atomic::Ordering::Acquire));
-    let mut opt_images: Option<Images> = None;
+/// Returns the most recent images of the view.
+/// Images of the other views received meanwhile are kept until they are taken.
+pub fn recv(view_id: ViewId) -> Option<Images> {
+    let mut received_imgs = RECEIVED_IMGS.lock();
     let img_rx = unsafe {
         #[allow(static_mut_refs)]
         IMG_RX.get_mut().unwrap()
     };
-    while let Ok((curr_req_id, imgs)) = img_rx.try_recv() {
-        if curr_req_id.slightly_larger_than_or_equal_to(max_req_id) {
-            max_req_id = curr_req_id;
-            opt_images = Some(imgs);
+    while let Ok((curr_view_id, curr_req_id, imgs)) = img_rx.try_recv() {
+        let received = received_imgs.entry(curr_view_id).or_default();
+        if curr_req_id.slightly_larger_than_or_equal_to(received.req_id) {
+            received.req_id = curr_req_id;
+            received.imgs = Some(imgs);
         }
     }
 
-    /* if opt_images.is_some() {
-        println!("return req_id={}", max_req_id);
-    } */
+    received_imgs
+        .get_mut(&view_id)
+        .and_then(|received| received.imgs.take())
+}
 
-    RECENT_REQ_ID.store(max_req_id.0, atomic::Ordering::Release);
-    opt_images
+/// Returns the overview image of the track. Only the segments whose hash (segment_hashes) changed
+/// since they were cached are drawn by `draw_segment` (all segments if the parameters changed),
+/// and the overview caches of the track with the other widths are removed.
+pub fn get_or_draw_overview(
+    id: usize,
+    width: u32,
+    height: u32,
+    dpr: f32,
+    max_sec: f64,
+    segment_hashes: Vec<u64>,
+    draw_segment: impl Fn((u32, u32)) -> Vec<u8> + Sync,
+) -> Vec<u8> {
+    let params = OverviewParams {
+        height,
+        dpr,
+        max_sec,
+    };
+    let mut prev_segments: Vec<_> = OVERVIEW_CACHES
+        .remove(&(id, width))
+        .filter(|(_, cache)| cache.params == params)
+        .map_or_else(Vec::new, |(_, cache)| {
+            cache.segments.into_iter().map(Some).collect()
+        });
+    prev_segments.resize_with(segment_hashes.len(), || None);
+    let segments: Vec<_> = segment_hashes
+        .into_par_iter()
+        .zip(prev_segments)
+        .enumerate()
+        .map(|(i, (hash, prev))| match prev {
+            Some((prev_hash, img)) if prev_hash == hash => (hash, img),
+            _ => {
+                let px_start = i as u32 * OVERVIEW_SEGMENT_WIDTH;
+                let px_end = (px_start + OVERVIEW_SEGMENT_WIDTH).min(width);
+                let img = draw_segment((px_start, px_end));
+                let segment_width = (px_end - px_start) as usize;
+                let shape = (img.len() / segment_width / 4, segment_width, 4);
+                (hash, Array3::from_shape_vec(shape, img).unwrap())
+            }
+        })
+        .collect();
+    let views: Vec<_> = segments.iter().map(|(_, img)| img.view()).collect();
+    let img = ndarray::concatenate(Axis(1), &views).unwrap();
+    OVERVIEW_CACHES.retain(|&(cached_id, _), _| cached_id != id);
+    OVERVIEW_CACHES.insert((id, width), OverviewCache { params, segments });
+    img.into_raw_vec_and_offset().0
+}
+
+/// remove the overview caches of the removed tracks
+pub fn remove_overview_caches(ids: &[usize]) {
+    OVERVIEW_CACHES.retain(|&(id, _), _| !ids.contains(&id));
 }
 
 /// Choose image caches and crop.
@@ -312,7 +411,7 @@ async fn categorize_blend_caches(
             blend: -1.,
             ..params.clone()
         };
-        let (tracklist, tm) = join!(TRACK_LIST.read(), TM.read());
+        let (tm, tracklist) = TM.read_both(&TRACK_LIST).await;
         wav_imgs.extend(tm.draw_part_imgs(&tracklist, &need_wav_parts_only, &params, None));
     };
     let blended_imgs = blend_imgs(
@@ -346,7 +445,7 @@ async fn draw_part_imgs(
         .map(|tup| *total_widths.get(tup).unwrap() <= MAX_IMG_CACHE_WIDTH)
         .collect();
     // let fast_resize_vec = vec![true; cat_by_spec.need_parts.len()];
-    let (tracklist, tm) = join!(TRACK_LIST.read(), TM.read());
+    let (tm, tracklist) = TM.read_both(&TRACK_LIST).await;
     tm.draw_part_imgs(&tracklist, need_parts, params, fast_resize_vec)
 }
 
@@ -367,7 +466,7 @@ async fn draw_new_caches(
 
     // draw new caches
     let (new_spec_caches, new_wav_caches) = {
-        let (tracklist, tm) = join!(TRACK_LIST.read(), TM.read());
+        let (tm, tracklist) = TM.read_both(&TRACK_LIST).await;
         rayon::join(
             || {
                 tm.draw_entire_imgs(
@@ -375,7 +474,7 @@ async fn draw_new_caches(
                     &need_new_spec_caches,
                     height,
                     px_per_sec,
-                    ImageKind::Spec,
+                    ImageKind::Spec(params.hz_range, params.grey_range, params.freq_break),
                 )
             },
             || {
@@ -432,8 +531,8 @@ async fn draw_imgs(
     params: Arc<RwLock<DrawParams>>,
     spec_caches: ArcImgCaches,
     wav_caches: ArcImgCaches,
-    img_tx: mpsc::Sender<(Wrapping<usize>, Images)>,
-    req_id: Wrapping<usize>,
+    img_tx: ImgTx,
+    (view_id, req_id): (ViewId, Wrapping<usize>),
 ) {
     let params_backup = params.read().await.clone();
     let (total_widths, cat_by_spec, cat_by_wav, blended_imgs) = categorize_blend_caches(
@@ -448,7 +547,7 @@ async fn draw_imgs(
             "[cached] req_id: {}, blend: {}",
             req_id, params_backup.blend
         ); */
-        img_tx.send((req_id, blended_imgs)).await.unwrap();
+        img_tx.send((view_id, req_id, blended_imgs)).await.unwrap();
     }
     if *params.read().await != params_backup {
         return;
@@ -464,7 +563,7 @@ async fn draw_imgs(
     .await;
     if !blended_imgs.is_empty() {
         // println!("[part] req_id: {}, blend: {}", req_id, params_backup.blend);
-        img_tx.send((req_id, blended_imgs)).await.unwrap();
+        img_tx.send((view_id, req_id, blended_imgs)).await.unwrap();
     }
     if *params.read().await != params_backup {
         return;
@@ -484,7 +583,7 @@ async fn draw_imgs(
             "[new cache] req_id: {}, blend: {}",
             req_id, params_backup.blend
         ) */
-        img_tx.send((req_id, blended_imgs)).await.unwrap();
+        img_tx.send((view_id, req_id, blended_imgs)).await.unwrap();
     }
 }
 
@@ -495,45 +594,53 @@ async fn take_abort_await(task_handle: &mut Option<JoinHandle<()>>) {
     }
 }
 
-async fn main_loop(
-    mut msg_rx: mpsc::Receiver<ImgMsg>,
-    img_tx: mpsc::Sender<(Wrapping<usize>, Images)>,
-) {
-    let spec_caches = Arc::new(IdChDMap::default());
-    let wav_caches = Arc::new(IdChDMap::default());
-    let prev_params = Arc::new(RwLock::new(DrawParams::default()));
-    let mut req_id = Wrapping(0);
-    let mut task_handle: Option<JoinHandle<()>> = None;
+async fn main_loop(mut msg_rx: mpsc::Receiver<ImgMsg>, img_tx: ImgTx) {
+    let mut views: IntMap<ViewId, ViewState> = Default::default();
     while let Some(msg) = msg_rx.recv().await {
         match msg {
-            ImgMsg::Draw((id_ch_tuples, draw_params)) => {
+            ImgMsg::Draw((view_id, id_ch_tuples, draw_params)) => {
+                let view = views.entry(view_id).or_default();
                 {
-                    let mut prev_params_write = prev_params.write().await;
-                    take_abort_await(&mut task_handle).await;
+                    let mut prev_params_write = view.prev_params.write().await;
+                    take_abort_await(&mut view.task_handle).await;
                     if draw_params.is_params_for_different_img_cache(&prev_params_write) {
-                        spec_caches.clear();
-                        wav_caches.clear();
+                        view.spec_caches.clear();
+                        view.wav_caches.clear();
                     } else if draw_params.is_params_for_different_wav_cache(&prev_params_write) {
-                        wav_caches.clear();
+                        view.wav_caches.clear();
                     }
                     *prev_params_write = draw_params;
                 }
-                task_handle = Some(spawn(draw_imgs(
+                let job = Job::running(JobKind::Drawing);
+                let draw_task = draw_imgs(
                     id_ch_tuples,
-                    Arc::clone(&prev_params),
-                    Arc::clone(&spec_caches),
-                    Arc::clone(&wav_caches),
+                    Arc::clone(&view.prev_params),
+                    Arc::clone(&view.spec_caches),
+                    Arc::clone(&view.wav_caches),
                     img_tx.clone(),
-                    req_id,
-                )));
-                req_id += 1;
+                    (view_id, view.req_id),
+                );
+                // the job is dropped when the task is finished or aborted
+                view.task_handle = Some(spawn(async move {
+                    let _job = job;
+                    draw_task.await
+                }));
+                view.req_id += 1;
             }
             ImgMsg::Remove(id_ch_tuples) => {
-                take_abort_await(&mut task_handle).await;
-                id_ch_tuples.par_iter().for_each(|tup| {
-                    spec_caches.remove(tup);
-                    wav_caches.remove(tup);
-                });
+                for view in views.values_mut() {
+                    take_abort_await(&mut view.task_handle).await;
+                    id_ch_tuples.par_iter().for_each(|tup| {
+                        view.spec_caches.remove(tup);
+                        view.wav_caches.remove(tup);
+                    });
+                }
+            }
+            ImgMsg::RemoveView(view_id) => {
+                if let Some(mut view) = views.remove(&view_id) {
+                    take_abort_await(&mut view.task_handle).await;
+                }
+                RECEIVED_IMGS.lock().remove(&view_id);
             }
         }
     }
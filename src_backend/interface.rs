@@ -6,7 +6,7 @@
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
 
-use crate::{GuardClippingMode, IdChValueVec, IdChVec, SpecSetting};
+use crate::{GuardClippingMode, IdChValueVec, IdChVec, InitialView, SpecSetting};
 
 #[napi(object)]
 pub struct UserSettingsOptionals {
@@ -16,8 +16,19 @@ pub struct UserSettingsOptionals {
     #[napi(js_name = "dBRange")]
     pub dB_range: Option<f64>,
 
+    /// dB/octave
+    pub display_tilt: Option<f64>,
+
     pub common_guard_clipping: Option<GuardClippingMode>,
     pub common_normalize: Option<serde_json::Value>,
+
+    pub seek_increments: Option<SeekIncrements>,
+
+    /// cpal host forced by the user (see listAudioHosts). null for the default host.
+    pub audio_host: Option<String>,
+
+    /// schema version of the stored settings (see settings.rs)
+    pub settings_version: Option<u32>,
 }
 
 #[napi(object)]
@@ -28,14 +39,489 @@ pub struct UserSettings {
     #[napi(js_name = "dBRange")]
     pub dB_range: f64,
 
+    /// dB/octave
+    pub display_tilt: f64,
+
     pub common_guard_clipping: GuardClippingMode,
     pub common_normalize: serde_json::Value,
+
+    pub seek_increments: SeekIncrements,
+
+    pub audio_host: Option<String>,
+
+    pub settings_version: u32,
+}
+
+/// amounts (sec) of seeking by the keyboard shortcuts
+#[napi(object)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SeekIncrements {
+    pub small: f64,
+    pub medium: f64,
+    pub large: f64,
+}
+
+impl SeekIncrements {
+    pub const DEFAULT: Self = SeekIncrements {
+        small: 0.1,
+        medium: 1.,
+        large: 5.,
+    };
+
+    pub fn is_valid(&self) -> bool {
+        [self.small, self.medium, self.large]
+            .iter()
+            .all(|x| x.is_finite() && *x > 0.)
+    }
+
+    pub fn get(&self, amount_kind: SeekAmountKind) -> f64 {
+        match amount_kind {
+            SeekAmountKind::Small => self.small,
+            SeekAmountKind::Medium => self.medium,
+            SeekAmountKind::Large => self.large,
+        }
+    }
+}
+
+impl Default for SeekIncrements {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[napi(string_enum)]
+#[derive(Debug, Eq, PartialEq)]
+pub enum SeekAmountKind {
+    Small,
+    Medium,
+    Large,
+}
+
+/// how the view follows the playhead during playback
+#[napi(string_enum)]
+#[derive(Debug, Eq, PartialEq)]
+pub enum FollowMode {
+    /// turn the page when the playhead reaches the end of the view
+    Page,
+    /// keep the playhead at the center of the view
+    Continuous,
+}
+
+impl FollowMode {
+    /// start sec of the view following the playhead at position_sec.
+    /// The view is snapped not to go beyond 0 ~ max_sec (the end of the longest track).
+    pub fn scroll_target(&self, position_sec: f64, view_width_sec: f64, max_sec: f64) -> f64 {
+        let start_sec = match self {
+            FollowMode::Page => (position_sec / view_width_sec).floor() * view_width_sec,
+            FollowMode::Continuous => position_sec - view_width_sec / 2.,
+        };
+        start_sec.min(max_sec - view_width_sec).max(0.)
+    }
+}
+
+/// position under the cursor
+#[napi(object)]
+pub struct CursorPosition {
+    pub sec: f64,
+    /// sample index with the fractional part (integer if snapped to the sample)
+    pub samples: f64,
+}
+
+/// spectrum of a spectrogram frame captured by captureSpectrumSnapshot
+#[napi(object)]
+#[derive(Clone)]
+pub struct SpectrumSnapshot {
+    /// unique name of the snapshot
+    pub name: String,
+    pub id_ch: String,
+    pub sec: f64,
+    /// center frequency of each frequency bin (or mel bin)
+    pub hz: Vec<f64>,
+    #[napi(js_name = "dB")]
+    pub dB: Vec<f64>,
+}
+
+#[napi(object)]
+pub struct AddedTrack {
+    pub id: u32,
+    /// suggested view skipping the leading and trailing silence,
+    /// with the amp range fitted to the 99.9th percentile of the amplitude
+    pub initial_view: InitialView,
+}
+
+/// track of the session file imported by importSession
+#[napi(object)]
+pub struct ImportedTrack {
+    /// id in the session file
+    pub session_id: u32,
+    /// id in the current session
+    pub id: u32,
+    /// false if the file is already open (Append mode), so the open track is used
+    pub is_new: bool,
+}
+
+#[napi(object)]
+pub struct ImportedSession {
+    pub tracks: Vec<ImportedTrack>,
+    /// paths of the tracks which are not found or failed to open
+    pub missing_paths: Vec<String>,
+    /// markers of the session file with trackId mapped to the current session
+    pub markers: Vec<serde_json::Value>,
+    /// annotations of the session file with trackId mapped to the current session
+    pub annotations: Vec<serde_json::Value>,
+}
+
+/// result of a request coalesced by coalescer.rs
+#[napi(object)]
+pub struct CoalescedBuffer {
+    /// if a newer request for the same target superseded this request (buf is empty)
+    pub is_stale: bool,
+    pub buf: Buffer,
+}
+
+impl CoalescedBuffer {
+    pub fn stale() -> Self {
+        CoalescedBuffer {
+            is_stale: true,
+            buf: Buffer::from(Vec::new()),
+        }
+    }
+}
+
+/// vertical layout (px) of the overview image drawn by getOverview with the same height and dpr
+#[napi(object)]
+pub struct OverviewDrawingInfo {
+    /// top of each channel drawn
+    pub ch_tops: Vec<u32>,
+    pub ch_height: u32,
+    /// height of the limiter gain lanes at the top and the bottom of each channel.
+    /// zero if the limiter gain is not drawn.
+    pub gain_lane_height: u32,
+    /// dB axis markers [position, label] of the upper limiter gain lanes
+    /// (0: top, 1: bottom of the lane). The lower lanes are upside down (1 - position).
+    pub gain_axis_markers: serde_json::Value,
+}
+
+/// information for the renderer drawing the waveform slice
+#[napi(object)]
+pub struct WavDrawingInfo {
+    /// if a newer request for the same channel superseded this request (the others are null)
+    pub is_stale: bool,
+    /// class of each pixel column (0: normal, 1: limited, 2: clipped).
+    /// null if the track is neither clipped nor limited.
+    pub pixel_classes: Option<Buffer>,
+    /// envelope of the channel before the normalization and guard clipping (limiting),
+    /// to draw the ghost of the original waveform.
+    /// null if not requested or if neither of them changes the channel.
+    pub pre_envelope: Option<WavEnvelope>,
+    /// envelope of the processed channel, with the same pixel columns as pre_envelope
+    pub post_envelope: Option<WavEnvelope>,
+}
+
+impl WavDrawingInfo {
+    pub fn stale() -> Self {
+        WavDrawingInfo {
+            is_stale: true,
+            pixel_classes: None,
+            pre_envelope: None,
+            post_envelope: None,
+        }
+    }
+}
+
+/// min/max amplitude of each pixel column of the waveform
+#[napi(object)]
+pub struct WavEnvelope {
+    pub min: Vec<f64>,
+    pub max: Vec<f64>,
+}
+
+impl From<(Vec<f32>, Vec<f32>)> for WavEnvelope {
+    fn from((min, max): (Vec<f32>, Vec<f32>)) -> Self {
+        WavEnvelope {
+            min: min.into_iter().map(|x| x as f64).collect(),
+            max: max.into_iter().map(|x| x as f64).collect(),
+        }
+    }
 }
 
 #[napi(object)]
 pub struct PlayerState {
     pub is_playing: bool,
     pub position_sec: f64,
+    /// if the output limiter is reducing the gain (player-limiter-active indicator)
+    pub is_limiter_active: bool,
+    pub err: String,
+    /// name of the current output device
+    pub device_name: String,
+    /// number of the automatic output device changes (e.g. the device was unplugged).
+    /// The frontend emits player-device-changed when it increases.
+    pub device_change_count: u32,
+    /// monitor dim (attenuation by 20 dB)
+    pub monitor_dim: bool,
+    pub monitor_mute: bool,
+    /// calibrated monitor gain (dB) applied on top of the volume
+    #[napi(js_name = "referenceLeveldB")]
+    pub reference_level_dB: f64,
+}
+
+/// transport of the player for mirroring by external tools (e.g. OSC bridges)
+#[napi(object)]
+pub struct TransportState {
+    pub is_playing: bool,
+    pub position_sec: f64,
+    /// playing speed relative to the track (for extrapolating the position while playing)
+    pub speed: f64,
+    pub loop_start_sec: Option<f64>,
+    pub loop_end_sec: Option<f64>,
+    /// ids of the tracks loaded to the player
+    pub active_track_ids: Vec<u32>,
+    /// increased on every discontinuous change of the transport
+    /// (play/pause, seek, track change, loop change, jump back at the loop end, end of the tracks).
+    /// The frontend emits transport-changed when it changes.
+    pub revision: u32,
+}
+
+/// format of the output stream negotiated with the output device
+#[napi(object)]
+pub struct AudioStreamInfo {
+    pub device_name: String,
+    /// e.g. "f32", "i16"
+    pub sample_format: String,
+    pub sample_rate: u32,
+    pub channels: u32,
+    pub is_float: bool,
+    /// if TPDF dither is added to the playback (integer sample formats of 24 bits or less)
+    pub is_dithered: bool,
+    /// [min, max] buffer size (frames) supported by the device. null if unknown.
+    pub buffer_size_range: Option<Vec<u32>>,
+}
+
+/// buffer underruns (dropouts) of the output stream
+#[napi(object)]
+pub struct BufferHealthInfo {
+    /// number of the underruns detected since the app started
+    pub n_underruns: u32,
+    /// buffer size (frames) of the output stream. null for the default of the device.
+    pub buffer_size: Option<u32>,
+    /// if the buffer size is doubled on repeated underruns
+    pub adaptive: bool,
+}
+
+/// global time selection shared by all tracks
+#[napi(object)]
+#[derive(Clone, Copy)]
+pub struct Selection {
+    pub start_sec: f64,
+    pub end_sec: f64,
+}
+
+#[napi(object)]
+pub struct SelectionStats {
+    #[napi(js_name = "globalLUFS")]
+    pub global_lufs: f64,
+    #[napi(js_name = "rmsdB")]
+    pub rms_dB: f64,
+    #[napi(js_name = "maxPeakdB")]
+    pub max_peak_dB: f64,
+}
+
+#[napi(object)]
+pub struct SpecTileGrid {
+    /// width and height of a tile (px)
+    pub tile_size: u32,
+    /// number of tiles along time
+    pub n_tiles_x: u32,
+    /// number of tiles along frequency
+    pub n_tiles_y: u32,
+    /// time resolution of the spectrogram at zoom level 0
+    pub sec_per_frame: f64,
+    /// if the time resolution was reduced to keep the spectrogram under the memory limit
+    pub is_resolution_reduced: bool,
+}
+
+/// STFT parameters actually used for the spectrogram of a track
+#[napi(object)]
+pub struct EffectiveStftParams {
+    pub win_length: u32,
+    pub hop_length: u32,
+    pub n_fft: u32,
+    /// frequency resolution of the FFT (Hz/bin, before the mel filterbank)
+    pub hz_per_bin: f64,
+    /// time resolution (ms/frame)
+    pub ms_per_frame: f64,
+    /// if hop_length was increased to keep the spectrogram under the memory limit
+    pub is_resolution_reduced: bool,
+}
+
+#[napi(object)]
+pub struct JobCount {
+    pub queued: u32,
+    pub running: u32,
+}
+
+#[napi(object)]
+pub struct BackendBusyState {
+    /// calculating specs or greys
+    pub spec_update: JobCount,
+    /// loading tracks, normalization, guard clipping, and other analyses
+    pub stats: JobCount,
+    /// writing files
+    pub export: JobCount,
+    /// drawing images
+    pub drawing: JobCount,
+    /// rendering the playback previews (time-stretch and pitch-shift)
+    pub playback: JobCount,
+    /// if any job is queued or running
+    pub is_busy: bool,
+}
+
+/// number and size of the temporary files registered in tempfiles.rs
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct TempFilesUsage {
+    pub n_files: u32,
+    pub size_mb: f64,
+}
+
+/// progress of a job of n_total items (e.g. files to transcode or to probe, or pairs to compare)
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct JobProgress {
+    pub n_done: u32,
+    pub n_total: u32,
+}
+
+impl JobProgress {
+    pub const fn new() -> Self {
+        JobProgress {
+            n_done: 0,
+            n_total: 0,
+        }
+    }
+}
+
+/// progress of the spectrogram of a channel being calculated
+#[napi(object)]
+pub struct SpecProgress {
+    pub id_ch: String,
+    /// fraction of the STFT frames done (0 ~ 1)
+    pub fraction: f64,
+}
+
+#[napi(object)]
+pub struct SpecProgressState {
+    pub progress: Vec<SpecProgress>,
+    /// incremented on every change
+    pub version: u32,
+}
+
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct DownloadProgress {
+    /// id of the track to be added from the url
+    pub track_id: u32,
+    pub url: String,
+    pub downloaded_bytes: f64,
+    /// None if the server doesn't tell the content length
+    pub total_bytes: Option<f64>,
+}
+
+/// result of adding a track from a URL.
+/// Either download_err or decode_err is non-empty if failed.
+#[napi(object)]
+pub struct UrlTrackResult {
+    pub track_id: u32,
+    pub url: String,
+    /// path of the downloaded file in the cache
+    pub path: String,
+    pub download_err: String,
+    pub decode_err: String,
+}
+
+/// a color of the colorbar and its dB value
+#[napi(object)]
+pub struct ColorbarEntry {
+    #[napi(js_name = "dB")]
+    pub dB: f64,
+    /// [r, g, b, a]
+    pub rgba: Vec<u8>,
+}
+
+/// magnitude response of the DSP applied to the playback (for plotting the coloration)
+#[napi(object)]
+pub struct PlaybackChainResponse {
+    /// log-spaced frequencies
+    pub hzs: Vec<f64>,
+    /// magnitude (dB) at hzs of each channel of the impulse response
+    /// (a single flat channel if the impulse response is disabled)
+    #[napi(js_name = "magnitudesdB")]
+    pub magnitudes_dB: Vec<Vec<f64>>,
+    /// ceiling of the output limiter (dBFS). null if the limiter is disabled.
+    /// The limiter doesn't change the magnitude response below the ceiling.
+    #[napi(js_name = "limiterCeilingdB")]
+    pub limiter_ceiling_dB: Option<f64>,
+}
+
+/// frequency axis of the spectrogram image of a track
+#[napi(object)]
+pub struct TrackFreqInfo {
+    /// [min_hz, max_hz] spanned by the image.
+    /// Differs among the tracks only if the frequency axis is unlocked.
+    pub hz_range: Vec<f64>,
+    pub nyquist_hz: f64,
+    /// ratio of the height (from the top) of the zero-padded region above the Nyquist frequency
+    /// (to be hatched). 0 if the image has no such region.
+    pub padding_ratio: f64,
+}
+
+#[napi(object)]
+pub struct TrackGroup {
+    pub group_id: u32,
+    /// the value of the key (e.g. folder path) shared by the tracks
+    pub name: String,
+    pub track_ids: Vec<u32>,
+}
+
+/// tracks paired by autoPairTracks for A/B comparison
+#[napi(object)]
+pub struct TrackPair {
+    /// the part of the file names matched by "*" of the patterns
+    pub key: String,
+    pub track_id_a: u32,
+    pub track_id_b: u32,
+}
+
+/// spectral centroid of each frame for drawing a brightness curve over the spectrogram
+#[napi(object)]
+pub struct CentroidTimeseries {
+    /// hop (sec) actually used. The frame i is centered at i * hopSec.
+    pub hop_sec: f64,
+    /// spectral centroid (Hz) of each frame. NaN for the silent frames.
+    pub hz: Vec<f64>,
+}
+
+#[napi(string_enum)]
+#[derive(Debug, Eq, PartialEq)]
+pub enum ClipboardContentKind {
+    /// PNG of the waveform/spectrogram with the axes
+    Image,
+    /// WAV of the original audio
+    Audio,
+}
+
+/// temporary files of the selection to be put on the clipboard. null if not requested
+#[napi(object)]
+pub struct SelectionClipboardFiles {
+    pub image_path: Option<String>,
+    pub audio_path: Option<String>,
+}
+
+#[napi(object)]
+pub struct TranscodeResult {
+    pub track_id: u32,
+    /// output path. empty if failed
+    pub path: String,
     pub err: String,
 }
 
@@ -89,3 +575,23 @@ pub fn parse_id_ch_tuples(id_ch_strs: Vec<String>) -> Result<IdChVec> {
     }
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scroll_target_works() {
+        let page = FollowMode::Page;
+        assert_eq!(page.scroll_target(3.5, 2., 10.), 2.);
+        assert_eq!(page.scroll_target(4., 2., 10.), 4.);
+        // the last page is snapped to the end
+        assert_eq!(page.scroll_target(9.5, 3., 10.), 7.);
+        assert_eq!(page.scroll_target(1., 20., 10.), 0.);
+
+        let continuous = FollowMode::Continuous;
+        assert_eq!(continuous.scroll_target(5., 2., 10.), 4.);
+        assert_eq!(continuous.scroll_target(0.5, 2., 10.), 0.);
+        assert_eq!(continuous.scroll_target(9.5, 2., 10.), 8.);
+    }
+}
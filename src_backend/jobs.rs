@@ -0,0 +1,144 @@
+//! Counts of background jobs for the busy indicator of the frontend,
+//! and the renderer session for cancelling the stale jobs after reloading
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use napi::bindgen_prelude::spawn_blocking;
+use napi::tokio::task::JoinHandle;
+
+use crate::interface::{BackendBusyState, JobCount};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobKind {
+    /// calculating specs or greys
+    SpecUpdate,
+    /// loading tracks, normalization, guard clipping, and other analyses
+    Stats,
+    /// writing files
+    Export,
+    /// drawing images
+    Drawing,
+    /// rendering the playback previews (time-stretch and pitch-shift)
+    Playback,
+}
+
+const N_KINDS: usize = 5;
+
+/// generation of the renderer session. Increased by init (i.e. reloading the renderer).
+static SESSION: AtomicU32 = AtomicU32::new(0);
+
+/// (queued, running) for each JobKind
+static COUNTS: [(AtomicU32, AtomicU32); N_KINDS] = [
+    (AtomicU32::new(0), AtomicU32::new(0)),
+    (AtomicU32::new(0), AtomicU32::new(0)),
+    (AtomicU32::new(0), AtomicU32::new(0)),
+    (AtomicU32::new(0), AtomicU32::new(0)),
+    (AtomicU32::new(0), AtomicU32::new(0)),
+];
+
+/// Token of the renderer session in which a command is called.
+/// The commands check it not to apply stale results to the session after reloading
+/// (and to skip the remaining work).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Session(u32);
+
+impl Session {
+    pub fn current() -> Self {
+        Session(SESSION.load(Ordering::Acquire))
+    }
+
+    /// true if the renderer is reloaded after this session began.
+    /// Checking it while holding TRACK_LIST or TM guarantees that init is not resetting them.
+    pub fn is_stale(self) -> bool {
+        SESSION.load(Ordering::Acquire) != self.0
+    }
+
+    /// Should be called by init while holding the write locks of TRACK_LIST and TM.
+    pub fn begin_new() {
+        SESSION.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// RAII guard counting a job. The count is decreased when dropped (finished or aborted).
+pub struct Job {
+    kind: JobKind,
+    is_running: bool,
+}
+
+impl Job {
+    pub fn queue(kind: JobKind) -> Self {
+        COUNTS[kind as usize].0.fetch_add(1, Ordering::AcqRel);
+        Job {
+            kind,
+            is_running: false,
+        }
+    }
+
+    pub fn running(kind: JobKind) -> Self {
+        COUNTS[kind as usize].1.fetch_add(1, Ordering::AcqRel);
+        Job {
+            kind,
+            is_running: true,
+        }
+    }
+
+    pub fn start(&mut self) {
+        if !self.is_running {
+            let (queued, running) = &COUNTS[self.kind as usize];
+            queued.fetch_sub(1, Ordering::AcqRel);
+            running.fetch_add(1, Ordering::AcqRel);
+            self.is_running = true;
+        }
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        let (queued, running) = &COUNTS[self.kind as usize];
+        if self.is_running {
+            running.fetch_sub(1, Ordering::AcqRel);
+        } else {
+            queued.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+/// spawn_blocking counting the job as queued until it starts to run
+pub fn spawn_blocking_job<F, R>(kind: JobKind, f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let mut job = Job::queue(kind);
+    spawn_blocking(move || {
+        job.start();
+        f()
+    })
+}
+
+fn job_count(kind: JobKind) -> JobCount {
+    let (queued, running) = &COUNTS[kind as usize];
+    JobCount {
+        queued: queued.load(Ordering::Acquire),
+        running: running.load(Ordering::Acquire),
+    }
+}
+
+pub fn busy_state() -> BackendBusyState {
+    let spec_update = job_count(JobKind::SpecUpdate);
+    let stats = job_count(JobKind::Stats);
+    let export = job_count(JobKind::Export);
+    let drawing = job_count(JobKind::Drawing);
+    let playback = job_count(JobKind::Playback);
+    let is_busy = [&spec_update, &stats, &export, &drawing, &playback]
+        .iter()
+        .any(|count| count.queued + count.running > 0);
+    BackendBusyState {
+        spec_update,
+        stats,
+        export,
+        drawing,
+        playback,
+        is_busy,
+    }
+}
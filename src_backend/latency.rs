@@ -0,0 +1,105 @@
+//! Round-trip latency measurement (loopback test) playing a chirp through the player
+//! while recording it from an input device
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, Sample, SampleFormat, SizedSample};
+use log::error;
+use napi::tokio::sync::oneshot;
+use parking_lot::Mutex;
+
+use crate::player::{self, PlayerCommand};
+use crate::{estimate_delay, log_chirp, LatencyMeasurement, CHIRP_SEC};
+
+/// time for the input stream to be stable before playing the chirp
+const WARM_UP: Duration = Duration::from_millis(300);
+/// maximum latency to be measured
+const MAX_LATENCY: Duration = Duration::from_secs(1);
+
+pub fn input_device_names() -> Vec<String> {
+    player::audio_host()
+        .input_devices()
+        .map(|devices| devices.filter_map(|device| device.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+fn find_input_device(name: Option<&str>) -> Option<cpal::Device> {
+    let host = player::audio_host();
+    match name {
+        Some(name) => host
+            .input_devices()
+            .ok()?
+            .find(|device| device.name().is_ok_and(|x| x == name)),
+        None => host.default_input_device(),
+    }
+}
+
+/// input stream appending the first channel to recorded
+fn build_input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    recorded: Arc<Mutex<Vec<f32>>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let n_ch = config.channels as usize;
+    device.build_input_stream(
+        config,
+        move |data: &[T], _| {
+            recorded
+                .lock()
+                .extend(data.chunks(n_ch).map(|frame| frame[0].to_sample::<f32>()));
+        },
+        |err| error!("input stream error: {}", err),
+        None,
+    )
+}
+
+/// Should be called in a blocking thread.
+/// input_device: name of the input device. The default input device is used if None.
+pub fn measure_output_latency(input_device: Option<&str>) -> Result<LatencyMeasurement, String> {
+    let device = find_input_device(input_device).ok_or("The input device doesn't exist.")?;
+    let supported_config = device.default_input_config().map_err(|e| e.to_string())?;
+    let sr = supported_config.sample_rate().0;
+    let config = supported_config.config();
+    let recorded = Arc::new(Mutex::new(Vec::new()));
+    let stream = match supported_config.sample_format() {
+        SampleFormat::F32 => build_input_stream::<f32>(&device, &config, Arc::clone(&recorded)),
+        SampleFormat::I16 => build_input_stream::<i16>(&device, &config, Arc::clone(&recorded)),
+        SampleFormat::I32 => build_input_stream::<i32>(&device, &config, Arc::clone(&recorded)),
+        SampleFormat::U16 => build_input_stream::<u16>(&device, &config, Arc::clone(&recorded)),
+        format => return Err(format!("Unsupported sample format {}.", format)),
+    }
+    .map_err(|e| e.to_string())?;
+    stream.play().map_err(|e| e.to_string())?;
+    std::thread::sleep(WARM_UP);
+
+    let chirp = log_chirp(sr);
+    let (played_tx, played_rx) = oneshot::channel();
+    player::blocking_send(PlayerCommand::PlayTestSignal {
+        frames: chirp.iter().map(|&x| x.into()).collect(),
+        sr,
+        played_tx,
+    });
+    played_rx
+        .blocking_recv()
+        .map_err(|_| "The player doesn't respond.")?;
+    let i_played = recorded.lock().len();
+    std::thread::sleep(Duration::from_secs_f64(CHIRP_SEC) + MAX_LATENCY);
+    drop(stream);
+
+    let recorded = recorded.lock();
+    let (delay, confidence) = recorded
+        .get(i_played..)
+        .and_then(|recorded| estimate_delay(chirp.view(), recorded.into()))
+        .ok_or("The test signal isn't detected. Check the loopback connection and the volume.")?;
+    Ok(LatencyMeasurement {
+        latency_ms: delay as f64 / sr as f64 * 1000.,
+        confidence: confidence as f64,
+        sr,
+    })
+}
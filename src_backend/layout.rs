@@ -0,0 +1,258 @@
+//! Authoritative layout of the channel lanes of the tracks (order, heights, and collapsed state).
+//! The frontend mutates it by commands and reads it by a single query,
+//! so drawing requests can be validated against it and sessions can restore the exact layout.
+
+use std::collections::BTreeMap;
+
+use napi_derive::napi;
+
+pub const DEFAULT_LANE_HEIGHT: u32 = 250;
+pub const MAX_LANE_HEIGHT: u32 = 5000;
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelLane {
+    /// channel of the track shown in the lane
+    pub ch: u32,
+    /// height of the lane (px)
+    pub height: u32,
+}
+
+/// lanes of a track in the display order
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackLayout {
+    pub track_id: u32,
+    pub collapsed: bool,
+    pub lanes: Vec<ChannelLane>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct TrackLanes {
+    /// channels in the display order
+    order: Vec<usize>,
+    /// height of the lane of each channel
+    heights: Vec<u32>,
+    collapsed: bool,
+}
+
+impl TrackLanes {
+    fn new(n_ch: usize, height: u32) -> Self {
+        TrackLanes {
+            order: (0..n_ch).collect(),
+            heights: vec![height; n_ch],
+            collapsed: false,
+        }
+    }
+
+    fn n_ch(&self) -> usize {
+        self.heights.len()
+    }
+}
+
+fn check_height(height: u32) -> Result<(), String> {
+    if (1..=MAX_LANE_HEIGHT).contains(&height) {
+        Ok(())
+    } else {
+        Err(format!(
+            "The lane height should be between 1 and {}.",
+            MAX_LANE_HEIGHT
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct LayoutModel {
+    tracks: BTreeMap<usize, TrackLanes>,
+    /// height of the lanes of the tracks added later
+    default_height: u32,
+}
+
+impl LayoutModel {
+    pub const fn new() -> Self {
+        LayoutModel {
+            tracks: BTreeMap::new(),
+            default_height: DEFAULT_LANE_HEIGHT,
+        }
+    }
+
+    /// Add the default lanes of new tracks, reset the lanes of the tracks
+    /// whose number of channels changed, and remove the lanes of the removed tracks.
+    /// tracks: (track id, number of channels) of all the current tracks
+    pub fn sync(&mut self, tracks: impl IntoIterator<Item = (usize, usize)>) {
+        let tracks: BTreeMap<_, _> = tracks.into_iter().collect();
+        self.tracks
+            .retain(|id, lanes| tracks.get(id) == Some(&lanes.n_ch()));
+        for (id, n_ch) in tracks {
+            self.tracks
+                .entry(id)
+                .or_insert_with(|| TrackLanes::new(n_ch, self.default_height));
+        }
+    }
+
+    pub fn layout(&self) -> Vec<TrackLayout> {
+        self.tracks
+            .iter()
+            .map(|(&id, lanes)| TrackLayout {
+                track_id: id as u32,
+                collapsed: lanes.collapsed,
+                lanes: lanes
+                    .order
+                    .iter()
+                    .map(|&ch| ChannelLane {
+                        ch: ch as u32,
+                        height: lanes.heights[ch],
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// false if the track doesn't exist
+    pub fn is_collapsed(&self, id: usize) -> bool {
+        self.tracks.get(&id).is_some_and(|lanes| lanes.collapsed)
+    }
+
+    fn lanes_mut(&mut self, id: usize) -> Result<&mut TrackLanes, String> {
+        self.tracks
+            .get_mut(&id)
+            .ok_or_else(|| "The track doesn't exist.".into())
+    }
+
+    pub fn set_lane_height(&mut self, id: usize, ch: usize, height: u32) -> Result<(), String> {
+        check_height(height)?;
+        let lanes = self.lanes_mut(id)?;
+        *lanes
+            .heights
+            .get_mut(ch)
+            .ok_or("The channel doesn't exist.")? = height;
+        Ok(())
+    }
+
+    /// set the height of all the lanes including those of the tracks added later
+    pub fn set_all_lane_heights(&mut self, height: u32) -> Result<(), String> {
+        check_height(height)?;
+        self.default_height = height;
+        self.tracks
+            .values_mut()
+            .for_each(|lanes| lanes.heights.fill(height));
+        Ok(())
+    }
+
+    /// move the lane at from_index to to_index of the display order
+    pub fn move_lane(
+        &mut self,
+        id: usize,
+        from_index: usize,
+        to_index: usize,
+    ) -> Result<(), String> {
+        let lanes = self.lanes_mut(id)?;
+        if from_index >= lanes.n_ch() || to_index >= lanes.n_ch() {
+            return Err("The lane index is out of range.".into());
+        }
+        let ch = lanes.order.remove(from_index);
+        lanes.order.insert(to_index, ch);
+        Ok(())
+    }
+
+    pub fn set_collapsed(&mut self, id: usize, collapsed: bool) -> Result<(), String> {
+        self.lanes_mut(id)?.collapsed = collapsed;
+        Ok(())
+    }
+
+    /// Restore the layout (e.g. stored in a session).
+    /// Tracks not in the layout are kept as they are.
+    /// Nothing is changed if any of the track layouts is invalid.
+    pub fn restore(&mut self, layout: &[TrackLayout]) -> Result<(), String> {
+        let mut restored = Vec::with_capacity(layout.len());
+        for track_layout in layout {
+            let id = track_layout.track_id as usize;
+            let n_ch = self
+                .tracks
+                .get(&id)
+                .ok_or_else(|| format!("The track {} doesn't exist.", id))?
+                .n_ch();
+            let order: Vec<_> = track_layout.lanes.iter().map(|x| x.ch as usize).collect();
+            let mut heights = vec![0; n_ch];
+            for lane in &track_layout.lanes {
+                check_height(lane.height)?;
+                if let Some(height) = heights.get_mut(lane.ch as usize) {
+                    *height = lane.height;
+                }
+            }
+            let mut sorted = order.clone();
+            sorted.sort_unstable();
+            if !sorted.iter().copied().eq(0..n_ch) {
+                return Err(format!(
+                    "The lanes of the track {} should have each channel exactly once.",
+                    id
+                ));
+            }
+            restored.push((
+                id,
+                TrackLanes {
+                    order,
+                    heights,
+                    collapsed: track_layout.collapsed,
+                },
+            ));
+        }
+        self.tracks.extend(restored);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_model_works() {
+        let mut model = LayoutModel::new();
+        model.sync([(0, 2), (3, 1)]);
+        assert_eq!(model.layout().len(), 2);
+        assert_eq!(model.layout()[0].lanes[1].height, DEFAULT_LANE_HEIGHT);
+
+        model.set_lane_height(0, 1, 100).unwrap();
+        model.move_lane(0, 1, 0).unwrap();
+        model.set_collapsed(3, true).unwrap();
+        assert!(model.set_lane_height(0, 2, 100).is_err());
+        assert!(model.set_lane_height(0, 0, 0).is_err());
+        assert!(model.move_lane(3, 0, 1).is_err());
+        assert!(model.set_collapsed(1, true).is_err());
+        assert!(model.is_collapsed(3) && !model.is_collapsed(0));
+        let layout = model.layout();
+        assert_eq!(
+            layout[0].lanes,
+            [
+                ChannelLane { ch: 1, height: 100 },
+                ChannelLane {
+                    ch: 0,
+                    height: DEFAULT_LANE_HEIGHT
+                },
+            ]
+        );
+
+        // removed tracks and tracks with a different number of channels are reset
+        model.set_all_lane_heights(300).unwrap();
+        model.sync([(0, 2), (3, 2), (5, 1)]);
+        let synced = model.layout();
+        assert_eq!(synced[0].lanes[0], ChannelLane { ch: 1, height: 300 });
+        assert!(!synced[1].collapsed);
+        assert!(synced
+            .iter()
+            .flat_map(|x| &x.lanes)
+            .all(|x| x.height == 300));
+
+        // restore
+        let mut restored = LayoutModel::new();
+        restored.sync([(0, 2), (3, 2), (5, 1)]);
+        restored.restore(&layout[..1]).unwrap();
+        assert_eq!(restored.layout()[0], layout[0]);
+        let mut invalid = layout[0].clone();
+        invalid.lanes[1].ch = 1;
+        assert!(restored.restore(&[invalid]).is_err());
+        assert!(restored.restore(&layout[1..]).is_err());
+        assert_eq!(restored.layout()[0], layout[0]);
+    }
+}
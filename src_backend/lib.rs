@@ -3,29 +3,77 @@
 // need to statically link OpenBLAS on Windows
 extern crate blas_src;
 
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::LazyLock;
+use std::time::Duration;
 
-use log::LevelFilter;
+use identity_hash::{IntMap, IntSet};
+use itertools::izip;
+use log::{warn, LevelFilter};
 use napi::bindgen_prelude::*;
-use napi::tokio::{join, sync::RwLock as AsyncRwLock};
+use napi::tokio::join;
+use napi::tokio::sync::oneshot;
 use napi_derive::napi;
+use ndarray::ArrayView2;
 use parking_lot::RwLock as SyncRwLock;
+use rayon::prelude::*;
 use serde_json::json;
 use simple_logger::SimpleLogger;
 
+#[warn(dead_code)]
+mod acoustid;
 #[warn(dead_code)]
 mod backend;
 #[warn(dead_code)]
+mod coalescer;
+#[warn(dead_code)]
+mod crash;
+#[warn(dead_code)]
+mod download;
+#[warn(dead_code)]
 mod img_mgr;
 #[warn(dead_code)]
 mod interface;
 #[warn(dead_code)]
+mod jobs;
+#[warn(dead_code)]
+mod latency;
+#[warn(dead_code)]
+mod layout;
+#[warn(dead_code)]
+mod locks;
+#[warn(dead_code)]
+mod os;
+#[warn(dead_code)]
+mod output;
+#[warn(dead_code)]
 mod player;
+#[warn(dead_code)]
+mod remote;
+#[warn(dead_code)]
+mod script;
+#[warn(dead_code)]
+mod session_file;
+#[warn(dead_code)]
+mod settings;
+#[warn(dead_code)]
+mod tempfiles;
+#[warn(dead_code)]
+mod viewport_sync;
 
+use acoustid::TrackIdentity;
 use backend::*;
+use coalescer::{RequestKey, Ticket};
 use img_mgr::ImgMsg;
 use interface::*;
+use jobs::{spawn_blocking_job, JobKind, Session};
+use layout::{LayoutModel, TrackLayout};
+use locks::{LockRank, OrderedRwLock};
+use os::{MediaAction, NowPlaying};
 use player::{PlayerCommand, PlayerNotification};
+use session_file::{SessionFile, SessionImportMode};
+use viewport_sync::ViewportState;
 
 #[cfg(all(
     any(windows, unix),
@@ -36,14 +84,63 @@ use player::{PlayerCommand, PlayerNotification};
 #[global_allocator]
 static ALLOC: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
-static TRACK_LIST: LazyLock<AsyncRwLock<TrackList>> =
-    LazyLock::new(|| AsyncRwLock::new(TrackList::new()));
-static TM: LazyLock<AsyncRwLock<TrackManager>> =
-    LazyLock::new(|| AsyncRwLock::new(TrackManager::new()));
+const MB: usize = 1 << 20;
+/// limits the number of frames of get_centroid_timeseries
+const MIN_CENTROID_HOP_SEC: f64 = 0.001;
+/// directory in the temp dir for the files put on the clipboard
+const CLIPBOARD_DIR_NAME: &str = "thesia_clipboard";
+/// error of the commands cancelled by reloading the renderer
+const RELOADED_ERR_MSG: &str = "Cancelled by reloading the renderer.";
+/// the changed settings are written after they haven't changed for this duration
+const SETTINGS_DEBOUNCE: Duration = Duration::from_millis(500);
+/// the changed settings are written at least this often while they keep changing
+const SETTINGS_MAX_DELAY: Duration = Duration::from_secs(3);
+/// interval of checking if the changed settings should be written
+const SETTINGS_FLUSH_INTERVAL: Duration = Duration::from_millis(200);
+/// maximum length of the colormap requested by set_colormap_length
+const MAX_COLORMAP_LENGTH: u32 = 1 << 13;
+
+// Acquire TM before TRACK_LIST when holding both (see locks.rs).
+static TRACK_LIST: LazyLock<OrderedRwLock<TrackList>> =
+    LazyLock::new(|| OrderedRwLock::new(LockRank::TrackList, TrackList::new()));
+static TM: LazyLock<OrderedRwLock<TrackManager>> =
+    LazyLock::new(|| OrderedRwLock::new(LockRank::Tm, TrackManager::new()));
 
 // TODO: prevent making mistake not to update the values below. Maybe sth like auto-sync?
 static HZ_RANGE: SyncRwLock<(f32, f32)> = SyncRwLock::new((0., f32::INFINITY));
 static SPEC_SETTING: SyncRwLock<SpecSetting> = SyncRwLock::new(SpecSetting::new());
+static TRANSCODE_PROGRESS: SyncRwLock<JobProgress> = SyncRwLock::new(JobProgress::new());
+static PROBE_PROGRESS: SyncRwLock<JobProgress> = SyncRwLock::new(JobProgress::new());
+static COMPARE_PROGRESS: SyncRwLock<JobProgress> = SyncRwLock::new(JobProgress::new());
+/// set by cancel_probe_files. The files not probed yet are skipped.
+static PROBE_CANCELLED: AtomicBool = AtomicBool::new(false);
+/// progress of the downloads of all the running addTracksFromUrls calls (keyed by the track id)
+static DOWNLOAD_PROGRESS: SyncRwLock<Vec<DownloadProgress>> = SyncRwLock::new(Vec::new());
+static SEEK_INCREMENTS: SyncRwLock<SeekIncrements> = SyncRwLock::new(SeekIncrements::DEFAULT);
+/// tempo and meter for the time axis in bars/beats. None if the time axis is in seconds.
+static TEMPO_GRID: SyncRwLock<Option<TempoGrid>> = SyncRwLock::new(None);
+/// global time selection (start_sec, end_sec) consumed by stats, exports, and player looping
+static SELECTION: SyncRwLock<Option<(f64, f64)>> = SyncRwLock::new(None);
+/// tracks existing but not on screen when set_visible_tracks was called.
+/// Calculating their specs is deferred after the other tracks on loading or reloading.
+static OFFSCREEN_TRACK_IDS: SyncRwLock<Vec<usize>> = SyncRwLock::new(Vec::new());
+/// channel lane layout of the tracks. Synced to TRACK_LIST on every access.
+static LAYOUT: SyncRwLock<LayoutModel> = SyncRwLock::new(LayoutModel::new());
+static PLAYER_LOOPING: AtomicBool = AtomicBool::new(false);
+/// denoiser of the last set_player_denoise with (profile track id, playback version of the track).
+/// Changing only the amount reuses the noise profile.
+static DENOISE_PROFILE: SyncRwLock<Option<(usize, u64, SpectralDenoiser)>> = SyncRwLock::new(None);
+/// spectra captured for comparing them on one plot.
+/// Kept after the track is removed, and cleared only by clearSpectrumSnapshots.
+static SPECTRUM_SNAPSHOTS: SyncRwLock<Vec<SpectrumSnapshot>> = SyncRwLock::new(Vec::new());
+/// if the cursor positions are rounded to the nearest sample
+static SNAP_TO_SAMPLE: AtomicBool = AtomicBool::new(false);
+/// break of the frequency axis of each view (view_id as the key). No break if not exists.
+static FREQ_AXIS_BREAKS: LazyLock<SyncRwLock<IntMap<u32, FreqAxisBreak>>> =
+    LazyLock::new(Default::default);
+/// files to open passed by argv or the OS, which can arrive before init.
+/// Kept across init until take_pending_open_files.
+static PENDING_OPEN_FILES: SyncRwLock<Vec<String>> = SyncRwLock::new(Vec::new());
 
 fn _init_once() {
     rayon::ThreadPoolBuilder::new()
@@ -54,6 +151,46 @@ fn _init_once() {
         .with_level(LevelFilter::Info)
         .init()
         .unwrap();
+    crash::install_panic_hook(snapshot_backend_state);
+}
+
+/// backend state for the crash report without the paths of the tracks.
+/// The locked states are null because the panic can occur while they are locked.
+fn snapshot_backend_state() -> serde_json::Value {
+    let tracks = TRACK_LIST.try_read().ok().map(|tracklist| {
+        let tracks: Vec<_> = tracklist
+            .all_ids()
+            .into_iter()
+            .filter_map(|id| tracklist.get(id))
+            .collect();
+        let decoded_bytes: usize = tracks
+            .iter()
+            .map(|track| track.original_wavs().len() * std::mem::size_of::<f32>())
+            .sum();
+        json!({
+            "count": tracks.len(),
+            "sampleRates": tracks.iter().map(|track| track.sr()).collect::<Vec<_>>(),
+            "totalSec": tracks.iter().map(|track| track.sec()).sum::<f64>(),
+            "decodedMB": decoded_bytes as f64 / MB as f64,
+            "commonNormalize": serde_json::to_value(tracklist.common_normalize).ok(),
+        })
+    });
+    let spec = TM.try_read().ok().map(|tm| {
+        json!({
+            "setting": serde_json::to_value(&tm.setting).ok(),
+            "dBRange": tm.dB_range,
+            "displayTilt": tm.display_tilt,
+            "maxSpecMB": tm.max_spec_bytes.map(|bytes| bytes as f64 / MB as f64),
+        })
+    });
+    json!({
+        "tracks": tracks,
+        "spec": spec,
+        "seekIncrements": SEEK_INCREMENTS.try_read().map(|x| vec![x.small, x.medium, x.large]),
+        "selection": SELECTION.try_read().map(|x| *x),
+        "playerLooping": PLAYER_LOOPING.load(Ordering::Acquire),
+        "heldLocks": locks::held_locks(),
+    })
 }
 
 // On Windows, this cause hanging.
@@ -63,8 +200,40 @@ fn _napi_init() {
     _init_once();
 }
 
+/// Migrate the settings stored by the frontend to the current schema version.
+/// Should be called before init.
+#[napi]
+fn migrate_user_settings(user_settings: serde_json::Value) -> serde_json::Value {
+    settings::migrate_user_settings(user_settings)
+}
+
+/// Set the directory (app data dir) where the crash report is saved if the backend panics.
+/// Should be called before init.
+#[napi]
+fn set_crash_report_dir(dir: String) {
+    crash::set_report_dir(PathBuf::from(dir));
+}
+
+/// Take the crash report (JSON) saved by the previous run in the crash report dir
+/// to offer it to the user. The report is removed from the dir.
+/// null if the previous run didn't crash.
+#[napi]
+fn take_crash_report() -> Result<Option<String>> {
+    crash::take_report().map_err(|e| Error::new(Status::GenericFailure, e.to_string()))
+}
+
 #[napi]
-fn init(user_settings: UserSettingsOptionals) -> Result<UserSettings> {
+fn init(mut env: Env, user_settings: UserSettingsOptionals) -> Result<UserSettings> {
+    crash::record_command("init");
+    // the temporary files are removed when the process exits
+    static CLEANUP_HOOK_ADDED: AtomicBool = AtomicBool::new(false);
+    // and the changed settings are written
+    if !CLEANUP_HOOK_ADDED.swap(true, Ordering::AcqRel) {
+        env.add_env_cleanup_hook((), |_| {
+            flush_user_settings_or_warn();
+            tempfiles::clean_all()
+        })?;
+    }
     // On Windows, reloading cause restarting of renderer process.
     // (See killAndReload in src/main/menu.ts)
     // So INITIALIZED_ONCE may not be needed, but use it for defensive purpose.
@@ -76,12 +245,15 @@ fn init(user_settings: UserSettingsOptionals) -> Result<UserSettings> {
     }
 
     let user_settings = {
-        let mut tracklist = TRACK_LIST.blocking_write();
         let mut tm = TM.blocking_write();
+        let mut tracklist = TRACK_LIST.blocking_write();
+        // the pending commands of the previous session are cancelled
+        Session::begin_new();
         if !tracklist.is_empty() {
             *tracklist = TrackList::new();
             *tm = TrackManager::new();
         }
+        tracklist.clear_reserved_ids();
         if let Some(setting) = user_settings.spec_setting {
             tm.set_setting(&tracklist, setting.clone());
         }
@@ -89,6 +261,9 @@ fn init(user_settings: UserSettingsOptionals) -> Result<UserSettings> {
         if let Some(dB_range) = user_settings.dB_range {
             tm.set_dB_range(&tracklist, dB_range as f32);
         }
+        if let Some(display_tilt) = user_settings.display_tilt {
+            tm.set_display_tilt(&tracklist, display_tilt as f32);
+        }
         if let Some(mode) = user_settings.common_guard_clipping {
             tracklist.set_common_guard_clipping(mode);
         }
@@ -96,65 +271,589 @@ fn init(user_settings: UserSettingsOptionals) -> Result<UserSettings> {
             let target = serde_json::from_value(target)?;
             tracklist.set_common_normalize(target);
         }
+        if let Some(seek_increments) = user_settings.seek_increments {
+            if seek_increments.is_valid() {
+                *SEEK_INCREMENTS.write() = seek_increments;
+            }
+        }
+        // applied when the player (re)initializes the stream
+        if let Err(e) = player::set_audio_host(user_settings.audio_host.as_deref()) {
+            warn!("{} The default audio host is used.", e);
+        }
         UserSettings {
             spec_setting: tm.setting.clone(),
             blend: user_settings.blend.unwrap_or(0.5),
             dB_range: tm.dB_range as f64,
+            display_tilt: tm.display_tilt as f64,
             common_guard_clipping: tracklist.common_guard_clipping,
             common_normalize: serde_json::to_value(tracklist.common_normalize).unwrap(),
+            seek_increments: *SEEK_INCREMENTS.read(),
+            audio_host: player::audio_host_name(),
+            settings_version: user_settings
+                .settings_version
+                .map_or(settings::SETTINGS_VERSION, |v| {
+                    v.max(settings::SETTINGS_VERSION)
+                }),
         }
     };
     *HZ_RANGE.write() = (0., f32::INFINITY);
     *SPEC_SETTING.write() = user_settings.spec_setting.clone();
+    OFFSCREEN_TRACK_IDS.write().clear();
 
     img_mgr::spawn_task();
     player::spawn_task();
+    spawn_settings_flush_task();
     Ok(user_settings)
 }
 
+/// Set the store file of electron-settings where setUserSetting writes the changed settings.
+/// Should be called before init.
+#[napi]
+fn set_settings_store_path(path: String) {
+    settings::set_store_path(PathBuf::from(path));
+}
+
+/// Change the stored user setting. Returns immediately without touching the disk.
+/// The changed settings are written together after they stop changing for a moment
+/// (e.g. after dragging a slider), and on exit.
+#[napi]
+fn set_user_setting(key: String, value: serde_json::Value) {
+    settings::set_pending(key, value);
+}
+
+/// Write the changed settings now (e.g. before the window is closed)
+#[napi]
+fn flush_user_settings() -> Result<()> {
+    settings::flush()
+        .map(|_| ())
+        .map_err(|e| Error::from_reason(format!("Failed to write the settings: {}", e)))
+}
+
+/// Queue the files to open (passed by argv or the OS). Can be called before init.
+/// The paths already in the queue are ignored.
+#[napi]
+fn queue_open_files(paths: Vec<String>) {
+    let mut pending = PENDING_OPEN_FILES.write();
+    for path in paths {
+        if !pending.contains(&path) {
+            pending.push(path);
+        }
+    }
+}
+
+/// Take the files queued by queueOpenFiles in the order of arrival.
+/// Should be called after init, and whenever the files are queued after that.
+#[napi]
+fn take_pending_open_files() -> Vec<String> {
+    std::mem::take(&mut *PENDING_OPEN_FILES.write())
+}
+
+fn flush_user_settings_or_warn() {
+    if let Err(e) = settings::flush() {
+        warn!("Failed to write the settings: {}", e);
+    }
+}
+
+/// periodically write the changed settings in a blocking thread not to block the commands
+fn spawn_settings_flush_task() {
+    static SPAWNED: AtomicBool = AtomicBool::new(false);
+    if SPAWNED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    spawn(async {
+        let mut interval = napi::tokio::time::interval(SETTINGS_FLUSH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if settings::should_flush(SETTINGS_DEBOUNCE, SETTINGS_MAX_DELAY) {
+                let _ = spawn_blocking(flush_user_settings_or_warn).await;
+            }
+        }
+    });
+}
+
+/// Reserve the ids of the tracks to be added by addTracks.
+/// The ids are shared with the tracks added by scripts and session imports, so they never collide.
+#[napi]
+async fn reserve_track_ids(n: u32) -> Vec<u32> {
+    TRACK_LIST
+        .write()
+        .await
+        .reserve_ids(n as usize)
+        .into_iter()
+        .map(|id| id as u32)
+        .collect()
+}
+
+/// Returns the tracks added successfully with the suggested initial views framing them.
 #[napi]
-async fn add_tracks(id_list: Vec<u32>, path_list: Vec<String>) -> Vec<u32> {
+async fn add_tracks(id_list: Vec<u32>, path_list: Vec<String>) -> Vec<AddedTrack> {
+    crash::record_command("addTracks");
     assert!(!id_list.is_empty() && id_list.len() == path_list.len());
 
-    let added_ids = spawn_blocking(move || {
-        TRACK_LIST
-            .blocking_write()
-            .add_tracks(id_list.into_iter().map(|x| x as usize).collect(), path_list)
+    let session = Session::current();
+    let (added_ids, added_tracks) = spawn_blocking_job(JobKind::Stats, move || {
+        let mut tracklist = TRACK_LIST.blocking_write();
+        if session.is_stale() {
+            return Default::default();
+        }
+        let added_ids =
+            tracklist.add_tracks(id_list.into_iter().map(|x| x as usize).collect(), path_list);
+        let added_tracks = added_ids
+            .iter()
+            .map(|&id| AddedTrack {
+                id: id as u32,
+                initial_view: tracklist[id].initial_view(),
+            })
+            .collect();
+        (added_ids, added_tracks)
     })
     .await
     .unwrap();
-    let added_ids_u32 = added_ids.iter().map(|&x| x as u32).collect();
-    spawn_blocking(move || {
-        TM.blocking_write()
-            .add_tracks(&TRACK_LIST.blocking_read(), &added_ids);
+    add_tracks_to_tm(session, added_ids);
+    added_tracks
+}
+
+/// calculate the specs of the added tracks unless the renderer is reloaded
+fn add_tracks_to_tm(session: Session, added_ids: Vec<usize>) {
+    if added_ids.is_empty() {
+        return;
+    }
+    let (deferred_ids, added_ids) = partition_offscreen(added_ids);
+    spawn_blocking_job(JobKind::SpecUpdate, move || {
+        let mut tm = TM.blocking_write();
+        if !session.is_stale() {
+            let tracklist = TRACK_LIST.blocking_read();
+            tm.add_tracks(&tracklist, &added_ids);
+            tm.defer_specs(&tracklist, &deferred_ids);
+        }
     });
-    added_ids_u32
+    spawn_deferred_spec_job(session);
+}
+
+/// (offscreen ids, the other ids) by the last set_visible_tracks
+fn partition_offscreen(ids: Vec<usize>) -> (Vec<usize>, Vec<usize>) {
+    let offscreen_ids = OFFSCREEN_TRACK_IDS.read();
+    ids.into_iter().partition(|id| offscreen_ids.contains(id))
+}
+
+/// Calculate the deferred specs in the background after the specs of the other tracks.
+/// The greys are updated by applyTrackListChanges or setVisibleTracks.
+fn spawn_deferred_spec_job(session: Session) {
+    spawn_blocking_job(JobKind::SpecUpdate, move || {
+        let job = TM.blocking_read().deferred_spec_job(None);
+        if job.is_empty() || session.is_stale() {
+            return;
+        }
+        let specs = job.run(&TRACK_LIST.blocking_read());
+        let mut tm = TM.blocking_write();
+        if !session.is_stale() {
+            tm.insert_deferred_specs(specs);
+        }
+    });
+}
+
+/// add a track from a headerless PCM file (.pcm, .raw) of interleaved samples.
+/// offset: number of bytes to skip at the beginning of the file (default: 0)
+#[napi]
+async fn add_raw_track(
+    track_id: u32,
+    path: String,
+    sr: u32,
+    channels: u32,
+    sample_format: RawSampleFormat,
+    offset: Option<u32>,
+) -> Result<()> {
+    let raw_params = RawPcmParams {
+        sr,
+        n_ch: channels as usize,
+        sample_format,
+        offset: offset.unwrap_or(0) as usize,
+    };
+    let id = track_id as usize;
+    let session = Session::current();
+    spawn_blocking_job(JobKind::Stats, move || {
+        let mut tracklist = TRACK_LIST.blocking_write();
+        if session.is_stale() {
+            return Err(RELOADED_ERR_MSG.into());
+        }
+        tracklist
+            .add_raw_track(id, path, raw_params)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap()
+    .map_err(Error::from_reason)?;
+    add_tracks_to_tm(session, vec![id]);
+    Ok(())
+}
+
+/// Write the signal to a 32-bit float wav file in `dir_name` of the temporary directory,
+/// and add it as a track of `id` placed at `offset_sec`. Returns the path of the file.
+fn write_temp_wav_and_add_track(
+    dir_name: &str,
+    stem: &str,
+    wavs: ArrayView2<f32>,
+    sr: u32,
+    id: usize,
+    offset_sec: f64,
+    session: Session,
+) -> std::io::Result<String> {
+    let out_dir = std::env::temp_dir().join(dir_name);
+    std::fs::create_dir_all(&out_dir)?;
+    let path = unique_output_path(&out_dir, stem, TranscodeCodec::Wav, &[]);
+    let transcode_params = TranscodeParams {
+        bit_depth: Some(32),
+        ..Default::default()
+    };
+    transcode(wavs, sr, TranscodeCodec::Wav, &transcode_params, &path)?;
+    tempfiles::register(&path);
+    let path = path.to_string_lossy().into_owned();
+    let mut tracklist = TRACK_LIST.blocking_write();
+    if session.is_stale() {
+        return Err(std::io::Error::other(RELOADED_ERR_MSG));
+    }
+    let added_ids = tracklist.add_tracks(vec![id], vec![path.clone()]);
+    if added_ids.is_empty() {
+        return Err(std::io::Error::other("Failed to add the track."));
+    }
+    if offset_sec != 0. {
+        tracklist.set_offset(id, offset_sec);
+    }
+    Ok(path)
+}
+
+/// invalid inputs are reported as InvalidArg
+fn io_to_napi_error(e: std::io::Error) -> Error {
+    match e.kind() {
+        std::io::ErrorKind::InvalidInput => Error::new(Status::InvalidArg, e.to_string()),
+        _ => Error::from_reason(e.to_string()),
+    }
+}
+
+/// add a track of a synthetic signal (e.g. a sine sweep as a reference of the analyzer).
+/// params: {sr, sec, channels, peakdB, hz, startHz, endHz, seed} (all optional).
+/// The signal is written to a 32-bit float wav file in the temporary directory,
+/// and its path is returned.
+#[napi]
+async fn add_synthetic_track(
+    track_id: u32,
+    kind: TestSignalKind,
+    params: serde_json::Value,
+) -> Result<String> {
+    let params: TestSignalParams = serde_json::from_value(params)?;
+    let id = track_id as usize;
+    let session = Session::current();
+    let path = spawn_blocking_job(JobKind::Export, move || -> std::io::Result<String> {
+        let wavs = generate_test_signal(kind, &params)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        write_temp_wav_and_add_track(
+            "thesia_test_signals",
+            &params.stem(kind),
+            wavs.view(),
+            params.sr,
+            id,
+            0.,
+            session,
+        )
+    })
+    .await
+    .unwrap()
+    .map_err(io_to_napi_error)?;
+    add_tracks_to_tm(session, vec![id]);
+    Ok(path)
+}
+
+/// Preview an edit of the global selection of the track (reverse, fades, or gain) without
+/// modifying the track. The whole track with the edited selection is written to a 32-bit float
+/// wav file in the temporary directory and added as a track of new_track_id,
+/// placed at the same offset as the source. params: {gaindB} (for Gain).
+/// Returns the path of the derived track.
+#[napi]
+async fn preview_selection_transform(
+    track_id: u32,
+    kind: SelectionTransformKind,
+    new_track_id: u32,
+    params: serde_json::Value,
+) -> Result<String> {
+    let params: SelectionTransformParams = serde_json::from_value(params)?;
+    params
+        .validate(kind)
+        .map_err(|e| Error::new(Status::InvalidArg, e))?;
+    let Some(selection) = *SELECTION.read() else {
+        return Err(Error::new(Status::InvalidArg, "No selection exists."));
+    };
+    let (id, new_id) = (track_id as usize, new_track_id as usize);
+    let session = Session::current();
+    let path = spawn_blocking_job(JobKind::Export, move || -> std::io::Result<String> {
+        let invalid_input = |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidInput, msg);
+        let (wavs, sr, offset_sec, stem) = {
+            let tracklist = TRACK_LIST.blocking_read();
+            let track = tracklist
+                .get(id)
+                .ok_or_else(|| invalid_input("The track doesn't exist."))?;
+            let range = track.timeline().session_to_track_range(selection);
+            let (i_start, i_end) = track.sample_range(range);
+            if i_start >= i_end {
+                return Err(invalid_input("The selection is out of the track."));
+            }
+            let wavs = transform_selection(track.original_wavs(), (i_start, i_end), kind, &params);
+            let stem = format!("{}_{}", track.file_stem(), params.suffix(kind));
+            (wavs, track.sr(), track.offset_sec, stem)
+        };
+        write_temp_wav_and_add_track(
+            "thesia_edit_previews",
+            &stem,
+            wavs.view(),
+            sr,
+            new_id,
+            offset_sec,
+            session,
+        )
+    })
+    .await
+    .unwrap()
+    .map_err(io_to_napi_error)?;
+    add_tracks_to_tm(session, vec![new_id]);
+    Ok(path)
+}
+
+/// download http(s) URLs to a temporary cache in parallel, and add tracks of the downloaded files.
+/// The progress of each URL can be polled by getDownloadProgress while downloading.
+#[napi]
+async fn add_tracks_from_urls(id_list: Vec<u32>, urls: Vec<String>) -> Vec<UrlTrackResult> {
+    crash::record_command("addTracksFromUrls");
+    assert!(!id_list.is_empty() && id_list.len() == urls.len());
+
+    {
+        // the progress of the other calls running concurrently is kept
+        let mut progress_list = DOWNLOAD_PROGRESS.write();
+        progress_list.retain(|progress| !id_list.contains(&progress.track_id));
+        progress_list.extend(
+            izip!(&id_list, &urls).map(|(&track_id, url)| DownloadProgress {
+                track_id,
+                url: url.clone(),
+                ..Default::default()
+            }),
+        );
+    }
+    let session = Session::current();
+    let (results, added_ids) = spawn_blocking_job(JobKind::Stats, move || {
+        let download_results: Vec<_> = id_list
+            .par_iter()
+            .zip(&urls)
+            .map(|(&track_id, url)| {
+                if session.is_stale() {
+                    return Err(RELOADED_ERR_MSG.to_string());
+                }
+                download::download_to_cache(url, |downloaded_bytes, total_bytes| {
+                    let mut progress_list = DOWNLOAD_PROGRESS.write();
+                    if let Some(progress) = progress_list
+                        .iter_mut()
+                        .find(|progress| progress.track_id == track_id)
+                    {
+                        progress.downloaded_bytes = downloaded_bytes as f64;
+                        progress.total_bytes = total_bytes.map(|x| x as f64);
+                    }
+                })
+            })
+            .collect();
+        DOWNLOAD_PROGRESS
+            .write()
+            .retain(|progress| !id_list.contains(&progress.track_id));
+        let (ids_to_add, paths_to_add): (Vec<_>, Vec<_>) = id_list
+            .iter()
+            .zip(&download_results)
+            .filter_map(|(&id, result)| {
+                let path = result.as_ref().ok()?;
+                Some((id as usize, path.to_string_lossy().into_owned()))
+            })
+            .unzip();
+        let added_ids = {
+            let mut tracklist = TRACK_LIST.blocking_write();
+            if session.is_stale() {
+                Vec::new()
+            } else {
+                tracklist.add_tracks(ids_to_add, paths_to_add)
+            }
+        };
+        let results: Vec<_> = izip!(id_list, urls, download_results)
+            .map(|(id, url, result)| match result {
+                Ok(path) => UrlTrackResult {
+                    track_id: id,
+                    url,
+                    path: path.to_string_lossy().into_owned(),
+                    download_err: "".to_string(),
+                    decode_err: if added_ids.contains(&(id as usize)) {
+                        "".to_string()
+                    } else {
+                        "The downloaded file is not a supported audio file.".to_string()
+                    },
+                },
+                Err(err) => UrlTrackResult {
+                    track_id: id,
+                    url,
+                    path: "".to_string(),
+                    download_err: err,
+                    decode_err: "".to_string(),
+                },
+            })
+            .collect();
+        (results, added_ids)
+    })
+    .await
+    .unwrap();
+    add_tracks_to_tm(session, added_ids);
+    results
+}
+
+/// progress of the running downloads of addTracksFromUrls (including concurrent calls)
+#[napi]
+fn get_download_progress() -> Vec<DownloadProgress> {
+    DOWNLOAD_PROGRESS.read().clone()
+}
+
+/// Read the format, sample rate, channels, and duration of the files from the headers
+/// without decoding the audio (e.g. to show a dropped folder of files immediately).
+/// The audio is decoded and analyzed (LUFS, peak, ...) by add_tracks on demand.
+/// Each result has a non-empty err if the file can't be probed or the probing was cancelled.
+#[napi]
+async fn probe_files(paths: Vec<String>) -> Vec<ProbedAudioFile> {
+    PROBE_CANCELLED.store(false, Ordering::Release);
+    *PROBE_PROGRESS.write() = JobProgress {
+        n_done: 0,
+        n_total: paths.len() as u32,
+    };
+    let session = Session::current();
+    spawn_blocking_job(JobKind::Stats, move || {
+        paths
+            .into_par_iter()
+            .map(|path| {
+                if PROBE_CANCELLED.load(Ordering::Acquire) || session.is_stale() {
+                    return ProbedAudioFile {
+                        path,
+                        err: "cancelled".into(),
+                        ..Default::default()
+                    };
+                }
+                let result = probe_audio_file(&path).unwrap_or_else(|e| ProbedAudioFile {
+                    err: e.to_string(),
+                    path,
+                    ..Default::default()
+                });
+                PROBE_PROGRESS.write().n_done += 1;
+                result
+            })
+            .collect()
+    })
+    .await
+    .unwrap()
+}
+
+#[napi]
+fn get_probe_progress() -> JobProgress {
+    PROBE_PROGRESS.read().clone()
+}
+
+/// cancel the running probe_files. The files already probed are still returned.
+#[napi]
+fn cancel_probe_files() {
+    PROBE_CANCELLED.store(true, Ordering::Release);
 }
 
 #[napi]
 async fn reload_tracks(track_ids: Vec<u32>) -> Vec<u32> {
+    crash::record_command("reloadTracks");
     assert!(!track_ids.is_empty());
 
     let track_ids: Vec<_> = track_ids.into_iter().map(|x| x as usize).collect();
-    let (reloaded_ids, no_err_ids) =
-        spawn_blocking(move || TRACK_LIST.blocking_write().reload_tracks(&track_ids))
-            .await
-            .unwrap();
-    spawn_blocking(move || {
-        TM.blocking_write()
-            .reload_tracks(&TRACK_LIST.blocking_read(), &reloaded_ids);
+    let session = Session::current();
+    let (reloaded_ids, no_err_ids) = spawn_blocking_job(JobKind::Stats, move || {
+        let mut tracklist = TRACK_LIST.blocking_write();
+        if session.is_stale() {
+            return (Vec::new(), Vec::new());
+        }
+        tracklist.reload_tracks(&track_ids)
+    })
+    .await
+    .unwrap();
+    let (deferred_ids, reloaded_ids) = partition_offscreen(reloaded_ids);
+    spawn_blocking_job(JobKind::SpecUpdate, move || {
+        let mut tm = TM.blocking_write();
+        if !session.is_stale() {
+            let tracklist = TRACK_LIST.blocking_read();
+            tm.reload_tracks(&tracklist, &reloaded_ids);
+            tm.defer_specs(&tracklist, &deferred_ids);
+        }
     });
+    spawn_deferred_spec_job(session);
     no_err_ids.into_iter().map(|x| x as u32).collect()
 }
 
+/// Inform the tracks on screen (e.g. on scroll). On loading or reloading, the specs of the
+/// other tracks existing now are calculated after the specs of the visible tracks.
+/// The deferred specs of the visible tracks are calculated immediately, and the id_ch strings
+/// to be refreshed are returned (like applyTrackListChanges).
+#[napi]
+async fn set_visible_tracks(track_ids: Vec<u32>) -> Vec<String> {
+    crash::record_command("setVisibleTracks");
+    let visible_ids: Vec<_> = track_ids.into_iter().map(|x| x as usize).collect();
+    let session = Session::current();
+    let updated = spawn_blocking_job(JobKind::SpecUpdate, move || {
+        *OFFSCREEN_TRACK_IDS.write() = TRACK_LIST
+            .blocking_read()
+            .all_id_set()
+            .into_iter()
+            .filter(|id| !visible_ids.contains(id))
+            .collect();
+        let (job, needs_greys) = {
+            let tm = TM.blocking_read();
+            (
+                tm.deferred_spec_job(Some(&visible_ids)),
+                tm.needs_greys(&visible_ids),
+            )
+        };
+        if job.is_empty() && !needs_greys {
+            return None;
+        }
+        let specs = job.run(&TRACK_LIST.blocking_read());
+        let mut tm = TM.blocking_write();
+        if session.is_stale() {
+            return None;
+        }
+        tm.insert_deferred_specs(specs);
+        let tracklist = TRACK_LIST.blocking_read();
+        let (updated_id_set, sr) = tm.apply_track_list_changes(&tracklist);
+        let updated_ids: Vec<usize> = updated_id_set.into_iter().collect();
+        Some((tracklist.id_ch_tuples_from(&updated_ids), sr))
+    })
+    .await
+    .unwrap();
+    let Some((id_ch_tuples, sr)) = updated else {
+        return Vec::new();
+    };
+    let id_ch_strs = id_ch_tuples
+        .iter()
+        .map(|&(id, ch)| format_id_ch(id, ch))
+        .collect();
+    join!(
+        img_mgr::send(ImgMsg::Remove(id_ch_tuples)),
+        player::send(PlayerCommand::SetSr(sr))
+    );
+    id_ch_strs
+}
+
 #[napi]
 fn remove_tracks(track_ids: Vec<u32>) {
+    crash::record_command("removeTracks");
     assert!(!track_ids.is_empty());
 
     let track_ids: Vec<_> = track_ids.into_iter().map(|x| x as usize).collect();
     let removed_id_ch_tuples = TRACK_LIST.blocking_write().remove_tracks(&track_ids);
+    acoustid::remove_identities(&track_ids);
+    img_mgr::remove_overview_caches(&track_ids);
+    coalescer::remove_tracks(&track_ids);
     spawn(remove_all_imgs());
-    spawn_blocking(move || {
+    spawn_blocking_job(JobKind::SpecUpdate, move || {
         let hz_range = TM
             .blocking_write()
             .remove_tracks(&TRACK_LIST.blocking_read(), &removed_id_ch_tuples);
@@ -166,7 +865,8 @@ fn remove_tracks(track_ids: Vec<u32>) {
 
 #[napi]
 async fn apply_track_list_changes() -> Vec<String> {
-    let (id_ch_tuples, sr) = spawn_blocking(move || {
+    crash::record_command("applyTrackListChanges");
+    let (id_ch_tuples, sr) = spawn_blocking_job(JobKind::SpecUpdate, move || {
         let mut tm = TM.blocking_write();
         let tracklist = TRACK_LIST.blocking_read();
         let (updated_id_set, sr) = tm.apply_track_list_changes(&tracklist);
@@ -186,7 +886,13 @@ async fn apply_track_list_changes() -> Vec<String> {
     id_ch_strs
 }
 
+/// view_id: images of each view are drawn and cached independently (default: main view 0).
+/// hz_range: frequency range of the spectrogram of the view within the current hz range
+/// (e.g. a detail lane zoomed to 0 ~ 2 kHz). The current hz range is used if not given.
+/// Returns the (min, max) dB mapped to the colormap if the auto contrast mode is Viewport
+/// (for the colorbar). Returns None in Global mode (use getMindB and getMaxdB).
 #[napi]
+#[allow(clippy::too_many_arguments, non_snake_case)]
 async fn set_image_state(
     id_ch_strs: Vec<String>,
     start_sec: f64,
@@ -195,7 +901,9 @@ async fn set_image_state(
     px_per_sec: f64,
     opt_for_wav: serde_json::Value,
     blend: f64,
-) -> Result<()> {
+    view_id: Option<u32>,
+    hz_range: Option<(f64, f64)>,
+) -> Result<Option<(f64, f64)>> {
     // let start = Instant::now();
     let opt_for_wav: DrawOptionForWav = serde_json::from_value(opt_for_wav)?;
     assert!(!id_ch_strs.is_empty());
@@ -205,14 +913,17 @@ async fn set_image_state(
     assert!(height >= 1);
     assert!(opt_for_wav.amp_range.0 <= opt_for_wav.amp_range.1);
     assert!((0.0..=1.0).contains(&blend));
+    assert!(hz_range.is_none_or(|(min_hz, max_hz)| 0. <= min_hz && min_hz < max_hz));
 
     let id_ch_tuples = {
         let tm = TM.read().await;
+        let layout = synced_layout(&*TRACK_LIST.read().await);
         parse_id_ch_tuples(id_ch_strs)?
             .into_iter()
-            .filter(|id_ch| tm.exists(id_ch))
+            .filter(|id_ch| tm.exists(id_ch) && !layout.is_collapsed(id_ch.0))
             .collect()
     };
+    let view_id = view_id.unwrap_or(img_mgr::MAIN_VIEW_ID);
     let params = DrawParams {
         start_sec,
         width,
@@ -220,11 +931,70 @@ async fn set_image_state(
         px_per_sec,
         opt_for_wav,
         blend,
+        hz_range: hz_range.map(|(min_hz, max_hz)| (min_hz as f32, max_hz as f32)),
+        grey_range: None,
+        freq_break: FREQ_AXIS_BREAKS.read().get(&view_id).copied(),
     };
-    img_mgr::send(ImgMsg::Draw((id_ch_tuples, params))).await;
+    let (id_ch_tuples, params, dB_range) = spawn_blocking_job(JobKind::Drawing, move || {
+        let tm = TM.blocking_read();
+        if tm.auto_contrast_mode != AutoContrastMode::Viewport {
+            return (id_ch_tuples, params, None);
+        }
+        let tracklist = TRACK_LIST.blocking_read();
+        let grey_range = tm.visible_grey_range(&tracklist, &id_ch_tuples, &params);
+        let dB_range = grey_range.map(|range| tm.grey_range_to_dB(range));
+        (
+            id_ch_tuples,
+            DrawParams {
+                grey_range,
+                ..params
+            },
+            dB_range,
+        )
+    })
+    .await
+    .unwrap();
+    img_mgr::send(ImgMsg::Draw((view_id, id_ch_tuples, params))).await;
+    Ok(dB_range.map(|(min_dB, max_dB)| (min_dB as f64, max_dB as f64)))
+}
+
+/// remove the image caches of the view (e.g. when the detail lane is closed)
+#[napi]
+async fn remove_image_view(view_id: u32) {
+    FREQ_AXIS_BREAKS.write().remove(&view_id);
+    img_mgr::send(ImgMsg::RemoveView(view_id)).await;
+}
+
+/// Break the frequency axis of the view (default: main view 0) into two segments
+/// (e.g. {hz: 100, ratio: 0.5} for 0 ~ 100 Hz in the lower half, and the rest in the upper half).
+/// None removes the break. The axis isn't broken if the break is out of the hz range of a track.
+/// Call setImageState of the view again to redraw the images.
+#[napi]
+fn set_freq_axis_break(freq_break: Option<FreqAxisBreak>, view_id: Option<u32>) -> Result<()> {
+    let view_id = view_id.unwrap_or(img_mgr::MAIN_VIEW_ID);
+    match freq_break {
+        Some(freq_break) if !freq_break.is_valid() => {
+            return Err(Error::new(
+                Status::InvalidArg,
+                "The break frequency should be positive and the ratio should be in (0, 1).",
+            ));
+        }
+        Some(freq_break) => {
+            FREQ_AXIS_BREAKS.write().insert(view_id, freq_break);
+        }
+        None => {
+            FREQ_AXIS_BREAKS.write().remove(&view_id);
+        }
+    }
     Ok(())
 }
 
+/// break of the frequency axis of the view (default: main view 0). None if not broken.
+#[napi]
+fn get_freq_axis_break(view_id: Option<u32>) -> Option<FreqAxisBreak> {
+    get_freq_break_of(view_id)
+}
+
 #[napi(js_name = "getdBRange")]
 #[allow(non_snake_case)]
 async fn get_dB_range() -> f64 {
@@ -234,8 +1004,9 @@ async fn get_dB_range() -> f64 {
 #[napi(js_name = "setdBRange")]
 #[allow(non_snake_case)]
 async fn set_dB_range(dB_range: f64) {
+    crash::record_command("setdBRange");
     assert!(dB_range > 0.);
-    spawn_blocking(move || {
+    spawn_blocking_job(JobKind::SpecUpdate, move || {
         TM.blocking_write()
             .set_dB_range(&TRACK_LIST.blocking_read(), dB_range as f32)
     })
@@ -244,6 +1015,51 @@ async fn set_dB_range(dB_range: f64) {
     remove_all_imgs().await;
 }
 
+#[napi]
+async fn get_auto_contrast_mode() -> AutoContrastMode {
+    TM.read().await.auto_contrast_mode
+}
+
+/// Global: the colormap covers the dB range of all tracks.
+/// Viewport: the colormap is stretched to the dB range of the visible time and frequency region.
+#[napi]
+async fn set_auto_contrast_mode(mode: AutoContrastMode) {
+    TM.write().await.auto_contrast_mode = mode;
+    remove_all_imgs().await;
+}
+
+#[napi]
+async fn get_freq_interpolation() -> FreqInterpolation {
+    TM.read().await.freq_interpolation
+}
+
+/// interpolation of the frequency axis of the spectrogram.
+/// BandLimited: Lanczos interpolation.
+/// BinAverage: average of the bins in each pixel (e.g. dense high bins of a zoomed-out view)
+#[napi]
+async fn set_freq_interpolation(interpolation: FreqInterpolation) {
+    TM.write().await.freq_interpolation = interpolation;
+    remove_all_imgs().await;
+}
+
+#[napi]
+async fn get_display_tilt() -> f64 {
+    TM.read().await.display_tilt as f64
+}
+
+/// tilt (dB/octave) pivoted at 1 kHz, applied only to the spectrogram display
+#[napi]
+#[allow(non_snake_case)]
+async fn set_display_tilt(dB_per_oct: f64) {
+    spawn_blocking_job(JobKind::SpecUpdate, move || {
+        TM.blocking_write()
+            .set_display_tilt(&TRACK_LIST.blocking_read(), dB_per_oct as f32)
+    })
+    .await
+    .unwrap();
+    remove_all_imgs().await;
+}
+
 #[napi]
 fn get_hz_range(max_track_hz: f64) -> [f64; 2] {
     let hz_range = calc_valid_hz_range(max_track_hz as f32);
@@ -257,7 +1073,7 @@ async fn set_hz_range(min_hz: f64, max_hz: f64) -> bool {
     assert!(min_hz < max_hz);
     let hz_range = (min_hz as f32, max_hz as f32);
     *HZ_RANGE.write() = hz_range;
-    let need_update = spawn_blocking(move || {
+    let need_update = spawn_blocking_job(JobKind::SpecUpdate, move || {
         TM.blocking_write()
             .set_hz_range(&TRACK_LIST.blocking_read(), hz_range)
     })
@@ -269,6 +1085,42 @@ async fn set_hz_range(min_hz: f64, max_hz: f64) -> bool {
     need_update
 }
 
+#[napi]
+async fn get_freq_axis_locked() -> bool {
+    TM.read().await.freq_axis_locked
+}
+
+/// If locked, all tracks are drawn in the same absolute hz range so that tracks of different
+/// sample rates line up (the region above the Nyquist frequency of each track is zero-padded).
+/// If unlocked, the hz range of each track is clipped to its Nyquist frequency.
+/// Use getTrackFreqInfo to draw the frequency axis or the hatched region of each track.
+#[napi]
+async fn set_freq_axis_locked(locked: bool) -> bool {
+    let need_update = spawn_blocking_job(JobKind::SpecUpdate, move || {
+        TM.blocking_write()
+            .set_freq_axis_locked(&TRACK_LIST.blocking_read(), locked)
+    })
+    .await
+    .unwrap();
+    if need_update {
+        remove_all_imgs().await;
+    }
+    need_update
+}
+
+/// returns null if the track doesn't exist
+#[napi]
+fn get_track_freq_info(track_id: u32) -> Option<TrackFreqInfo> {
+    let tm = TM.blocking_read();
+    let sr = TRACK_LIST.blocking_read().get(track_id as usize)?.sr();
+    let hz_range = tm.track_hz_range(sr);
+    Some(TrackFreqInfo {
+        hz_range: vec![hz_range.0 as f64, hz_range.1 as f64],
+        nyquist_hz: sr as f64 / 2.,
+        padding_ratio: tm.nyquist_padding_ratio(sr) as f64,
+    })
+}
+
 #[napi]
 fn get_spec_setting() -> SpecSetting {
     SPEC_SETTING.read().clone()
@@ -276,11 +1128,12 @@ fn get_spec_setting() -> SpecSetting {
 
 #[napi]
 async fn set_spec_setting(spec_setting: SpecSetting) {
+    crash::record_command("setSpecSetting");
     assert!(spec_setting.win_ms > 0.);
     assert!(spec_setting.t_overlap >= 1);
     assert!(spec_setting.f_overlap >= 1);
     *SPEC_SETTING.write() = spec_setting.clone();
-    spawn_blocking(move || {
+    spawn_blocking_job(JobKind::SpecUpdate, move || {
         TM.blocking_write()
             .set_setting(&TRACK_LIST.blocking_read(), spec_setting)
     })
@@ -289,23 +1142,325 @@ async fn set_spec_setting(spec_setting: SpecSetting) {
     remove_all_imgs().await;
 }
 
+/// maximum size of the spectrogram per channel (MB). zero means no limit.
 #[napi]
-fn get_common_guard_clipping() -> GuardClippingMode {
-    TRACK_LIST.blocking_read().common_guard_clipping
+async fn get_max_spec_memory_mb() -> f64 {
+    TM.read()
+        .await
+        .max_spec_bytes
+        .map_or(0., |bytes| bytes as f64 / MB as f64)
 }
 
+/// The time resolution of the spectrogram of long tracks is reduced
+/// to keep the size under max_mb per channel. zero or negative means no limit.
 #[napi]
-async fn set_common_guard_clipping(mode: GuardClippingMode) {
-    spawn_blocking(move || TRACK_LIST.blocking_write().set_common_guard_clipping(mode))
-        .await
-        .unwrap();
-    spawn_blocking(move || {
+async fn set_max_spec_memory_mb(max_mb: f64) {
+    crash::record_command("setMaxSpecMemoryMb");
+    let max_bytes = (max_mb > 0.).then(|| (max_mb * MB as f64).round() as usize);
+    spawn_blocking_job(JobKind::SpecUpdate, move || {
         TM.blocking_write()
-            .update_all_specs_greys(&TRACK_LIST.blocking_read());
+            .set_max_spec_bytes(&TRACK_LIST.blocking_read(), max_bytes)
     })
     .await
     .unwrap();
-    join!(remove_all_imgs(), refresh_track_player());
+    remove_all_imgs().await;
+}
+
+#[napi]
+fn get_common_guard_clipping() -> GuardClippingMode {
+    TRACK_LIST.blocking_read().common_guard_clipping
+}
+
+#[napi]
+async fn set_common_guard_clipping(mode: GuardClippingMode) {
+    crash::record_command("setCommonGuardClipping");
+    spawn_blocking_job(JobKind::Stats, move || {
+        TRACK_LIST.blocking_write().set_common_guard_clipping(mode)
+    })
+    .await
+    .unwrap();
+    spawn_blocking_job(JobKind::SpecUpdate, move || {
+        TM.blocking_write()
+            .update_all_specs_greys(&TRACK_LIST.blocking_read());
+    })
+    .await
+    .unwrap();
+    join!(remove_all_imgs(), refresh_track_player());
+}
+
+#[napi]
+fn get_wav_view_mode(track_id: u32) -> WavViewMode {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .map_or_else(Default::default, |track| track.wav_view_mode)
+}
+
+#[napi]
+async fn set_wav_view_mode(track_id: u32, mode: WavViewMode) -> Result<()> {
+    let track_id = track_id as usize;
+    let id_ch_tuples = spawn_blocking_job(JobKind::Stats, move || {
+        let mut tracklist = TRACK_LIST.blocking_write();
+        tracklist
+            .set_wav_view_mode(track_id, mode)
+            .then(|| tracklist.id_ch_tuples_from(&[track_id]))
+    })
+    .await
+    .unwrap()
+    .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))?;
+    img_mgr::send(ImgMsg::Remove(id_ch_tuples)).await;
+    Ok(())
+}
+
+/// setWavViewMode of multiple tracks at once. Nothing is changed if any of the tracks doesn't exist.
+#[napi]
+async fn set_tracks_wav_view_mode(track_ids: Vec<u32>, mode: WavViewMode) -> Result<()> {
+    let track_ids: Vec<_> = track_ids.into_iter().map(|id| id as usize).collect();
+    let id_ch_tuples = spawn_blocking_job(JobKind::Stats, move || {
+        let mut tracklist = TRACK_LIST.blocking_write();
+        tracklist
+            .set_tracks_wav_view_mode(&track_ids, mode)
+            .then(|| tracklist.id_ch_tuples_from(&track_ids))
+    })
+    .await
+    .unwrap()
+    .ok_or_else(|| Error::new(Status::InvalidArg, "Some of the tracks don't exist."))?;
+    img_mgr::send(ImgMsg::Remove(id_ch_tuples)).await;
+    Ok(())
+}
+
+#[napi]
+fn get_track_polarity_invert(track_id: u32) -> bool {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .is_some_and(|track| track.polarity_inverted)
+}
+
+/// non-destructive polarity inversion of all channels of the track
+/// (affects playback, waveform, and spectrogram)
+#[napi]
+async fn set_track_polarity_invert(track_id: u32, inverted: bool) -> Result<()> {
+    let track_id = track_id as usize;
+    let exists = spawn_blocking_job(JobKind::Stats, move || {
+        TRACK_LIST
+            .blocking_write()
+            .set_polarity_inverted(track_id, inverted)
+    })
+    .await
+    .unwrap();
+    if !exists {
+        return Err(Error::new(Status::InvalidArg, "The track doesn't exist."));
+    }
+    refresh_track_channels(vec![track_id]).await;
+    Ok(())
+}
+
+/// setTrackPolarityInvert of multiple tracks at once with a single recalculation of the specs.
+/// Nothing is changed if any of the tracks doesn't exist.
+#[napi]
+async fn set_tracks_polarity_invert(track_ids: Vec<u32>, inverted: bool) -> Result<()> {
+    let track_ids: Vec<_> = track_ids.into_iter().map(|id| id as usize).collect();
+    let (track_ids, exist) = spawn_blocking_job(JobKind::Stats, move || {
+        let exist = TRACK_LIST
+            .blocking_write()
+            .set_tracks_polarity_inverted(&track_ids, inverted);
+        (track_ids, exist)
+    })
+    .await
+    .unwrap();
+    if !exist {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "Some of the tracks don't exist.",
+        ));
+    }
+    refresh_track_channels(track_ids).await;
+    Ok(())
+}
+
+/// source channel of each channel of the track
+#[napi]
+fn get_channel_order(track_id: u32) -> Vec<u32> {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .map_or_else(Vec::new, |track| {
+            track.channel_order.iter().map(|&ch| ch as u32).collect()
+        })
+}
+
+/// non-destructive swapping of two channels of the track
+/// (affects playback, waveform, and spectrogram)
+#[napi]
+async fn swap_channels(track_id: u32, ch_a: u32, ch_b: u32) -> Result<()> {
+    let track_id = track_id as usize;
+    let is_valid = spawn_blocking_job(JobKind::Stats, move || {
+        TRACK_LIST
+            .blocking_write()
+            .swap_channels(track_id, ch_a as usize, ch_b as usize)
+    })
+    .await
+    .unwrap();
+    if !is_valid {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The track or the channel doesn't exist.",
+        ));
+    }
+    refresh_track_channels(vec![track_id]).await;
+    Ok(())
+}
+
+/// Decode only the channels of the track where mask is true (e.g. a few channels of
+/// an ambisonics file to save memory). mask has an element per channel of the file.
+#[napi]
+async fn set_decoded_channels(track_id: u32, mask: Vec<bool>) -> Result<()> {
+    crash::record_command("setDecodedChannels");
+    let track_id = track_id as usize;
+    let channels: Vec<_> = (0..mask.len()).filter(|&ch| mask[ch]).collect();
+    if channels.is_empty() {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "At least one channel should be decoded.",
+        ));
+    }
+    let changed = spawn_blocking_job(JobKind::Stats, move || {
+        let mut tracklist = TRACK_LIST.blocking_write();
+        if !tracklist.has(track_id) {
+            return Err(Error::new(Status::InvalidArg, "The track doesn't exist."));
+        }
+        if mask.len() != tracklist[track_id].format_info.n_ch {
+            return Err(Error::new(
+                Status::InvalidArg,
+                "The length of mask should be the number of channels of the file.",
+            ));
+        }
+        let channels = (channels.len() < mask.len()).then_some(channels);
+        tracklist
+            .set_decoded_channels(track_id, channels)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    })
+    .await
+    .unwrap()?;
+    if changed {
+        refresh_track_channels(vec![track_id]).await;
+    }
+    Ok(())
+}
+
+/// Assign group ids to tracks sharing the same album, date, or folder.
+/// The album and the date are read from the tags of the files.
+/// The tracks without the tag are not grouped.
+#[napi]
+async fn auto_group_tracks(by: TrackGroupBy) -> Vec<TrackGroup> {
+    let groups = TRACK_LIST.write().await.auto_group_tracks(by);
+    groups
+        .into_iter()
+        .enumerate()
+        .map(|(group_id, (name, track_ids))| TrackGroup {
+            group_id: group_id as u32,
+            name,
+            track_ids: track_ids.into_iter().map(|id| id as u32).collect(),
+        })
+        .collect()
+}
+
+/// Pair the tracks for A/B comparison by the file names matching pattern_a and pattern_b
+/// with the same part matched by "*" (e.g. "*_orig.wav" and "*_proc.wav").
+/// Each pattern should contain exactly one "*", and is matched case-sensitively.
+/// The pairs are ordered by the matched part. Tracks without the counterpart are not paired.
+#[napi]
+async fn auto_pair_tracks(pattern_a: String, pattern_b: String) -> Result<Vec<TrackPair>> {
+    let pairs = TRACK_LIST
+        .read()
+        .await
+        .auto_pair_tracks(&pattern_a, &pattern_b)
+        .map_err(|e| Error::new(Status::InvalidArg, e))?;
+    Ok(pairs
+        .into_iter()
+        .map(|(key, id_a, id_b)| TrackPair {
+            key,
+            track_id_a: id_a as u32,
+            track_id_b: id_b as u32,
+        })
+        .collect())
+}
+
+/// group id assigned by autoGroupTracks. null if the track isn't grouped.
+#[napi]
+fn get_track_group(track_id: u32) -> Option<u32> {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .and_then(|track| track.group_id)
+        .map(|group_id| group_id as u32)
+}
+
+/// ids of the tracks satisfying all the predicates of the filter
+/// (e.g. {nameContains: "vocal", sr: 44100, maxLUFS: -20, minSec: 600})
+#[napi]
+async fn query_tracks(filter: serde_json::Value) -> Result<Vec<u32>> {
+    let filter: TrackFilter = serde_json::from_value(filter)?;
+    let track_ids = TRACK_LIST.read().await.query_tracks(&filter);
+    Ok(track_ids.into_iter().map(|id| id as u32).collect())
+}
+
+fn synced_layout(tracklist: &TrackList) -> parking_lot::RwLockWriteGuard<'static, LayoutModel> {
+    let mut layout = LAYOUT.write();
+    layout.sync(
+        tracklist
+            .all_ids()
+            .into_iter()
+            .map(|id| (id, tracklist[id].n_ch())),
+    );
+    layout
+}
+
+/// channel lanes (in the display order) of all the tracks
+#[napi]
+async fn get_layout() -> Vec<TrackLayout> {
+    synced_layout(&*TRACK_LIST.read().await).layout()
+}
+
+#[napi]
+async fn set_lane_height(track_id: u32, ch: u32, height: u32) -> Result<()> {
+    synced_layout(&*TRACK_LIST.read().await)
+        .set_lane_height(track_id as usize, ch as usize, height)
+        .map_err(|e| Error::new(Status::InvalidArg, e))
+}
+
+/// set the height of all the lanes including those of the tracks added later
+#[napi]
+async fn set_all_lane_heights(height: u32) -> Result<()> {
+    synced_layout(&*TRACK_LIST.read().await)
+        .set_all_lane_heights(height)
+        .map_err(|e| Error::new(Status::InvalidArg, e))
+}
+
+/// move the lane of the track at from_index to to_index in the display order
+#[napi]
+async fn move_lane(track_id: u32, from_index: u32, to_index: u32) -> Result<()> {
+    synced_layout(&*TRACK_LIST.read().await)
+        .move_lane(track_id as usize, from_index as usize, to_index as usize)
+        .map_err(|e| Error::new(Status::InvalidArg, e))
+}
+
+/// The images of the channels of collapsed tracks are not drawn.
+#[napi]
+async fn set_track_collapsed(track_id: u32, collapsed: bool) -> Result<()> {
+    synced_layout(&*TRACK_LIST.read().await)
+        .set_collapsed(track_id as usize, collapsed)
+        .map_err(|e| Error::new(Status::InvalidArg, e))
+}
+
+/// restore the layout returned by getLayout (e.g. from a session).
+/// Nothing is changed if any of the tracks doesn't match.
+#[napi]
+async fn set_layout(layout: Vec<TrackLayout>) -> Result<()> {
+    synced_layout(&*TRACK_LIST.read().await)
+        .restore(&layout)
+        .map_err(|e| Error::new(Status::InvalidArg, e))
 }
 
 #[napi]
@@ -315,14 +1470,15 @@ fn get_common_normalize() -> serde_json::Value {
 
 #[napi]
 async fn set_common_normalize(target: serde_json::Value) -> Result<()> {
+    crash::record_command("setCommonNormalize");
     let target = serde_json::from_value(target)?;
 
-    spawn_blocking(move || {
+    spawn_blocking_job(JobKind::Stats, move || {
         TRACK_LIST.blocking_write().set_common_normalize(target);
     })
     .await
     .unwrap();
-    spawn_blocking(move || {
+    spawn_blocking_job(JobKind::SpecUpdate, move || {
         TM.blocking_write()
             .update_all_specs_greys(&TRACK_LIST.blocking_read());
     })
@@ -333,8 +1489,131 @@ async fn set_common_normalize(target: serde_json::Value) -> Result<()> {
 }
 
 #[napi(ts_return_type = "Record<string, Buffer>")]
-fn get_images() -> IdChImages {
-    img_mgr::recv().map_or_else(Default::default, IdChImages)
+fn get_images(view_id: Option<u32>) -> IdChImages {
+    img_mgr::recv(view_id.unwrap_or(img_mgr::MAIN_VIEW_ID))
+        .map_or_else(Default::default, IdChImages)
+}
+
+/// path of the track relative to the directory containing the session file.
+/// The absolute path is returned if the relative path can't be made (e.g. different drives).
+#[napi]
+fn to_session_relative_path(path: String, session_path: String) -> String {
+    relative_to_session(path.as_ref(), session_path.as_ref())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// resolve the track path stored in the session file.
+/// relative_path (from the directory containing the session file) is tried first,
+/// then absolute_path. Returns null if the file doesn't exist at both paths.
+#[napi]
+fn resolve_session_track_path(
+    relative_path: Option<String>,
+    absolute_path: Option<String>,
+    session_path: String,
+) -> Option<String> {
+    resolve_session_path(
+        relative_path.as_deref().map(Path::new),
+        absolute_path.as_deref().map(Path::new),
+        session_path.as_ref(),
+    )
+    .map(|path| path.to_string_lossy().into_owned())
+}
+
+/// Import the tracks of the session file into the current session.
+/// Append: the ids colliding with the current tracks are moved, and the open files are reused.
+/// Replace: the current tracks are removed first.
+/// The markers and annotations are returned with the mapped track ids to be merged by the renderer.
+/// applyTrackListChanges should be called after this.
+#[napi]
+async fn import_session(path: String, mode: SessionImportMode) -> Result<ImportedSession> {
+    crash::record_command("importSession");
+    let session_path = PathBuf::from(&path);
+    let (session_file, resolved, mut missing_paths) =
+        spawn_blocking_job(JobKind::Stats, move || {
+            let session_file = SessionFile::read(&session_path)?;
+            let (resolved, missing) = session_file.resolve_tracks(&session_path);
+            Ok::<_, String>((session_file, resolved, missing))
+        })
+        .await
+        .unwrap()
+        .map_err(Error::from_reason)?;
+
+    // the ids of the removed tracks are not reused
+    // because the specs of the removed tracks are removed in the background
+    let used_ids = TRACK_LIST.read().await.all_id_set();
+    if mode == SessionImportMode::Replace && !used_ids.is_empty() {
+        let ids = used_ids.iter().map(|&id| id as u32).collect();
+        spawn_blocking_job(JobKind::Stats, move || remove_tracks(ids))
+            .await
+            .unwrap();
+    }
+
+    // the open files are reused, and the others are added with the collision-free ids
+    let (reused, to_add): (Vec<_>, Vec<_>) = {
+        let tracklist = TRACK_LIST.read().await;
+        resolved
+            .into_iter()
+            .map(|(session_id, path)| {
+                let path = path.to_string_lossy().into_owned();
+                (session_id, tracklist.find_id_by_path(&path), path)
+            })
+            .partition(|(_, id, _)| id.is_some())
+    };
+    let id_map = {
+        let session_ids: Vec<_> = to_add.iter().map(|(session_id, ..)| *session_id).collect();
+        let mut tracklist = TRACK_LIST.write().await;
+        let mut unavailable_ids = tracklist.unavailable_ids();
+        unavailable_ids.extend(used_ids);
+        let id_map = session_file::assign_ids(&session_ids, &unavailable_ids);
+        tracklist.reserve_given_ids(id_map.values().copied());
+        id_map
+    };
+    let (id_list, path_list): (Vec<_>, Vec<_>) = to_add
+        .iter()
+        .map(|(session_id, _, path)| (id_map[session_id] as u32, path.clone()))
+        .unzip();
+    let added_ids: IntSet<_> = if id_list.is_empty() {
+        Default::default()
+    } else {
+        add_tracks(id_list, path_list)
+            .await
+            .into_iter()
+            .map(|track| track.id as usize)
+            .collect()
+    };
+
+    let mut tracks = Vec::with_capacity(reused.len() + to_add.len());
+    let mut imported_id_map = IntMap::default();
+    for (session_id, id, _) in reused {
+        let id = id.unwrap();
+        imported_id_map.insert(session_id, id);
+        tracks.push(ImportedTrack {
+            session_id: session_id as u32,
+            id: id as u32,
+            is_new: false,
+        });
+    }
+    for (session_id, _, path) in to_add {
+        let id = id_map[&session_id];
+        if !added_ids.contains(&id) {
+            missing_paths.push(path);
+            continue;
+        }
+        imported_id_map.insert(session_id, id);
+        tracks.push(ImportedTrack {
+            session_id: session_id as u32,
+            id: id as u32,
+            is_new: true,
+        });
+    }
+    tracks.sort_unstable_by_key(|track| track.session_id);
+    Ok(ImportedSession {
+        tracks,
+        missing_paths,
+        markers: session_file::remap_track_ids(session_file.markers, &imported_id_map),
+        annotations: session_file::remap_track_ids(session_file.annotations, &imported_id_map),
+    })
 }
 
 #[napi]
@@ -346,282 +1625,1967 @@ async fn find_id_by_path(path: String) -> i32 {
         .map_or(-1, |id| id as i32)
 }
 
+/// Requests for the same track are coalesced.
+/// Superseded requests (e.g. during resizing) return immediately with is_stale = true.
 #[napi]
-async fn get_overview(track_id: u32, width: u32, height: u32, dpr: f64) -> Buffer {
+async fn get_overview(track_id: u32, width: u32, height: u32, dpr: f64) -> CoalescedBuffer {
     assert!(width >= 1 && height >= 1);
 
-    spawn_blocking(move || {
-        TM.blocking_read()
-            .draw_overview(
-                &TRACK_LIST.blocking_read(),
-                track_id as usize,
-                width,
-                height,
-                dpr as f32,
-            )
-            .into()
+    let ticket = Ticket::register(RequestKey::Overview(track_id as usize));
+    if !ticket.throttle().await {
+        return CoalescedBuffer::stale();
+    }
+    spawn_blocking_job(JobKind::Drawing, move || {
+        if ticket.is_stale() {
+            return CoalescedBuffer::stale();
+        }
+        let tracklist = TRACK_LIST.blocking_read();
+        let Some(track) = tracklist.get(track_id as usize) else {
+            return CoalescedBuffer {
+                is_stale: false,
+                buf: Vec::new().into(),
+            };
+        };
+        let (dpr, px_per_sec) = (dpr as f32, width as f64 / tracklist.max_sec);
+        let buf = img_mgr::get_or_draw_overview(
+            track_id as usize,
+            width,
+            height,
+            dpr,
+            tracklist.max_sec,
+            calc_overview_segment_hashes(track, width, height, dpr, px_per_sec),
+            |px_range| draw_overview_segment_of(track, width, height, dpr, px_per_sec, px_range),
+        );
+        CoalescedBuffer {
+            is_stale: false,
+            buf: buf.into(),
+        }
     })
     .await
     .unwrap()
 }
 
+/// tiny PNG of the overview of the whole track for previews (e.g. the recent files list).
+/// Cached by the content hash of the track.
 #[napi]
-fn freq_pos_to_hz_on_current_range(y: f64, height: u32) -> f64 {
-    assert!(height >= 1);
-
-    convert_freq_pos_to_hz(y as f32, height, None) as f64
+async fn get_thumbnail(track_id: u32, width: u32, height: u32) -> Result<Buffer> {
+    if !(1..=MAX_THUMBNAIL_SIZE).contains(&width) || !(1..=MAX_THUMBNAIL_SIZE).contains(&height) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            format!(
+                "The thumbnail size should be between 1 and {}.",
+                MAX_THUMBNAIL_SIZE
+            ),
+        ));
+    }
+    spawn_blocking_job(JobKind::Drawing, move || {
+        get_or_render_thumbnail(
+            &TRACK_LIST.blocking_read(),
+            track_id as usize,
+            width,
+            height,
+        )
+    })
+    .await
+    .unwrap()
+    .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))?
+    .map(Into::into)
+    .map_err(Error::from_reason)
 }
 
+/// layout of the overview image and the dB axis markers of its limiter gain lanes.
+/// null if the track doesn't exist.
 #[napi]
-fn freq_pos_to_hz(y: f64, height: u32, hz_range: (f64, f64)) -> f64 {
+fn get_overview_drawing_info(
+    track_id: u32,
+    height: u32,
+    dpr: f64,
+    max_num_ticks: u32,
+) -> Option<OverviewDrawingInfo> {
     assert!(height >= 1);
+    let tracklist = TRACK_LIST.blocking_read();
+    let track = tracklist.get(track_id as usize)?;
+    let layout = calc_overview_layout(track, height, dpr as f32);
+    let gain_axis_markers = if layout.gain_lane_height > 0 {
+        calc_gain_axis_markers(layout.max_reduction_dB, max_num_ticks)
+    } else {
+        Vec::new()
+    };
+    Some(OverviewDrawingInfo {
+        ch_tops: layout.ch_tops.into_iter().map(|x| x as u32).collect(),
+        ch_height: layout.ch_height as u32,
+        gain_lane_height: layout.gain_lane_height as u32,
+        gain_axis_markers: json!(gain_axis_markers),
+    })
+}
 
-    let hz_range = (hz_range.0 as f32, hz_range.1 as f32);
-    convert_freq_pos_to_hz(y as f32, height, Some(hz_range)) as f64
+/// samples of the channel in the time range, copied into a new buffer
+/// with the header of the binary layout (see binary_layout.rs)
+#[napi]
+fn get_wav_slice(id_ch_str: String, start_sec: f64, end_sec: f64) -> Result<Buffer> {
+    assert!(start_sec <= end_sec);
+
+    let (id, ch) = parse_id_ch_tuples(vec![id_ch_str])?.pop().unwrap();
+    let tracklist = TRACK_LIST.blocking_read();
+    let track = tracklist
+        .get(id)
+        .filter(|track| ch < track.n_ch())
+        .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))?;
+    let wav = track.channel(ch);
+    let sr = track.sr() as f64;
+    let timeline = track.timeline();
+    let (start_sec, end_sec) = timeline.session_to_track_range((start_sec, end_sec));
+    let i_start = ((start_sec * sr).floor().max(0.) as usize).min(wav.len());
+    let i_end = ((end_sec * sr).ceil().max(0.) as usize).clamp(i_start, wav.len());
+    Ok(encode_wav_slice(
+        id,
+        ch,
+        wav.slice(ndarray::s![i_start..i_end]),
+        track.sr(),
+        timeline.track_to_session(i_start as f64 / sr),
+    )
+    .into())
 }
 
+/// per-pixel classes of the waveform slice drawn with width px,
+/// so that the renderer can color the clipped or limited samples.
+/// If with_envelopes, the envelopes before and after the normalization and guard clipping
+/// are included for the compare mode.
+/// Requests for the same channel are coalesced.
+/// Superseded requests (e.g. during scrolling) return immediately with is_stale = true.
 #[napi]
-fn freq_hz_to_pos(hz: f64, height: u32, hz_range: (f64, f64)) -> f64 {
-    assert!(height >= 1);
+async fn get_wav_drawing_info(
+    id_ch_str: String,
+    start_sec: f64,
+    end_sec: f64,
+    width: u32,
+    with_envelopes: Option<bool>,
+) -> Result<WavDrawingInfo> {
+    assert!(start_sec <= end_sec);
 
-    let hz_range = (hz_range.0 as f32, hz_range.1 as f32);
-    convert_freq_hz_to_pos(hz as f32, height, Some(hz_range)) as f64
+    let (id, ch) = parse_id_ch_tuples(vec![id_ch_str])?.pop().unwrap();
+    let ticket = Ticket::register(RequestKey::WavDrawingInfo((id, ch)));
+    if !ticket.throttle().await {
+        return Ok(WavDrawingInfo::stale());
+    }
+    spawn_blocking_job(JobKind::Drawing, move || {
+        if ticket.is_stale() {
+            return Ok(WavDrawingInfo::stale());
+        }
+        let tracklist = TRACK_LIST.blocking_read();
+        let track = tracklist
+            .get(id)
+            .filter(|track| ch < track.n_ch())
+            .ok_or("The track doesn't exist.")?;
+        let range = track
+            .timeline()
+            .session_to_track_range((start_sec, end_sec));
+        let pixel_classes = track.wav_pixel_classes(ch, range, width);
+        let (pre_envelope, post_envelope) = with_envelopes
+            .unwrap_or(false)
+            .then(|| track.wav_compare_envelopes(ch, range, width))
+            .flatten()
+            .map_or((None, None), |[pre, post]| {
+                (Some(pre.into()), Some(post.into()))
+            });
+        Ok(WavDrawingInfo {
+            is_stale: false,
+            pixel_classes: pixel_classes.map(|classes| {
+                classes
+                    .into_iter()
+                    .map(|class| class as u8)
+                    .collect::<Vec<_>>()
+                    .into()
+            }),
+            pre_envelope,
+            post_envelope,
+        })
+    })
+    .await
+    .unwrap()
+    .map_err(|e| Error::new(Status::InvalidArg, e))
 }
 
+/// Returns the tile of the spectrogram grey image with the binary layout.
+/// zoom_level 0 is the resolution of the spectrogram (one column per frame),
+/// and each level above halves the resolution.
+/// tile_x: index along time, tile_y: index along frequency (from the highest frequency)
+/// Requests for the same tile position of the channel are coalesced.
+/// Superseded requests (e.g. during zooming) return immediately with is_stale = true.
 #[napi]
-fn seconds_to_label(sec: f64) -> String {
-    convert_sec_to_label(sec)
+async fn get_spec_tile(
+    id_ch_str: String,
+    zoom_level: u32,
+    tile_x: u32,
+    tile_y: u32,
+) -> Result<CoalescedBuffer> {
+    get_spectrogram_layer(id_ch_str, SpecLayer::Magnitude, zoom_level, tile_x, tile_y).await
 }
 
+/// Returns the tile of the grey image of the spectrogram layer with the same layout as getSpecTile.
+/// The delta layer should be enabled by setSpecDeltaLayer in advance.
+/// Coalesced with getSpecTile of the same tile position.
 #[napi]
-fn time_label_to_seconds(label: String) -> f64 {
-    convert_time_label_to_sec(&label).unwrap_or(f64::NAN)
+async fn get_spectrogram_layer(
+    id_ch_str: String,
+    layer: SpecLayer,
+    zoom_level: u32,
+    tile_x: u32,
+    tile_y: u32,
+) -> Result<CoalescedBuffer> {
+    let id_ch = parse_id_ch_tuples(vec![id_ch_str])?.pop().unwrap();
+    let ticket = Ticket::register(RequestKey::SpecTile(id_ch, (tile_x, tile_y)));
+    if !ticket.throttle().await {
+        return Ok(CoalescedBuffer::stale());
+    }
+    spawn_blocking_job(JobKind::Drawing, move || {
+        if ticket.is_stale() {
+            return Ok(CoalescedBuffer::stale());
+        }
+        let tm = TM.blocking_read();
+        let tracklist = TRACK_LIST.blocking_read();
+        if !tm.exists(&id_ch) {
+            return Err("The spectrogram doesn't exist.");
+        }
+        if layer == SpecLayer::Delta && !tm.delta_layer_enabled {
+            return Err("The delta layer is disabled.");
+        }
+        tm.encode_spec_tile(
+            &tracklist,
+            id_ch,
+            layer,
+            zoom_level,
+            (tile_y as usize, tile_x as usize),
+        )
+        .map(|buf| CoalescedBuffer {
+            is_stale: false,
+            buf: buf.into(),
+        })
+        .ok_or("The tile doesn't exist.")
+    })
+    .await
+    .unwrap()
+    .map_err(|e| Error::new(Status::InvalidArg, e))
 }
 
 #[napi]
-fn hz_to_label(hz: f64) -> String {
-    convert_hz_to_label(hz as f32)
+async fn get_spec_delta_layer() -> bool {
+    TM.read().await.delta_layer_enabled
 }
 
+/// enable or disable calculating the delta (temporal first difference) layer of spectrograms
 #[napi]
-fn freq_label_to_hz(label: String) -> f64 {
-    convert_freq_label_to_hz(&label).unwrap_or(f32::NAN) as f64
+async fn set_spec_delta_layer(enabled: bool) {
+    spawn_blocking_job(JobKind::SpecUpdate, move || {
+        TM.blocking_write()
+            .set_delta_layer_enabled(&TRACK_LIST.blocking_read(), enabled)
+    })
+    .await
+    .unwrap();
 }
 
 #[napi]
-fn get_time_axis_markers(
-    start_sec: f64,
-    end_sec: f64,
-    tick_unit: f64,
-    label_interval: u32,
-    max_sec: f64,
-) -> serde_json::Value {
-    assert!(start_sec <= end_sec);
-    assert!(label_interval > 0);
-    json!(calc_time_axis_markers(
-        start_sec,
-        end_sec,
-        tick_unit,
-        label_interval,
-        max_sec
-    ))
+async fn get_spec_tile_grid(id_ch_str: String, zoom_level: u32) -> Result<SpecTileGrid> {
+    let id_ch = parse_id_ch_tuples(vec![id_ch_str])?.pop().unwrap();
+    let tm = TM.read().await;
+    if !tm.exists(&id_ch) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The spectrogram doesn't exist.",
+        ));
+    }
+    let (n_tiles_y, n_tiles_x) = tm
+        .spec_tile_grid(id_ch, zoom_level)
+        .ok_or_else(|| Error::new(Status::InvalidArg, "The zoom level is too large."))?;
+    let (sec_per_frame, is_resolution_reduced) = tm
+        .spec_resolution(&*TRACK_LIST.read().await, id_ch)
+        .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))?;
+    Ok(SpecTileGrid {
+        tile_size: visualize::SPEC_TILE_SIZE as u32,
+        n_tiles_x: n_tiles_x as u32,
+        n_tiles_y: n_tiles_y as u32,
+        sec_per_frame,
+        is_resolution_reduced,
+    })
 }
 
+/// win/hop/n_fft derived from the sample rate of the track and the spec setting
+/// (and the memory limit of the spectrogram)
 #[napi]
-fn get_freq_axis_markers(
-    max_num_ticks: u32,
-    max_num_labels: u32,
-    max_track_hz: f64,
-) -> serde_json::Value {
-    assert_axis_params(max_num_ticks, max_num_labels);
+async fn get_effective_stft_params(track_id: u32) -> Result<EffectiveStftParams> {
+    let (tm, tracklist) = TM.read_both(&TRACK_LIST).await;
+    let id = track_id as usize;
+    let (hop_length, win_length, n_fft) = tm
+        .effective_framing_params(&tracklist, id)
+        .ok_or_else(|| Error::new(Status::InvalidArg, "The spectrogram doesn't exist."))?;
+    let sr = tracklist[id].sr() as f64;
+    Ok(EffectiveStftParams {
+        win_length: win_length as u32,
+        hop_length: hop_length as u32,
+        n_fft: n_fft as u32,
+        hz_per_bin: sr / n_fft as f64,
+        ms_per_frame: hop_length as f64 / sr * 1000.,
+        is_resolution_reduced: hop_length > tm.setting.calc_hop_length(sr as u32),
+    })
+}
 
-    json!(calc_freq_axis_markers(
-        calc_valid_hz_range(max_track_hz as f32),
-        SPEC_SETTING.read().freq_scale,
-        max_num_ticks,
-        max_num_labels
-    ))
+/// point_a, point_b: (sec, hz)
+#[napi]
+async fn measure(
+    id_ch_str: String,
+    point_a: (f64, f64),
+    point_b: (f64, f64),
+) -> Result<SpecMeasurement> {
+    if [point_a.0, point_a.1, point_b.0, point_b.1]
+        .iter()
+        .any(|x| !x.is_finite())
+    {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The points should be finite.",
+        ));
+    }
+    let id_ch = parse_id_ch_tuples(vec![id_ch_str])?.pop().unwrap();
+    let (tm, tracklist) = TM.read_both(&TRACK_LIST).await;
+    tm.measure(&tracklist, id_ch, point_a, point_b)
+        .ok_or_else(|| Error::new(Status::InvalidArg, "The spectrogram doesn't exist."))
+}
+
+/// dB at the percentile (0 ~ 100) over time for each frequency bin of the spectrogram
+/// (e.g. 10th percentile as the noise floor spectrum)
+#[napi]
+async fn get_noise_profile(track_id: u32, ch: u32, percentile: f64) -> Result<NoiseProfile> {
+    if !(0.0..=100.0).contains(&percentile) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The percentile should be in 0 ~ 100.",
+        ));
+    }
+    spawn_blocking_job(JobKind::Stats, move || {
+        let tm = TM.blocking_read();
+        let tracklist = TRACK_LIST.blocking_read();
+        tm.noise_profile(&tracklist, (track_id as usize, ch as usize), percentile)
+    })
+    .await
+    .unwrap()
+    .ok_or_else(|| Error::new(Status::InvalidArg, "The spectrogram doesn't exist."))
+}
+
+/// Capture the spectrum of id_ch at sec (e.g. the playhead) to compare it with other spectra
+/// captured at different times or from different tracks.
+/// name: "{file name} {id_ch} @ {time}" if not given. The snapshot with the same name is replaced.
+#[napi]
+#[allow(non_snake_case)]
+async fn capture_spectrum_snapshot(
+    id_ch_str: String,
+    sec: f64,
+    name: Option<String>,
+) -> Result<SpectrumSnapshot> {
+    let id_ch = parse_id_ch_tuples(vec![id_ch_str.clone()])?.pop().unwrap();
+    let (tm, tracklist) = TM.read_both(&TRACK_LIST).await;
+    let (hz, dB) = tm
+        .spectrum_at(&tracklist, id_ch, sec)
+        .ok_or_else(|| Error::new(Status::InvalidArg, "The spectrogram frame doesn't exist."))?;
+    let name = name.unwrap_or_else(|| {
+        let file_name = tracklist
+            .get(id_ch.0)
+            .map_or_else(Default::default, |track| track.file_stem());
+        format!(
+            "{} {} @ {}",
+            file_name,
+            id_ch_str,
+            convert_sec_to_label(sec)
+        )
+    });
+    let snapshot = SpectrumSnapshot {
+        name,
+        id_ch: id_ch_str,
+        sec,
+        hz,
+        dB,
+    };
+    let mut snapshots = SPECTRUM_SNAPSHOTS.write();
+    snapshots.retain(|x| x.name != snapshot.name);
+    snapshots.push(snapshot.clone());
+    Ok(snapshot)
+}
+
+/// captured spectra in the captured order
+#[napi]
+fn list_spectrum_snapshots() -> Vec<SpectrumSnapshot> {
+    SPECTRUM_SNAPSHOTS.read().clone()
+}
+
+/// false if the snapshot of the name doesn't exist
+#[napi]
+fn remove_spectrum_snapshot(name: String) -> bool {
+    let mut snapshots = SPECTRUM_SNAPSHOTS.write();
+    let len = snapshots.len();
+    snapshots.retain(|x| x.name != name);
+    snapshots.len() < len
+}
+
+#[napi]
+fn clear_spectrum_snapshots() {
+    SPECTRUM_SNAPSHOTS.write().clear();
+}
+
+/// view_id: the frequency axis of the view may be broken (default: main view 0)
+#[napi]
+fn freq_pos_to_hz_on_current_range(y: f64, height: u32, view_id: Option<u32>) -> f64 {
+    assert!(height >= 1);
+
+    convert_freq_pos_to_hz(y as f32, height, None, get_freq_break_of(view_id)) as f64
+}
+
+#[napi]
+fn freq_pos_to_hz(y: f64, height: u32, hz_range: (f64, f64), view_id: Option<u32>) -> f64 {
+    assert!(height >= 1);
+
+    let hz_range = (hz_range.0 as f32, hz_range.1 as f32);
+    convert_freq_pos_to_hz(y as f32, height, Some(hz_range), get_freq_break_of(view_id)) as f64
+}
+
+#[napi]
+fn freq_hz_to_pos(hz: f64, height: u32, hz_range: (f64, f64), view_id: Option<u32>) -> f64 {
+    assert!(height >= 1);
+
+    let hz_range = (hz_range.0 as f32, hz_range.1 as f32);
+    convert_freq_hz_to_pos(
+        hz as f32,
+        height,
+        Some(hz_range),
+        get_freq_break_of(view_id),
+    ) as f64
+}
+
+#[napi]
+fn seconds_to_label(sec: f64) -> String {
+    convert_sec_to_label(sec)
+}
+
+/// string of the position in the unit. sr is only used for the units in samples.
+#[napi]
+fn format_position(sec: f64, unit: PositionUnit, sr: u32) -> String {
+    backend::format_position(sec, unit, sr)
+}
+
+/// Convert the cursor x (px) on the view starting at start_sec to the position.
+/// The position is snapped to the nearest sample if getSnapToSample() is true.
+#[napi]
+fn cursor_to_position(x: f64, start_sec: f64, px_per_sec: f64, sr: u32) -> Result<CursorPosition> {
+    if !px_per_sec.is_finite() || px_per_sec <= 0. || sr == 0 {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "px_per_sec and sr should be positive.",
+        ));
+    }
+    let sec = start_sec + x / px_per_sec;
+    let samples = convert_sec_to_samples(sec, sr, SNAP_TO_SAMPLE.load(Ordering::Acquire));
+    Ok(CursorPosition {
+        sec: samples / sr as f64,
+        samples,
+    })
+}
+
+#[napi]
+fn get_snap_to_sample() -> bool {
+    SNAP_TO_SAMPLE.load(Ordering::Acquire)
+}
+
+#[napi]
+fn set_snap_to_sample(enabled: bool) {
+    SNAP_TO_SAMPLE.store(enabled, Ordering::Release);
+}
+
+#[napi]
+fn time_label_to_seconds(label: String) -> f64 {
+    convert_time_label_to_sec(&label).unwrap_or(f64::NAN)
+}
+
+#[napi]
+fn hz_to_label(hz: f64) -> String {
+    convert_hz_to_label(hz as f32)
+}
+
+#[napi]
+fn freq_label_to_hz(label: String) -> f64 {
+    convert_freq_label_to_hz(&label).unwrap_or(f32::NAN) as f64
+}
+
+#[napi]
+fn get_time_axis_markers(
+    start_sec: f64,
+    end_sec: f64,
+    tick_unit: f64,
+    label_interval: u32,
+    max_sec: f64,
+) -> serde_json::Value {
+    assert!(start_sec <= end_sec);
+    assert!(label_interval > 0);
+    json!(calc_time_axis_markers(
+        start_sec,
+        end_sec,
+        tick_unit,
+        label_interval,
+        max_sec,
+        TEMPO_GRID.read().as_ref(),
+    ))
+}
+
+/// Show the time axis in bars/beats instead of seconds.
+/// The markers of getTimeAxisMarkers are in bars/beats (tickUnit in beats),
+/// and getFittedTimeAxisMarkers labels bar numbers (and beats if zoomed in).
+/// time_signature: (beats per bar, beat unit), offset_sec: time of the first beat of bar 1
+#[napi]
+fn set_tempo_grid(bpm: f64, time_signature: (u32, u32), offset_sec: f64) -> Result<()> {
+    let tempo_grid = TempoGrid::new(bpm, time_signature, offset_sec).ok_or_else(|| {
+        Error::new(
+            Status::InvalidArg,
+            "bpm and time signature should be positive.",
+        )
+    })?;
+    *TEMPO_GRID.write() = Some(tempo_grid);
+    Ok(())
+}
+
+/// show the time axis in seconds again
+#[napi]
+fn clear_tempo_grid() {
+    *TEMPO_GRID.write() = None;
+}
+
+#[napi]
+fn get_freq_axis_markers(
+    max_num_ticks: u32,
+    max_num_labels: u32,
+    max_track_hz: f64,
+    view_id: Option<u32>,
+) -> serde_json::Value {
+    assert_axis_params(max_num_ticks, max_num_labels);
+
+    json!(calc_broken_freq_axis_markers(
+        calc_valid_hz_range(max_track_hz as f32),
+        SPEC_SETTING.read().freq_scale,
+        get_freq_break_of(view_id),
+        max_num_ticks,
+        max_num_labels
+    ))
+}
+
+#[napi]
+fn get_amp_axis_markers(
+    max_num_ticks: u32,
+    max_num_labels: u32,
+    amp_range: (f64, f64),
+) -> serde_json::Value {
+    assert_axis_params(max_num_ticks, max_num_labels);
+    assert!(amp_range.0 < amp_range.1);
+
+    json!(calc_amp_axis_markers(
+        max_num_ticks,
+        max_num_labels,
+        (amp_range.0 as f32, amp_range.1 as f32),
+    ))
+}
+
+#[napi(js_name = "getdBAxisMarkers")]
+#[allow(non_snake_case)]
+fn get_dB_axis_markers(
+    max_num_ticks: u32,
+    max_num_labels: u32,
+    min_dB: f64,
+    max_dB: f64,
+) -> serde_json::Value {
+    assert_axis_params(max_num_ticks, max_num_labels);
+
+    json!(calc_dB_axis_markers(
+        max_num_ticks,
+        max_num_labels,
+        (min_dB as f32, max_dB as f32)
+    ))
+}
+
+/// Time axis markers with the densest labels not overlapping each other in the width (px).
+/// label_metrics: {charWidths, defaultCharWidth, labelHeight, minGap} measured in the frontend.
+/// Unlabeled markers are minor ticks.
+#[napi]
+fn get_fitted_time_axis_markers(
+    start_sec: f64,
+    end_sec: f64,
+    width: f64,
+    max_sec: f64,
+    label_metrics: serde_json::Value,
+) -> Result<serde_json::Value> {
+    assert!(start_sec < end_sec);
+    assert!(width > 0.);
+    let metrics: LabelMetrics = serde_json::from_value(label_metrics)?;
+
+    Ok(json!(fit_time_axis_markers(
+        start_sec,
+        end_sec,
+        width as f32,
+        max_sec,
+        TEMPO_GRID.read().as_ref(),
+        &metrics
+    )))
+}
+
+/// frequency axis markers with the most labels not overlapping each other in the height (px)
+#[napi]
+fn get_fitted_freq_axis_markers(
+    height: f64,
+    max_track_hz: f64,
+    label_metrics: serde_json::Value,
+    view_id: Option<u32>,
+) -> Result<serde_json::Value> {
+    assert!(height > 0.);
+    let metrics: LabelMetrics = serde_json::from_value(label_metrics)?;
+
+    Ok(json!(fit_freq_axis_markers(
+        calc_valid_hz_range(max_track_hz as f32),
+        SPEC_SETTING.read().freq_scale,
+        get_freq_break_of(view_id),
+        height as f32,
+        &metrics
+    )))
+}
+
+/// amplitude axis markers with the most labels not overlapping each other in the height (px)
+#[napi]
+fn get_fitted_amp_axis_markers(
+    height: f64,
+    amp_range: (f64, f64),
+    label_metrics: serde_json::Value,
+) -> Result<serde_json::Value> {
+    assert!(height > 0.);
+    assert!(amp_range.0 < amp_range.1);
+    let metrics: LabelMetrics = serde_json::from_value(label_metrics)?;
+
+    Ok(json!(fit_amp_axis_markers(
+        (amp_range.0 as f32, amp_range.1 as f32),
+        height as f32,
+        &metrics
+    )))
+}
+
+/// dB axis markers with the most labels not overlapping each other in the height (px)
+#[napi(js_name = "getFitteddBAxisMarkers")]
+#[allow(non_snake_case)]
+fn get_fitted_dB_axis_markers(
+    height: f64,
+    min_dB: f64,
+    max_dB: f64,
+    label_metrics: serde_json::Value,
+) -> Result<serde_json::Value> {
+    assert!(height > 0.);
+    let metrics: LabelMetrics = serde_json::from_value(label_metrics)?;
+
+    Ok(json!(fit_dB_axis_markers(
+        (min_dB as f32, max_dB as f32),
+        height as f32,
+        &metrics
+    )))
+}
+
+#[napi(js_name = "getMaxdB")]
+#[allow(non_snake_case)]
+async fn get_max_dB() -> f64 {
+    TM.read().await.max_dB as f64
+}
+
+#[napi(js_name = "getMindB")]
+#[allow(non_snake_case)]
+async fn get_min_dB() -> f64 {
+    TM.read().await.min_dB as f64
+}
+
+#[napi]
+fn get_max_track_hz() -> f64 {
+    TM.blocking_read().max_sr as f64 / 2.
+}
+
+#[napi]
+fn get_longest_track_length_sec() -> f64 {
+    TRACK_LIST.blocking_read().max_sec
+}
+
+/// start sec of the view following the playhead at position_sec during playback.
+/// All the render layers should scroll to this value to move identically.
+#[napi]
+fn get_follow_scroll_target(
+    position_sec: f64,
+    view_width_sec: f64,
+    mode: FollowMode,
+) -> Result<f64> {
+    if !view_width_sec.is_finite() || view_width_sec <= 0. {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "view_width_sec should be positive.",
+        ));
+    }
+    let max_sec = TRACK_LIST.blocking_read().max_sec;
+    Ok(mode.scroll_target(position_sec, view_width_sec, max_sec))
+}
+
+/// visible time and frequency ranges shared with the observers (e.g. OSC clients)
+#[napi]
+fn get_viewport() -> ViewportState {
+    viewport_sync::viewport()
+}
+
+/// Set the visible ranges. sec_range: [start, end], hz_range: [min, max].
+/// The observers waiting in waitViewportChanged are notified if the ranges are changed.
+#[napi]
+fn set_viewport(sec_range: Vec<f64>, hz_range: Vec<f64>) -> Result<ViewportState> {
+    let (&[start_sec, end_sec], &[min_hz, max_hz]) = (sec_range.as_slice(), hz_range.as_slice())
+    else {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "sec_range and hz_range should have two elements.",
+        ));
+    };
+    viewport_sync::set_viewport((start_sec, end_sec), (min_hz, max_hz))
+        .ok_or_else(|| Error::new(Status::InvalidArg, "Invalid viewport."))
+}
+
+/// viewport-changed event: resolved with the viewport when its version differs from version
+/// (immediately if it already differs), e.g. changed by an OSC client mirroring another view.
+#[napi]
+async fn wait_viewport_changed(version: u32) -> ViewportState {
+    viewport_sync::viewport_changed(version).await
+}
+
+#[napi]
+fn get_channel_counts(track_id: u32) -> u32 {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .map_or(0, |track| track.n_ch() as u32)
+}
+
+#[napi]
+fn get_length_sec(track_id: u32) -> f64 {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .map_or(0., |track| track.sec())
+}
+
+#[napi]
+fn get_sample_rate(track_id: u32) -> u32 {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .map_or(0, |track| track.sr())
+}
+
+/// stats of the track in the global selection. null if no selection or out of the track
+#[napi]
+async fn get_selection_stats(track_id: u32) -> Result<Option<SelectionStats>> {
+    let Some(range) = *SELECTION.read() else {
+        return Ok(None);
+    };
+    spawn_blocking_job(JobKind::Stats, move || {
+        let tracklist = TRACK_LIST.blocking_read();
+        let track = tracklist
+            .get(track_id as usize)
+            .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))?;
+        let range = track.timeline().session_to_track_range(range);
+        Ok(track.stats_in_range(range).map(|stats| SelectionStats {
+            global_lufs: stats.global_lufs,
+            rms_dB: stats.rms_dB as f64,
+            max_peak_dB: stats.max_peak_dB as f64,
+        }))
+    })
+    .await
+    .unwrap()
+}
+
+#[napi]
+fn get_format_info(track_id: u32) -> AudioFormatInfo {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .map_or_else(Default::default, |track| track.format_info.clone())
+}
+
+/// Enable the online lookup of identifyTrack with the AcoustID API key of the user.
+/// null disables the lookup.
+#[napi]
+fn set_acoustid_api_key(key: Option<String>) {
+    acoustid::set_api_key(key);
+}
+
+/// true if fpcalc of Chromaprint (needed by identifyTrack) is bundled or in PATH
+#[napi]
+fn is_fingerprint_available() -> bool {
+    acoustid::find_fpcalc().is_some()
+}
+
+/// Identify the track by its AcoustID fingerprint (calculated by fpcalc of Chromaprint)
+/// and keep the title and the artist of the matched recording (see getTrackIdentity).
+/// null if no recording matches or the lookup failed (e.g. offline).
+/// Err with "fpcalc of Chromaprint is not found. ..." if fpcalc is not available.
+#[napi]
+async fn identify_track(track_id: u32) -> Result<Option<TrackIdentity>> {
+    if !acoustid::is_enabled() {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The online lookup is disabled. Set the AcoustID API key first.",
+        ));
+    }
+    let id = track_id as usize;
+    let path = TRACK_LIST
+        .read()
+        .await
+        .get(id)
+        .map(|track| track.path_string())
+        .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))?;
+    let identity = spawn_blocking_job(JobKind::Stats, move || {
+        let (duration, fingerprint) = acoustid::fingerprint(Path::new(&path))?;
+        match acoustid::lookup(duration, &fingerprint) {
+            Ok(identity) => Ok(identity),
+            Err(e) => {
+                warn!("Failed to look up the track {}: {}", path, e);
+                Ok(None)
+            }
+        }
+    })
+    .await
+    .unwrap()
+    .map_err(|e| Error::from_reason(e.to_string()))?;
+    if let Some(identity) = &identity {
+        acoustid::set_identity(id, identity.clone());
+    }
+    Ok(identity)
+}
+
+/// title and artist found by identifyTrack. null if not identified.
+#[napi]
+fn get_track_identity(track_id: u32) -> Option<TrackIdentity> {
+    acoustid::identity(track_id as usize)
+}
+
+/// true for each channel of the file which is decoded (see setDecodedChannels).
+/// Empty if the track doesn't exist.
+#[napi]
+fn get_decoded_channel_mask(track_id: u32) -> Vec<bool> {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .map_or_else(Vec::new, |track| {
+            let channels = track.decoded_channels();
+            (0..track.format_info.n_ch)
+                .map(|ch| channels.contains(&ch))
+                .collect()
+        })
+}
+
+/// time ranges of corrupt packets which were filled with silence while decoding the track
+#[napi]
+fn get_decode_gaps(track_id: u32) -> Vec<DecodeGap> {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .map_or_else(Vec::new, |track| track.decode_gaps().to_vec())
+}
+
+#[napi(js_name = "getGlobalLUFS")]
+fn get_global_lufs(track_id: u32) -> f64 {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .map_or(f64::NEG_INFINITY, |track| track.stats().global_lufs)
+}
+
+#[napi(js_name = "getRMSdB")]
+#[allow(non_snake_case)]
+fn get_rms_dB(track_id: u32) -> f64 {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .map_or(f64::NEG_INFINITY, |track| track.stats().rms_dB as f64)
+}
+
+#[napi(js_name = "getMaxPeakdB")]
+#[allow(non_snake_case)]
+fn get_max_peak_dB(track_id: u32) -> f64 {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .map_or(f64::NEG_INFINITY, |track| track.stats().max_peak_dB as f64)
+}
+
+/// LRA, crest factor, PSR, and PLR of the track (after normalization and guard clipping)
+#[napi]
+fn get_dynamics_stats(track_id: u32) -> Result<DynamicsStats> {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .map(|track| track.stats().dynamics())
+        .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))
+}
+
+/// loudness, RMS, and peak of the channel of the track measured alone,
+/// for quantifying the imbalance between channels
+#[napi]
+fn get_channel_stats(track_id: u32, ch: u32) -> Result<ChannelStats> {
+    let tracklist = TRACK_LIST.blocking_read();
+    let track = tracklist
+        .get(track_id as usize)
+        .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))?;
+    track
+        .stats()
+        .channel(ch as usize)
+        .ok_or_else(|| Error::new(Status::InvalidArg, "The channel doesn't exist."))
+}
+
+#[napi]
+fn get_guard_clip_stats(track_id: u32) -> String {
+    TRACK_LIST
+        .blocking_read()
+        .guard_clip_summary(track_id as usize)
+        .unwrap_or_default()
+}
+
+/// gain staging of the track: true peak before normalization, normalization gain,
+/// guard clipping gain, and the resulting peaks and headroom (overall and per channel)
+#[napi]
+async fn get_headroom_report(track_id: u32) -> Result<HeadroomReport> {
+    spawn_blocking_job(JobKind::Stats, move || {
+        TRACK_LIST
+            .blocking_read()
+            .headroom_report(track_id as usize)
+    })
+    .await
+    .unwrap()
+    .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))
+}
+
+/// SHA-256 (hex) of the decoded PCM of the track (before normalization and channel transforms).
+/// Equal hashes mean the files decode to bit-identical audio with the same sample rate.
+#[napi]
+async fn get_pcm_hash(track_id: u32) -> Result<String> {
+    spawn_blocking_job(JobKind::Stats, move || {
+        TRACK_LIST
+            .blocking_read()
+            .get(track_id as usize)
+            .map(|track| track.pcm_hash())
+    })
+    .await
+    .unwrap()
+    .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))
+}
+
+/// transcode the original audio (before normalization) of tracks to out_dir on the rayon pool.
+/// Only the global selection is exported if exists unless params.wholeTrack is true.
+/// The progress can be polled by getTranscodeProgress.
+#[napi]
+async fn transcode_tracks(
+    track_ids: Vec<u32>,
+    codec: TranscodeCodec,
+    params: serde_json::Value,
+    out_dir: String,
+) -> Result<Vec<TranscodeResult>> {
+    let params: TranscodeParams = serde_json::from_value(params)?;
+    let out_dir = PathBuf::from(out_dir);
+    if !out_dir.is_dir() {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The output directory doesn't exist.",
+        ));
+    }
+    let selection = if params.whole_track {
+        None
+    } else {
+        *SELECTION.read()
+    };
+    *TRANSCODE_PROGRESS.write() = JobProgress {
+        n_done: 0,
+        n_total: track_ids.len() as u32,
+    };
+    let results = spawn_blocking_job(JobKind::Export, move || {
+        // output paths are determined in advance to avoid collisions among tracks
+        let paths = {
+            let tracklist = TRACK_LIST.blocking_read();
+            track_ids.iter().fold(Vec::new(), |mut paths, &id| {
+                let stem = tracklist
+                    .get(id as usize)
+                    .map_or_else(|| id.to_string(), |track| track.file_stem());
+                paths.push(unique_output_path(&out_dir, &stem, codec, &paths));
+                paths
+            })
+        };
+        track_ids
+            .into_par_iter()
+            .zip(paths)
+            .map(|(id, path)| {
+                let result = TRACK_LIST
+                    .blocking_read()
+                    .get(id as usize)
+                    .map(|track| {
+                        let wavs = track.original_wavs();
+                        let wavs = match selection {
+                            Some(range) => {
+                                let (i_start, i_end) = track.sample_range(range);
+                                wavs.slice_move(ndarray::s![.., i_start..i_end])
+                            }
+                            None => wavs,
+                        };
+                        (wavs.to_owned(), track.sr())
+                    })
+                    .ok_or_else(|| "The track doesn't exist.".to_string())
+                    .and_then(|(wavs, sr)| {
+                        transcode(wavs.view(), sr, codec, &params, &path).map_err(|e| e.to_string())
+                    });
+                TRANSCODE_PROGRESS.write().n_done += 1;
+                match result {
+                    Ok(()) => TranscodeResult {
+                        track_id: id,
+                        path: path.to_string_lossy().into_owned(),
+                        err: "".to_string(),
+                    },
+                    Err(err) => TranscodeResult {
+                        track_id: id,
+                        path: "".to_string(),
+                        err,
+                    },
+                }
+            })
+            .collect()
+    })
+    .await
+    .unwrap();
+    Ok(results)
+}
+
+/// integrated loudness and maximum momentary/short-term loudness of each segment_sec of the track
+/// (e.g. 60 for a per-minute table). The last segment can be shorter.
+#[napi]
+async fn get_segmented_loudness(track_id: u32, segment_sec: f64) -> Result<Vec<SegmentLoudness>> {
+    calc_segmented_loudness_of(track_id, segment_sec).await
+}
+
+/// how much each of the n_bands highest octave bands (centered at 1 kHz x 2^k, down to 15.625 Hz)
+/// contributes to the integrated loudness of the track (K-weighted, gated by the whole signal).
+/// The bands are ordered from the lowest, and their contributions sum up to 1.
+#[napi]
+async fn get_band_loudness_contribution(
+    track_id: u32,
+    n_bands: u32,
+) -> Result<BandLoudnessContribution> {
+    if n_bands == 0 {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The number of bands should be positive.",
+        ));
+    }
+    spawn_blocking_job(JobKind::Stats, move || {
+        TRACK_LIST
+            .blocking_read()
+            .get(track_id as usize)
+            .map(|track| track.band_loudness(n_bands as usize))
+    })
+    .await
+    .unwrap()
+    .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))
+}
+
+/// write the table of get_segmented_loudness as CSV
+#[napi]
+async fn export_segmented_loudness_csv(
+    track_id: u32,
+    segment_sec: f64,
+    path: String,
+) -> Result<()> {
+    let segments = calc_segmented_loudness_of(track_id, segment_sec).await?;
+    spawn_blocking_job(JobKind::Export, move || {
+        std::fs::write(&path, segmented_loudness_to_csv(&segments))
+    })
+    .await
+    .unwrap()
+    .map_err(|e| Error::from_reason(e.to_string()))
+}
+
+async fn calc_segmented_loudness_of(
+    track_id: u32,
+    segment_sec: f64,
+) -> Result<Vec<SegmentLoudness>> {
+    if !segment_sec.is_finite() || segment_sec <= 0. {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "segment_sec should be positive.",
+        ));
+    }
+    spawn_blocking_job(JobKind::Stats, move || {
+        TRACK_LIST
+            .blocking_read()
+            .get(track_id as usize)
+            .map(|track| track.segmented_loudness(segment_sec))
+    })
+    .await
+    .unwrap()
+    .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))
+}
+
+/// write a standalone HTML report with the overview images, stats, and guard clipping summaries
+/// of all tracks
+#[napi]
+async fn export_session_report_html(path: String) -> Result<()> {
+    spawn_blocking_job(JobKind::Export, move || {
+        let tm = TM.blocking_read();
+        let tracklist = TRACK_LIST.blocking_read();
+        std::fs::write(&path, render_report_html(&tm, &tracklist))
+    })
+    .await
+    .unwrap()
+    .map_err(|e| Error::from_reason(e.to_string()))
+}
+
+/// Run a script (JSON array of steps such as addFolder, setCommonNormalize, exportReport)
+/// sequentially. Stops at the first failed step.
+/// The frontend should add result.addedTrackIds to its track list.
+#[napi]
+async fn run_script(script: serde_json::Value) -> Result<script::ScriptResult> {
+    crash::record_command("runScript");
+    let steps = script::parse_script(script).map_err(|e| Error::new(Status::InvalidArg, e))?;
+    Ok(script::run(steps).await)
+}
+
+/// write a PNG of the visible time/freq window of the visible tracks stacked.
+/// layout.axes, layout.freqGrid, and layout.colorbar burn in the axes, the frequency grid,
+/// and the colorbar (dB) respectively.
+#[napi]
+async fn export_viewport_image(layout: serde_json::Value, path: String) -> Result<()> {
+    let layout: ViewportLayout = serde_json::from_value(layout)?;
+    spawn_blocking_job(JobKind::Export, move || {
+        let tm = TM.blocking_read();
+        let tracklist = TRACK_LIST.blocking_read();
+        let png = render_viewport_png(&tm, &tracklist, &layout)?;
+        std::fs::write(&path, png).map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap()
+    .map_err(Error::from_reason)
+}
+
+/// Write the global selection of the track to temporary files to be put on the clipboard
+/// by the frontend (e.g. for bug reports or handing off to a DAW).
+/// The files of the previous call of the same selection are overwritten.
+#[napi]
+async fn export_selection_for_clipboard(
+    track_id: u32,
+    kinds: Vec<ClipboardContentKind>,
+) -> Result<SelectionClipboardFiles> {
+    if kinds.is_empty() {
+        return Err(Error::new(Status::InvalidArg, "No content kind is given."));
+    }
+    let Some(selection) = *SELECTION.read() else {
+        return Err(Error::new(Status::InvalidArg, "No selection exists."));
+    };
+    let id = track_id as usize;
+    spawn_blocking_job(JobKind::Export, move || {
+        let tm = TM.blocking_read();
+        let tracklist = TRACK_LIST.blocking_read();
+        let track = tracklist
+            .get(id)
+            .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))?;
+        let timeline = track.timeline();
+        let (start_sec, end_sec) = timeline.session_to_track_range(selection);
+        let (start_sec, end_sec) = (start_sec.max(0.), end_sec.min(track.sec()));
+        if start_sec >= end_sec {
+            return Err(Error::new(
+                Status::InvalidArg,
+                "The selection is out of the track.",
+            ));
+        }
+        let to_err = |e: std::io::Error| Error::from_reason(e.to_string());
+        let out_dir = std::env::temp_dir().join(CLIPBOARD_DIR_NAME);
+        std::fs::create_dir_all(&out_dir).map_err(to_err)?;
+        let stem = format!("{}_{:.3}-{:.3}s", track.file_stem(), start_sec, end_sec);
+        let image_path = if kinds.contains(&ClipboardContentKind::Image) {
+            // the viewport is on the session timeline
+            let (start_sec, end_sec) = timeline.track_to_session_range((start_sec, end_sec));
+            let layout = ViewportLayout {
+                track_ids: vec![id],
+                start_sec,
+                end_sec,
+                ..Default::default()
+            };
+            let png = render_viewport_png(&tm, &tracklist, &layout).map_err(Error::from_reason)?;
+            let path = out_dir.join(format!("{}.png", stem));
+            std::fs::write(&path, png).map_err(to_err)?;
+            tempfiles::register(&path);
+            Some(path.to_string_lossy().into_owned())
+        } else {
+            None
+        };
+        let audio_path = if kinds.contains(&ClipboardContentKind::Audio) {
+            let (i_start, i_end) = track.sample_range((start_sec, end_sec));
+            let wavs = track
+                .original_wavs()
+                .slice_move(ndarray::s![.., i_start..i_end]);
+            let path = out_dir.join(format!("{}.wav", stem));
+            transcode(
+                wavs,
+                track.sr(),
+                TranscodeCodec::Wav,
+                &TranscodeParams::default(),
+                &path,
+            )
+            .map_err(to_err)?;
+            tempfiles::register(&path);
+            Some(path.to_string_lossy().into_owned())
+        } else {
+            None
+        };
+        Ok(SelectionClipboardFiles {
+            image_path,
+            audio_path,
+        })
+    })
+    .await
+    .unwrap()
+}
+
+/// number and size of the temporary files created by thesia (downloads, exports, ...)
+#[napi]
+fn get_temp_files_usage() -> TempFilesUsage {
+    tempfiles::usage()
+}
+
+/// Remove the temporary files except the sources of the open tracks (e.g. downloaded URLs).
+/// Returns the usage freed. All the temporary files are removed on exit anyway.
+#[napi]
+async fn clean_temp_files() -> TempFilesUsage {
+    let tracklist = TRACK_LIST.read().await;
+    let keep: Vec<_> = tracklist
+        .all_ids()
+        .into_iter()
+        .filter_map(|id| tracklist.get(id))
+        .map(|track| PathBuf::from(track.path_string()))
+        .collect();
+    spawn_blocking_job(JobKind::Export, move || tempfiles::clean(&keep))
+        .await
+        .unwrap()
+}
+
+/// counts of queued/running background jobs for the busy indicator
+#[napi]
+fn get_backend_busy_state() -> BackendBusyState {
+    jobs::busy_state()
+}
+
+/// channels whose specs are being calculated (or queued) with the fraction done,
+/// e.g. to show the progress on the tracks of long files after addTracks or setSpecSetting.
+/// The changes are notified by waitSpecProgressChanged.
+#[napi]
+fn get_spec_progress() -> Vec<SpecProgress> {
+    format_spec_progress(spec_progress())
+}
+
+/// spec-progress event: resolved with the progress when its version differs from version
+/// (immediately if it already differs). Not resolved while the progress is unchanged.
+#[napi]
+async fn wait_spec_progress_changed(version: u32) -> SpecProgressState {
+    let (version, progress) = spec_progress_changed(version).await;
+    SpecProgressState {
+        progress: format_spec_progress(progress),
+        version,
+    }
+}
+
+fn format_spec_progress(progress: Vec<(IdCh, f32)>) -> Vec<SpecProgress> {
+    progress
+        .into_iter()
+        .map(|((id, ch), fraction)| SpecProgress {
+            id_ch: format_id_ch(id, ch),
+            fraction: fraction as f64,
+        })
+        .collect()
+}
+
+#[napi]
+fn get_transcode_progress() -> JobProgress {
+    TRANSCODE_PROGRESS.read().clone()
+}
+
+/// returns null for non-stereo tracks
+#[napi]
+async fn get_mono_compatibility(track_id: u32) -> Option<MonoCompatibility> {
+    spawn_blocking_job(JobKind::Stats, move || {
+        TRACK_LIST
+            .blocking_read()
+            .get(track_id as usize)
+            .and_then(|track| track.mono_compatibility())
+    })
+    .await
+    .unwrap()
+}
+
+/// detect dual-mono, polarity-inverted, or silent channels. returns null for non-stereo tracks
+#[napi]
+async fn analyze_channel_relationship(track_id: u32) -> Option<ChannelRelationship> {
+    spawn_blocking_job(JobKind::Stats, move || {
+        TRACK_LIST
+            .blocking_read()
+            .get(track_id as usize)
+            .and_then(|track| track.channel_relationship())
+    })
+    .await
+    .unwrap()
+}
+
+/// find discontinuities and zero runs characteristic of recording dropouts.
+/// sensitivity: 0 ~ 1 (higher detects more)
+#[napi]
+async fn detect_dropouts(track_id: u32, sensitivity: f64) -> Result<Vec<Dropout>> {
+    spawn_blocking_job(JobKind::Stats, move || {
+        TRACK_LIST
+            .blocking_read()
+            .get(track_id as usize)
+            .map(|track| track.dropouts(sensitivity))
+    })
+    .await
+    .unwrap()
+    .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))
+}
+
+/// number of the samples of the channel in each of n_bins bins dividing -1 ~ 1 uniformly
+/// (before normalization). The samples out of the range are counted in the first/last bin.
+#[napi]
+async fn get_amplitude_histogram(track_id: u32, ch: u32, n_bins: u32) -> Result<Vec<u32>> {
+    if n_bins == 0 {
+        return Err(Error::new(Status::InvalidArg, "n_bins should be positive."));
+    }
+    spawn_blocking_job(JobKind::Stats, move || {
+        let tracklist = TRACK_LIST.blocking_read();
+        let track = tracklist
+            .get(track_id as usize)
+            .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))?;
+        if ch as usize >= track.n_ch() {
+            return Err(Error::new(Status::InvalidArg, "The channel doesn't exist."));
+        }
+        Ok(track.amplitude_histogram(ch as usize, n_bins as usize))
+    })
+    .await
+    .unwrap()
+}
+
+/// bit depth actually used by the samples of the track,
+/// e.g. 16 for a 24-bit file which contains 16-bit data.
+#[napi]
+async fn get_bit_depth_usage(track_id: u32) -> Result<BitDepthUsage> {
+    spawn_blocking_job(JobKind::Stats, move || {
+        TRACK_LIST
+            .blocking_read()
+            .get(track_id as usize)
+            .map(|track| track.bit_depth_usage())
+    })
+    .await
+    .unwrap()
+    .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))
+}
+
+/// Estimate how likely the track has been through a lossy encoder (e.g. a wav converted from
+/// an mp3) from the spectral cutoff shelf and pre-echo before transients.
+#[napi]
+async fn estimate_lossy_generation(track_id: u32) -> Result<LossyGenerationEstimate> {
+    spawn_blocking_job(JobKind::Stats, move || {
+        TRACK_LIST
+            .blocking_read()
+            .get(track_id as usize)
+            .map(|track| track.lossy_generation())
+    })
+    .await
+    .unwrap()
+    .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))
+}
+
+/// Find the segments of all the tracks similar to the selection (start_sec ~ end_sec) of
+/// the channel ch of the track (e.g. every occurrence of a beep or an artifact across takes).
+/// threshold: minimum similarity (0 ~ 1, e.g. 0.9).
+/// The result is ranked by the similarity and includes the selection itself.
+#[napi]
+async fn find_similar(
+    track_id: u32,
+    ch: u32,
+    start_sec: f64,
+    end_sec: f64,
+    threshold: f64,
+) -> Result<Vec<SimilarSegment>> {
+    if !(start_sec.is_finite() && start_sec < end_sec && (0.0..=1.).contains(&threshold)) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "start_sec should be less than end_sec, and threshold should be in [0, 1].",
+        ));
+    }
+    spawn_blocking_job(JobKind::Stats, move || {
+        TRACK_LIST.blocking_read().find_similar(
+            (track_id as usize, ch as usize),
+            (start_sec, end_sec),
+            threshold,
+        )
+    })
+    .await
+    .unwrap()
+    .map_err(|e| Error::new(Status::InvalidArg, e))
+}
+
+/// N x N matrix comparing every pair of the tracks (e.g. which files are versions of each other).
+/// [i][j]: the estimated offset, correlation coefficient and the residual RMS after aligning
+/// track_ids[j] to track_ids[i] and matching the gain. null on the diagonal or if either is silent.
+/// The beginning (30 sec) of the mono mixdown of the original audio is compared.
+/// The progress (number of pairs) can be polled by getCompareProgress.
+#[napi]
+async fn compute_compare_matrix(
+    track_ids: Vec<u32>,
+) -> Result<Vec<Vec<Option<TrackPairComparison>>>> {
+    let n = track_ids.len() as u32;
+    *COMPARE_PROGRESS.write() = JobProgress {
+        n_done: 0,
+        n_total: n * n.saturating_sub(1) / 2,
+    };
+    let ids: Vec<_> = track_ids.into_iter().map(|x| x as usize).collect();
+    spawn_blocking_job(JobKind::Stats, move || {
+        TRACK_LIST
+            .blocking_read()
+            .compare_tracks(&ids, || COMPARE_PROGRESS.write().n_done += 1)
+    })
+    .await
+    .unwrap()
+    .map_err(|e| Error::new(Status::InvalidArg, e))
+}
+
+#[napi]
+fn get_compare_progress() -> JobProgress {
+    COMPARE_PROGRESS.read().clone()
+}
+
+/// spectral centroid (Hz) of each frame of the channel (e.g. to compare the high frequency
+/// behavior of de-essers or codecs over time). The window is the same as the spectrogram.
+/// hop_sec: default to the hop of the spectrogram
+#[napi]
+async fn get_centroid_timeseries(
+    track_id: u32,
+    ch: u32,
+    hop_sec: Option<f64>,
+) -> Result<CentroidTimeseries> {
+    if hop_sec.is_some_and(|hop_sec| !(hop_sec.is_finite() && hop_sec >= MIN_CENTROID_HOP_SEC)) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            format!("hop_sec should be at least {}.", MIN_CENTROID_HOP_SEC),
+        ));
+    }
+    let setting = SPEC_SETTING.read().clone();
+    spawn_blocking_job(JobKind::Stats, move || {
+        let tracklist = TRACK_LIST.blocking_read();
+        let track = tracklist
+            .get(track_id as usize)
+            .filter(|track| (ch as usize) < track.n_ch())
+            .ok_or_else(|| Error::new(Status::InvalidArg, "The track or channel doesn't exist."))?;
+        let sr = track.sr();
+        let (default_hop_length, win_length, n_fft) = setting.calc_framing_params(sr);
+        let hop_length = hop_sec.map_or(default_hop_length, |hop_sec| {
+            ((hop_sec * sr as f64).round() as usize).max(1)
+        });
+        let centroid = calc_spectral_centroid(
+            track.channel(ch as usize),
+            sr,
+            win_length,
+            hop_length,
+            n_fft,
+        );
+        Ok(CentroidTimeseries {
+            hop_sec: hop_length as f64 / sr as f64,
+            hz: centroid.into_iter().map(|x| x as f64).collect(),
+        })
+    })
+    .await
+    .unwrap()
+}
+
+#[napi]
+fn get_path(track_id: u32) -> String {
+    TRACK_LIST
+        .blocking_read()
+        .get(track_id as usize)
+        .map_or_else(String::new, |track| track.path_string())
+}
+
+#[napi]
+fn get_file_name(track_id: u32) -> String {
+    TRACK_LIST
+        .blocking_read()
+        .filename(track_id as usize)
+        .to_owned()
+}
+
+#[napi]
+fn get_color_map() -> Buffer {
+    visualize::get_colormap_rgb().into()
+}
+
+/// Negotiate the length of the colormap with the gradient resolution of the frontend
+/// (e.g. the height of the colorbar in device pixels, which depends on the number of tracks
+/// shown, the display scaling and the zoom). Call again whenever the resolution changes.
+/// Returns the RGB colors of the length (2 ~ 8192) from the lowest to the highest dB.
+/// The colormaps of the recent lengths are cached, so switching back to a length is cheap.
+#[napi]
+async fn set_colormap_length(length: u32) -> Result<Buffer> {
+    if !(2..=MAX_COLORMAP_LENGTH).contains(&length) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            format!(
+                "The length of the colormap should be 2 ~ {}.",
+                MAX_COLORMAP_LENGTH
+            ),
+        ));
+    }
+    Ok(TM
+        .write()
+        .await
+        .colormap_rgb(length as usize)
+        .to_vec()
+        .into())
+}
+
+/// n_steps (>= 2) colors of the spectrogram colormap from the lowest to the highest dB
+/// paired with their exact dB values under the current dB range and display tilt.
+/// dB_range: the range returned by setImageState in the Viewport auto contrast mode.
+/// min dB ~ max dB of all tracks is used if not given.
+#[napi]
+#[allow(non_snake_case)]
+async fn get_colorbar(n_steps: u32, dB_range: Option<(f64, f64)>) -> Result<Vec<ColorbarEntry>> {
+    if n_steps < 2 {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The number of steps should be at least 2.",
+        ));
+    }
+    let tm = TM.read().await;
+    let dB_range = dB_range.map(|(min_dB, max_dB)| (min_dB as f32, max_dB as f32));
+    if dB_range.is_none() && !tm.min_dB.is_finite() {
+        return Err(Error::from_reason("No track exists."));
+    }
+    Ok(tm
+        .colorbar(n_steps as usize, dB_range)
+        .into_iter()
+        .map(|(dB, rgba)| ColorbarEntry {
+            dB: dB as f64,
+            rgba: rgba.to_vec(),
+        })
+        .collect())
+}
+
+#[napi(js_name = "setVolumedB")]
+#[allow(non_snake_case)]
+async fn set_volume_dB(volume_dB: f64) {
+    player::send(PlayerCommand::SetVolumedB(volume_dB)).await;
+}
+
+/// attenuate the playback by 20 dB (on top of the volume)
+#[napi]
+async fn set_monitor_dim(dim: bool) {
+    player::update_monitor(|monitor| monitor.dim = dim).await;
+}
+
+#[napi]
+async fn set_monitor_mute(mute: bool) {
+    player::update_monitor(|monitor| monitor.mute = mute).await;
+}
+
+/// calibrated monitor gain (dB, <= 0) applied on top of the volume,
+/// so that A/B listening happens at a consistent level
+#[napi(js_name = "setReferenceLeveldB")]
+#[allow(non_snake_case)]
+async fn set_reference_level_dB(level_dB: f64) -> Result<()> {
+    if !(level_dB.is_finite() && level_dB <= 0.) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The reference level should be at most 0 dB.",
+        ));
+    }
+    player::update_monitor(|monitor| monitor.reference_level_dB = level_dB).await;
+    Ok(())
+}
+
+/// threshold_dB is clamped to 0 dBFS at most
+#[napi]
+#[allow(non_snake_case)]
+async fn set_output_limiter(enabled: bool, threshold_dB: f64) {
+    let threshold = enabled.then(|| threshold_dB.min(0.).amp_from_dB_default());
+    player::send(PlayerCommand::SetOutputLimiter(threshold)).await;
+}
+
+#[napi]
+async fn set_player_downmix(mode: DownmixMode) {
+    player::send(PlayerCommand::SetDownmix(mode)).await;
+}
+
+/// convolve the playback with the impulse response in the audio file (e.g. headphone correction).
+/// null to disable.
+#[napi]
+async fn set_player_ir(path: Option<String>) -> Result<()> {
+    let ir = match path {
+        Some(path) => Some(
+            spawn_blocking_job(JobKind::Stats, move || ImpulseResponse::open(&path))
+                .await
+                .unwrap()
+                .map_err(|e| Error::from_reason(e.to_string()))?,
+        ),
+        None => None,
+    };
+    player::send(PlayerCommand::SetImpulseResponse(ir)).await;
+    Ok(())
+}
+
+/// preview of denoising by subtracting the noise floor of the profile track from the playback.
+/// amount: 0 ~ 1. null profile_track_id to disable.
+#[napi]
+async fn set_player_denoise(profile_track_id: Option<u32>, amount: f64) -> Result<()> {
+    if !(0.0..=1.0).contains(&amount) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The denoise amount should be in 0 ~ 1.",
+        ));
+    }
+    let denoiser = match profile_track_id {
+        Some(id) => Some(get_or_calc_denoiser(id as usize, amount).await?),
+        None => None,
+    };
+    player::send(PlayerCommand::SetDenoise(denoiser)).await;
+    Ok(())
+}
+
+/// denoiser with the noise profile of the track calculated in a job (reused if cached)
+async fn get_or_calc_denoiser(id: usize, amount: f64) -> Result<SpectralDenoiser> {
+    let version = TRACK_LIST
+        .read()
+        .await
+        .get(id)
+        .map(|track| track.playback_version());
+    let Some(version) = version else {
+        return Err(Error::new(Status::InvalidArg, "The track doesn't exist."));
+    };
+    if let Some((_, _, denoiser)) = DENOISE_PROFILE
+        .read()
+        .as_ref()
+        .filter(|(cached_id, cached_version, _)| (*cached_id, *cached_version) == (id, version))
+    {
+        return Ok(denoiser.with_amount(amount));
+    }
+    let denoiser = spawn_blocking_job(JobKind::Playback, move || {
+        TRACK_LIST
+            .blocking_read()
+            .get(id)
+            .map(|track| (track.playback_version(), track.spectral_denoiser(amount)))
+    })
+    .await
+    .unwrap();
+    let Some((version, denoiser)) = denoiser else {
+        return Err(Error::new(Status::InvalidArg, "The track doesn't exist."));
+    };
+    *DENOISE_PROFILE.write() = Some((id, version, denoiser.clone()));
+    Ok(denoiser)
+}
+
+/// magnitude response of the DSP applied to the playback at n_points log-spaced frequencies
+/// from 20 Hz to the Nyquist frequency of the output device.
+/// Only the impulse response convolution changes the magnitude response;
+/// the output limiter is reported by its ceiling.
+#[napi]
+#[allow(non_snake_case)]
+async fn get_playback_chain_response(n_points: u32) -> Result<PlaybackChainResponse> {
+    const MIN_HZ: f64 = 20.;
+    if n_points < 2 {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The number of points should be at least 2.",
+        ));
+    }
+    let (chain_tx, chain_rx) = oneshot::channel();
+    player::send(PlayerCommand::GetPlaybackChain(chain_tx)).await;
+    let chain = chain_rx
+        .await
+        .map_err(|_| Error::from_reason("The player doesn't respond."))?;
+    let response = spawn_blocking_job(JobKind::Stats, move || {
+        let max_hz = chain.sr as f64 / 2.;
+        let hzs: Vec<_> = (0..n_points)
+            .map(|i| MIN_HZ * (max_hz / MIN_HZ).powf(i as f64 / (n_points - 1) as f64))
+            .collect();
+        let magnitudes_dB = match &chain.impulse_response {
+            Some(ir) => ir
+                .magnitude_response(&hzs)
+                .outer_iter()
+                .map(|mags| {
+                    mags.iter()
+                        .map(|mag| mag.dB_from_amp_default() as f64)
+                        .collect()
+                })
+                .collect(),
+            None => vec![vec![0.; hzs.len()]],
+        };
+        PlaybackChainResponse {
+            hzs,
+            magnitudes_dB,
+            limiter_ceiling_dB: chain
+                .output_limiter_threshold
+                .map(|threshold| threshold.dB_from_amp_default()),
+        }
+    })
+    .await
+    .unwrap();
+    Ok(response)
 }
 
+/// Render the playback of the current track over sec_range (session time) offline
+/// with the DSP chain of the player (time-stretch, denoiser, TF box solo, downmix, IR,
+/// volume and output limiter) at the sample rate of the output device,
+/// and write it to a 32-bit float stereo wav file at path. Dither is not applied.
+/// Returns path.
 #[napi]
-fn get_amp_axis_markers(
-    max_num_ticks: u32,
-    max_num_labels: u32,
-    amp_range: (f64, f64),
-) -> serde_json::Value {
-    assert_axis_params(max_num_ticks, max_num_labels);
-    assert!(amp_range.0 < amp_range.1);
-
-    json!(calc_amp_axis_markers(
-        max_num_ticks,
-        max_num_labels,
-        (amp_range.0 as f32, amp_range.1 as f32),
-    ))
+async fn bounce_playback(path: String, sec_range: (f64, f64)) -> Result<String> {
+    let (start_sec, end_sec) = sec_range;
+    if !start_sec.is_finite() || !end_sec.is_finite() || start_sec >= end_sec {
+        return Err(Error::new(Status::InvalidArg, "Invalid time range."));
+    }
+    let Some(id) = player::transport().track_id else {
+        return Err(Error::from_reason("No track is loaded to the player."));
+    };
+    let (chain_tx, chain_rx) = oneshot::channel();
+    player::send(PlayerCommand::GetPlaybackChain(chain_tx)).await;
+    let chain = chain_rx
+        .await
+        .map_err(|_| Error::from_reason("The player doesn't respond."))?;
+    spawn_blocking_job(JobKind::Export, move || -> std::io::Result<String> {
+        let wavs = {
+            let tracklist = TRACK_LIST.blocking_read();
+            let track = tracklist
+                .get(id)
+                .ok_or_else(|| std::io::Error::other("The track doesn't exist."))?;
+            track.render_playback(
+                chain.downmix_mode,
+                chain.impulse_response.as_ref(),
+                chain.denoiser.as_ref(),
+                chain.tf_box_solo.as_ref(),
+                chain.volume,
+                chain.output_limiter_threshold,
+                sec_range,
+                chain.sr,
+            )
+        };
+        let transcode_params = TranscodeParams {
+            bit_depth: Some(32),
+            ..Default::default()
+        };
+        transcode(
+            wavs.view(),
+            chain.sr,
+            TranscodeCodec::Wav,
+            &transcode_params,
+            Path::new(&path),
+        )?;
+        Ok(path)
+    })
+    .await
+    .unwrap()
+    .map_err(|e| Error::from_reason(e.to_string()))
 }
 
-#[napi(js_name = "getdBAxisMarkers")]
-#[allow(non_snake_case)]
-fn get_dB_axis_markers(
-    max_num_ticks: u32,
-    max_num_labels: u32,
-    min_dB: f64,
-    max_dB: f64,
-) -> serde_json::Value {
-    assert_axis_params(max_num_ticks, max_num_labels);
-
-    json!(calc_dB_axis_markers(
-        max_num_ticks,
-        max_num_labels,
-        (min_dB as f32, max_dB as f32)
-    ))
+#[napi]
+fn get_input_devices() -> Vec<String> {
+    latency::input_device_names()
 }
 
-#[napi(js_name = "getMaxdB")]
-#[allow(non_snake_case)]
-async fn get_max_dB() -> f64 {
-    TM.read().await.max_dB as f64
+/// round-trip latency measured by playing a chirp through the player (the track is paused)
+/// while recording from the input device (e.g. via a loopback cable or a microphone).
+/// input_device: name of the input device. The default input device is used if not given.
+#[napi]
+async fn measure_output_latency(input_device: Option<String>) -> Result<LatencyMeasurement> {
+    spawn_blocking_job(JobKind::Stats, move || {
+        latency::measure_output_latency(input_device.as_deref())
+    })
+    .await
+    .unwrap()
+    .map_err(Error::from_reason)
 }
 
-#[napi(js_name = "getMindB")]
-#[allow(non_snake_case)]
-async fn get_min_dB() -> f64 {
-    TM.read().await.min_dB as f64
+/// fade duration (ms) on pause/resume. zero to disable fading
+#[napi]
+async fn set_player_fade_millisec(fade_ms: f64) {
+    player::send(PlayerCommand::SetFadeMillisec(fade_ms)).await;
 }
 
+/// playback starts pre_roll_ms earlier than the seek point
 #[napi]
-fn get_max_track_hz() -> f64 {
-    TM.blocking_read().max_sr as f64 / 2.
+async fn set_player_pre_roll_millisec(pre_roll_ms: f64) {
+    player::send(PlayerCommand::SetPreRollMillisec(pre_roll_ms)).await;
 }
 
+/// non-destructive time-stretch of the track for playback.
+/// ratio: output duration / original duration (> 1: slower). clamped to 0.25 ~ 4
 #[napi]
-fn get_longest_track_length_sec() -> f64 {
-    TRACK_LIST.blocking_read().max_sec
+async fn set_track_stretch(track_id: u32, ratio: f64) -> Result<()> {
+    if !(ratio.is_finite() && ratio > 0.) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The stretch ratio should be a positive number.",
+        ));
+    }
+    set_stretch_params(vec![track_id as usize], move |params| params.ratio = ratio).await
 }
 
+/// setTrackStretch of multiple tracks at once. Nothing is changed if any of the tracks doesn't exist.
 #[napi]
-fn get_channel_counts(track_id: u32) -> u32 {
-    TRACK_LIST
-        .blocking_read()
-        .get(track_id as usize)
-        .map_or(0, |track| track.n_ch() as u32)
+async fn set_tracks_stretch(track_ids: Vec<u32>, ratio: f64) -> Result<()> {
+    if !(ratio.is_finite() && ratio > 0.) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The stretch ratio should be a positive number.",
+        ));
+    }
+    let track_ids = track_ids.into_iter().map(|id| id as usize).collect();
+    set_stretch_params(track_ids, move |params| params.ratio = ratio).await
 }
 
+/// non-destructive pitch-shift of the track for playback. clamped to ±24 semitones
 #[napi]
-fn get_length_sec(track_id: u32) -> f64 {
-    TRACK_LIST
-        .blocking_read()
-        .get(track_id as usize)
-        .map_or(0., |track| track.sec())
+async fn set_track_pitch(track_id: u32, semitones: f64) -> Result<()> {
+    if !semitones.is_finite() {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The pitch shift should be a finite number.",
+        ));
+    }
+    set_stretch_params(vec![track_id as usize], move |params| {
+        params.semitones = semitones
+    })
+    .await
 }
 
+/// setTrackPitch of multiple tracks at once. Nothing is changed if any of the tracks doesn't exist.
 #[napi]
-fn get_sample_rate(track_id: u32) -> u32 {
-    TRACK_LIST
-        .blocking_read()
-        .get(track_id as usize)
-        .map_or(0, |track| track.sr())
+async fn set_tracks_pitch(track_ids: Vec<u32>, semitones: f64) -> Result<()> {
+    if !semitones.is_finite() {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The pitch shift should be a finite number.",
+        ));
+    }
+    let track_ids = track_ids.into_iter().map(|id| id as usize).collect();
+    set_stretch_params(track_ids, move |params| params.semitones = semitones).await
 }
 
 #[napi]
-fn get_format_info(track_id: u32) -> AudioFormatInfo {
+fn get_track_offset(track_id: u32) -> f64 {
     TRACK_LIST
         .blocking_read()
         .get(track_id as usize)
-        .map_or_else(Default::default, |track| track.format_info.clone())
+        .map_or(0., |track| track.offset_sec)
 }
 
-#[napi(js_name = "getGlobalLUFS")]
-fn get_global_lufs(track_id: u32) -> f64 {
-    TRACK_LIST
-        .blocking_read()
-        .get(track_id as usize)
-        .map_or(f64::NEG_INFINITY, |track| track.stats().global_lufs)
+/// Place the track at offset_sec of the session timeline.
+/// The selection, the playhead, and the drawing requests are on the session timeline.
+#[napi]
+async fn set_track_offset(track_id: u32, offset_sec: f64) -> Result<()> {
+    if !offset_sec.is_finite() {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The offset should be a finite number.",
+        ));
+    }
+    let track_id = track_id as usize;
+    let id_ch_tuples = spawn_blocking_job(JobKind::Stats, move || {
+        let mut tracklist = TRACK_LIST.blocking_write();
+        tracklist
+            .set_offset(track_id, offset_sec)
+            .then(|| tracklist.id_ch_tuples_from(&[track_id]))
+    })
+    .await
+    .unwrap()
+    .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))?;
+    img_mgr::send(ImgMsg::Remove(id_ch_tuples)).await;
+    refresh_track_player().await;
+    Ok(())
 }
 
-#[napi(js_name = "getRMSdB")]
-#[allow(non_snake_case)]
-fn get_rms_dB(track_id: u32) -> f64 {
+/// Convert the session time (e.g. of a marker) to the time of the track
+#[napi]
+fn session_to_track_sec(track_id: u32, sec: f64) -> Result<f64> {
     TRACK_LIST
         .blocking_read()
         .get(track_id as usize)
-        .map_or(f64::NEG_INFINITY, |track| track.stats().rms_dB as f64)
+        .map(|track| track.timeline().session_to_track(sec))
+        .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))
 }
 
-#[napi(js_name = "getMaxPeakdB")]
-#[allow(non_snake_case)]
-fn get_max_peak_dB(track_id: u32) -> f64 {
+/// Convert the time of the track (e.g. of a detected event) to the session time
+#[napi]
+fn track_to_session_sec(track_id: u32, sec: f64) -> Result<f64> {
     TRACK_LIST
         .blocking_read()
         .get(track_id as usize)
-        .map_or(f64::NEG_INFINITY, |track| track.stats().max_peak_dB as f64)
+        .map(|track| track.timeline().track_to_session(sec))
+        .ok_or_else(|| Error::new(Status::InvalidArg, "The track doesn't exist."))
 }
 
 #[napi]
-fn get_guard_clip_stats(track_id: u32) -> String {
-    let tracklist = TRACK_LIST.blocking_read();
-    let mode = tracklist.common_guard_clipping;
-    let prefix = mode.to_string();
-    tracklist
-        .get(track_id as usize)
-        .map_or_else(String::new, |track| {
-            let stats = if mode == GuardClippingMode::ReduceGlobalLevel {
-                track.guard_clip_stats().slice_move(ndarray::s![0..1])
-            } else {
-                track.guard_clip_stats()
-            };
-            itertools::intersperse(
-                stats.iter().map(|stat| {
-                    let stat = stat.to_string();
-                    if !stat.is_empty() {
-                        format!("{} by {}", &prefix, stat)
-                    } else {
-                        stat
-                    }
-                }),
-                "\n".to_string(),
-            )
-            .collect()
-        })
+async fn set_track_player(track_id: u32, sec: Option<f64>) {
+    let track_id = track_id as usize;
+    if TRACK_LIST.read().await.has(track_id) {
+        player::send(PlayerCommand::SetTrack((Some(track_id), sec))).await;
+    }
 }
 
 #[napi]
-fn get_path(track_id: u32) -> String {
-    TRACK_LIST
-        .blocking_read()
-        .get(track_id as usize)
-        .map_or_else(String::new, |track| track.path_string())
+async fn seek_player(sec: f64) {
+    player::send(PlayerCommand::Seek(sec)).await;
 }
 
+/// seek forward (backward if backward is true) by the seek increment of amount_kind.
+/// The destination is snapped to a sample of the current track.
 #[napi]
-fn get_file_name(track_id: u32) -> String {
-    TRACK_LIST
-        .blocking_read()
-        .filename(track_id as usize)
-        .to_owned()
+async fn seek_player_relative(amount_kind: SeekAmountKind, backward: Option<bool>) {
+    let amount = SEEK_INCREMENTS.read().get(amount_kind);
+    let amount = if backward.unwrap_or(false) {
+        -amount
+    } else {
+        amount
+    };
+    player::send(PlayerCommand::SeekRelative(amount)).await;
 }
 
 #[napi]
-fn get_color_map() -> Buffer {
-    visualize::get_colormap_rgb().into()
-}
-
-#[napi(js_name = "setVolumedB")]
-#[allow(non_snake_case)]
-async fn set_volume_dB(volume_dB: f64) {
-    player::send(PlayerCommand::SetVolumedB(volume_dB)).await;
+fn get_seek_increments() -> SeekIncrements {
+    *SEEK_INCREMENTS.read()
 }
 
+/// amounts (sec) of seeking by seekPlayerRelative. All should be positive.
 #[napi]
-async fn set_track_player(track_id: u32, sec: Option<f64>) {
-    let track_id = track_id as usize;
-    if TRACK_LIST.read().await.has(track_id) {
-        player::send(PlayerCommand::SetTrack((Some(track_id), sec))).await;
+fn set_seek_increments(small: f64, medium: f64, large: f64) -> Result<()> {
+    let seek_increments = SeekIncrements {
+        small,
+        medium,
+        large,
+    };
+    if !seek_increments.is_valid() {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "Seek increments should be positive.",
+        ));
     }
+    *SEEK_INCREMENTS.write() = seek_increments;
+    Ok(())
 }
 
+/// Tape-style scrubbing while dragging the playhead.
+/// The playback is paused and a short grain around sec is played at the speed
+/// (track sec per sec, negative: backward).
 #[napi]
-async fn seek_player(sec: f64) {
-    player::send(PlayerCommand::Seek(sec)).await;
+async fn scrub_player(sec: f64, speed: f64) {
+    player::send(PlayerCommand::Scrub { sec, speed }).await;
 }
 
 #[napi]
@@ -634,22 +3598,244 @@ async fn resume_player() {
     player::send(PlayerCommand::Resume).await;
 }
 
+/// Execute the action of the OS media keys or media controls forwarded by navigator.mediaSession.
+/// seek_sec: destination of SeekTo, or the offset of SeekBackward/SeekForward
+/// (the medium seek increment if not given).
+#[napi]
+async fn handle_media_action(action: MediaAction, seek_sec: Option<f64>) -> Result<()> {
+    let default_offset_sec = SEEK_INCREMENTS.read().medium;
+    os::handle_media_action(action, seek_sec, default_offset_sec)
+        .await
+        .map_err(|e| Error::new(Status::InvalidArg, e))
+}
+
+/// metadata and position for the OS media controls. null if no track is loaded to the player.
+#[napi]
+async fn get_now_playing() -> Option<NowPlaying> {
+    os::now_playing().await
+}
+
 #[napi]
 fn get_player_state() -> PlayerState {
+    let (device_name, device_change_count) = player::output_device();
+    let monitor = player::monitor();
     match player::recv() {
         PlayerNotification::Ok(state) => PlayerState {
             is_playing: state.is_playing,
             position_sec: state.position_sec,
+            is_limiter_active: state.is_limiter_active,
             err: "".to_string(),
+            device_name,
+            device_change_count,
+            monitor_dim: monitor.dim,
+            monitor_mute: monitor.mute,
+            reference_level_dB: monitor.reference_level_dB,
         },
         PlayerNotification::Err(e_str) => PlayerState {
             is_playing: false,
             position_sec: 0.,
+            is_limiter_active: false,
             err: e_str,
+            device_name,
+            device_change_count,
+            monitor_dim: monitor.dim,
+            monitor_mute: monitor.mute,
+            reference_level_dB: monitor.reference_level_dB,
         },
     }
 }
 
+/// transport of the player for external sync.
+/// The revision is read before the state, so the state is never older than the revision.
+#[napi]
+fn get_transport_state() -> TransportState {
+    let transport = player::transport();
+    let (is_playing, position_sec, speed) = match player::recv() {
+        PlayerNotification::Ok(state) => (state.is_playing, state.position_sec, state.speed),
+        PlayerNotification::Err(_) => (false, 0., 1.),
+    };
+    TransportState {
+        is_playing,
+        position_sec,
+        speed,
+        loop_start_sec: transport.loop_range.map(|(start, _)| start),
+        loop_end_sec: transport.loop_range.map(|(_, end)| end),
+        active_track_ids: transport.track_id.map(|id| id as u32).into_iter().collect(),
+        revision: transport.revision,
+    }
+}
+
+/// Start the OSC server for the remote control of the player on the UDP port
+/// (0: any free port) and return the bound port. The running server is restarted.
+/// The server listens on 127.0.0.1 unless bind_addr (e.g. "0.0.0.0" to accept the other hosts
+/// on the network, which can then control the player without authentication) is given.
+#[napi]
+fn start_osc_server(port: u32, bind_addr: Option<String>) -> Result<u32> {
+    let port = u16::try_from(port)
+        .map_err(|_| Error::new(Status::InvalidArg, "The port should be 0 ~ 65535."))?;
+    let bind_ip = match bind_addr {
+        Some(addr) => addr.parse().map_err(|_| {
+            Error::new(
+                Status::InvalidArg,
+                format!("{} is not a valid IP address.", addr),
+            )
+        })?,
+        None => remote::DEFAULT_BIND_IP,
+    };
+    remote::start_osc_server(bind_ip, port)
+        .map(u32::from)
+        .map_err(|e| Error::from_reason(e.to_string()))
+}
+
+#[napi]
+fn stop_osc_server() {
+    remote::stop_osc_server();
+}
+
+/// null if the OSC server is not running
+#[napi]
+fn get_osc_server_port() -> Option<u32> {
+    remote::osc_server_port().map(u32::from)
+}
+
+/// format of the output stream negotiated with the output device.
+/// returns null if the format is unknown.
+#[napi]
+fn get_audio_stream_info() -> Option<AudioStreamInfo> {
+    let format = player::stream_format()?;
+    let is_dithered = format.dither_lsb().is_some();
+    Some(AudioStreamInfo {
+        device_name: player::output_device().0,
+        sample_format: format!("{:?}", format.sample_format).to_lowercase(),
+        sample_rate: format.sr,
+        channels: format.channels as u32,
+        is_float: format.sample_format.is_float(),
+        is_dithered,
+        buffer_size_range: format.buffer_size_range.map(|(min, max)| vec![min, max]),
+    })
+}
+
+/// names of the audio hosts (cpal) available on this platform
+/// (e.g. "WASAPI", "ASIO", "ALSA", "JACK"). The first is the default.
+#[napi]
+fn list_audio_hosts() -> Vec<String> {
+    player::list_audio_hosts()
+}
+
+/// Force the audio host of the name (one of listAudioHosts). null for the default host.
+/// The output stream is rebuilt on the default output device of the host.
+#[napi]
+async fn set_audio_host(name: Option<String>) -> Result<()> {
+    player::set_audio_host(name.as_deref()).map_err(|e| Error::new(Status::InvalidArg, e))?;
+    player::send(PlayerCommand::ChangeAudioHost).await;
+    Ok(())
+}
+
+/// null if the default host is used
+#[napi]
+fn get_audio_host() -> Option<String> {
+    player::audio_host_name()
+}
+
+#[napi]
+fn get_buffer_health() -> BufferHealthInfo {
+    let health = player::buffer_health();
+    BufferHealthInfo {
+        n_underruns: health.n_underruns,
+        buffer_size: health.buffer_size,
+        adaptive: health.adaptive,
+    }
+}
+
+/// Set the buffer size (frames) of the output stream. null for the default of the device.
+/// The output stream is rebuilt.
+#[napi]
+async fn set_buffer_size(buffer_size: Option<u32>) -> Result<()> {
+    if buffer_size == Some(0) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The buffer size should be positive.",
+        ));
+    }
+    player::send(PlayerCommand::SetBufferSize(buffer_size)).await;
+    Ok(())
+}
+
+/// If true, the buffer size is doubled when the underruns repeat.
+#[napi]
+fn set_adaptive_buffer_size(adaptive: bool) {
+    player::set_adaptive_buffer_size(adaptive);
+}
+
+/// set the global time selection (sec) shared by all tracks
+#[napi]
+async fn set_selection(start_sec: f64, end_sec: f64) -> Result<()> {
+    if !(start_sec >= 0. && end_sec > start_sec) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The selection should satisfy 0 <= start_sec < end_sec.",
+        ));
+    }
+    *SELECTION.write() = Some((start_sec, end_sec));
+    update_player_loop().await;
+    Ok(())
+}
+
+#[napi]
+async fn clear_selection() {
+    *SELECTION.write() = None;
+    update_player_loop().await;
+}
+
+#[napi]
+fn get_selection() -> Option<Selection> {
+    SELECTION
+        .read()
+        .map(|(start_sec, end_sec)| Selection { start_sec, end_sec })
+}
+
+/// if enabled, the player loops the global selection
+#[napi]
+async fn set_player_looping(enabled: bool) {
+    PLAYER_LOOPING.store(enabled, Ordering::Release);
+    update_player_loop().await;
+}
+
+#[napi]
+fn get_player_looping() -> bool {
+    PLAYER_LOOPING.load(Ordering::Acquire)
+}
+
+/// Play only the time-frequency box (e.g. dragged on the spectrogram) from start_sec.
+/// The box is band-pass filtered with zero phase and crossfaded at the time edges.
+/// The outside of the box is silent until clearTfBoxSolo is called.
+#[napi]
+async fn solo_tf_box(start_sec: f64, end_sec: f64, min_hz: f64, max_hz: f64) -> Result<()> {
+    if !(start_sec >= 0. && end_sec > start_sec) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The box should satisfy 0 <= start_sec < end_sec.",
+        ));
+    }
+    if !(min_hz >= 0. && max_hz > min_hz) {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "The box should satisfy 0 <= min_hz < max_hz.",
+        ));
+    }
+    player::send(PlayerCommand::SoloTfBox {
+        sec_range: (start_sec, end_sec),
+        hz_range: (min_hz as f32, max_hz as f32),
+    })
+    .await;
+    Ok(())
+}
+
+#[napi]
+async fn clear_tf_box_solo() {
+    player::send(PlayerCommand::ClearTfBoxSolo).await;
+}
+
 #[inline]
 pub fn assert_axis_params(max_num_ticks: u32, max_num_labels: u32) {
     assert!(max_num_ticks >= 2);
@@ -667,29 +3853,105 @@ async fn refresh_track_player() {
     player::send(PlayerCommand::SetTrack((None, None))).await;
 }
 
+/// recalculate the specs and images, and reload the player after channels of the tracks changed
+async fn refresh_track_channels(track_ids: Vec<usize>) {
+    spawn_blocking_job(JobKind::SpecUpdate, move || {
+        TM.blocking_write()
+            .reload_tracks(&TRACK_LIST.blocking_read(), &track_ids);
+    })
+    .await
+    .unwrap();
+    spawn_deferred_spec_job(Session::current());
+    join!(remove_all_imgs(), refresh_track_player());
+}
+
+/// send the global selection clamped to the tracks to the player if looping is enabled
+async fn update_player_loop() {
+    let selection = *SELECTION.read();
+    let loop_range = match selection {
+        Some((start_sec, end_sec)) if PLAYER_LOOPING.load(Ordering::Acquire) => {
+            let max_sec = TRACK_LIST.read().await.max_sec;
+            let end_sec = end_sec.min(max_sec);
+            (start_sec < end_sec).then_some((start_sec, end_sec))
+        }
+        _ => None,
+    };
+    player::send(PlayerCommand::SetLoop(loop_range)).await;
+}
+
+/// update the stretch params of the tracks (all or nothing)
+async fn set_stretch_params(
+    track_ids: Vec<usize>,
+    update: impl Fn(&mut StretchParams) + Send,
+) -> Result<()> {
+    let n_tracks = track_ids.len();
+    let pending = TRACK_LIST.read().await.prepare_stretch(&track_ids, update);
+    let Some(mut pending) = pending else {
+        let msg = if n_tracks == 1 {
+            "The track doesn't exist."
+        } else {
+            "Some of the tracks don't exist."
+        };
+        return Err(Error::new(Status::InvalidArg, msg));
+    };
+    if pending.is_empty() {
+        return Ok(());
+    }
+    // the phase vocoder runs without the lock not to block the drawing and the player
+    let pending = spawn_blocking_job(JobKind::Playback, move || {
+        pending.par_iter_mut().for_each(PendingStretch::compute);
+        pending
+    })
+    .await
+    .unwrap();
+    TRACK_LIST.write().await.apply_stretch(pending);
+    refresh_track_player().await;
+    Ok(())
+}
+
 #[inline]
 fn calc_valid_hz_range(max_track_hz: f32) -> (f32, f32) {
     TrackManager::calc_valid_hz_range(&HZ_RANGE.read(), max_track_hz)
 }
 
 #[inline]
-fn convert_freq_pos_to_hz(y: f32, height: u32, hz_range: Option<(f32, f32)>) -> f32 {
+fn get_freq_break_of(view_id: Option<u32>) -> Option<FreqAxisBreak> {
+    FREQ_AXIS_BREAKS
+        .read()
+        .get(&view_id.unwrap_or(img_mgr::MAIN_VIEW_ID))
+        .copied()
+}
+
+#[inline]
+fn convert_freq_pos_to_hz(
+    y: f32,
+    height: u32,
+    hz_range: Option<(f32, f32)>,
+    freq_break: Option<FreqAxisBreak>,
+) -> f32 {
     let hz_range =
         hz_range.unwrap_or_else(|| calc_valid_hz_range(TM.blocking_read().max_sr as f32 / 2.));
     let rel_freq = 1. - y / height as f32;
-    SPEC_SETTING
-        .read()
-        .freq_scale
-        .relative_freq_to_hz(rel_freq, hz_range)
+    let freq_scale = SPEC_SETTING.read().freq_scale;
+    match freq_break {
+        Some(freq_break) => freq_break.relative_freq_to_hz(rel_freq, hz_range, freq_scale),
+        None => freq_scale.relative_freq_to_hz(rel_freq, hz_range),
+    }
 }
 
 #[inline]
-fn convert_freq_hz_to_pos(hz: f32, height: u32, hz_range: Option<(f32, f32)>) -> f32 {
+fn convert_freq_hz_to_pos(
+    hz: f32,
+    height: u32,
+    hz_range: Option<(f32, f32)>,
+    freq_break: Option<FreqAxisBreak>,
+) -> f32 {
     let hz_range =
         hz_range.unwrap_or_else(|| calc_valid_hz_range(TM.blocking_read().max_sr as f32 / 2.));
-    let rel_freq = SPEC_SETTING
-        .read()
-        .freq_scale
-        .hz_to_relative_freq(hz, hz_range);
+    let freq_scale = SPEC_SETTING.read().freq_scale;
+    let rel_freq = match freq_break {
+        Some(freq_break) => freq_break.hz_to_relative_freq(hz, hz_range, freq_scale),
+        None => freq_scale.hz_to_relative_freq(hz, hz_range),
+    };
     (1. - rel_freq) * height as f32
 }
@@ -0,0 +1,433 @@
+//! RwLocks of the global states shared by the commands (TM and TRACK_LIST)
+//!
+//! Lock order: a command holding both locks should acquire TM before TRACK_LIST
+//! (e.g. `TM.blocking_write().set_x(&TRACK_LIST.blocking_read())`).
+//! The async RwLock of tokio is write-preferring, so even two readers can deadlock
+//! with the writers queued in the opposite order.
+//!
+//! With the `lock-watchdog` feature, every acquisition is recorded, and
+//! - a lock held (or waited for) longer than HELD_TOO_LONG is logged with all the held locks,
+//! - a blocking acquisition against the lock order on the same thread is logged as an error.
+//!
+//! The order is checked only for the blocking acquisitions (e.g. in spawn_blocking_job),
+//! because an async task holding a guard can move between the threads.
+//! Async commands needing both locks should use read_both instead of `join!`,
+//! which polls both acquisitions at once and can hold TRACK_LIST while waiting for TM.
+
+use std::ops::{Deref, DerefMut};
+
+use napi::tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+/// position of the lock in the lock order. The locks should be acquired in ascending order.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum LockRank {
+    Tm,
+    TrackList,
+}
+
+impl LockRank {
+    pub fn name(self) -> &'static str {
+        match self {
+            LockRank::Tm => "TM",
+            LockRank::TrackList => "TRACK_LIST",
+        }
+    }
+}
+
+/// async RwLock with its rank in the lock order (and the diagnostics of the watchdog)
+pub struct OrderedRwLock<T> {
+    rank: LockRank,
+    lock: RwLock<T>,
+}
+
+pub struct ReadGuard<'a, T> {
+    guard: RwLockReadGuard<'a, T>,
+    #[cfg(feature = "lock-watchdog")]
+    _held: watchdog::Held,
+}
+
+pub struct WriteGuard<'a, T> {
+    guard: RwLockWriteGuard<'a, T>,
+    #[cfg(feature = "lock-watchdog")]
+    _held: watchdog::Held,
+}
+
+impl<T> OrderedRwLock<T> {
+    pub fn new(rank: LockRank, value: T) -> Self {
+        OrderedRwLock {
+            rank,
+            lock: RwLock::new(value),
+        }
+    }
+
+    pub async fn read(&self) -> ReadGuard<'_, T> {
+        #[cfg(feature = "lock-watchdog")]
+        let waiting = watchdog::Waiting::new(self.rank, false, false);
+        let guard = self.lock.read().await;
+        ReadGuard {
+            guard,
+            #[cfg(feature = "lock-watchdog")]
+            _held: waiting.acquired(),
+        }
+    }
+
+    pub async fn write(&self) -> WriteGuard<'_, T> {
+        #[cfg(feature = "lock-watchdog")]
+        let waiting = watchdog::Waiting::new(self.rank, true, false);
+        let guard = self.lock.write().await;
+        WriteGuard {
+            guard,
+            #[cfg(feature = "lock-watchdog")]
+            _held: waiting.acquired(),
+        }
+    }
+
+    /// Should not be called in the async context (panics as RwLock::blocking_read).
+    pub fn blocking_read(&self) -> ReadGuard<'_, T> {
+        #[cfg(feature = "lock-watchdog")]
+        let waiting = watchdog::Waiting::new(self.rank, false, true);
+        let guard = self.lock.blocking_read();
+        ReadGuard {
+            guard,
+            #[cfg(feature = "lock-watchdog")]
+            _held: waiting.acquired(),
+        }
+    }
+
+    /// Should not be called in the async context (panics as RwLock::blocking_write).
+    pub fn blocking_write(&self) -> WriteGuard<'_, T> {
+        #[cfg(feature = "lock-watchdog")]
+        let waiting = watchdog::Waiting::new(self.rank, true, true);
+        let guard = self.lock.blocking_write();
+        WriteGuard {
+            guard,
+            #[cfg(feature = "lock-watchdog")]
+            _held: waiting.acquired(),
+        }
+    }
+
+    /// not waiting, so it is not checked against the lock order
+    pub fn try_read(&self) -> Result<ReadGuard<'_, T>, TryLockError> {
+        let guard = self.lock.try_read()?;
+        Ok(ReadGuard {
+            guard,
+            #[cfg(feature = "lock-watchdog")]
+            _held: watchdog::Held::register(self.rank, false, false),
+        })
+    }
+
+    /// Read-lock self and then other, which should be in the lock order.
+    pub async fn read_both<'a, U>(
+        &'a self,
+        other: &'a OrderedRwLock<U>,
+    ) -> (ReadGuard<'a, T>, ReadGuard<'a, U>) {
+        assert!(
+            self.rank < other.rank,
+            "{} should be locked before {}",
+            other.rank.name(),
+            self.rank.name()
+        );
+        let first = self.read().await;
+        let second = other.read().await;
+        (first, second)
+    }
+
+    pub fn rank(&self) -> LockRank {
+        self.rank
+    }
+}
+
+impl<T> Deref for ReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> Deref for WriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for WriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+/// descriptions of the locks held now in the acquisition order (for the crash report).
+/// Always empty without the `lock-watchdog` feature.
+pub fn held_locks() -> Vec<String> {
+    #[cfg(feature = "lock-watchdog")]
+    {
+        watchdog::held_locks()
+    }
+    #[cfg(not(feature = "lock-watchdog"))]
+    {
+        Vec::new()
+    }
+}
+
+#[cfg(feature = "lock-watchdog")]
+mod watchdog {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{LazyLock, Once};
+    use std::thread::{self, ThreadId};
+    use std::time::{Duration, Instant};
+
+    use identity_hash::IntMap;
+    use log::{error, warn};
+    use parking_lot::Mutex;
+
+    use super::LockRank;
+
+    /// a lock held (or waited for) longer than this is logged
+    const HELD_TOO_LONG: Duration = Duration::from_secs(2);
+    const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+    #[derive(Clone, Debug)]
+    pub(super) struct HeldLock {
+        pub rank: LockRank,
+        pub is_write: bool,
+        pub is_blocking: bool,
+        pub thread: ThreadId,
+        pub thread_name: Option<String>,
+        pub since: Instant,
+        /// logged by the watchdog as held too long
+        pub warned: bool,
+    }
+
+    impl HeldLock {
+        fn describe(&self, now: Instant) -> String {
+            format!(
+                "{} ({}) held by {} for {:?}",
+                self.rank.name(),
+                if self.is_write { "write" } else { "read" },
+                self.thread_name.as_deref().unwrap_or("unnamed thread"),
+                now - self.since,
+            )
+        }
+    }
+
+    /// acquisition number -> the lock. The acquisition numbers increase in the acquisition order.
+    static HELD: LazyLock<Mutex<IntMap<u64, HeldLock>>> =
+        LazyLock::new(|| Mutex::new(IntMap::default()));
+    static NEXT_NUMBER: AtomicU64 = AtomicU64::new(0);
+    static START_WATCHDOG: Once = Once::new();
+
+    /// the lock held by the same thread with the same or a higher rank than rank.
+    /// Acquiring the same lock again can deadlock too (even a read while a writer is waiting).
+    pub(super) fn find_order_violation<'a>(
+        held: impl IntoIterator<Item = &'a HeldLock>,
+        rank: LockRank,
+        thread: ThreadId,
+    ) -> Option<&'a HeldLock> {
+        held.into_iter()
+            .find(|lock| lock.is_blocking && lock.thread == thread && lock.rank >= rank)
+    }
+
+    /// Log the held locks periodically if they are held too long.
+    fn start_watchdog() {
+        START_WATCHDOG.call_once(|| {
+            let spawned = thread::Builder::new()
+                .name("lock-watchdog".into())
+                .spawn(|| loop {
+                    thread::sleep(CHECK_INTERVAL);
+                    let now = Instant::now();
+                    let mut held = HELD.lock();
+                    let is_stale = held
+                        .values()
+                        .any(|lock| !lock.warned && now - lock.since > HELD_TOO_LONG);
+                    if !is_stale {
+                        continue;
+                    }
+                    warn!(
+                        "A lock is held longer than {:?}. Held locks: {:?}",
+                        HELD_TOO_LONG,
+                        describe_all(&held, now)
+                    );
+                    held.values_mut()
+                        .filter(|lock| now - lock.since > HELD_TOO_LONG)
+                        .for_each(|lock| lock.warned = true);
+                });
+            if let Err(e) = spawned {
+                error!("Failed to start the lock watchdog: {}", e);
+            }
+        });
+    }
+
+    fn describe_all(held: &IntMap<u64, HeldLock>, now: Instant) -> Vec<String> {
+        let mut numbers: Vec<_> = held.keys().copied().collect();
+        numbers.sort_unstable();
+        numbers
+            .into_iter()
+            .map(|number| held[&number].describe(now))
+            .collect()
+    }
+
+    pub(super) fn held_locks() -> Vec<String> {
+        HELD.try_lock()
+            .map(|held| describe_all(&held, Instant::now()))
+            .unwrap_or_default()
+    }
+
+    /// an acquisition waiting for the lock
+    pub(super) struct Waiting {
+        rank: LockRank,
+        is_write: bool,
+        is_blocking: bool,
+        since: Instant,
+    }
+
+    impl Waiting {
+        pub fn new(rank: LockRank, is_write: bool, is_blocking: bool) -> Self {
+            start_watchdog();
+            if is_blocking {
+                let thread = thread::current().id();
+                let held = HELD.lock();
+                if let Some(lock) = find_order_violation(held.values(), rank, thread) {
+                    error!(
+                        "Acquiring {} ({}) while holding {} violates the lock order. Held locks: {:?}",
+                        rank.name(),
+                        if is_write { "write" } else { "read" },
+                        lock.rank.name(),
+                        describe_all(&held, Instant::now()),
+                    );
+                }
+            }
+            Waiting {
+                rank,
+                is_write,
+                is_blocking,
+                since: Instant::now(),
+            }
+        }
+
+        pub fn acquired(self) -> Held {
+            let waited = self.since.elapsed();
+            if waited > HELD_TOO_LONG {
+                warn!(
+                    "Waited {:?} for {} ({}). Held locks: {:?}",
+                    waited,
+                    self.rank.name(),
+                    if self.is_write { "write" } else { "read" },
+                    held_locks(),
+                );
+            }
+            Held::register(self.rank, self.is_write, self.is_blocking)
+        }
+    }
+
+    /// RAII record of a held lock. Removed from the held locks when dropped.
+    pub(super) struct Held(u64);
+
+    impl Held {
+        pub fn register(rank: LockRank, is_write: bool, is_blocking: bool) -> Self {
+            let number = NEXT_NUMBER.fetch_add(1, Ordering::Relaxed);
+            let current = thread::current();
+            let lock = HeldLock {
+                rank,
+                is_write,
+                is_blocking,
+                thread: current.id(),
+                thread_name: current.name().map(Into::into),
+                since: Instant::now(),
+                warned: false,
+            };
+            HELD.lock().insert(number, lock);
+            Held(number)
+        }
+    }
+
+    impl Drop for Held {
+        fn drop(&mut self) {
+            let lock = HELD.lock().remove(&self.0);
+            if let Some(lock) = lock.filter(|lock| lock.warned) {
+                warn!(
+                    "{} is released after {:?}.",
+                    lock.rank.name(),
+                    lock.since.elapsed()
+                );
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn held(rank: LockRank, is_write: bool, is_blocking: bool) -> HeldLock {
+            HeldLock {
+                rank,
+                is_write,
+                is_blocking,
+                thread: thread::current().id(),
+                thread_name: None,
+                since: Instant::now(),
+                warned: false,
+            }
+        }
+
+        #[test]
+        fn find_order_violation_works() {
+            let thread = thread::current().id();
+            let tm = [held(LockRank::Tm, true, true)];
+            let tracklist = [held(LockRank::TrackList, false, true)];
+            let find = |held: &[HeldLock], rank| {
+                find_order_violation(held, rank, thread).map(|lock| lock.rank)
+            };
+            assert_eq!(find(&tm, LockRank::TrackList), None);
+            assert_eq!(find(&tracklist, LockRank::Tm), Some(LockRank::TrackList));
+            // the same lock
+            assert_eq!(
+                find(&tracklist, LockRank::TrackList),
+                Some(LockRank::TrackList)
+            );
+            assert_eq!(find(&tm, LockRank::Tm), Some(LockRank::Tm));
+            // held by an async task
+            let async_tracklist = [held(LockRank::TrackList, false, false)];
+            assert_eq!(find(&async_tracklist, LockRank::Tm), None);
+
+            let other_thread = thread::spawn(|| thread::current().id()).join().unwrap();
+            assert!(find_order_violation(&tracklist, LockRank::Tm, other_thread).is_none());
+        }
+
+        #[test]
+        fn held_locks_are_released() {
+            let lock = super::super::OrderedRwLock::new(LockRank::Tm, 0);
+            let is_held = || {
+                HELD.lock()
+                    .values()
+                    .any(|lock| lock.thread == thread::current().id() && lock.is_write)
+            };
+            let guard = lock.blocking_write();
+            assert!(is_held());
+            drop(guard);
+            assert!(!is_held());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordered_rwlock_works() {
+        let lock = OrderedRwLock::new(LockRank::TrackList, vec![1]);
+        assert_eq!(lock.rank(), LockRank::TrackList);
+        {
+            let mut guard = lock.blocking_write();
+            guard.push(2);
+            assert!(lock.try_read().is_err());
+        }
+        let (a, b) = (lock.blocking_read(), lock.try_read().unwrap());
+        assert_eq!(*a, [1, 2]);
+        assert_eq!(b.len(), 2);
+        assert!(LockRank::Tm < LockRank::TrackList);
+    }
+}
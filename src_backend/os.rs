@@ -0,0 +1,127 @@
+//! Integration with the media session of the OS
+//! (SMTC on Windows, MPRIS on Linux, and MPNowPlayingInfoCenter on macOS).
+//! The OS session is registered by the renderer (navigator.mediaSession of Chromium uses the
+//! native API of each platform), which forwards the actions of the hardware media keys here
+//! and shows now_playing on the OS media controls.
+
+use napi_derive::napi;
+
+use crate::player::{self, PlayerCommand, PlayerNotification};
+use crate::{acoustid, TRACK_LIST};
+
+/// action requested by the media keys or the media controls of the OS
+#[napi(string_enum)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MediaAction {
+    Play,
+    Pause,
+    PlayPause,
+    /// pause (the playhead is kept)
+    Stop,
+    SeekBackward,
+    SeekForward,
+    SeekTo,
+    PreviousTrack,
+    NextTrack,
+}
+
+/// metadata and position of the track loaded to the player for the OS media controls
+#[napi(object)]
+pub struct NowPlaying {
+    /// title found by identifyTrack, or the file name
+    pub title: String,
+    pub artist: Option<String>,
+    pub duration_sec: f64,
+    pub position_sec: f64,
+    pub is_playing: bool,
+}
+
+/// Execute the action on the player.
+/// seek_sec: destination of SeekTo, or the offset of SeekBackward/SeekForward
+/// (default_offset_sec if None).
+pub async fn handle_media_action(
+    action: MediaAction,
+    seek_sec: Option<f64>,
+    default_offset_sec: f64,
+) -> Result<(), String> {
+    let is_playing = matches!(player::recv(), PlayerNotification::Ok(state) if state.is_playing);
+    let offset_sec = seek_sec.unwrap_or(default_offset_sec);
+    let command = match action {
+        MediaAction::Play => PlayerCommand::Resume,
+        MediaAction::Pause | MediaAction::Stop => PlayerCommand::Pause,
+        MediaAction::PlayPause if is_playing => PlayerCommand::Pause,
+        MediaAction::PlayPause => PlayerCommand::Resume,
+        MediaAction::SeekBackward => PlayerCommand::SeekRelative(-offset_sec),
+        MediaAction::SeekForward => PlayerCommand::SeekRelative(offset_sec),
+        MediaAction::SeekTo => match seek_sec.filter(|sec| sec.is_finite()) {
+            Some(sec) => PlayerCommand::Seek(sec),
+            None => return Err("SeekTo needs the seek time.".into()),
+        },
+        MediaAction::PreviousTrack | MediaAction::NextTrack => {
+            let ids = TRACK_LIST.read().await.all_ids();
+            let forward = action == MediaAction::NextTrack;
+            match neighbor_track(&ids, player::transport().track_id, forward) {
+                Some(id) => PlayerCommand::SetTrack((Some(id), None)),
+                None => return Ok(()),
+            }
+        }
+    };
+    player::send(command).await;
+    Ok(())
+}
+
+/// None if no track is loaded to the player
+pub async fn now_playing() -> Option<NowPlaying> {
+    let id = player::transport().track_id?;
+    let (title, duration_sec) = {
+        let tracklist = TRACK_LIST.read().await;
+        let track = tracklist.get(id)?;
+        (track.file_stem(), track.sec())
+    };
+    let identity = acoustid::identity(id);
+    let (position_sec, is_playing) = match player::recv() {
+        PlayerNotification::Ok(state) => (state.position_sec, state.is_playing),
+        PlayerNotification::Err(_) => (0., false),
+    };
+    Some(NowPlaying {
+        title: identity
+            .as_ref()
+            .and_then(|identity| identity.title.clone())
+            .unwrap_or(title),
+        artist: identity.and_then(|identity| identity.artist),
+        duration_sec,
+        position_sec,
+        is_playing,
+    })
+}
+
+/// the track before or after current in ids (ascending). The first track if current is None.
+/// None at the ends (not wrapped around).
+fn neighbor_track(ids: &[usize], current: Option<usize>, forward: bool) -> Option<usize> {
+    let Some(current) = current else {
+        return ids.first().copied();
+    };
+    if forward {
+        ids.iter().copied().find(|&id| id > current)
+    } else {
+        ids.iter().copied().rev().find(|&id| id < current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighbor_track_works() {
+        let ids = [0, 2, 5];
+        assert_eq!(neighbor_track(&ids, Some(2), true), Some(5));
+        assert_eq!(neighbor_track(&ids, Some(2), false), Some(0));
+        assert_eq!(neighbor_track(&ids, Some(5), true), None);
+        assert_eq!(neighbor_track(&ids, Some(0), false), None);
+        // the current track is removed
+        assert_eq!(neighbor_track(&ids, Some(3), true), Some(5));
+        assert_eq!(neighbor_track(&ids, None, false), Some(0));
+        assert_eq!(neighbor_track(&[], None, true), None);
+    }
+}
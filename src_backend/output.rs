@@ -0,0 +1,318 @@
+//! Output stage of the player applied to the mix of all the sounds in the audio callback.
+//! The volume, the fade, the output limiter and the dither are applied in real time,
+//! so changing them neither rebuilds the sound of the track nor blocks the player thread.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use atomic_float::{AtomicF32, AtomicF64};
+use kittyaudio::{
+    Backend, DefaultRenderer, Device, Frame, Renderer, RendererHandle, Sound, SoundHandle,
+    StreamSettings,
+};
+use log::error;
+use parking_lot::Mutex;
+
+use crate::{PerfectLimiter, TpdfDither};
+
+/// linear ramp of the fade gain of the output stage
+#[derive(Clone, Copy, Debug)]
+struct Fade {
+    /// None to start from the current fade gain
+    from: Option<f32>,
+    to: f32,
+    duration: Duration,
+}
+
+/// parameters and meters of the output stage shared with the audio callback
+#[derive(Debug)]
+pub struct OutputControl {
+    /// volume set by the user multiplied by the monitor gain
+    volume: AtomicF32,
+    /// fade requested by fade_to and not taken by the audio callback yet
+    fade: Mutex<Option<Fade>>,
+    has_fade: AtomicBool,
+    /// limiter threshold (amplitude). INFINITY if the limiter is disabled
+    limiter_threshold: AtomicF64,
+    /// if the limiter reduced the gain since the last take_limiter_active
+    limiter_active: AtomicBool,
+    /// LSB of the integer sample format of the output stream. 0 if no dither is needed.
+    dither_lsb: AtomicF32,
+}
+
+impl OutputControl {
+    pub fn new() -> Self {
+        OutputControl {
+            volume: AtomicF32::new(1.),
+            fade: Mutex::new(None),
+            has_fade: AtomicBool::new(false),
+            limiter_threshold: AtomicF64::new(f64::INFINITY),
+            limiter_active: AtomicBool::new(false),
+            dither_lsb: AtomicF32::new(0.),
+        }
+    }
+
+    #[inline]
+    pub fn set_volume(&self, volume: f32) {
+        self.volume.store(volume, Ordering::Release);
+    }
+
+    /// Ramp the fade gain (multiplied to the volume) linearly from `from` to `to` during
+    /// `duration` in the audio callback. None `from` to start from the current fade gain.
+    pub fn fade_to(&self, from: Option<f32>, to: f32, duration: Duration) {
+        let mut fade = self.fade.lock();
+        *fade = Some(Fade { from, to, duration });
+        self.has_fade.store(true, Ordering::Release);
+    }
+
+    /// take the fade requested by fade_to without blocking the audio callback
+    fn take_fade(&self) -> Option<Fade> {
+        if !self.has_fade.load(Ordering::Acquire) {
+            return None;
+        }
+        let mut fade = self.fade.try_lock()?;
+        self.has_fade.store(false, Ordering::Release);
+        fade.take()
+    }
+
+    /// None to disable the limiter
+    #[inline]
+    pub fn set_limiter_threshold(&self, threshold: Option<f64>) {
+        self.limiter_threshold
+            .store(threshold.unwrap_or(f64::INFINITY), Ordering::Release);
+    }
+
+    /// if the limiter reduced the gain since the last call
+    #[inline]
+    pub fn take_limiter_active(&self) -> bool {
+        self.limiter_active.swap(false, Ordering::AcqRel)
+    }
+
+    /// None to disable the dither
+    #[inline]
+    pub fn set_dither_lsb(&self, lsb: Option<f32>) {
+        self.dither_lsb.store(lsb.unwrap_or(0.), Ordering::Release);
+    }
+}
+
+/// volume (with the fade gain) → limiter → TPDF dither.
+/// The limiter always runs (with the infinite threshold if disabled)
+/// so that the latency doesn't change when it is toggled.
+#[derive(Clone)]
+pub struct OutputStage {
+    control: Arc<OutputControl>,
+    fade_gain: f32,
+    fade_target: f32,
+    /// change of the fade gain per sample
+    fade_delta: f32,
+    /// sample rate of the limiter
+    sr: u32,
+    /// created at the sample rate of the stream
+    limiter: Option<PerfectLimiter>,
+    dither: TpdfDither,
+}
+
+impl OutputStage {
+    pub fn new(control: Arc<OutputControl>) -> Self {
+        OutputStage {
+            control,
+            fade_gain: 1.,
+            fade_target: 1.,
+            fade_delta: 0.,
+            sr: 0,
+            limiter: None,
+            dither: TpdfDither::new(0),
+        }
+    }
+
+    fn step_fade_gain(&mut self, sr: u32) -> f32 {
+        if let Some(fade) = self.control.take_fade() {
+            if let Some(from) = fade.from {
+                self.fade_gain = from;
+            }
+            self.fade_target = fade.to;
+            let n_samples = fade.duration.as_secs_f32() * sr as f32;
+            self.fade_delta = if n_samples >= 1. {
+                (fade.to - self.fade_gain).abs() / n_samples
+            } else {
+                f32::INFINITY
+            };
+        }
+        if self.fade_gain < self.fade_target {
+            self.fade_gain = (self.fade_gain + self.fade_delta).min(self.fade_target);
+        } else if self.fade_gain > self.fade_target {
+            self.fade_gain = (self.fade_gain - self.fade_delta).max(self.fade_target);
+        }
+        self.fade_gain
+    }
+
+    pub fn process(&mut self, frame: Frame, sr: u32) -> Frame {
+        let threshold = self.control.limiter_threshold.load(Ordering::Acquire);
+        if self.sr != sr {
+            self.limiter = None;
+            self.sr = sr;
+        }
+        let limiter = self.limiter.get_or_insert_with(|| {
+            let mut limiter = PerfectLimiter::with_default(sr);
+            limiter.reset(2);
+            limiter
+        });
+        if limiter.threshold != threshold {
+            limiter.set_threshold(threshold);
+        }
+        let volume = self.control.volume.load(Ordering::Acquire) * self.step_fade_gain(sr);
+        let (output, gain) = limiter.step_stereo((frame.left * volume, frame.right * volume));
+        if gain < 1. {
+            self.control.limiter_active.store(true, Ordering::Release);
+        }
+        let lsb = self.control.dither_lsb.load(Ordering::Acquire);
+        if lsb > 0. {
+            self.dither.process(output.into(), lsb)
+        } else {
+            output.into()
+        }
+    }
+}
+
+#[derive(Clone)]
+struct OutputRenderer {
+    mix: DefaultRenderer,
+    stage: OutputStage,
+}
+
+impl Renderer for OutputRenderer {
+    fn next_frame(&mut self, sample_rate: u32) -> Frame {
+        let frame = self.mix.next_frame(sample_rate);
+        self.stage.process(frame, sample_rate)
+    }
+}
+
+/// kittyaudio::Mixer with the output stage applied to the mix of the sounds
+pub struct OutputMixer {
+    renderer: RendererHandle<OutputRenderer>,
+    backend: Arc<Mutex<Backend>>,
+}
+
+impl OutputMixer {
+    pub fn new(control: Arc<OutputControl>) -> Self {
+        OutputMixer {
+            renderer: RendererHandle::new(OutputRenderer {
+                mix: DefaultRenderer::default(),
+                stage: OutputStage::new(control),
+            }),
+            backend: Arc::new(Mutex::new(Backend::new())),
+        }
+    }
+
+    /// start the output stream on a new thread (in the same way as kittyaudio::Mixer::init_ex)
+    pub fn init_ex(&self, device: Device, settings: StreamSettings) {
+        let backend = self.backend.clone();
+        let renderer = self.renderer.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = backend
+                .lock()
+                .start_audio_thread(device, settings, renderer)
+            {
+                error!("{}", err);
+            }
+        });
+    }
+
+    pub fn play(&self, sound: Sound) -> SoundHandle {
+        let handle = SoundHandle::new(sound);
+        self.renderer.guard().mix.sounds.push(handle.clone());
+        handle
+    }
+
+    /// true if no sound is playing
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.renderer.guard().mix.sounds.is_empty()
+    }
+
+    #[inline]
+    pub fn clear(&self) {
+        self.renderer.guard().mix.sounds.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_stage_works() {
+        let sr = 48000;
+        let control = Arc::new(OutputControl::new());
+        let mut stage = OutputStage::new(control.clone());
+        let input: Vec<_> = (0..sr)
+            .map(|i| (0.8 * (i as f32 * 0.01).sin(), 0.4 * (i as f32 * 0.03).cos()))
+            .collect();
+        let latency = PerfectLimiter::with_default(sr)._latency_samples();
+
+        control.set_volume(0.5);
+        let outputs: Vec<_> = input.iter().map(|&x| stage.process(x.into(), sr)).collect();
+        for (y, x) in outputs[latency..].iter().zip(&input) {
+            assert!((y.left - x.0 * 0.5).abs() < 1e-6 && (y.right - x.1 * 0.5).abs() < 1e-6);
+        }
+        assert!(!control.take_limiter_active());
+
+        // the limiter is applied after the volume without rebuilding anything
+        control.set_volume(4.);
+        control.set_limiter_threshold(Some(0.5));
+        let outputs: Vec<_> = input.iter().map(|&x| stage.process(x.into(), sr)).collect();
+        assert!(outputs
+            .iter()
+            .all(|y| y.left.abs() <= 0.5 + 1e-4 && y.right.abs() <= 0.5 + 1e-4));
+        assert!(control.take_limiter_active());
+        assert!(!control.take_limiter_active());
+
+        // the dither is ±1 LSB at any volume since it is added after the gains
+        let lsb = 1. / 32768.;
+        let control = Arc::new(OutputControl::new());
+        let mut stage = OutputStage::new(control.clone());
+        control.set_volume(0.01);
+        control.set_dither_lsb(Some(lsb));
+        let outputs: Vec<_> = input.iter().map(|&x| stage.process(x.into(), sr)).collect();
+        let errors: Vec<_> = outputs[latency..]
+            .iter()
+            .zip(&input)
+            .flat_map(|(y, x)| [y.left - x.0 * 0.01, y.right - x.1 * 0.01])
+            .collect();
+        assert!(errors.iter().all(|e| e.abs() <= lsb * 1.001));
+        assert!(errors.iter().any(|e| e.abs() > lsb * 0.5));
+    }
+
+    #[test]
+    fn fade_works() {
+        let sr = 1000;
+        let control = Arc::new(OutputControl::new());
+        let mut stage = OutputStage::new(control.clone());
+        let mut gains = |n: usize| -> Vec<f32> {
+            (0..n)
+                .map(|_| {
+                    stage.step_fade_gain(sr);
+                    stage.fade_gain
+                })
+                .collect()
+        };
+
+        control.fade_to(None, 0., Duration::from_millis(100));
+        let fade_out = gains(200);
+        assert!((fade_out[49] - 0.5).abs() < 1e-4);
+        assert!(fade_out.windows(2).all(|w| w[0] >= w[1]));
+        assert!(fade_out[99] < 1e-4);
+        assert_eq!(fade_out[199], 0.);
+
+        control.fade_to(Some(0.), 1., Duration::from_millis(10));
+        let fade_in = gains(20);
+        assert!((fade_in[4] - 0.5).abs() < 1e-4);
+        assert!(fade_in[9] > 1. - 1e-4);
+        assert_eq!(fade_in[19], 1.);
+
+        // no fade
+        control.fade_to(None, 0.5, Duration::ZERO);
+        assert_eq!(gains(1), [0.5]);
+    }
+}
@@ -1,47 +1,267 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
 use std::sync::atomic::{self, AtomicU32, AtomicUsize};
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 
 use atomic_float::AtomicF32;
-use cpal::{traits::DeviceTrait, SupportedStreamConfigsError};
-use kittyaudio::{Device, KaError, Mixer, Sound, SoundHandle, StreamSettings};
-use log::{error, info};
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::{SampleFormat, SupportedBufferSize, SupportedStreamConfigsError};
+use kittyaudio::{Device, Frame, KaError, Sound, SoundHandle, StreamSettings};
+use log::{error, info, warn};
 use napi::bindgen_prelude::spawn_blocking;
 use napi::tokio::sync::mpsc::{self, error::TryRecvError};
-use napi::tokio::sync::watch;
+use napi::tokio::sync::{oneshot, watch};
+use parking_lot::RwLock;
 
-use crate::{DeciBel, TRACK_LIST};
+use crate::crash;
+use crate::jobs::{spawn_blocking_job, JobKind};
+use crate::output::{OutputControl, OutputMixer};
+use crate::{
+    calc_dither_lsb, DeciBel, DownmixMode, ImpulseResponse, SpectralDenoiser, TfBoxSolo,
+    TrackTimeline, TRACK_LIST,
+};
 
 const PLAYER_NOTI_INTERVAL: Duration = Duration::from_millis(100);
+/// duration of a scrubbing grain (sec)
+const SCRUB_GRAIN_SEC: f64 = 0.08;
+const MAX_SCRUB_SPEED: f64 = 4.;
+/// interval of checking if the output device still exists (enumerating devices is slow)
+const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// attenuation of the monitor dim
+#[allow(non_upper_case_globals)]
+const MONITOR_DIM_dB: f64 = -20.;
+/// lag of the playback position behind the wall clock during a notification interval
+/// regarded as a buffer underrun (the audio callback couldn't keep up)
+const UNDERRUN_LAG_SEC: f64 = 0.05;
+/// the buffer size is doubled if the underruns occur MAX_UNDERRUNS times in this window
+const UNDERRUN_WINDOW: Duration = Duration::from_secs(10);
+const MAX_UNDERRUNS: usize = 3;
+/// buffer size (frames) assumed when the default buffer size of the device is used
+const DEFAULT_BUFFER_SIZE: u32 = 512;
+/// upper limit of the adaptive buffer size if the device doesn't tell its range
+const MAX_ADAPTIVE_BUFFER_SIZE: u32 = 8192;
+/// number of the processed playbacks kept by the player (each is as long as the track)
+const MAX_RENDERED_PLAYBACKS: usize = 4;
 
 static COMMAND_TX: OnceLock<mpsc::Sender<PlayerCommand>> = OnceLock::new();
 static NOTI_RX: OnceLock<watch::Receiver<PlayerNotification>> = OnceLock::new();
+/// (name of the current output device, number of the automatic device changes)
+static OUTPUT_DEVICE: RwLock<(String, u32)> = RwLock::new((String::new(), 0));
+static MONITOR: RwLock<MonitorState> = RwLock::new(MonitorState::new());
+/// None if the format of the output stream is unknown
+static STREAM_FORMAT: RwLock<Option<StreamFormat>> = RwLock::new(None);
+static TRANSPORT: RwLock<Transport> = RwLock::new(Transport::new());
+static BUFFER_HEALTH: RwLock<BufferHealth> = RwLock::new(BufferHealth::new());
+/// cpal host forced by the user. None for the default host of the platform.
+static AUDIO_HOST: RwLock<Option<cpal::HostId>> = RwLock::new(None);
+
+/// buffer underruns of the output stream and the buffer size adapted to them
+#[derive(Clone, Debug)]
+pub struct BufferHealth {
+    /// number of the underruns detected since the player started
+    pub n_underruns: u32,
+    /// buffer size (frames) requested to the device. None for the default of the device
+    pub buffer_size: Option<u32>,
+    /// if the buffer size is doubled on repeated underruns
+    pub adaptive: bool,
+}
+
+impl BufferHealth {
+    const fn new() -> Self {
+        BufferHealth {
+            n_underruns: 0,
+            buffer_size: None,
+            adaptive: true,
+        }
+    }
+}
+
+/// transport of the player mirrored for the external sync (e.g. OSC bridges)
+#[derive(Clone, Debug)]
+pub struct Transport {
+    /// id of the track loaded to the player. None if no track is loaded
+    pub track_id: Option<usize>,
+    /// loop range (start_sec, end_sec) of the player. None if looping is disabled
+    pub loop_range: Option<(f64, f64)>,
+    /// increased on every discontinuous change of the transport
+    /// (play/pause, seek, track change, loop range change, jump back at the loop end,
+    /// reaching the end of the tracks). Not increased while the position advances by playing.
+    pub revision: u32,
+}
+
+impl Transport {
+    const fn new() -> Self {
+        Transport {
+            track_id: None,
+            loop_range: None,
+            revision: 0,
+        }
+    }
+}
+
+/// format of the output stream negotiated with the device
+#[derive(Clone, Debug)]
+pub struct StreamFormat {
+    pub sample_format: SampleFormat,
+    pub sr: u32,
+    pub channels: u16,
+    /// (min, max) buffer size (frames) supported by the device.
+    /// The stream uses the default buffer size of the device. None if unknown.
+    pub buffer_size_range: Option<(u32, u32)>,
+}
+
+impl StreamFormat {
+    /// LSB of the integer sample format to be dithered. None if no dither is needed.
+    pub fn dither_lsb(&self) -> Option<f32> {
+        calc_dither_lsb(
+            self.sample_format.sample_size() as u32 * 8,
+            self.sample_format.is_float(),
+        )
+    }
+}
+
+/// monitor section applied on top of the volume (like the monitor section of a console)
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(non_snake_case)]
+pub struct MonitorState {
+    /// attenuate by MONITOR_DIM_dB
+    pub dim: bool,
+    pub mute: bool,
+    /// calibrated monitor gain (dB, <= 0) so that A/B listening happens at a consistent level
+    pub reference_level_dB: f64,
+}
+
+impl MonitorState {
+    const fn new() -> Self {
+        MonitorState {
+            dim: false,
+            mute: false,
+            reference_level_dB: 0.,
+        }
+    }
+
+    #[allow(non_snake_case)]
+    fn gain(&self) -> f32 {
+        if self.mute {
+            return 0.;
+        }
+        let dim_dB = if self.dim { MONITOR_DIM_dB } else { 0. };
+        (self.reference_level_dB + dim_dB).amp_from_dB_default() as f32
+    }
+}
 
 pub enum PlayerCommand {
     /// only caused by refreshing of frontend
     Initialize,
     /// set volume
     SetVolumedB(f64),
+    /// apply the monitor state changed by update_monitor
+    ApplyMonitor,
     /// if zero, the default sr is used
     SetSr(u32),
+    /// output limiter threshold (amplitude). None to disable the limiter
+    SetOutputLimiter(Option<f64>),
+    /// downmix applied to the playback
+    SetDownmix(DownmixMode),
+    /// impulse response convolved with the playback. None to disable
+    SetImpulseResponse(Option<ImpulseResponse>),
+    /// spectral subtraction of the noise floor from the playback. None to disable.
+    /// The noise profile is calculated by the sender not to block the player.
+    SetDenoise(Option<SpectralDenoiser>),
+    /// Play only the time-frequency box from sec_range.0 (e.g. a box drawn on the spectrogram).
+    /// sec_range: (start, end) sec, hz_range: (min, max) Hz. The outside of the box is silent.
+    SoloTfBox {
+        sec_range: (f64, f64),
+        hz_range: (f32, f32),
+    },
+    /// stop soloing the time-frequency box
+    ClearTfBoxSolo,
+    /// buffer size (frames) of the output stream. None for the default of the device.
+    /// The stream is rebuilt.
+    SetBufferSize(Option<u32>),
+    /// rebuild the stream on the default output device of the audio host set by set_audio_host
+    ChangeAudioHost,
+    /// fade duration (ms) on pause/resume. zero to disable fading
+    SetFadeMillisec(f64),
+    /// seek to the time pre_roll (ms) earlier than the seek point
+    SetPreRollMillisec(f64),
     /// arg: (optional track_id, optional start_time (sec))
     /// if track_id is None, the current track is reloaded
     SetTrack((Option<usize>, Option<f64>)),
     /// arg: time (sec)
     Seek(f64),
+    /// arg: time (sec) relative to the current position (negative: backward).
+    /// The destination is snapped to a sample of the current track. Pre-roll is not applied.
+    SeekRelative(f64),
+    /// Move the playhead to sec (pausing the playback) and play a short grain around it
+    /// at the speed (negative: backward). Designed for continuous updates while dragging.
+    Scrub { sec: f64, speed: f64 },
+    /// loop range (start_sec, end_sec). None to disable looping
+    SetLoop(Option<(f64, f64)>),
+    /// Pause the track and play the test signal for measuring the latency.
+    /// played_tx is notified right after the signal is handed to the mixer.
+    PlayTestSignal {
+        frames: Vec<Frame>,
+        sr: u32,
+        played_tx: oneshot::Sender<()>,
+    },
+    /// reply the current settings of the DSP applied to the playback
+    GetPlaybackChain(oneshot::Sender<PlaybackChain>),
+    /// frames processed by the job spawned by the player. Played if still needed.
+    PlaybackRendered {
+        key: PlaybackKey,
+        sr: u32,
+        frames: Vec<Frame>,
+    },
     /// pause playing
     Pause,
     /// resume playing
     Resume,
 }
 
+impl PlayerCommand {
+    /// name recorded for the crash report (without the arguments, which can be large)
+    fn name(&self) -> &'static str {
+        match self {
+            PlayerCommand::Initialize => "Initialize",
+            PlayerCommand::SetVolumedB(_) => "SetVolumedB",
+            PlayerCommand::ApplyMonitor => "ApplyMonitor",
+            PlayerCommand::SetSr(_) => "SetSr",
+            PlayerCommand::SetOutputLimiter(_) => "SetOutputLimiter",
+            PlayerCommand::SetDownmix(_) => "SetDownmix",
+            PlayerCommand::SetImpulseResponse(_) => "SetImpulseResponse",
+            PlayerCommand::SetDenoise(_) => "SetDenoise",
+            PlayerCommand::SoloTfBox { .. } => "SoloTfBox",
+            PlayerCommand::ClearTfBoxSolo => "ClearTfBoxSolo",
+            PlayerCommand::SetBufferSize(_) => "SetBufferSize",
+            PlayerCommand::ChangeAudioHost => "ChangeAudioHost",
+            PlayerCommand::SetFadeMillisec(_) => "SetFadeMillisec",
+            PlayerCommand::SetPreRollMillisec(_) => "SetPreRollMillisec",
+            PlayerCommand::SetTrack(_) => "SetTrack",
+            PlayerCommand::Seek(_) => "Seek",
+            PlayerCommand::SeekRelative(_) => "SeekRelative",
+            PlayerCommand::Scrub { .. } => "Scrub",
+            PlayerCommand::SetLoop(_) => "SetLoop",
+            PlayerCommand::PlayTestSignal { .. } => "PlayTestSignal",
+            PlayerCommand::GetPlaybackChain(_) => "GetPlaybackChain",
+            PlayerCommand::PlaybackRendered { .. } => "PlaybackRendered",
+            PlayerCommand::Pause => "Pause",
+            PlayerCommand::Resume => "Resume",
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct InternalPlayerState {
     /// if currently playing
     pub is_playing: bool,
     /// playing position (sec)
     pub position_sec: f64,
+    /// if the output limiter reduced the gain during the last notification interval
+    pub is_limiter_active: bool,
+    /// playing speed relative to the track (1 / stretch ratio of the track)
+    pub speed: f64,
     /// timestamp when this state is created
     pub instant: Instant,
 }
@@ -49,7 +269,7 @@ pub struct InternalPlayerState {
 impl InternalPlayerState {
     pub fn position_sec_elapsed(&self) -> f64 {
         if self.is_playing {
-            self.position_sec + self.instant.elapsed().as_secs_f64()
+            self.position_sec + self.instant.elapsed().as_secs_f64() * self.speed
         } else {
             self.position_sec
         }
@@ -61,11 +281,39 @@ impl Default for InternalPlayerState {
         InternalPlayerState {
             is_playing: false,
             position_sec: 0.,
+            is_limiter_active: false,
+            speed: 1.,
             instant: Instant::now(),
         }
     }
 }
 
+/// settings of the DSP applied to the playback
+#[derive(Clone, Debug)]
+pub struct PlaybackChain {
+    /// sample rate of the output device
+    pub sr: u32,
+    pub impulse_response: Option<ImpulseResponse>,
+    /// output limiter threshold (amplitude)
+    pub output_limiter_threshold: Option<f64>,
+    pub downmix_mode: DownmixMode,
+    pub denoiser: Option<SpectralDenoiser>,
+    pub tf_box_solo: Option<TfBoxSolo>,
+    /// volume set by the user multiplied by the monitor gain
+    pub volume: f32,
+}
+
+/// track and DSP settings of the processed playback
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlaybackKey {
+    track_id: usize,
+    playback_version: u64,
+    downmix_mode: DownmixMode,
+    /// increased whenever the impulse response or the denoiser is set
+    chain_version: u64,
+    tf_box_solo: Option<TfBoxSolo>,
+}
+
 #[derive(Clone, Debug)]
 pub enum PlayerNotification {
     Ok(InternalPlayerState),
@@ -73,12 +321,22 @@ pub enum PlayerNotification {
 }
 
 pub async fn send(msg: PlayerCommand) {
+    crash::record_command(msg.name());
     let msg_tx = COMMAND_TX.get().unwrap().clone();
     if let Err(e) = msg_tx.send(msg).await {
         panic!("PLAYER MSG_TX error: {}", e);
     }
 }
 
+/// send from a thread outside of the async runtime
+pub fn blocking_send(msg: PlayerCommand) {
+    crash::record_command(msg.name());
+    let msg_tx = COMMAND_TX.get().unwrap();
+    if let Err(e) = msg_tx.blocking_send(msg) {
+        panic!("PLAYER MSG_TX error: {}", e);
+    }
+}
+
 pub fn recv() -> PlayerNotification {
     let noti_rx = NOTI_RX.get().unwrap();
     let noti = (noti_rx.borrow()).clone();
@@ -91,8 +349,149 @@ pub fn recv() -> PlayerNotification {
     }
 }
 
+/// (name of the current output device, number of the automatic device changes).
+/// The device is changed automatically when the default device changes
+/// or the current device is lost (e.g. USB interface unplugged).
+pub fn output_device() -> (String, u32) {
+    OUTPUT_DEVICE.read().clone()
+}
+
+#[inline]
+pub fn stream_format() -> Option<StreamFormat> {
+    STREAM_FORMAT.read().clone()
+}
+
+#[inline]
+pub fn buffer_health() -> BufferHealth {
+    BUFFER_HEALTH.read().clone()
+}
+
+/// enable doubling the buffer size on repeated underruns
+pub fn set_adaptive_buffer_size(adaptive: bool) {
+    BUFFER_HEALTH.write().adaptive = adaptive;
+}
+
+#[inline]
+pub fn transport() -> Transport {
+    TRANSPORT.read().clone()
+}
+
+/// modify the transport and increase the revision
+fn update_transport(f: impl FnOnce(&mut Transport)) {
+    let mut transport = TRANSPORT.write();
+    f(&mut transport);
+    transport.revision = transport.revision.wrapping_add(1);
+}
+
+#[inline]
+pub fn monitor() -> MonitorState {
+    *MONITOR.read()
+}
+
+/// modify the monitor state and apply it to the playback
+pub async fn update_monitor(f: impl FnOnce(&mut MonitorState)) {
+    f(&mut MONITOR.write());
+    send(PlayerCommand::ApplyMonitor).await;
+}
+
+/// names of the cpal hosts available on this platform (e.g. "WASAPI", "ASIO", "ALSA", "JACK")
+pub fn list_audio_hosts() -> Vec<String> {
+    cpal::available_hosts()
+        .into_iter()
+        .map(|id| id.name().to_string())
+        .collect()
+}
+
+/// None if the default host of the platform is used
+pub fn audio_host_name() -> Option<String> {
+    AUDIO_HOST.read().map(|id| id.name().to_string())
+}
+
+/// Force the cpal host of the name (case-insensitive). None for the default host.
+/// The stream is rebuilt by PlayerCommand::ChangeAudioHost.
+pub fn set_audio_host(name: Option<&str>) -> Result<(), String> {
+    let host_id = match name {
+        Some(name) => {
+            let host_id = cpal::available_hosts()
+                .into_iter()
+                .find(|id| id.name().eq_ignore_ascii_case(name))
+                .ok_or_else(|| format!("The audio host {} is not available.", name))?;
+            cpal::host_from_id(host_id).map_err(|e| e.to_string())?;
+            Some(host_id)
+        }
+        None => None,
+    };
+    *AUDIO_HOST.write() = host_id;
+    Ok(())
+}
+
+/// the host set by set_audio_host, or the default host of the platform
+pub fn audio_host() -> cpal::Host {
+    AUDIO_HOST
+        .read()
+        .and_then(|id| cpal::host_from_id(id).ok())
+        .unwrap_or_else(cpal::default_host)
+}
+
+/// name of the default output device of the audio host
+fn default_output_device_name() -> Result<String, KaError> {
+    if AUDIO_HOST.read().is_some() {
+        if let Some(name) = audio_host()
+            .default_output_device()
+            .and_then(|device| device.name().ok())
+        {
+            return Ok(name);
+        }
+    }
+    Device::Default.name()
+}
+
+/// the output device of the name in the audio host.
+/// Falls back to the default host if the device is not found in the audio host.
+fn find_output_device(device_name: &str) -> Result<Device, KaError> {
+    if AUDIO_HOST.read().is_none() {
+        return Device::from_name(device_name);
+    }
+    audio_host()
+        .output_devices()
+        .ok()
+        .and_then(|mut devices| {
+            devices.find(|device| device.name().is_ok_and(|x| x == device_name))
+        })
+        .map_or_else(
+            || Device::from_name(device_name),
+            |device| Ok(Device::Custom(device)),
+        )
+}
+
+/// true if enumerating the devices fails not to rebuild the stream needlessly
+fn output_device_exists(device_name: &str) -> bool {
+    audio_host().output_devices().map_or(true, |mut devices| {
+        devices.any(|device| device.name().is_ok_and(|x| x == device_name))
+    })
+}
+
+/// The output stream uses the sample format and the channels of the default output config
+/// of the device with the sample rate overridden.
+fn query_stream_format(device_name: &str, sr: u32) -> Option<StreamFormat> {
+    let Ok(Device::Custom(device)) = find_output_device(device_name) else {
+        return None;
+    };
+    let config = device.default_output_config().ok()?;
+    let buffer_size_range = match config.buffer_size() {
+        SupportedBufferSize::Range { min, max } => Some((*min, *max)),
+        SupportedBufferSize::Unknown => None,
+    };
+    Some(StreamFormat {
+        sample_format: config.sample_format(),
+        sr,
+        channels: config.channels(),
+        buffer_size_range,
+    })
+}
+
 fn get_supported_sr_list(device_name: &str) -> Result<Vec<u32>, KaError> {
-    if let Device::Custom(device) = Device::from_name(device_name)? {
+    if let Device::Custom(device) = find_output_device(device_name)? {
         match device.supported_output_configs() {
             Ok(supported_configs) => Ok(supported_configs
                 .flat_map(|c| (c.min_sample_rate().0..=c.max_sample_rate().0))
@@ -145,8 +544,24 @@ fn get_optimal_sr(device_name: &str, sr: u32) -> Result<Option<u32>, KaError> {
     }
 }
 
-fn calc_position_sec(sound_handle: &SoundHandle) -> f64 {
-    sound_handle.index() as f64 / sound_handle.sample_rate() as f64
+/// position on the session timeline (not the time of the stretched sound)
+fn calc_position_sec(sound_handle: &SoundHandle, timeline: TrackTimeline) -> f64 {
+    timeline.sound_to_session(sound_handle.index() as f64 / sound_handle.sample_rate() as f64)
+}
+
+/// true if the position advanced slower than the wall clock by UNDERRUN_LAG_SEC
+/// between the two states of continuous playback
+fn is_underrun(prev_state: &InternalPlayerState, state: &InternalPlayerState) -> bool {
+    let expected_sec = (state.instant - prev_state.instant).as_secs_f64() * prev_state.speed;
+    state.position_sec - prev_state.position_sec < expected_sec - UNDERRUN_LAG_SEC
+}
+
+/// double the buffer size within the range supported by the device.
+/// None if it cannot be increased.
+fn increased_buffer_size(buffer_size: Option<u32>, range: Option<(u32, u32)>) -> Option<u32> {
+    let max = range.map_or(MAX_ADAPTIVE_BUFFER_SIZE, |(_, max)| max);
+    let current = buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE);
+    (current < max).then(|| (current * 2).min(max))
 }
 
 fn noti_err(noti_tx: &watch::Sender<PlayerNotification>, err: KaError) {
@@ -161,10 +576,32 @@ fn main_loop(
     noti_tx: watch::Sender<PlayerNotification>,
 ) {
     let current_sr = AtomicU32::new(48000);
+    // volume set by the user (without the monitor gain)
+    let user_volume = Cell::new(1f32);
+    // volume multiplied by the monitor gain
     let current_volume = AtomicF32::new(1.);
     let current_track_id = AtomicUsize::new(0);
+    let output_limiter_threshold = RefCell::new(None::<f64>);
+    // volume and output limiter applied to the mix in the audio callback
+    let output_control = Arc::new(OutputControl::new());
+    let downmix_mode = RefCell::new(DownmixMode::Off);
+    let impulse_response = RefCell::new(None::<ImpulseResponse>);
+    let denoiser = RefCell::new(None::<SpectralDenoiser>);
+    let tf_box_solo = RefCell::new(None::<TfBoxSolo>);
+    let chain_version = Cell::new(0u64);
+    // processed playbacks with their keys and sample rates, the most recent first
+    let rendered = RefCell::new(VecDeque::<(PlaybackKey, u32, Vec<Frame>)>::new());
+    // key of the playback being processed by a job for the current sound
+    let rendering = RefCell::new(None::<PlaybackKey>);
+    // offset and stretch ratio of the track of the current sound
+    let timeline = Cell::new(TrackTimeline::default());
+    let mut fade = Duration::ZERO;
+    // deadline of the pause waiting for the fade-out of the output stage
+    let mut pending_pause = None::<Instant>;
+    let mut pre_roll_sec = 0.;
+    let mut loop_range = None::<(f64, f64)>;
     let get_device_name = || {
-        Device::Default.name().unwrap_or_else(|err| {
+        default_output_device_name().unwrap_or_else(|err| {
             noti_err(&noti_tx, err);
             "".into()
         })
@@ -172,12 +609,12 @@ fn main_loop(
     let device_name = RefCell::new(String::new());
     let init_mixer = |sr: Option<u32>, change_device: bool| {
         let sr = sr.unwrap_or(48000);
-        let mixer = Mixer::new();
+        let mixer = OutputMixer::new(output_control.clone());
         if change_device {
             *device_name.borrow_mut() = get_device_name();
         }
         let device = loop {
-            match Device::from_name(&device_name.borrow()) {
+            match find_output_device(&device_name.borrow()) {
                 Ok(d) => break d,
                 Err(err) => {
                     noti_err(&noti_tx, err);
@@ -186,52 +623,151 @@ fn main_loop(
                 }
             };
         };
+        let buffer_size = BUFFER_HEALTH.read().buffer_size;
         mixer.init_ex(
             device,
             StreamSettings {
                 sample_rate: Some(sr),
+                buffer_size,
                 ..Default::default()
             },
         );
-        info!("device: {}, sr: {}", device_name.borrow(), sr);
+        info!(
+            "device: {}, sr: {}, buffer size: {:?}",
+            device_name.borrow(),
+            sr,
+            buffer_size
+        );
+        OUTPUT_DEVICE.write().0.clone_from(&device_name.borrow());
+        let stream_format = query_stream_format(&device_name.borrow(), sr);
+        info!("stream format: {:?}", stream_format);
+        output_control.set_dither_lsb(stream_format.as_ref().and_then(StreamFormat::dither_lsb));
+        *STREAM_FORMAT.write() = stream_format;
         current_sr.store(sr, atomic::Ordering::Release);
         mixer
     };
+    let mut last_device_check = Instant::now();
+    // instants of the recent underruns in UNDERRUN_WINDOW
+    let mut underrun_instants = Vec::<Instant>::new();
     let mut mixer = init_mixer(None, true);
     let mut sound_handle = SoundHandle::new({
         let mut sound = Sound::default();
         sound.pause();
         sound
     });
-    let set_track = |mixer: &mut Mixer,
+    let set_track = |mixer: &mut OutputMixer,
                      sound_handle: &mut SoundHandle,
                      track_id: Option<usize>,
                      start_time_sec: f64,
                      is_playing: bool| {
         let track_id = track_id.unwrap_or(current_track_id.load(atomic::Ordering::Acquire));
-        let sound = TRACK_LIST
-            .blocking_read()
-            .get(track_id)
-            .map(|track| Sound::from_frames(track.sr(), track.interleaved_frames()));
+        let is_dry = *downmix_mode.borrow() == DownmixMode::Off
+            && impulse_response.borrow().is_none()
+            && denoiser.borrow().is_none()
+            && tf_box_solo.borrow().is_none();
+        // the processing runs in a job without the lock, and the sound is set when it's done
+        let sound = TRACK_LIST.blocking_read().get(track_id).map(|track| {
+            timeline.set(track.timeline());
+            if is_dry && !track.is_stretched() {
+                *rendering.borrow_mut() = None;
+                return Some(Sound::from_frames(track.sr(), track.interleaved_frames()));
+            }
+            let key = PlaybackKey {
+                track_id,
+                playback_version: track.playback_version(),
+                downmix_mode: *downmix_mode.borrow(),
+                chain_version: chain_version.get(),
+                tf_box_solo: tf_box_solo.borrow().clone(),
+            };
+            if let Some((_, sr, frames)) = rendered.borrow().iter().find(|(k, ..)| *k == key) {
+                *rendering.borrow_mut() = None;
+                return Some(Sound::from_frames(*sr, frames));
+            }
+            if rendering.borrow().as_ref() != Some(&key) {
+                // only the box with the tail of the IR is copied when soloing
+                let ir = impulse_response.borrow().clone();
+                let tail_sec = ir.as_ref().map_or(0., ImpulseResponse::sec);
+                let pending = track.prepare_playback(key.tf_box_solo.as_ref(), tail_sec);
+                let sr = track.sr();
+                let denoiser = denoiser.borrow().clone();
+                let key = key.clone();
+                spawn_blocking_job(JobKind::Playback, move || {
+                    let frames = pending.render(
+                        key.downmix_mode,
+                        ir.as_ref(),
+                        denoiser.as_ref(),
+                        key.tf_box_solo.as_ref(),
+                    );
+                    blocking_send(PlayerCommand::PlaybackRendered { key, sr, frames });
+                });
+                info!("rendering the playback of track {}", track_id);
+            }
+            *rendering.borrow_mut() = Some(key);
+            None
+        });
 
         info!("sound created with track {}", track_id);
         match sound {
-            Some(mut sound) => {
+            Some(Some(mut sound)) => {
                 sound.paused = !is_playing;
-                sound.set_volume(current_volume.load(atomic::Ordering::Acquire));
-                sound.seek_to(start_time_sec);
-                mixer.renderer.guard().sounds.clear();
+                sound.seek_to(timeline.get().session_to_sound(start_time_sec));
+                mixer.clear();
                 info!("mixer clear");
                 *sound_handle = mixer.play(sound);
                 info!("sound added");
                 current_track_id.store(track_id, atomic::Ordering::Release);
+                if TRANSPORT.read().track_id != Some(track_id) {
+                    update_transport(|transport| transport.track_id = Some(track_id));
+                }
+            }
+            Some(None) => {
+                // silent until the processed playback is rendered
+                mixer.clear();
+                current_track_id.store(track_id, atomic::Ordering::Release);
+                if TRANSPORT.read().track_id != Some(track_id) {
+                    update_transport(|transport| transport.track_id = Some(track_id));
+                }
             }
             None => {
-                mixer.renderer.guard().sounds.clear();
+                *rendering.borrow_mut() = None;
+                mixer.clear();
                 info!("mixer clear");
+                if TRANSPORT.read().track_id.is_some() {
+                    update_transport(|transport| transport.track_id = None);
+                }
             }
         }
     };
+    let current_state = |noti_tx: &watch::Sender<PlayerNotification>| {
+        if let PlayerNotification::Ok(state) = &(*noti_tx.borrow()) {
+            (state.position_sec_elapsed(), state.is_playing)
+        } else {
+            (0., false)
+        }
+    };
+    let pause = |sound_handle: &mut SoundHandle| {
+        sound_handle.pause();
+        // restore the fade gain for the other sounds (e.g. scrubbing grains)
+        output_control.fade_to(Some(1.), 1., Duration::ZERO);
+        if matches!(*noti_tx.borrow(), PlayerNotification::Ok(_)) {
+            noti_tx
+                .send(PlayerNotification::Ok(InternalPlayerState {
+                    is_playing: false,
+                    position_sec: calc_position_sec(sound_handle, timeline.get()),
+                    is_limiter_active: false,
+                    speed: timeline.get().stretch_ratio.recip(),
+                    instant: Instant::now(),
+                }))
+                .unwrap();
+        }
+        update_transport(|_| {});
+        info!("pause");
+    };
+    let update_volume = || {
+        let volume = user_volume.get() * monitor().gain();
+        current_volume.store(volume, atomic::Ordering::Release);
+        output_control.set_volume(volume);
+    };
 
     loop {
         match msg_rx.try_recv() {
@@ -258,11 +794,104 @@ fn main_loop(
                 PlayerCommand::SetSr(_) => {
                     info!("sr no change");
                 }
+                PlayerCommand::ChangeAudioHost => {
+                    let (position_sec, is_playing) = current_state(&noti_tx);
+                    let new_device = match default_output_device_name() {
+                        Ok(new_device) => new_device,
+                        Err(err) => {
+                            noti_err(&noti_tx, err);
+                            continue;
+                        }
+                    };
+                    let sr = match get_optimal_sr(
+                        &new_device,
+                        current_sr.load(atomic::Ordering::Acquire),
+                    ) {
+                        Ok(sr) => sr,
+                        Err(err) => {
+                            noti_err(&noti_tx, err);
+                            continue;
+                        }
+                    };
+                    mixer = init_mixer(sr, true);
+                    OUTPUT_DEVICE.write().1 += 1;
+                    set_track(
+                        &mut mixer,
+                        &mut sound_handle,
+                        None,
+                        position_sec,
+                        is_playing,
+                    );
+                }
+                PlayerCommand::SetBufferSize(buffer_size) => {
+                    BUFFER_HEALTH.write().buffer_size = buffer_size;
+                    underrun_instants.clear();
+                    let (position_sec, is_playing) = current_state(&noti_tx);
+                    mixer = init_mixer(Some(current_sr.load(atomic::Ordering::Acquire)), false);
+                    set_track(
+                        &mut mixer,
+                        &mut sound_handle,
+                        None,
+                        position_sec,
+                        is_playing,
+                    );
+                }
                 #[allow(non_snake_case)]
                 PlayerCommand::SetVolumedB(volume_dB) => {
-                    let volume = volume_dB.amp_from_dB_default() as f32;
-                    current_volume.store(volume, atomic::Ordering::Release);
-                    sound_handle.set_volume(volume);
+                    user_volume.set(volume_dB.amp_from_dB_default() as f32);
+                    update_volume();
+                }
+                PlayerCommand::ApplyMonitor => {
+                    update_volume();
+                    info!("monitor: {:?}", monitor());
+                }
+                PlayerCommand::SetOutputLimiter(threshold) => {
+                    *output_limiter_threshold.borrow_mut() = threshold;
+                    output_control.set_limiter_threshold(threshold);
+                    info!("output limiter: {:?}", threshold);
+                }
+                PlayerCommand::SetDownmix(mode) => {
+                    *downmix_mode.borrow_mut() = mode;
+                    let (position_sec, is_playing) = current_state(&noti_tx);
+                    set_track(
+                        &mut mixer,
+                        &mut sound_handle,
+                        None,
+                        position_sec,
+                        is_playing,
+                    );
+                    info!("downmix: {:?}", mode);
+                }
+                PlayerCommand::SetImpulseResponse(ir) => {
+                    let is_enabled = ir.is_some();
+                    *impulse_response.borrow_mut() = ir;
+                    chain_version.set(chain_version.get() + 1);
+                    let (position_sec, is_playing) = current_state(&noti_tx);
+                    set_track(
+                        &mut mixer,
+                        &mut sound_handle,
+                        None,
+                        position_sec,
+                        is_playing,
+                    );
+                    info!(
+                        "impulse response: {}",
+                        if is_enabled { "on" } else { "off" }
+                    );
+                }
+                PlayerCommand::SetDenoise(new_denoiser) => {
+                    let is_enabled = new_denoiser.is_some();
+                    *denoiser.borrow_mut() = new_denoiser;
+                    chain_version.set(chain_version.get() + 1);
+                    let (position_sec, is_playing) = current_state(&noti_tx);
+                    set_track(
+                        &mut mixer,
+                        &mut sound_handle,
+                        None,
+                        position_sec,
+                        is_playing,
+                    );
+                    info!("denoise: {}", if is_enabled { "on" } else { "off" });
                 }
                 PlayerCommand::SetTrack((track_id, start_time)) => {
                     info!("set track");
@@ -283,61 +912,230 @@ fn main_loop(
                         is_playing,
                     );
                 }
-                PlayerCommand::Seek(sec) => {
-                    let max_sec = TRACK_LIST.blocking_read().max_sec;
-                    let sec = sec.min(max_sec);
+                PlayerCommand::SoloTfBox {
+                    sec_range,
+                    hz_range,
+                } => {
+                    *tf_box_solo.borrow_mut() = Some(TfBoxSolo {
+                        sec_range,
+                        hz_range,
+                    });
+                    let start_sec = sec_range.0.max(0.);
+                    set_track(&mut mixer, &mut sound_handle, None, start_sec, true);
                     noti_tx.send_modify(|noti| {
                         if let PlayerNotification::Ok(state) = noti {
-                            if state.is_playing && mixer.is_finished() {
-                                set_track(
-                                    &mut mixer,
-                                    &mut sound_handle,
-                                    Some(current_track_id.load(atomic::Ordering::Acquire)),
-                                    sec,
-                                    state.is_playing,
-                                );
-                            } else {
-                                sound_handle.seek_to(sec);
+                            state.is_playing = true;
+                            state.position_sec = start_sec;
+                            state.instant = Instant::now();
+                        }
+                    });
+                    update_transport(|_| {});
+                    info!("solo tf box: {:?} sec, {:?} Hz", sec_range, hz_range);
+                }
+                PlayerCommand::ClearTfBoxSolo => {
+                    if tf_box_solo.borrow_mut().take().is_some() {
+                        let (position_sec, is_playing) = current_state(&noti_tx);
+                        set_track(
+                            &mut mixer,
+                            &mut sound_handle,
+                            None,
+                            position_sec,
+                            is_playing,
+                        );
+                    }
+                    info!("clear tf box solo");
+                }
+                PlayerCommand::SetFadeMillisec(fade_ms) => {
+                    fade = Duration::from_secs_f64(fade_ms.max(0.) / 1000.);
+                    info!("fade: {:?}", fade);
+                }
+                PlayerCommand::SetPreRollMillisec(pre_roll_ms) => {
+                    pre_roll_sec = pre_roll_ms.max(0.) / 1000.;
+                    info!("pre-roll: {} sec", pre_roll_sec);
+                }
+                PlayerCommand::SetLoop(range) => {
+                    loop_range = range;
+                    if TRANSPORT.read().loop_range != range {
+                        update_transport(|transport| transport.loop_range = range);
+                    }
+                    info!("loop: {:?}", range);
+                }
+                PlayerCommand::Seek(_) | PlayerCommand::SeekRelative(_) => {
+                    let (sec, max_sec) = {
+                        let tracklist = TRACK_LIST.blocking_read();
+                        let sec = match msg {
+                            PlayerCommand::SeekRelative(amount) => {
+                                let (position_sec, _) = current_state(&noti_tx);
+                                let sec = position_sec + amount;
+                                let current_id = current_track_id.load(atomic::Ordering::Acquire);
+                                match tracklist.get(current_id) {
+                                    Some(track) => {
+                                        let sr = track.sr() as f64;
+                                        (sec * sr).round() / sr
+                                    }
+                                    None => sec,
+                                }
                             }
+                            PlayerCommand::Seek(sec) => sec - pre_roll_sec,
+                            _ => unreachable!(),
+                        };
+                        (sec, tracklist.max_sec)
+                    };
+                    let sec = sec.max(0.).min(max_sec);
+                    let is_playing = match &*noti_tx.borrow() {
+                        PlayerNotification::Ok(state) => Some(state.is_playing),
+                        PlayerNotification::Err(_) => None,
+                    };
+                    if let Some(is_playing) = is_playing {
+                        // set_track is called outside of send_modify not to block the receivers
+                        if is_playing && mixer.is_finished() {
+                            set_track(
+                                &mut mixer,
+                                &mut sound_handle,
+                                Some(current_track_id.load(atomic::Ordering::Acquire)),
+                                sec,
+                                is_playing,
+                            );
+                        } else {
+                            sound_handle.seek_to(timeline.get().session_to_sound(sec));
+                        }
+                        noti_tx.send_modify(|noti| {
+                            if let PlayerNotification::Ok(state) = noti {
+                                state.position_sec = sec;
+                                state.instant = Instant::now();
+                            }
+                        });
+                    }
+                    update_transport(|_| {});
+                    info!("seek to {}", sec);
+                }
+                PlayerCommand::Scrub { sec, speed } => {
+                    let max_sec = TRACK_LIST.blocking_read().max_sec;
+                    let sec = sec.clamp(0., max_sec);
+                    if pending_pause.take().is_some() {
+                        output_control.fade_to(Some(1.), 1., Duration::ZERO);
+                    }
+                    if mixer.is_finished() {
+                        set_track(&mut mixer, &mut sound_handle, None, sec, false);
+                    } else {
+                        sound_handle.pause();
+                        sound_handle.seek_to(timeline.get().session_to_sound(sec));
+                    }
+                    noti_tx.send_modify(|noti| {
+                        if let PlayerNotification::Ok(state) = noti {
+                            state.is_playing = false;
                             state.position_sec = sec;
                             state.instant = Instant::now();
                         }
                     });
-                    info!("seek to {}", sec);
+                    update_transport(|_| {});
+                    let speed = speed.clamp(-MAX_SCRUB_SPEED, MAX_SCRUB_SPEED);
+                    let grain = TRACK_LIST
+                        .blocking_read()
+                        .get(current_track_id.load(atomic::Ordering::Acquire))
+                        .filter(|_| speed.abs() > 1e-3)
+                        .map(|track| {
+                            let frames = track.scrub_grain_frames(
+                                sec,
+                                speed,
+                                SCRUB_GRAIN_SEC,
+                                *downmix_mode.borrow(),
+                            );
+                            Sound::from_frames(track.sr(), &frames)
+                        });
+                    if let Some(grain) = grain {
+                        mixer.play(grain);
+                    }
                 }
-                PlayerCommand::Pause => {
+                PlayerCommand::PlayTestSignal {
+                    frames,
+                    sr,
+                    played_tx,
+                } => {
                     sound_handle.pause();
-                    if matches!(*noti_tx.borrow(), PlayerNotification::Ok(_)) {
-                        noti_tx
-                            .send(PlayerNotification::Ok(InternalPlayerState {
-                                is_playing: false,
-                                position_sec: calc_position_sec(&sound_handle),
-                                instant: Instant::now(),
-                            }))
-                            .unwrap();
+                    if pending_pause.take().is_some() {
+                        output_control.fade_to(Some(1.), 1., Duration::ZERO);
+                    }
+                    noti_tx.send_modify(|noti| {
+                        if let PlayerNotification::Ok(state) = noti {
+                            state.position_sec = state.position_sec_elapsed();
+                            state.is_playing = false;
+                            state.instant = Instant::now();
+                        }
+                    });
+                    mixer.play(Sound::from_frames(sr, &frames));
+                    played_tx.send(()).ok();
+                    update_transport(|_| {});
+                    info!("test signal");
+                }
+                PlayerCommand::GetPlaybackChain(chain_tx) => {
+                    chain_tx
+                        .send(PlaybackChain {
+                            sr: current_sr.load(atomic::Ordering::Acquire),
+                            impulse_response: impulse_response.borrow().clone(),
+                            output_limiter_threshold: *output_limiter_threshold.borrow(),
+                            downmix_mode: *downmix_mode.borrow(),
+                            denoiser: denoiser.borrow().clone(),
+                            tf_box_solo: tf_box_solo.borrow().clone(),
+                            volume: current_volume.load(atomic::Ordering::Acquire),
+                        })
+                        .ok();
+                }
+                PlayerCommand::PlaybackRendered { key, sr, frames } => {
+                    let is_current = rendering.borrow().as_ref() == Some(&key);
+                    {
+                        let mut rendered = rendered.borrow_mut();
+                        rendered.push_front((key.clone(), sr, frames));
+                        rendered.truncate(MAX_RENDERED_PLAYBACKS);
+                    }
+                    if is_current {
+                        let (position_sec, is_playing) = current_state(&noti_tx);
+                        set_track(
+                            &mut mixer,
+                            &mut sound_handle,
+                            Some(key.track_id),
+                            position_sec,
+                            is_playing,
+                        );
+                        info!("rendered the playback of track {}", key.track_id);
+                    }
+                }
+                PlayerCommand::Pause => {
+                    if fade.is_zero() || sound_handle.paused() {
+                        pause(&mut sound_handle);
+                    } else if pending_pause.is_none() {
+                        // paused on the idle loop after fading out
+                        output_control.fade_to(None, 0., fade);
+                        pending_pause = Some(Instant::now() + fade);
                     }
-                    info!("pause");
                 }
                 PlayerCommand::Resume => {
-                    sound_handle.resume();
-
+                    // continue from the current gain if it is fading out
+                    let fade_from = pending_pause.take().is_none().then_some(0.);
                     let position_sec = if let PlayerNotification::Ok(state) = &(*noti_tx.borrow()) {
                         state.position_sec
                     } else {
                         0.
                     };
                     if mixer.is_finished() {
-                        set_track(&mut mixer, &mut sound_handle, None, position_sec, true);
+                        set_track(&mut mixer, &mut sound_handle, None, position_sec, false);
                     }
+                    if !fade.is_zero() || fade_from.is_none() {
+                        output_control.fade_to(fade_from, 1., fade);
+                    }
+                    sound_handle.resume();
                     if matches!(*noti_tx.borrow(), PlayerNotification::Ok(_)) {
                         noti_tx
                             .send(PlayerNotification::Ok(InternalPlayerState {
                                 is_playing: true,
                                 position_sec,
+                                is_limiter_active: false,
+                                speed: timeline.get().stretch_ratio.recip(),
                                 instant: Instant::now(),
                             }))
                             .unwrap();
                     }
+                    update_transport(|_| {});
                     info!("play");
                 }
             },
@@ -349,19 +1147,51 @@ fn main_loop(
                 //     });
                 //     mixer = init_mixer(Some(current_sr.load(atomic::Ordering::Acquire)));
                 // }
+                if pending_pause.is_some_and(|deadline| Instant::now() >= deadline) {
+                    pending_pause = None;
+                    pause(&mut sound_handle);
+                }
                 // notification
+                let mut sleep_duration = PLAYER_NOTI_INTERVAL;
+                if let Some(deadline) = pending_pause {
+                    sleep_duration =
+                        sleep_duration.min(deadline.saturating_duration_since(Instant::now()));
+                }
                 let prev_state = if let PlayerNotification::Ok(state) = &(*noti_tx.borrow()) {
                     Some(state.clone())
                 } else {
                     None
                 };
                 if let Some(prev_state) = prev_state {
+                    let is_limiter_active = output_control.take_limiter_active();
                     let mut state = InternalPlayerState {
                         is_playing: prev_state.is_playing,
-                        position_sec: calc_position_sec(&sound_handle),
+                        position_sec: calc_position_sec(&sound_handle, timeline.get()),
+                        is_limiter_active: prev_state.is_playing && is_limiter_active,
+                        speed: timeline.get().stretch_ratio.recip(),
                         instant: Instant::now(),
                     };
-                    if mixer.is_finished() {
+                    let loop_start = loop_range.and_then(|(loop_start, loop_end)| {
+                        let position_sec = if mixer.is_finished() {
+                            prev_state.position_sec_elapsed()
+                        } else {
+                            state.position_sec
+                        };
+                        (prev_state.is_playing
+                            && prev_state.position_sec < loop_end
+                            && position_sec >= loop_end)
+                            .then_some(loop_start)
+                    });
+                    if let Some(loop_start) = loop_start {
+                        if mixer.is_finished() {
+                            set_track(&mut mixer, &mut sound_handle, None, loop_start, true);
+                        } else {
+                            sound_handle.seek_to(timeline.get().session_to_sound(loop_start));
+                        }
+                        state.is_playing = true;
+                        state.position_sec = loop_start;
+                        update_transport(|_| {});
+                    } else if mixer.is_finished() {
                         // no current sound
                         {
                             // only for logging
@@ -371,7 +1201,7 @@ fn main_loop(
                             }
                         }
                         let position_sec = prev_state.position_sec
-                            + (state.instant - prev_state.instant).as_secs_f64();
+                            + (state.instant - prev_state.instant).as_secs_f64() * prev_state.speed;
                         let max_sec = TRACK_LIST.blocking_read().max_sec;
                         if position_sec >= max_sec {
                             state.is_playing = false;
@@ -379,6 +1209,9 @@ fn main_loop(
                             if prev_state.position_sec != max_sec {
                                 info!("reached max_sec {}", max_sec);
                             }
+                            if prev_state.is_playing {
+                                update_transport(|_| {});
+                            }
                         } else if prev_state.is_playing {
                             state.is_playing = true;
                             state.position_sec = position_sec;
@@ -387,11 +1220,63 @@ fn main_loop(
                             state.position_sec = prev_state.position_sec;
                         }
                     }
-                    noti_tx.send(PlayerNotification::Ok(state)).unwrap();
+                    let underrun = loop_start.is_none()
+                        && !mixer.is_finished()
+                        && prev_state.is_playing
+                        && state.is_playing
+                        && is_underrun(&prev_state, &state);
+                    // wake up in time to jump back at the loop end
+                    if let Some((_, loop_end)) = loop_range.filter(|_| state.is_playing) {
+                        let until_loop_end = (loop_end - state.position_sec) / state.speed;
+                        if until_loop_end > 0. {
+                            sleep_duration =
+                                sleep_duration.min(Duration::from_secs_f64(until_loop_end));
+                        }
+                    }
+                    noti_tx.send(PlayerNotification::Ok(state.clone())).unwrap();
+                    if underrun {
+                        let mut health = BUFFER_HEALTH.write();
+                        health.n_underruns += 1;
+                        warn!("buffer underrun (total {})", health.n_underruns);
+                        underrun_instants.retain(|x| x.elapsed() < UNDERRUN_WINDOW);
+                        underrun_instants.push(state.instant);
+                        let range = STREAM_FORMAT
+                            .read()
+                            .as_ref()
+                            .and_then(|format| format.buffer_size_range);
+                        let new_buffer_size = (health.adaptive
+                            && underrun_instants.len() >= MAX_UNDERRUNS)
+                            .then(|| increased_buffer_size(health.buffer_size, range))
+                            .flatten();
+                        if let Some(buffer_size) = new_buffer_size {
+                            info!("buffer size increased to {}", buffer_size);
+                            health.buffer_size = Some(buffer_size);
+                            drop(health);
+                            underrun_instants.clear();
+                            mixer =
+                                init_mixer(Some(current_sr.load(atomic::Ordering::Acquire)), false);
+                            set_track(
+                                &mut mixer,
+                                &mut sound_handle,
+                                None,
+                                state.position_sec_elapsed(),
+                                true,
+                            );
+                            continue;
+                        }
+                    }
+                }
+                // the stream of the lost device dies silently, so check if it still exists
+                let device_lost = last_device_check.elapsed() >= DEVICE_CHECK_INTERVAL && {
+                    last_device_check = Instant::now();
+                    !output_device_exists(&device_name.borrow())
+                };
+                if device_lost {
+                    warn!("output device lost: {}", device_name.borrow());
                 }
-                let new_device = Device::Default.name();
+                let new_device = default_output_device_name();
                 if let Ok(new_device) = new_device {
-                    if new_device != *device_name.borrow() {
+                    if device_lost || new_device != *device_name.borrow() {
                         let sr = match get_optimal_sr(
                             &new_device,
                             current_sr.load(atomic::Ordering::Acquire),
@@ -403,6 +1288,7 @@ fn main_loop(
                             }
                         };
                         mixer = init_mixer(sr, true);
+                        OUTPUT_DEVICE.write().1 += 1;
                         sound_handle.pause();
 
                         let state = if let PlayerNotification::Ok(state) = &(*noti_tx.borrow()) {
@@ -422,7 +1308,7 @@ fn main_loop(
                         continue;
                     }
                 }
-                std::thread::sleep(PLAYER_NOTI_INTERVAL);
+                std::thread::sleep(sleep_duration);
             }
             Err(TryRecvError::Disconnected) => {
                 break;
@@ -0,0 +1,507 @@
+//! OSC remote control of the player so that hardware controllers (or OSC bridges) can drive
+//! listening sessions. MIDI Machine Control (MMC) sysex messages are accepted
+//! as the blob argument of /thesia/mmc (e.g. forwarded by a MIDI-to-OSC bridge).
+//!
+//! Incoming messages:
+//! - /thesia/play, /thesia/pause, /thesia/stop, /thesia/toggle
+//!   (an optional numeric argument of 0 is ignored, i.e. the release of a button)
+//! - /thesia/seek {sec}, /thesia/nudge {sec (negative: backward)}
+//! - /thesia/track {track id}
+//! - /thesia/mmc {blob of MMC sysex (stop, play, deferred play, pause, locate)}
+//! - /thesia/viewport {start sec} {end sec} {min hz} {max hz}
+//!
+//! Feedback sent to the clients (the senders of the recent messages):
+//! /thesia/playing {0 or 1}, /thesia/position {sec}, /thesia/track {track id or -1},
+//! /thesia/viewport {start sec} {end sec} {min hz} {max hz} (see viewport_sync.rs)
+//!
+//! The messages are not authenticated, so the server only listens on the loopback interface
+//! unless another address is given explicitly. The invalid messages are logged at the debug
+//! level only, so that the senders can't flood the log.
+
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use log::{debug, info};
+use parking_lot::Mutex;
+
+use crate::player::{self, PlayerCommand, PlayerNotification};
+use crate::viewport_sync::{self, ViewportState};
+use crate::TRACK_LIST;
+
+const ADDR_PREFIX: &str = "/thesia";
+/// timeout of receiving and interval of the feedback
+const FEEDBACK_INTERVAL: Duration = Duration::from_millis(50);
+const MAX_PACKET_SIZE: usize = 4096;
+/// the oldest client stops getting the feedback when a new client exceeds this
+const MAX_CLIENTS: usize = 8;
+/// only the local OSC bridges can connect by default
+pub const DEFAULT_BIND_IP: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+static SERVER: Mutex<Option<OscServer>> = Mutex::new(None);
+
+struct OscServer {
+    port: u16,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum OscArg {
+    Int(i32),
+    /// both of float32 (f) and float64 (d). Encoded as float32.
+    Float(f64),
+    Str(String),
+    Blob(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct OscMessage {
+    addr: String,
+    args: Vec<OscArg>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RemoteCommand {
+    Play,
+    Pause,
+    TogglePlay,
+    Seek(f64),
+    SeekRelative(f64),
+    SelectTrack(usize),
+    /// (sec_range, hz_range)
+    SetViewport((f64, f64), (f64, f64)),
+}
+
+/// OSC string: null-terminated and padded to a multiple of 4 bytes
+fn read_osc_str(buf: &[u8], pos: &mut usize) -> Option<String> {
+    let rest = buf.get(*pos..)?;
+    let len = rest.iter().position(|&b| b == 0)?;
+    let s = std::str::from_utf8(&rest[..len]).ok()?.to_string();
+    *pos += (len + 4) & !3;
+    (*pos <= buf.len()).then_some(s)
+}
+
+fn read_u32(buf: &[u8], pos: &mut usize) -> Option<u32> {
+    let bytes = buf.get(*pos..*pos + 4)?;
+    *pos += 4;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(buf: &[u8], pos: &mut usize) -> Option<u64> {
+    let bytes = buf.get(*pos..*pos + 8)?;
+    *pos += 8;
+    Some(u64::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// None if the message is malformed or has an unsupported type of argument
+fn parse_osc_message(buf: &[u8]) -> Option<OscMessage> {
+    let mut pos = 0;
+    let addr = read_osc_str(buf, &mut pos)?;
+    if !addr.starts_with('/') {
+        return None;
+    }
+    // the type tag string can be omitted by old implementations
+    let tags = if pos < buf.len() {
+        read_osc_str(buf, &mut pos)?
+    } else {
+        ",".into()
+    };
+    let args = tags
+        .strip_prefix(',')?
+        .chars()
+        .map(|tag| match tag {
+            'i' => read_u32(buf, &mut pos).map(|x| OscArg::Int(x as i32)),
+            'f' => read_u32(buf, &mut pos).map(|x| OscArg::Float(f32::from_bits(x) as f64)),
+            'd' => read_u64(buf, &mut pos).map(|x| OscArg::Float(f64::from_bits(x))),
+            's' => read_osc_str(buf, &mut pos).map(OscArg::Str),
+            'b' => {
+                let len = read_u32(buf, &mut pos)? as usize;
+                let blob = buf.get(pos..pos.checked_add(len)?)?.to_vec();
+                pos += (len + 3) & !3;
+                Some(OscArg::Blob(blob))
+            }
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(OscMessage { addr, args })
+}
+
+/// messages of the packet (a message or a bundle).
+/// The time tags of the bundles are ignored, i.e. the messages are executed immediately.
+fn parse_osc_packet(buf: &[u8]) -> Vec<OscMessage> {
+    if !buf.starts_with(b"#bundle\0") {
+        return parse_osc_message(buf).into_iter().collect();
+    }
+    // "#bundle", time tag (8 bytes), then (size, element) pairs
+    let mut pos = 16;
+    let mut messages = Vec::new();
+    while let Some(size) = read_u32(buf, &mut pos) {
+        let Some(element) = buf.get(pos..pos + size as usize) else {
+            break;
+        };
+        messages.extend(parse_osc_packet(element));
+        pos += size as usize;
+    }
+    messages
+}
+
+fn write_osc_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend(s.as_bytes());
+    buf.resize((buf.len() + 4) & !3, 0);
+}
+
+fn encode_osc_message(msg: &OscMessage) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_osc_str(&mut buf, &msg.addr);
+    let tags: String = msg
+        .args
+        .iter()
+        .map(|arg| match arg {
+            OscArg::Int(_) => 'i',
+            OscArg::Float(_) => 'f',
+            OscArg::Str(_) => 's',
+            OscArg::Blob(_) => 'b',
+        })
+        .collect();
+    write_osc_str(&mut buf, &format!(",{}", tags));
+    for arg in &msg.args {
+        match arg {
+            OscArg::Int(x) => buf.extend(x.to_be_bytes()),
+            OscArg::Float(x) => buf.extend((*x as f32).to_be_bytes()),
+            OscArg::Str(s) => write_osc_str(&mut buf, s),
+            OscArg::Blob(blob) => {
+                buf.extend((blob.len() as u32).to_be_bytes());
+                buf.extend(blob);
+                buf.resize((buf.len() + 3) & !3, 0);
+            }
+        }
+    }
+    buf
+}
+
+/// MMC sysex: F0 7F {device id} 06 {command} ... F7.
+/// The messages to any device id are accepted.
+fn parse_mmc(bytes: &[u8]) -> Option<RemoteCommand> {
+    let [0xF0, 0x7F, _, 0x06, command, ref data @ .., 0xF7] = *bytes else {
+        return None;
+    };
+    match (command, data) {
+        // stop, pause
+        (0x01 | 0x09, _) => Some(RemoteCommand::Pause),
+        // play, deferred play
+        (0x02 | 0x03, _) => Some(RemoteCommand::Play),
+        // locate to the time code: 06 01 hr mn sc fr ff
+        (0x44, &[0x06, 0x01, hr, mn, sc, fr, ff]) => {
+            // bit 5~6 of hr: frame rate. The drop-frame time code (29.97 fps) approximates
+            // the wall clock with the nominal 30 fps.
+            let fps = match (hr >> 5) & 0x03 {
+                0 => 24.,
+                1 => 25.,
+                _ => 30.,
+            };
+            let sec = ((hr & 0x1F) as u32 * 3600 + mn as u32 * 60 + sc as u32) as f64
+                + (fr as f64 + ff as f64 / 100.) / fps;
+            Some(RemoteCommand::Seek(sec))
+        }
+        _ => None,
+    }
+}
+
+fn parse_command(msg: &OscMessage) -> Option<RemoteCommand> {
+    let arg_f64 = |i: usize| match msg.args.get(i)? {
+        OscArg::Int(x) => Some(*x as f64),
+        OscArg::Float(x) => Some(*x),
+        _ => None,
+    };
+    // buttons of the controllers send 1 on press and 0 on release
+    let pressed = || arg_f64(0).is_none_or(|x| x != 0.);
+    match msg.addr.strip_prefix(ADDR_PREFIX)? {
+        "/play" => pressed().then_some(RemoteCommand::Play),
+        "/pause" | "/stop" => pressed().then_some(RemoteCommand::Pause),
+        "/toggle" => pressed().then_some(RemoteCommand::TogglePlay),
+        "/seek" => arg_f64(0)
+            .filter(|x| x.is_finite())
+            .map(RemoteCommand::Seek),
+        "/nudge" => arg_f64(0)
+            .filter(|x| x.is_finite())
+            .map(RemoteCommand::SeekRelative),
+        "/track" => arg_f64(0)
+            .filter(|&x| x >= 0. && x.fract() == 0.)
+            .map(|x| RemoteCommand::SelectTrack(x as usize)),
+        "/viewport" => Some(RemoteCommand::SetViewport(
+            (arg_f64(0)?, arg_f64(1)?),
+            (arg_f64(2)?, arg_f64(3)?),
+        )),
+        "/mmc" => match msg.args.first()? {
+            OscArg::Blob(bytes) => parse_mmc(bytes),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Should be called outside of the async runtime.
+fn execute(command: RemoteCommand) {
+    let is_playing = || matches!(player::recv(), PlayerNotification::Ok(state) if state.is_playing);
+    let msg = match command {
+        RemoteCommand::Play => PlayerCommand::Resume,
+        RemoteCommand::Pause => PlayerCommand::Pause,
+        RemoteCommand::TogglePlay if is_playing() => PlayerCommand::Pause,
+        RemoteCommand::TogglePlay => PlayerCommand::Resume,
+        RemoteCommand::Seek(sec) => PlayerCommand::Seek(sec),
+        RemoteCommand::SeekRelative(amount) => PlayerCommand::SeekRelative(amount),
+        RemoteCommand::SelectTrack(id) => {
+            if !TRACK_LIST.blocking_read().has(id) {
+                debug!("OSC: the track {} doesn't exist", id);
+                return;
+            }
+            PlayerCommand::SetTrack((Some(id), None))
+        }
+        // not logged because it is sent continuously while navigating
+        RemoteCommand::SetViewport(sec_range, hz_range) => {
+            if viewport_sync::set_viewport(sec_range, hz_range).is_none() {
+                debug!("OSC: invalid viewport {:?} {:?}", sec_range, hz_range);
+            }
+            return;
+        }
+    };
+    info!("OSC: {:?}", command);
+    player::blocking_send(msg);
+}
+
+/// (is_playing, position_sec, track id or -1)
+fn current_feedback() -> (bool, f64, i32) {
+    let track_id = player::transport().track_id.map_or(-1, |id| id as i32);
+    match player::recv() {
+        PlayerNotification::Ok(state) => (state.is_playing, state.position_sec, track_id),
+        PlayerNotification::Err(_) => (false, 0., track_id),
+    }
+}
+
+fn send_feedback(socket: &UdpSocket, clients: &[SocketAddr], feedback: (bool, f64, i32)) {
+    let (is_playing, position_sec, track_id) = feedback;
+    let messages = [
+        ("/playing", OscArg::Int(is_playing as i32)),
+        ("/position", OscArg::Float(position_sec)),
+        ("/track", OscArg::Int(track_id)),
+    ]
+    .map(|(addr, arg)| {
+        encode_osc_message(&OscMessage {
+            addr: format!("{}{}", ADDR_PREFIX, addr),
+            args: vec![arg],
+        })
+    });
+    send_to_clients(socket, clients, &messages);
+}
+
+fn send_viewport(socket: &UdpSocket, clients: &[SocketAddr], viewport: &ViewportState) {
+    let msg = encode_osc_message(&OscMessage {
+        addr: format!("{}/viewport", ADDR_PREFIX),
+        args: [
+            viewport.start_sec,
+            viewport.end_sec,
+            viewport.min_hz,
+            viewport.max_hz,
+        ]
+        .map(OscArg::Float)
+        .into(),
+    });
+    send_to_clients(socket, clients, &[msg]);
+}
+
+fn send_to_clients(socket: &UdpSocket, clients: &[SocketAddr], messages: &[Vec<u8>]) {
+    for client in clients {
+        for msg in messages {
+            // the client may be gone, which is not an error of the server
+            socket.send_to(msg, client).ok();
+        }
+    }
+}
+
+fn serve(socket: UdpSocket, stop: &AtomicBool) {
+    let mut buf = [0u8; MAX_PACKET_SIZE];
+    let mut clients: Vec<SocketAddr> = Vec::new();
+    let mut last_feedback = None;
+    let mut last_viewport_version = None;
+    let mut last_feedback_instant = Instant::now();
+    while !stop.load(Ordering::Acquire) {
+        match socket.recv_from(&mut buf) {
+            Ok((len, client)) => {
+                if !clients.contains(&client) {
+                    if clients.len() >= MAX_CLIENTS {
+                        clients.remove(0);
+                    }
+                    clients.push(client);
+                    // the new client gets the current state
+                    last_feedback = None;
+                    last_viewport_version = None;
+                }
+                for msg in parse_osc_packet(&buf[..len]) {
+                    match parse_command(&msg) {
+                        Some(command) => execute(command),
+                        None => debug!("OSC: unsupported message {:?}", msg),
+                    }
+                }
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            // e.g. ConnectionReset on Windows after sending to a closed port
+            Err(e) => debug!("OSC: {}", e),
+        }
+        if clients.is_empty() || last_feedback_instant.elapsed() < FEEDBACK_INTERVAL {
+            continue;
+        }
+        last_feedback_instant = Instant::now();
+        let feedback = current_feedback();
+        if last_feedback != Some(feedback) {
+            send_feedback(&socket, &clients, feedback);
+            last_feedback = Some(feedback);
+        }
+        let viewport = viewport_sync::viewport();
+        if last_viewport_version != Some(viewport.version) {
+            send_viewport(&socket, &clients, &viewport);
+            last_viewport_version = Some(viewport.version);
+        }
+    }
+    info!("OSC server stopped");
+}
+
+/// Start the OSC server on the UDP port (0: any free port) of the interface of bind_ip
+/// (usually DEFAULT_BIND_IP) and return the bound port. The running server is stopped first.
+pub fn start_osc_server(bind_ip: IpAddr, port: u16) -> io::Result<u16> {
+    stop_osc_server();
+    let socket = UdpSocket::bind((bind_ip, port))?;
+    socket.set_read_timeout(Some(FEEDBACK_INTERVAL))?;
+    let port = socket.local_addr()?.port();
+    let stop = Arc::new(AtomicBool::new(false));
+    let handle = std::thread::spawn({
+        let stop = stop.clone();
+        move || serve(socket, &stop)
+    });
+    *SERVER.lock() = Some(OscServer { port, stop, handle });
+    info!("OSC server listening on {}:{}", bind_ip, port);
+    Ok(port)
+}
+
+pub fn stop_osc_server() {
+    if let Some(server) = SERVER.lock().take() {
+        server.stop.store(true, Ordering::Release);
+        server.handle.join().ok();
+    }
+}
+
+/// None if the server is not running
+pub fn osc_server_port() -> Option<u16> {
+    SERVER.lock().as_ref().map(|server| server.port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(addr: &str, args: Vec<OscArg>) -> OscMessage {
+        OscMessage {
+            addr: addr.into(),
+            args,
+        }
+    }
+
+    #[test]
+    fn osc_message_roundtrip() {
+        let original = msg(
+            "/thesia/seek",
+            vec![
+                OscArg::Float(1.5),
+                OscArg::Int(-3),
+                OscArg::Str("abcd".into()),
+                OscArg::Blob(vec![1, 2, 3]),
+            ],
+        );
+        let buf = encode_osc_message(&original);
+        assert_eq!(buf.len() % 4, 0);
+        assert_eq!(&buf[..16], b"/thesia/seek\0\0\0\0");
+        assert_eq!(parse_osc_message(&buf), Some(original));
+
+        assert_eq!(parse_osc_message(&buf[..buf.len() - 4]), None);
+        assert_eq!(parse_osc_message(b"play\0\0\0\0,\0\0\0"), None);
+    }
+
+    #[test]
+    fn osc_bundle_works() {
+        let play = encode_osc_message(&msg("/thesia/play", vec![]));
+        let seek = encode_osc_message(&msg("/thesia/seek", vec![OscArg::Float(2.)]));
+        let mut inner = b"#bundle\0".to_vec();
+        inner.extend([0; 8]);
+        inner.extend((seek.len() as u32).to_be_bytes());
+        inner.extend(&seek);
+        let mut bundle = b"#bundle\0".to_vec();
+        bundle.extend([0, 0, 0, 0, 0, 0, 0, 1]);
+        for element in [&play, &inner] {
+            bundle.extend((element.len() as u32).to_be_bytes());
+            bundle.extend(element);
+        }
+        let commands: Vec<_> = parse_osc_packet(&bundle)
+            .iter()
+            .filter_map(parse_command)
+            .collect();
+        assert_eq!(commands, [RemoteCommand::Play, RemoteCommand::Seek(2.)]);
+    }
+
+    #[test]
+    fn parse_command_works() {
+        let parse = |addr: &str, args: Vec<OscArg>| parse_command(&msg(addr, args));
+        assert_eq!(parse("/thesia/play", vec![]), Some(RemoteCommand::Play));
+        assert_eq!(
+            parse("/thesia/toggle", vec![OscArg::Float(1.)]),
+            Some(RemoteCommand::TogglePlay)
+        );
+        assert_eq!(parse("/thesia/pause", vec![OscArg::Int(0)]), None);
+        assert_eq!(
+            parse("/thesia/nudge", vec![OscArg::Int(-5)]),
+            Some(RemoteCommand::SeekRelative(-5.))
+        );
+        assert_eq!(
+            parse("/thesia/track", vec![OscArg::Int(3)]),
+            Some(RemoteCommand::SelectTrack(3))
+        );
+        assert_eq!(parse("/thesia/track", vec![OscArg::Float(0.5)]), None);
+        assert_eq!(
+            parse(
+                "/thesia/viewport",
+                [1., 2., 0., 8000.].map(OscArg::Float).into()
+            ),
+            Some(RemoteCommand::SetViewport((1., 2.), (0., 8000.)))
+        );
+        assert_eq!(
+            parse(
+                "/thesia/viewport",
+                vec![OscArg::Float(1.), OscArg::Float(2.)]
+            ),
+            None
+        );
+        assert_eq!(parse("/thesia/seek", vec![]), None);
+        assert_eq!(parse("/other/play", vec![]), None);
+    }
+
+    #[test]
+    fn parse_mmc_works() {
+        let mmc = |data: &[u8]| {
+            let mut bytes = vec![0xF0, 0x7F, 0x7F, 0x06];
+            bytes.extend(data);
+            bytes.push(0xF7);
+            parse_mmc(&bytes)
+        };
+        assert_eq!(mmc(&[0x02]), Some(RemoteCommand::Play));
+        assert_eq!(mmc(&[0x01]), Some(RemoteCommand::Pause));
+        assert_eq!(mmc(&[0x04]), None);
+        // 25 fps, 01:02:03 + 12.5 frames
+        assert_eq!(
+            mmc(&[0x44, 0x06, 0x01, 0x21, 0x02, 0x03, 12, 50]),
+            Some(RemoteCommand::Seek(3723.5))
+        );
+        assert_eq!(parse_mmc(&[0xF0, 0x7F, 0x7F, 0x06, 0x02]), None);
+    }
+}
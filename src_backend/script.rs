@@ -0,0 +1,248 @@
+//! Command scripts running a sequence of backend commands with delays,
+//! so that repetitive sequences (e.g. load a folder, normalize, and export the report)
+//! can be automated and workflows can be reproduced deterministically.
+//!
+//! A script is a JSON array of steps, e.g.
+//! `[{"command": "addFolder", "path": "/masters"}, {"command": "exportReport", "path": "r.html",
+//! "delayMs": 500}]`. Each step waits for the previous step to finish (and delayMs).
+
+use std::path::Path;
+use std::time::Duration;
+
+use napi::tokio::time::sleep;
+use napi_derive::napi;
+use serde::Deserialize;
+
+use crate::jobs::{spawn_blocking_job, JobKind, Session};
+use crate::{RELOADED_ERR_MSG, TM, TRACK_LIST};
+
+const MAX_DELAY_MS: u64 = 60_000;
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(
+    tag = "command",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ScriptCommand {
+    /// add the files as tracks. The files failed to decode are skipped.
+    AddTracks {
+        paths: Vec<String>,
+    },
+    /// add the files in the folder (not recursively) in the order of the file names
+    AddFolder {
+        path: String,
+    },
+    /// the same argument as setCommonNormalize
+    SetCommonNormalize {
+        target: serde_json::Value,
+    },
+    SetSelection {
+        start_sec: f64,
+        end_sec: f64,
+    },
+    ClearSelection,
+    /// write the HTML report of the session (exportSessionReportHtml)
+    ExportReport {
+        path: String,
+    },
+    ExportSegmentedLoudnessCsv {
+        track_id: u32,
+        segment_sec: f64,
+        path: String,
+    },
+    SetPlayerTrack {
+        track_id: u32,
+    },
+    Seek {
+        sec: f64,
+    },
+    Play,
+    Pause,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptStep {
+    /// delay before the command (at most MAX_DELAY_MS)
+    #[serde(default)]
+    pub delay_ms: u64,
+    #[serde(flatten)]
+    pub command: ScriptCommand,
+}
+
+#[napi(object)]
+pub struct ScriptResult {
+    /// number of the steps finished successfully
+    pub n_done: u32,
+    /// error of the failed step. The steps after it are not executed.
+    pub error: Option<String>,
+    /// tracks added by the script (with the ids reserved like reserveTrackIds),
+    /// which the frontend should add to its track list
+    pub added_track_ids: Vec<u32>,
+}
+
+pub fn parse_script(script: serde_json::Value) -> Result<Vec<ScriptStep>, String> {
+    let steps: Vec<ScriptStep> =
+        serde_json::from_value(script).map_err(|e| format!("Invalid script: {}", e))?;
+    if let Some(step) = steps.iter().find(|step| step.delay_ms > MAX_DELAY_MS) {
+        return Err(format!(
+            "The delay of {:?} should be at most {} ms.",
+            step.command, MAX_DELAY_MS
+        ));
+    }
+    Ok(steps)
+}
+
+/// Run the steps in order until a step fails or the renderer is reloaded.
+pub async fn run(steps: Vec<ScriptStep>) -> ScriptResult {
+    let session = Session::current();
+    let mut added_ids = Vec::new();
+    let mut n_done = 0;
+    let mut error = None;
+    for step in steps {
+        if step.delay_ms > 0 {
+            sleep(Duration::from_millis(step.delay_ms)).await;
+        }
+        if session.is_stale() {
+            error = Some(RELOADED_ERR_MSG.into());
+            break;
+        }
+        if let Err(e) = execute(step.command, &mut added_ids).await {
+            error = Some(format!("step {}: {}", n_done, e));
+            break;
+        }
+        n_done += 1;
+    }
+    ScriptResult {
+        n_done,
+        error,
+        added_track_ids: added_ids.into_iter().map(|id| id as u32).collect(),
+    }
+}
+
+async fn execute(command: ScriptCommand, added_ids: &mut Vec<usize>) -> Result<(), String> {
+    let reason = |e: napi::Error| e.reason;
+    match command {
+        ScriptCommand::AddTracks { paths } => added_ids.extend(add_tracks(paths).await?),
+        ScriptCommand::AddFolder { path } => {
+            let paths = list_files(Path::new(&path)).map_err(|e| e.to_string())?;
+            added_ids.extend(add_tracks(paths).await?);
+        }
+        ScriptCommand::SetCommonNormalize { target } => {
+            crate::set_common_normalize(target).await.map_err(reason)?
+        }
+        ScriptCommand::SetSelection { start_sec, end_sec } => {
+            crate::set_selection(start_sec, end_sec)
+                .await
+                .map_err(reason)?
+        }
+        ScriptCommand::ClearSelection => crate::clear_selection().await,
+        ScriptCommand::ExportReport { path } => crate::export_session_report_html(path)
+            .await
+            .map_err(reason)?,
+        ScriptCommand::ExportSegmentedLoudnessCsv {
+            track_id,
+            segment_sec,
+            path,
+        } => crate::export_segmented_loudness_csv(track_id, segment_sec, path)
+            .await
+            .map_err(reason)?,
+        ScriptCommand::SetPlayerTrack { track_id } => {
+            if !TRACK_LIST.read().await.has(track_id as usize) {
+                return Err(format!("The track {} doesn't exist.", track_id));
+            }
+            crate::set_track_player(track_id, None).await;
+        }
+        ScriptCommand::Seek { sec } => crate::seek_player(sec).await,
+        ScriptCommand::Play => crate::resume_player().await,
+        ScriptCommand::Pause => crate::pause_player().await,
+    }
+    Ok(())
+}
+
+/// files (not hidden) in dir sorted by the names
+fn list_files(dir: &Path) -> std::io::Result<Vec<String>> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .file_name()
+                    .is_some_and(|name| !name.to_string_lossy().starts_with('.'))
+        })
+        .collect();
+    paths.sort();
+    Ok(paths
+        .into_iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect())
+}
+
+/// Add the tracks with newly reserved ids, and wait for their specs.
+/// Returns the ids of the added tracks.
+async fn add_tracks(paths: Vec<String>) -> Result<Vec<usize>, String> {
+    if paths.is_empty() {
+        return Err("No file to add.".into());
+    }
+    let added_ids = spawn_blocking_job(JobKind::Stats, move || {
+        let mut tracklist = TRACK_LIST.blocking_write();
+        let ids = tracklist.reserve_ids(paths.len());
+        tracklist.add_tracks(ids, paths)
+    })
+    .await
+    .unwrap();
+    if added_ids.is_empty() {
+        return Err("None of the files can be added.".into());
+    }
+    let ids = added_ids.clone();
+    spawn_blocking_job(JobKind::SpecUpdate, move || {
+        TM.blocking_write()
+            .add_tracks(&TRACK_LIST.blocking_read(), &ids);
+    })
+    .await
+    .unwrap();
+    crate::apply_track_list_changes().await;
+    Ok(added_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parse_script_works() {
+        let steps = parse_script(json!([
+            {"command": "addFolder", "path": "samples"},
+            {"command": "setSelection", "startSec": 1.0, "endSec": 2.0, "delayMs": 100},
+            {"command": "play"},
+        ]))
+        .unwrap();
+        assert_eq!(steps.len(), 3);
+        assert_eq!(
+            steps[1],
+            ScriptStep {
+                delay_ms: 100,
+                command: ScriptCommand::SetSelection {
+                    start_sec: 1.,
+                    end_sec: 2.
+                },
+            }
+        );
+        assert_eq!(steps[2].command, ScriptCommand::Play);
+
+        assert!(parse_script(json!([{"command": "format"}])).is_err());
+        assert!(parse_script(json!([{"command": "play", "delayMs": 3_600_000}])).is_err());
+    }
+
+    #[test]
+    fn list_files_works() {
+        let paths = list_files(Path::new("samples")).unwrap();
+        assert!(paths.iter().any(|path| path.ends_with("sample_48k.wav")));
+        assert!(paths.windows(2).all(|w| w[0] < w[1]));
+        // not recursive
+        assert!(!paths.iter().any(|path| path.contains("stereo")));
+    }
+}
@@ -0,0 +1,137 @@
+//! Session files saved by the frontend, read by the backend to import their tracks
+//! into the current session. The markers and annotations are owned by the renderer,
+//! so they are only remapped to the imported track ids and handed back.
+
+use std::path::{Path, PathBuf};
+
+use identity_hash::{IntMap, IntSet};
+use napi_derive::napi;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::backend::resolve_session_path;
+
+#[napi(string_enum)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SessionImportMode {
+    /// keep the current tracks and add the tracks of the session file
+    Append,
+    /// remove the current tracks before adding the tracks of the session file
+    Replace,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionFile {
+    pub tracks: Vec<SessionTrack>,
+    #[serde(default)]
+    pub markers: Vec<Value>,
+    #[serde(default)]
+    pub annotations: Vec<Value>,
+}
+
+/// track path as stored by toSessionRelativePath (and the absolute path as a fallback)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionTrack {
+    pub id: usize,
+    pub relative_path: Option<String>,
+    pub absolute_path: Option<String>,
+}
+
+impl SessionFile {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid session file: {}", e))
+    }
+
+    /// ((id in the session file, resolved path) of the existing tracks, paths of the missing tracks)
+    pub fn resolve_tracks(&self, session_path: &Path) -> (Vec<(usize, PathBuf)>, Vec<String>) {
+        let mut resolved = Vec::with_capacity(self.tracks.len());
+        let mut missing = Vec::new();
+        for track in &self.tracks {
+            let relative = track.relative_path.as_deref().map(Path::new);
+            let absolute = track.absolute_path.as_deref().map(Path::new);
+            match resolve_session_path(relative, absolute, session_path) {
+                Some(path) => resolved.push((track.id, path)),
+                None => missing.extend(
+                    track
+                        .absolute_path
+                        .clone()
+                        .or_else(|| track.relative_path.clone()),
+                ),
+            }
+        }
+        (resolved, missing)
+    }
+}
+
+/// Map the ids in the session file to the ids of the current session.
+/// An id is kept unless it is used, and the colliding ids are moved after all the ids.
+pub fn assign_ids(session_ids: &[usize], used_ids: &IntSet<usize>) -> IntMap<usize, usize> {
+    let mut next_id = used_ids
+        .iter()
+        .chain(session_ids)
+        .max()
+        .map_or(0, |&id| id + 1);
+    session_ids
+        .iter()
+        .map(|&id| {
+            if used_ids.contains(&id) {
+                next_id += 1;
+                (id, next_id - 1)
+            } else {
+                (id, id)
+            }
+        })
+        .collect()
+}
+
+/// Replace "trackId" of the markers or annotations by id_map.
+/// The items of the tracks not imported are dropped, and the items without "trackId" are kept.
+pub fn remap_track_ids(items: Vec<Value>, id_map: &IntMap<usize, usize>) -> Vec<Value> {
+    items
+        .into_iter()
+        .filter_map(|mut item| {
+            let Some(track_id) = item.get_mut("trackId") else {
+                return Some(item);
+            };
+            let id = track_id
+                .as_u64()
+                .and_then(|id| id_map.get(&(id as usize)))?;
+            *track_id = (*id).into();
+            Some(item)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn assign_ids_works() {
+        let used_ids: IntSet<_> = [0, 1, 4].into_iter().collect();
+        let id_map = assign_ids(&[1, 2, 4], &used_ids);
+        assert_eq!(id_map[&2], 2);
+        assert_eq!(id_map[&1], 5);
+        assert_eq!(id_map[&4], 6);
+        assert_eq!(assign_ids(&[0, 3], &IntSet::default())[&3], 3);
+    }
+
+    #[test]
+    fn remap_track_ids_works() {
+        let id_map: IntMap<_, _> = [(0, 5)].into_iter().collect();
+        let items = vec![
+            json!({"trackId": 0, "sec": 1.5}),
+            json!({"trackId": 1, "sec": 2.}),
+            json!({"sec": 3.}),
+        ];
+        assert_eq!(
+            remap_track_ids(items, &id_map),
+            [json!({"trackId": 5, "sec": 1.5}), json!({"sec": 3.})]
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! Versioned schema of the user settings stored by the frontend (electron-settings),
+//! the migration of the stored settings to the current version,
+//! and the debounced writing of the changed settings to the store file
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use log::warn;
+use parking_lot::{Mutex, RwLock};
+use serde_json::{Map, Value};
+
+/// key of the schema version in the stored settings
+pub const SETTINGS_VERSION_KEY: &str = "settingsVersion";
+
+/// current schema version.
+/// Settings stored without SETTINGS_VERSION_KEY are version 0 (before versioning).
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// keys of the current version (js names of UserSettings)
+const KNOWN_KEYS: [&str; 9] = [
+    SETTINGS_VERSION_KEY,
+    "specSetting",
+    "blend",
+    "dBRange",
+    "displayTilt",
+    "commonGuardClipping",
+    "commonNormalize",
+    "seekIncrements",
+    "audioHost",
+];
+
+type Migration = fn(&mut Map<String, Value>);
+
+/// MIGRATIONS[i] converts the settings of version i to version i + 1.
+/// When renaming or restructuring keys, bump SETTINGS_VERSION and append a migration here
+/// so that the values stored by the previous versions are carried over.
+const MIGRATIONS: [Migration; SETTINGS_VERSION as usize] = [migrate_v0_to_v1];
+
+/// v1 has the same keys as v0 and only introduces SETTINGS_VERSION_KEY.
+fn migrate_v0_to_v1(_settings: &mut Map<String, Value>) {}
+
+fn stored_version(settings: &Map<String, Value>) -> u32 {
+    match settings.get(SETTINGS_VERSION_KEY) {
+        None => 0,
+        Some(version) => match version.as_u64().and_then(|v| u32::try_from(v).ok()) {
+            Some(version) => version,
+            None => {
+                warn!("Invalid settings version {}. Regarded as 0.", version);
+                0
+            }
+        },
+    }
+}
+
+/// Migrate the stored settings to the current version.
+/// Unknown keys are kept (with a warning) instead of being dropped,
+/// so the settings written by a newer version survive running an older version.
+/// Settings of a newer version are returned as they are.
+pub fn migrate_user_settings(settings: Value) -> Value {
+    let mut settings = match settings {
+        Value::Object(settings) => settings,
+        Value::Null => Map::new(),
+        _ => {
+            warn!("The stored settings is not an object. Reset to the default settings.");
+            Map::new()
+        }
+    };
+    let version = stored_version(&settings);
+    if version > SETTINGS_VERSION {
+        warn!(
+            "The settings version {} is newer than {}. Unknown keys are ignored.",
+            version, SETTINGS_VERSION
+        );
+        return Value::Object(settings);
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(&mut settings);
+    }
+    settings.insert(SETTINGS_VERSION_KEY.into(), SETTINGS_VERSION.into());
+    settings
+        .keys()
+        .filter(|key| !KNOWN_KEYS.contains(&key.as_str()))
+        .for_each(|key| {
+            warn!(
+                "Unknown key \"{}\" in the settings is kept but ignored.",
+                key
+            )
+        });
+    Value::Object(settings)
+}
+
+/// store file of electron-settings. The changed settings are not written if None.
+static STORE_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+/// changed settings not written yet. None if nothing changed (not dirty).
+static PENDING: Mutex<Option<PendingSettings>> = Mutex::new(None);
+
+struct PendingSettings {
+    values: Map<String, Value>,
+    first_change: Instant,
+    last_change: Instant,
+}
+
+pub fn set_store_path(path: PathBuf) {
+    *STORE_PATH.write() = Some(path);
+}
+
+/// Keep the changed setting to be written by flush. Doesn't touch the disk.
+pub fn set_pending(key: String, value: Value) {
+    let now = Instant::now();
+    let mut pending = PENDING.lock();
+    let pending = pending.get_or_insert_with(|| PendingSettings {
+        values: Map::new(),
+        first_change: now,
+        last_change: now,
+    });
+    pending.values.insert(key, value);
+    pending.last_change = now;
+}
+
+/// true if the settings haven't changed for debounce,
+/// or have kept changing for max_delay (e.g. while dragging a slider)
+pub fn should_flush(debounce: Duration, max_delay: Duration) -> bool {
+    PENDING.lock().as_ref().is_some_and(|pending| {
+        pending.last_change.elapsed() >= debounce || pending.first_change.elapsed() >= max_delay
+    })
+}
+
+/// Write the changed settings to the store file. Ok(false) if nothing changed.
+/// On failure, the settings are kept to be written next time.
+pub fn flush() -> io::Result<bool> {
+    let Some(path) = STORE_PATH.read().clone() else {
+        return Ok(false);
+    };
+    let Some(pending) = PENDING.lock().take() else {
+        return Ok(false);
+    };
+    let stored = match fs::read_to_string(&path) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+            warn!("The settings file is invalid ({}). It is overwritten.", e);
+            Value::Null
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Value::Null,
+        Err(e) => return Err(restore_pending(pending, e)),
+    };
+    let json = serde_json::to_string(&merge_settings(stored, pending.values.clone()))?;
+    // written to a temporary file first not to leave a truncated file on crash
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, json)
+        .and_then(|_| fs::rename(&tmp_path, &path))
+        .map_err(|e| restore_pending(pending, e))?;
+    Ok(true)
+}
+
+/// Put the settings which failed to be written back to PENDING unless they changed again.
+fn restore_pending(failed: PendingSettings, e: io::Error) -> io::Error {
+    let mut pending = PENDING.lock();
+    match pending.as_mut() {
+        Some(pending) => {
+            for (key, value) in failed.values {
+                pending.values.entry(key).or_insert(value);
+            }
+            pending.first_change = failed.first_change;
+        }
+        None => *pending = Some(failed),
+    }
+    e
+}
+
+/// the stored settings (not an object is regarded as empty) updated by the changed settings
+fn merge_settings(stored: Value, changed: Map<String, Value>) -> Value {
+    let mut settings = match stored {
+        Value::Object(settings) => settings,
+        _ => Map::new(),
+    };
+    settings.extend(changed);
+    Value::Object(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    /// settings stored by each published version
+    fn published_settings() -> Vec<(u32, Value)> {
+        let v0 = json!({
+            "specSetting": {
+                "winMillisec": 40.,
+                "tOverlap": 4,
+                "fOverlap": 1,
+                "freqScale": "Mel",
+            },
+            "blend": 0.3,
+            "dBRange": 100.,
+            "displayTilt": 3.,
+            "commonGuardClipping": "Limiter",
+            "commonNormalize": {"type": "LUFS", "target": -18.},
+        });
+        let mut v1 = v0.clone();
+        v1[SETTINGS_VERSION_KEY] = json!(1);
+        vec![(0, v0), (1, v1)]
+    }
+
+    #[test]
+    fn migrate_published_versions() {
+        let published = published_settings();
+        assert_eq!(published.len(), SETTINGS_VERSION as usize + 1);
+        let (_, current) = published.last().unwrap().clone();
+        for (version, settings) in published {
+            assert_eq!(stored_version(settings.as_object().unwrap()), version);
+            let migrated = migrate_user_settings(settings);
+            assert_eq!(migrated, current, "version {}", version);
+            // round trip
+            assert_eq!(migrate_user_settings(migrated.clone()), migrated);
+        }
+    }
+
+    #[test]
+    fn migrate_keeps_unknown_keys() {
+        let settings = json!({"blend": 0.7, "futureKey": [1, 2]});
+        let migrated = migrate_user_settings(settings);
+        assert_eq!(migrated["blend"], json!(0.7));
+        assert_eq!(migrated["futureKey"], json!([1, 2]));
+        assert_eq!(migrated[SETTINGS_VERSION_KEY], json!(SETTINGS_VERSION));
+
+        let newer = json!({SETTINGS_VERSION_KEY: SETTINGS_VERSION + 1, "renamedKey": 1});
+        assert_eq!(migrate_user_settings(newer.clone()), newer);
+
+        assert_eq!(
+            migrate_user_settings(Value::Null),
+            json!({SETTINGS_VERSION_KEY: SETTINGS_VERSION})
+        );
+        assert_eq!(
+            migrate_user_settings(json!({SETTINGS_VERSION_KEY: "x"})),
+            json!({SETTINGS_VERSION_KEY: SETTINGS_VERSION})
+        );
+    }
+
+    #[test]
+    fn merge_settings_works() {
+        let stored = json!({"blend": 0.3, "dBRange": 100., "futureKey": 1});
+        let changed: Map<_, _> = [
+            ("blend".into(), json!(0.7)),
+            ("displayTilt".into(), json!(3.)),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            merge_settings(stored, changed.clone()),
+            json!({"blend": 0.7, "dBRange": 100., "futureKey": 1, "displayTilt": 3.})
+        );
+        assert_eq!(
+            merge_settings(Value::Null, changed),
+            json!({"blend": 0.7, "displayTilt": 3.})
+        );
+    }
+}
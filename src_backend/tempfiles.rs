@@ -0,0 +1,123 @@
+//! Lifecycle of the temporary files created by thesia
+//! (downloaded URLs, selections exported for the clipboard, synthetic test signals, ...).
+//! The files are registered when created so that their disk usage can be accounted,
+//! and they are removed by clean_temp_files or when the process exits.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use parking_lot::Mutex;
+
+use crate::interface::TempFilesUsage;
+
+const MB: f64 = (1 << 20) as f64;
+
+static FILES: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+pub fn register(path: impl Into<PathBuf>) {
+    FILES
+        .lock()
+        .get_or_insert_with(Default::default)
+        .insert(path.into());
+}
+
+/// size of the registered files. The files removed by others are unregistered.
+pub fn usage() -> TempFilesUsage {
+    let mut files = FILES.lock();
+    let Some(files) = files.as_mut() else {
+        return Default::default();
+    };
+    let mut bytes = 0;
+    files.retain(|path| match fs::metadata(path) {
+        Ok(metadata) => {
+            bytes += metadata.len();
+            true
+        }
+        Err(_) => false,
+    });
+    TempFilesUsage {
+        n_files: files.len() as u32,
+        size_mb: bytes as f64 / MB,
+    }
+}
+
+/// Remove the registered files except the files in keep (e.g. sources of the open tracks),
+/// and their directories if empty. Returns the usage freed.
+pub fn clean(keep: &[PathBuf]) -> TempFilesUsage {
+    let mut files = FILES.lock();
+    let Some(files) = files.as_mut() else {
+        return Default::default();
+    };
+    let mut freed = TempFilesUsage::default();
+    files.retain(|path| {
+        if keep.contains(path) {
+            return true;
+        }
+        let bytes = fs::metadata(path).map_or(0, |metadata| metadata.len());
+        match fs::remove_file(path) {
+            Ok(_) => {
+                freed.n_files += 1;
+                freed.size_mb += bytes as f64 / MB;
+                remove_empty_parent(path);
+                false
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+            Err(e) => {
+                warn!(
+                    "Failed to remove the temporary file {}: {}",
+                    path.display(),
+                    e
+                );
+                true
+            }
+        }
+    });
+    freed
+}
+
+/// remove the registered files on exit
+pub fn clean_all() {
+    clean(&[]);
+}
+
+/// The parent is removed only if it is a sub-directory of the temp dir.
+fn remove_empty_parent(path: &Path) {
+    let Some(parent) = path.parent() else {
+        return;
+    };
+    if parent != std::env::temp_dir() && parent.starts_with(std::env::temp_dir()) {
+        // fails if not empty
+        let _ = fs::remove_dir(parent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_works() {
+        let dir = std::env::temp_dir().join("thesia_tempfiles_test");
+        fs::create_dir_all(&dir).unwrap();
+        let (kept, removed) = (dir.join("kept.bin"), dir.join("removed.bin"));
+        fs::write(&kept, [0u8; 1024]).unwrap();
+        fs::write(&removed, [0u8; 2048]).unwrap();
+        register(&kept);
+        register(&removed);
+        register(dir.join("not_exists.bin"));
+
+        let before = usage();
+        assert!(before.n_files >= 2);
+        assert!(before.size_mb >= 3072. / MB);
+
+        let freed = clean(&[kept.clone()]);
+        assert!(freed.n_files >= 1);
+        assert!(!removed.exists());
+        assert!(kept.exists());
+
+        clean_all();
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,100 @@
+//! Navigation state (the visible time and frequency ranges) shared with the observers,
+//! e.g. external sync plugins or another thesia instance mirroring the view via OSC (remote.rs).
+//! Every change bumps the version so that the observers can wait for the next change.
+
+use std::sync::LazyLock;
+
+use napi::tokio::sync::watch;
+use napi_derive::napi;
+
+#[napi(object)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewportState {
+    pub start_sec: f64,
+    pub end_sec: f64,
+    pub min_hz: f64,
+    pub max_hz: f64,
+    /// incremented on every change
+    pub version: u32,
+}
+
+impl Default for ViewportState {
+    fn default() -> Self {
+        ViewportState {
+            start_sec: 0.,
+            end_sec: 0.,
+            min_hz: 0.,
+            max_hz: f64::INFINITY,
+            version: 0,
+        }
+    }
+}
+
+static VIEWPORT: LazyLock<watch::Sender<ViewportState>> =
+    LazyLock::new(|| watch::channel(Default::default()).0);
+
+#[inline]
+pub fn viewport() -> ViewportState {
+    *VIEWPORT.borrow()
+}
+
+/// None if the ranges are invalid. The version is not bumped if the ranges are the same.
+pub fn set_viewport(sec_range: (f64, f64), hz_range: (f64, f64)) -> Option<ViewportState> {
+    let valid = sec_range.0.is_finite()
+        && sec_range.1.is_finite()
+        && sec_range.0 < sec_range.1
+        && hz_range.0 >= 0.
+        && hz_range.0 < hz_range.1;
+    if !valid {
+        return None;
+    }
+    VIEWPORT.send_if_modified(|state| {
+        if (state.start_sec, state.end_sec) == sec_range && (state.min_hz, state.max_hz) == hz_range
+        {
+            return false;
+        }
+        *state = ViewportState {
+            start_sec: sec_range.0,
+            end_sec: sec_range.1,
+            min_hz: hz_range.0,
+            max_hz: hz_range.1,
+            version: state.version.wrapping_add(1),
+        };
+        true
+    });
+    Some(viewport())
+}
+
+/// Wait until the version of the viewport differs from version (immediately if already differs).
+pub async fn viewport_changed(version: u32) -> ViewportState {
+    let mut rx = VIEWPORT.subscribe();
+    loop {
+        let state = *rx.borrow_and_update();
+        if state.version != version {
+            return state;
+        }
+        if rx.changed().await.is_err() {
+            return state;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_viewport_works() {
+        let version = viewport().version;
+        let state = set_viewport((1., 2.), (0., 8000.)).unwrap();
+        assert_eq!(state.version, version.wrapping_add(1));
+        assert_eq!((state.start_sec, state.end_sec), (1., 2.));
+        assert_eq!(
+            set_viewport((1., 2.), (0., 8000.)).unwrap().version,
+            state.version
+        );
+        assert_eq!(set_viewport((2., 1.), (0., 8000.)), None);
+        assert_eq!(set_viewport((1., 2.), (-1., 8000.)), None);
+        assert_eq!(viewport(), state);
+    }
+}